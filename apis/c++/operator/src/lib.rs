@@ -2,7 +2,7 @@
 #![warn(unsafe_op_in_unsafe_fn)]
 
 use dora_operator_api::{
-    self, register_operator, DoraOperator, DoraOutputSender, DoraStatus, Event, IntoArrow,
+    self, register_operator, DoraOperator, DoraOutputSender, DoraStatus, Event,
 };
 use ffi::DoraSendOutputResult;
 
@@ -45,7 +45,7 @@ pub struct OutputSender<'a, 'b>(&'a mut DoraOutputSender<'b>);
 fn send_output(sender: &mut OutputSender, id: &str, data: &[u8]) -> DoraSendOutputResult {
     let error = sender
         .0
-        .send(id.into(), data.to_owned().into_arrow())
+        .send_bytes(id.into(), data.to_owned())
         .err()
         .unwrap_or_default();
     DoraSendOutputResult { error }
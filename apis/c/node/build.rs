@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "csharp")]
+    csbindgen::Builder::default()
+        .input_extern_file("src/lib.rs")
+        .csharp_dll_name("dora_node_api_c")
+        .csharp_namespace("Dora")
+        .csharp_class_name("NativeMethods")
+        .generate_csharp_file("../../csharp/NativeMethods.g.cs")
+        .expect("failed to generate C# bindings");
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+}
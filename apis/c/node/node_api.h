@@ -1,4 +1,5 @@
 #include <stddef.h>
+#include <stdint.h>
 
 void *init_dora_context_from_env();
 void free_dora_context(void *dora_context);
@@ -17,6 +18,31 @@ enum DoraEventType
 enum DoraEventType read_dora_event_type(void *dora_event);
 
 void read_dora_input_id(void *dora_event, char **out_ptr, size_t *out_len);
-void read_dora_input_data(void *dora_event, char **out_ptr, size_t *out_len);
+int read_dora_input_data(void *dora_event, char **out_ptr, size_t *out_len);
+uint64_t read_dora_input_timestamp(void *dora_event);
+void read_dora_input_open_telemetry_context(void *dora_event, char **out_ptr, size_t *out_len);
+int read_dora_input_sequence(void *dora_event, char **out_key_ptr, size_t *out_key_len, uint64_t *out_number);
+
+enum DoraMetadataType
+{
+    DoraMetadataType_Missing,
+    DoraMetadataType_Bool,
+    DoraMetadataType_Integer,
+    DoraMetadataType_Float,
+    DoraMetadataType_String,
+    DoraMetadataType_Bytes,
+    DoraMetadataType_List,
+};
+enum DoraMetadataType read_dora_input_metadata_type(void *dora_event, char *key_ptr, size_t key_len);
+int read_dora_input_metadata_bool(void *dora_event, char *key_ptr, size_t key_len, _Bool *out);
+int read_dora_input_metadata_integer(void *dora_event, char *key_ptr, size_t key_len, int64_t *out);
+int read_dora_input_metadata_float(void *dora_event, char *key_ptr, size_t key_len, double *out);
+int read_dora_input_metadata_bytes(void *dora_event, char *key_ptr, size_t key_len, char **out_ptr, size_t *out_len);
+void read_dora_error_message(void *dora_event, char **out_ptr, size_t *out_len);
 
 int dora_send_output(void *dora_context, char *id_ptr, size_t id_len, char *data_ptr, size_t data_len);
+
+void *dora_begin_output(void *dora_context, char *id_ptr, size_t id_len, size_t data_len);
+void read_dora_output_buffer(void *dora_output, char **out_ptr, size_t *out_len);
+int dora_commit_output(void *dora_context, void *dora_output);
+void dora_abort_output(void *dora_context, void *dora_output);
@@ -1,7 +1,10 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 use arrow_array::UInt8Array;
-use dora_node_api::{arrow::array::AsArray, DoraNode, Event, EventStream};
+use dora_node_api::{
+    arrow::{array::AsArray, datatypes::DataType},
+    DoraNode, Event, EventStream, MetadataValue, PendingOutput,
+};
 use eyre::Context;
 use std::{ffi::c_void, ptr, slice};
 
@@ -155,6 +158,10 @@ pub unsafe extern "C" fn read_dora_input_id(
 /// Writes a null pointer and length `0` if the given event is not an input event
 /// or when an input event has no associated data.
 ///
+/// Returns `0` on success. Returns `-1` and writes a null pointer and length `0`
+/// if the input data is an Arrow array of a type other than `UInt8`, which is
+/// not supported by the C API yet.
+///
 /// ## Safety
 ///
 /// The `event` argument must be a dora event received through
@@ -167,31 +174,323 @@ pub unsafe extern "C" fn read_dora_input_data(
     event: *const (),
     out_ptr: *mut *const u8,
     out_len: *mut usize,
-) {
+) -> isize {
     let event: &Event = unsafe { &*event.cast() };
-    match event {
-        Event::Input { data, metadata, .. } => match metadata.type_info.data_type {
-            dora_node_api::arrow::datatypes::DataType::UInt8 => {
+    let (ptr, len, result) = match event {
+        Event::Input { data, metadata, .. } => match &metadata.type_info.data_type {
+            DataType::UInt8 => {
                 let array: &UInt8Array = data.as_primitive();
-                let ptr = array.values().as_ptr();
-                unsafe {
-                    *out_ptr = ptr;
-                    *out_len = metadata.type_info.len;
-                }
+                (array.values().as_ptr(), metadata.type_info.len, 0)
             }
-            dora_node_api::arrow::datatypes::DataType::Null => unsafe {
-                *out_ptr = ptr::null();
-                *out_len = 0;
-            },
-            _ => {
-                todo!("dora C++ Node does not yet support higher level type of arrow. Only UInt8. 
-                The ultimate solution should be based on arrow FFI interface. Feel free to contribute :)")
+            DataType::Null => (ptr::null(), 0, 0),
+            other => {
+                tracing::error!(
+                    "input data of type `{other}` is not supported by the C API \
+                    (only `UInt8` arrays are supported)"
+                );
+                (ptr::null(), 0, -1)
             }
         },
-        _ => unsafe {
-            *out_ptr = ptr::null();
-            *out_len = 0;
-        },
+        _ => (ptr::null(), 0, 0),
+    };
+    unsafe {
+        *out_ptr = ptr;
+        *out_len = len;
+    }
+    result
+}
+
+/// Reads out the timestamp of the given input event.
+///
+/// Returns the time at which the input was sent, in nanoseconds since the UNIX
+/// epoch. Returns `0` if the given event is not an input event.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`]. The event must be still valid, i.e., not
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_timestamp(event: *const ()) -> u64 {
+    let event: &Event = unsafe { &*event.cast() };
+    match event {
+        Event::Input { metadata, .. } => {
+            metadata.timestamp().get_time().to_duration().as_nanos() as u64
+        }
+        _ => 0,
+    }
+}
+
+/// Reads out the OpenTelemetry context of the given input event.
+///
+/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
+/// serialized context string, which is empty if the sender did not set it.
+///
+/// Writes a null pointer and length `0` if the given event is not an input event.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`]. The event must be still valid, i.e., not
+/// freed yet. The returned `out_ptr` must not be used after
+/// freeing the `event`, since it points directly into the event's
+/// memory.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_open_telemetry_context(
+    event: *const (),
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) {
+    let event: &Event = unsafe { &*event.cast() };
+    let context = match event {
+        Event::Input { metadata, .. } => {
+            Some(metadata.parameters.open_telemetry_context.as_bytes())
+        }
+        _ => None,
+    };
+    unsafe { write_str(context, out_ptr, out_len) };
+}
+
+/// Reads out the sequence number of the given input event.
+///
+/// Writes the `out_key_ptr` and `out_key_len` with the start pointer and length
+/// of the UTF-8 encoded key of the stream that the input belongs to, and
+/// `out_number` with the position of the input in that stream.
+///
+/// Returns `0` on success. Returns `-1` and writes a null pointer, length `0`,
+/// and number `0` if the given event is not an input event or when the sender
+/// did not set a sequence number.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`]. The event must be still valid, i.e., not
+/// freed yet. The returned `out_key_ptr` must not be used after
+/// freeing the `event`, since it points directly into the event's
+/// memory.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_sequence(
+    event: *const (),
+    out_key_ptr: *mut *const u8,
+    out_key_len: *mut usize,
+    out_number: *mut u64,
+) -> isize {
+    let event: &Event = unsafe { &*event.cast() };
+    let sequence = match event {
+        Event::Input { metadata, .. } => metadata.parameters.sequence.as_ref(),
+        _ => None,
+    };
+    unsafe {
+        write_str(sequence.map(|s| s.key.as_bytes()), out_key_ptr, out_key_len);
+        *out_number = sequence.map(|s| s.number).unwrap_or_default();
+    }
+    if sequence.is_some() {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Type of a user-defined metadata value of an input.
+#[repr(C)]
+pub enum MetadataType {
+    Missing,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Bytes,
+    List,
+}
+
+/// Reads out the type of the user-defined metadata value with the given key.
+///
+/// The `key_ptr` and `key_len` fields must be the start pointer and length of an
+/// UTF-8 encoded string. Returns [`MetadataType::Missing`] if the given event is
+/// not an input event or when the input has no metadata value with the given key.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`]. The event must be still valid, i.e., not
+/// freed yet. The `key_ptr` and `key_len` fields must be the start pointer
+/// and length of an UTF-8 encoded string.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_metadata_type(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+) -> MetadataType {
+    match unsafe { metadata_value(event, key_ptr, key_len) } {
+        None => MetadataType::Missing,
+        Some(MetadataValue::Bool(_)) => MetadataType::Bool,
+        Some(MetadataValue::Integer(_)) => MetadataType::Integer,
+        Some(MetadataValue::Float(_)) => MetadataType::Float,
+        Some(MetadataValue::String(_)) => MetadataType::String,
+        Some(MetadataValue::Bytes(_)) => MetadataType::Bytes,
+        Some(MetadataValue::List(_)) => MetadataType::List,
+    }
+}
+
+/// Reads out the user-defined boolean metadata value with the given key.
+///
+/// Returns `0` on success. Returns `-1` if the value is missing or not a boolean.
+///
+/// ## Safety
+///
+/// See [`read_dora_input_metadata_type`]. The `out` argument must be a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_metadata_bool(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+    out: *mut bool,
+) -> isize {
+    match unsafe { metadata_value(event, key_ptr, key_len) } {
+        Some(MetadataValue::Bool(value)) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out the user-defined integer metadata value with the given key.
+///
+/// Returns `0` on success. Returns `-1` if the value is missing or not an integer.
+///
+/// ## Safety
+///
+/// See [`read_dora_input_metadata_type`]. The `out` argument must be a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_metadata_integer(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+    out: *mut i64,
+) -> isize {
+    match unsafe { metadata_value(event, key_ptr, key_len) } {
+        Some(MetadataValue::Integer(value)) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out the user-defined float metadata value with the given key.
+///
+/// Returns `0` on success. Returns `-1` if the value is missing or not a float.
+///
+/// ## Safety
+///
+/// See [`read_dora_input_metadata_type`]. The `out` argument must be a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_metadata_float(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+    out: *mut f64,
+) -> isize {
+    match unsafe { metadata_value(event, key_ptr, key_len) } {
+        Some(MetadataValue::Float(value)) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out the user-defined string or bytes metadata value with the given key.
+///
+/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
+/// value. String values are UTF-8 encoded.
+///
+/// Returns `0` on success. Returns `-1` and writes a null pointer and length `0`
+/// if the value is missing or neither a string nor bytes.
+///
+/// ## Safety
+///
+/// See [`read_dora_input_metadata_type`]. The returned `out_ptr` must not be
+/// used after freeing the `event`, since it points directly into the event's
+/// memory.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_metadata_bytes(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> isize {
+    let value = match unsafe { metadata_value(event, key_ptr, key_len) } {
+        Some(MetadataValue::String(value)) => Some(value.as_bytes()),
+        Some(MetadataValue::Bytes(value)) => Some(value.as_slice()),
+        _ => None,
+    };
+    let result = if value.is_some() { 0 } else { -1 };
+    unsafe { write_str(value, out_ptr, out_len) };
+    result
+}
+
+unsafe fn metadata_value<'a>(
+    event: *const (),
+    key_ptr: *const u8,
+    key_len: usize,
+) -> Option<&'a MetadataValue> {
+    let event: &Event = unsafe { &*event.cast() };
+    let key = unsafe { slice::from_raw_parts(key_ptr, key_len) };
+    let key = match std::str::from_utf8(key) {
+        Ok(key) => key,
+        Err(err) => {
+            tracing::error!("metadata key is not valid UTF-8: {err}");
+            return None;
+        }
+    };
+    match event {
+        Event::Input { metadata, .. } => metadata.parameters.values.get(key),
+        _ => None,
+    }
+}
+
+/// Reads out the message of the given error event.
+///
+/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
+/// UTF-8 encoded error message.
+///
+/// Writes a null pointer and length `0` if the given event is not an error event.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`]. The event must be still valid, i.e., not
+/// freed yet. The returned `out_ptr` must not be used after
+/// freeing the `event`, since it points directly into the event's
+/// memory.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_error_message(
+    event: *const (),
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) {
+    let event: &Event = unsafe { &*event.cast() };
+    let message = match event {
+        Event::Error(message) => Some(message.as_bytes()),
+        _ => None,
+    };
+    unsafe { write_str(message, out_ptr, out_len) };
+}
+
+unsafe fn write_str(value: Option<&[u8]>, out_ptr: *mut *const u8, out_len: *mut usize) {
+    let (ptr, len) = match value {
+        Some(value) => (value.as_ptr(), value.len()),
+        None => (ptr::null(), 0),
+    };
+    unsafe {
+        *out_ptr = ptr;
+        *out_len = len;
     }
 }
 
@@ -251,10 +550,115 @@ unsafe fn try_send_output(
     let context: &mut DoraContext = unsafe { &mut *context.cast() };
     let id = std::str::from_utf8(unsafe { slice::from_raw_parts(id_ptr, id_len) })?;
     let output_id = id.to_owned().into();
-    let data = unsafe { slice::from_raw_parts(data_ptr, data_len) };
+    let data = if data_len == 0 {
+        // the pointer might be null for empty outputs
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(data_ptr, data_len) }
+    };
     context
         .node
         .send_output_raw(output_id, Default::default(), data.len(), |out| {
             out.copy_from_slice(data);
         })
 }
+
+/// Allocates a sample of `data_len` bytes for the given output, which can be
+/// filled incrementally before it is sent.
+///
+/// Returns a pointer to the pending output on success. Use
+/// [`read_dora_output_buffer`] to get the buffer that should be filled. Each
+/// pending output must be passed to exactly one of [`dora_commit_output`] or
+/// [`dora_abort_output`], which free it.
+///
+/// On error, a null pointer is returned.
+///
+/// ## Safety
+///
+/// - The `context` argument must be a dora context created through
+///   [`init_dora_context_from_env`]. The context must be still valid, i.e., not
+///   freed yet.
+/// - The `id_ptr` and `id_len` fields must be the start pointer and length of an
+///   UTF8-encoded string.
+#[no_mangle]
+pub unsafe extern "C" fn dora_begin_output(
+    context: *mut c_void,
+    id_ptr: *const u8,
+    id_len: usize,
+    data_len: usize,
+) -> *mut c_void {
+    let begin = || {
+        let context: &mut DoraContext = unsafe { &mut *context.cast() };
+        let id = std::str::from_utf8(unsafe { slice::from_raw_parts(id_ptr, id_len) })?;
+        context.node.begin_output(id.to_owned().into(), data_len)
+    };
+    match begin() {
+        Ok(output) => Box::into_raw(Box::new(output)).cast(),
+        Err(err) => {
+            tracing::error!("{err:?}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Reads out the writable buffer of the given pending output.
+///
+/// Writes the `out_ptr` and `out_len` with the start pointer and length of the
+/// buffer.
+///
+/// ## Safety
+///
+/// The `output` argument must be a pending output created through
+/// [`dora_begin_output`] that was not committed or aborted yet. The returned
+/// `out_ptr` must not be used after committing or aborting the output.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_output_buffer(
+    output: *mut c_void,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) {
+    let output: &mut PendingOutput = unsafe { &mut *output.cast() };
+    unsafe {
+        *out_ptr = output.as_mut_ptr();
+        *out_len = output.len();
+    }
+}
+
+/// Sends the given pending output to subscribed dora nodes/operators and frees
+/// it.
+///
+/// Returns `0` on success and `-1` on error.
+///
+/// ## Safety
+///
+/// - The `context` argument must be the dora context that the output was
+///   created with. The context must be still valid, i.e., not freed yet.
+/// - The `output` argument must be a pending output created through
+///   [`dora_begin_output`] that was not committed or aborted yet.
+#[no_mangle]
+pub unsafe extern "C" fn dora_commit_output(context: *mut c_void, output: *mut c_void) -> isize {
+    let context: &mut DoraContext = unsafe { &mut *context.cast() };
+    let output: Box<PendingOutput> = unsafe { Box::from_raw(output.cast()) };
+    match context.node.commit_output(*output, Default::default()) {
+        Ok(()) => 0,
+        Err(err) => {
+            tracing::error!("{err:?}");
+            -1
+        }
+    }
+}
+
+/// Discards the given pending output without sending it and frees it.
+///
+/// ## Safety
+///
+/// - The `context` argument must be the dora context that the output was
+///   created with. The context must be still valid, i.e., not freed yet.
+/// - The `output` argument must be a pending output created through
+///   [`dora_begin_output`] that was not committed or aborted yet.
+#[no_mangle]
+pub unsafe extern "C" fn dora_abort_output(context: *mut c_void, output: *mut c_void) {
+    let context: &mut DoraContext = unsafe { &mut *context.cast() };
+    let output: Box<PendingOutput> = unsafe { Box::from_raw(output.cast()) };
+    context.node.abort_output(*output);
+}
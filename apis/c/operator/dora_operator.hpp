@@ -0,0 +1,375 @@
+#ifndef __RUST_DORA_OPERATOR_API_CXX__
+#define __RUST_DORA_OPERATOR_API_CXX__
+
+// C++ API for dora operators, built on top of the C operator API.
+//
+// Define a class with a default constructor and an
+// `dora::Status on_event(dora::Event &event, dora::OutputSender &output_sender)`
+// method, then register it through `DORA_REGISTER_OPERATOR(ClassName)`.
+// Classes with a `ClassName(const dora::InitContext &context)` constructor
+// receive the operator configuration, e.g. its environment variables.
+//
+// Exceptions thrown by the constructor or by `on_event` are
+// caught at the FFI boundary and reported to the dora runtime as errors.
+
+#include "operator_api.h"
+
+#include <chrono>
+#include <cstddef>
+#include <cstdint>
+#include <exception>
+#include <map>
+#include <stdexcept>
+#include <string>
+#include <type_traits>
+#include <utility>
+#include <vector>
+
+namespace dora
+{
+    enum class Status : uint8_t
+    {
+        Continue = DORA_STATUS_CONTINUE,
+        Stop = DORA_STATUS_STOP,
+        StopAll = DORA_STATUS_STOP_ALL,
+    };
+
+    /// Thrown when a call into the dora runtime fails.
+    class Error : public std::runtime_error
+    {
+    public:
+        using std::runtime_error::runtime_error;
+    };
+
+    namespace internal
+    {
+        inline std::string to_string(const Vec_uint8_t &vec)
+        {
+            if (vec.ptr == nullptr)
+            {
+                return std::string();
+            }
+            return std::string(reinterpret_cast<const char *>(vec.ptr), vec.len);
+        }
+
+        /// Throws a `dora::Error` with the given context if the result is an error.
+        inline void check(DoraResult_t result, const std::string &context)
+        {
+            if (result.error != nullptr)
+            {
+                std::string message = to_string(*result.error);
+                dora_free_result(result);
+                throw Error(context + ": " + message);
+            }
+        }
+
+        /// Copies and frees a string that was allocated by the dora runtime.
+        inline std::string take_string(char *raw)
+        {
+            std::string value(raw);
+            dora_free_input_id(raw);
+            return value;
+        }
+    }
+
+    /// Configuration of the operator, passed to its constructor.
+    class InitContext
+    {
+    public:
+        explicit InitContext(const DoraInitContext_t *raw)
+        {
+            for (size_t i = 0; i < raw->env.len; i++)
+            {
+                const EnvVar_t &var = raw->env.ptr[i];
+                env_[internal::to_string(var.key)] = internal::to_string(var.value);
+            }
+            input_config_ = internal::to_string(raw->input_config);
+        }
+
+        /// Environment variables of the operator, including its resolved secrets.
+        const std::map<std::string, std::string> &env() const { return env_; }
+
+        /// Returns the value of the given environment variable or `fallback` if it is not set.
+        std::string get_env(const std::string &key, const std::string &fallback = "") const
+        {
+            auto it = env_.find(key);
+            return it == env_.end() ? fallback : it->second;
+        }
+
+        /// Resolved queue configuration of each input as a JSON object, e.g.
+        /// `{"image": {"queue_size": 10, "policy": "drop_oldest", ...}}`.
+        const std::string &input_config() const { return input_config_; }
+
+    private:
+        std::map<std::string, std::string> env_;
+        std::string input_config_;
+    };
+
+    /// Metadata of an input.
+    class Metadata
+    {
+    public:
+        explicit Metadata(std::string open_telemetry_context)
+            : open_telemetry_context_(std::move(open_telemetry_context)) {}
+
+        const std::string &open_telemetry_context() const { return open_telemetry_context_; }
+
+    private:
+        std::string open_telemetry_context_;
+    };
+
+    /// Data of an input, which is freed when this object is destroyed.
+    class Data
+    {
+    public:
+        explicit Data(Vec_uint8_t raw) : raw_(raw) {}
+        Data(const Data &) = delete;
+        Data &operator=(const Data &) = delete;
+        Data(Data &&other) noexcept : raw_(other.raw_) { other.raw_ = Vec_uint8_t{}; }
+        Data &operator=(Data &&other) noexcept
+        {
+            std::swap(raw_, other.raw_);
+            return *this;
+        }
+        ~Data()
+        {
+            if (raw_.ptr != nullptr)
+            {
+                dora_free_data(raw_);
+            }
+        }
+
+        const uint8_t *data() const { return raw_.ptr; }
+        size_t size() const { return raw_.len; }
+        bool empty() const { return raw_.len == 0; }
+        const uint8_t *begin() const { return raw_.ptr; }
+        const uint8_t *end() const { return raw_.ptr + raw_.len; }
+
+    private:
+        Vec_uint8_t raw_;
+    };
+
+    /// An event that is passed to the `on_event` method of the operator.
+    class Event
+    {
+    public:
+        enum class Kind
+        {
+            Input,
+            InputClosed,
+            Stop,
+            Error,
+            DeadlineMissed,
+        };
+
+        explicit Event(RawEvent_t *raw) : raw_(raw)
+        {
+            if (raw->input != nullptr)
+            {
+                kind_ = Kind::Input;
+                id_ = internal::take_string(dora_read_input_id(raw->input));
+            }
+            else if (raw->input_closed.ptr != nullptr)
+            {
+                kind_ = Kind::InputClosed;
+                id_ = internal::to_string(raw->input_closed);
+            }
+            else if (raw->error.ptr != nullptr)
+            {
+                kind_ = Kind::Error;
+                error_ = internal::to_string(raw->error);
+            }
+            else if (raw->deadline_missed != nullptr)
+            {
+                kind_ = Kind::DeadlineMissed;
+                id_ = internal::to_string(raw->deadline_missed->input_id);
+                elapsed_ = std::chrono::nanoseconds(raw->deadline_missed->elapsed_ns);
+            }
+            else
+            {
+                kind_ = Kind::Stop;
+            }
+        }
+
+        Kind kind() const { return kind_; }
+
+        /// The input ID of `Input`, `InputClosed`, and `DeadlineMissed` events.
+        const std::string &id() const { return id_; }
+
+        /// The time since the last message of the input of `DeadlineMissed` events.
+        std::chrono::nanoseconds elapsed() const { return elapsed_; }
+
+        /// The error message of `Error` events.
+        const std::string &error() const { return error_; }
+
+        /// Takes the data of an `Input` event.
+        ///
+        /// The data can only be taken once; later calls return empty data.
+        Data data()
+        {
+            if (kind_ != Kind::Input)
+            {
+                throw Error("event has no data because it is not an input");
+            }
+            return Data(dora_read_data(raw_->input));
+        }
+
+        /// Moves the data of an `Input` event into the given `ArrowArray` and
+        /// `ArrowSchema` structs of the Arrow C data interface.
+        ///
+        /// The caller must release both structs after use. Like `data()`, the
+        /// data can only be taken once.
+        void arrow_data(void *array_out, void *schema_out)
+        {
+            if (kind_ != Kind::Input)
+            {
+                throw Error("event has no data because it is not an input");
+            }
+            internal::check(dora_read_input_arrow(raw_->input, array_out, schema_out),
+                            "failed to read input `" + id_ + "`");
+        }
+
+        /// Returns the metadata of an `Input` event.
+        Metadata metadata() const
+        {
+            if (kind_ != Kind::Input)
+            {
+                throw Error("event has no metadata because it is not an input");
+            }
+            return Metadata(internal::take_string(dora_read_open_telemetry_context(raw_->input)));
+        }
+
+    private:
+        RawEvent_t *raw_;
+        Kind kind_;
+        std::string id_;
+        std::string error_;
+        std::chrono::nanoseconds elapsed_{0};
+    };
+
+    /// Sends outputs of the operator.
+    class OutputSender
+    {
+    public:
+        explicit OutputSender(const SendOutput_t *raw) : raw_(raw) {}
+
+        /// Sends the given bytes on the given output. Throws `dora::Error` on failure.
+        void send(const std::string &id, const uint8_t *data, size_t len) const
+        {
+            internal::check(dora_send_operator_output(raw_, id.c_str(), data, len),
+                            "failed to send output `" + id + "`");
+        }
+
+        void send(const std::string &id, const std::vector<uint8_t> &data) const
+        {
+            send(id, data.data(), data.size());
+        }
+
+        /// Sends an Arrow array, given as `ArrowArray` and `ArrowSchema` structs
+        /// of the Arrow C data interface. Takes ownership of both structs.
+        ///
+        /// Unlike byte outputs, the data type is preserved, so e.g. Python
+        /// operators receive a typed `pyarrow` array.
+        void send_arrow(const std::string &id, void *array, void *schema) const
+        {
+            internal::check(dora_send_operator_output_arrow(raw_, id.c_str(), array, schema),
+                            "failed to send output `" + id + "`");
+        }
+
+    private:
+        const SendOutput_t *raw_;
+    };
+
+    namespace internal
+    {
+        /// Converts the currently handled exception into an error result.
+        inline DoraResult_t exception_to_result() noexcept
+        {
+            try
+            {
+                throw;
+            }
+            catch (const std::exception &e)
+            {
+                return dora_result_from_error(e.what());
+            }
+            catch (...)
+            {
+                return dora_result_from_error("operator threw an unknown C++ exception");
+            }
+        }
+
+        template <typename T>
+        DoraInitResult_t init_operator(const DoraInitContext_t *raw_context) noexcept
+        {
+            DoraInitResult_t result = {};
+            try
+            {
+                if constexpr (std::is_constructible_v<T, const InitContext &>)
+                {
+                    result.operator_context = new T(InitContext(raw_context));
+                }
+                else
+                {
+                    result.operator_context = new T();
+                }
+            }
+            catch (...)
+            {
+                result.result = exception_to_result();
+            }
+            return result;
+        }
+
+        template <typename T>
+        DoraResult_t drop_operator(void *operator_context) noexcept
+        {
+            delete static_cast<T *>(operator_context);
+            return DoraResult_t{};
+        }
+
+        template <typename T>
+        OnEventResult_t on_event(
+            RawEvent_t *raw_event,
+            const SendOutput_t *send_output,
+            void *operator_context) noexcept
+        {
+            OnEventResult_t result = {};
+            result.status = DORA_STATUS_CONTINUE;
+            try
+            {
+                Event event(raw_event);
+                OutputSender output_sender(send_output);
+                Status status = static_cast<T *>(operator_context)->on_event(event, output_sender);
+                result.status = static_cast<DoraStatus_t>(status);
+            }
+            catch (...)
+            {
+                result.result = exception_to_result();
+            }
+            return result;
+        }
+    }
+}
+
+/// Exports the given operator class through the dora operator ABI.
+#define DORA_REGISTER_OPERATOR(OPERATOR)                                      \
+    extern "C" DoraInitResult_t dora_init_operator_with_context(             \
+        const DoraInitContext_t *context)                                     \
+    {                                                                         \
+        return ::dora::internal::init_operator<OPERATOR>(context);            \
+    }                                                                         \
+    extern "C" DoraResult_t dora_drop_operator(void *operator_context)        \
+    {                                                                         \
+        return ::dora::internal::drop_operator<OPERATOR>(operator_context);   \
+    }                                                                         \
+    extern "C" OnEventResult_t dora_on_event(                                 \
+        RawEvent_t *event,                                                    \
+        const SendOutput_t *send_output,                                      \
+        void *operator_context)                                               \
+    {                                                                         \
+        return ::dora::internal::on_event<OPERATOR>(event, send_output,       \
+                                                    operator_context);        \
+    }
+
+#endif /* __RUST_DORA_OPERATOR_API_CXX__ */
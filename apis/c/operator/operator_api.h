@@ -16,6 +16,11 @@ extern "C"
 
     EXPORT DoraInitResult_t dora_init_operator(void);
 
+    // Optional alternative to `dora_init_operator` that receives the operator
+    // configuration, e.g. its environment variables and secrets. If an operator
+    // defines this function, the runtime calls it instead of `dora_init_operator`.
+    EXPORT DoraInitResult_t dora_init_operator_with_context(const DoraInitContext_t *context);
+
     EXPORT DoraResult_t dora_drop_operator(void *operator_context);
 
     EXPORT OnEventResult_t dora_on_event(
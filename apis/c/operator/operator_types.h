@@ -58,6 +58,64 @@ typedef struct DoraInitOperator {
     DoraInitResult_t (*init_operator)(void);
 } DoraInitOperator_t;
 
+/** <No documentation available> */
+typedef struct EnvVar {
+    /** <No documentation available> */
+    Vec_uint8_t key;
+
+    /** <No documentation available> */
+    Vec_uint8_t value;
+} EnvVar_t;
+
+/** \brief
+ *  Same as [`Vec<T>`][`rust::Vec`], but with guaranteed `#[repr(C)]` layout
+ */
+typedef struct Vec_EnvVar {
+    /** <No documentation available> */
+    EnvVar_t * ptr;
+
+    /** <No documentation available> */
+    size_t len;
+
+    /** <No documentation available> */
+    size_t cap;
+} Vec_EnvVar_t;
+
+/** \brief
+ *  Configuration of an operator, passed on initialization.
+ */
+typedef struct DoraInitContext {
+    /** \brief
+     *  Environment variables of the operator, including its resolved secrets.
+     */
+    Vec_EnvVar_t env;
+
+    /** \brief
+     *  Parameters of the operator as specified in the dataflow, encoded as JSON.
+     *
+     *  Set to `null` if the operator has no parameters.
+     */
+    Vec_uint8_t params;
+
+    /** \brief
+     *  Resolved queue configuration of each input, encoded as a JSON object
+     *  that maps input IDs to e.g. their `queue_size`, `policy`, and `ttl`.
+     */
+    Vec_uint8_t input_config;
+} DoraInitContext_t;
+
+/** \brief
+ *  Alternative to [`DoraInitOperator`] that gives the operator access to its
+ *  configuration.
+ *
+ *  If a library exports `dora_init_operator_with_context`, the runtime calls it
+ *  instead of `dora_init_operator`.
+ */
+typedef struct DoraInitOperatorWithContext {
+    /** <No documentation available> */
+    DoraInitResult_t (*init_operator_with_context)(DoraInitContext_t const *);
+} DoraInitOperatorWithContext_t;
+
 /** <No documentation available> */
 /** \remark Has the same ABI as `uint8_t` **/
 #ifdef DOXYGEN
@@ -88,6 +146,19 @@ typedef struct OnEventResult {
 /** <No documentation available> */
 typedef struct Input Input_t;
 
+/** \brief
+ *  An input that received no message within its configured `deadline`.
+ */
+typedef struct DeadlineMissed {
+    /** <No documentation available> */
+    Vec_uint8_t input_id;
+
+    /** \brief
+     *  Nanoseconds since the last message of the input.
+     */
+    uint64_t elapsed_ns;
+} DeadlineMissed_t;
+
 
 #include <stdbool.h>
 
@@ -104,6 +175,9 @@ typedef struct RawEvent {
 
     /** <No documentation available> */
     Vec_uint8_t error;
+
+    /** <No documentation available> */
+    DeadlineMissed_t * deadline_missed;
 } RawEvent_t;
 
 /** <No documentation available> */
@@ -154,16 +228,46 @@ void
 dora_free_input_id (
     char * _input_id);
 
+/** <No documentation available> */
+void
+dora_free_result (
+    DoraResult_t _result);
+
 /** <No documentation available> */
 Vec_uint8_t
 dora_read_data (
     Input_t * input);
 
+/** \brief
+ *  Moves the data of the input into the given structs of the
+ *  [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html).
+ *
+ *  `array_out` and `schema_out` must point to an uninitialized `ArrowArray` and
+ *  `ArrowSchema`. On success, the caller must release both of them after use.
+ *  The data can only be read once, either through this function or through
+ *  [`dora_read_data`].
+ */
+DoraResult_t
+dora_read_input_arrow (
+    Input_t * input,
+    void * array_out,
+    void * schema_out);
+
 /** <No documentation available> */
 char *
 dora_read_input_id (
     Input_t const * input);
 
+/** <No documentation available> */
+char *
+dora_read_open_telemetry_context (
+    Input_t const * input);
+
+/** <No documentation available> */
+DoraResult_t
+dora_result_from_error (
+    char const * error);
+
 /** <No documentation available> */
 DoraResult_t
 dora_send_operator_output (
@@ -172,6 +276,22 @@ dora_send_operator_output (
     uint8_t const * data_ptr,
     size_t data_len);
 
+/** \brief
+ *  Sends an Arrow array on the given output.
+ *
+ *  `array` and `schema` must point to an `ArrowArray` and an `ArrowSchema` of
+ *  the [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html).
+ *  The function takes ownership of both, so they are released afterwards,
+ *  also on errors. The data type of the array is sent along with the data,
+ *  so receivers get a typed array instead of raw bytes.
+ */
+DoraResult_t
+dora_send_operator_output_arrow (
+    SendOutput_t const * send_output,
+    char const * id,
+    void * array,
+    void * schema);
+
 
 #ifdef __cplusplus
 } /* extern \"C\" */
@@ -1,4 +1,5 @@
 pub const HEADER_OPERATOR_API: &str = include_str!("../operator_api.h");
 pub const HEADER_OPERATOR_TYPES: &str = include_str!("../operator_types.h");
+pub const HEADER_OPERATOR_CXX: &str = include_str!("../dora_operator.hpp");
 
 pub use dora_operator_api_types;
@@ -0,0 +1,303 @@
+using System;
+using System.Text;
+
+namespace Dora
+{
+    /// <summary>
+    /// A dora node that is connected to the daemon that spawned it.
+    /// </summary>
+    /// <remarks>
+    /// The node reads its configuration from the environment variables that the
+    /// daemon sets, so it must be started by dora as part of a dataflow.
+    /// </remarks>
+    public sealed unsafe class DoraNode : IDisposable
+    {
+        private void* context;
+
+        public DoraNode()
+        {
+            context = NativeMethods.init_dora_context_from_env();
+            if (context == null)
+            {
+                throw new InvalidOperationException("failed to initialize dora node");
+            }
+        }
+
+        /// <summary>
+        /// Waits for the next event of the node.
+        /// </summary>
+        /// <returns>
+        /// The next event, or <c>null</c> if all event streams were closed. The
+        /// event must be disposed once it is no longer needed.
+        /// </returns>
+        public DoraEvent? NextEvent()
+        {
+            var @event = NativeMethods.dora_next_event(Context);
+            return @event == null ? null : new DoraEvent(@event);
+        }
+
+        /// <summary>
+        /// Sends the given bytes on the output with the given ID.
+        /// </summary>
+        public void SendOutput(string outputId, ReadOnlySpan<byte> data)
+        {
+            var id = Encoding.UTF8.GetBytes(outputId);
+            fixed (byte* idPtr = id)
+            fixed (byte* dataPtr = data)
+            {
+                var result = NativeMethods.dora_send_output(
+                    Context, idPtr, (nuint)id.Length, dataPtr, (nuint)data.Length);
+                if (result != 0)
+                {
+                    throw new InvalidOperationException($"failed to send output `{outputId}`");
+                }
+            }
+        }
+
+        public void Dispose()
+        {
+            if (context != null)
+            {
+                NativeMethods.free_dora_context(context);
+                context = null;
+            }
+        }
+
+        private void* Context =>
+            context != null ? context : throw new ObjectDisposedException(nameof(DoraNode));
+    }
+
+    public enum DoraEventType
+    {
+        Stop,
+        Input,
+        InputClosed,
+        Error,
+        Unknown,
+    }
+
+    public enum DoraMetadataType
+    {
+        Missing,
+        Bool,
+        Integer,
+        Float,
+        String,
+        Bytes,
+        List,
+    }
+
+    /// <summary>
+    /// An event received by a <see cref="DoraNode"/>.
+    /// </summary>
+    /// <remarks>
+    /// The data of input events points directly into the event's memory, so it
+    /// must not be used after the event is disposed.
+    /// </remarks>
+    public sealed unsafe class DoraEvent : IDisposable
+    {
+        private void* @event;
+
+        internal DoraEvent(void* @event)
+        {
+            this.@event = @event;
+        }
+
+        public DoraEventType Type => (DoraEventType)NativeMethods.read_dora_event_type(Event);
+
+        /// <summary>
+        /// The ID of the input, or <c>null</c> if this is not an input event.
+        /// </summary>
+        public string? Id
+        {
+            get
+            {
+                byte* ptr;
+                nuint len;
+                NativeMethods.read_dora_input_id(Event, &ptr, &len);
+                return ReadString(ptr, len);
+            }
+        }
+
+        /// <summary>
+        /// The raw bytes of the input, which are empty if this is not an input event.
+        /// </summary>
+        /// <exception cref="NotSupportedException">
+        /// The input data is an Arrow array of a type other than <c>UInt8</c>.
+        /// </exception>
+        public ReadOnlySpan<byte> Data
+        {
+            get
+            {
+                byte* ptr;
+                nuint len;
+                if (NativeMethods.read_dora_input_data(Event, &ptr, &len) != 0)
+                {
+                    throw new NotSupportedException($"unsupported data type of input `{Id}`");
+                }
+                return ptr == null ? ReadOnlySpan<byte>.Empty : new ReadOnlySpan<byte>(ptr, (int)len);
+            }
+        }
+
+        /// <summary>
+        /// The time at which the input was sent, in nanoseconds since the UNIX epoch.
+        /// </summary>
+        public ulong Timestamp => NativeMethods.read_dora_input_timestamp(Event);
+
+        /// <summary>
+        /// The serialized OpenTelemetry context of the input, if any.
+        /// </summary>
+        public string? OpenTelemetryContext
+        {
+            get
+            {
+                byte* ptr;
+                nuint len;
+                NativeMethods.read_dora_input_open_telemetry_context(Event, &ptr, &len);
+                return ReadString(ptr, len);
+            }
+        }
+
+        /// <summary>
+        /// The key and position of the input in its keyed stream, or <c>null</c>
+        /// if the sender did not set a sequence number.
+        /// </summary>
+        public (string Key, ulong Number)? Sequence
+        {
+            get
+            {
+                byte* ptr;
+                nuint len;
+                ulong number;
+                if (NativeMethods.read_dora_input_sequence(Event, &ptr, &len, &number) != 0)
+                {
+                    return null;
+                }
+                return (ReadString(ptr, len)!, number);
+            }
+        }
+
+        /// <summary>
+        /// The type of the user-defined metadata value with the given key.
+        /// </summary>
+        public DoraMetadataType MetadataType(string key)
+        {
+            var keyBytes = Encoding.UTF8.GetBytes(key);
+            fixed (byte* keyPtr = keyBytes)
+            {
+                return (DoraMetadataType)NativeMethods.read_dora_input_metadata_type(
+                    Event, keyPtr, (nuint)keyBytes.Length);
+            }
+        }
+
+        /// <summary>
+        /// Reads a user-defined boolean metadata value.
+        /// </summary>
+        public bool TryGetMetadata(string key, out bool value)
+        {
+            var keyBytes = Encoding.UTF8.GetBytes(key);
+            bool result = default;
+            fixed (byte* keyPtr = keyBytes)
+            {
+                var found = NativeMethods.read_dora_input_metadata_bool(
+                    Event, keyPtr, (nuint)keyBytes.Length, &result) == 0;
+                value = result;
+                return found;
+            }
+        }
+
+        /// <summary>
+        /// Reads a user-defined integer metadata value.
+        /// </summary>
+        public bool TryGetMetadata(string key, out long value)
+        {
+            var keyBytes = Encoding.UTF8.GetBytes(key);
+            long result = default;
+            fixed (byte* keyPtr = keyBytes)
+            {
+                var found = NativeMethods.read_dora_input_metadata_integer(
+                    Event, keyPtr, (nuint)keyBytes.Length, &result) == 0;
+                value = result;
+                return found;
+            }
+        }
+
+        /// <summary>
+        /// Reads a user-defined float metadata value.
+        /// </summary>
+        public bool TryGetMetadata(string key, out double value)
+        {
+            var keyBytes = Encoding.UTF8.GetBytes(key);
+            double result = default;
+            fixed (byte* keyPtr = keyBytes)
+            {
+                var found = NativeMethods.read_dora_input_metadata_float(
+                    Event, keyPtr, (nuint)keyBytes.Length, &result) == 0;
+                value = result;
+                return found;
+            }
+        }
+
+        /// <summary>
+        /// Reads a user-defined string metadata value.
+        /// </summary>
+        public bool TryGetMetadata(string key, out string? value)
+        {
+            if (MetadataType(key) != DoraMetadataType.String)
+            {
+                value = null;
+                return false;
+            }
+            value = Encoding.UTF8.GetString(MetadataBytes(key));
+            return true;
+        }
+
+        /// <summary>
+        /// Reads a user-defined bytes metadata value.
+        /// </summary>
+        /// <remarks>
+        /// The returned bytes point directly into the event's memory.
+        /// </remarks>
+        public ReadOnlySpan<byte> MetadataBytes(string key)
+        {
+            var keyBytes = Encoding.UTF8.GetBytes(key);
+            fixed (byte* keyPtr = keyBytes)
+            {
+                byte* ptr;
+                nuint len;
+                NativeMethods.read_dora_input_metadata_bytes(
+                    Event, keyPtr, (nuint)keyBytes.Length, &ptr, &len);
+                return ptr == null ? ReadOnlySpan<byte>.Empty : new ReadOnlySpan<byte>(ptr, (int)len);
+            }
+        }
+
+        /// <summary>
+        /// The error message, or <c>null</c> if this is not an error event.
+        /// </summary>
+        public string? ErrorMessage
+        {
+            get
+            {
+                byte* ptr;
+                nuint len;
+                NativeMethods.read_dora_error_message(Event, &ptr, &len);
+                return ReadString(ptr, len);
+            }
+        }
+
+        public void Dispose()
+        {
+            if (@event != null)
+            {
+                NativeMethods.free_dora_event(@event);
+                @event = null;
+            }
+        }
+
+        private void* Event =>
+            @event != null ? @event : throw new ObjectDisposedException(nameof(DoraEvent));
+
+        private static string? ReadString(byte* ptr, nuint len) =>
+            ptr == null ? null : Encoding.UTF8.GetString(ptr, (int)len);
+    }
+}
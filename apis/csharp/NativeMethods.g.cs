@@ -0,0 +1,107 @@
+// <auto-generated>
+// This code is generated by csbindgen.
+// DON'T CHANGE THIS DIRECTLY.
+// </auto-generated>
+#pragma warning disable CS8500
+#pragma warning disable CS8981
+using System;
+using System.Runtime.InteropServices;
+
+
+namespace Dora
+{
+    internal static unsafe partial class NativeMethods
+    {
+        const string __DllName = "dora_node_api_c";
+
+
+
+        [DllImport(__DllName, EntryPoint = "init_dora_context_from_env", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void* init_dora_context_from_env();
+
+        [DllImport(__DllName, EntryPoint = "free_dora_context", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void free_dora_context(void* context);
+
+        [DllImport(__DllName, EntryPoint = "dora_next_event", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void* dora_next_event(void* context);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_event_type", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern EventType read_dora_event_type(void* @event);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_id", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void read_dora_input_id(void* @event, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_data", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_data(void* @event, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_timestamp", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern ulong read_dora_input_timestamp(void* @event);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_open_telemetry_context", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void read_dora_input_open_telemetry_context(void* @event, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_sequence", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_sequence(void* @event, byte** out_key_ptr, nuint* out_key_len, ulong* out_number);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_metadata_type", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern MetadataType read_dora_input_metadata_type(void* @event, byte* key_ptr, nuint key_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_metadata_bool", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_metadata_bool(void* @event, byte* key_ptr, nuint key_len, bool* @out);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_metadata_integer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_metadata_integer(void* @event, byte* key_ptr, nuint key_len, long* @out);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_metadata_float", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_metadata_float(void* @event, byte* key_ptr, nuint key_len, double* @out);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_input_metadata_bytes", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint read_dora_input_metadata_bytes(void* @event, byte* key_ptr, nuint key_len, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_error_message", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void read_dora_error_message(void* @event, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "free_dora_event", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void free_dora_event(void* @event);
+
+        [DllImport(__DllName, EntryPoint = "dora_send_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint dora_send_output(void* context, byte* id_ptr, nuint id_len, byte* data_ptr, nuint data_len);
+
+        [DllImport(__DllName, EntryPoint = "dora_begin_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void* dora_begin_output(void* context, byte* id_ptr, nuint id_len, nuint data_len);
+
+        [DllImport(__DllName, EntryPoint = "read_dora_output_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void read_dora_output_buffer(void* output, byte** out_ptr, nuint* out_len);
+
+        [DllImport(__DllName, EntryPoint = "dora_commit_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nint dora_commit_output(void* context, void* output);
+
+        [DllImport(__DllName, EntryPoint = "dora_abort_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void dora_abort_output(void* context, void* output);
+
+
+    }
+
+
+    internal enum EventType : int
+    {
+        Stop,
+        Input,
+        InputClosed,
+        Error,
+        Unknown,
+    }
+
+    internal enum MetadataType : int
+    {
+        Missing,
+        Bool,
+        Integer,
+        Float,
+        String,
+        Bytes,
+        List,
+    }
+
+
+}
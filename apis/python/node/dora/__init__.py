@@ -10,23 +10,10 @@ pip install dora-rs
 ```
 """
 
-from enum import Enum
-
 from .dora import *
+from .status import DoraStatus
+from .events import *
+from .operator import Operator, on_event, on_input, on_stop
 
 __author__ = "Dora-rs Authors"
 __version__ = "0.3.2"
-
-
-class DoraStatus(Enum):
-    """Dora status to indicate if operator `on_input` loop
-     should be stopped.
-
-    Args:
-        Enum (u8): Status signaling to dora operator to
-        stop or continue the operator.
-    """
-
-    CONTINUE = 0
-    STOP = 1
-    STOP_ALL = 2
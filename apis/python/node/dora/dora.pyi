@@ -0,0 +1,86 @@
+"""Type stubs of the native `dora` module."""
+
+from types import ModuleType, TracebackType
+from typing import Any, Dict, List, Optional, Tuple, Type, Union
+
+import pyarrow as pa
+
+Metadata = Dict[str, Any]
+
+class BackpressureError(Exception):
+    """Raised when an output cannot be sent because no memory could be allocated for it."""
+
+class PyEvent:
+    """Native event. Use `dora.parse_event` to convert it into a typed event."""
+
+    def __getitem__(self, key: str) -> Any: ...
+    def inner(self) -> Optional[Any]: ...
+
+class Node:
+    def __init__(self) -> None: ...
+    def next(self, timeout: Optional[float] = None) -> Optional[PyEvent]: ...
+    def __next__(self) -> Optional[PyEvent]: ...
+    def __iter__(self) -> "Node": ...
+    def __enter__(self) -> "Node": ...
+    def __exit__(
+        self,
+        exc_type: Optional[Type[BaseException]],
+        exc_value: Optional[BaseException],
+        traceback: Optional[TracebackType],
+    ) -> bool: ...
+    def send_output(
+        self,
+        output_id: str,
+        data: Union[bytes, pa.Array, Any],
+        metadata: Optional[Metadata] = None,
+    ) -> None: ...
+    def call_service(
+        self,
+        service: str,
+        data: Union[bytes, pa.Array],
+        metadata: Optional[Metadata] = None,
+        timeout: Optional[float] = None,
+    ) -> Tuple[pa.Array, Metadata]: ...
+    def ready(self) -> None: ...
+    def dataflow_descriptor(self) -> Dict[str, Any]: ...
+    def input_config(self) -> Dict[str, Dict[str, Any]]: ...
+    def merge_external_events(self, subscription: Any) -> None: ...
+
+class DescriptorBuilder:
+    def __init__(self) -> None: ...
+    def add_node(
+        self,
+        id: str,
+        source: str,
+        args: Optional[str] = None,
+        inputs: Optional[Dict[str, Union[str, Dict[str, Any]]]] = None,
+        outputs: Optional[List[str]] = None,
+        env: Optional[Dict[str, Any]] = None,
+        machine: Optional[str] = None,
+    ) -> None: ...
+    def add_operator(
+        self,
+        id: str,
+        python: str,
+        inputs: Optional[Dict[str, Union[str, Dict[str, Any]]]] = None,
+        outputs: Optional[List[str]] = None,
+        params: Optional[Any] = None,
+        env: Optional[Dict[str, Any]] = None,
+        machine: Optional[str] = None,
+    ) -> None: ...
+    def to_yaml(self) -> str: ...
+    def save(self, path: str) -> None: ...
+
+class OperatorTest:
+    def __init__(self, definition: str) -> None: ...
+    def send_input(
+        self, id: str, data: pa.Array, metadata: Optional[Metadata] = None
+    ) -> None: ...
+    def close_input(self, id: str) -> None: ...
+    def stop(self) -> None: ...
+    def next_output(self, timeout: float = 1.0) -> Optional[Dict[str, Any]]: ...
+    def finish(self) -> Dict[str, Any]: ...
+
+def start_runtime() -> None: ...
+
+experimental: ModuleType
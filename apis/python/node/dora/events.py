@@ -0,0 +1,272 @@
+"""
+Typed events of the dora node and operator APIs.
+
+The native event objects behave like dictionaries (`event["type"] == "INPUT"`).
+`parse_event` converts them into the dataclasses below, which IDEs and type
+checkers understand:
+
+```python
+from dora import InputEvent, Node, StopEvent, typed_events
+
+with Node() as node:
+    for event in typed_events(node):
+        if isinstance(event, InputEvent) and event.id == "image":
+            ...
+        elif isinstance(event, StopEvent):
+            break
+```
+"""
+
+from dataclasses import dataclass, field
+from enum import Enum
+from typing import Any, Dict, Iterator, List, Optional, Union
+
+import pyarrow as pa
+
+__all__ = [
+    "EventType",
+    "StopEvent",
+    "InputEvent",
+    "InputClosedEvent",
+    "DeadlineMissedEvent",
+    "SyncedInputEvent",
+    "InputBatchEvent",
+    "ServiceRequestEvent",
+    "HopLatency",
+    "LatencyAlert",
+    "LatencyBudgetExceededEvent",
+    "ParameterUpdateEvent",
+    "OutputUnsubscribedEvent",
+    "OutputSubscribedEvent",
+    "ErrorEvent",
+    "ExternalEvent",
+    "UnknownEvent",
+    "Event",
+    "parse_event",
+    "typed_events",
+]
+
+
+class EventType(str, Enum):
+    """Value of the `type` entry of native events."""
+
+    STOP = "STOP"
+    INPUT = "INPUT"
+    INPUT_CLOSED = "INPUT_CLOSED"
+    DEADLINE_MISSED = "DEADLINE_MISSED"
+    SYNCED_INPUT = "SYNCED_INPUT"
+    INPUT_BATCH = "INPUT_BATCH"
+    SERVICE_REQUEST = "SERVICE_REQUEST"
+    LATENCY_BUDGET_EXCEEDED = "LATENCY_BUDGET_EXCEEDED"
+    PARAMETER_UPDATE = "PARAMETER_UPDATE"
+    OUTPUT_UNSUBSCRIBED = "OUTPUT_UNSUBSCRIBED"
+    OUTPUT_SUBSCRIBED = "OUTPUT_SUBSCRIBED"
+    ERROR = "ERROR"
+    UNKNOWN = "UNKNOWN"
+
+
+@dataclass(frozen=True)
+class StopEvent:
+    """The dataflow is stopping."""
+
+    type = EventType.STOP
+
+
+@dataclass(frozen=True)
+class InputEvent:
+    """A message arrived on the input `id`."""
+
+    id: str
+    value: pa.Array
+    metadata: Dict[str, Any] = field(default_factory=dict)
+    type = EventType.INPUT
+
+
+@dataclass(frozen=True)
+class InputClosedEvent:
+    """The sender of the input `id` stopped."""
+
+    id: str
+    type = EventType.INPUT_CLOSED
+
+
+@dataclass(frozen=True)
+class DeadlineMissedEvent:
+    """No message arrived on the input `id` within its `deadline`."""
+
+    id: str
+    elapsed: float
+    """Time since the last message arrived on the input, in seconds."""
+    type = EventType.DEADLINE_MISSED
+
+
+@dataclass(frozen=True)
+class SyncedInputEvent:
+    """One message per input, aligned according to the `sync` policy."""
+
+    inputs: Dict[str, InputEvent]
+    type = EventType.SYNCED_INPUT
+
+
+@dataclass(frozen=True)
+class InputBatchEvent:
+    """Consecutive messages of the input `id`, see its `batch` option."""
+
+    id: str
+    value: pa.ListArray
+    """List array with one entry per message."""
+    metadata: List[Dict[str, Any]]
+    type = EventType.INPUT_BATCH
+
+
+@dataclass(frozen=True)
+class ServiceRequestEvent:
+    """Another node called the service `id`."""
+
+    id: str
+    value: pa.Array
+    metadata: Dict[str, Any] = field(default_factory=dict)
+    type = EventType.SERVICE_REQUEST
+
+
+@dataclass(frozen=True)
+class HopLatency:
+    source: str
+    target: str
+    latency: float
+
+
+@dataclass(frozen=True)
+class LatencyAlert:
+    path: List[str]
+    budget: float
+    latency: float
+    hops: List[HopLatency]
+
+
+@dataclass(frozen=True)
+class LatencyBudgetExceededEvent:
+    """The input `id` exceeded the latency budget of a path. Durations are in seconds."""
+
+    id: str
+    alert: LatencyAlert
+    type = EventType.LATENCY_BUDGET_EXCEEDED
+
+
+@dataclass(frozen=True)
+class ParameterUpdateEvent:
+    """The parameter `key` was changed, e.g. through `dora param set`."""
+
+    key: str
+    value: Any
+    type = EventType.PARAMETER_UPDATE
+
+
+@dataclass(frozen=True)
+class OutputUnsubscribedEvent:
+    """All consumers of the output `id` stopped, see `notify_unsubscribed`."""
+
+    id: str
+    type = EventType.OUTPUT_UNSUBSCRIBED
+
+
+@dataclass(frozen=True)
+class OutputSubscribedEvent:
+    """A consumer of the unsubscribed output `id` is running again."""
+
+    id: str
+    type = EventType.OUTPUT_SUBSCRIBED
+
+
+@dataclass(frozen=True)
+class ErrorEvent:
+    error: str
+    type = EventType.ERROR
+
+
+@dataclass(frozen=True)
+class ExternalEvent:
+    """An event of a stream that was merged into the node's events."""
+
+    value: Any
+
+
+@dataclass(frozen=True)
+class UnknownEvent:
+    """An event that this version of the Python API doesn't know yet."""
+
+    raw: Any
+    type = EventType.UNKNOWN
+
+
+Event = Union[
+    StopEvent,
+    InputEvent,
+    InputClosedEvent,
+    DeadlineMissedEvent,
+    SyncedInputEvent,
+    InputBatchEvent,
+    ServiceRequestEvent,
+    LatencyBudgetExceededEvent,
+    ParameterUpdateEvent,
+    OutputUnsubscribedEvent,
+    OutputSubscribedEvent,
+    ErrorEvent,
+    ExternalEvent,
+    UnknownEvent,
+]
+
+
+def _alert(raw: Dict[str, Any]) -> LatencyAlert:
+    hops = [HopLatency(hop["from"], hop["to"], hop["latency"]) for hop in raw["hops"]]
+    return LatencyAlert(raw["path"], raw["budget"], raw["latency"], hops)
+
+
+def parse_event(raw: Any) -> Event:
+    """Converts a native event into its typed representation."""
+    if raw["kind"] == "external":
+        return ExternalEvent(raw)
+
+    ty = raw["type"]
+    if ty == EventType.STOP:
+        return StopEvent()
+    if ty == EventType.INPUT:
+        return InputEvent(raw["id"], raw["value"], raw["metadata"])
+    if ty == EventType.INPUT_CLOSED:
+        return InputClosedEvent(raw["id"])
+    if ty == EventType.DEADLINE_MISSED:
+        return DeadlineMissedEvent(raw["id"], raw["elapsed"])
+    if ty == EventType.SYNCED_INPUT:
+        inputs = {
+            id: InputEvent(id, input["value"], input["metadata"])
+            for id, input in raw["inputs"].items()
+        }
+        return SyncedInputEvent(inputs)
+    if ty == EventType.INPUT_BATCH:
+        return InputBatchEvent(raw["id"], raw["value"], raw["metadata"])
+    if ty == EventType.SERVICE_REQUEST:
+        return ServiceRequestEvent(raw["id"], raw["value"], raw["metadata"])
+    if ty == EventType.LATENCY_BUDGET_EXCEEDED:
+        return LatencyBudgetExceededEvent(raw["id"], _alert(raw["alert"]))
+    if ty == EventType.PARAMETER_UPDATE:
+        return ParameterUpdateEvent(raw["id"], raw["value"])
+    if ty == EventType.OUTPUT_UNSUBSCRIBED:
+        return OutputUnsubscribedEvent(raw["id"])
+    if ty == EventType.OUTPUT_SUBSCRIBED:
+        return OutputSubscribedEvent(raw["id"])
+    if ty == EventType.ERROR:
+        return ErrorEvent(raw["error"])
+    return UnknownEvent(raw)
+
+
+def typed_events(node: Any, timeout: Optional[float] = None) -> Iterator[Event]:
+    """Yields the events of the given `Node` as typed events.
+
+    Stops when the event stream is closed or, if a `timeout` in seconds is
+    given, when no event arrives in time.
+    """
+    while True:
+        raw = node.next(timeout)
+        if raw is None:
+            return
+        yield parse_event(raw)
@@ -0,0 +1,105 @@
+"""
+Base class for Python operators that dispatches typed events to handler methods.
+
+```python
+import pyarrow as pa
+from dora import DoraStatus, InputEvent, Operator as BaseOperator, on_input, on_stop
+
+
+class Operator(BaseOperator):
+    @on_input("image")
+    def on_image(self, event: InputEvent, send_output) -> None:
+        send_output("bbox", pa.array([1, 2, 3]), event.metadata)
+
+    @on_stop
+    def on_stop(self, event, send_output) -> DoraStatus:
+        return DoraStatus.STOP
+```
+
+Handlers return `None` to continue or a `DoraStatus`. Events without handler
+are ignored.
+"""
+
+from typing import Any, Callable, Dict, Optional, Protocol, Tuple, TypeVar
+
+from .events import Event, EventType, ExternalEvent, parse_event
+from .status import DoraStatus
+
+__all__ = ["SendOutput", "Operator", "on_event", "on_input", "on_stop"]
+
+
+class SendOutput(Protocol):
+    """The `send_output` callback that is passed to `Operator.on_event`."""
+
+    def __call__(
+        self,
+        output: str,
+        data: Any,
+        metadata: Optional[Dict[str, Any]] = None,
+        timeout: Optional[float] = None,
+    ) -> None: ...
+
+
+Handler = Callable[[Any, Any, SendOutput], Optional[DoraStatus]]
+H = TypeVar("H", bound=Handler)
+
+# handlers are registered per event type and, optionally, per input ID
+_HandlerKey = Tuple[EventType, Optional[str]]
+
+
+def on_event(event_type: EventType, *ids: str) -> Callable[[H], H]:
+    """Registers the decorated method as handler for events of the given type.
+
+    If `ids` are given, the handler is only called for events with these IDs,
+    e.g. for `INPUT_CLOSED` events of specific inputs.
+    """
+
+    def decorator(handler: H) -> H:
+        keys = getattr(handler, "_dora_handles", [])
+        keys.extend((event_type, id) for id in ids or [None])
+        handler._dora_handles = keys  # type: ignore[attr-defined]
+        return handler
+
+    return decorator
+
+
+def on_input(*ids: str) -> Callable[[H], H]:
+    """Registers the decorated method as handler for the given inputs, or for
+    all inputs if no ID is given."""
+    return on_event(EventType.INPUT, *ids)
+
+
+def on_stop(handler: H) -> H:
+    """Registers the decorated method as handler for the `STOP` event."""
+    return on_event(EventType.STOP)(handler)
+
+
+class Operator:
+    """Base class for operators with typed event handlers, see the module docs."""
+
+    _dora_handlers: Dict[_HandlerKey, Handler] = {}
+
+    def __init_subclass__(cls, **kwargs: Any) -> None:
+        super().__init_subclass__(**kwargs)
+        handlers: Dict[_HandlerKey, Handler] = {}
+        # handlers of subclasses override the handlers of their base classes
+        for klass in reversed(cls.__mro__):
+            for attribute in vars(klass).values():
+                for key in getattr(attribute, "_dora_handles", []):
+                    handlers[key] = attribute
+        cls._dora_handlers = handlers
+
+    def on_event(self, dora_event: Any, send_output: SendOutput) -> DoraStatus:
+        event = parse_event(dora_event)
+        handler = self._handler(event)
+        if handler is None:
+            return DoraStatus.CONTINUE
+        status = handler(self, event, send_output)
+        return DoraStatus.CONTINUE if status is None else status
+
+    def _handler(self, event: Event) -> Optional[Handler]:
+        if isinstance(event, ExternalEvent):
+            return None
+        id = getattr(event, "id", None)
+        handlers = self._dora_handlers
+        return handlers.get((event.type, id)) or handlers.get((event.type, None))
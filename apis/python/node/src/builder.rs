@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use dora_node_api::dora_core::{
+    config::Input,
+    descriptor::{self, EnvValue, NodeBuilder, OperatorSource, PythonSource},
+};
+use eyre::Context;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// `DescriptorBuilder` generates dataflow descriptors from Python code, e.g. to
+/// create a node per camera or to parameterize dataflows for experiments.
+///
+/// ```python
+/// from dora import DescriptorBuilder
+///
+/// builder = DescriptorBuilder()
+/// for i in range(4):
+///     builder.add_node(
+///         f"camera-{i}",
+///         "camera.py",
+///         args=f"--device {i}",
+///         inputs={"tick": "dora/timer/millis/50"},
+///         outputs=["image"],
+///     )
+///     builder.add_operator(
+///         f"detector-{i}",
+///         "detector.py",
+///         inputs={"image": {"source": f"camera-{i}/image", "queue_size": 1}},
+///         outputs=["bbox"],
+///         params={"threshold": 0.5},
+///     )
+/// builder.save("dataflow.yml")
+/// ```
+///
+#[pyclass]
+#[derive(Default)]
+pub struct DescriptorBuilder {
+    builder: descriptor::DescriptorBuilder,
+}
+
+#[pymethods]
+impl DescriptorBuilder {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a custom node that runs the given executable or script.
+    ///
+    /// Inputs map input IDs to `<node>/<output>` strings or to dictionaries with
+    /// input options such as `queue_size`.
+    #[pyo3(signature = (id, source, args=None, inputs=None, outputs=None, env=None, machine=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_node(
+        &mut self,
+        id: String,
+        source: String,
+        args: Option<String>,
+        inputs: Option<&PyDict>,
+        outputs: Option<Vec<String>>,
+        env: Option<&PyDict>,
+        machine: Option<String>,
+    ) -> PyResult<()> {
+        let mut node = self.builder.custom_node(id, source);
+        if let Some(args) = args {
+            node.args(args);
+        }
+        configure(&mut node, inputs, outputs, env, machine)
+    }
+
+    /// Adds a node that runs the given Python operator.
+    ///
+    /// The `params` are passed to the operator on initialization.
+    #[pyo3(signature = (id, python, inputs=None, outputs=None, params=None, env=None, machine=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_operator(
+        &mut self,
+        id: String,
+        python: String,
+        inputs: Option<&PyDict>,
+        outputs: Option<Vec<String>>,
+        params: Option<&PyAny>,
+        env: Option<&PyDict>,
+        machine: Option<String>,
+    ) -> PyResult<()> {
+        let source = OperatorSource::Python(PythonSource {
+            source: python,
+            conda_env: None,
+            requirements: None,
+        });
+        let mut node = self.builder.operator_node(id, source);
+        if let Some(params) = params {
+            node.params(pythonize::depythonize(params)?);
+        }
+        configure(&mut node, inputs, outputs, env, machine)
+    }
+
+    /// Returns the dataflow as YAML string.
+    pub fn to_yaml(&self) -> eyre::Result<String> {
+        self.builder.to_yaml()
+    }
+
+    /// Writes the dataflow as YAML file to the given path.
+    pub fn save(&self, path: PathBuf) -> eyre::Result<()> {
+        let yaml = self.builder.to_yaml()?;
+        std::fs::write(&path, yaml)
+            .wrap_err_with(|| format!("failed to write dataflow to `{}`", path.display()))
+    }
+}
+
+fn configure(
+    node: &mut NodeBuilder,
+    inputs: Option<&PyDict>,
+    outputs: Option<Vec<String>>,
+    env: Option<&PyDict>,
+    machine: Option<String>,
+) -> PyResult<()> {
+    for (input_id, input) in inputs.into_iter().flatten() {
+        let input: Input = pythonize::depythonize(input)
+            .wrap_err_with(|| format!("invalid input `{input_id}`"))?;
+        node.input_with_options(input_id.extract::<String>()?, input);
+    }
+    for output in outputs.into_iter().flatten() {
+        node.output(output);
+    }
+    for (key, value) in env.into_iter().flatten() {
+        let value: EnvValue = pythonize::depythonize(value)
+            .wrap_err_with(|| format!("invalid value of env variable `{key}`"))?;
+        node.env(key.extract::<String>()?, value);
+    }
+    if let Some(machine) = machine {
+        node.machine(machine);
+    }
+    Ok(())
+}
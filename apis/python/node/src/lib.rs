@@ -2,16 +2,25 @@
 
 use std::time::Duration;
 
+use arrow::array::Array;
 use arrow::pyarrow::{FromPyArrow, ToPyArrow};
+use dora_node_api::arrow_utils::{copy_array_into_sample, required_data_size};
+use dora_node_api::dora_core::config::DataId;
+use dora_node_api::dora_core::message::ArrowTypeInfo;
 use dora_node_api::merged::{MergeExternalSend, MergedEvent};
-use dora_node_api::{DoraNode, EventStream};
-use dora_operator_api_python::{pydict_to_metadata, PyEvent};
+use dora_node_api::{DoraNode, EventStream, DEFAULT_SERVICE_TIMEOUT};
+use dora_operator_api_python::{
+    dlpack_to_arrow, metadata_to_pydict, pydict_to_metadata, BackpressureError, PyEvent,
+};
 use dora_ros2_bridge_python::Ros2Subscription;
 use eyre::Context;
 use futures::{Stream, StreamExt};
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyDict};
 
+mod builder;
+mod testing;
+
 /// The custom node API lets you integrate `dora` into your application.
 /// It allows you to retrieve input and send output in any fashion you want.
 ///
@@ -23,6 +32,14 @@ use pyo3::types::{PyBytes, PyDict};
 /// node = Node()
 /// ```
 ///
+/// The node can also be used as context manager, which closes all outputs on exit:
+///
+/// ```python
+/// with Node() as node:
+///     for event in node:
+///         ...
+/// ```
+///
 #[pyclass]
 pub struct Node {
     events: Events,
@@ -73,12 +90,29 @@ impl Node {
         slf
     }
 
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Closes all outputs of the node, so that their receivers are notified
+    /// without waiting for the node to exit.
+    fn __exit__(
+        &mut self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> eyre::Result<bool> {
+        let outputs = self.node.node_config().outputs.iter().cloned().collect();
+        self.node.close_outputs(outputs)?;
+        Ok(false)
+    }
+
     /// `send_output` send data from the node.
     ///
     /// ```python
     /// Args:
     ///    output_id: str,
-    ///    data: Bytes|Arrow,
+    ///    data: Bytes|Arrow|DLPack tensor,
     ///    metadata: Option[Dict],
     /// ```
     ///
@@ -86,33 +120,119 @@ impl Node {
     /// node.send_output("string", b"string", {"open_telemetry_context": "7632e76"})
     /// ```
     ///
+    /// Tensors in GPU memory are copied to host memory before sending. Their
+    /// original device is recorded in the `device` metadata field.
+    ///
+    /// Raises a `BackpressureError` if the shared memory budget of the node or
+    /// dataflow doesn't allow allocating the output, e.g. to skip the current frame.
+    ///
     pub fn send_output(
         &mut self,
         output_id: String,
         data: PyObject,
         metadata: Option<&PyDict>,
         py: Python,
-    ) -> eyre::Result<()> {
-        let parameters = pydict_to_metadata(metadata)?;
+    ) -> PyResult<()> {
+        let mut parameters = pydict_to_metadata(metadata)?;
 
-        if let Ok(py_bytes) = data.downcast::<PyBytes>(py) {
-            let data = py_bytes.as_bytes();
+        let output_id = DataId::from(output_id);
+        let mut allocate_sample = |data_len| {
             self.node
-                .send_output_bytes(output_id.into(), parameters, data.len(), data)
-                .wrap_err("failed to send output")?;
+                .allocate_output_sample(&output_id, data_len)
+                .map_err(|err| {
+                    BackpressureError::new_err(format!(
+                        "failed to allocate output sample of {data_len} bytes: {err:#}"
+                    ))
+                })
+        };
+        let (sample, type_info) = if let Ok(py_bytes) = data.downcast::<PyBytes>(py) {
+            let data = py_bytes.as_bytes();
+            let mut sample = allocate_sample(data.len())?;
+            sample.copy_from_slice(data);
+            (sample, ArrowTypeInfo::byte_array(data.len()))
         } else if let Ok(arrow_array) = arrow::array::ArrayData::from_pyarrow(data.as_ref(py)) {
-            self.node.send_output(
-                output_id.into(),
-                parameters,
-                arrow::array::make_array(arrow_array),
-            )?;
+            let mut sample = allocate_sample(required_data_size(&arrow_array))?;
+            let type_info = copy_array_into_sample(&mut sample, &arrow_array);
+            (sample, type_info)
+        } else if let Some((arrow_array, device)) = dlpack_to_arrow(data.as_ref(py))? {
+            parameters.device = device;
+            let mut sample = allocate_sample(required_data_size(&arrow_array))?;
+            let type_info = copy_array_into_sample(&mut sample, &arrow_array);
+            (sample, type_info)
         } else {
-            eyre::bail!("invalid `data` type, must by `PyBytes` or arrow array")
-        }
+            return Err(eyre::eyre!(
+                "invalid `data` type, must by `PyBytes`, arrow array, or DLPack tensor"
+            )
+            .into());
+        };
+
+        self.node
+            .send_output_sample(output_id, type_info, parameters, Some(sample))
+            .wrap_err("failed to send output")?;
 
         Ok(())
     }
 
+    /// `call_service` calls a service of another node and blocks until it replies.
+    ///
+    /// The service is referenced as `<node_id>/<service>`. Returns the reply value
+    /// as pyarrow array together with its metadata. Fails if the service doesn't
+    /// reply within `timeout` seconds (10 by default).
+    ///
+    /// ```python
+    /// value, metadata = node.call_service("detector/query", pa.array([1, 2, 3]))
+    /// ```
+    pub fn call_service(
+        &mut self,
+        service: &str,
+        data: PyObject,
+        metadata: Option<&PyDict>,
+        timeout: Option<f32>,
+        py: Python,
+    ) -> PyResult<(PyObject, PyObject)> {
+        let mut parameters = pydict_to_metadata(metadata)?;
+        let data = if let Ok(py_bytes) = data.downcast::<PyBytes>(py) {
+            arrow::array::UInt8Array::from(py_bytes.as_bytes().to_vec()).into_data()
+        } else if let Ok(arrow_array) = arrow::array::ArrayData::from_pyarrow(data.as_ref(py)) {
+            arrow_array
+        } else if let Some((arrow_array, device)) = dlpack_to_arrow(data.as_ref(py))? {
+            parameters.device = device;
+            arrow_array
+        } else {
+            return Err(eyre::eyre!(
+                "invalid `data` type, must be `PyBytes`, arrow array, or DLPack tensor"
+            )
+            .into());
+        };
+
+        let timeout = timeout.map_or(DEFAULT_SERVICE_TIMEOUT, Duration::from_secs_f32);
+        let call = self.node.call_service_with_timeout(
+            service,
+            parameters,
+            arrow::array::make_array(data),
+            timeout,
+        )?;
+        let (metadata, value) = py.allow_threads(|| call.wait())?;
+        Ok((
+            value.to_data().to_pyarrow(py)?,
+            metadata_to_pydict(&metadata, py).to_object(py),
+        ))
+    }
+
+    /// `ready` signals that the node finished its initialization.
+    ///
+    /// Nodes with `ready_signal: true` must call this once they can handle
+    /// inputs. The other nodes of the dataflow are only started afterwards.
+    ///
+    /// ```python
+    /// node = Node()
+    /// model = load_model()
+    /// node.ready()
+    /// ```
+    pub fn ready(&mut self) -> eyre::Result<()> {
+        self.node.ready()
+    }
+
     /// Returns the full dataflow descriptor that this node is part of.
     ///
     /// This method returns the parsed dataflow YAML file.
@@ -120,6 +240,15 @@ impl Node {
         pythonize::pythonize(py, self.node.dataflow_descriptor())
     }
 
+    /// Returns the resolved queue configuration of each input of the node.
+    ///
+    /// ```python
+    /// queue_size = node.input_config()["image"]["queue_size"]
+    /// ```
+    pub fn input_config(&self, py: Python) -> pythonize::Result<PyObject> {
+        pythonize::pythonize(py, &self.node.input_config())
+    }
+
     pub fn merge_external_events(
         &mut self,
         subscription: &mut Ros2Subscription,
@@ -206,6 +335,9 @@ pub fn start_runtime() -> eyre::Result<()> {
 fn dora(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(start_runtime, m)?)?;
     m.add_class::<Node>().unwrap();
+    m.add_class::<builder::DescriptorBuilder>()?;
+    m.add_class::<testing::OperatorTest>()?;
+    m.add("BackpressureError", py.get_type::<BackpressureError>())?;
 
     let ros2_bridge = PyModule::new(py, "ros2_bridge")?;
     dora_ros2_bridge_python::create_dora_ros2_bridge_module(ros2_bridge)?;
@@ -215,3 +347,99 @@ fn dora(py: Python, m: &PyModule) -> PyResult<()> {
 
     Ok(())
 }
+
+/// The Python stubs and typed events are written by hand, so these tests
+/// compare them with the Rust definitions that they describe.
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    const STUBS: &str = include_str!("../dora/dora.pyi");
+    const EVENTS: &str = include_str!("../dora/events.py");
+
+    /// Returns the Python names of the methods of the `#[pymethods]` block of
+    /// the given type.
+    fn pymethods(source: &str, ty: &str) -> BTreeSet<String> {
+        let start = source
+            .find(&format!("#[pymethods]\nimpl {ty} {{\n"))
+            .unwrap_or_else(|| panic!("no #[pymethods] block for `{ty}`"));
+        source[start..]
+            .lines()
+            .skip(2)
+            .take_while(|line| *line != "}")
+            .filter_map(|line| {
+                let line = line.strip_prefix("    ")?;
+                let name = line.strip_prefix("pub fn ").or(line.strip_prefix("fn "))?;
+                let name = name.split(['(', '<']).next()?;
+                Some(if name == "new" { "__init__" } else { name }.to_owned())
+            })
+            .collect()
+    }
+
+    /// Returns the names of the methods of the given class of a Python file.
+    fn python_methods(source: &str, class: &str) -> BTreeSet<String> {
+        let start = source
+            .find(&format!("\nclass {class}"))
+            .unwrap_or_else(|| panic!("no class `{class}`"));
+        source[start + 1..]
+            .lines()
+            .skip(1)
+            .take_while(|line| line.is_empty() || line.starts_with(' '))
+            .filter_map(|line| {
+                let name = line.strip_prefix("    def ")?;
+                Some(name.split('(').next()?.to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stubs_match_pymethods() {
+        let sources = [
+            ("Node", include_str!("lib.rs")),
+            ("DescriptorBuilder", include_str!("builder.rs")),
+            ("OperatorTest", include_str!("testing.rs")),
+            ("PyEvent", include_str!("../../operator/src/lib.rs")),
+        ];
+        for (ty, source) in sources {
+            assert_eq!(
+                python_methods(STUBS, ty),
+                pymethods(source, ty),
+                "methods of `{ty}` in dora.pyi don't match its #[pymethods]"
+            );
+        }
+    }
+
+    #[test]
+    fn event_types_match_py_event() {
+        let source = include_str!("../../operator/src/lib.rs");
+        let start = source
+            .find("fn ty(event: &Event) -> &str {")
+            .expect("no `PyEvent::ty` function");
+        let end = start + source[start..].find("\n    }\n").unwrap();
+        let rust_types: BTreeSet<_> = source[start..end]
+            .lines()
+            .filter_map(|line| line.split("=> \"").nth(1)?.strip_suffix("\","))
+            .collect();
+
+        let start = EVENTS.find("\nclass EventType").unwrap();
+        let python_types: BTreeSet<_> = EVENTS[start + 1..]
+            .lines()
+            .skip(1)
+            .take_while(|line| line.is_empty() || line.starts_with(' '))
+            .filter_map(|line| line.trim().split_once(" = "))
+            .map(|(name, value)| {
+                assert_eq!(value, format!("\"{name}\""), "invalid `EventType.{name}`");
+                name
+            })
+            .collect();
+        assert_eq!(python_types, rust_types, "EventType doesn't match PyEvent");
+
+        let parse_event = &EVENTS[EVENTS.find("\ndef parse_event").unwrap()..];
+        for ty in rust_types.iter().filter(|ty| **ty != "UNKNOWN") {
+            assert!(
+                parse_event.contains(&format!("if ty == EventType.{ty}:")),
+                "`parse_event` doesn't handle `{ty}` events"
+            );
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use arrow::array::Array;
+use arrow::pyarrow::{FromPyArrow, ToPyArrow};
+use dora_operator_api_python::{metadata_to_pydict, pydict_to_metadata};
+use dora_test::{StopReason, TestOutput};
+use eyre::ContextCompat;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// `OperatorTest` runs a single operator in-process for unit tests, e.g. with
+/// pytest. No coordinator or daemon is needed.
+///
+/// ```python
+/// import pyarrow as pa
+/// from dora import OperatorTest
+///
+/// def test_operator():
+///     test = OperatorTest(
+///         """
+///         id: counter
+///         python: counter_op.py
+///         inputs:
+///           tick: dora/timer/millis/100
+///         outputs:
+///           - count
+///         """
+///     )
+///     test.send_input("tick", pa.array([1]))
+///     output = test.next_output()
+///     assert output["id"] == "count"
+///
+///     result = test.finish()
+///     assert result["stop_reason"] == "INPUTS_CLOSED"
+/// ```
+///
+#[pyclass]
+pub struct OperatorTest {
+    test: Option<dora_test::OperatorTest>,
+}
+
+#[pymethods]
+impl OperatorTest {
+    /// Starts the operator described by the given YAML, which uses the syntax
+    /// of an operator entry in a dataflow file.
+    #[new]
+    pub fn new(definition: &str, py: Python) -> eyre::Result<Self> {
+        let test = py.allow_threads(|| dora_test::OperatorTest::from_yaml(definition))?;
+        Ok(Self { test: Some(test) })
+    }
+
+    /// Sends an input to the operator. The data must be a pyarrow array.
+    pub fn send_input(
+        &mut self,
+        id: &str,
+        data: PyObject,
+        metadata: Option<&PyDict>,
+        py: Python,
+    ) -> eyre::Result<()> {
+        let parameters = pydict_to_metadata(metadata)?;
+        let data = arrow::array::ArrayData::from_pyarrow(data.as_ref(py))?;
+        self.test()?
+            .send_input_with_parameters(id, parameters, data)
+    }
+
+    /// Notifies the operator that the given input is closed.
+    pub fn close_input(&mut self, id: &str) -> eyre::Result<()> {
+        self.test()?.close_input(id)
+    }
+
+    /// Sends a stop event to the operator.
+    pub fn stop(&mut self) -> eyre::Result<()> {
+        self.test()?.stop()
+    }
+
+    /// Waits for the next output of the operator.
+    ///
+    /// Returns a dictionary with the `id`, `value`, and `metadata` of the output,
+    /// or `None` if the operator sends no output within `timeout` seconds.
+    #[pyo3(signature = (timeout=1.0))]
+    pub fn next_output(&mut self, timeout: f32, py: Python) -> eyre::Result<Option<PyObject>> {
+        let test = self.test()?;
+        let output = py.allow_threads(|| test.next_output(Duration::from_secs_f32(timeout)))?;
+        output.map(|output| output_to_py(output, py)).transpose()
+    }
+
+    /// Closes all inputs and waits until the operator stops.
+    ///
+    /// Returns a dictionary with the remaining `outputs` and the `stop_reason`,
+    /// which is one of `INPUTS_CLOSED`, `STOP`, and `STOP_ALL`.
+    pub fn finish(&mut self, py: Python) -> eyre::Result<PyObject> {
+        let test = self
+            .test
+            .take()
+            .wrap_err("operator test is finished already")?;
+        let run = py.allow_threads(|| test.finish())?;
+
+        let outputs = run
+            .outputs
+            .into_iter()
+            .map(|output| output_to_py(output, py))
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let stop_reason = match run.stop_reason {
+            StopReason::InputsClosed => "INPUTS_CLOSED",
+            StopReason::ExplicitStop => "STOP",
+            StopReason::ExplicitStopAll => "STOP_ALL",
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("outputs", PyList::new(py, outputs))?;
+        dict.set_item("stop_reason", stop_reason)?;
+        Ok(dict.into())
+    }
+}
+
+impl OperatorTest {
+    fn test(&mut self) -> eyre::Result<&mut dora_test::OperatorTest> {
+        self.test
+            .as_mut()
+            .wrap_err("operator test is finished already")
+    }
+}
+
+fn output_to_py(output: TestOutput, py: Python) -> eyre::Result<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", output.id.to_string())?;
+    dict.set_item("value", output.data.to_data().to_pyarrow(py)?)?;
+    dict.set_item("metadata", metadata_to_pydict(&output.metadata, py))?;
+    Ok(dict.into())
+}
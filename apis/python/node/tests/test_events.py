@@ -0,0 +1,86 @@
+import pyarrow as pa
+
+from dora import (
+    DeadlineMissedEvent,
+    ErrorEvent,
+    ExternalEvent,
+    InputBatchEvent,
+    InputClosedEvent,
+    InputEvent,
+    LatencyBudgetExceededEvent,
+    OutputSubscribedEvent,
+    ParameterUpdateEvent,
+    ServiceRequestEvent,
+    StopEvent,
+    SyncedInputEvent,
+    UnknownEvent,
+    parse_event,
+)
+
+
+def dora_event(type, **entries):
+    return {"kind": "dora", "type": type, **entries}
+
+
+def test_parse_input_events():
+    value = pa.array([1, 2, 3])
+    metadata = {"open_telemetry_context": ""}
+    event = parse_event(dora_event("INPUT", id="image", value=value, metadata=metadata))
+    assert event == InputEvent("image", value, metadata)
+
+    batch = pa.array([[1], [2, 3]])
+    event = parse_event(
+        dora_event("INPUT_BATCH", id="image", value=batch, metadata=[{}, {}])
+    )
+    assert event == InputBatchEvent("image", batch, [{}, {}])
+
+    inputs = {
+        "left": {"value": value, "metadata": {}},
+        "right": {"value": value, "metadata": {}},
+    }
+    event = parse_event(dora_event("SYNCED_INPUT", inputs=inputs))
+    assert isinstance(event, SyncedInputEvent)
+    assert event.inputs["right"] == InputEvent("right", value, {})
+
+    event = parse_event(
+        dora_event("SERVICE_REQUEST", id="query", value=value, metadata={})
+    )
+    assert event == ServiceRequestEvent("query", value, {})
+
+
+def test_parse_status_events():
+    assert parse_event(dora_event("STOP")) == StopEvent()
+    closed = parse_event(dora_event("INPUT_CLOSED", id="image"))
+    assert closed == InputClosedEvent("image")
+    assert parse_event(
+        dora_event("DEADLINE_MISSED", id="image", elapsed=0.5)
+    ) == DeadlineMissedEvent("image", 0.5)
+    assert parse_event(
+        dora_event("PARAMETER_UPDATE", id="threshold", value=0.7)
+    ) == ParameterUpdateEvent("threshold", 0.7)
+    subscribed = parse_event(dora_event("OUTPUT_SUBSCRIBED", id="bbox"))
+    assert subscribed == OutputSubscribedEvent("bbox")
+    assert parse_event(dora_event("ERROR", error="failed")) == ErrorEvent("failed")
+
+
+def test_parse_latency_alerts():
+    alert = {
+        "path": ["camera", "detector"],
+        "budget": 0.05,
+        "latency": 0.08,
+        "hops": [{"from": "camera", "to": "detector", "latency": 0.08}],
+    }
+    event = parse_event(
+        dora_event("LATENCY_BUDGET_EXCEEDED", id="image", alert=alert)
+    )
+    assert isinstance(event, LatencyBudgetExceededEvent)
+    assert event.alert.path == ["camera", "detector"]
+    assert event.alert.hops[0].target == "detector"
+
+
+def test_parse_other_events():
+    external = {"kind": "external", "value": 42}
+    assert parse_event(external) == ExternalEvent(external)
+
+    raw = dora_event("SOMETHING_NEW")
+    assert parse_event(raw) == UnknownEvent(raw)
@@ -0,0 +1,82 @@
+import pyarrow as pa
+
+from dora import (
+    DoraStatus,
+    EventType,
+    InputEvent,
+    Operator,
+    on_event,
+    on_input,
+    on_stop,
+)
+
+
+def dora_event(type, **entries):
+    return {"kind": "dora", "type": type, **entries}
+
+
+def input_event(id):
+    return dora_event("INPUT", id=id, value=pa.array([1]), metadata={})
+
+
+class Recorder:
+    def __init__(self):
+        self.outputs = []
+
+    def __call__(self, output, data, metadata=None, timeout=None):
+        self.outputs.append(output)
+
+
+class Detector(Operator):
+    @on_input("image")
+    def on_image(self, event, send_output):
+        assert isinstance(event, InputEvent)
+        send_output("bbox", event.value, event.metadata)
+
+    @on_input()
+    def on_other_input(self, event, send_output):
+        send_output(f"other-{event.id}", event.value)
+
+    @on_event(EventType.INPUT_CLOSED, "image")
+    def on_image_closed(self, event, send_output):
+        return DoraStatus.STOP
+
+    @on_stop
+    def on_stop(self, event, send_output):
+        return DoraStatus.STOP_ALL
+
+
+def test_dispatch_by_type_and_id():
+    operator = Detector()
+    send_output = Recorder()
+
+    assert operator.on_event(input_event("image"), send_output) == DoraStatus.CONTINUE
+    assert operator.on_event(input_event("depth"), send_output) == DoraStatus.CONTINUE
+    assert send_output.outputs == ["bbox", "other-depth"]
+
+    closed = dora_event("INPUT_CLOSED", id="image")
+    assert operator.on_event(closed, send_output) == DoraStatus.STOP
+    assert operator.on_event(dora_event("STOP"), send_output) == DoraStatus.STOP_ALL
+
+
+def test_ignore_events_without_handler():
+    operator = Detector()
+    send_output = Recorder()
+
+    closed = dora_event("INPUT_CLOSED", id="depth")
+    assert operator.on_event(closed, send_output) == DoraStatus.CONTINUE
+    external = {"kind": "external", "value": 42}
+    assert operator.on_event(external, send_output) == DoraStatus.CONTINUE
+    assert send_output.outputs == []
+
+
+def test_subclasses_override_handlers():
+    class Tracker(Detector):
+        @on_input("image")
+        def track(self, event, send_output):
+            send_output("track", event.value)
+
+    send_output = Recorder()
+    Tracker().on_event(input_event("image"), send_output)
+    Detector().on_event(input_event("image"), send_output)
+    assert send_output.outputs == ["track", "bbox"]
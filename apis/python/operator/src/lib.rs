@@ -1,7 +1,23 @@
-use arrow::{array::ArrayRef, pyarrow::ToPyArrow};
-use dora_node_api::{merged::MergedEvent, Event, Metadata, MetadataParameters};
+use arrow::{
+    array::{ArrayData, ArrayRef},
+    pyarrow::{FromPyArrow, ToPyArrow},
+};
+use dora_node_api::{
+    merged::MergedEvent, Device, Event, Metadata, MetadataParameters, MetadataValue, SequenceNumber,
+};
 use eyre::{Context, Result};
-use pyo3::{exceptions::PyLookupError, prelude::*, types::PyDict};
+use pyo3::{
+    exceptions::{PyLookupError, PyUserWarning},
+    prelude::*,
+    types::{PyBool, PyBytes, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple},
+};
+
+pyo3::create_exception!(
+    dora,
+    BackpressureError,
+    pyo3::exceptions::PyException,
+    "Raised when an output cannot be sent because no memory could be allocated for it."
+);
 
 #[pyclass]
 pub struct PyEvent {
@@ -27,6 +43,9 @@ impl PyEvent {
                     "value" => self.value(py)?,
                     "metadata" => Self::metadata(event, py),
                     "error" => Self::error(event).map(|v| v.to_object(py)),
+                    "elapsed" => Self::elapsed(event).map(|v| v.to_object(py)),
+                    "inputs" => Self::inputs(event, py)?,
+                    "alert" => Self::alert(event, py)?,
                     other => {
                         return Err(PyLookupError::new_err(format!(
                             "event has no property `{other}`"
@@ -53,6 +72,14 @@ impl PyEvent {
             Event::Stop => "STOP",
             Event::Input { .. } => "INPUT",
             Event::InputClosed { .. } => "INPUT_CLOSED",
+            Event::DeadlineMissed { .. } => "DEADLINE_MISSED",
+            Event::SyncedInput { .. } => "SYNCED_INPUT",
+            Event::InputBatch { .. } => "INPUT_BATCH",
+            Event::ServiceRequest { .. } => "SERVICE_REQUEST",
+            Event::LatencyBudgetExceeded { .. } => "LATENCY_BUDGET_EXCEEDED",
+            Event::ParameterUpdate { .. } => "PARAMETER_UPDATE",
+            Event::OutputUnsubscribed { .. } => "OUTPUT_UNSUBSCRIBED",
+            Event::OutputSubscribed { .. } => "OUTPUT_SUBSCRIBED",
             Event::Error(_) => "ERROR",
             _other => "UNKNOWN",
         }
@@ -61,15 +88,32 @@ impl PyEvent {
     fn id(event: &Event) -> Option<&str> {
         match event {
             Event::Input { id, .. } => Some(id),
+            Event::InputBatch { id, .. } => Some(id),
             Event::InputClosed { id } => Some(id),
+            Event::DeadlineMissed { id, .. } => Some(id),
+            Event::LatencyBudgetExceeded { id, .. } => Some(id),
+            Event::ServiceRequest { service, .. } => Some(service),
+            Event::ParameterUpdate { key, .. } => Some(key),
+            Event::OutputUnsubscribed { id } | Event::OutputSubscribed { id } => Some(id),
             _ => None,
         }
     }
 
     /// Returns the payload of an input event as an arrow array (if any).
+    ///
+    /// The payload of `INPUT_BATCH` events is a list array with one entry per message.
+    /// For `PARAMETER_UPDATE` events, the new value of the parameter is returned.
     fn value(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
         match (&self.event, &self.data) {
-            (MergedEvent::Dora(Event::Input { .. }), Some(data)) => {
+            (MergedEvent::Dora(Event::ParameterUpdate { value, .. }), _) => {
+                Ok(Some(metadata_value_to_py(value, py)))
+            }
+            (
+                MergedEvent::Dora(
+                    Event::Input { .. } | Event::InputBatch { .. } | Event::ServiceRequest { .. },
+                ),
+                Some(data),
+            ) => {
                 // TODO: Does this call leak data?
                 let array_data = data.to_data().to_pyarrow(py)?;
                 Ok(Some(array_data))
@@ -80,7 +124,18 @@ impl PyEvent {
 
     fn metadata(event: &Event, py: Python<'_>) -> Option<PyObject> {
         match event {
-            Event::Input { metadata, .. } => Some(metadata_to_pydict(metadata, py).to_object(py)),
+            Event::Input { metadata, .. } | Event::ServiceRequest { metadata, .. } => {
+                Some(metadata_to_pydict(metadata, py).to_object(py))
+            }
+            Event::InputBatch { metadata, .. } => Some(
+                PyList::new(
+                    py,
+                    metadata
+                        .iter()
+                        .map(|metadata| metadata_to_pydict(metadata, py)),
+                )
+                .to_object(py),
+            ),
             _ => None,
         }
     }
@@ -91,6 +146,53 @@ impl PyEvent {
             _other => None,
         }
     }
+
+    /// Returns the time since the last input for `DEADLINE_MISSED` events, in seconds.
+    fn elapsed(event: &Event) -> Option<f64> {
+        match event {
+            Event::DeadlineMissed { elapsed, .. } => Some(elapsed.as_secs_f64()),
+            _other => None,
+        }
+    }
+
+    /// Returns the inputs of a `SYNCED_INPUT` event as a dict that maps each input ID
+    /// to a dict with `value` and `metadata` entries.
+    fn inputs(event: &Event, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let Event::SyncedInput { inputs } = event else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        for (id, (metadata, data)) in inputs {
+            let input = PyDict::new(py);
+            input.set_item("value", data.to_data().to_pyarrow(py)?)?;
+            input.set_item("metadata", metadata_to_pydict(metadata, py))?;
+            dict.set_item(id.as_str(), input)?;
+        }
+        Ok(Some(dict.to_object(py)))
+    }
+
+    /// Returns the alert of a `LATENCY_BUDGET_EXCEEDED` event as a dict with `path`,
+    /// `budget`, `latency` and per-hop `hops` entries. Durations are in seconds.
+    fn alert(event: &Event, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let Event::LatencyBudgetExceeded { alert, .. } = event else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        let path: Vec<String> = alert.path.iter().map(|node| node.to_string()).collect();
+        dict.set_item("path", path)?;
+        dict.set_item("budget", alert.budget.as_secs_f64())?;
+        dict.set_item("latency", alert.latency.as_secs_f64())?;
+        let hops = PyList::empty(py);
+        for hop in &alert.hops {
+            let entry = PyDict::new(py);
+            entry.set_item("from", hop.from.to_string())?;
+            entry.set_item("to", hop.to.to_string())?;
+            entry.set_item("latency", hop.latency.as_secs_f64())?;
+            hops.append(entry)?;
+        }
+        dict.set_item("hops", hops)?;
+        Ok(Some(dict.to_object(py)))
+    }
 }
 
 impl From<Event> for PyEvent {
@@ -101,7 +203,12 @@ impl From<Event> for PyEvent {
 
 impl From<MergedEvent<PyObject>> for PyEvent {
     fn from(mut event: MergedEvent<PyObject>) -> Self {
-        let data = if let MergedEvent::Dora(Event::Input { data, .. }) = &mut event {
+        let data = if let MergedEvent::Dora(
+            Event::Input { data, .. }
+            | Event::InputBatch { data, .. }
+            | Event::ServiceRequest { data, .. },
+        ) = &mut event
+        {
             Some(data.clone())
         } else {
             None
@@ -129,13 +236,125 @@ pub fn pydict_to_metadata(dict: Option<&PyDict>) -> Result<MetadataParameters> {
                         .context("parsing open telemetry context failed")?;
                     default_metadata.open_telemetry_context = otel_context.to_string();
                 }
-                _ => (),
+                "sequence" => {
+                    let (key, number): (String, u64) = value
+                        .extract()
+                        .context("parsing sequence failed, expected `(key, number)` tuple")?;
+                    default_metadata.sequence = Some(SequenceNumber { key, number });
+                }
+                "device" => {
+                    let (device_type, device_id): (i32, i32) = value.extract().context(
+                        "parsing device failed, expected `(device_type, device_id)` tuple",
+                    )?;
+                    default_metadata.device = Some(Device {
+                        device_type,
+                        device_id,
+                    });
+                }
+                "annotates" => {
+                    let timestamp: &str = value
+                        .extract()
+                        .context("parsing annotates failed, expected a timestamp string")?;
+                    default_metadata.annotates = Some(
+                        timestamp
+                            .parse()
+                            .map_err(|err| eyre::eyre!("invalid annotates timestamp: {err:?}"))?,
+                    );
+                }
+                "logical_time" => {
+                    default_metadata.logical_time =
+                        Some(value.extract().context("parsing logical_time failed")?);
+                }
+                // set by the sender, so they are ignored when metadata is forwarded
+                "timestamp" | "lineage" | "format" => (),
+                "values" => {
+                    let values: &PyDict = value
+                        .downcast()
+                        .map_err(|_| eyre::eyre!("parsing values failed, expected a dict"))?;
+                    for (key, value) in values {
+                        let key: String = key.extract().context("parsing metadata value keys")?;
+                        match py_to_metadata_value(value) {
+                            Ok(value) => {
+                                default_metadata.values.insert(key, value);
+                            }
+                            Err(err) => warn(
+                                metadata.py(),
+                                &format!("ignoring metadata value `{key}`: {err}"),
+                            )?,
+                        }
+                    }
+                }
+                key => warn(
+                    metadata.py(),
+                    &format!(
+                        "ignoring unknown metadata key `{key}`, custom values need to be \
+                        set in the `values` dict"
+                    ),
+                )?,
             }
         }
     }
     Ok(default_metadata)
 }
 
+/// Issues a Python `UserWarning`, which is shown once per call site by default.
+fn warn(py: Python, message: &str) -> Result<()> {
+    PyErr::warn(py, py.get_type::<PyUserWarning>(), message, 1)?;
+    Ok(())
+}
+
+/// Converts a Python object to a typed metadata value.
+///
+/// Supports `bool`, `int`, `float`, `str`, `bytes`, and lists or tuples of these types.
+fn py_to_metadata_value(value: &PyAny) -> Result<MetadataValue> {
+    // `bool` is a subclass of `int` in Python, so it needs to be checked first
+    let value = if let Ok(value) = value.downcast::<PyBool>() {
+        MetadataValue::Bool(value.is_true())
+    } else if value.is_instance_of::<PyLong>() {
+        MetadataValue::Integer(value.extract().context("integer does not fit into i64")?)
+    } else if let Ok(value) = value.downcast::<PyFloat>() {
+        MetadataValue::Float(value.value())
+    } else if let Ok(value) = value.downcast::<PyString>() {
+        MetadataValue::String(value.to_str()?.to_owned())
+    } else if let Ok(value) = value.downcast::<PyBytes>() {
+        MetadataValue::Bytes(value.as_bytes().to_owned())
+    } else if let Ok(list) = value.downcast::<PyList>() {
+        MetadataValue::List(
+            list.iter()
+                .map(py_to_metadata_value)
+                .collect::<Result<_>>()?,
+        )
+    } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+        MetadataValue::List(
+            tuple
+                .iter()
+                .map(py_to_metadata_value)
+                .collect::<Result<_>>()?,
+        )
+    } else {
+        eyre::bail!(
+            "unsupported metadata value type `{}`",
+            value.get_type().name()?
+        );
+    };
+    Ok(value)
+}
+
+pub fn metadata_value_to_py(value: &MetadataValue, py: Python<'_>) -> PyObject {
+    match value {
+        MetadataValue::Bool(value) => value.to_object(py),
+        MetadataValue::Integer(value) => value.to_object(py),
+        MetadataValue::Float(value) => value.to_object(py),
+        MetadataValue::String(value) => value.to_object(py),
+        MetadataValue::Bytes(value) => PyBytes::new(py, value).to_object(py),
+        MetadataValue::List(values) => PyList::new(
+            py,
+            values.iter().map(|value| metadata_value_to_py(value, py)),
+        )
+        .to_object(py),
+    }
+}
+
 pub fn metadata_to_pydict<'a>(metadata: &'a Metadata, py: Python<'a>) -> &'a PyDict {
     let dict = PyDict::new(py);
     dict.set_item(
@@ -144,9 +363,115 @@ pub fn metadata_to_pydict<'a>(metadata: &'a Metadata, py: Python<'a>) -> &'a PyD
     )
     .wrap_err("could not make metadata a python dictionary item")
     .unwrap();
+    dict.set_item("timestamp", metadata.timestamp().to_string())
+        .wrap_err("could not make metadata a python dictionary item")
+        .unwrap();
+    let lineage: Vec<_> = metadata
+        .lineage()
+        .iter()
+        .map(|entry| (&entry.node, &entry.output, entry.timestamp.to_string()))
+        .collect();
+    dict.set_item("lineage", lineage)
+        .wrap_err("could not make metadata a python dictionary item")
+        .unwrap();
+    if let Some(annotates) = &metadata.parameters.annotates {
+        dict.set_item("annotates", annotates.to_string())
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
+    if let Some(SequenceNumber { key, number }) = &metadata.parameters.sequence {
+        dict.set_item("sequence", (key, number))
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
+    if let Some(Device {
+        device_type,
+        device_id,
+    }) = &metadata.parameters.device
+    {
+        dict.set_item("device", (device_type, device_id))
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
+    if let Some(format) = &metadata.parameters.format {
+        dict.set_item("format", format.to_string())
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
+    if let Some(logical_time) = metadata.parameters.logical_time {
+        dict.set_item("logical_time", logical_time)
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
+    if !metadata.parameters.values.is_empty() {
+        let values = PyDict::new(py);
+        for (key, value) in &metadata.parameters.values {
+            values
+                .set_item(key, metadata_value_to_py(value, py))
+                .wrap_err("could not make metadata a python dictionary item")
+                .unwrap();
+        }
+        dict.set_item("values", values)
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
     dict
 }
 
+/// `DLDeviceType` code of host memory.
+const DLPACK_CPU: i32 = 1;
+
+/// Converts an object that implements the DLPack protocol (`__dlpack__`) to an arrow array.
+///
+/// Tensors that are not located in host memory are copied to the host first. In this
+/// case, the original device is returned too, so that it can be recorded in the metadata.
+/// Device memory is never shared with receivers directly (e.g. through CUDA IPC handles),
+/// since dora only tracks the lifetime of sent buffers in shared memory.
+///
+/// Returns `None` if the object does not implement the DLPack protocol.
+pub fn dlpack_to_arrow(data: &PyAny) -> Result<Option<(ArrayData, Option<Device>)>> {
+    if !data.hasattr("__dlpack__")? {
+        return Ok(None);
+    }
+    let (device_type, device_id): (i32, i32) = data
+        .call_method0("__dlpack_device__")
+        .and_then(|device| device.extract())
+        .context("failed to query DLPack device")?;
+
+    let (host_data, device) = if device_type == DLPACK_CPU {
+        (data, None)
+    } else {
+        // no framework independent way to copy to the host exists, so try the
+        // methods of the common tensor libraries (PyTorch and CuPy)
+        let host_data = if data.hasattr("cpu")? {
+            data.call_method0("cpu")
+        } else if data.hasattr("get")? {
+            data.call_method0("get")
+        } else {
+            eyre::bail!(
+                "cannot copy tensor from device `{device_type}:{device_id}` to host, \
+                expected a `cpu()` or `get()` method"
+            );
+        }
+        .context("failed to copy tensor to host")?;
+        let device = Device {
+            device_type,
+            device_id,
+        };
+        (host_data, Some(device))
+    };
+
+    let py = data.py();
+    let array = py
+        .import("numpy")?
+        .call_method1("from_dlpack", (host_data,))
+        .context("failed to import tensor through DLPack")?
+        .call_method1("reshape", (-1,))?;
+    let array = py.import("pyarrow")?.call_method1("array", (array,))?;
+    let array = ArrayData::from_pyarrow(array).context("failed to convert tensor to arrow")?;
+    Ok(Some((array, device)))
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
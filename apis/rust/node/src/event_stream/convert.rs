@@ -0,0 +1,388 @@
+//! Builtin conversions between common data encodings.
+//!
+//! Conversions are configured through the `convert` option of inputs and are
+//! applied before the input is delivered to the node.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        make_array, Array, ArrayData, ArrayRef, AsArray, Float16Array, Float32Array, Float64Array,
+        UInt8Array,
+    },
+    buffer::Buffer,
+    datatypes::{ArrowPrimitiveType, DataType, Float16Type, Float32Type, Float64Type, UInt8Type},
+};
+use dora_arrow_convert::ArrowData;
+use dora_core::{
+    config::{Conversion, DataId, NodeRunConfig, ValueType},
+    message::Metadata,
+};
+use eyre::{bail, Context};
+
+use super::Event;
+
+pub struct InputConversions {
+    conversions: BTreeMap<DataId, Conversion>,
+    /// Inputs whose last message couldn't be converted.
+    failing: BTreeSet<DataId>,
+}
+
+impl InputConversions {
+    pub fn new(run_config: &NodeRunConfig) -> Self {
+        let conversions = run_config
+            .inputs
+            .iter()
+            .filter_map(|(id, input)| Some((id.clone(), input.convert?)))
+            .collect();
+        Self {
+            conversions,
+            failing: BTreeSet::new(),
+        }
+    }
+
+    /// Applies the configured conversion if the given event is an input.
+    ///
+    /// Messages that can't be converted are dropped, so that a single malformed
+    /// message doesn't stop the node. A warning is logged when an input starts
+    /// failing.
+    pub fn apply(&mut self, event: Event) -> Option<Event> {
+        let Event::Input { id, metadata, data } = event else {
+            return Some(event);
+        };
+        match self.convert_input(&id, metadata, data) {
+            Ok((metadata, data)) => {
+                if self.failing.remove(&id) {
+                    tracing::info!("messages of input `{id}` are converted again");
+                }
+                Some(Event::Input { id, metadata, data })
+            }
+            Err(err) => {
+                if self.failing.insert(id.clone()) {
+                    tracing::warn!("dropping messages of input `{id}`: {err:?}");
+                } else {
+                    tracing::debug!("dropping message of input `{id}`: {err:?}");
+                }
+                None
+            }
+        }
+    }
+
+    fn convert_input(
+        &self,
+        id: &DataId,
+        mut metadata: Metadata,
+        ArrowData(mut data): ArrowData,
+    ) -> eyre::Result<(Metadata, ArrowData)> {
+        if let Some(conversion) = self.conversions.get(id) {
+            data = convert(*conversion, &data).wrap_err_with(|| {
+                format!("failed to apply `{conversion}` conversion to input `{id}`")
+            })?;
+            // the checksum only matches the received data
+            metadata.parameters.checksum = None;
+        }
+        Ok((metadata, ArrowData(data)))
+    }
+}
+
+/// Converts the given array according to the given conversion.
+pub fn convert(conversion: Conversion, data: &ArrayRef) -> eyre::Result<ArrayRef> {
+    match conversion {
+        Conversion::Bgr8ToRgb8 | Conversion::Rgb8ToBgr8 => swap_channels(bytes(data)?),
+        Conversion::Nv12ToRgb8 { width, height } => {
+            nv12_to_rgb8(bytes(data)?, width as usize, height as usize)
+        }
+        Conversion::F16ToF32 => f16_to_f32(data),
+        Conversion::F32ToF16 => f32_to_f16(data),
+        Conversion::QuaternionToEuler => map_chunks(data, quaternion_to_euler),
+        Conversion::EulerToQuaternion => map_chunks(data, euler_to_quaternion),
+        Conversion::ToBytes => to_bytes(data),
+        Conversion::FromBytes(value_type) => from_bytes(bytes(data)?, value_type),
+    }
+}
+
+fn bytes(data: &ArrayRef) -> eyre::Result<&UInt8Array> {
+    match data.as_primitive_opt::<UInt8Type>() {
+        Some(array) => Ok(array),
+        None => bail!("expected `UInt8` array, got `{}`", data.data_type()),
+    }
+}
+
+fn swap_channels(data: &UInt8Array) -> eyre::Result<ArrayRef> {
+    if data.len() % 3 != 0 {
+        bail!("expected a multiple of 3 bytes, got {}", data.len());
+    }
+    let mut pixels = data.values().to_vec();
+    for pixel in pixels.chunks_exact_mut(3) {
+        pixel.swap(0, 2);
+    }
+    Ok(Arc::new(UInt8Array::from(pixels)))
+}
+
+/// Converts NV12 to rgb8 using the BT.601 coefficients for limited range YUV.
+fn nv12_to_rgb8(data: &UInt8Array, width: usize, height: usize) -> eyre::Result<ArrayRef> {
+    let expected_len = width * height * 3 / 2;
+    if data.len() != expected_len {
+        bail!(
+            "expected {expected_len} bytes for a {width}x{height} NV12 image, got {}",
+            data.len()
+        );
+    }
+    let (y_plane, uv_plane) = data.values().split_at(width * height);
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for row in 0..height {
+        for column in 0..width {
+            let uv_index = (row / 2) * width + (column / 2) * 2;
+            let c = i32::from(y_plane[row * width + column]) - 16;
+            let d = i32::from(uv_plane[uv_index]) - 128;
+            let e = i32::from(uv_plane[uv_index + 1]) - 128;
+
+            let clamp = |value: i32| (value >> 8).clamp(0, 255) as u8;
+            rgb.push(clamp(298 * c + 409 * e + 128));
+            rgb.push(clamp(298 * c - 100 * d - 208 * e + 128));
+            rgb.push(clamp(298 * c + 516 * d + 128));
+        }
+    }
+    Ok(Arc::new(UInt8Array::from(rgb)))
+}
+
+/// Returns the values of a fixed-width array as bytes in native byte order.
+fn to_bytes(data: &ArrayRef) -> eyre::Result<ArrayRef> {
+    let data_type = data.data_type();
+    let Some(width) = data_type.primitive_width() else {
+        bail!("expected fixed-width array, got `{data_type}`");
+    };
+    if data.null_count() != 0 {
+        bail!("array has nulls");
+    }
+    let data = data.to_data();
+    let start = data.offset() * width;
+    let values = &data.buffers()[0].as_slice()[start..start + data.len() * width];
+    Ok(Arc::new(UInt8Array::from(values.to_vec())))
+}
+
+fn from_bytes(data: &UInt8Array, value_type: ValueType) -> eyre::Result<ArrayRef> {
+    let data_type = match value_type {
+        ValueType::UInt8 => DataType::UInt8,
+        ValueType::UInt16 => DataType::UInt16,
+        ValueType::UInt32 => DataType::UInt32,
+        ValueType::UInt64 => DataType::UInt64,
+        ValueType::Int8 => DataType::Int8,
+        ValueType::Int16 => DataType::Int16,
+        ValueType::Int32 => DataType::Int32,
+        ValueType::Int64 => DataType::Int64,
+        ValueType::Float32 => DataType::Float32,
+        ValueType::Float64 => DataType::Float64,
+    };
+    let width = data_type.primitive_width().unwrap_or(1);
+    if data.null_count() != 0 {
+        bail!("array has nulls");
+    }
+    if data.len() % width != 0 {
+        bail!(
+            "expected a multiple of {width} bytes for `{data_type}` values, got {}",
+            data.len()
+        );
+    }
+    // copy the bytes to a new buffer, which is properly aligned for the values
+    let values = Buffer::from_slice_ref(data.values());
+    let converted =
+        ArrayData::try_new(data_type, data.len() / width, None, 0, vec![values], vec![])
+            .wrap_err("failed to create array from bytes")?;
+    Ok(make_array(converted))
+}
+
+// `arrow::compute::cast` doesn't support half-precision floats, so the values
+// are converted one by one
+
+fn f16_to_f32(data: &ArrayRef) -> eyre::Result<ArrayRef> {
+    expect_type(data, &DataType::Float16)?;
+    let converted: Float32Array = data.as_primitive::<Float16Type>().unary(|v| v.to_f32());
+    Ok(Arc::new(converted))
+}
+
+fn f32_to_f16(data: &ArrayRef) -> eyre::Result<ArrayRef> {
+    expect_type(data, &DataType::Float32)?;
+    let converted: Float16Array = data
+        .as_primitive::<Float32Type>()
+        .unary(<Float16Type as ArrowPrimitiveType>::Native::from_f32);
+    Ok(Arc::new(converted))
+}
+
+fn expect_type(data: &ArrayRef, data_type: &DataType) -> eyre::Result<()> {
+    if data.data_type() != data_type {
+        bail!("expected `{data_type}` array, got `{}`", data.data_type());
+    }
+    Ok(())
+}
+
+/// Applies the given function to each chunk of `N` values of a float array.
+///
+/// The result has the same float type as the input.
+fn map_chunks<const N: usize, const M: usize>(
+    data: &ArrayRef,
+    f: fn([f64; N]) -> [f64; M],
+) -> eyre::Result<ArrayRef> {
+    let data_type = data.data_type();
+    if !matches!(data_type, DataType::Float32 | DataType::Float64) {
+        bail!("expected `Float32` or `Float64` array, got `{data_type}`");
+    }
+    let values = arrow::compute::cast(data, &DataType::Float64)?;
+    let values = values.as_primitive::<Float64Type>().values();
+    if values.len() % N != 0 {
+        bail!("expected a multiple of {N} values, got {}", values.len());
+    }
+    let converted: Vec<f64> = values
+        .chunks_exact(N)
+        .flat_map(|chunk| f(chunk.try_into().unwrap()))
+        .collect();
+    let converted: ArrayRef = Arc::new(Float64Array::from(converted));
+    arrow::compute::cast(&converted, data_type).wrap_err("failed to cast result")
+}
+
+/// Converts a `[x, y, z, w]` quaternion to `[roll, pitch, yaw]` angles in radians.
+fn quaternion_to_euler([x, y, z, w]: [f64; 4]) -> [f64; 3] {
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let sin_pitch = 2.0 * (w * y - z * x);
+    let pitch = if sin_pitch.abs() >= 1.0 {
+        std::f64::consts::FRAC_PI_2.copysign(sin_pitch)
+    } else {
+        sin_pitch.asin()
+    };
+    let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+    [roll, pitch, yaw]
+}
+
+/// Converts `[roll, pitch, yaw]` angles in radians to a `[x, y, z, w]` quaternion.
+fn euler_to_quaternion([roll, pitch, yaw]: [f64; 3]) -> [f64; 4] {
+    let (sr, cr) = (roll / 2.0).sin_cos();
+    let (sp, cp) = (pitch / 2.0).sin_cos();
+    let (sy, cy) = (yaw / 2.0).sin_cos();
+    [
+        sr * cp * cy - cr * sp * sy,
+        cr * sp * cy + sr * cp * sy,
+        cr * cp * sy - sr * sp * cy,
+        cr * cp * cy + sr * sp * sy,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float32Array;
+    use dora_core::message::{uhlc::HLC, ArrowTypeInfo, MetadataParameters};
+
+    #[test]
+    fn swap_bgr_channels() {
+        let data: ArrayRef = Arc::new(UInt8Array::from(vec![1, 2, 3, 4, 5, 6]));
+        let converted = convert(Conversion::Bgr8ToRgb8, &data).unwrap();
+        assert_eq!(
+            converted.as_primitive::<UInt8Type>().values().as_ref(),
+            &[3, 2, 1, 6, 5, 4]
+        );
+    }
+
+    #[test]
+    fn nv12_gray() {
+        // 2x2 image with mid gray luma and neutral chroma
+        let data: ArrayRef = Arc::new(UInt8Array::from(vec![126, 126, 126, 126, 128, 128]));
+        let conversion = Conversion::Nv12ToRgb8 {
+            width: 2,
+            height: 2,
+        };
+        let converted = convert(conversion, &data).unwrap();
+        assert_eq!(converted.len(), 12);
+        assert!(converted
+            .as_primitive::<UInt8Type>()
+            .values()
+            .iter()
+            .all(|&v| v == 128));
+    }
+
+    #[test]
+    fn float16_roundtrip() {
+        let data: ArrayRef = Arc::new(Float32Array::from(vec![0.5, -2.0, 1024.0]));
+        let half = convert(Conversion::F32ToF16, &data).unwrap();
+        assert_eq!(half.data_type(), &DataType::Float16);
+        let converted = convert(Conversion::F16ToF32, &half).unwrap();
+        assert_eq!(&converted, &data);
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let data: ArrayRef = Arc::new(Float32Array::from(vec![0.5, -2.0, 1024.0]));
+        let bytes = convert(Conversion::ToBytes, &data).unwrap();
+        assert_eq!(bytes.data_type(), &DataType::UInt8);
+        assert_eq!(bytes.len(), 12);
+        let converted = convert(Conversion::FromBytes(ValueType::Float32), &bytes).unwrap();
+        assert_eq!(&converted, &data);
+        assert!(convert(Conversion::FromBytes(ValueType::Int64), &bytes).is_err());
+
+        let sliced = data.slice(1, 2);
+        let bytes = convert(Conversion::ToBytes, &sliced).unwrap();
+        assert_eq!(
+            bytes.as_primitive::<UInt8Type>().values().as_ref(),
+            [(-2.0f32).to_ne_bytes(), 1024.0f32.to_ne_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn quaternion_roundtrip() {
+        let data: ArrayRef = Arc::new(Float64Array::from(vec![0.1, -0.4, 1.2]));
+        let quaternion = convert(Conversion::EulerToQuaternion, &data).unwrap();
+        assert_eq!(quaternion.len(), 4);
+        let euler = convert(Conversion::QuaternionToEuler, &quaternion).unwrap();
+        let euler = euler.as_primitive::<Float64Type>().values();
+        for (a, b) in euler.iter().zip([0.1, -0.4, 1.2]) {
+            assert!((a - b).abs() < 1e-9, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn drop_checksum_of_converted_inputs() {
+        let id = DataId::from("image".to_owned());
+        let input = |data: Vec<u8>| {
+            let data: ArrayRef = Arc::new(UInt8Array::from(data));
+            let parameters = MetadataParameters {
+                checksum: Some(42),
+                ..Default::default()
+            };
+            Event::Input {
+                id: id.clone(),
+                metadata: Metadata::from_parameters(
+                    HLC::default().new_timestamp(),
+                    ArrowTypeInfo::empty(),
+                    parameters,
+                ),
+                data: ArrowData(data),
+            }
+        };
+        let checksum = |event: Option<Event>| match event {
+            Some(Event::Input { metadata, .. }) => metadata.parameters.checksum,
+            other => panic!("unexpected event {other:?}"),
+        };
+
+        let mut unchanged = InputConversions {
+            conversions: BTreeMap::new(),
+            failing: BTreeSet::new(),
+        };
+        assert_eq!(checksum(unchanged.apply(input(vec![1, 2, 3]))), Some(42));
+
+        let mut converted = InputConversions {
+            conversions: [(id.clone(), Conversion::Bgr8ToRgb8)].into(),
+            failing: BTreeSet::new(),
+        };
+        assert_eq!(checksum(converted.apply(input(vec![1, 2, 3]))), None);
+
+        // malformed messages are dropped without affecting later messages
+        assert!(converted.apply(input(vec![1, 2])).is_none());
+        assert!(converted.failing.contains(&id));
+        assert_eq!(checksum(converted.apply(input(vec![1, 2, 3]))), None);
+        assert!(converted.failing.is_empty());
+        assert!(matches!(converted.apply(Event::Stop), Some(Event::Stop)));
+    }
+}
@@ -0,0 +1,235 @@
+//! Monitoring of the `deadline` option of inputs.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use dora_core::config::{DataId, NodeRunConfig};
+
+use super::Event;
+
+pub struct InputDeadlines {
+    inputs: BTreeMap<DataId, InputDeadline>,
+    clock: DeadlineClock,
+}
+
+/// Time base of the deadlines. All times are durations since its start.
+enum DeadlineClock {
+    Wall {
+        start: Instant,
+    },
+    /// Simulated time of the dataflow, `None` until the first update arrives.
+    Sim {
+        now: Option<Duration>,
+    },
+}
+
+struct InputDeadline {
+    deadline: Duration,
+    last_arrival: Duration,
+    next_check: Duration,
+    /// Whether the input missed its deadline since the last message arrived.
+    missed: bool,
+}
+
+impl InputDeadlines {
+    /// Creates the deadlines of the given inputs, based on simulated time if
+    /// `sim_time` is set.
+    pub fn new(run_config: &NodeRunConfig, sim_time: bool) -> Self {
+        let clock = if sim_time {
+            DeadlineClock::Sim { now: None }
+        } else {
+            DeadlineClock::Wall {
+                start: Instant::now(),
+            }
+        };
+        let inputs = run_config
+            .inputs
+            .iter()
+            .filter_map(|(id, input)| {
+                let deadline = input.deadline?;
+                Some((
+                    id.clone(),
+                    InputDeadline {
+                        deadline,
+                        last_arrival: Duration::ZERO,
+                        next_check: deadline,
+                        missed: false,
+                    },
+                ))
+            })
+            .collect();
+        Self { inputs, clock }
+    }
+
+    fn now(&self) -> Option<Duration> {
+        match self.clock {
+            DeadlineClock::Wall { start } => Some(start.elapsed()),
+            DeadlineClock::Sim { now } => now,
+        }
+    }
+
+    /// Advances the simulated time.
+    pub fn set_sim_time(&mut self, time: Duration) {
+        let DeadlineClock::Sim { now } = &mut self.clock else {
+            tracing::warn!("ignoring simulated time update, the dataflow uses wall-clock time");
+            return;
+        };
+        if now.is_none() {
+            // the deadlines start with the first update
+            for input in self.inputs.values_mut() {
+                input.last_arrival = time;
+                input.next_check = time + input.deadline;
+            }
+        }
+        *now = Some(time);
+    }
+
+    /// Updates the arrival times based on the given event that is about to be delivered.
+    pub fn update(&mut self, event: &Event) {
+        match event {
+            Event::Input { id, .. } => {
+                let Some(now) = self.now() else {
+                    return;
+                };
+                if let Some(input) = self.inputs.get_mut(id) {
+                    input.last_arrival = now;
+                    input.next_check = now + input.deadline;
+                    input.missed = false;
+                }
+            }
+            Event::InputClosed { id } => {
+                self.inputs.remove(id);
+            }
+            Event::Stop => self.inputs.clear(),
+            _ => {}
+        }
+    }
+
+    /// Returns the point in time at which the next deadline expires.
+    ///
+    /// Returns `None` for simulated time, which is only checked when it advances.
+    pub fn next_check(&self) -> Option<Instant> {
+        let DeadlineClock::Wall { start } = self.clock else {
+            return None;
+        };
+        let next_check = self.inputs.values().map(|i| i.next_check).min()?;
+        Some(start + next_check)
+    }
+
+    /// Returns a `DeadlineMissed` event if an input missed its deadline.
+    ///
+    /// The event is repeated every `deadline` interval until a new message arrives
+    /// on the input.
+    pub fn missed(&mut self) -> Option<Event> {
+        let now = self.now()?;
+        let (id, input) = self
+            .inputs
+            .iter_mut()
+            .filter(|(_, input)| input.next_check <= now)
+            .min_by_key(|(_, input)| input.next_check)?;
+        input.next_check = now + input.deadline;
+        let elapsed = now.saturating_sub(input.last_arrival);
+        if !input.missed {
+            tracing::warn!("input `{id}` missed its deadline ({elapsed:?} since last message)");
+            input.missed = true;
+        }
+        Some(Event::DeadlineMissed {
+            id: id.clone(),
+            elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, NullArray};
+    use dora_arrow_convert::ArrowData;
+    use dora_core::{
+        config::{Input, InputMapping, NodeId, NodeRunConfig, UserInputMapping},
+        message::{uhlc::HLC, ArrowTypeInfo, Metadata, MetadataParameters},
+    };
+    use std::sync::Arc;
+
+    fn deadlines(deadline: Duration) -> InputDeadlines {
+        let mut run_config = NodeRunConfig {
+            inputs: Default::default(),
+            outputs: Default::default(),
+            services: Default::default(),
+            encoding: Default::default(),
+            notify_unsubscribed: false,
+        };
+        run_config.inputs.insert(
+            DataId::from("image".to_owned()),
+            Input {
+                mapping: InputMapping::User(UserInputMapping {
+                    source: NodeId::from("camera".to_owned()),
+                    output: DataId::from("image".to_owned()),
+                }),
+                queue_size: None,
+                order_by_key: false,
+                order_timeout: None,
+                deadline: Some(deadline),
+                convert: None,
+                when: None,
+                batch: None,
+                format: None,
+                compact_by: None,
+                max_rate: None,
+                every_nth: None,
+                compression: None,
+                ttl: None,
+            },
+        );
+        InputDeadlines::new(&run_config, true)
+    }
+
+    fn input() -> Event {
+        let data: ArrayRef = Arc::new(NullArray::new(0));
+        Event::Input {
+            id: DataId::from("image".to_owned()),
+            metadata: Metadata::from_parameters(
+                HLC::default().new_timestamp(),
+                ArrowTypeInfo::empty(),
+                MetadataParameters::default(),
+            ),
+            data: ArrowData(data),
+        }
+    }
+
+    fn missed(deadlines: &mut InputDeadlines) -> Option<Duration> {
+        match deadlines.missed()? {
+            Event::DeadlineMissed { elapsed, .. } => Some(elapsed),
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn deadlines_on_sim_time() {
+        let mut deadlines = deadlines(Duration::from_secs(1));
+        assert_eq!(deadlines.next_check(), None);
+
+        // nothing is missed before the simulated time starts
+        deadlines.update(&input());
+        assert_eq!(missed(&mut deadlines), None);
+
+        // the deadlines start with the first update, not at zero
+        deadlines.set_sim_time(Duration::from_secs(100));
+        assert_eq!(missed(&mut deadlines), None);
+        deadlines.set_sim_time(Duration::from_millis(100_500));
+        assert_eq!(missed(&mut deadlines), None);
+        deadlines.set_sim_time(Duration::from_secs(101));
+        assert_eq!(missed(&mut deadlines), Some(Duration::from_secs(1)));
+        assert_eq!(missed(&mut deadlines), None);
+
+        // arrivals are measured in simulated time
+        deadlines.set_sim_time(Duration::from_millis(101_200));
+        deadlines.update(&input());
+        deadlines.set_sim_time(Duration::from_millis(102_000));
+        assert_eq!(missed(&mut deadlines), None);
+        deadlines.set_sim_time(Duration::from_millis(102_200));
+        assert_eq!(missed(&mut deadlines), Some(Duration::from_secs(1)));
+    }
+}
@@ -1,12 +1,16 @@
-use std::{ptr::NonNull, sync::Arc};
+use std::{collections::BTreeMap, ptr::NonNull, sync::Arc, time::Duration};
 
 use aligned_vec::{AVec, ConstAlign};
+use arrow::datatypes::DataType;
 use dora_arrow_convert::{ArrowData, IntoArrow};
 use dora_core::{
     config::{DataId, OperatorId},
-    message::{ArrowTypeInfo, BufferOffset, Metadata},
+    message::{ArrowTypeInfo, BufferOffset, Metadata, MetadataValue},
 };
 use eyre::{Context, Result};
+
+use super::latency::LatencyAlert;
+use crate::ServiceRequestId;
 use shared_memory_extended::{Shmem, ShmemConf};
 
 #[derive(Debug)]
@@ -24,6 +28,66 @@ pub enum Event {
     InputClosed {
         id: DataId,
     },
+    /// One message per input, aligned according to the `sync` policy of the operator.
+    ///
+    /// Only delivered to operators that configure a `sync` policy.
+    SyncedInput {
+        inputs: BTreeMap<DataId, (Metadata, ArrowData)>,
+    },
+    /// Consecutive messages of an input, coalesced according to its `batch` option.
+    ///
+    /// The data is an Arrow list array with one entry per message. Only delivered
+    /// to operators.
+    InputBatch {
+        id: DataId,
+        metadata: Vec<Metadata>,
+        data: ArrowData,
+    },
+    /// Another node called a service of this node.
+    ///
+    /// Answer it through [`DoraNode::send_service_reply`](crate::DoraNode::send_service_reply).
+    /// Operators receive the service ID without the operator prefix.
+    ServiceRequest {
+        service: DataId,
+        request: ServiceRequestId,
+        metadata: Metadata,
+        data: ArrowData,
+    },
+    /// No message arrived on the input within its configured `deadline`.
+    DeadlineMissed {
+        id: DataId,
+        /// Time since the last message arrived on the input.
+        elapsed: Duration,
+    },
+    /// A message took longer than the latency budget of a path that ends at
+    /// this node, see the `latency_budgets` of the dataflow.
+    ///
+    /// Delivered after the input that completed the path.
+    LatencyBudgetExceeded {
+        id: DataId,
+        alert: LatencyAlert,
+    },
+    /// A parameter that the operator declares was changed, e.g. through
+    /// `dora param set`.
+    ///
+    /// Only delivered to operators.
+    ParameterUpdate {
+        key: String,
+        value: MetadataValue,
+    },
+    /// All consumers of the output stopped, so messages sent on it are not
+    /// received by anyone.
+    ///
+    /// Only delivered if `notify_unsubscribed` is set, e.g. to pause expensive
+    /// processing until an `OutputSubscribed` event arrives. Consumers on other
+    /// machines are not tracked, so their outputs are always subscribed.
+    OutputUnsubscribed {
+        id: DataId,
+    },
+    /// A consumer of an output that was unsubscribed before is running again.
+    OutputSubscribed {
+        id: DataId,
+    },
     Error(String),
 }
 
@@ -51,15 +115,57 @@ impl RawData {
             }
         };
 
+        if is_legacy_byte_array(&raw_buffer, type_info) {
+            return buffer_into_arrow_array(
+                &raw_buffer,
+                &ArrowTypeInfo::byte_array(raw_buffer.len()),
+            );
+        }
         buffer_into_arrow_array(&raw_buffer, type_info)
     }
 }
 
+impl RawData {
+    /// Checks the data against the checksum that the sender stored in the metadata.
+    ///
+    /// Messages without checksum are accepted.
+    #[cfg(feature = "checksum")]
+    pub fn verify_checksum(&self, metadata: &Metadata) -> eyre::Result<()> {
+        let Some(expected) = metadata.parameters.checksum else {
+            return Ok(());
+        };
+        let data: &[u8] = match self {
+            RawData::Empty => &[],
+            RawData::Vec(data) => data,
+            RawData::SharedMemory(data) => &data.data,
+        };
+        let actual = crc32c::crc32c(data);
+        if actual != expected {
+            eyre::bail!(
+                "checksum mismatch: expected {expected:#010x}, got {actual:#010x} ({} bytes)",
+                data.len()
+            );
+        }
+        Ok(())
+    }
+}
+
 pub struct SharedMemoryData {
     pub data: MappedInputData,
     pub _drop: flume::Sender<()>,
 }
 
+/// Senders of the pre-Arrow API sent raw bytes without type information.
+///
+/// Such messages are wrapped as `UInt8` arrays, so that they can still be
+/// received by nodes that use Arrow.
+fn is_legacy_byte_array(raw_buffer: &arrow::buffer::Buffer, type_info: &ArrowTypeInfo) -> bool {
+    !raw_buffer.is_empty()
+        && type_info.data_type == DataType::Null
+        && type_info.buffer_offsets.is_empty()
+        && type_info.child_data.is_empty()
+}
+
 fn buffer_into_arrow_array(
     raw_buffer: &arrow::buffer::Buffer,
     type_info: &ArrowTypeInfo,
@@ -126,3 +232,45 @@ impl std::ops::Deref for MappedInputData {
 
 unsafe impl Send for MappedInputData {}
 unsafe impl Sync for MappedInputData {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_legacy_bytes() {
+        let data = RawData::Vec(AVec::from_slice(1, &[1, 2, 3]));
+        let array = data.into_arrow_array(&ArrowTypeInfo::empty()).unwrap();
+        assert_eq!(array.data_type(), &DataType::UInt8);
+        assert_eq!(array.len(), 3);
+
+        let array = RawData::Empty
+            .into_arrow_array(&ArrowTypeInfo::empty())
+            .unwrap();
+        assert_eq!(array.data_type(), &DataType::Null);
+    }
+
+    #[cfg(feature = "checksum")]
+    #[test]
+    fn detect_corrupted_data() {
+        use dora_core::message::{uhlc::HLC, MetadataParameters};
+
+        let metadata = |checksum| {
+            let parameters = MetadataParameters {
+                checksum,
+                ..Default::default()
+            };
+            Metadata::from_parameters(
+                HLC::default().new_timestamp(),
+                ArrowTypeInfo::empty(),
+                parameters,
+            )
+        };
+        let data = RawData::Vec(AVec::from_slice(1, b"123456789"));
+        assert!(data.verify_checksum(&metadata(Some(0xe306_9283))).is_ok());
+        assert!(data.verify_checksum(&metadata(None)).is_ok());
+        let err = data.verify_checksum(&metadata(Some(0))).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+        assert!(RawData::Empty.verify_checksum(&metadata(Some(0))).is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+//! Monitoring of the `latency_budgets` of the dataflow.
+
+use std::time::Duration;
+
+use dora_core::{
+    config::{LatencyPath, NodeId},
+    descriptor::Descriptor,
+    message::{lineage::LineageEntry, uhlc},
+};
+use serde::{Deserialize, Serialize};
+
+use super::Event;
+
+/// Latency of a path that exceeded its budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyAlert {
+    pub path: Vec<NodeId>,
+    pub budget: Duration,
+    /// Time from the message of the first node until the message of the last
+    /// hop was delivered.
+    pub latency: Duration,
+    /// Latency of each hop of the path, in path order.
+    pub hops: Vec<HopLatency>,
+}
+
+/// Time between the messages sent by two consecutive nodes of a path.
+///
+/// For the last hop, this is the time until the message was delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HopLatency {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub latency: Duration,
+}
+
+/// The latency budgets of the paths that end at this node.
+pub struct LatencyBudgets {
+    budgets: Vec<(LatencyPath, Duration)>,
+}
+
+impl LatencyBudgets {
+    pub fn new(node_id: &NodeId, descriptor: &Descriptor) -> Self {
+        let budgets = descriptor
+            .latency_budgets
+            .iter()
+            .filter(|(path, _)| path.0.last() == Some(node_id))
+            .map(|(path, budget)| (path.clone(), *budget))
+            .collect();
+        Self { budgets }
+    }
+
+    /// Returns a `LatencyBudgetExceeded` event for every budget that the given
+    /// input exceeds.
+    ///
+    /// The path of an input is derived from its lineage, so only inputs whose
+    /// lineage follows the full path are checked.
+    pub fn check(&self, event: &Event, now: uhlc::Timestamp) -> Vec<Event> {
+        let Event::Input { id, metadata, .. } = event else {
+            return Vec::new();
+        };
+        let lineage = metadata.lineage();
+        self.budgets
+            .iter()
+            .filter_map(|(path, budget)| {
+                let alert = path_latency(&path.0, lineage, now)?;
+                if alert.latency <= *budget {
+                    return None;
+                }
+                tracing::warn!(
+                    path = %path,
+                    budget = ?budget,
+                    latency = ?alert.latency,
+                    hops = ?alert.hops,
+                    "latency budget exceeded"
+                );
+                Some(Event::LatencyBudgetExceeded {
+                    id: id.clone(),
+                    alert: LatencyAlert {
+                        budget: *budget,
+                        ..alert
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// Computes the latency of the given path if the lineage follows it.
+fn path_latency(
+    path: &[NodeId],
+    lineage: &[LineageEntry],
+    now: uhlc::Timestamp,
+) -> Option<LatencyAlert> {
+    // lineage[0] was sent by the second to last node of the path, the
+    // following entries by the nodes before it
+    let senders = path.len() - 1;
+    let entries: Vec<&LineageEntry> = lineage.get(..senders)?.iter().rev().collect();
+    if entries
+        .iter()
+        .zip(path)
+        .any(|(entry, node)| entry.node != node.to_string())
+    {
+        return None;
+    }
+
+    let time = |timestamp: &uhlc::Timestamp| timestamp.get_time().to_duration();
+    let mut times: Vec<Duration> = entries.iter().map(|e| time(&e.timestamp)).collect();
+    times.push(time(&now));
+    let hops = path
+        .windows(2)
+        .zip(times.windows(2))
+        .map(|(nodes, times)| HopLatency {
+            from: nodes[0].clone(),
+            to: nodes[1].clone(),
+            latency: times[1].saturating_sub(times[0]),
+        })
+        .collect();
+    Some(LatencyAlert {
+        path: path.to_vec(),
+        budget: Duration::ZERO,
+        latency: times[times.len() - 1].saturating_sub(times[0]),
+        hops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::lineage::extend_lineage;
+
+    #[test]
+    fn per_hop_latency() {
+        let clock = uhlc::HLC::default();
+        let path: Vec<NodeId> = ["camera", "detector", "planner"]
+            .into_iter()
+            .map(|n| n.to_owned().into())
+            .collect();
+        let camera = extend_lineage("camera", "image", clock.new_timestamp(), &[]);
+        let detector = extend_lineage("detector", "bbox", clock.new_timestamp(), &camera);
+        let now = clock.new_timestamp();
+
+        let alert = path_latency(&path, &detector, now).unwrap();
+        assert_eq!(alert.hops.len(), 2);
+        assert_eq!(alert.hops[0].from, path[0]);
+        assert_eq!(alert.hops[1].to, path[2]);
+        let total: Duration = alert.hops.iter().map(|h| h.latency).sum();
+        assert_eq!(alert.latency, total);
+
+        // the lineage doesn't follow the path
+        assert!(path_latency(&path, &camera, now).is_none());
+        let other = extend_lineage("lidar", "points", clock.new_timestamp(), &[]);
+        let other = extend_lineage("detector", "bbox", clock.new_timestamp(), &other);
+        assert!(path_latency(&path, &other, now).is_none());
+    }
+}
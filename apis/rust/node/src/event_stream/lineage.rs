@@ -0,0 +1,28 @@
+//! Tracking of the lineage of the latest input, which is attached to the
+//! outputs of the node.
+
+use std::sync::{Arc, Mutex};
+
+use dora_core::message::lineage::LineageEntry;
+
+use super::Event;
+
+/// Lineage of the input that the node received last.
+///
+/// Shared between the event stream, which updates it, and the node, which
+/// attaches it to the outputs.
+#[derive(Debug, Clone, Default)]
+pub struct InputLineage(Arc<Mutex<Vec<LineageEntry>>>);
+
+impl InputLineage {
+    /// Updates the lineage based on the given event that is about to be delivered.
+    pub fn update(&self, event: &Event) {
+        if let Event::Input { metadata, .. } = event {
+            *self.0.lock().unwrap() = metadata.parameters.lineage.clone();
+        }
+    }
+
+    pub fn get(&self) -> Vec<LineageEntry> {
+        self.0.lock().unwrap().clone()
+    }
+}
@@ -1,44 +1,84 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 pub use event::{Event, MappedInputData, RawData};
 use futures::{
     future::{select, Either},
-    Stream, StreamExt,
+    FutureExt, Stream, StreamExt,
 };
 use futures_timer::Delay;
+pub use latency::{HopLatency, LatencyAlert};
+pub(crate) use lineage::InputLineage;
 
 use self::{
+    convert::InputConversions,
+    deadline::InputDeadlines,
     event::SharedMemoryData,
+    latency::LatencyBudgets,
+    ordering::InputOrdering,
     thread::{EventItem, EventStreamThreadHandle},
 };
-use crate::daemon_connection::DaemonChannel;
+use crate::{daemon_connection::DaemonChannel, ServiceRequestId};
+use aligned_vec::{AVec, ConstAlign};
 use dora_core::{
-    config::NodeId,
+    config::{DataId, NodeId, NodeRunConfig},
     daemon_messages::{
-        self, DaemonCommunication, DaemonRequest, DataflowId, NodeEvent, Timestamped,
+        self, DaemonCommunication, DaemonRequest, DataMessage, DataflowId, NodeEvent, Timestamped,
     },
-    message::uhlc,
+    descriptor::Descriptor,
+    message::{uhlc, Metadata},
 };
 use eyre::{eyre, Context};
 
+mod convert;
+mod deadline;
 mod event;
+mod latency;
+mod lineage;
 pub mod merged;
+mod ordering;
 mod thread;
 
+/// Number of local inputs that can be pending in the event stream.
+const LOCAL_INPUT_CAPACITY: usize = 64;
+
 pub struct EventStream {
     node_id: NodeId,
     receiver: flume::r#async::RecvStream<'static, EventItem>,
+    /// Inputs that don't come from the daemon, see [`LocalInputSender`].
+    local_receiver: flume::r#async::RecvStream<'static, EventItem>,
+    local_sender: flume::Sender<EventItem>,
+    conversions: InputConversions,
+    deadlines: InputDeadlines,
+    /// Wakes up the stream when the next input deadline expires.
+    deadline_timer: Option<Delay>,
+    ordering: InputOrdering,
+    /// Events that were released by the `ordering`, but not delivered yet.
+    ordered: VecDeque<EventItem>,
+    /// Wakes up the stream when the next held back input expires.
+    ordering_timer: Option<Delay>,
+    /// Lineage of the latest input, `None` if lineage tracking is disabled.
+    lineage: Option<InputLineage>,
+    latency_budgets: LatencyBudgets,
+    /// Alerts that are delivered after the input that exceeded the budget.
+    latency_alerts: VecDeque<Event>,
     _thread_handle: EventStreamThreadHandle,
     close_channel: DaemonChannel,
     clock: Arc<uhlc::HLC>,
 }
 
 impl EventStream {
-    #[tracing::instrument(level = "trace", skip(clock))]
+    #[tracing::instrument(level = "trace", skip(run_config, descriptor, clock))]
     pub(crate) fn init(
         dataflow_id: DataflowId,
         node_id: &NodeId,
         daemon_communication: &DaemonCommunication,
+        run_config: &NodeRunConfig,
+        descriptor: &Descriptor,
         clock: Arc<uhlc::HLC>,
     ) -> eyre::Result<Self> {
         let channel = match daemon_communication {
@@ -65,7 +105,15 @@ impl EventStream {
                 })?,
         };
 
-        Self::init_on_channel(dataflow_id, node_id, channel, close_channel, clock)
+        Self::init_on_channel(
+            dataflow_id,
+            node_id,
+            channel,
+            close_channel,
+            run_config,
+            descriptor,
+            clock,
+        )
     }
 
     pub(crate) fn init_on_channel(
@@ -73,6 +121,8 @@ impl EventStream {
         node_id: &NodeId,
         mut channel: DaemonChannel,
         mut close_channel: DaemonChannel,
+        run_config: &NodeRunConfig,
+        descriptor: &Descriptor,
         clock: Arc<uhlc::HLC>,
     ) -> eyre::Result<Self> {
         channel.register(dataflow_id, node_id.clone(), clock.new_timestamp())?;
@@ -97,9 +147,22 @@ impl EventStream {
         let (tx, rx) = flume::bounded(0);
         let thread_handle = thread::init(node_id.clone(), tx, channel, clock.clone())?;
 
+        let (local_sender, local_receiver) = flume::bounded(LOCAL_INPUT_CAPACITY);
+
         Ok(EventStream {
             node_id: node_id.clone(),
             receiver: rx.into_stream(),
+            local_receiver: local_receiver.into_stream(),
+            local_sender,
+            conversions: InputConversions::new(run_config),
+            deadlines: InputDeadlines::new(run_config, descriptor.clock.is_some()),
+            deadline_timer: None,
+            ordering: InputOrdering::new(run_config),
+            ordered: VecDeque::new(),
+            ordering_timer: None,
+            lineage: descriptor.track_lineage().then(InputLineage::default),
+            latency_budgets: LatencyBudgets::new(node_id, descriptor),
+            latency_alerts: VecDeque::new(),
             _thread_handle: thread_handle,
             close_channel,
             clock,
@@ -117,17 +180,81 @@ impl EventStream {
     }
 
     pub async fn recv_async(&mut self) -> Option<Event> {
-        self.receiver.next().await.map(Self::convert_event_item)
+        self.next().await
     }
 
     pub async fn recv_async_timeout(&mut self, dur: Duration) -> Option<Event> {
-        let next_event = match select(Delay::new(dur), self.receiver.next()).await {
-            Either::Left((_elapsed, _)) => {
-                Some(EventItem::TimeoutError(eyre!("Receiver timed out")))
-            }
+        match select(Delay::new(dur), self.next()).await {
+            Either::Left((_elapsed, _)) => Some(Self::convert_event_item(EventItem::TimeoutError(
+                eyre!("Receiver timed out"),
+            ))),
             Either::Right((event, _)) => event,
-        };
-        next_event.map(Self::convert_event_item)
+        }
+    }
+
+    /// Returns a sender for inputs that the node produces itself, e.g. outputs
+    /// of operators that are connected to other operators of the same runtime.
+    ///
+    /// Local inputs go through the same conversions, checks, and deadlines as
+    /// the inputs that the daemon sends.
+    pub fn local_input_sender(&self) -> LocalInputSender {
+        LocalInputSender {
+            tx: self.local_sender.clone(),
+        }
+    }
+
+    /// Lineage of the input that was received last, which the node attaches
+    /// to its outputs.
+    ///
+    /// Returns `None` if the dataflow doesn't track lineage.
+    pub(crate) fn input_lineage(&self) -> Option<InputLineage> {
+        self.lineage.clone()
+    }
+
+    /// Polls the next event item, restoring the order of `order_by_key` inputs.
+    fn poll_ordered(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<EventItem>> {
+        loop {
+            if let Some(item) = self.ordered.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            let now = Instant::now();
+            let expired = self.ordering.expire(now);
+            if !expired.is_empty() {
+                self.ordered.extend(expired);
+                continue;
+            }
+            // local inputs are sent before the node closes the corresponding outputs,
+            // so they are delivered before the `InputClosed` events of the daemon
+            let item = match self.local_receiver.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => Poll::Ready(Some(item)),
+                Poll::Ready(None) | Poll::Pending => self.receiver.poll_next_unpin(cx),
+            };
+            match item {
+                Poll::Ready(Some(item)) => {
+                    let ready = self.ordering.push(item, now);
+                    self.ordered.extend(ready);
+                }
+                Poll::Ready(None) => {
+                    // deliver held back inputs before closing the stream
+                    let ready = self.ordering.flush();
+                    if ready.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    self.ordered.extend(ready);
+                }
+                Poll::Pending => {
+                    if let Some(next_expiry) = self.ordering.next_expiry() {
+                        let mut timer =
+                            Delay::new(next_expiry.saturating_duration_since(Instant::now()));
+                        if timer.poll_unpin(cx).is_ready() {
+                            cx.waker().wake_by_ref();
+                        }
+                        self.ordering_timer = Some(timer);
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
     }
 
     fn convert_event_item(item: EventItem) -> Event {
@@ -136,6 +263,9 @@ impl EventStream {
                 NodeEvent::Stop => Event::Stop,
                 NodeEvent::Reload { operator_id } => Event::Reload { operator_id },
                 NodeEvent::InputClosed { id } => Event::InputClosed { id },
+                NodeEvent::ParameterUpdate { key, value } => Event::ParameterUpdate { key, value },
+                NodeEvent::OutputUnsubscribed { id } => Event::OutputUnsubscribed { id },
+                NodeEvent::OutputSubscribed { id } => Event::OutputSubscribed { id },
                 NodeEvent::Input { id, metadata, data } => {
                     let data = match data {
                         None => Ok(None),
@@ -155,6 +285,10 @@ impl EventStream {
                     };
                     let data = data.and_then(|data| {
                         let raw_data = data.unwrap_or(RawData::Empty);
+                        #[cfg(feature = "checksum")]
+                        raw_data
+                            .verify_checksum(&metadata)
+                            .wrap_err_with(|| format!("received corrupted data on input `{id}`"))?;
                         raw_data
                             .into_arrow_array(&metadata.type_info)
                             .map(arrow::array::make_array)
@@ -168,6 +302,34 @@ impl EventStream {
                         Err(err) => Event::Error(format!("{err:?}")),
                     }
                 }
+                NodeEvent::ServiceRequest {
+                    service,
+                    caller,
+                    request_id,
+                    metadata,
+                    data,
+                } => {
+                    let data = match data {
+                        None => Ok(RawData::Empty),
+                        Some(daemon_messages::DataMessage::Vec(v)) => Ok(RawData::Vec(v)),
+                        Some(daemon_messages::DataMessage::SharedMemory { .. }) => {
+                            Err(eyre!("service requests must not use shared memory"))
+                        }
+                    };
+                    let data = data.and_then(|data| {
+                        data.into_arrow_array(&metadata.type_info)
+                            .map(arrow::array::make_array)
+                    });
+                    match data {
+                        Ok(data) => Event::ServiceRequest {
+                            service,
+                            request: ServiceRequestId { caller, request_id },
+                            metadata,
+                            data: data.into(),
+                        },
+                        Err(err) => Event::Error(format!("{err:?}")),
+                    }
+                }
                 NodeEvent::AllInputsClosed => {
                     let err = eyre!(
                         "received `AllInputsClosed` event, which should be handled by background task"
@@ -175,6 +337,12 @@ impl EventStream {
                     tracing::error!("{err:?}");
                     Event::Error(err.wrap_err("internal error").to_string())
                 }
+                NodeEvent::SimTime { .. } => {
+                    let err =
+                        eyre!("received `SimTime` event, which should be handled in `poll_next`");
+                    tracing::error!("{err:?}");
+                    Event::Error(err.wrap_err("internal error").to_string())
+                }
             },
 
             EventItem::FatalError(err) => {
@@ -194,9 +362,94 @@ impl Stream for EventStream {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.receiver
-            .poll_next_unpin(cx)
-            .map(|item| item.map(Self::convert_event_item))
+        if let Some(event) = self.latency_alerts.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if let Some(event) = self.deadlines.missed() {
+            return Poll::Ready(Some(event));
+        }
+        let item = self.poll_ordered(cx);
+        match item {
+            Poll::Ready(Some(EventItem::NodeEvent {
+                event: NodeEvent::SimTime { time },
+                ..
+            })) => {
+                self.deadlines.set_sim_time(time);
+                // check the deadlines and poll the receiver again
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Poll::Ready(item) => {
+                let event = match item.map(Self::convert_event_item) {
+                    Some(event) => match self.conversions.apply(event) {
+                        Some(event) => Some(event),
+                        None => {
+                            // the input was dropped, poll the receiver again
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                    },
+                    None => None,
+                };
+                if let Some(event) = &event {
+                    self.deadlines.update(event);
+                    if let Some(lineage) = &self.lineage {
+                        lineage.update(event);
+                    }
+                    let alerts = self
+                        .latency_budgets
+                        .check(event, self.clock.new_timestamp());
+                    self.latency_alerts.extend(alerts);
+                }
+                Poll::Ready(event)
+            }
+            Poll::Pending => {
+                if let Some(next_check) = self.deadlines.next_check() {
+                    let mut timer =
+                        Delay::new(next_check.saturating_duration_since(Instant::now()));
+                    if timer.poll_unpin(cx).is_ready() {
+                        cx.waker().wake_by_ref();
+                    }
+                    self.deadline_timer = Some(timer);
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Sends inputs to an [`EventStream`] without going through the daemon.
+#[derive(Debug, Clone)]
+pub struct LocalInputSender {
+    tx: flume::Sender<EventItem>,
+}
+
+impl LocalInputSender {
+    /// Queues the given input without blocking.
+    ///
+    /// Fails if the event stream was closed or if too many local inputs are
+    /// pending already, e.g. because the node doesn't keep up with an output
+    /// that is connected to its own input.
+    pub fn try_send(
+        &self,
+        id: DataId,
+        metadata: Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+    ) -> eyre::Result<()> {
+        // local inputs don't use shared memory, so there is nothing to acknowledge
+        let (ack_channel, _) = flume::bounded(0);
+        let item = EventItem::NodeEvent {
+            event: NodeEvent::Input {
+                id,
+                metadata,
+                data: data.map(DataMessage::Vec),
+            },
+            ack_channel,
+        };
+        self.tx.try_send(item).map_err(|err| match err {
+            flume::TrySendError::Full(_) => eyre!("too many pending local inputs"),
+            flume::TrySendError::Disconnected(_) => eyre!("event stream was closed"),
+        })
     }
 }
 
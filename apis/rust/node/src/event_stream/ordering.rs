@@ -0,0 +1,469 @@
+//! Restores the logical order of inputs that are produced by parallel workers.
+//!
+//! Inputs with the `order_by_key` option are not delivered in arrival order.
+//! Instead, they are held back until all messages with a lower sequence number
+//! of the same key were delivered, or until their `order_timeout` expires.
+//! The sequence of a key starts at the first number that is received for it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+
+use dora_core::{
+    config::{DataId, NodeRunConfig, DEFAULT_ORDER_TIMEOUT, DEFAULT_QUEUE_SIZE},
+    daemon_messages::NodeEvent,
+};
+
+use super::thread::EventItem;
+
+pub struct InputOrdering {
+    ordered_inputs: BTreeMap<DataId, OrderedInput>,
+    /// Ordered inputs that were not closed yet.
+    open_inputs: BTreeSet<DataId>,
+    /// `InputClosed` events of ordered inputs that still have held back messages.
+    closed: Vec<(DataId, EventItem)>,
+    keys: BTreeMap<String, KeyState>,
+}
+
+struct OrderedInput {
+    /// Maximum number of held back messages per key.
+    max_held: usize,
+    timeout: Duration,
+}
+
+struct KeyState {
+    /// Starts at the first sequence number seen for the key.
+    next_number: u64,
+    held: BTreeMap<u64, HeldEvent>,
+}
+
+struct HeldEvent {
+    id: DataId,
+    /// Time at which the event is delivered even if messages are still missing.
+    release_at: Instant,
+    item: EventItem,
+}
+
+impl InputOrdering {
+    pub fn new(run_config: &NodeRunConfig) -> Self {
+        let ordered_inputs: BTreeMap<_, _> = run_config
+            .inputs
+            .iter()
+            .filter(|(_, input)| input.order_by_key)
+            .map(|(id, input)| {
+                let ordered = OrderedInput {
+                    max_held: input.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE).max(1),
+                    timeout: input.order_timeout.unwrap_or(DEFAULT_ORDER_TIMEOUT),
+                };
+                (id.clone(), ordered)
+            })
+            .collect();
+        Self {
+            open_inputs: ordered_inputs.keys().cloned().collect(),
+            ordered_inputs,
+            closed: Vec::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the given event and returns all events that are ready for delivery.
+    pub fn push(&mut self, item: EventItem, now: Instant) -> Vec<EventItem> {
+        let EventItem::NodeEvent { event, .. } = &item else {
+            return vec![item];
+        };
+        let (id, sequence) = match event {
+            NodeEvent::Input { id, metadata, .. } => (id, metadata.parameters.sequence.as_ref()),
+            NodeEvent::InputClosed { id } if self.ordered_inputs.contains_key(id) => {
+                let id = id.clone();
+                self.open_inputs.remove(&id);
+                if self.open_inputs.is_empty() {
+                    // no missing message can arrive anymore
+                    let mut ready = self.flush();
+                    ready.push(item);
+                    return ready;
+                }
+                // the missing messages might still arrive on the other inputs, so
+                // the input is only closed after its held back messages are delivered
+                self.closed.push((id, item));
+                let mut ready = Vec::new();
+                self.release_closed(&mut ready);
+                return ready;
+            }
+            _ => return vec![item],
+        };
+        let (Some(input), Some(sequence)) = (self.ordered_inputs.get(id), sequence) else {
+            return vec![item];
+        };
+        let number = sequence.number;
+        let state = self
+            .keys
+            .entry(sequence.key.clone())
+            .or_insert_with(|| KeyState::starting_at(number));
+
+        if number < state.next_number || state.held.contains_key(&number) {
+            tracing::debug!(
+                "delivering input `{id}` with outdated or duplicate sequence number {number}"
+            );
+            return vec![item];
+        }
+        let held = HeldEvent {
+            id: id.clone(),
+            release_at: now + input.timeout,
+            item,
+        };
+        let max_held = input.max_held;
+        state.held.insert(number, held);
+
+        let mut ready = Vec::new();
+        while state.held.len() > max_held {
+            // give up waiting for the missing messages
+            if let Some(&number) = state.held.keys().next() {
+                state.release_until(number, &mut ready);
+            }
+        }
+        state.release_consecutive(&mut ready);
+        self.release_closed(&mut ready);
+        ready
+    }
+
+    /// Returns the held back events whose `order_timeout` expired, together
+    /// with the events that follow them.
+    pub fn expire(&mut self, now: Instant) -> Vec<EventItem> {
+        let mut ready = Vec::new();
+        for state in self.keys.values_mut() {
+            let expired = state
+                .held
+                .iter()
+                .filter(|(_, held)| held.release_at <= now)
+                .map(|(&number, _)| number)
+                .max();
+            if let Some(number) = expired {
+                state.release_until(number, &mut ready);
+                state.release_consecutive(&mut ready);
+            }
+        }
+        self.release_closed(&mut ready);
+        ready
+    }
+
+    /// Returns the point in time at which the next held back event expires.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.keys
+            .values()
+            .flat_map(|state| state.held.values())
+            .map(|held| held.release_at)
+            .min()
+    }
+
+    /// Returns all held back events, ordered by key and sequence number.
+    pub fn flush(&mut self) -> Vec<EventItem> {
+        let mut ready = Vec::new();
+        for state in self.keys.values_mut() {
+            if let Some(&last) = state.held.keys().next_back() {
+                state.release_until(last, &mut ready);
+            }
+        }
+        ready.extend(self.closed.drain(..).map(|(_, item)| item));
+        ready
+    }
+
+    /// Delivers the `InputClosed` events of inputs without held back messages.
+    fn release_closed(&mut self, ready: &mut Vec<EventItem>) {
+        if self.closed.is_empty() {
+            return;
+        }
+        let held_inputs: BTreeSet<_> = self
+            .keys
+            .values()
+            .flat_map(|state| state.held.values())
+            .map(|held| &held.id)
+            .collect();
+        let (pending, closed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.closed)
+            .into_iter()
+            .partition(|(id, _)| held_inputs.contains(id));
+        self.closed = pending;
+        ready.extend(closed.into_iter().map(|(_, item)| item));
+    }
+}
+
+impl KeyState {
+    fn starting_at(number: u64) -> Self {
+        Self {
+            next_number: number,
+            held: BTreeMap::new(),
+        }
+    }
+
+    /// Releases all held back events up to the given sequence number, skipping
+    /// the missing ones.
+    fn release_until(&mut self, number: u64, ready: &mut Vec<EventItem>) {
+        let later = match number.checked_add(1) {
+            Some(end) => self.held.split_off(&end),
+            None => BTreeMap::new(),
+        };
+        let released = std::mem::replace(&mut self.held, later);
+        if let Some((&first, held)) = released.first_key_value() {
+            if first > self.next_number {
+                tracing::debug!(
+                    "skipping sequence numbers {}..{first} of input `{}`",
+                    self.next_number,
+                    held.id
+                );
+            }
+        }
+        ready.extend(released.into_values().map(|held| held.item));
+        self.next_number = self.next_number.max(number.saturating_add(1));
+    }
+
+    fn release_consecutive(&mut self, ready: &mut Vec<EventItem>) {
+        while let Some(held) = self.held.remove(&self.next_number) {
+            self.next_number = self.next_number.saturating_add(1);
+            ready.push(held.item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::{
+        config::{Input, InputMapping, NodeId, UserInputMapping},
+        message::{uhlc, ArrowTypeInfo, Metadata, MetadataParameters, SequenceNumber},
+    };
+
+    fn input(id: &str, key: &str, number: u64) -> EventItem {
+        let parameters = MetadataParameters {
+            sequence: Some(SequenceNumber {
+                key: key.to_owned(),
+                number,
+            }),
+            ..Default::default()
+        };
+        let event = NodeEvent::Input {
+            id: DataId::from(id.to_owned()),
+            metadata: Metadata::from_parameters(
+                uhlc::HLC::default().new_timestamp(),
+                ArrowTypeInfo::empty(),
+                parameters,
+            ),
+            data: None,
+        };
+        item(event)
+    }
+
+    fn closed(id: &str) -> EventItem {
+        item(NodeEvent::InputClosed {
+            id: DataId::from(id.to_owned()),
+        })
+    }
+
+    fn item(event: NodeEvent) -> EventItem {
+        EventItem::NodeEvent {
+            event,
+            ack_channel: flume::bounded(0).0,
+        }
+    }
+
+    fn numbers(events: Vec<EventItem>) -> Vec<u64> {
+        events
+            .into_iter()
+            .map(|item| match item {
+                EventItem::NodeEvent {
+                    event: NodeEvent::Input { metadata, .. },
+                    ..
+                } => metadata.parameters.sequence.unwrap().number,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+
+    fn describe(events: Vec<EventItem>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|item| match item {
+                EventItem::NodeEvent {
+                    event: NodeEvent::Input { id, metadata, .. },
+                    ..
+                } => format!("{id}:{}", metadata.parameters.sequence.unwrap().number),
+                EventItem::NodeEvent {
+                    event: NodeEvent::InputClosed { id },
+                    ..
+                } => format!("{id} closed"),
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+
+    fn ordering(queue_size: usize) -> InputOrdering {
+        let mut run_config = NodeRunConfig {
+            inputs: Default::default(),
+            outputs: Default::default(),
+            services: Default::default(),
+            encoding: Default::default(),
+            notify_unsubscribed: false,
+        };
+        for (id, source) in [("a", "worker_a"), ("b", "worker_b")] {
+            run_config.inputs.insert(
+                DataId::from(id.to_owned()),
+                Input {
+                    mapping: InputMapping::User(UserInputMapping {
+                        source: NodeId::from(source.to_owned()),
+                        output: DataId::from("out".to_owned()),
+                    }),
+                    queue_size: Some(queue_size),
+                    order_by_key: true,
+                    order_timeout: Some(Duration::from_secs(1)),
+                    deadline: None,
+                    convert: None,
+                    when: None,
+                    batch: None,
+                    format: None,
+                    compact_by: None,
+                    max_rate: None,
+                    every_nth: None,
+                    compression: None,
+                    ttl: None,
+                },
+            );
+        }
+        InputOrdering::new(&run_config)
+    }
+
+    #[test]
+    fn reorder_across_inputs() {
+        let mut ordering = ordering(10);
+        let now = Instant::now();
+        assert_eq!(numbers(ordering.push(input("a", "frame", 0), now)), vec![0]);
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 2), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(numbers(ordering.push(input("b", "other", 0), now)), vec![0]);
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 4), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 1), now)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 3), now)),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn skip_missing_when_full() {
+        let mut ordering = ordering(2);
+        let now = Instant::now();
+        assert_eq!(numbers(ordering.push(input("b", "frame", 0), now)), vec![0]);
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 2), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 3), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 5), now)),
+            vec![2, 3]
+        );
+        // late message is delivered immediately
+        assert_eq!(numbers(ordering.push(input("b", "frame", 1), now)), vec![1]);
+        assert_eq!(numbers(ordering.flush()), vec![5]);
+    }
+
+    #[test]
+    fn release_held_messages_after_timeout() {
+        let mut ordering = ordering(10);
+        let now = Instant::now();
+        assert_eq!(numbers(ordering.push(input("b", "frame", 0), now)), vec![0]);
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 2), now)),
+            Vec::<u64>::new()
+        );
+        let later = now + Duration::from_millis(500);
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 4), later)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(ordering.next_expiry(), Some(now + Duration::from_secs(1)));
+        assert_eq!(numbers(ordering.expire(later)), Vec::<u64>::new());
+
+        // message 3 is still missing, but 4 follows the expired message 2
+        assert_eq!(
+            numbers(ordering.expire(now + Duration::from_secs(1))),
+            vec![2]
+        );
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 3), later)),
+            vec![3, 4]
+        );
+        assert_eq!(ordering.next_expiry(), None);
+    }
+
+    #[test]
+    fn close_input_after_its_held_messages() {
+        let mut ordering = ordering(10);
+        let now = Instant::now();
+        assert_eq!(
+            describe(ordering.push(input("b", "frame", 0), now)),
+            ["b:0"]
+        );
+        assert!(ordering.push(input("a", "frame", 2), now).is_empty());
+        assert!(ordering.push(input("b", "frame", 3), now).is_empty());
+
+        // the missing message might still arrive on input `b`
+        assert!(ordering.push(closed("a"), now).is_empty());
+        assert_eq!(
+            describe(ordering.push(input("b", "frame", 1), now)),
+            ["b:1", "a:2", "b:3", "a closed"]
+        );
+
+        assert!(ordering.push(input("b", "frame", 5), now).is_empty());
+        assert_eq!(
+            describe(ordering.push(closed("b"), now)),
+            ["b:5", "b closed"]
+        );
+    }
+
+    #[test]
+    fn start_at_first_sequence_number_of_key() {
+        let mut ordering = ordering(10);
+        let now = Instant::now();
+        assert_eq!(numbers(ordering.push(input("a", "frame", 1), now)), vec![1]);
+        assert_eq!(numbers(ordering.push(input("b", "frame", 2), now)), vec![2]);
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", 4), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", 3), now)),
+            vec![3, 4]
+        );
+        assert_eq!(ordering.next_expiry(), None);
+    }
+
+    #[test]
+    fn release_last_sequence_number() {
+        let mut ordering = ordering(10);
+        let now = Instant::now();
+        let last = u64::MAX;
+        assert_eq!(
+            numbers(ordering.push(input("a", "frame", last - 2), now)),
+            vec![last - 2]
+        );
+        assert_eq!(
+            numbers(ordering.push(input("b", "frame", last), now)),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            numbers(ordering.expire(now + Duration::from_secs(1))),
+            vec![last]
+        );
+        assert_eq!(ordering.next_expiry(), None);
+    }
+}
@@ -16,11 +16,23 @@
 pub use arrow;
 pub use dora_arrow_convert::*;
 pub use dora_core;
-pub use dora_core::message::{uhlc, Metadata, MetadataParameters};
-pub use event_stream::{merged, Event, EventStream, MappedInputData, RawData};
+pub use dora_core::message::{
+    annotation::{AnnotatedMessage, Annotation, AnnotationMerger},
+    uhlc, Device, Metadata, MetadataParameters, MetadataValue, SequenceNumber,
+};
+pub use event_stream::{
+    merged, Event, EventStream, HopLatency, LatencyAlert, LocalInputSender, MappedInputData,
+    RawData,
+};
 pub use flume::Receiver;
-pub use node::{arrow_utils, DataSample, DoraNode, ZERO_COPY_THRESHOLD};
+pub use node::{
+    arrow_utils,
+    service::{ServiceCall, ServiceRequestId, DEFAULT_SERVICE_TIMEOUT},
+    DataSample, DoraNode, PendingOutput, ZERO_COPY_THRESHOLD,
+};
+pub use observer::Observer;
 
 mod daemon_connection;
 mod event_stream;
 mod node;
+mod observer;
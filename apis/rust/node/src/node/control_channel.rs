@@ -1,9 +1,12 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use super::service::ServiceRequestId;
 use crate::daemon_connection::DaemonChannel;
 use dora_core::{
     config::{DataId, NodeId},
-    daemon_messages::{DaemonCommunication, DaemonRequest, DataMessage, DataflowId, Timestamped},
+    daemon_messages::{
+        DaemonCommunication, DaemonRequest, DataMessage, DataflowId, ServiceResponse, Timestamped,
+    },
     message::{uhlc::HLC, Metadata},
 };
 use eyre::{bail, eyre, Context};
@@ -63,6 +66,23 @@ impl ControlChannel {
         Ok(())
     }
 
+    pub fn report_ready(&mut self) -> eyre::Result<()> {
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: DaemonRequest::Ready,
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to report readiness to dora-daemon")?;
+        match reply {
+            dora_core::daemon_messages::DaemonReply::Result(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err("failed to report readiness to dora-daemon")?,
+            other => bail!("unexpected ready reply: {other:?}"),
+        }
+        Ok(())
+    }
+
     pub fn report_closed_outputs(&mut self, outputs: Vec<DataId>) -> eyre::Result<()> {
         let reply = self
             .channel
@@ -80,6 +100,36 @@ impl ControlChannel {
         Ok(())
     }
 
+    pub fn reserve_shared_memory(&mut self, len: usize) -> eyre::Result<()> {
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: DaemonRequest::ReserveSharedMemory { len: len as u64 },
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to send ReserveSharedMemory request to dora-daemon")?;
+        match reply {
+            dora_core::daemon_messages::DaemonReply::Result(result) => result.map_err(|e| eyre!(e)),
+            other => bail!("unexpected ReserveSharedMemory reply: {other:?}"),
+        }
+    }
+
+    pub fn release_shared_memory(&mut self, len: usize) -> eyre::Result<()> {
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: DaemonRequest::ReleaseSharedMemory { len: len as u64 },
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to send ReleaseSharedMemory request to dora-daemon")?;
+        match reply {
+            dora_core::daemon_messages::DaemonReply::Result(result) => result
+                .map_err(|e| eyre!(e))
+                .wrap_err("failed to release shared memory"),
+            other => bail!("unexpected ReleaseSharedMemory reply: {other:?}"),
+        }
+    }
+
     pub fn send_message(
         &mut self,
         output_id: DataId,
@@ -103,4 +153,57 @@ impl ControlChannel {
             other => bail!("unexpected SendMessage reply: {other:?}"),
         }
     }
+
+    pub fn call_service(
+        &mut self,
+        node_id: NodeId,
+        service: DataId,
+        request_id: u64,
+        timeout: Duration,
+        metadata: Metadata,
+        data: Option<DataMessage>,
+    ) -> eyre::Result<()> {
+        let request = DaemonRequest::CallService {
+            node_id,
+            service,
+            request_id,
+            timeout,
+            metadata,
+            data,
+        };
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: request,
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to send CallService request to dora-daemon")?;
+        match reply {
+            dora_core::daemon_messages::DaemonReply::Result(result) => result.map_err(|e| eyre!(e)),
+            other => bail!("unexpected CallService reply: {other:?}"),
+        }
+    }
+
+    pub fn send_service_reply(
+        &mut self,
+        request: ServiceRequestId,
+        result: Result<ServiceResponse, String>,
+    ) -> eyre::Result<()> {
+        let request = DaemonRequest::ServiceReply {
+            caller: request.caller,
+            request_id: request.request_id,
+            result,
+        };
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: request,
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to send ServiceReply request to dora-daemon")?;
+        match reply {
+            dora_core::daemon_messages::DaemonReply::Empty => Ok(()),
+            other => bail!("unexpected ServiceReply reply: {other:?}"),
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use std::{sync::Arc, time::Duration};
 
+use super::service::PendingCalls;
 use crate::daemon_connection::DaemonChannel;
 use dora_core::{
     config::NodeId,
@@ -23,6 +24,7 @@ impl DropStream {
         dataflow_id: DataflowId,
         node_id: &NodeId,
         daemon_communication: &DaemonCommunication,
+        pending_calls: PendingCalls,
         hlc: Arc<uhlc::HLC>,
     ) -> eyre::Result<Self> {
         let channel = match daemon_communication {
@@ -38,13 +40,14 @@ impl DropStream {
                 .wrap_err_with(|| format!("failed to connect drop stream for node `{node_id}`"))?,
         };
 
-        Self::init_on_channel(dataflow_id, node_id, channel, hlc)
+        Self::init_on_channel(dataflow_id, node_id, channel, pending_calls, hlc)
     }
 
     pub fn init_on_channel(
         dataflow_id: DataflowId,
         node_id: &NodeId,
         mut channel: DaemonChannel,
+        pending_calls: PendingCalls,
         clock: Arc<uhlc::HLC>,
     ) -> eyre::Result<Self> {
         channel.register(dataflow_id, node_id.clone(), clock.new_timestamp())?;
@@ -65,10 +68,14 @@ impl DropStream {
             other => eyre::bail!("unexpected drop subscribe reply: {other:?}"),
         }
 
-        let (tx, rx) = flume::bounded(0);
+        // unbounded to not delay service replies when the node doesn't handle the
+        // finished drop tokens for a while
+        let (tx, rx) = flume::unbounded();
         let node_id_cloned = node_id.clone();
 
-        let handle = std::thread::spawn(|| drop_stream_loop(node_id_cloned, tx, channel, clock));
+        let handle = std::thread::spawn(|| {
+            drop_stream_loop(node_id_cloned, tx, channel, pending_calls, clock)
+        });
 
         Ok(Self {
             receiver: rx,
@@ -85,11 +92,12 @@ impl std::ops::Deref for DropStream {
     }
 }
 
-#[tracing::instrument(skip(tx, channel, clock))]
+#[tracing::instrument(skip(tx, channel, pending_calls, clock))]
 fn drop_stream_loop(
     node_id: NodeId,
     tx: flume::Sender<DropToken>,
     mut channel: DaemonChannel,
+    pending_calls: PendingCalls,
     clock: Arc<uhlc::HLC>,
 ) {
     'outer: loop {
@@ -131,9 +139,22 @@ fn drop_stream_loop(
                         break 'outer;
                     }
                 }
+                NodeDropEvent::ServiceReply { request_id, result } => {
+                    let reply_tx = pending_calls.lock().unwrap().remove(&request_id);
+                    match reply_tx {
+                        Some(reply_tx) => {
+                            let _ = reply_tx.send(result);
+                        }
+                        None => {
+                            tracing::debug!("ignoring reply to dropped service call {request_id}")
+                        }
+                    }
+                }
             }
         }
     }
+    // fail the remaining calls
+    pending_calls.lock().unwrap().clear();
 }
 
 struct DropStreamThreadHandle {
@@ -1,22 +1,29 @@
-use crate::EventStream;
+use crate::{event_stream::InputLineage, EventStream, RawData};
 
 use self::{
     arrow_utils::{copy_array_into_sample, required_data_size},
     control_channel::ControlChannel,
     drop_stream::DropStream,
+    numa::NumaPlacement,
+    serialize::serialize,
+    service::{PendingCalls, ServiceCall, ServiceRequestId, DEFAULT_SERVICE_TIMEOUT},
 };
 use aligned_vec::{AVec, ConstAlign};
-use arrow::array::Array;
+use arrow::array::{make_array, Array, ArrayData};
 use dora_core::{
-    config::{DataId, NodeId, NodeRunConfig},
-    daemon_messages::{DataMessage, DataflowId, DropToken, NodeConfig},
-    descriptor::Descriptor,
-    message::{uhlc, ArrowTypeInfo, Metadata, MetadataParameters},
+    config::{DataId, Input, InputMapping, InputQueueConfig, NodeId, NodeRunConfig},
+    daemon_messages::{DataMessage, DataflowId, DropToken, NodeConfig, ServiceResponse},
+    descriptor::{CoreNodeKind, Descriptor},
+    message::{
+        annotation::Annotation, lineage::extend_lineage, uhlc, ArrowTypeInfo, DataFormat, Metadata,
+        MetadataParameters,
+    },
 };
 use eyre::{bail, WrapErr};
+use futures_timer::Delay;
 use shared_memory_extended::{Shmem, ShmemConf};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
     ops::{Deref, DerefMut},
     sync::Arc,
     time::Duration,
@@ -28,6 +35,9 @@ use dora_tracing::set_up_tracing;
 pub mod arrow_utils;
 mod control_channel;
 mod drop_stream;
+mod numa;
+mod serialize;
+pub mod service;
 
 pub const ZERO_COPY_THRESHOLD: usize = 4096;
 
@@ -41,10 +51,35 @@ pub struct DoraNode {
     sent_out_shared_memory: HashMap<DropToken, ShmemHandle>,
     drop_stream: DropStream,
     cache: VecDeque<ShmemHandle>,
+    /// Regions of pending outputs that were dropped without being committed or
+    /// aborted, which are moved to the `cache`.
+    discarded_regions: (flume::Sender<ShmemHandle>, flume::Receiver<ShmemHandle>),
+    shared_memory: SharedMemoryLimits,
+    numa: NumaPlacement,
+    pending_calls: PendingCalls,
+    next_request_id: u64,
+    /// Whether the lineage of outputs is tracked, see [`Descriptor::track_lineage`].
+    track_lineage: bool,
+    /// Lineage of the latest input, `None` if disabled.
+    input_lineage: Option<InputLineage>,
+    /// Serialization formats that consumers request for the outputs.
+    output_formats: BTreeMap<DataId, BTreeSet<DataFormat>>,
 
     dataflow_descriptor: Descriptor,
 }
 
+/// Limits for the shared memory regions that a node allocates.
+struct SharedMemoryLimits {
+    /// Maximum total size of the regions owned by this node.
+    node_budget: Option<usize>,
+    /// Whether new regions must be reserved from the dataflow's budget.
+    dataflow_budget: bool,
+    /// Maximum number of freed regions that are kept for reuse.
+    pool_size: usize,
+    /// Total size of the regions owned by this node, including cached regions.
+    allocated: usize,
+}
+
 impl DoraNode {
     /// Initiate a node from environment variables set by `dora-coordinator`
     ///
@@ -78,16 +113,45 @@ impl DoraNode {
 
         let clock = Arc::new(uhlc::HLC::default());
 
-        let event_stream =
-            EventStream::init(dataflow_id, &node_id, &daemon_communication, clock.clone())
-                .wrap_err("failed to init event stream")?;
-        let drop_stream =
-            DropStream::init(dataflow_id, &node_id, &daemon_communication, clock.clone())
-                .wrap_err("failed to init drop stream")?;
+        let event_stream = EventStream::init(
+            dataflow_id,
+            &node_id,
+            &daemon_communication,
+            &run_config,
+            &dataflow_descriptor,
+            clock.clone(),
+        )
+        .wrap_err("failed to init event stream")?;
+        let input_lineage = event_stream.input_lineage();
+        let pending_calls = PendingCalls::default();
+        let drop_stream = DropStream::init(
+            dataflow_id,
+            &node_id,
+            &daemon_communication,
+            pending_calls.clone(),
+            clock.clone(),
+        )
+        .wrap_err("failed to init drop stream")?;
         let control_channel =
             ControlChannel::init(dataflow_id, &node_id, &daemon_communication, clock.clone())
                 .wrap_err("failed to init control channel")?;
 
+        let shared_memory_config = &dataflow_descriptor.shared_memory;
+        let node_budget = dataflow_descriptor
+            .nodes
+            .iter()
+            .find(|n| n.id == node_id)
+            .and_then(|n| n.shared_memory_budget)
+            .or(shared_memory_config.node_budget);
+        let shared_memory = SharedMemoryLimits {
+            node_budget: node_budget.map(|budget| budget as usize),
+            dataflow_budget: shared_memory_config.dataflow_budget.is_some(),
+            pool_size: shared_memory_config.pool_size,
+            allocated: 0,
+        };
+        let numa = NumaPlacement::new(&node_id, &dataflow_descriptor);
+        let output_formats = output_formats(&dataflow_descriptor, &node_id);
+
         let node = Self {
             id: node_id,
             dataflow_id: dataflow_id,
@@ -97,6 +161,14 @@ impl DoraNode {
             sent_out_shared_memory: HashMap::new(),
             drop_stream,
             cache: VecDeque::new(),
+            discarded_regions: flume::unbounded(),
+            shared_memory,
+            numa,
+            pending_calls,
+            next_request_id: 0,
+            track_lineage: dataflow_descriptor.track_lineage(),
+            input_lineage,
+            output_formats,
 
             dataflow_descriptor,
         };
@@ -136,7 +208,7 @@ impl DoraNode {
     where
         F: FnOnce(&mut [u8]),
     {
-        let mut sample = self.allocate_data_sample(data_len)?;
+        let mut sample = self.allocate_output_sample(&output_id, data_len)?;
         data(&mut sample);
 
         let type_info = ArrowTypeInfo::byte_array(data_len);
@@ -154,7 +226,7 @@ impl DoraNode {
 
         let total_len = required_data_size(&arrow_array);
 
-        let mut sample = self.allocate_data_sample(total_len)?;
+        let mut sample = self.allocate_output_sample(&output_id, total_len)?;
         let type_info = copy_array_into_sample(&mut sample, &arrow_array);
 
         self.send_output_sample(output_id, type_info, parameters, Some(sample))
@@ -175,6 +247,24 @@ impl DoraNode {
         })
     }
 
+    /// Sends annotations for a received message on the given output.
+    ///
+    /// Visualization sinks can merge the annotations with the annotated message
+    /// through an [`AnnotationMerger`](dora_core::message::annotation::AnnotationMerger).
+    pub fn send_annotations(
+        &mut self,
+        output_id: DataId,
+        annotated: &Metadata,
+        annotations: &[Annotation],
+    ) -> eyre::Result<()> {
+        let parameters = MetadataParameters {
+            annotates: Some(annotated.timestamp()),
+            ..Default::default()
+        };
+        let data = Annotation::encode(annotations);
+        self.send_output_bytes(output_id, parameters, data.len(), &data)
+    }
+
     pub fn send_typed_output<F>(
         &mut self,
         output_id: DataId,
@@ -186,12 +276,93 @@ impl DoraNode {
     where
         F: FnOnce(&mut [u8]),
     {
-        let mut sample = self.allocate_data_sample(data_len)?;
+        let mut sample = self.allocate_output_sample(&output_id, data_len)?;
         data(&mut sample);
 
         self.send_output_sample(output_id, type_info, parameters, Some(sample))
     }
 
+    /// Allocates a sample for the given output that is filled incrementally.
+    ///
+    /// The returned [`PendingOutput`] must be passed to either
+    /// [`commit_output`](Self::commit_output) to send it or
+    /// [`abort_output`](Self::abort_output) to discard it, e.g. when an error
+    /// occurs while filling it. Aborted or dropped samples are not sent and
+    /// their shared memory region is kept for reuse.
+    pub fn begin_output(
+        &mut self,
+        output_id: DataId,
+        data_len: usize,
+    ) -> eyre::Result<PendingOutput> {
+        if !self.node_config.outputs.contains(&output_id) {
+            eyre::bail!("unknown output `{output_id}`");
+        }
+        let sample = self.allocate_output_sample(&output_id, data_len)?;
+        Ok(PendingOutput {
+            output_id,
+            sample: Some(sample),
+            discarded_regions: self.discarded_regions.0.clone(),
+        })
+    }
+
+    /// Sends the given pending output as a byte array.
+    pub fn commit_output(
+        &mut self,
+        output: PendingOutput,
+        parameters: MetadataParameters,
+    ) -> eyre::Result<()> {
+        let type_info = ArrowTypeInfo::byte_array(output.len());
+        self.commit_typed_output(output, type_info, parameters)
+    }
+
+    /// Sends the given pending output, interpreting its data according to the
+    /// given type info.
+    pub fn commit_typed_output(
+        &mut self,
+        mut output: PendingOutput,
+        type_info: ArrowTypeInfo,
+        parameters: MetadataParameters,
+    ) -> eyre::Result<()> {
+        let sample = output.sample.take();
+        self.send_output_sample(output.output_id.clone(), type_info, parameters, sample)
+    }
+
+    /// Discards the given pending output without sending it.
+    pub fn abort_output(&mut self, mut output: PendingOutput) {
+        if let Some(sample) = output.sample.take() {
+            self.discard_sample(sample);
+        }
+    }
+
+    /// Discards the given sample without sending it.
+    ///
+    /// The shared memory region of the sample is reused for later samples.
+    pub fn discard_sample(&mut self, sample: DataSample) {
+        match sample.inner {
+            DataSampleInner::Shmem(region) => self.add_to_cache(region),
+            DataSampleInner::Vec(_) => {}
+        }
+    }
+
+    /// Takes the data of the given sample without sending it, e.g. to deliver
+    /// it within the node.
+    ///
+    /// Shared memory samples are copied, and their region is reused for later
+    /// samples.
+    pub fn take_sample_data(&mut self, sample: DataSample) -> AVec<u8, ConstAlign<128>> {
+        match sample.inner {
+            DataSampleInner::Vec(mut data) => {
+                data.truncate(sample.len);
+                data
+            }
+            DataSampleInner::Shmem(region) => {
+                let data = AVec::from_slice(128, unsafe { &region.as_slice()[..sample.len] });
+                self.add_to_cache(region);
+                data
+            }
+        }
+    }
+
     pub fn send_output_sample(
         &mut self,
         output_id: DataId,
@@ -204,11 +375,60 @@ impl DoraNode {
         if !self.node_config.outputs.contains(&output_id) {
             eyre::bail!("unknown output");
         }
-        let metadata = Metadata::from_parameters(
-            self.clock.new_timestamp(),
-            type_info,
-            parameters.into_owned(),
-        );
+        let Some(formats) = self.output_formats.get(&output_id) else {
+            return self.send_sample(output_id, type_info, parameters, sample);
+        };
+
+        // consumers that request a `format` receive a serialized copy
+        let raw = match sample.as_deref() {
+            Some(data) => RawData::Vec(AVec::from_slice(128, data)),
+            None => RawData::Empty,
+        };
+        let array = make_array(raw.into_arrow_array(&type_info)?);
+        let copies = formats
+            .iter()
+            .map(|format| {
+                let data = serialize(*format, &array)
+                    .wrap_err_with(|| format!("failed to serialize output as {format}"))?;
+                Ok((*format, data))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let copy_parameters = parameters.clone();
+        self.send_sample(output_id.clone(), type_info, parameters, sample)?;
+        for (format, data) in copies {
+            let parameters = MetadataParameters {
+                format: Some(format),
+                ..copy_parameters.clone()
+            };
+            let mut sample = self.allocate_output_sample(&output_id, data.len())?;
+            sample.copy_from_slice(&data);
+            let type_info = ArrowTypeInfo::byte_array(data.len());
+            self.send_sample(output_id.clone(), type_info, parameters, Some(sample))?;
+        }
+        Ok(())
+    }
+
+    fn send_sample(
+        &mut self,
+        output_id: DataId,
+        type_info: ArrowTypeInfo,
+        parameters: MetadataParameters,
+        sample: Option<DataSample>,
+    ) -> eyre::Result<()> {
+        let mut parameters = parameters.into_owned();
+        // replaces checksums of forwarded input metadata, which don't match the data
+        parameters.checksum = sample_checksum(sample.as_deref());
+        let timestamp = self.clock.new_timestamp();
+        if self.track_lineage {
+            let upstream = match &self.input_lineage {
+                Some(input_lineage) if parameters.lineage.is_empty() => input_lineage.get(),
+                _ => std::mem::take(&mut parameters.lineage),
+            };
+            parameters.lineage =
+                extend_lineage(&self.id.to_string(), &output_id, timestamp, &upstream);
+        }
+        let metadata = Metadata::from_parameters(timestamp, type_info, parameters);
 
         let (data, shmem) = match sample {
             Some(sample) => sample.finalize(),
@@ -227,6 +447,129 @@ impl DoraNode {
         Ok(())
     }
 
+    /// Stops deriving the lineage of outputs from the latest input.
+    ///
+    /// Outputs then only continue the lineage that is set in their
+    /// [`MetadataParameters`]. This is useful for nodes that process
+    /// multiple streams concurrently, such as the operator runtime.
+    pub fn disable_input_lineage(&mut self) {
+        self.input_lineage = None;
+    }
+
+    /// Calls a service of another node and returns a future for the reply.
+    ///
+    /// Services are referenced as `<node_id>/<service>`, or as
+    /// `<node_id>/<operator_id>/<service>` for operators of runtime nodes.
+    /// Service data is always copied, so services are meant for small requests.
+    /// The call fails if the service doesn't reply within
+    /// [`DEFAULT_SERVICE_TIMEOUT`](service::DEFAULT_SERVICE_TIMEOUT), see
+    /// [`call_service_with_timeout`](Self::call_service_with_timeout).
+    ///
+    /// ```no_run
+    /// use dora_node_api::{arrow::array::UInt8Array, DoraNode, MetadataParameters};
+    ///
+    /// let (mut node, mut events) = DoraNode::init_from_env().expect("Could not init node.");
+    ///
+    /// let request = UInt8Array::from(vec![1, 2, 3]);
+    /// let call = node
+    ///     .call_service("detector/query", MetadataParameters::default(), request)
+    ///     .expect("Could not call service");
+    /// let (metadata, reply) = call.wait().expect("service call failed");
+    /// ```
+    pub fn call_service(
+        &mut self,
+        service: &str,
+        parameters: MetadataParameters,
+        data: impl Array,
+    ) -> eyre::Result<ServiceCall> {
+        self.call_service_with_timeout(service, parameters, data, DEFAULT_SERVICE_TIMEOUT)
+    }
+
+    /// Calls a service of another node and fails if it doesn't reply within
+    /// the given timeout.
+    pub fn call_service_with_timeout(
+        &mut self,
+        service: &str,
+        parameters: MetadataParameters,
+        data: impl Array,
+        timeout: Duration,
+    ) -> eyre::Result<ServiceCall> {
+        let Some((node_id, service_id)) = service.split_once('/') else {
+            bail!("invalid service `{service}`, expected `<node_id>/<service>`");
+        };
+        let (type_info, data) = inline_data(&data.to_data());
+        let metadata = Metadata::from_parameters(self.clock.new_timestamp(), type_info, parameters);
+
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let (reply_tx, reply_rx) = flume::bounded(1);
+        self.pending_calls
+            .lock()
+            .unwrap()
+            .insert(request_id, reply_tx);
+
+        let result = self.control_channel.call_service(
+            NodeId::from(node_id.to_owned()),
+            DataId::from(service_id.to_owned()),
+            request_id,
+            timeout,
+            metadata,
+            Some(data),
+        );
+        if let Err(err) = result {
+            self.pending_calls.lock().unwrap().remove(&request_id);
+            return Err(err.wrap_err(format!("failed to call service `{service}`")));
+        }
+
+        Ok(ServiceCall {
+            request_id,
+            pending_calls: self.pending_calls.clone(),
+            reply: reply_rx.into_recv_async(),
+            timeout,
+            deadline: Delay::new(timeout),
+        })
+    }
+
+    /// Answers a received [`Event::ServiceRequest`](crate::Event::ServiceRequest).
+    pub fn send_service_reply(
+        &mut self,
+        request: ServiceRequestId,
+        parameters: MetadataParameters,
+        data: impl Array,
+    ) -> eyre::Result<()> {
+        let (type_info, data) = inline_data(&data.to_data());
+        let metadata = Metadata::from_parameters(self.clock.new_timestamp(), type_info, parameters);
+        let response = ServiceResponse {
+            metadata,
+            data: Some(data),
+        };
+        self.control_channel
+            .send_service_reply(request, Ok(response))
+            .wrap_err("failed to send service reply")
+    }
+
+    /// Answers a received service request that could not be handled with an error.
+    pub fn send_service_error(
+        &mut self,
+        request: ServiceRequestId,
+        error: impl Into<String>,
+    ) -> eyre::Result<()> {
+        self.control_channel
+            .send_service_reply(request, Err(error.into()))
+            .wrap_err("failed to send service error")
+    }
+
+    /// Signals that the node finished its initialization.
+    ///
+    /// Nodes with `ready_signal: true` in the dataflow description must call this
+    /// once they're ready to receive inputs. The other nodes of the dataflow are
+    /// only started after all such nodes are ready. For other nodes, this is a no-op.
+    pub fn ready(&mut self) -> eyre::Result<()> {
+        self.control_channel
+            .report_ready()
+            .wrap_err("failed to signal readiness to daemon")
+    }
+
     pub fn close_outputs(&mut self, outputs: Vec<DataId>) -> eyre::Result<()> {
         for output_id in &outputs {
             if !self.node_config.outputs.remove(output_id) {
@@ -254,9 +597,30 @@ impl DoraNode {
     }
 
     pub fn allocate_data_sample(&mut self, data_len: usize) -> eyre::Result<DataSample> {
+        self.allocate_sample(data_len, self.numa.node())
+    }
+
+    /// Allocates a sample for the given output.
+    ///
+    /// Unlike [`allocate_data_sample`](Self::allocate_data_sample), this places
+    /// shared memory samples on the NUMA node of the output's receivers if they
+    /// specify one.
+    pub fn allocate_output_sample(
+        &mut self,
+        output_id: &DataId,
+        data_len: usize,
+    ) -> eyre::Result<DataSample> {
+        self.allocate_sample(data_len, self.numa.output(output_id))
+    }
+
+    fn allocate_sample(
+        &mut self,
+        data_len: usize,
+        numa_node: Option<u32>,
+    ) -> eyre::Result<DataSample> {
         let data = if data_len >= ZERO_COPY_THRESHOLD {
             // create shared memory region
-            let shared_memory = self.allocate_shared_memory(data_len)?;
+            let shared_memory = self.allocate_shared_memory(data_len, numa_node)?;
 
             DataSample {
                 inner: DataSampleInner::Shmem(shared_memory),
@@ -271,13 +635,20 @@ impl DoraNode {
         Ok(data)
     }
 
-    fn allocate_shared_memory(&mut self, data_len: usize) -> eyre::Result<ShmemHandle> {
+    fn allocate_shared_memory(
+        &mut self,
+        data_len: usize,
+        numa_node: Option<u32>,
+    ) -> eyre::Result<ShmemHandle> {
+        while let Ok(region) = self.discarded_regions.1.try_recv() {
+            self.add_to_cache(region);
+        }
         let cache_index = self
             .cache
             .iter()
             .enumerate()
             .rev()
-            .filter(|(_, s)| s.len() >= data_len)
+            .filter(|(_, s)| s.len() >= data_len && s.1 == numa_node)
             .min_by_key(|(_, s)| s.len())
             .map(|(i, _)| i);
         let memory = match cache_index {
@@ -285,19 +656,89 @@ impl DoraNode {
                 // we know that this index exists, so we can safely unwrap here
                 self.cache.remove(i).unwrap()
             }
-            None => ShmemHandle(Box::new(
-                ShmemConf::new()
+            None => {
+                self.reserve_shared_memory(data_len)?;
+                let memory = ShmemConf::new()
                     .size(data_len)
                     .writable(true)
                     .create()
-                    .wrap_err("failed to allocate shared memory")?,
-            )),
+                    .wrap_err("failed to allocate shared memory");
+                match memory {
+                    Ok(memory) => {
+                        if let Some(numa_node) = numa_node {
+                            if let Err(err) =
+                                numa::bind_to_numa_node(memory.as_ptr(), memory.len(), numa_node)
+                            {
+                                tracing::warn!(
+                                    "failed to place shared memory on NUMA node {numa_node}: {err}"
+                                );
+                            }
+                        }
+                        ShmemHandle(Box::new(memory), numa_node)
+                    }
+                    Err(err) => {
+                        self.release_shared_memory(data_len);
+                        return Err(err);
+                    }
+                }
+            }
         };
         assert!(memory.len() >= data_len);
 
         Ok(memory)
     }
 
+    /// Accounts for a new shared memory region of the given size.
+    ///
+    /// Frees cached regions if the node budget would be exceeded otherwise. Fails
+    /// if the node or dataflow budget doesn't allow the allocation.
+    fn reserve_shared_memory(&mut self, data_len: usize) -> eyre::Result<()> {
+        if let Some(budget) = self.shared_memory.node_budget {
+            // regions of finished outputs can be freed or reused
+            self.handle_finished_drop_tokens()?;
+            while self.shared_memory.allocated + data_len > budget {
+                match self.cache.pop_front() {
+                    Some(region) => self.free_shared_memory(region),
+                    None => bail!(
+                        "shared memory budget of node `{}` exceeded: cannot allocate {data_len} \
+                        bytes ({} of {budget} bytes are in use)",
+                        self.id,
+                        self.shared_memory.allocated
+                    ),
+                }
+            }
+        }
+        if self.shared_memory.dataflow_budget {
+            let mut result = self.control_channel.reserve_shared_memory(data_len);
+            // free cached regions to make room for the new region
+            while result.is_err() {
+                let Some(region) = self.cache.pop_front() else {
+                    break;
+                };
+                self.free_shared_memory(region);
+                result = self.control_channel.reserve_shared_memory(data_len);
+            }
+            result?;
+        }
+        self.shared_memory.allocated += data_len;
+        Ok(())
+    }
+
+    fn release_shared_memory(&mut self, len: usize) {
+        self.shared_memory.allocated = self.shared_memory.allocated.saturating_sub(len);
+        if self.shared_memory.dataflow_budget {
+            if let Err(err) = self.control_channel.release_shared_memory(len) {
+                tracing::warn!("{err:?}");
+            }
+        }
+    }
+
+    fn free_shared_memory(&mut self, region: ShmemHandle) {
+        let len = region.len();
+        drop(region);
+        self.release_shared_memory(len);
+    }
+
     fn handle_finished_drop_tokens(&mut self) -> eyre::Result<()> {
         loop {
             match self.drop_stream.try_recv() {
@@ -315,11 +756,11 @@ impl DoraNode {
     }
 
     fn add_to_cache(&mut self, memory: ShmemHandle) {
-        const MAX_CACHE_SIZE: usize = 20;
-
         self.cache.push_back(memory);
-        while self.cache.len() > MAX_CACHE_SIZE {
-            self.cache.pop_front();
+        while self.cache.len() > self.shared_memory.pool_size {
+            if let Some(region) = self.cache.pop_front() {
+                self.free_shared_memory(region);
+            }
         }
     }
 
@@ -329,6 +770,18 @@ impl DoraNode {
     pub fn dataflow_descriptor(&self) -> &Descriptor {
         &self.dataflow_descriptor
     }
+
+    /// Returns the resolved queue configuration of each input of the node.
+    pub fn input_config(&self) -> BTreeMap<DataId, InputQueueConfig> {
+        self.node_config
+            .inputs
+            .iter()
+            .map(|(id, input)| {
+                let config = input.queue_config(self.dataflow_descriptor.deterministic);
+                (id.clone(), config)
+            })
+            .collect()
+    }
 }
 
 impl Drop for DoraNode {
@@ -384,6 +837,13 @@ impl Drop for DoraNode {
     }
 }
 
+/// Copies the given array into a message that is sent inline, without shared memory.
+fn inline_data(data: &ArrayData) -> (ArrowTypeInfo, DataMessage) {
+    let mut buffer: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, required_data_size(data));
+    let type_info = copy_array_into_sample(&mut buffer, data);
+    (type_info, DataMessage::Vec(buffer))
+}
+
 pub struct DataSample {
     inner: DataSampleInner,
     len: usize,
@@ -450,12 +910,68 @@ impl std::fmt::Debug for DataSample {
     }
 }
 
+/// An output sample that is filled before it is sent, see
+/// [`DoraNode::begin_output`].
+///
+/// Dropping it without [committing](DoraNode::commit_output) it has the same
+/// effect as [aborting](DoraNode::abort_output) it: its shared memory region is
+/// returned to the node for reuse.
+pub struct PendingOutput {
+    output_id: DataId,
+    /// Only `None` while the output is committed or aborted.
+    sample: Option<DataSample>,
+    discarded_regions: flume::Sender<ShmemHandle>,
+}
+
+impl PendingOutput {
+    pub fn output_id(&self) -> &DataId {
+        &self.output_id
+    }
+}
+
+impl Deref for PendingOutput {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.sample.as_deref().unwrap_or_default()
+    }
+}
+
+impl DerefMut for PendingOutput {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.sample.as_deref_mut().unwrap_or_default()
+    }
+}
+
+impl Drop for PendingOutput {
+    fn drop(&mut self) {
+        if let Some(DataSample {
+            inner: DataSampleInner::Shmem(region),
+            ..
+        }) = self.sample.take()
+        {
+            // the node might be dropped already, then the region is freed
+            let _ = self.discarded_regions.send(region);
+        }
+    }
+}
+
+impl std::fmt::Debug for PendingOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingOutput")
+            .field("output_id", &self.output_id)
+            .field("sample", &self.sample)
+            .finish_non_exhaustive()
+    }
+}
+
 enum DataSampleInner {
     Shmem(ShmemHandle),
     Vec(AVec<u8, ConstAlign<128>>),
 }
 
-struct ShmemHandle(Box<Shmem>);
+/// A shared memory region and the NUMA node that it was placed on.
+struct ShmemHandle(Box<Shmem>, Option<u32>);
 
 impl Deref for ShmemHandle {
     type Target = Shmem;
@@ -473,3 +989,89 @@ impl DerefMut for ShmemHandle {
 
 unsafe impl Send for ShmemHandle {}
 unsafe impl Sync for ShmemHandle {}
+
+/// Collects the serialization formats that the consumers of the outputs of the
+/// given node request through the `format` option of their inputs.
+fn output_formats(
+    descriptor: &Descriptor,
+    node_id: &NodeId,
+) -> BTreeMap<DataId, BTreeSet<DataFormat>> {
+    let mut formats: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+    for node in descriptor.resolve_aliases_and_set_defaults() {
+        let inputs: Vec<Input> = match node.kind {
+            CoreNodeKind::Custom(custom) => custom.run_config.inputs.into_values().collect(),
+            CoreNodeKind::Runtime(runtime) => runtime
+                .operators
+                .into_iter()
+                .flat_map(|operator| operator.config.inputs.into_values())
+                .collect(),
+        };
+        for input in inputs {
+            let Some(format) = input.serialization_format() else {
+                continue;
+            };
+            if let InputMapping::User(mapping) = input.mapping {
+                if &mapping.source == node_id {
+                    formats.entry(mapping.output).or_default().insert(format);
+                }
+            }
+        }
+    }
+    formats
+}
+
+/// Computes the checksum of the sample if the `checksum` feature is enabled.
+#[cfg(feature = "checksum")]
+fn sample_checksum(sample: Option<&[u8]>) -> Option<u32> {
+    Some(crc32c::crc32c(sample.unwrap_or_default()))
+}
+
+#[cfg(not(feature = "checksum"))]
+fn sample_checksum(_sample: Option<&[u8]>) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn return_regions_of_dropped_outputs() {
+        let (discarded_regions, returned) = flume::unbounded();
+        let memory = ShmemConf::new().size(4096).writable(true).create().unwrap();
+        let sample = DataSample {
+            inner: DataSampleInner::Shmem(ShmemHandle(Box::new(memory), None)),
+            len: 100,
+        };
+        let mut output = PendingOutput {
+            output_id: DataId::from("image".to_owned()),
+            sample: Some(sample),
+            discarded_regions: discarded_regions.clone(),
+        };
+        output[..3].copy_from_slice(b"abc");
+        assert_eq!(output.len(), 100);
+        drop(output);
+        let region = returned.try_recv().unwrap();
+        assert_eq!(unsafe { &region.as_slice()[..3] }, b"abc");
+
+        // small samples have no region that could be reused
+        let sample = AVec::__from_elem(128, 0, 10).into();
+        drop(PendingOutput {
+            output_id: DataId::from("image".to_owned()),
+            sample: Some(sample),
+            discarded_regions,
+        });
+        assert!(returned.try_recv().is_err());
+    }
+
+    #[test]
+    fn checksum_sent_samples() {
+        #[cfg(feature = "checksum")]
+        {
+            assert_eq!(sample_checksum(Some(b"123456789")), Some(0xe306_9283));
+            assert_eq!(sample_checksum(None), Some(0));
+        }
+        #[cfg(not(feature = "checksum"))]
+        assert_eq!(sample_checksum(Some(b"123456789")), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! NUMA-aware placement of shared memory regions.
+//!
+//! On multi-socket machines, accessing memory of another socket is considerably
+//! slower than accessing local memory. So shared memory samples are allocated
+//! on the NUMA node of the producing node by default. If all receivers of an
+//! output specify the same NUMA node, the samples of this output are allocated
+//! on the receivers' NUMA node instead.
+//!
+//! Placement is only supported on Linux, on other platforms the configuration
+//! is ignored.
+
+use dora_core::{
+    config::{DataId, InputMapping, NodeId, NumaNode},
+    descriptor::{CoreNodeKind, Descriptor},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The NUMA nodes that shared memory samples are allocated on.
+#[derive(Debug, Default)]
+pub(super) struct NumaPlacement {
+    /// NUMA node of this node.
+    node: Option<u32>,
+    /// NUMA nodes of outputs whose receivers all run on the same NUMA node.
+    outputs: BTreeMap<DataId, u32>,
+}
+
+impl NumaPlacement {
+    pub fn new(node_id: &NodeId, descriptor: &Descriptor) -> Self {
+        let nodes = descriptor.resolve_aliases_and_set_defaults();
+
+        let node = nodes
+            .iter()
+            .find(|n| &n.id == node_id)
+            .and_then(|n| n.numa_node)
+            .and_then(|numa_node| match numa_node {
+                NumaNode::Id(id) => Some(id),
+                NumaNode::Auto => {
+                    let current = current_numa_node();
+                    if current.is_none() {
+                        tracing::warn!("failed to determine NUMA node of node `{node_id}`");
+                    }
+                    current
+                }
+            });
+
+        // collect the affinity hints of the receivers of each output
+        let mut hints: BTreeMap<DataId, BTreeSet<Option<u32>>> = BTreeMap::new();
+        for receiver in &nodes {
+            let hint = match receiver.numa_node {
+                Some(NumaNode::Id(id)) => Some(id),
+                _ => None,
+            };
+            let inputs: Vec<_> = match &receiver.kind {
+                CoreNodeKind::Custom(custom) => custom.run_config.inputs.values().collect(),
+                CoreNodeKind::Runtime(runtime) => runtime
+                    .operators
+                    .iter()
+                    .flat_map(|operator| operator.config.inputs.values())
+                    .collect(),
+            };
+            for input in inputs {
+                if let InputMapping::User(mapping) = &input.mapping {
+                    if &mapping.source == node_id {
+                        hints
+                            .entry(mapping.output.clone())
+                            .or_default()
+                            .insert(hint);
+                    }
+                }
+            }
+        }
+        let outputs = hints
+            .into_iter()
+            .filter_map(|(output, hints)| match Vec::from_iter(hints).as_slice() {
+                [Some(id)] => Some((output, *id)),
+                _ => None,
+            })
+            .collect();
+
+        let placement = Self { node, outputs };
+        if cfg!(not(target_os = "linux")) && placement.is_configured() {
+            tracing::warn!("NUMA placement of shared memory is only supported on Linux");
+            return Self::default();
+        }
+        placement
+    }
+
+    fn is_configured(&self) -> bool {
+        self.node.is_some() || !self.outputs.is_empty()
+    }
+
+    /// Returns the NUMA node for samples that are not associated with an output.
+    pub fn node(&self) -> Option<u32> {
+        self.node
+    }
+
+    /// Returns the NUMA node for samples of the given output.
+    pub fn output(&self, output_id: &DataId) -> Option<u32> {
+        self.outputs.get(output_id).copied().or(self.node)
+    }
+}
+
+/// Sets the memory policy of the given memory region to prefer the given NUMA node.
+///
+/// The region must be page-aligned and not yet accessed, as the policy only
+/// applies to pages that are allocated afterwards.
+#[cfg(target_os = "linux")]
+pub(super) fn bind_to_numa_node(ptr: *mut u8, len: usize, numa_node: u32) -> std::io::Result<()> {
+    const MPOL_PREFERRED: libc::c_int = 1;
+    const BITS: usize = libc::c_ulong::BITS as usize;
+
+    let numa_node = numa_node as usize;
+    let mut nodemask = vec![0 as libc::c_ulong; numa_node / BITS + 1];
+    nodemask[numa_node / BITS] |= 1 << (numa_node % BITS);
+    // the kernel ignores the last bit of the node mask
+    let max_node = nodemask.len() * BITS + 1;
+
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            len,
+            MPOL_PREFERRED,
+            nodemask.as_ptr(),
+            max_node,
+            0,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn bind_to_numa_node(
+    _ptr: *mut u8,
+    _len: usize,
+    _numa_node: u32,
+) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Returns the NUMA node of the CPU that the current thread is running on.
+#[cfg(target_os = "linux")]
+fn current_numa_node() -> Option<u32> {
+    let mut cpu: libc::c_uint = 0;
+    let mut node: libc::c_uint = 0;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_getcpu,
+            &mut cpu,
+            &mut node,
+            std::ptr::null_mut::<libc::c_void>(),
+        )
+    };
+    (result == 0).then_some(node)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_numa_node() -> Option<u32> {
+    None
+}
@@ -0,0 +1,204 @@
+//! Serialization of output data to formats that don't require Arrow.
+//!
+//! Configured through the `format` option of inputs. Senders publish a
+//! serialized copy of their output for every format that its consumers
+//! request. All formats encode the values of the array as a list, using the
+//! JSON representation of Arrow.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::ArrayRef,
+    datatypes::{Field, Schema},
+    json::ArrayWriter,
+    record_batch::RecordBatch,
+};
+use dora_core::message::DataFormat;
+use eyre::Context;
+use serde_json::Value;
+
+/// Serializes the values of the given array to the given format.
+pub fn serialize(format: DataFormat, data: &ArrayRef) -> eyre::Result<Vec<u8>> {
+    let values = to_json_values(data)?;
+    let mut out = Vec::new();
+    match format {
+        DataFormat::Arrow => eyre::bail!("arrow data does not need to be serialized"),
+        DataFormat::Json => serde_json::to_writer(&mut out, &values)?,
+        DataFormat::Cbor => {
+            write_cbor_head(&mut out, 4, values.len() as u64);
+            for value in &values {
+                write_cbor(&mut out, value);
+            }
+        }
+        DataFormat::Protobuf => {
+            for value in &values {
+                write_protobuf_field(&mut out, 1, &protobuf_value(value));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn to_json_values(data: &ArrayRef) -> eyre::Result<Vec<Value>> {
+    let schema = Schema::new(vec![Field::new("value", data.data_type().clone(), true)]);
+    let batch = RecordBatch::try_new(Arc::new(schema), vec![data.clone()])
+        .wrap_err("failed to create record batch")?;
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write(&batch)?;
+    writer.finish()?;
+    let rows: Vec<serde_json::Map<String, Value>> =
+        serde_json::from_slice(&writer.into_inner()).wrap_err("failed to parse JSON rows")?;
+    // null values are omitted from the rows
+    Ok(rows
+        .into_iter()
+        .map(|mut row| row.remove("value").unwrap_or_default())
+        .collect())
+}
+
+fn write_cbor(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                write_cbor_head(out, 0, n);
+            } else if let Some(n) = number.as_i64() {
+                // negative integers are encoded as `-1 - n`
+                write_cbor_head(out, 1, !n as u64);
+            } else {
+                out.push(0xfb);
+                out.extend_from_slice(&number.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            write_cbor_head(out, 3, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(values) => {
+            write_cbor_head(out, 4, values.len() as u64);
+            for value in values {
+                write_cbor(out, value);
+            }
+        }
+        Value::Object(fields) => {
+            write_cbor_head(out, 5, fields.len() as u64);
+            for (key, value) in fields {
+                write_cbor_head(out, 3, key.len() as u64);
+                out.extend_from_slice(key.as_bytes());
+                write_cbor(out, value);
+            }
+        }
+    }
+}
+
+fn write_cbor_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if let Ok(n) = u8::try_from(n) {
+        out.extend_from_slice(&[major | 24, n]);
+    } else if let Ok(n) = u16::try_from(n) {
+        out.push(major | 25);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = u32::try_from(n) {
+        out.push(major | 26);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+/// Encodes the value as `google.protobuf.Value` message.
+fn protobuf_value(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    match value {
+        Value::Null => {
+            write_protobuf_tag(&mut out, 1, 0);
+            write_varint(&mut out, 0);
+        }
+        Value::Number(number) => {
+            write_protobuf_tag(&mut out, 2, 1);
+            out.extend_from_slice(&number.as_f64().unwrap_or(f64::NAN).to_le_bytes());
+        }
+        Value::String(s) => write_protobuf_field(&mut out, 3, s.as_bytes()),
+        Value::Bool(b) => {
+            write_protobuf_tag(&mut out, 4, 0);
+            write_varint(&mut out, u64::from(*b));
+        }
+        Value::Object(fields) => {
+            // `google.protobuf.Struct` with a `map<string, Value> fields = 1`
+            let mut map = Vec::new();
+            for (key, value) in fields {
+                let mut entry = Vec::new();
+                write_protobuf_field(&mut entry, 1, key.as_bytes());
+                write_protobuf_field(&mut entry, 2, &protobuf_value(value));
+                write_protobuf_field(&mut map, 1, &entry);
+            }
+            write_protobuf_field(&mut out, 5, &map);
+        }
+        Value::Array(values) => {
+            // `google.protobuf.ListValue` with a `repeated Value values = 1`
+            let mut list = Vec::new();
+            for value in values {
+                write_protobuf_field(&mut list, 1, &protobuf_value(value));
+            }
+            write_protobuf_field(&mut out, 6, &list);
+        }
+    }
+    out
+}
+
+/// Writes a length-delimited field.
+fn write_protobuf_field(out: &mut Vec<u8>, field: u64, data: &[u8]) {
+    write_protobuf_tag(out, field, 2);
+    write_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn write_protobuf_tag(out: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(out, (field << 3) | wire_type);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, Int32Array, StringArray};
+
+    #[test]
+    fn serialize_formats() {
+        let data: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(-300)]));
+        assert_eq!(
+            serialize(DataFormat::Json, &data).unwrap(),
+            b"[1,null,-300]"
+        );
+        assert_eq!(
+            serialize(DataFormat::Cbor, &data).unwrap(),
+            [0x83, 0x01, 0xf6, 0x39, 0x01, 0x2b]
+        );
+
+        let data: ArrayRef = Arc::new(StringArray::from(vec!["a"]));
+        assert_eq!(
+            serialize(DataFormat::Cbor, &data).unwrap(),
+            [0x81, 0x61, b'a']
+        );
+        assert_eq!(
+            serialize(DataFormat::Protobuf, &data).unwrap(),
+            [0x0a, 0x03, 0x1a, 0x01, b'a']
+        );
+
+        let data: ArrayRef = Arc::new(Float64Array::from(vec![1.5]));
+        let mut expected = vec![0x0a, 0x09, 0x11];
+        expected.extend_from_slice(&1.5f64.to_le_bytes());
+        assert_eq!(serialize(DataFormat::Protobuf, &data).unwrap(), expected);
+    }
+}
@@ -0,0 +1,145 @@
+//! Request/response calls between nodes.
+//!
+//! Nodes declare the services that they answer in the dataflow descriptor.
+//! Other nodes call them through [`DoraNode::call_service`](super::DoraNode::call_service),
+//! which returns a [`ServiceCall`] future that resolves to the reply. The
+//! requests arrive as [`Event::ServiceRequest`](crate::Event::ServiceRequest)
+//! on the event stream of the serving node.
+//!
+//! Replies are delivered on the drop stream, which is polled by a background
+//! thread, so calls also complete while the caller doesn't consume its event
+//! stream. Services of nodes on other machines are called through their
+//! daemon. Calls fail if the service doesn't reply within their timeout.
+
+use crate::RawData;
+use dora_arrow_convert::ArrowData;
+use dora_core::{
+    config::NodeId,
+    daemon_messages::{DataMessage, ServiceResponse},
+    message::Metadata,
+};
+use eyre::{bail, eyre, Context};
+use futures::FutureExt;
+use futures_timer::Delay;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
+
+/// Timeout of [`DoraNode::call_service`](super::DoraNode::call_service).
+pub const DEFAULT_SERVICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reply senders of the pending service calls of a node, by request ID.
+pub(crate) type PendingCalls =
+    Arc<Mutex<HashMap<u64, flume::Sender<Result<ServiceResponse, String>>>>>;
+
+/// Identifies a received service request.
+///
+/// Pass it to [`DoraNode::send_service_reply`](super::DoraNode::send_service_reply)
+/// to answer the request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ServiceRequestId {
+    pub(crate) caller: NodeId,
+    pub(crate) request_id: u64,
+}
+
+impl ServiceRequestId {
+    /// The node that sent the request.
+    pub fn caller(&self) -> &NodeId {
+        &self.caller
+    }
+}
+
+/// The pending reply of a service call.
+///
+/// Dropping the call discards the reply.
+pub struct ServiceCall {
+    pub(crate) request_id: u64,
+    pub(crate) pending_calls: PendingCalls,
+    pub(crate) reply: flume::r#async::RecvFut<'static, Result<ServiceResponse, String>>,
+    pub(crate) timeout: Duration,
+    pub(crate) deadline: Delay,
+}
+
+impl ServiceCall {
+    /// Blocks until the service replies.
+    pub fn wait(self) -> eyre::Result<(Metadata, ArrowData)> {
+        futures::executor::block_on(self)
+    }
+}
+
+impl Future for ServiceCall {
+    type Output = eyre::Result<(Metadata, ArrowData)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let reply = match self.reply.poll_unpin(cx) {
+            Poll::Ready(reply) => reply,
+            Poll::Pending => {
+                return match self.deadline.poll_unpin(cx) {
+                    Poll::Ready(()) => Poll::Ready(Err(eyre!(
+                        "service did not reply within {:?}",
+                        self.timeout
+                    ))),
+                    Poll::Pending => Poll::Pending,
+                };
+            }
+        };
+        Poll::Ready(decode_reply(reply))
+    }
+}
+
+impl Drop for ServiceCall {
+    fn drop(&mut self) {
+        if let Ok(mut pending_calls) = self.pending_calls.lock() {
+            pending_calls.remove(&self.request_id);
+        }
+    }
+}
+
+fn decode_reply(
+    reply: Result<Result<ServiceResponse, String>, flume::RecvError>,
+) -> eyre::Result<(Metadata, ArrowData)> {
+    let response = reply
+        .map_err(|_| eyre!("connection to dora-daemon closed before the service replied"))?
+        .map_err(|err| eyre!(err))
+        .wrap_err("service call failed")?;
+    let data = match response.data {
+        None => RawData::Empty,
+        Some(DataMessage::Vec(data)) => RawData::Vec(data),
+        Some(DataMessage::SharedMemory { .. }) => {
+            bail!("service replies must not use shared memory")
+        }
+    };
+    let data = data
+        .into_arrow_array(&response.metadata.type_info)
+        .map(arrow::array::make_array)?;
+    Ok((response.metadata, data.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_out_and_forget_calls() {
+        let pending_calls = PendingCalls::default();
+        let (reply_tx, reply_rx) = flume::bounded(1);
+        pending_calls.lock().unwrap().insert(7, reply_tx);
+        let timeout = Duration::from_millis(10);
+        let call = ServiceCall {
+            request_id: 7,
+            pending_calls: pending_calls.clone(),
+            reply: reply_rx.into_recv_async(),
+            timeout,
+            deadline: Delay::new(timeout),
+        };
+
+        let err = call.wait().unwrap_err();
+        assert!(err.to_string().contains("did not reply"), "{err}");
+        assert!(pending_calls.lock().unwrap().is_empty());
+    }
+}
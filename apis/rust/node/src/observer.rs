@@ -0,0 +1,168 @@
+//! Read-only connections to a dora daemon.
+//!
+//! Observers receive the outputs of a running dataflow without being part of it.
+//! They can't send outputs or control the dataflow, which makes them safe to use
+//! for monitoring tools such as dashboards or `dora tap`.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    net::{Ipv4Addr, SocketAddr},
+};
+
+use dora_core::{
+    config::{DataId, NodeId},
+    daemon_messages::{
+        DaemonReply, DaemonRequest, DataMessage, DataflowId, NodeEvent, Timestamped,
+    },
+    message::uhlc,
+    topics::DORA_DAEMON_OBSERVER_PORT_DEFAULT,
+};
+use eyre::{bail, eyre, Context};
+
+use crate::{daemon_connection::DaemonChannel, Event, RawData};
+
+pub struct Observer {
+    channel: DaemonChannel,
+    queue: VecDeque<Timestamped<NodeEvent>>,
+    clock: uhlc::HLC,
+    finished: bool,
+}
+
+impl Observer {
+    /// Connects to the daemon on the local machine and observes the given outputs
+    /// of the given dataflow.
+    ///
+    /// All outputs are observed if the `outputs` set is empty.
+    pub fn connect(
+        dataflow_id: DataflowId,
+        outputs: BTreeSet<(NodeId, DataId)>,
+    ) -> eyre::Result<Self> {
+        let daemon_addr = SocketAddr::new(
+            Ipv4Addr::LOCALHOST.into(),
+            DORA_DAEMON_OBSERVER_PORT_DEFAULT,
+        );
+        Self::connect_to(daemon_addr, dataflow_id, outputs)
+    }
+
+    /// Connects to the observer port of the daemon at the given address.
+    pub fn connect_to(
+        daemon_addr: SocketAddr,
+        dataflow_id: DataflowId,
+        outputs: BTreeSet<(NodeId, DataId)>,
+    ) -> eyre::Result<Self> {
+        let mut channel = DaemonChannel::new_tcp(daemon_addr)
+            .wrap_err("failed to connect to observer port of dora-daemon")?;
+        let clock = uhlc::HLC::default();
+        let reply = channel
+            .request(&Timestamped {
+                inner: DaemonRequest::RegisterObserver {
+                    dataflow_id,
+                    outputs,
+                    dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+                },
+                timestamp: clock.new_timestamp(),
+            })
+            .wrap_err("failed to send register request to dora-daemon")?;
+        match reply {
+            DaemonReply::Result(Ok(())) => {}
+            DaemonReply::Result(Err(err)) => bail!("failed to register observer: {err}"),
+            other => bail!("unexpected register reply: {other:?}"),
+        }
+
+        Ok(Self {
+            channel,
+            queue: VecDeque::new(),
+            clock,
+            finished: false,
+        })
+    }
+
+    /// Waits for the next observed output.
+    ///
+    /// The outputs are reported as [`Event::Input`] with an ID of the form
+    /// `<node_id>/<output_id>`. Returns `None` once the dataflow is finished.
+    pub fn recv(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                if let Some(event) = convert_event(event.inner) {
+                    return Some(event);
+                }
+                continue;
+            }
+            if self.finished {
+                return None;
+            }
+            match self.next_events() {
+                Ok(events) if events.is_empty() => self.finished = true,
+                Ok(events) => self.queue.extend(events),
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Event::Error(format!("{err:?}")));
+                }
+            }
+        }
+    }
+
+    fn next_events(&mut self) -> eyre::Result<Vec<Timestamped<NodeEvent>>> {
+        let reply = self
+            .channel
+            .request(&Timestamped {
+                inner: DaemonRequest::NextEvent {
+                    drop_tokens: Vec::new(),
+                },
+                timestamp: self.clock.new_timestamp(),
+            })
+            .wrap_err("failed to request next events from dora-daemon")?;
+        match reply {
+            DaemonReply::NextEvents(events) => Ok(events),
+            DaemonReply::Result(Err(err)) => Err(eyre!(err)),
+            other => bail!("unexpected NextEvent reply: {other:?}"),
+        }
+    }
+}
+
+fn convert_event(event: NodeEvent) -> Option<Event> {
+    match event {
+        NodeEvent::Input { id, metadata, data } => {
+            let raw_data = match data {
+                None => RawData::Empty,
+                Some(DataMessage::Vec(data)) => RawData::Vec(data),
+                Some(DataMessage::SharedMemory { .. }) => {
+                    return Some(Event::Error(format!(
+                        "observer received shared memory data for `{id}`"
+                    )))
+                }
+            };
+            let event = match raw_data.into_arrow_array(&metadata.type_info) {
+                Ok(data) => Event::Input {
+                    id,
+                    metadata,
+                    data: arrow::array::make_array(data).into(),
+                },
+                Err(err) => Event::Error(format!("{err:?}")),
+            };
+            Some(event)
+        }
+        NodeEvent::InputClosed { id } => Some(Event::InputClosed { id }),
+        NodeEvent::Stop => Some(Event::Stop),
+        NodeEvent::Reload { .. }
+        | NodeEvent::AllInputsClosed
+        | NodeEvent::ServiceRequest { .. }
+        | NodeEvent::ParameterUpdate { .. }
+        | NodeEvent::OutputUnsubscribed { .. }
+        | NodeEvent::OutputSubscribed { .. }
+        | NodeEvent::SimTime { .. } => None,
+    }
+}
+
+impl Drop for Observer {
+    fn drop(&mut self) {
+        let request = Timestamped {
+            inner: DaemonRequest::EventStreamDropped,
+            timestamp: self.clock.new_timestamp(),
+        };
+        if let Err(err) = self.channel.request(&request) {
+            tracing::debug!("failed to close observer connection: {err:?}");
+        }
+    }
+}
@@ -21,6 +21,7 @@
 pub use dora_arrow_convert::*;
 pub use dora_operator_api_macros::register_operator;
 pub use dora_operator_api_types as types;
+use std::time::Duration;
 pub use types::DoraStatus;
 use types::{
     arrow::{self, array::Array},
@@ -35,6 +36,7 @@ pub enum Event<'a> {
     Input { id: &'a str, data: ArrowData },
     InputParseError { id: &'a str, error: String },
     InputClosed { id: &'a str },
+    DeadlineMissed { id: &'a str, elapsed: Duration },
     Stop,
 }
 
@@ -66,4 +68,100 @@ impl DoraOutputSender<'_> {
         });
         result.into_result()
     }
+
+    /// Sends the given bytes as an Arrow `UInt8` array.
+    ///
+    /// Eases the migration of operators that were written against the
+    /// byte-based API of older dora versions.
+    pub fn send_bytes(&mut self, id: String, data: Vec<u8>) -> Result<(), String> {
+        self.send(id, data.into_arrow())
+    }
+
+    /// Allocates a byte sample of `len` bytes for the given output, which can be
+    /// filled incrementally before it is sent.
+    ///
+    /// The returned [`PendingOutput`] must be passed to either
+    /// [`commit_output`](Self::commit_output) to send it or
+    /// [`abort_output`](Self::abort_output) to discard it, e.g. when an error
+    /// occurs while filling it. Dropping it discards it too.
+    pub fn begin_output(&mut self, id: String, len: usize) -> PendingOutput {
+        PendingOutput {
+            id,
+            data: vec![0; len],
+        }
+    }
+
+    /// Sends the given pending output as an Arrow `UInt8` array.
+    pub fn commit_output(&mut self, output: PendingOutput) -> Result<(), String> {
+        self.send_bytes(output.id, output.data)
+    }
+
+    /// Discards the given pending output without sending it.
+    pub fn abort_output(&mut self, output: PendingOutput) {
+        drop(output);
+    }
+}
+
+/// An output sample that is filled before it is sent, see
+/// [`DoraOutputSender::begin_output`].
+#[derive(Debug)]
+pub struct PendingOutput {
+    id: String,
+    data: Vec<u8>,
+}
+
+impl PendingOutput {
+    pub fn output_id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl std::ops::Deref for PendingOutput {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl std::ops::DerefMut for PendingOutput {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use types::{safer_ffi::closure::ArcDynFn1, DoraResult};
+
+    #[test]
+    fn commit_and_abort_outputs() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sent_clone = sent.clone();
+        let send_output = SendOutput {
+            send_output: ArcDynFn1::new(Arc::new(move |output: Output| {
+                let data = arrow::ffi::from_ffi(output.data_array, &output.schema).unwrap();
+                let data = arrow::array::make_array(data);
+                let bytes: Vec<u8> = TryFrom::try_from(&ArrowData(data)).unwrap();
+                sent_clone
+                    .lock()
+                    .unwrap()
+                    .push(((*output.id).to_owned(), bytes));
+                DoraResult::SUCCESS
+            })),
+        };
+        let mut sender = DoraOutputSender(&send_output);
+
+        let mut aborted = sender.begin_output("image".to_owned(), 4);
+        aborted[..2].copy_from_slice(&[1, 2]);
+        sender.abort_output(aborted);
+        let mut committed = sender.begin_output("image".to_owned(), 3);
+        committed.copy_from_slice(&[3, 4, 5]);
+        assert_eq!(committed.output_id(), "image");
+        sender.commit_output(committed).unwrap();
+
+        assert_eq!(*sent.lock().unwrap(), [("image".to_owned(), vec![3, 4, 5])]);
+    }
 }
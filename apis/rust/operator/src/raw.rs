@@ -2,7 +2,7 @@ use crate::{DoraOperator, DoraOutputSender, DoraStatus, Event};
 use dora_operator_api_types::{
     arrow, DoraInitResult, DoraResult, OnEventResult, RawEvent, SendOutput,
 };
-use std::ffi::c_void;
+use std::{ffi::c_void, time::Duration};
 
 pub type OutputFnRaw = unsafe extern "C" fn(
     id_start: *const u8,
@@ -58,6 +58,11 @@ pub unsafe fn dora_on_event<O: DoraOperator>(
         }
     } else if let Some(input_id) = &event.input_closed {
         Event::InputClosed { id: input_id }
+    } else if let Some(deadline_missed) = &event.deadline_missed {
+        Event::DeadlineMissed {
+            id: &deadline_missed.input_id,
+            elapsed: Duration::from_nanos(deadline_missed.elapsed_ns),
+        }
     } else if event.stop {
         Event::Stop
     } else {
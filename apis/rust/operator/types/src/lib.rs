@@ -24,6 +24,45 @@ pub struct DoraInitOperator {
     pub init_operator: unsafe extern "C" fn() -> DoraInitResult,
 }
 
+/// Alternative to [`DoraInitOperator`] that gives the operator access to its
+/// configuration.
+///
+/// If a library exports `dora_init_operator_with_context`, the runtime calls it
+/// instead of `dora_init_operator`.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+pub struct DoraInitOperatorWithContext {
+    pub init_operator_with_context:
+        unsafe extern "C" fn(context: *const DoraInitContext) -> DoraInitResult,
+}
+
+/// Configuration of an operator, passed on initialization.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug)]
+pub struct DoraInitContext {
+    /// Environment variables of the operator, including its resolved secrets.
+    pub env: safer_ffi::Vec<EnvVar>,
+    /// Parameters of the operator as specified in the dataflow, encoded as JSON.
+    ///
+    /// Set to `null` if the operator has no parameters.
+    pub params: safer_ffi::String,
+    /// Resolved queue configuration of each input, encoded as a JSON object
+    /// that maps input IDs to e.g. their `queue_size`, `policy`, and `ttl`.
+    pub input_config: safer_ffi::String,
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug)]
+pub struct EnvVar {
+    pub key: safer_ffi::String,
+    pub value: safer_ffi::String,
+}
+
 #[derive_ReprC]
 #[ffi_export]
 #[repr(C)]
@@ -98,6 +137,18 @@ pub struct RawEvent {
     pub input_closed: Option<safer_ffi::String>,
     pub stop: bool,
     pub error: Option<safer_ffi::String>,
+    pub deadline_missed: Option<safer_ffi::boxed::Box<DeadlineMissed>>,
+}
+
+/// An input that received no message within its configured `deadline`.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug)]
+pub struct DeadlineMissed {
+    pub input_id: safer_ffi::String,
+    /// Nanoseconds since the last message of the input.
+    pub elapsed_ns: u64,
 }
 
 #[derive_ReprC]
@@ -174,6 +225,49 @@ pub fn dora_read_data(input: &mut Input) -> Option<safer_ffi::Vec<u8>> {
 #[ffi_export]
 pub fn dora_free_data(_data: safer_ffi::Vec<u8>) {}
 
+/// Moves the data of the input into the given structs of the
+/// [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html).
+///
+/// `array_out` and `schema_out` must point to an uninitialized `ArrowArray` and
+/// `ArrowSchema`. On success, the caller must release both of them after use.
+/// The data can only be read once, either through this function or through
+/// [`dora_read_data`].
+#[ffi_export]
+pub unsafe fn dora_read_input_arrow(
+    input: &mut Input,
+    array_out: *mut std::ffi::c_void,
+    schema_out: *mut std::ffi::c_void,
+) -> DoraResult {
+    let Some(data_array) = input.data_array.take() else {
+        return DoraResult::from_error("data already taken".into());
+    };
+    let result =
+        arrow::ffi::from_ffi(data_array, &input.schema).and_then(|data| arrow::ffi::to_ffi(&data));
+    match result {
+        Ok((array, schema)) => {
+            unsafe {
+                std::ptr::write(array_out.cast::<FFI_ArrowArray>(), array);
+                std::ptr::write(schema_out.cast::<FFI_ArrowSchema>(), schema);
+            }
+            DoraResult::SUCCESS
+        }
+        Err(err) => DoraResult::from_error(err.to_string()),
+    }
+}
+
+#[ffi_export]
+pub fn dora_read_open_telemetry_context(input: &Input) -> char_p_boxed {
+    char_p::new(&*input.metadata.open_telemetry_context)
+}
+
+#[ffi_export]
+pub fn dora_result_from_error(error: safer_ffi::char_p::char_p_ref<'_>) -> DoraResult {
+    DoraResult::from_error(error.to_str().to_owned())
+}
+
+#[ffi_export]
+pub fn dora_free_result(_result: DoraResult) {}
+
 #[ffi_export]
 pub unsafe fn dora_send_operator_output(
     send_output: &SendOutput,
@@ -204,8 +298,116 @@ pub unsafe fn dora_send_operator_output(
     }
 }
 
+/// Sends an Arrow array on the given output.
+///
+/// `array` and `schema` must point to an `ArrowArray` and an `ArrowSchema` of
+/// the [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html).
+/// The function takes ownership of both, so they are released afterwards,
+/// also on errors. The data type of the array is sent along with the data,
+/// so receivers get a typed array instead of raw bytes.
+#[ffi_export]
+pub unsafe fn dora_send_operator_output_arrow(
+    send_output: &SendOutput,
+    id: safer_ffi::char_p::char_p_ref<'_>,
+    array: *mut std::ffi::c_void,
+    schema: *mut std::ffi::c_void,
+) -> DoraResult {
+    // move the structs out, leaving released structs behind
+    let data_array =
+        unsafe { std::ptr::replace(array.cast::<FFI_ArrowArray>(), FFI_ArrowArray::empty()) };
+    let schema =
+        unsafe { std::ptr::replace(schema.cast::<FFI_ArrowSchema>(), FFI_ArrowSchema::empty()) };
+    let output = Output {
+        id: id.to_str().to_owned().into(),
+        data_array,
+        schema,
+        metadata: Metadata {
+            open_telemetry_context: String::new().into(),
+        },
+    };
+    send_output.send_output.call(output)
+}
+
 pub fn generate_headers(target_file: &Path) -> ::std::io::Result<()> {
     ::safer_ffi::headers::builder()
         .to_file(target_file)?
         .generate()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float32Array, StringArray};
+    use safer_ffi::closure::ArcDynFn1;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn send_arrow_output() {
+        let received = Arc::new(Mutex::new(None));
+        let received_clone = received.clone();
+        let send_output = SendOutput {
+            send_output: ArcDynFn1::new(Arc::new(move |output: Output| {
+                let data = arrow::ffi::from_ffi(output.data_array, &output.schema).unwrap();
+                *received_clone.lock().unwrap() = Some(arrow::array::make_array(data));
+                DoraResult::SUCCESS
+            })),
+        };
+
+        let (mut array, mut schema) =
+            arrow::ffi::to_ffi(&Float32Array::from(vec![1.0, 2.5]).into_data()).unwrap();
+        let id = char_p::new("values");
+        let result = unsafe {
+            dora_send_operator_output_arrow(
+                &send_output,
+                id.as_ref(),
+                (&mut array as *mut FFI_ArrowArray).cast(),
+                (&mut schema as *mut FFI_ArrowSchema).cast(),
+            )
+        };
+        assert!(result.error.is_none());
+        let received = received.lock().unwrap().take().unwrap();
+        assert_eq!(
+            received.as_any().downcast_ref::<Float32Array>(),
+            Some(&Float32Array::from(vec![1.0, 2.5]))
+        );
+        // ownership was moved, so the caller's structs are released
+        assert!(array.is_released());
+    }
+
+    #[test]
+    fn read_arrow_input() {
+        let (data_array, schema) =
+            arrow::ffi::to_ffi(&StringArray::from(vec!["a", "b"]).into_data()).unwrap();
+        let mut input = Input {
+            id: "text".to_owned().into(),
+            data_array: Some(data_array),
+            schema,
+            metadata: Metadata {
+                open_telemetry_context: String::new().into(),
+            },
+        };
+
+        let mut array = FFI_ArrowArray::empty();
+        let mut schema = FFI_ArrowSchema::empty();
+        let result = unsafe {
+            dora_read_input_arrow(
+                &mut input,
+                (&mut array as *mut FFI_ArrowArray).cast(),
+                (&mut schema as *mut FFI_ArrowSchema).cast(),
+            )
+        };
+        assert!(result.error.is_none());
+        let data = arrow::ffi::from_ffi(array, &schema).unwrap();
+        assert_eq!(StringArray::from(data), StringArray::from(vec!["a", "b"]));
+
+        let mut array = FFI_ArrowArray::empty();
+        let result = unsafe {
+            dora_read_input_arrow(
+                &mut input,
+                (&mut array as *mut FFI_ArrowArray).cast(),
+                (&mut schema as *mut FFI_ArrowSchema).cast(),
+            )
+        };
+        assert!(result.error.is_some());
+    }
+}
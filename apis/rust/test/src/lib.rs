@@ -0,0 +1,373 @@
+//! Harness for unit tests of dora operators.
+//!
+//! Runs a single operator in-process, using the same code as `dora-runtime`,
+//! but without a coordinator or daemon. Tests feed the operator with events and
+//! assert on the outputs it sends and on the reason why it stopped:
+//!
+//! ```no_run
+//! use dora_test::{arrow::array::UInt64Array, OperatorTest, StopReason};
+//! use std::time::Duration;
+//!
+//! let mut test = OperatorTest::from_yaml(
+//!     r#"
+//!     id: counter
+//!     shared-library: target/debug/counter
+//!     inputs:
+//!       tick: dora/timer/millis/100
+//!     outputs:
+//!       - count
+//!     "#,
+//! )?;
+//! test.send_input("tick", UInt64Array::from(vec![1]))?;
+//! let output = test.next_output(Duration::from_secs(1))?.expect("no output");
+//! assert_eq!(output.id.as_str(), "count");
+//!
+//! let run = test.finish()?;
+//! assert!(matches!(run.stop_reason, StopReason::InputsClosed));
+//! # eyre::Ok(())
+//! ```
+//!
+//! Python operators are supported if the `python` feature is enabled.
+
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{
+    config::DataId,
+    descriptor::{Descriptor, OperatorDefinition, OperatorSource},
+};
+use dora_node_api::{
+    arrow::array::{Array, ArrayData},
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    uhlc, ArrowData, DataSample, Event, Metadata, MetadataParameters, RawData,
+};
+use dora_runtime::testing::{spawn_operator, LogStream, OperatorEvent, TestOperator};
+use eyre::{eyre, Context, ContextCompat};
+use std::{thread::JoinHandle, time::Duration};
+use tokio::sync::mpsc;
+use uuid::{NoContext, Timestamp, Uuid};
+
+pub use dora_node_api::arrow;
+pub use dora_runtime::testing::StopReason;
+
+/// Runs an operator for a test.
+///
+/// Events pass through the same input queues as in a dataflow, so inputs are
+/// dropped when the operator falls behind their `queue_size`.
+pub struct OperatorTest {
+    operator: OperatorDefinition,
+    incoming: Option<flume::Sender<Event>>,
+    events: mpsc::Receiver<OperatorEvent>,
+    runtime: tokio::runtime::Runtime,
+    clock: uhlc::HLC,
+    thread: JoinHandle<eyre::Result<()>>,
+    stop_reason: Option<StopReason>,
+}
+
+impl OperatorTest {
+    /// Starts the operator described by the given YAML, using the syntax of an
+    /// operator entry in a dataflow file.
+    pub fn from_yaml(definition: &str) -> eyre::Result<Self> {
+        let definition =
+            serde_yaml::from_str(definition).wrap_err("failed to parse operator definition")?;
+        Self::new(definition)
+    }
+
+    /// Starts the given operator and waits until it is initialized.
+    pub fn new(operator: OperatorDefinition) -> eyre::Result<Self> {
+        // the operator is not part of a dataflow, so an empty one is passed
+        let descriptor: Descriptor =
+            serde_yaml::from_str("nodes: []").wrap_err("failed to create dataflow descriptor")?;
+        Self::with_dataflow(operator, descriptor)
+    }
+
+    /// Starts the given operator with the dataflow-wide options of the given
+    /// descriptor, e.g. `deterministic`, and waits until it is initialized.
+    pub fn with_dataflow(
+        operator: OperatorDefinition,
+        descriptor: Descriptor,
+    ) -> eyre::Result<Self> {
+        match &operator.config.source {
+            OperatorSource::SharedLibrary(_) => {}
+            OperatorSource::Python(_) => {
+                #[cfg(feature = "python")]
+                pyo3::prepare_freethreaded_python();
+                #[cfg(not(feature = "python"))]
+                eyre::bail!("testing Python operators requires the `python` feature");
+            }
+            OperatorSource::Wasm(_) => eyre::bail!("WASM operators are not supported"),
+        }
+
+        // the input queues of the operator run in the background
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .wrap_err("failed to build tokio runtime")?;
+        let env = operator
+            .config
+            .env
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.to_string()))
+            .collect();
+
+        let TestOperator {
+            incoming,
+            events,
+            init_done,
+            thread,
+        } = spawn_operator(
+            runtime.handle(),
+            Uuid::new_v7(Timestamp::now(NoContext)),
+            operator.clone(),
+            descriptor,
+            env,
+        )?;
+
+        match runtime.block_on(init_done) {
+            Ok(result) => result.wrap_err("failed to init operator")?,
+            Err(_) => {
+                // the operator exited before its initialization finished
+                match thread.join() {
+                    Ok(result) => result?,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+                eyre::bail!("operator exited during init");
+            }
+        }
+
+        Ok(Self {
+            operator,
+            incoming: Some(incoming),
+            events,
+            runtime,
+            clock: uhlc::HLC::default(),
+            thread,
+            stop_reason: None,
+        })
+    }
+
+    /// Sends an input with default metadata to the operator.
+    pub fn send_input(&mut self, id: &str, data: impl Array) -> eyre::Result<()> {
+        self.send_input_with_parameters(id, MetadataParameters::default(), data.to_data())
+    }
+
+    /// Sends an input with the given metadata parameters to the operator.
+    ///
+    /// The data is converted in the same way as data that a node sends, e.g.
+    /// the buffers of the array are aligned.
+    pub fn send_input_with_parameters(
+        &mut self,
+        id: &str,
+        parameters: MetadataParameters,
+        data: ArrayData,
+    ) -> eyre::Result<()> {
+        let mut sample = vec![0; required_data_size(&data)];
+        let type_info = copy_array_into_sample(&mut sample, &data);
+        let raw = RawData::Vec(AVec::from_slice(128, &sample));
+        let data = raw
+            .into_arrow_array(&type_info)
+            .wrap_err("failed to convert input data")?;
+        let metadata = Metadata::from_parameters(self.clock.new_timestamp(), type_info, parameters);
+        self.send_event(Event::Input {
+            id: DataId::from(id.to_owned()),
+            metadata,
+            data: ArrowData(arrow::array::make_array(data)),
+        })
+    }
+
+    /// Notifies the operator that the given input is closed.
+    pub fn close_input(&mut self, id: &str) -> eyre::Result<()> {
+        self.send_event(Event::InputClosed {
+            id: DataId::from(id.to_owned()),
+        })
+    }
+
+    /// Sends a stop event to the operator.
+    pub fn stop(&mut self) -> eyre::Result<()> {
+        self.send_event(Event::Stop)
+    }
+
+    /// Sends an arbitrary event to the operator.
+    pub fn send_event(&mut self, event: Event) -> eyre::Result<()> {
+        self.incoming
+            .as_ref()
+            .wrap_err("inputs of operator are closed already")?
+            .send(event)
+            .map_err(|_| eyre!("operator `{}` is not running anymore", self.operator.id))
+    }
+
+    /// Waits for the next output of the operator.
+    ///
+    /// Returns `None` if the operator sends no output within the given timeout
+    /// or if it stopped.
+    pub fn next_output(&mut self, timeout: Duration) -> eyre::Result<Option<TestOutput>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.stop_reason.is_none() {
+            let recv = self.events.recv();
+            let event = match self
+                .runtime
+                .block_on(tokio::time::timeout_at(deadline, recv))
+            {
+                Ok(Some(event)) => event,
+                Ok(None) | Err(_) => break,
+            };
+            if let Some(output) = self.handle_event(event)? {
+                return Ok(Some(output));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Closes all inputs and waits until the operator stops.
+    ///
+    /// Returns the outputs that were not retrieved through
+    /// [`next_output`](Self::next_output) yet, together with the stop reason.
+    pub fn finish(mut self) -> eyre::Result<TestRun> {
+        self.incoming = None;
+        let mut outputs = Vec::new();
+        while let Some(event) = self.events.blocking_recv() {
+            outputs.extend(self.handle_event(event)?);
+        }
+        match self.thread.join() {
+            Ok(result) => result.wrap_err("failed to run operator")?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+        let stop_reason = self
+            .stop_reason
+            .wrap_err("operator exited without stop reason")?;
+        Ok(TestRun {
+            outputs,
+            stop_reason,
+        })
+    }
+
+    fn handle_event(&mut self, event: OperatorEvent) -> eyre::Result<Option<TestOutput>> {
+        match event {
+            OperatorEvent::AllocateOutputSample { len, sample } => {
+                let data: AVec<u8, ConstAlign<128>> =
+                    AVec::from_iter(128, std::iter::repeat(0).take(len));
+                let _ = sample.send(Ok(DataSample::from(data)));
+            }
+            OperatorEvent::DiscardOutputSample { .. } => {}
+            OperatorEvent::Output {
+                output_id,
+                type_info,
+                parameters,
+                data,
+            } => {
+                let raw = match data.as_deref() {
+                    Some(data) => RawData::Vec(AVec::from_slice(128, data)),
+                    None => RawData::Empty,
+                };
+                let data = raw
+                    .into_arrow_array(&type_info)
+                    .wrap_err_with(|| format!("failed to convert output `{output_id}`"))?;
+                let metadata =
+                    Metadata::from_parameters(self.clock.new_timestamp(), type_info, parameters);
+                return Ok(Some(TestOutput {
+                    id: output_id,
+                    metadata,
+                    data: ArrowData(arrow::array::make_array(data)),
+                }));
+            }
+            OperatorEvent::ServiceReply { .. } => {
+                // service calls are not supported by the harness, so there is
+                // nobody waiting for the reply
+            }
+            OperatorEvent::Log { stream, message } => {
+                let line = format!("[{}] {message}", self.operator.id);
+                match stream {
+                    LogStream::Stdout => println!("{line}"),
+                    LogStream::Stderr => eprintln!("{line}"),
+                }
+            }
+            OperatorEvent::Error(err) => {
+                return Err(err.wrap_err(format!("operator `{}` raised an error", self.operator.id)))
+            }
+            OperatorEvent::Panic(payload) => std::panic::resume_unwind(payload),
+            OperatorEvent::Finished { reason } => self.stop_reason = Some(reason),
+        }
+        Ok(None)
+    }
+}
+
+/// An output that the operator sent.
+#[derive(Debug)]
+pub struct TestOutput {
+    pub id: DataId,
+    pub metadata: Metadata,
+    pub data: ArrowData,
+}
+
+/// Result of [`OperatorTest::finish`].
+#[derive(Debug)]
+pub struct TestRun {
+    pub outputs: Vec<TestOutput>,
+    pub stop_reason: StopReason,
+}
+
+#[cfg(all(test, feature = "python"))]
+mod tests {
+    use super::*;
+    use dora_node_api::arrow::array::UInt64Array;
+
+    const OPERATOR: &str = "\
+import enum
+
+class Status(enum.Enum):
+    CONTINUE = 0
+    STOP = 1
+
+class Operator:
+    def on_event(self, event, send_output):
+        if event['type'] == 'INPUT_BATCH':
+            send_output('batch', event['id'].encode())
+        elif event['type'] == 'INPUT' and event['id'] == 'stop':
+            return Status.STOP
+        return Status.CONTINUE
+";
+
+    fn operator_test(name: &str) -> OperatorTest {
+        let dir = std::env::temp_dir().join(format!("dora-test-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("operator.py");
+        std::fs::write(&path, OPERATOR).unwrap();
+        OperatorTest::from_yaml(&format!(
+            r#"
+            id: op
+            python: {}
+            inputs:
+              tick:
+                source: dora/timer/millis/100
+                batch:
+                  max_size: 2
+              stop: dora/timer/secs/1
+            outputs:
+              - batch
+            "#,
+            path.display()
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn batch_inputs_like_the_runtime() {
+        let mut test = operator_test("batch");
+        test.send_input("tick", UInt64Array::from(vec![1])).unwrap();
+        test.send_input("tick", UInt64Array::from(vec![2])).unwrap();
+        let output = test.next_output(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(output.id.as_str(), "batch");
+
+        let run = test.finish().unwrap();
+        assert!(run.outputs.is_empty());
+        assert!(matches!(run.stop_reason, StopReason::InputsClosed));
+    }
+
+    #[test]
+    fn report_explicit_stop() {
+        let mut test = operator_test("stop");
+        test.send_input("stop", UInt64Array::from(vec![1])).unwrap();
+        let run = test.finish().unwrap();
+        assert!(matches!(run.stop_reason, StopReason::ExplicitStop));
+    }
+}
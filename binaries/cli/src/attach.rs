@@ -1,16 +1,26 @@
 use communication_layer_request_reply::TcpRequestReplyConnection;
 use dora_core::{
+    config::{NodeId, OperatorId},
     descriptor::{resolve_path, CoreNodeKind, Descriptor},
     topics::{ControlRequest, ControlRequestReply},
 };
 use eyre::Context;
 use notify::event::ModifyKind;
 use notify::{Config, Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashMap;
-use std::{path::PathBuf, sync::mpsc, time::Duration};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
 use tracing::{error, info};
 use uuid::Uuid;
 
+/// Editors often write a file in multiple steps, so operators are only reloaded
+/// once their source did not change for this interval.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
 pub fn attach_dataflow(
     dataflow: Descriptor,
     dataflow_path: PathBuf,
@@ -45,6 +55,8 @@ pub fn attach_dataflow(
                             .wrap_err_with(|| {
                                 format!("failed to resolve node source `{}`", python_source.source)
                             })?;
+                        // file events are reported for canonical paths
+                        let path = path.canonicalize().unwrap_or(path);
                         node_path_lookup
                             .insert(path, (dataflow_id, node.id.clone(), Some(op.id.clone())));
                     }
@@ -56,29 +68,40 @@ pub fn attach_dataflow(
 
     // Setup dataflow file watcher if reload option is set.
     let watcher_tx = tx.clone();
+    let mut node_logs = hot_reload.then(|| {
+        NodeLogs::new(
+            dataflow_id,
+            node_path_lookup
+                .values()
+                .map(|(_, node_id, _)| node_id.clone()),
+        )
+    });
     let _watcher = if hot_reload {
-        let hash = node_path_lookup.clone();
-        let paths = hash.keys();
+        // watch the directories instead of the files because editors often
+        // save files by replacing them
+        let directories: BTreeSet<_> = node_path_lookup
+            .keys()
+            .filter_map(|path| path.parent())
+            .map(|dir| dir.to_owned())
+            .collect();
+        let sources: BTreeSet<_> = node_path_lookup.keys().cloned().collect();
+        let (changes_tx, changes_rx) = mpsc::channel();
+        std::thread::spawn(move || debounce_reloads(changes_rx, node_path_lookup, watcher_tx));
         let notifier = move |event| {
-            if let Ok(NotifyEvent {
-                paths,
-                kind: EventKind::Modify(ModifyKind::Data(_data)),
-                ..
-            }) = event
-            {
-                for path in paths {
-                    if let Some((dataflow_id, node_id, operator_id)) = node_path_lookup.get(&path) {
-                        watcher_tx
-                            .send(ControlRequest::Reload {
-                                dataflow_id: *dataflow_id,
-                                node_id: node_id.clone(),
-                                operator_id: operator_id.clone(),
-                            })
-                            .context("Could not send reload request to the cli loop")
-                            .unwrap();
-                    }
-                }
-                // TODO: Manage different file event
+            let Ok(NotifyEvent { paths, kind, .. }) = event else {
+                return;
+            };
+            if !matches!(
+                kind,
+                EventKind::Create(_)
+                    | EventKind::Modify(
+                        ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any
+                    )
+            ) {
+                return;
+            }
+            for path in paths.into_iter().filter(|path| sources.contains(path)) {
+                let _ = changes_tx.send(path);
             }
         };
 
@@ -87,9 +110,12 @@ pub fn attach_dataflow(
             Config::default().with_poll_interval(Duration::from_secs(1)),
         )?;
 
-        for path in paths {
-            watcher.watch(path, RecursiveMode::Recursive)?;
+        for directory in &directories {
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .wrap_err_with(|| format!("failed to watch `{}`", directory.display()))?;
         }
+        println!("watching Python operators for changes, press Ctrl-C to stop the dataflow");
         Some(watcher)
     } else {
         None
@@ -117,11 +143,24 @@ pub fn attach_dataflow(
 
     loop {
         let control_request = match rx.recv_timeout(Duration::from_secs(1)) {
-            Err(_err) => ControlRequest::Check {
-                dataflow_uuid: dataflow_id,
-            },
+            Err(_err) => {
+                if let Some(node_logs) = &mut node_logs {
+                    node_logs.print_new_lines(session);
+                }
+                ControlRequest::Check {
+                    dataflow_uuid: dataflow_id,
+                }
+            }
             Ok(reload_event) => reload_event,
         };
+        if let ControlRequest::Reload {
+            node_id,
+            operator_id: Some(operator_id),
+            ..
+        } = &control_request
+        {
+            println!("source of `{node_id}/{operator_id}` changed, reloading");
+        }
 
         let reply_raw = session
             .request(&serde_json::to_vec(&control_request)?)
@@ -130,8 +169,13 @@ pub fn attach_dataflow(
             serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
         match result {
             ControlRequestReply::DataflowStarted { uuid: _ } => (),
-            ControlRequestReply::DataflowStopped { uuid, result } => {
+            ControlRequestReply::DataflowStopped {
+                uuid,
+                result,
+                report,
+            } => {
                 info!("dataflow {uuid} stopped");
+                crate::print_rate_report(&report);
                 break result
                     .map_err(|err| eyre::eyre!(err))
                     .wrap_err("dataflow failed");
@@ -143,3 +187,122 @@ pub fn attach_dataflow(
         };
     }
 }
+
+/// Sends a reload request for each changed source once the source did not
+/// change for [`RELOAD_DEBOUNCE`].
+///
+/// Runs until the file watcher is dropped or the attach loop is finished.
+fn debounce_reloads(
+    changes: mpsc::Receiver<PathBuf>,
+    node_path_lookup: HashMap<PathBuf, (Uuid, NodeId, Option<OperatorId>)>,
+    requests: mpsc::SyncSender<ControlRequest>,
+) {
+    let mut last_change: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        let next_due = last_change
+            .values()
+            .min()
+            .map(|&last| last + RELOAD_DEBOUNCE);
+        let change = match next_due {
+            Some(due) => changes.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => changes.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match change {
+            Ok(path) => {
+                last_change.insert(path, Instant::now());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let due: Vec<_> = last_change
+            .iter()
+            .filter(|(_, &last)| now.duration_since(last) >= RELOAD_DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in due {
+            last_change.remove(&path);
+            let Some((dataflow_id, node_id, operator_id)) = node_path_lookup.get(&path) else {
+                continue;
+            };
+            let request = ControlRequest::Reload {
+                dataflow_id: *dataflow_id,
+                node_id: node_id.clone(),
+                operator_id: operator_id.clone(),
+            };
+            if requests.send(request).is_err() {
+                // the attach loop is finished already
+                return;
+            }
+        }
+    }
+}
+
+/// Follows the logs of the given nodes by polling the coordinator.
+///
+/// Only the new part of each log is requested. Logs of restarted nodes are
+/// appended to their previous log, so they never shrink.
+struct NodeLogs {
+    dataflow_id: Uuid,
+    /// Number of bytes of each log that were printed already.
+    printed: BTreeMap<NodeId, u64>,
+}
+
+impl NodeLogs {
+    fn new(dataflow_id: Uuid, nodes: impl IntoIterator<Item = NodeId>) -> Self {
+        Self {
+            dataflow_id,
+            printed: nodes.into_iter().map(|node| (node, 0)).collect(),
+        }
+    }
+
+    fn print_new_lines(&mut self, session: &mut TcpRequestReplyConnection) {
+        let mut stdout = std::io::stdout();
+        for (node_id, printed) in &mut self.printed {
+            let logs = match crate::logs::request_logs(
+                session,
+                Some(self.dataflow_id),
+                None,
+                node_id.to_string(),
+                *printed,
+            ) {
+                Ok(logs) => logs,
+                Err(err) => {
+                    // the node might not have written a log file yet
+                    tracing::debug!("failed to get logs of node `{node_id}`: {err:?}");
+                    continue;
+                }
+            };
+            let _ = stdout.write_all(&logs);
+            *printed += logs.len() as u64;
+        }
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_after_last_change() {
+        let path = PathBuf::from("/dataflow/op.py");
+        let node_id = NodeId::from("node".to_owned());
+        let lookup = HashMap::from([(path.clone(), (Uuid::nil(), node_id, None))]);
+        let (changes_tx, changes_rx) = mpsc::channel();
+        let (requests_tx, requests_rx) = mpsc::sync_channel(2);
+        std::thread::spawn(move || debounce_reloads(changes_rx, lookup, requests_tx));
+
+        let start = Instant::now();
+        changes_tx.send(path.clone()).unwrap();
+        std::thread::sleep(RELOAD_DEBOUNCE / 2);
+        changes_tx.send(path).unwrap();
+
+        let request = requests_rx.recv_timeout(RELOAD_DEBOUNCE * 4).unwrap();
+        assert!(matches!(request, ControlRequest::Reload { .. }));
+        // the reload is delayed until the last change is old enough
+        assert!(start.elapsed() >= RELOAD_DEBOUNCE * 3 / 2);
+        assert!(requests_rx.recv_timeout(RELOAD_DEBOUNCE * 2).is_err());
+    }
+}
@@ -1,11 +1,13 @@
 use dora_core::{
-    config::OperatorId,
+    config::{NodeId, OperatorId},
     descriptor::{Descriptor, SINGLE_OPERATOR_DEFAULT_ID},
 };
 use eyre::{eyre, Context};
-use std::{path::Path, process::Command};
+use std::{path::Path, process::Command, time::Instant};
 
-pub fn build(dataflow: &Path) -> eyre::Result<()> {
+use crate::progress::{OutputFormat, Progress, ProgressEvent};
+
+pub fn build(dataflow: &Path, output: OutputFormat) -> eyre::Result<()> {
     let descriptor = Descriptor::blocking_read(dataflow)?;
     let dataflow_absolute = if dataflow.is_relative() {
         std::env::current_dir().unwrap().join(dataflow)
@@ -15,36 +17,50 @@ pub fn build(dataflow: &Path) -> eyre::Result<()> {
     let working_dir = dataflow_absolute.parent().unwrap();
 
     let default_op_id = OperatorId::from(SINGLE_OPERATOR_DEFAULT_ID.to_string());
+    let mut progress = Progress::new(output);
 
     for node in &descriptor.nodes {
         match &node.kind {
             dora_core::descriptor::NodeKind::Runtime(runtime_node) => {
                 for operator in &runtime_node.operators {
-                    run_build_command(operator.config.build.as_deref(), working_dir).with_context(
-                        || {
-                            format!(
-                                "build command failed for operator `{}/{}`",
-                                node.id, operator.id
-                            )
-                        },
-                    )?;
-                }
-            }
-            dora_core::descriptor::NodeKind::Custom(custom_node) => {
-                run_build_command(custom_node.build.as_deref(), working_dir).with_context(|| {
-                    format!("build command failed for custom node `{}`", node.id)
-                })?
-            }
-            dora_core::descriptor::NodeKind::Operator(operator) => {
-                run_build_command(operator.config.build.as_deref(), working_dir).with_context(
-                    || {
+                    run_build_command(
+                        operator.config.build.as_deref(),
+                        working_dir,
+                        &node.id,
+                        Some(&operator.id),
+                        &mut progress,
+                    )
+                    .with_context(|| {
                         format!(
                             "build command failed for operator `{}/{}`",
-                            node.id,
-                            operator.id.as_ref().unwrap_or(&default_op_id)
+                            node.id, operator.id
                         )
-                    },
-                )?
+                    })?;
+                }
+            }
+            dora_core::descriptor::NodeKind::Custom(custom_node) => run_build_command(
+                custom_node.build.as_deref(),
+                working_dir,
+                &node.id,
+                None,
+                &mut progress,
+            )
+            .with_context(|| format!("build command failed for custom node `{}`", node.id))?,
+            dora_core::descriptor::NodeKind::Operator(operator) => {
+                let operator_id = operator.id.as_ref().unwrap_or(&default_op_id);
+                run_build_command(
+                    operator.config.build.as_deref(),
+                    working_dir,
+                    &node.id,
+                    Some(operator_id),
+                    &mut progress,
+                )
+                .with_context(|| {
+                    format!(
+                        "build command failed for operator `{}/{}`",
+                        node.id, operator_id
+                    )
+                })?
             }
         }
     }
@@ -52,25 +68,46 @@ pub fn build(dataflow: &Path) -> eyre::Result<()> {
     Ok(())
 }
 
-fn run_build_command(build: Option<&str>, working_dir: &Path) -> eyre::Result<()> {
-    if let Some(build) = build {
-        let mut split = build.split_whitespace();
-        let mut cmd = Command::new(
-            split
-                .next()
-                .ok_or_else(|| eyre!("build command is empty"))?,
-        );
-        cmd.args(split);
-        cmd.current_dir(working_dir);
-        let exit_status = cmd
-            .status()
-            .wrap_err_with(|| format!("failed to run `{}`", build))?;
-        if exit_status.success() {
-            Ok(())
-        } else {
-            Err(eyre!("build command returned an error code"))
-        }
-    } else {
+fn run_build_command(
+    build: Option<&str>,
+    working_dir: &Path,
+    node_id: &NodeId,
+    operator_id: Option<&OperatorId>,
+    progress: &mut Progress,
+) -> eyre::Result<()> {
+    let Some(build) = build else {
+        return Ok(());
+    };
+    progress.event(ProgressEvent::BuildStarted {
+        node: node_id.clone(),
+        operator: operator_id.cloned(),
+    });
+    let start = Instant::now();
+    let result = run_build_command_inner(build, working_dir);
+    progress.event(ProgressEvent::BuildFinished {
+        node: node_id.clone(),
+        operator: operator_id.cloned(),
+        duration_ms: start.elapsed().as_millis(),
+        error: result.as_ref().err().map(|err| format!("{err:#}")),
+    });
+    result
+}
+
+fn run_build_command_inner(build: &str, working_dir: &Path) -> eyre::Result<()> {
+    let mut split = build.split_whitespace();
+    let mut cmd = Command::new(
+        split
+            .next()
+            .ok_or_else(|| eyre!("build command is empty"))?,
+    );
+    cmd.args(split);
+    cmd.current_dir(working_dir);
+    let exit_status = cmd
+        .status()
+        .wrap_err_with(|| format!("failed to run `{}`", build))?;
+    if exit_status.success() {
         Ok(())
+    } else {
+        Err(eyre!("build command returned an error code"))
     }
 }
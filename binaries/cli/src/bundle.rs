@@ -0,0 +1,666 @@
+//! Packaging of a dataflow and the sources of its nodes into a single archive,
+//! which `dora start` runs without network access on other machines.
+//!
+//! The archive is a gzipped tarball with the resolved dataflow, all node and
+//! operator sources, and a manifest with the SHA-256 checksum of each file.
+//! Python operators are bundled with the other Python files of their directory
+//! and with wheels of their requirements, which are downloaded for the Python
+//! interpreter of the bundling machine.
+//!
+//! The checksums of the files are verified when the bundle is extracted, which
+//! detects corrupted bundles. As the manifest is part of the bundle, modified
+//! bundles are only detected when the checksum of the whole bundle is passed
+//! to `dora start --sha256`.
+
+use dora_core::{
+    adjust_shared_library_path,
+    descriptor::{
+        source_is_url, Descriptor, NodeKind, OperatorConfig, OperatorSource, SHELL_SOURCE,
+        SINGLE_OPERATOR_DEFAULT_ID,
+    },
+};
+use dora_download::download_file;
+use eyre::{bail, eyre, Context, ContextCompat};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    env::consts::EXE_EXTENSION,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+use uuid::{NoContext, Timestamp, Uuid};
+
+/// File extension of dataflow bundles.
+pub const EXTENSION: &str = "dorapkg";
+
+const DATAFLOW_FILE: &str = "dataflow.yml";
+const MANIFEST_FILE: &str = "manifest.yml";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    /// Version of the `dora` CLI that created the bundle.
+    dora_version: String,
+    /// SHA-256 checksums of all files of the bundle, including the dataflow.
+    files: BTreeMap<PathBuf, String>,
+    /// URLs of the files that were downloaded.
+    #[serde(default)]
+    downloads: BTreeMap<PathBuf, String>,
+}
+
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+/// Creates a bundle of the given dataflow at `output`.
+pub fn create(dataflow: &Path, output: &Path) -> eyre::Result<()> {
+    let descriptor =
+        Descriptor::blocking_read(dataflow).wrap_err("failed to read yaml dataflow")?;
+    let working_dir = dataflow
+        .canonicalize()
+        .context("failed to canonicalize dataflow path")?
+        .parent()
+        .ok_or_else(|| eyre!("dataflow path has no parent dir"))?
+        .to_owned();
+    descriptor
+        .check(&working_dir)
+        .wrap_err("could not validate yaml")?;
+    bundle_descriptor(descriptor, working_dir, output)
+}
+
+/// Bundles the given descriptor with the files that it references, without
+/// checking it first.
+fn bundle_descriptor(
+    mut descriptor: Descriptor,
+    working_dir: PathBuf,
+    output: &Path,
+) -> eyre::Result<()> {
+    let download_dir = create_private_dir("dora-bundle")?;
+    let mut sources = Sources {
+        working_dir,
+        download_dir: download_dir.clone(),
+        files: BTreeMap::new(),
+        downloads: BTreeMap::new(),
+        external: 0,
+        rt: tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("tokio runtime failed")?,
+    };
+    let result = sources
+        .collect(&mut descriptor)
+        .and_then(|()| write_bundle(&descriptor, &sources, output));
+    let _ = std::fs::remove_dir_all(&download_dir);
+    result?;
+
+    let archive =
+        std::fs::read(output).wrap_err_with(|| format!("failed to read `{}`", output.display()))?;
+    println!(
+        "bundled dataflow with {} files into `{}` (sha256: {})",
+        sources.files.len(),
+        output.display(),
+        checksum(&archive)
+    );
+    Ok(())
+}
+
+/// Extracts the given bundle into a new private directory after verifying its
+/// checksums.
+///
+/// If `expected_sha256` is given, the checksum of the whole bundle must match
+/// it.
+///
+/// Returns the path of the extracted dataflow file.
+pub fn extract(bundle: &Path, expected_sha256: Option<&str>) -> eyre::Result<PathBuf> {
+    let archive = std::fs::read(bundle)
+        .wrap_err_with(|| format!("failed to read bundle `{}`", bundle.display()))?;
+    if let Some(expected) = expected_sha256 {
+        let actual = checksum(&archive);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            bail!(
+                "checksum of bundle `{}` is {actual}, but {expected} was expected",
+                bundle.display()
+            );
+        }
+    }
+
+    let mut files = BTreeMap::new();
+    let mut entries = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+    for entry in entries.entries().context("failed to read bundle")? {
+        let mut entry = entry.context("failed to read bundle entry")?;
+        let path = entry.path().context("invalid path in bundle")?.into_owned();
+        if !is_contained(&path) {
+            bail!("bundle contains invalid path `{}`", path.display());
+        }
+        let mode = entry.header().mode().unwrap_or(0o644);
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut data)
+            .wrap_err_with(|| format!("failed to read `{}` from bundle", path.display()))?;
+        files.insert(path, (data, mode));
+    }
+
+    let (manifest, _) = files
+        .remove(Path::new(MANIFEST_FILE))
+        .context("bundle has no manifest")?;
+    let manifest: Manifest =
+        serde_yaml::from_slice(&manifest).context("failed to parse bundle manifest")?;
+    if manifest.dora_version != VERSION {
+        tracing::warn!(
+            "bundle was created by dora {}, but this is dora {VERSION}",
+            manifest.dora_version
+        );
+    }
+    for (path, (data, _)) in &files {
+        let expected = manifest.files.get(path).ok_or_else(|| {
+            eyre!(
+                "file `{}` is not part of the bundle manifest",
+                path.display()
+            )
+        })?;
+        if &checksum(data) != expected {
+            bail!(
+                "checksum mismatch for `{}`, the bundle is corrupted",
+                path.display()
+            );
+        }
+    }
+    if let Some(missing) = manifest
+        .files
+        .keys()
+        .find(|path| !files.contains_key(*path))
+    {
+        bail!("bundle is missing file `{}`", missing.display());
+    }
+
+    let target_dir = create_private_dir(&format!("dora-bundle-{}", &checksum(&archive)[..16]))?;
+    for (path, (data, mode)) in files {
+        let target = target_dir.join(&path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        std::fs::write(&target, data)
+            .wrap_err_with(|| format!("failed to extract `{}`", target.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode))
+                .wrap_err_with(|| format!("failed to set permissions of `{}`", target.display()))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+    }
+    Ok(target_dir.join(DATAFLOW_FILE))
+}
+
+/// The files that are added to the bundle.
+struct Sources {
+    working_dir: PathBuf,
+    download_dir: PathBuf,
+    /// Maps the paths in the bundle to the files on disk.
+    files: BTreeMap<PathBuf, PathBuf>,
+    downloads: BTreeMap<PathBuf, String>,
+    /// Number of files outside of the working directory.
+    external: usize,
+    rt: tokio::runtime::Runtime,
+}
+
+impl Sources {
+    /// Adds the sources of all nodes and points the descriptor to their paths
+    /// in the bundle.
+    fn collect(&mut self, descriptor: &mut Descriptor) -> eyre::Result<()> {
+        for node in &mut descriptor.nodes {
+            let node_id = node.id.to_string();
+            match &mut node.kind {
+                NodeKind::Custom(custom) => {
+                    let source = &mut custom.source;
+                    if *source == SHELL_SOURCE {
+                        continue;
+                    }
+                    if source_is_url(source) {
+                        let target = Path::new("downloads")
+                            .join(&node_id)
+                            .join(url_file_name(source));
+                        self.add_download(source, &target)?;
+                        *source = target.to_string_lossy().into_owned();
+                    } else if self
+                        .working_dir
+                        .join(executable(Path::new(source.as_str())))
+                        .is_file()
+                    {
+                        *source = self.add_local(source, |path| Ok(executable(path)))?;
+                    } else {
+                        tracing::warn!(
+                            "source `{source}` of node `{node_id}` is not a file, it must be \
+                            available on the `PATH` of the target machine"
+                        );
+                    }
+                }
+                NodeKind::Runtime(runtime) => {
+                    for operator in &mut runtime.operators {
+                        let dir = Path::new(&node_id).join(operator.id.to_string());
+                        self.add_operator(&dir, &mut operator.config)?;
+                    }
+                }
+                NodeKind::Operator(operator) => {
+                    let operator_id = operator
+                        .id
+                        .as_ref()
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| SINGLE_OPERATOR_DEFAULT_ID.to_owned());
+                    let dir = Path::new(&node_id).join(operator_id);
+                    self.add_operator(&dir, &mut operator.config)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the sources of an operator, using `dir` as directory for downloads.
+    fn add_operator(&mut self, dir: &Path, config: &mut OperatorConfig) -> eyre::Result<()> {
+        let downloads = Path::new("downloads").join(dir);
+        match &mut config.source {
+            OperatorSource::SharedLibrary(source) if source_is_url(source) => {
+                // the library name gets the platform-specific prefix and suffix
+                let target = downloads.join("operator");
+                self.add_download(source, &adjust_shared_library_path(&target)?)?;
+                *source = target.to_string_lossy().into_owned();
+            }
+            OperatorSource::SharedLibrary(source) => {
+                *source = self.add_local(source, adjust_shared_library_path)?;
+            }
+            OperatorSource::Python(python) => {
+                if source_is_url(&python.source) {
+                    self.add_file(&downloads, &mut python.source)?;
+                } else {
+                    python.source = self.add_python_operator(&python.source)?;
+                }
+                if let Some(requirements) = &mut python.requirements {
+                    self.add_requirements(&downloads, requirements)?;
+                }
+            }
+            OperatorSource::Wasm(source) => self.add_file(&downloads, source)?,
+        }
+        if let Some(schema) = &mut config.config_schema {
+            let source = schema.to_string_lossy().into_owned();
+            *schema = self.add_local(&source, |path| Ok(path.to_owned()))?.into();
+        }
+        Ok(())
+    }
+
+    /// Adds a file that is used as is, downloading it first if it's a URL.
+    fn add_file(&mut self, downloads: &Path, source: &mut String) -> eyre::Result<()> {
+        if source_is_url(source) {
+            let target = downloads.join(url_file_name(source));
+            self.add_download(source, &target)?;
+            *source = target.to_string_lossy().into_owned();
+        } else {
+            *source = self.add_local(source, |path| Ok(path.to_owned()))?;
+        }
+        Ok(())
+    }
+
+    /// Adds the local file that the `source` refers to and returns the source
+    /// that refers to its path in the bundle.
+    ///
+    /// The `file` function maps sources to file paths, e.g. to add the platform
+    /// specific suffix of shared libraries.
+    fn add_local(
+        &mut self,
+        source: &str,
+        file: impl Fn(&Path) -> eyre::Result<PathBuf>,
+    ) -> eyre::Result<String> {
+        let path = Path::new(source);
+        let bundle_source = self.bundle_path(path)?;
+        let local_file = self.working_dir.join(file(path)?);
+        if !local_file.is_file() {
+            bail!("source file `{}` does not exist", local_file.display());
+        }
+        self.files
+            .insert(normalize(&file(&bundle_source)?), local_file);
+        Ok(bundle_source.to_string_lossy().into_owned())
+    }
+
+    /// Adds a local Python operator together with the Python files of its
+    /// directory, which the operator can import.
+    fn add_python_operator(&mut self, source: &str) -> eyre::Result<String> {
+        let path = Path::new(source);
+        let bundle_source = self.bundle_path(path)?;
+        let local_file = self.working_dir.join(path);
+        if !local_file.is_file() {
+            bail!("source file `{}` does not exist", local_file.display());
+        }
+        let local_dir = local_file.parent().context("operator has no directory")?;
+        let bundle_dir = bundle_source.parent().unwrap_or(Path::new(""));
+        for file in python_files(local_dir)? {
+            let relative = file.strip_prefix(local_dir)?;
+            self.files
+                .insert(normalize(&bundle_dir.join(relative)), file);
+        }
+        self.files.insert(normalize(&bundle_source), local_file);
+        Ok(bundle_source.to_string_lossy().into_owned())
+    }
+
+    /// Downloads the requirements of a Python operator, so that they can be
+    /// installed without network access, and replaces them with a
+    /// requirements file that pins the downloaded packages.
+    fn add_requirements(
+        &mut self,
+        downloads: &Path,
+        requirements: &mut String,
+    ) -> eyre::Result<()> {
+        let local_requirements = if source_is_url(requirements) {
+            let local_file = self.download_dir.join(downloads).join("requirements.in");
+            self.rt
+                .block_on(download_file(requirements.as_str(), &local_file))
+                .wrap_err_with(|| format!("failed to download `{requirements}`"))?;
+            local_file
+        } else {
+            self.working_dir.join(requirements.as_str())
+        };
+
+        let wheels = downloads.join("wheels");
+        let local_wheels = self.download_dir.join(&wheels);
+        let python = dora_core::get_python_path()?;
+        let status = Command::new(python)
+            .args(["-m", "pip", "download", "--requirement"])
+            .arg(&local_requirements)
+            .arg("--dest")
+            .arg(&local_wheels)
+            .current_dir(&self.working_dir)
+            .status()
+            .context("failed to run `pip download`")?;
+        if !status.success() {
+            bail!(
+                "failed to download the packages of `{requirements}`, which the bundle \
+                must contain to start without network access"
+            );
+        }
+
+        // relative `--find-links` paths are resolved relative to the requirements file
+        let mut pinned = String::from("--no-index\n--find-links wheels\n");
+        let mut entries: Vec<_> = std::fs::read_dir(&local_wheels)
+            .wrap_err_with(|| format!("failed to read `{}`", local_wheels.display()))?
+            .collect::<Result<_, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            pinned.push_str(&package_pin(&file_name)?);
+            pinned.push('\n');
+            self.files.insert(wheels.join(&file_name), entry.path());
+        }
+        let target = downloads.join("requirements.txt");
+        let local_file = self.download_dir.join(&target);
+        std::fs::write(&local_file, pinned)
+            .wrap_err_with(|| format!("failed to write `{}`", local_file.display()))?;
+        self.files.insert(target.clone(), local_file);
+        *requirements = target.to_string_lossy().into_owned();
+        Ok(())
+    }
+
+    /// Returns the path of the given local source in the bundle.
+    fn bundle_path(&mut self, path: &Path) -> eyre::Result<PathBuf> {
+        if is_contained(path) {
+            return Ok(path.to_owned());
+        }
+        // sources outside of the working directory are moved into the bundle
+        self.external += 1;
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("source `{}` has no file name", path.display()))?;
+        Ok(Path::new("external")
+            .join(self.external.to_string())
+            .join(file_name))
+    }
+
+    fn add_download(&mut self, url: &str, target: &Path) -> eyre::Result<()> {
+        let local_file = self.download_dir.join(target);
+        self.rt
+            .block_on(download_file(url, &local_file))
+            .wrap_err_with(|| format!("failed to download `{url}`"))?;
+        self.files.insert(target.to_owned(), local_file);
+        self.downloads.insert(target.to_owned(), url.to_owned());
+        Ok(())
+    }
+}
+
+fn write_bundle(descriptor: &Descriptor, sources: &Sources, output: &Path) -> eyre::Result<()> {
+    let dataflow = serde_yaml::to_string(descriptor).context("failed to serialize dataflow")?;
+
+    let mut contents = BTreeMap::new();
+    contents.insert(PathBuf::from(DATAFLOW_FILE), dataflow.into_bytes());
+    for (path, local_file) in &sources.files {
+        let data = std::fs::read(local_file)
+            .wrap_err_with(|| format!("failed to read `{}`", local_file.display()))?;
+        contents.insert(path.clone(), data);
+    }
+    let manifest = Manifest {
+        dora_version: VERSION.to_owned(),
+        files: contents
+            .iter()
+            .map(|(path, data)| (path.clone(), checksum(data)))
+            .collect(),
+        downloads: sources.downloads.clone(),
+    };
+    let manifest = serde_yaml::to_string(&manifest).context("failed to serialize manifest")?;
+
+    let file = std::fs::File::create(output)
+        .wrap_err_with(|| format!("failed to create `{}`", output.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    append(
+        &mut archive,
+        Path::new(MANIFEST_FILE),
+        manifest.as_bytes(),
+        0o644,
+    )?;
+    for (path, data) in &contents {
+        let mode = match sources.files.get(path) {
+            Some(local_file) => file_mode(local_file)?,
+            None => 0o644,
+        };
+        append(&mut archive, path, data, mode)?;
+    }
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .wrap_err_with(|| format!("failed to write `{}`", output.display()))?;
+    Ok(())
+}
+
+fn append(
+    archive: &mut tar::Builder<impl std::io::Write>,
+    path: &Path,
+    data: &[u8],
+    mode: u32,
+) -> eyre::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(mode);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, path, data)
+        .wrap_err_with(|| format!("failed to add `{}` to bundle", path.display()))
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Path) -> eyre::Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(path)
+        .wrap_err_with(|| format!("failed to read metadata of `{}`", path.display()))?;
+    Ok(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> eyre::Result<u32> {
+    Ok(0o755)
+}
+
+fn checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Returns whether the path is relative and stays inside its base directory.
+fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+/// The path of a custom node executable, see `dora_core::descriptor::resolve_path`.
+fn executable(path: &Path) -> PathBuf {
+    if path.extension().is_none() {
+        path.with_extension(EXE_EXTENSION)
+    } else {
+        path.to_owned()
+    }
+}
+
+fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() => name,
+        _ => "source",
+    }
+}
+
+/// Returns the Python files in the given directory and its subdirectories,
+/// skipping hidden directories, caches, and virtual environments.
+fn python_files(dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).wrap_err_with(|| format!("failed to read `{}`", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            let skip =
+                name.starts_with('.') || name == "__pycache__" || path.join("pyvenv.cfg").exists();
+            if !skip {
+                files.extend(python_files(&path)?);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "py") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the requirement that pins the package of the given wheel or source
+/// archive, e.g. `numpy==1.26.4` for `numpy-1.26.4-cp311-cp311-linux_x86_64.whl`.
+fn package_pin(file_name: &str) -> eyre::Result<String> {
+    let name_and_version = if let Some(wheel) = file_name.strip_suffix(".whl") {
+        let mut parts = wheel.splitn(3, '-');
+        parts.next().zip(parts.next())
+    } else {
+        [".tar.gz", ".zip"]
+            .iter()
+            .find_map(|ext| file_name.strip_suffix(ext))
+            .and_then(|archive| archive.rsplit_once('-'))
+    };
+    let (name, version) =
+        name_and_version.with_context(|| format!("unknown package file `{file_name}`"))?;
+    Ok(format!("{name}=={version}"))
+}
+
+/// Creates a new directory in the temporary directory that only the current
+/// user can access.
+///
+/// Fails if the directory exists already, e.g. because another user created
+/// it in advance.
+fn create_private_dir(prefix: &str) -> eyre::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "{prefix}-{}",
+        Uuid::new_v7(Timestamp::now(NoContext)).simple()
+    ));
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(&dir)
+        .wrap_err_with(|| format!("failed to create `{}`", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_round_trip() {
+        let dir = std::env::temp_dir().join(format!("dora-bundle-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("operators/utils")).unwrap();
+        std::fs::write(dir.join("node.py"), "print('node')").unwrap();
+        std::fs::write(dir.join("operators/op.py"), "class Operator: pass").unwrap();
+        std::fs::write(dir.join("operators/utils/helper.py"), "VALUE = 1").unwrap();
+        std::fs::write(
+            dir.join("dataflow.yml"),
+            r#"
+nodes:
+  - id: node
+    custom:
+      source: ./node.py
+      outputs: [data]
+  - id: op
+    operator:
+      python: operators/op.py
+      inputs:
+        data: node/data
+"#,
+        )
+        .unwrap();
+
+        let bundle = dir.join(format!("dataflow.{EXTENSION}"));
+        // checking the descriptor would require a Python installation
+        let descriptor = Descriptor::blocking_read(&dir.join("dataflow.yml")).unwrap();
+        bundle_descriptor(descriptor, dir.canonicalize().unwrap(), &bundle).unwrap();
+        assert!(is_bundle(&bundle));
+
+        assert!(extract(&bundle, Some("0123")).is_err());
+        let archive = std::fs::read(&bundle).unwrap();
+        let dataflow = extract(&bundle, Some(&checksum(&archive))).unwrap();
+        let extracted = dataflow.parent().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(extracted.join("operators/op.py")).unwrap(),
+            "class Operator: pass"
+        );
+        // the imports of the operator are bundled too
+        assert!(extracted.join("operators/utils/helper.py").is_file());
+        assert!(extracted.join("node.py").is_file());
+
+        // every extraction uses a new directory
+        let again = extract(&bundle, None).unwrap();
+        assert_ne!(again, dataflow);
+        std::fs::remove_dir_all(again.parent().unwrap()).unwrap();
+        let descriptor = Descriptor::blocking_read(&dataflow).unwrap();
+        assert_eq!(descriptor.nodes.len(), 2);
+
+        std::fs::remove_dir_all(extracted).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pin_downloaded_packages() {
+        assert_eq!(
+            package_pin("numpy-1.26.4-cp311-cp311-manylinux_2_17_x86_64.whl").unwrap(),
+            "numpy==1.26.4"
+        );
+        assert_eq!(
+            package_pin("opencv_python-4.9.0.80.tar.gz").unwrap(),
+            "opencv_python==4.9.0.80"
+        );
+        assert!(package_pin("README").is_err());
+    }
+
+    #[test]
+    fn file_names_of_urls() {
+        assert_eq!(url_file_name("https://example.com/op.py?raw=1"), "op.py");
+        assert_eq!(url_file_name("https://example.com/"), "source");
+    }
+}
@@ -0,0 +1,97 @@
+use dora_core::transport::{
+    daemon_server_name, CA_CERT_FILE, DAEMON_CERT_FILE, DAEMON_KEY_FILE, TOKEN_SECRET_FILE,
+};
+use eyre::Context;
+use rand::RngCore;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+};
+use std::path::Path;
+
+/// Creates a certificate authority and a certificate for each of the given
+/// machines that is signed by it.
+///
+/// Every machine gets its own subdirectory, which can then be referenced as
+/// `transport.tls.certificates` in the config of the daemon on that machine.
+/// The subdirectories also contain a random secret that all daemons use to
+/// derive the dataflow tokens.
+pub fn certgen(out_dir: &Path, machines: &[String]) -> eyre::Result<()> {
+    let mut ca_params = CertificateParams::new(Vec::new());
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "dora certificate authority");
+    let ca = Certificate::from_params(ca_params).context("failed to create CA certificate")?;
+    let ca_pem = ca
+        .serialize_pem()
+        .context("failed to serialize CA certificate")?;
+
+    let mut token_secret = [0; 32];
+    rand::thread_rng().fill_bytes(&mut token_secret);
+
+    let default_machine = [String::new()];
+    let machines = if machines.is_empty() {
+        &default_machine[..]
+    } else {
+        machines
+    };
+    for machine in machines {
+        let server_name = daemon_server_name(machine);
+        let mut daemon_params = CertificateParams::new(vec![server_name.clone()]);
+        daemon_params
+            .distinguished_name
+            .push(DnType::CommonName, server_name.as_str());
+        // daemons act both as servers and as clients
+        daemon_params.extended_key_usages = vec![
+            ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsagePurpose::ClientAuth,
+        ];
+        let daemon = Certificate::from_params(daemon_params)
+            .with_context(|| format!("failed to create certificate for `{server_name}`"))?;
+
+        let dir = out_dir.join(if machine.is_empty() {
+            "default"
+        } else {
+            machine
+        });
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create `{}`", dir.display()))?;
+        write(&dir.join(CA_CERT_FILE), ca_pem.as_bytes())?;
+        write(
+            &dir.join(DAEMON_CERT_FILE),
+            daemon
+                .serialize_pem_with_signer(&ca)
+                .context("failed to sign daemon certificate")?
+                .as_bytes(),
+        )?;
+        write_private(
+            &dir.join(DAEMON_KEY_FILE),
+            daemon.serialize_private_key_pem().as_bytes(),
+        )?;
+        write_private(&dir.join(TOKEN_SECRET_FILE), &token_secret)?;
+
+        println!(
+            "created certificates for `{server_name}` in `{}`, copy the directory to the \
+            machine and reference it in the daemon config (`dora daemon --config`):\n\n\
+            transport:\n  tls:\n    certificates: {}\n",
+            dir.display(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+fn write(path: &Path, content: &[u8]) -> eyre::Result<()> {
+    std::fs::write(path, content).with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+fn write_private(path: &Path, content: &[u8]) -> eyre::Result<()> {
+    write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict permissions of `{}`", path.display()))?;
+    }
+    Ok(())
+}
@@ -1,18 +1,46 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
 
 use dora_core::descriptor::Descriptor;
-use eyre::Context;
+use eyre::{bail, Context};
 
 const MERMAID_TEMPLATE: &str = include_str!("mermaid-template.html");
 
-pub(crate) fn create(dataflow: std::path::PathBuf, mermaid: bool, open: bool) -> eyre::Result<()> {
-    if mermaid {
+/// Output format of `dora graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// HTML file that renders the graph using mermaid.js.
+    Html,
+    /// Mermaid flowchart, e.g. for GitHub markdown.
+    Mermaid,
+    /// Graphviz graph in the DOT language.
+    Dot,
+    /// SVG image, rendered using the Graphviz `dot` executable.
+    Svg,
+}
+
+pub(crate) fn create(
+    dataflow: std::path::PathBuf,
+    format: GraphFormat,
+    open: bool,
+) -> eyre::Result<()> {
+    if format == GraphFormat::Mermaid {
         let visualized = visualize_as_mermaid(&dataflow)?;
         println!("{visualized}");
-        println!(
+        eprintln!(
             "Paste the above output on https://mermaid.live/ or in a \
             ```mermaid code block on GitHub to display it."
         );
+    } else if format == GraphFormat::Dot {
+        let visualized = visualize_as_dot(&dataflow)?;
+        println!("{visualized}");
+    } else if format == GraphFormat::Svg {
+        let svg = visualize_as_svg(&dataflow)?;
+        println!("{svg}");
     } else {
         let html = visualize_as_html(&dataflow)?;
 
@@ -65,3 +93,46 @@ pub fn visualize_as_mermaid(dataflow: &Path) -> eyre::Result<String> {
 
     Ok(visualized)
 }
+
+pub fn visualize_as_dot(dataflow: &Path) -> eyre::Result<String> {
+    let descriptor = Descriptor::blocking_read(dataflow)
+        .with_context(|| format!("failed to read dataflow at `{}`", dataflow.display()))?;
+    let visualized = descriptor
+        .visualize_as_dot()
+        .context("failed to visualize descriptor")?;
+
+    Ok(visualized)
+}
+
+/// Renders the dataflow graph as SVG by passing its DOT representation to Graphviz.
+pub fn visualize_as_svg(dataflow: &Path) -> eyre::Result<String> {
+    let dot = visualize_as_dot(dataflow)?;
+
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context(
+            "failed to run Graphviz `dot` command (is Graphviz installed?); \
+            use `--format dot` to get the graph without rendering it",
+        )?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(dot.as_bytes())
+        .context("failed to pass graph to Graphviz")?;
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for Graphviz")?;
+    if !output.status.success() {
+        bail!(
+            "Graphviz failed to render the graph ({}):\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    String::from_utf8(output.stdout).context("Graphviz output is not valid UTF-8")
+}
@@ -1,6 +1,7 @@
 use communication_layer_request_reply::TcpRequestReplyConnection;
 use dora_core::topics::{ControlRequest, ControlRequestReply};
 use eyre::{bail, Context, Result};
+use std::{io::Write, time::Duration};
 use uuid::Uuid;
 
 use bat::{Input, PrettyPrinter};
@@ -10,25 +11,13 @@ pub fn logs(
     uuid: Option<Uuid>,
     name: Option<String>,
     node: String,
+    follow: bool,
 ) -> Result<()> {
-    let logs = {
-        let reply_raw = session
-            .request(
-                &serde_json::to_vec(&ControlRequest::Logs {
-                    uuid,
-                    name,
-                    node: node.clone(),
-                })
-                .wrap_err("")?,
-            )
-            .wrap_err("failed to send Logs request message")?;
-
-        let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
-        match reply {
-            ControlRequestReply::Logs(logs) => logs,
-            other => bail!("unexpected reply to daemon logs: {other:?}"),
-        }
-    };
+    let logs = request_logs(session, uuid, name.clone(), node.clone(), 0)?;
+
+    if follow {
+        return follow_logs(session, uuid, name, node, logs);
+    }
 
     PrettyPrinter::new()
         .header(false)
@@ -43,3 +32,51 @@ pub fn logs(
 
     Ok(())
 }
+
+pub(crate) fn request_logs(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    node: String,
+    offset: u64,
+) -> Result<Vec<u8>> {
+    let request = ControlRequest::Logs {
+        uuid,
+        name,
+        node,
+        offset,
+    };
+    let reply_raw = session
+        .request(&serde_json::to_vec(&request).wrap_err("")?)
+        .wrap_err("failed to send Logs request message")?;
+
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::Logs(logs) => Ok(logs),
+        other => bail!("unexpected reply to daemon logs: {other:?}"),
+    }
+}
+
+/// Prints the given logs and then polls for new log lines until the process is killed.
+fn follow_logs(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    node: String,
+    mut logs: Vec<u8>,
+) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    let mut printed = 0;
+    loop {
+        if !logs.is_empty() {
+            stdout
+                .write_all(&logs)
+                .and_then(|()| stdout.flush())
+                .wrap_err("failed to print logs")?;
+            printed += logs.len() as u64;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+        // only the new part of the log is requested
+        logs = request_logs(session, uuid, name.clone(), node.clone(), printed)?;
+    }
+}
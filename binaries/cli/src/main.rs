@@ -1,31 +1,50 @@
-use std::{net::Ipv4Addr, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    net::Ipv4Addr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use attach::attach_dataflow;
 use clap::Parser;
 use communication_layer_request_reply::{RequestReplyLayer, TcpLayer, TcpRequestReplyConnection};
-use dora_coordinator::Event;
+use dora_coordinator::{Event, PlacementPlugin};
 use dora_core::{
+    config::{NodeId, ParameterValue},
+    coordinator_messages::NodeState,
     descriptor::Descriptor,
     topics::{
-        control_socket_addr, ControlRequest, ControlRequestReply, DataflowId,
-        DORA_COORDINATOR_PORT_DEFAULT,
+        control_socket_addr, ControlRequest, ControlRequestReply, DataflowId, RateReport,
+        DORA_COORDINATOR_PORT_DEFAULT, DORA_WATCHDOG_HEALTH_PORT_DEFAULT,
     },
+    transport::{CoordinatorConfig, DaemonConfig},
 };
 use dora_daemon::Daemon;
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
 use eyre::{bail, Context};
+use graph::GraphFormat;
+use progress::{OutputFormat, Progress, ProgressEvent};
 use std::net::SocketAddr;
 use tokio::runtime::Builder;
 use uuid::Uuid;
 
 mod attach;
 mod build;
+mod bundle;
+mod certgen;
 mod check;
 mod graph;
 mod logs;
+mod nodes;
+mod params;
+mod progress;
+mod tap;
 mod template;
+mod top;
+mod trace;
 mod up;
+mod watchdog;
 
 #[derive(Debug, clap::Parser)]
 #[clap(version)]
@@ -42,16 +61,42 @@ enum Command {
         #[clap(long)]
         dataflow: Option<PathBuf>,
     },
-    /// Generate a visualization of the given graph using mermaid.js. Use --open to open browser.
+    /// Generate a visualization of the given graph. Use --open to open the HTML graph in a browser.
     Graph {
         dataflow: PathBuf,
-        #[clap(long, action)]
+        /// Output format: an HTML file using mermaid.js, or mermaid, DOT, or SVG on stdout.
+        #[clap(long, value_enum, default_value_t = GraphFormat::Html)]
+        format: GraphFormat,
+        /// Shorthand for `--format mermaid`.
+        #[clap(long, action, conflicts_with = "format")]
         mermaid: bool,
         #[clap(long, action)]
         open: bool,
     },
+    /// Print the JSON schema of dataflow files.
+    ///
+    /// Editors can use it for validation and autocompletion, e.g. by storing it
+    /// as `dataflow.schema.json` and adding
+    /// `# yaml-language-server: $schema=dataflow.schema.json` to a dataflow.
+    Schema,
     /// Run build commands provided in the given dataflow.
-    Build { dataflow: PathBuf },
+    Build {
+        dataflow: PathBuf,
+        /// Format of the progress output.
+        #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Package the given dataflow and the sources of its nodes into a single
+    /// archive, which `dora start` can run without network access.
+    ///
+    /// URL sources and the requirements of Python operators are downloaded,
+    /// and the checksums of all files are verified when the bundle is started.
+    Bundle {
+        dataflow: PathBuf,
+        /// Path of the bundle, defaults to the dataflow name with a `.dorapkg` extension.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
     /// Generate a new project, node or operator. Choose the language between Rust, Python, C or C++.
     New {
         #[clap(flatten)]
@@ -64,20 +109,50 @@ enum Command {
         #[clap(long)]
         config: Option<PathBuf>,
     },
+    /// Run a coordinator and a daemon and restart them when they crash.
+    ///
+    /// The given dataflows are started and restarted whenever they stop running.
+    Watchdog {
+        /// Dataflows to keep running.
+        dataflows: Vec<PathBuf>,
+        /// Local port of the HTTP health endpoint.
+        #[clap(long, default_value_t = DORA_WATCHDOG_HEALTH_PORT_DEFAULT)]
+        health_port: u16,
+        /// Maximum delay between restarts, in seconds.
+        #[clap(long, default_value_t = 60)]
+        max_backoff: u64,
+        /// File that keeps the state of the session across watchdog restarts.
+        #[clap(long, default_value = "dora-watchdog.json")]
+        state_file: PathBuf,
+    },
     /// Destroy running coordinator and daemon. If some dataflows are still running, they will be stopped first.
     Destroy {
         #[clap(long)]
         config: Option<PathBuf>,
     },
-    /// Start the given dataflow path. Attach a name to the running dataflow by using --name.
+    /// Start the given dataflow path or `.dorapkg` bundle. Attach a name to the running dataflow by using --name.
     Start {
         dataflow: PathBuf,
         #[clap(long)]
         name: Option<String>,
+        /// Wait until the dataflow is finished and stop it on Ctrl-C.
         #[clap(long, action)]
         attach: bool,
+        /// Reload Python operators when their source files change and print the
+        /// logs of their nodes. Implies `--attach`.
         #[clap(long, action)]
         hot_reload: bool,
+        /// Format of the progress output.
+        #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Maximum time that the nodes have to start, in seconds. `0` waits
+        /// indefinitely.
+        #[clap(long, default_value_t = 300)]
+        startup_timeout: u64,
+        /// Expected SHA-256 checksum of the `.dorapkg` bundle, as printed by
+        /// `dora bundle`. Detects bundles that were modified after bundling.
+        #[clap(long)]
+        sha256: Option<String>,
     },
     /// Stop the given dataflow UUID. If no id is provided, you will be able to choose between the running dataflows.
     Stop {
@@ -94,6 +169,51 @@ enum Command {
     Logs {
         dataflow: Option<String>,
         node: String,
+        /// Keep printing new log lines as they are written.
+        #[clap(long, short)]
+        follow: bool,
+    },
+    /// Show the lifecycle state of each node of a running dataflow.
+    Nodes { dataflow: Option<String> },
+    /// Show the restarts of failed nodes of a running dataflow.
+    Restarts { dataflow: Option<String> },
+    /// Show a live view of the resource usage and message rates of a running dataflow.
+    Top { dataflow: Option<String> },
+    /// Stop a single node of a running dataflow.
+    #[command(allow_missing_positional = true)]
+    Deactivate {
+        dataflow: Option<String>,
+        node: NodeId,
+    },
+    /// Show or change the parameters of the operators of a running dataflow.
+    Param {
+        #[clap(subcommand)]
+        command: ParamCommand,
+    },
+    /// Print the outputs of a dataflow that runs on the local machine.
+    ///
+    /// Uses a read-only connection, so the dataflow is not affected.
+    Tap {
+        /// UUID or name of the dataflow.
+        #[clap(long)]
+        dataflow: Option<String>,
+        /// Outputs to print, in the form `<node>/<output>`. Prints all outputs if none are given.
+        outputs: Vec<String>,
+    },
+    /// Trace the messages of an output back to the upstream messages that they
+    /// were derived from.
+    ///
+    /// Only works for dataflows that run on the local machine and that track the
+    /// lineage of their messages, i.e. set `lineage: true`.
+    Trace {
+        /// UUID or name of the dataflow.
+        #[clap(long)]
+        dataflow: Option<String>,
+        /// Output to trace, in the form `<node>/<output>`.
+        output: String,
+        /// Stop after tracing the given number of messages.
+        #[clap(long)]
+        count: Option<usize>,
     },
     // Metrics,
     // Stats,
@@ -108,11 +228,54 @@ enum Command {
 
         #[clap(long)]
         run_dataflow: Option<PathBuf>,
+        /// YAML configuration file, e.g. to enable TLS for the connections
+        /// to other daemons.
+        #[clap(long)]
+        config: Option<PathBuf>,
     },
     /// Run runtime
     Runtime,
     /// Run coordinator
-    Coordinator { port: Option<u16> },
+    Coordinator {
+        port: Option<u16>,
+        /// Plugin that assigns nodes to machines: either the URL of a webhook or
+        /// the path to a shared library.
+        #[clap(long)]
+        placement: Option<String>,
+        /// Serve liveness and readiness probes as JSON on `/health/live` and
+        /// `/health/ready` on this port.
+        #[clap(long)]
+        healthcheck_port: Option<u16>,
+        /// YAML configuration file, e.g. to enable the REST control API.
+        #[clap(long)]
+        config: Option<PathBuf>,
+    },
+    /// Generate the certificates for TLS connections between daemons.
+    Certgen {
+        /// Directory to write the certificates to.
+        #[clap(long, default_value = "certs")]
+        out: PathBuf,
+        /// Machine to create a certificate for. Can be given multiple times.
+        /// Defaults to the machine without ID.
+        #[clap(long = "machine")]
+        machines: Vec<String>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum ParamCommand {
+    /// Change a parameter of a node. The operators of the node that declare
+    /// the parameter are notified with a `PARAMETER_UPDATE` event.
+    #[command(allow_missing_positional = true)]
+    Set {
+        dataflow: Option<String>,
+        node: NodeId,
+        key: String,
+        /// New value, parsed as YAML, e.g. `0.5`, `true`, or `[1, 2]`.
+        value: ParameterValue,
+    },
+    /// Show the current parameter values of every node.
+    List { dataflow: Option<String> },
 }
 
 #[derive(Debug, clap::Args)]
@@ -137,6 +300,7 @@ enum Lang {
     Rust,
     Python,
     C,
+    #[value(alias = "cpp")]
     Cxx,
 }
 
@@ -182,21 +346,52 @@ fn run() -> eyre::Result<()> {
         },
         Command::Graph {
             dataflow,
+            format,
             mermaid,
             open,
         } => {
-            graph::create(dataflow, mermaid, open)?;
+            let format = if mermaid {
+                GraphFormat::Mermaid
+            } else {
+                format
+            };
+            graph::create(dataflow, format, open)?;
         }
-        Command::Build { dataflow } => {
-            build::build(&dataflow)?;
+        Command::Schema => {
+            let schema = dora_core::descriptor::schema::json_schema();
+            let schema =
+                serde_json::to_string_pretty(&schema).wrap_err("failed to serialize schema")?;
+            println!("{schema}");
+        }
+        Command::Build { dataflow, output } => {
+            build::build(&dataflow, output)?;
+        }
+        Command::Bundle { dataflow, output } => {
+            let output = output.unwrap_or_else(|| dataflow.with_extension(bundle::EXTENSION));
+            bundle::create(&dataflow, &output)?;
         }
         Command::New {
             args,
             internal_create_with_path_dependencies,
         } => template::create(args, internal_create_with_path_dependencies)?,
         Command::Up { config } => up::up(config.as_deref())?,
+        Command::Watchdog {
+            dataflows,
+            health_port,
+            max_backoff,
+            state_file,
+        } => watchdog::run(
+            dataflows,
+            health_port,
+            Duration::from_secs(max_backoff),
+            &state_file,
+        )?,
 
-        Command::Logs { dataflow, node } => {
+        Command::Logs {
+            dataflow,
+            node,
+            follow,
+        } => {
             let mut session =
                 connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
             let uuids = query_running_dataflows(&mut *session)
@@ -204,22 +399,105 @@ fn run() -> eyre::Result<()> {
             if let Some(dataflow) = dataflow {
                 let uuid = Uuid::parse_str(&dataflow).ok();
                 let name = if uuid.is_some() { None } else { Some(dataflow) };
-                logs::logs(&mut *session, uuid, name, node)?
+                logs::logs(&mut *session, uuid, name, node, follow)?
             } else {
                 let uuid = match &uuids[..] {
                     [] => bail!("No dataflows are running"),
                     [uuid] => uuid.clone(),
                     _ => inquire::Select::new("Choose dataflow to show logs:", uuids).prompt()?,
                 };
-                logs::logs(&mut *session, Some(uuid.uuid), None, node)?
+                logs::logs(&mut *session, Some(uuid.uuid), None, node, follow)?
+            }
+        }
+        Command::Nodes { dataflow } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(&mut *session, dataflow, "Choose dataflow to inspect:")?;
+            nodes::node_states(&mut *session, uuid)?
+        }
+        Command::Restarts { dataflow } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(&mut *session, dataflow, "Choose dataflow to inspect:")?;
+            nodes::restarts(&mut *session, uuid)?
+        }
+        Command::Deactivate { dataflow, node } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(
+                &mut *session,
+                dataflow,
+                "Choose dataflow to deactivate the node in:",
+            )?;
+            nodes::deactivate_node(&mut *session, uuid, node)?
+        }
+        Command::Param { command } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            match command {
+                ParamCommand::Set {
+                    dataflow,
+                    node,
+                    key,
+                    value,
+                } => {
+                    let uuid = resolve_dataflow(
+                        &mut *session,
+                        dataflow,
+                        "Choose dataflow to set the parameter in:",
+                    )?;
+                    params::set_parameter(&mut *session, uuid, node, key, value)?
+                }
+                ParamCommand::List { dataflow } => {
+                    let uuid =
+                        resolve_dataflow(&mut *session, dataflow, "Choose dataflow to inspect:")?;
+                    params::list_parameters(&mut *session, uuid)?
+                }
             }
         }
+        Command::Top { dataflow } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(&mut *session, dataflow, "Choose dataflow to show:")?;
+            top::top(&mut *session, uuid)?
+        }
+        Command::Tap { dataflow, outputs } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(&mut *session, dataflow, "Choose dataflow to tap:")?;
+            tap::tap(uuid, outputs)?
+        }
+        Command::Trace {
+            dataflow,
+            output,
+            count,
+        } => {
+            let mut session =
+                connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let uuid = resolve_dataflow(&mut *session, dataflow, "Choose dataflow to trace:")?;
+            trace::trace(uuid, &output, count)?
+        }
         Command::Start {
             dataflow,
             name,
             attach,
             hot_reload,
+            output,
+            startup_timeout,
+            sha256,
         } => {
+            if sha256.is_some() && !bundle::is_bundle(&dataflow) {
+                bail!(
+                    "`--sha256` is only supported for `.{}` bundles",
+                    bundle::EXTENSION
+                );
+            }
+            let dataflow = if bundle::is_bundle(&dataflow) {
+                bundle::extract(&dataflow, sha256.as_deref())
+                    .wrap_err("failed to extract dataflow bundle")?
+            } else {
+                dataflow
+            };
             let dataflow_descriptor =
                 Descriptor::blocking_read(&dataflow).wrap_err("Failed to read yaml dataflow")?;
             let working_dir = dataflow
@@ -233,14 +511,20 @@ fn run() -> eyre::Result<()> {
                 .wrap_err("Could not validate yaml")?;
             let mut session =
                 connect_to_coordinator().wrap_err("failed to connect to dora coordinator")?;
+            let mut progress = Progress::new(output);
             let dataflow_id = start_dataflow(
                 dataflow_descriptor.clone(),
                 name,
                 working_dir,
                 &mut *session,
             )?;
+            progress.event(ProgressEvent::DataflowStarted { uuid: dataflow_id });
+            let startup_timeout =
+                (startup_timeout > 0).then(|| Duration::from_secs(startup_timeout));
+            track_startup(&mut *session, dataflow_id, startup_timeout, &mut progress)?;
+            drop(progress);
 
-            if attach {
+            if attach || hot_reload {
                 attach_dataflow(
                     dataflow_descriptor,
                     dataflow,
@@ -266,14 +550,33 @@ fn run() -> eyre::Result<()> {
             }
         }
         Command::Destroy { config } => up::destroy(config.as_deref())?,
-        Command::Coordinator { port } => {
+        Command::Coordinator {
+            port,
+            placement,
+            healthcheck_port,
+            config,
+        } => {
+            let placement = placement
+                .map(|source| PlacementPlugin::new(&source))
+                .transpose()?;
+            let config = config
+                .as_deref()
+                .map(CoordinatorConfig::read)
+                .transpose()?
+                .unwrap_or_default();
             let rt = Builder::new_multi_thread()
                 .enable_all()
                 .build()
                 .context("tokio runtime failed")?;
             rt.block_on(async {
-                let (_port, task) =
-                    dora_coordinator::start(port, futures::stream::empty::<Event>()).await?;
+                let (_port, task) = dora_coordinator::start(
+                    port,
+                    placement,
+                    healthcheck_port,
+                    config.api,
+                    futures::stream::empty::<Event>(),
+                )
+                .await?;
                 task.await
             })
             .context("failed to run dora-coordinator")?
@@ -282,7 +585,12 @@ fn run() -> eyre::Result<()> {
             coordinator_addr,
             machine_id,
             run_dataflow,
+            config,
         } => {
+            let config = match config {
+                Some(path) => DaemonConfig::read(&path)?,
+                None => DaemonConfig::default(),
+            };
             let rt = Builder::new_multi_thread()
                 .enable_all()
                 .build()
@@ -306,13 +614,14 @@ fn run() -> eyre::Result<()> {
                             let localhost = Ipv4Addr::new(127, 0, 0, 1);
                             (localhost, DORA_COORDINATOR_PORT_DEFAULT).into()
                         });
-                        Daemon::run(addr, machine_id.unwrap_or_default()).await
+                        Daemon::run(addr, machine_id.unwrap_or_default(), config.transport).await
                     }
                 }
             })
             .context("failed to run dora-daemon")?
         }
         Command::Runtime => dora_runtime::main().context("Failed to run dora-runtime")?,
+        Command::Certgen { out, machines } => certgen::certgen(&out, &machines)?,
     };
 
     Ok(())
@@ -338,15 +647,83 @@ fn start_dataflow(
     let result: ControlRequestReply =
         serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
     match result {
-        ControlRequestReply::DataflowStarted { uuid } => {
-            eprintln!("{uuid}");
-            Ok(uuid)
-        }
+        ControlRequestReply::DataflowStarted { uuid } => Ok(uuid),
         ControlRequestReply::Error(err) => bail!("{err}"),
         other => bail!("unexpected start dataflow reply: {other:?}"),
     }
 }
 
+/// Reports the node states of a newly started dataflow until all nodes are running.
+///
+/// Fails if some nodes are still starting after the given timeout. The dataflow
+/// keeps running in this case.
+fn track_startup(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Uuid,
+    timeout: Option<Duration>,
+    progress: &mut Progress,
+) -> eyre::Result<()> {
+    let start = Instant::now();
+    let mut states = BTreeMap::new();
+    loop {
+        let Ok(new_states) = nodes::query_node_states(session, uuid) else {
+            // the dataflow finished already
+            return Ok(());
+        };
+        for (node_id, state) in &new_states {
+            if states.get(node_id) != Some(state) {
+                progress.event(ProgressEvent::NodeStateChanged {
+                    node: node_id.clone(),
+                    state: state.to_string(),
+                });
+            }
+        }
+        progress.node_states(&new_states);
+        states = new_states;
+
+        if states
+            .values()
+            .all(|state| *state != NodeState::Configuring)
+        {
+            break;
+        }
+        if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            let pending: Vec<_> = states
+                .iter()
+                .filter(|(_, state)| **state == NodeState::Configuring)
+                .map(|(node_id, _)| node_id.clone())
+                .collect();
+            progress.event(ProgressEvent::StartupTimedOut {
+                uuid,
+                pending: pending.clone(),
+            });
+            let pending: Vec<_> = pending.iter().map(|id| id.to_string()).collect();
+            bail!(
+                "nodes did not start within {:?}: {} (dataflow `{uuid}` is still running)",
+                start.elapsed(),
+                pending.join(", ")
+            );
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    let failed: Vec<_> = states
+        .into_iter()
+        .filter(|(_, state)| *state == NodeState::Errored)
+        .map(|(node_id, _)| node_id)
+        .collect();
+    progress.event(ProgressEvent::StartupFinished {
+        uuid,
+        duration_ms: start.elapsed().as_millis(),
+        failed: failed.clone(),
+    });
+    if !failed.is_empty() {
+        let failed: Vec<_> = failed.iter().map(|id| id.to_string()).collect();
+        bail!("failed to start nodes: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
 fn stop_dataflow_interactive(session: &mut TcpRequestReplyConnection) -> eyre::Result<()> {
     let uuids = query_running_dataflows(session).wrap_err("failed to query running dataflows")?;
     if uuids.is_empty() {
@@ -374,9 +751,16 @@ fn stop_dataflow(
     let result: ControlRequestReply =
         serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
     match result {
-        ControlRequestReply::DataflowStopped { uuid: _, result } => result
-            .map_err(|err| eyre::eyre!(err))
-            .wrap_err("dataflow failed"),
+        ControlRequestReply::DataflowStopped {
+            uuid: _,
+            result,
+            report,
+        } => {
+            print_rate_report(&report);
+            result
+                .map_err(|err| eyre::eyre!(err))
+                .wrap_err("dataflow failed")
+        }
         ControlRequestReply::Error(err) => bail!("{err}"),
         other => bail!("unexpected stop dataflow reply: {other:?}"),
     }
@@ -392,14 +776,28 @@ fn stop_dataflow_by_name(
     let result: ControlRequestReply =
         serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
     match result {
-        ControlRequestReply::DataflowStopped { uuid: _, result } => result
-            .map_err(|err| eyre::eyre!(err))
-            .wrap_err("dataflow failed"),
+        ControlRequestReply::DataflowStopped {
+            uuid: _,
+            result,
+            report,
+        } => {
+            print_rate_report(&report);
+            result
+                .map_err(|err| eyre::eyre!(err))
+                .wrap_err("dataflow failed")
+        }
         ControlRequestReply::Error(err) => bail!("{err}"),
         other => bail!("unexpected stop dataflow reply: {other:?}"),
     }
 }
 
+/// Prints the message statistics of a finished dataflow.
+fn print_rate_report(report: &RateReport) {
+    if !report.links.is_empty() {
+        println!("{report}");
+    }
+}
+
 fn list(session: &mut TcpRequestReplyConnection) -> Result<(), eyre::ErrReport> {
     let ids = query_running_dataflows(session)?;
 
@@ -432,6 +830,30 @@ fn query_running_dataflows(
     Ok(ids)
 }
 
+/// Resolves the given dataflow UUID or name, prompting the user if none is given.
+fn resolve_dataflow(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: Option<String>,
+    prompt: &str,
+) -> eyre::Result<Uuid> {
+    let ids = query_running_dataflows(session).wrap_err("failed to query running dataflows")?;
+    match dataflow {
+        Some(dataflow) => match Uuid::parse_str(&dataflow) {
+            Ok(uuid) => Ok(uuid),
+            Err(_) => ids
+                .iter()
+                .find(|id| id.name.as_deref() == Some(dataflow.as_str()))
+                .map(|id| id.uuid)
+                .ok_or_else(|| eyre::eyre!("no running dataflow with name `{dataflow}`")),
+        },
+        None => match &ids[..] {
+            [] => bail!("No dataflows are running"),
+            [id] => Ok(id.uuid),
+            _ => Ok(inquire::Select::new(prompt, ids).prompt()?.uuid),
+        },
+    }
+}
+
 fn connect_to_coordinator() -> std::io::Result<Box<TcpRequestReplyConnection>> {
     TcpLayer::new().connect(control_socket_addr())
 }
@@ -0,0 +1,111 @@
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::NodeId,
+    coordinator_messages::NodeState,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use eyre::{bail, Context, Result};
+use std::{collections::BTreeMap, time::SystemTime};
+use uuid::Uuid;
+
+/// Prints the lifecycle state of every node of the given dataflow.
+pub fn node_states(session: &mut TcpRequestReplyConnection, dataflow_uuid: Uuid) -> Result<()> {
+    let states = query_node_states(session, dataflow_uuid)?;
+
+    let width = states.keys().map(|id| id.to_string().len()).max();
+    for (node_id, state) in states {
+        println!(
+            "{:width$}  {state}",
+            node_id.to_string(),
+            width = width.unwrap_or(0)
+        );
+    }
+
+    Ok(())
+}
+
+pub fn query_node_states(
+    session: &mut TcpRequestReplyConnection,
+    dataflow_uuid: Uuid,
+) -> Result<BTreeMap<NodeId, NodeState>> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::NodeStates { dataflow_uuid }).unwrap())
+        .wrap_err("failed to send NodeStates request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::NodeStates(states) => Ok(states),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected node states reply: {other:?}"),
+    }
+}
+
+/// Asks the given node to stop, without stopping the rest of the dataflow.
+pub fn deactivate_node(
+    session: &mut TcpRequestReplyConnection,
+    dataflow_uuid: Uuid,
+    node_id: NodeId,
+) -> Result<()> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::DeactivateNode {
+                dataflow_uuid,
+                node_id,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send DeactivateNode request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::NodeDeactivated { uuid, node_id } => {
+            println!("deactivated node `{node_id}` of dataflow `{uuid}`");
+            Ok(())
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected deactivate node reply: {other:?}"),
+    }
+}
+
+/// Prints the restarts of failed nodes of the given dataflow, oldest first.
+pub fn restarts(session: &mut TcpRequestReplyConnection, dataflow_uuid: Uuid) -> Result<()> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::Restarts { dataflow_uuid }).unwrap())
+        .wrap_err("failed to send Restarts request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    let restarts = match reply {
+        ControlRequestReply::Restarts(restarts) => restarts,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected restarts reply: {other:?}"),
+    };
+
+    if restarts.is_empty() {
+        println!("no restarts");
+        return Ok(());
+    }
+    let width = restarts
+        .iter()
+        .map(|restart| restart.node_id.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for restart in restarts {
+        let ago = SystemTime::now()
+            .duration_since(restart.time)
+            .unwrap_or_default()
+            .as_secs();
+        let mut line = format!(
+            "{:>6}s ago  {:width$}  attempt {}  {} restart",
+            ago,
+            restart.node_id.to_string(),
+            restart.attempt,
+            restart.scope,
+        );
+        if restart.dataflow_uuid != dataflow_uuid {
+            line.push_str(&format!(" (in run {})", restart.dataflow_uuid));
+        }
+        if let Some(err) = restart.error {
+            line.push_str(&format!(" failed: {err}"));
+        }
+        println!("{line}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    config::{NodeId, ParameterValue},
+    topics::{ControlRequest, ControlRequestReply},
+};
+use eyre::{bail, Context, Result};
+use uuid::Uuid;
+
+/// Changes a parameter of the given node while the dataflow is running.
+pub fn set_parameter(
+    session: &mut TcpRequestReplyConnection,
+    dataflow_uuid: Uuid,
+    node_id: NodeId,
+    key: String,
+    value: ParameterValue,
+) -> Result<()> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::SetParameter {
+                dataflow_uuid,
+                node_id,
+                key,
+                value: value.0.clone(),
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send SetParameter request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::ParameterSet { uuid, node_id, key } => {
+            println!("set parameter `{key}` of node `{node_id}` in dataflow `{uuid}` to {value}");
+            Ok(())
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected set parameter reply: {other:?}"),
+    }
+}
+
+/// Prints the current parameter values of every node of the given dataflow.
+pub fn list_parameters(session: &mut TcpRequestReplyConnection, dataflow_uuid: Uuid) -> Result<()> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::Parameters { dataflow_uuid }).unwrap())
+        .wrap_err("failed to send Parameters request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    let parameters = match reply {
+        ControlRequestReply::Parameters(parameters) => parameters,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected parameters reply: {other:?}"),
+    };
+
+    if parameters.is_empty() {
+        println!("no parameters");
+        return Ok(());
+    }
+    for (node_id, parameters) in parameters {
+        println!("{node_id}:");
+        for (key, value) in parameters {
+            println!("  {key}: {}", ParameterValue(value));
+        }
+    }
+
+    Ok(())
+}
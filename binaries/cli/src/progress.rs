@@ -0,0 +1,192 @@
+use dora_core::{
+    config::{NodeId, OperatorId},
+    coordinator_messages::NodeState,
+};
+use std::{
+    io::{IsTerminal, Write},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Output format for the progress of long running commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable progress on stderr.
+    #[default]
+    Text,
+    /// One JSON object per progress event on stdout.
+    Json,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    BuildStarted {
+        node: NodeId,
+        operator: Option<OperatorId>,
+    },
+    BuildFinished {
+        node: NodeId,
+        operator: Option<OperatorId>,
+        duration_ms: u128,
+        error: Option<String>,
+    },
+    DataflowStarted {
+        uuid: Uuid,
+    },
+    NodeStateChanged {
+        node: NodeId,
+        state: String,
+    },
+    StartupFinished {
+        uuid: Uuid,
+        duration_ms: u128,
+        failed: Vec<NodeId>,
+    },
+    /// Some nodes didn't finish starting within the startup timeout.
+    StartupTimedOut {
+        uuid: Uuid,
+        pending: Vec<NodeId>,
+    },
+}
+
+#[derive(serde::Serialize)]
+struct TimedEvent<'a> {
+    elapsed_ms: u128,
+    #[serde(flatten)]
+    event: &'a ProgressEvent,
+}
+
+/// Reports progress events in the selected output format.
+pub struct Progress {
+    format: OutputFormat,
+    start: Instant,
+    /// Whether a status line that should be overwritten was printed last.
+    status_line: bool,
+}
+
+impl Progress {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            start: Instant::now(),
+            status_line: false,
+        }
+    }
+
+    pub fn event(&mut self, event: ProgressEvent) {
+        match self.format {
+            OutputFormat::Json => {
+                let event = TimedEvent {
+                    elapsed_ms: self.start.elapsed().as_millis(),
+                    event: &event,
+                };
+                if let Ok(line) = serde_json::to_string(&event) {
+                    println!("{line}");
+                }
+            }
+            OutputFormat::Text => {
+                let line = match &event {
+                    ProgressEvent::BuildStarted { node, operator } => {
+                        format!("building {}", display_id(node, operator))
+                    }
+                    ProgressEvent::BuildFinished {
+                        node,
+                        operator,
+                        duration_ms,
+                        error: None,
+                    } => format!(
+                        "built {} in {:.1?}",
+                        display_id(node, operator),
+                        Duration::from_millis(*duration_ms as u64)
+                    ),
+                    ProgressEvent::BuildFinished {
+                        node,
+                        operator,
+                        error: Some(error),
+                        ..
+                    } => format!("failed to build {}: {error}", display_id(node, operator)),
+                    ProgressEvent::DataflowStarted { uuid } => uuid.to_string(),
+                    ProgressEvent::NodeStateChanged { node, state } => {
+                        if state != "errored" {
+                            // shown in the status line instead
+                            return;
+                        }
+                        format!("node `{node}` failed")
+                    }
+                    ProgressEvent::StartupFinished {
+                        duration_ms,
+                        failed,
+                        ..
+                    } => {
+                        let duration = Duration::from_millis(*duration_ms as u64);
+                        if failed.is_empty() {
+                            format!("all nodes started in {duration:.1?}")
+                        } else {
+                            format!("{} node(s) failed to start", failed.len())
+                        }
+                    }
+                    ProgressEvent::StartupTimedOut { pending, .. } => {
+                        format!("{} node(s) are still starting", pending.len())
+                    }
+                };
+                self.clear_status();
+                eprintln!("{line}");
+            }
+        }
+    }
+
+    /// Shows a status line that is replaced by the next output.
+    ///
+    /// Status lines are only shown for text output on a terminal.
+    pub fn status(&mut self, line: &str) {
+        if self.format != OutputFormat::Text || !std::io::stderr().is_terminal() {
+            return;
+        }
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[K{line}");
+        let _ = stderr.flush();
+        self.status_line = true;
+    }
+
+    fn clear_status(&mut self) {
+        if self.status_line {
+            eprint!("\r\x1b[K");
+            self.status_line = false;
+        }
+    }
+
+    /// Reports the state of all nodes while a dataflow starts.
+    pub fn node_states<'a>(
+        &mut self,
+        states: impl IntoIterator<Item = (&'a NodeId, &'a NodeState)>,
+    ) {
+        let mut total = 0;
+        let mut waiting = Vec::new();
+        for (node_id, state) in states {
+            total += 1;
+            if *state == NodeState::Configuring {
+                waiting.push(node_id.to_string());
+            }
+        }
+        let line = format!(
+            "starting: {}/{total} nodes running (waiting for {})",
+            total - waiting.len(),
+            waiting.join(", ")
+        );
+        self.status(&line);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear_status();
+    }
+}
+
+fn display_id(node: &NodeId, operator: &Option<OperatorId>) -> String {
+    match operator {
+        Some(operator) => format!("operator `{node}/{operator}`"),
+        None => format!("node `{node}`"),
+    }
+}
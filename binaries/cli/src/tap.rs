@@ -0,0 +1,43 @@
+use dora_core::config::{DataId, NodeId};
+use dora_node_api::{arrow::array::Array, Event, Observer};
+use eyre::{Context, ContextCompat, Result};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// Prints the given outputs of a dataflow running on the local machine.
+///
+/// Uses a read-only observer connection, so the dataflow is not affected.
+pub fn tap(dataflow_uuid: Uuid, outputs: Vec<String>) -> Result<()> {
+    let outputs = outputs
+        .iter()
+        .map(|output| parse_output(output))
+        .collect::<Result<BTreeSet<_>>>()?;
+    let mut observer = Observer::connect(dataflow_uuid, outputs)
+        .wrap_err("failed to connect to local dora daemon")?;
+
+    while let Some(event) = observer.recv() {
+        match event {
+            Event::Input { id, metadata, data } => println!(
+                "{} {id}: {} values of type {}",
+                metadata.timestamp(),
+                data.len(),
+                data.data_type()
+            ),
+            Event::InputClosed { id } => println!("{id} closed"),
+            Event::Error(err) => eprintln!("error: {err}"),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn parse_output(output: &str) -> Result<(NodeId, DataId)> {
+    let (node, output_id) = output
+        .split_once('/')
+        .with_context(|| format!("output `{output}` must have the form `<node>/<output>`"))?;
+    Ok((
+        NodeId::from(node.to_owned()),
+        DataId::from(output_id.to_owned()),
+    ))
+}
@@ -15,12 +15,35 @@ pub fn create(args: crate::CommandNew) -> eyre::Result<()> {
     } = args;
 
     match kind {
-        crate::Kind::Operator => create_operator(name, path),
-        crate::Kind::CustomNode => create_custom_node(name, path),
+        crate::Kind::Operator => {
+            const DATAFLOW_YML: &str = include_str!("operator/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_operator(name.clone(), Some(root.clone()))?;
+            create_build_dir(&root)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
+        crate::Kind::CustomNode => {
+            const DATAFLOW_YML: &str = include_str!("node/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_custom_node(name.clone(), Some(root.clone()))?;
+            create_build_dir(&root)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
         crate::Kind::Dataflow => create_dataflow(name, path),
     }
 }
 
+const BUILD_INSTRUCTIONS: &str = include_str!("README-build.md");
+
+/// Creates the output directory of the build commands in `dataflow.yml`.
+fn create_build_dir(root: &Path) -> eyre::Result<()> {
+    let build_dir = root.join("build");
+    fs::create_dir_all(&build_dir)
+        .with_context(|| format!("failed to create directory `{}`", build_dir.display()))
+}
+
 fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
     const DATAFLOW_YML: &str = include_str!("dataflow-template.yml");
 
@@ -44,6 +67,8 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     create_operator("op_1".into(), Some(root.join("op_1")))?;
     create_operator("op_2".into(), Some(root.join("op_2")))?;
     create_custom_node("node_1".into(), Some(root.join("node_1")))?;
+    create_build_dir(root)?;
+    super::write_readme(root, &name, BUILD_INSTRUCTIONS)?;
 
     println!(
         "Created new C dataflow at `{name}` at {}",
@@ -81,8 +106,6 @@ fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     fs::write(&header_type_path, HEADER_OPERATOR_TYPES)
         .with_context(|| format!("failed to write `{}`", header_type_path.display()))?;
 
-    // TODO: Makefile?
-
     println!(
         "Created new C operator `{name}` at {}",
         Path::new(".").join(root).display()
@@ -113,8 +136,6 @@ fn create_custom_node(name: String, path: Option<PathBuf>) -> Result<(), eyre::E
     fs::write(&header_path, HEADER_NODE_API)
         .with_context(|| format!("failed to write `{}`", header_path.display()))?;
 
-    // TODO: Makefile?
-
     println!(
         "Created new C custom node `{name}` at {}",
         Path::new(".").join(root).display()
@@ -27,38 +27,35 @@ OnEventResult_t dora_on_event(
     const SendOutput_t *send_output,
     void *operator_context)
 {
+    OnEventResult_t result = {.status = DORA_STATUS_CONTINUE};
+
     if (event->input != NULL)
     {
-        char id[event->input->id.len + 1];
-        memcpy(id, event->input->id.ptr, event->input->id.len);
-        id[event->input->id.len] = 0;
+        Input_t *input = event->input;
+        char *id = dora_read_input_id(input);
 
         // example for matching on input name
         if (strcmp(id, "foo") == 0)
         {
-            char *out_id = "bar";
-            char *out_id_heap = strdup(out_id);
+            Vec_uint8_t data = dora_read_data(input);
+            printf("C operator received input `%s` with %zu bytes\n", id, data.len);
 
             int data_alloc_size = 10;
-            void *out_data = malloc(data_alloc_size);
+            uint8_t *out_data = (uint8_t *)malloc(data_alloc_size);
             // TODO intialize out_data
+            memset(out_data, 0, data_alloc_size);
 
-            Output_t output = {.id = {
-                                   .ptr = (uint8_t *)out_id_heap,
-                                   .len = strlen(out_id_heap),
-                                   .cap = strlen(out_id_heap) + 1,
-                               },
-                               .data = {.ptr = (uint8_t *)out_data, .len = strlen(out_data), .cap = data_alloc_size}};
-            DoraResult_t res = (send_output->send_output.call)(send_output->send_output.env_ptr, output);
+            result.result = dora_send_operator_output(send_output, "bar", out_data, data_alloc_size);
 
-            OnEventResult_t result = {.result = res, .status = DORA_STATUS_CONTINUE};
-            return result;
+            free(out_data);
+            dora_free_data(data);
         }
+
+        dora_free_input_id(id);
     }
     if (event->stop)
     {
         printf("C operator received stop event\n");
     }
-    OnEventResult_t result = {.status = DORA_STATUS_CONTINUE};
     return result;
 }
@@ -1,3 +1,4 @@
+use dora_operator_api_c::{HEADER_OPERATOR_API, HEADER_OPERATOR_CXX, HEADER_OPERATOR_TYPES};
 use eyre::{bail, Context};
 use std::{
     fs,
@@ -13,12 +14,35 @@ pub fn create(args: crate::CommandNew) -> eyre::Result<()> {
     } = args;
 
     match kind {
-        crate::Kind::Operator => create_operator(name, path),
-        crate::Kind::CustomNode => create_custom_node(name, path),
+        crate::Kind::Operator => {
+            const DATAFLOW_YML: &str = include_str!("operator-dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_operator(name.clone(), Some(root.clone()))?;
+            create_build_dir(&root)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
+        crate::Kind::CustomNode => {
+            const DATAFLOW_YML: &str = include_str!("node-dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_custom_node(name.clone(), Some(root.clone()))?;
+            create_build_dir(&root)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
         crate::Kind::Dataflow => create_dataflow(name, path),
     }
 }
 
+const BUILD_INSTRUCTIONS: &str = include_str!("README-build.md");
+
+/// Creates the output directory of the build commands in `dataflow.yml`.
+fn create_build_dir(root: &Path) -> eyre::Result<()> {
+    let build_dir = root.join("build");
+    fs::create_dir_all(&build_dir)
+        .with_context(|| format!("failed to create directory `{}`", build_dir.display()))
+}
+
 fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
     const DATAFLOW_YML: &str = include_str!("dataflow-template.yml");
 
@@ -42,6 +66,8 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     create_operator("op_1".into(), Some(root.join("op_1")))?;
     create_operator("op_2".into(), Some(root.join("op_2")))?;
     create_custom_node("node_1".into(), Some(root.join("node_1")))?;
+    create_build_dir(root)?;
+    super::write_readme(root, &name, BUILD_INSTRUCTIONS)?;
 
     println!(
         "Created new C++ dataflow at `{name}` at {}",
@@ -53,7 +79,6 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
 
 fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
     const OPERATOR: &str = include_str!("operator-template.cc");
-    const HEADER: &str = include_str!("operator-template.h");
 
     if name.contains('/') {
         bail!("operator name must not contain `/` separators");
@@ -70,11 +95,15 @@ fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     let operator_path = root.join("operator.cc");
     fs::write(&operator_path, OPERATOR)
         .with_context(|| format!("failed to write `{}`", operator_path.display()))?;
-    let header_path = root.join("operator.h");
-    fs::write(&header_path, HEADER)
-        .with_context(|| format!("failed to write `{}`", header_path.display()))?;
-
-    // TODO: Makefile?
+    for (file_name, header) in [
+        ("dora_operator.hpp", HEADER_OPERATOR_CXX),
+        ("operator_api.h", HEADER_OPERATOR_API),
+        ("operator_types.h", HEADER_OPERATOR_TYPES),
+    ] {
+        let header_path = root.join(file_name);
+        fs::write(&header_path, header)
+            .with_context(|| format!("failed to write `{}`", header_path.display()))?;
+    }
 
     println!(
         "Created new C++ operator `{name}` at {}",
@@ -103,8 +132,6 @@ fn create_custom_node(name: String, path: Option<PathBuf>) -> Result<(), eyre::E
     fs::write(&node_path, NODE)
         .with_context(|| format!("failed to write `{}`", node_path.display()))?;
 
-    // TODO: Makefile?
-
     println!(
         "Created new C++ custom node `{name}` at {}",
         Path::new(".").join(root).display()
@@ -1,4 +1,4 @@
-#include "dora-node-api.h" // adjust this path if necessary
+#include "dora-node-api.h"
 
 #include <iostream>
 #include <vector>
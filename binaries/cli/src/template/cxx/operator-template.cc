@@ -1,23 +1,36 @@
-#include "operator.h"
+#include "dora_operator.hpp"
+
 #include <iostream>
 #include <vector>
-#include "../build/dora-operator-api.h"
-
-Operator::Operator() {}
 
-std::unique_ptr<Operator> new_operator()
+class Operator
 {
-    return std::make_unique<Operator>();
-}
+public:
+    dora::Status on_event(dora::Event &event, dora::OutputSender &output_sender)
+    {
+        switch (event.kind())
+        {
+        case dora::Event::Kind::Input:
+        {
+            dora::Data data = event.data();
+            counter_ += 1;
+            std::cout << "Received input `" << event.id() << "` with " << data.size()
+                      << " bytes (counter: " << (unsigned int)counter_ << ")" << std::endl;
 
-DoraOnInputResult on_input(Operator &op, rust::Str id, rust::Slice<const uint8_t> data, OutputSender &output_sender)
-{
-    op.counter += 1;
-    std::cout << "Rust API operator received input `" << id.data() << "` with data `" << (unsigned int)data[0] << "` (internal counter: " << (unsigned int)op.counter << ")" << std::endl;
+            output_sender.send("some-output", std::vector<uint8_t>{counter_});
+            break;
+        }
+        case dora::Event::Kind::Stop:
+            std::cout << "Received stop event" << std::endl;
+            break;
+        default:
+            break;
+        }
+        return dora::Status::Continue;
+    }
+
+private:
+    uint8_t counter_ = 0;
+};
 
-    std::vector<unsigned char> out_vec{op.counter};
-    rust::Slice<const uint8_t> out_slice{out_vec.data(), out_vec.size()};
-    auto send_result = send_output(output_sender, rust::Str("status"), out_slice);
-    DoraOnInputResult result = {send_result.error, false};
-    return result;
-}
+DORA_REGISTER_OPERATOR(Operator)
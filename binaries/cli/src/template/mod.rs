@@ -1,3 +1,6 @@
+use eyre::Context;
+use std::{fs, path::Path};
+
 mod c;
 mod cxx;
 mod python;
@@ -11,3 +14,23 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
         crate::Lang::Cxx => cxx::create(args),
     }
 }
+
+/// Writes a `dataflow.yml` for running the generated operator or node on its own.
+fn write_dataflow_yml(root: &Path, name: &str, template: &str) -> eyre::Result<()> {
+    let dataflow_yml = template.replace("___name___", name);
+    let dataflow_yml_path = root.join("dataflow.yml");
+    fs::write(&dataflow_yml_path, dataflow_yml)
+        .with_context(|| format!("failed to write `{}`", dataflow_yml_path.display()))
+}
+
+/// Writes a `README.md` with the given language-specific build instructions.
+fn write_readme(root: &Path, name: &str, build_instructions: &str) -> eyre::Result<()> {
+    const README: &str = include_str!("README-template.md");
+
+    let readme = README
+        .replace("___name___", name)
+        .replace("___build___", build_instructions.trim());
+    let readme_path = root.join("README.md");
+    fs::write(&readme_path, readme)
+        .with_context(|| format!("failed to write `{}`", readme_path.display()))
+}
@@ -13,12 +13,26 @@ pub fn create(args: crate::CommandNew) -> eyre::Result<()> {
     } = args;
 
     match kind {
-        crate::Kind::Operator => create_operator(name, path),
-        crate::Kind::CustomNode => create_custom_node(name, path),
+        crate::Kind::Operator => {
+            const DATAFLOW_YML: &str = include_str!("operator/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_operator(name.clone(), Some(root.clone()))?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
+        crate::Kind::CustomNode => {
+            const DATAFLOW_YML: &str = include_str!("node/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_custom_node(name.clone(), Some(root.clone()))?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
         crate::Kind::Dataflow => create_dataflow(name, path),
     }
 }
 
+const BUILD_INSTRUCTIONS: &str = include_str!("README-build.md");
+
 fn create_operator(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrReport> {
     const OPERATOR_PY: &str = include_str!("operator/operator-template.py");
 
@@ -87,6 +101,7 @@ fn create_dataflow(name: String, path: Option<PathBuf>) -> Result<(), eyre::ErrR
     create_operator("op_1".into(), Some(root.join("op_1")))?;
     create_operator("op_2".into(), Some(root.join("op_2")))?;
     create_custom_node("node_1".into(), Some(root.join("node_1")))?;
+    super::write_readme(root, &name, BUILD_INSTRUCTIONS)?;
 
     println!(
         "Created new yaml dataflow `{name}` at {}",
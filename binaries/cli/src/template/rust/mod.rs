@@ -14,12 +14,26 @@ pub fn create(args: crate::CommandNew, use_path_deps: bool) -> eyre::Result<()>
     } = args;
 
     match kind {
-        crate::Kind::Operator => create_operator(name, path, use_path_deps),
-        crate::Kind::CustomNode => create_custom_node(name, path, use_path_deps),
+        crate::Kind::Operator => {
+            const DATAFLOW_YML: &str = include_str!("operator/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_operator(name.clone(), Some(root.clone()), use_path_deps)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
+        crate::Kind::CustomNode => {
+            const DATAFLOW_YML: &str = include_str!("node/dataflow-template.yml");
+            let root = path.unwrap_or_else(|| PathBuf::from(&name));
+            create_custom_node(name.clone(), Some(root.clone()), use_path_deps)?;
+            super::write_dataflow_yml(&root, &name, DATAFLOW_YML)?;
+            super::write_readme(&root, &name, BUILD_INSTRUCTIONS)
+        }
         crate::Kind::Dataflow => create_dataflow(name, path, use_path_deps),
     }
 }
 
+const BUILD_INSTRUCTIONS: &str = include_str!("README-build.md");
+
 fn create_dataflow(
     name: String,
     path: Option<PathBuf>,
@@ -52,6 +66,7 @@ fn create_dataflow(
     create_operator("op_1".into(), Some(root.join("op_1")), use_path_deps)?;
     create_operator("op_2".into(), Some(root.join("op_2")), use_path_deps)?;
     create_custom_node("node_1".into(), Some(root.join("node_1")), use_path_deps)?;
+    super::write_readme(root, &name, BUILD_INSTRUCTIONS)?;
 
     println!(
         "Created new Rust dataflow at `{name}` at {}",
@@ -0,0 +1,250 @@
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use dora_core::{
+    config::{NodeId, OperatorId},
+    topics::{ControlRequest, ControlRequestReply, DataflowStats},
+};
+use eyre::{bail, Context, Result};
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// CPU usage above which operators that receive no messages are flagged as busy.
+const BUSY_CPU_PERCENT: f64 = 90.;
+
+/// Shows a live view of the nodes and links of the given dataflow until `q` is pressed.
+pub fn top(session: &mut TcpRequestReplyConnection, dataflow_uuid: Uuid) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode().wrap_err("failed to enable raw terminal mode")?;
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+
+    let result = run(session, dataflow_uuid, &mut stdout);
+
+    let _ = execute!(stdout, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+fn run(
+    session: &mut TcpRequestReplyConnection,
+    dataflow_uuid: Uuid,
+    stdout: &mut std::io::Stdout,
+) -> Result<()> {
+    let mut previous: Option<(Instant, DataflowStats)> = None;
+    loop {
+        let stats = query_stats(session, dataflow_uuid)?;
+        let now = Instant::now();
+        let previous_stats = previous
+            .as_ref()
+            .map(|(time, stats)| (now.duration_since(*time), stats));
+        let lines = render(dataflow_uuid, &stats, previous_stats);
+
+        let (width, height) = terminal::size().unwrap_or((120, 40));
+        queue!(stdout, Clear(ClearType::All))?;
+        for (row, line) in lines.iter().take(height as usize).enumerate() {
+            let line: String = line.chars().take(width as usize).collect();
+            queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+        }
+        stdout.flush()?;
+        previous = Some((now, stats));
+
+        // wait for the next refresh, but react to key presses immediately
+        let next_refresh = now + REFRESH_INTERVAL;
+        while let Some(remaining) = next_refresh.checked_duration_since(Instant::now()) {
+            if event::poll(remaining)? {
+                if let Event::Key(key) = event::read()? {
+                    if is_quit(key) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+fn render(
+    dataflow_uuid: Uuid,
+    stats: &DataflowStats,
+    previous: Option<(Duration, &DataflowStats)>,
+) -> Vec<String> {
+    let mut lines = vec![
+        format!("dora top - dataflow {dataflow_uuid} (press q to quit)"),
+        String::new(),
+        format!(
+            "{:<24} {:<12} {:>8} {:>7} {:>10} {:>9}",
+            "NODE", "MACHINE", "PID", "CPU%", "MEMORY", "THROTTLE%"
+        ),
+    ];
+    for (node_id, node) in &stats.nodes {
+        let cpu = node.process.and_then(|current| {
+            let (elapsed, previous) = previous?;
+            let previous = previous.nodes.get(node_id)?;
+            if previous.pid != node.pid {
+                return None;
+            }
+            let cpu_time = current.cpu_time.checked_sub(previous.process?.cpu_time)?;
+            Some(cpu_time.as_secs_f64() / elapsed.as_secs_f64() * 100.)
+        });
+        // share of the time in which the cgroup of the node was throttled
+        let throttled = node.process.and_then(|current| {
+            let (elapsed, previous) = previous?;
+            let previous = previous.nodes.get(node_id)?;
+            let throttling = current.throttling?.since(&previous.process?.throttling?);
+            Some(throttling.throttled_time.as_secs_f64() / elapsed.as_secs_f64() * 100.)
+        });
+        lines.push(format!(
+            "{:<24} {:<12} {:>8} {:>7} {:>10} {:>9}",
+            node_id.to_string(),
+            node.machine,
+            node.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            cpu.map(|cpu| format!("{cpu:.1}"))
+                .unwrap_or_else(|| "-".into()),
+            node.process
+                .map(|process| format_bytes(process.memory_bytes as f64))
+                .unwrap_or_else(|| "-".into()),
+            throttled
+                .map(|throttled| format!("{throttled:.1}"))
+                .unwrap_or_else(|| "-".into()),
+        ));
+
+        // operators of runtime nodes, flagged if they use a full core without
+        // receiving any messages
+        for (operator_id, cpu_time) in &node.operators {
+            let cpu = previous.and_then(|(elapsed, previous)| {
+                let previous = previous.nodes.get(node_id)?;
+                if previous.pid != node.pid {
+                    return None;
+                }
+                let cpu_time = cpu_time.checked_sub(*previous.operators.get(operator_id)?)?;
+                Some(cpu_time.as_secs_f64() / elapsed.as_secs_f64() * 100.)
+            });
+            let busy = cpu.is_some_and(|cpu| cpu >= BUSY_CPU_PERCENT)
+                && !received_messages(stats, previous, node_id, operator_id);
+            lines.push(format!(
+                "{:<24} {:<12} {:>8} {:>7} {:>10}{}",
+                format!("  {operator_id}"),
+                "",
+                "",
+                cpu.map(|cpu| format!("{cpu:.1}"))
+                    .unwrap_or_else(|| "-".into()),
+                "",
+                if busy { " BUSY" } else { "" },
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!(
+        "{:<48} {:>9} {:>11} {:>10} {:>9}",
+        "LINK", "MSG/S", "BYTES/S", "MESSAGES", "DROPPED"
+    ));
+    for link in &stats.links {
+        let rates = previous.and_then(|(elapsed, previous)| {
+            let previous = previous
+                .links
+                .iter()
+                .find(|l| l.target == link.target && l.input == link.input)?;
+            let secs = elapsed.as_secs_f64();
+            Some((
+                link.messages.saturating_sub(previous.messages) as f64 / secs,
+                link.bytes.saturating_sub(previous.bytes) as f64 / secs,
+            ))
+        });
+        let (message_rate, byte_rate) = match rates {
+            Some((messages, bytes)) => (format!("{messages:.1}"), format_bytes(bytes)),
+            None => ("-".into(), "-".into()),
+        };
+        lines.push(format!(
+            "{:<48} {:>9} {:>11} {:>10} {:>9}",
+            format!(
+                "{}/{} -> {}/{}",
+                link.source, link.output, link.target, link.input
+            ),
+            message_rate,
+            byte_rate,
+            link.messages,
+            link.dropped,
+        ));
+    }
+    lines
+}
+
+/// Checks whether any input of the given operator received a message since the
+/// previous stats.
+///
+/// Returns `true` if this is not known, e.g. because the operator has no inputs
+/// that are forwarded through the daemon.
+fn received_messages(
+    stats: &DataflowStats,
+    previous: Option<(Duration, &DataflowStats)>,
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+) -> bool {
+    let Some((_, previous)) = previous else {
+        return true;
+    };
+    let prefix = format!("{operator_id}/");
+    let mut links = stats
+        .links
+        .iter()
+        .filter(|link| &link.target == node_id && link.input.as_str().starts_with(&prefix))
+        .peekable();
+    if links.peek().is_none() {
+        return true;
+    }
+    links.any(|link| {
+        previous
+            .links
+            .iter()
+            .find(|l| l.target == link.target && l.input == link.input)
+            .map(|previous| link.messages > previous.messages)
+            .unwrap_or(true)
+    })
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn query_stats(
+    session: &mut TcpRequestReplyConnection,
+    dataflow_uuid: Uuid,
+) -> Result<DataflowStats> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::DataflowStats { dataflow_uuid }).unwrap())
+        .wrap_err("failed to send DataflowStats request message")?;
+    let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::DataflowStats(stats) => Ok(stats),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected dataflow stats reply: {other:?}"),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::tap::parse_output;
+use dora_core::message::{lineage::LineageEntry, Metadata};
+use dora_node_api::{Event, Observer};
+use eyre::{Context, Result};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Prints the lineage of the messages of an output of a dataflow running on
+/// the local machine, i.e. the upstream messages that each message was
+/// derived from.
+///
+/// Uses a read-only observer connection, so the dataflow is not affected. The
+/// lineage is only tracked for dataflows that set `lineage: true` or define
+/// `latency_budgets`.
+pub fn trace(dataflow_uuid: Uuid, output: &str, count: Option<usize>) -> Result<()> {
+    let output = parse_output(output)?;
+    let mut observer = Observer::connect(dataflow_uuid, [output].into())
+        .wrap_err("failed to connect to local dora daemon")?;
+
+    let mut traced = 0;
+    while count.map(|count| traced < count).unwrap_or(true) {
+        let Some(event) = observer.recv() else {
+            break;
+        };
+        match event {
+            Event::Input { id, metadata, .. } => {
+                println!("{} {id}", metadata.timestamp());
+                if metadata.lineage().is_empty() {
+                    println!("  no lineage, set `lineage: true` in the dataflow to track it");
+                }
+                print_lineage(&metadata);
+                traced += 1;
+            }
+            Event::InputClosed { id } => println!("{id} closed"),
+            Event::Error(err) => eprintln!("error: {err}"),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the lineage entries, starting at the source, together with their
+/// age relative to the traced message.
+fn print_lineage(metadata: &Metadata) {
+    let time = metadata.timestamp().get_time().to_duration();
+    for LineageEntry {
+        node,
+        output,
+        timestamp,
+    } in metadata.lineage().iter().rev()
+    {
+        let age = time.saturating_sub(timestamp.get_time().to_duration());
+        println!("  {:>10}  {node}/{output}", format_age(age));
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    if age.is_zero() {
+        "0".to_owned()
+    } else {
+        format!("-{:.3}ms", age.as_secs_f64() * 1000.)
+    }
+}
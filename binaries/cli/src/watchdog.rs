@@ -0,0 +1,684 @@
+//! Supervises a local coordinator and daemon for unattended deployments.
+//!
+//! Crashed processes are restarted with an exponential backoff. Dataflows that
+//! are passed to the watchdog are started again whenever they are no longer
+//! running, e.g. because the coordinator or daemon crashed. The current state is
+//! reported as JSON through a local HTTP health endpoint.
+//!
+//! The session state, i.e. the names and UUIDs of the supervised dataflows and
+//! the restart counts, is stored in a state file. A restarted watchdog adopts
+//! the dataflows that are still running instead of starting them twice, and
+//! dataflows keep their name when they are started again.
+
+use crate::{
+    check::daemon_running, connect_to_coordinator, query_running_dataflows, start_dataflow,
+};
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    descriptor::Descriptor,
+    topics::{ControlRequest, DataflowId},
+};
+use eyre::{Context, ContextCompat};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Processes that run for this long are considered stable, which resets their backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Time that the coordinator and daemon get to exit after a destroy request.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn run(
+    dataflows: Vec<PathBuf>,
+    health_port: u16,
+    max_backoff: Duration,
+    state_file: &Path,
+) -> eyre::Result<()> {
+    let mut state = SessionState::load(state_file)?;
+    let mut coordinator = Supervised::dora(
+        "coordinator",
+        state.coordinator_restarts,
+        coordinator_running,
+    )?;
+    let mut daemon = Supervised::dora("daemon", state.daemon_restarts, local_daemon_running)?;
+    let mut dataflows = supervised_dataflows(dataflows, &state)?;
+
+    let health = Arc::new(Mutex::new(Health::default()));
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, health_port))
+        .wrap_err_with(|| format!("failed to bind health endpoint to port {health_port}"))?;
+    let health_clone = health.clone();
+    std::thread::spawn(move || serve_health(listener, health_clone));
+    println!("watchdog health endpoint listening on http://127.0.0.1:{health_port}");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_clone = stop.clone();
+    ctrlc::set_handler(move || stop_clone.store(true, Ordering::SeqCst))
+        .wrap_err("failed to set ctrl-c handler")?;
+
+    while !stop.load(Ordering::SeqCst) {
+        coordinator.poll(max_backoff)?;
+        if coordinator.is_running() {
+            daemon.poll(max_backoff)?;
+        }
+        if coordinator.is_running() && daemon.is_running() {
+            if let Err(err) = supervise_dataflows(&mut dataflows, max_backoff) {
+                tracing::debug!("failed to check dataflows: {err:?}");
+            }
+        }
+
+        let report = Health {
+            healthy: coordinator.is_running()
+                && daemon.is_running()
+                && dataflows.iter().all(|d| d.running),
+            coordinator: coordinator.health(),
+            daemon: daemon.health(),
+            dataflows: dataflows.iter().map(SupervisedDataflow::health).collect(),
+        };
+        *health.lock().unwrap() = report;
+
+        let current_state = SessionState::new(&coordinator, &daemon, &dataflows);
+        if current_state != state {
+            if let Err(err) = current_state.save(state_file) {
+                tracing::warn!("failed to save watchdog state: {err:?}");
+            }
+            state = current_state;
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    println!("stopping coordinator and daemon");
+    if let Ok(mut session) = connect_to_coordinator() {
+        let _ = session.request(&serde_json::to_vec(&ControlRequest::Destroy).unwrap());
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    for process in [&mut daemon, &mut coordinator] {
+        process.shut_down(deadline);
+    }
+
+    // the dataflows were stopped on purpose, so starting them in the next
+    // session is no restart
+    for dataflow in &mut dataflows {
+        dataflow.uuid = None;
+    }
+    SessionState::new(&coordinator, &daemon, &dataflows).save(state_file)
+}
+
+fn coordinator_running() -> bool {
+    connect_to_coordinator().is_ok()
+}
+
+fn local_daemon_running() -> bool {
+    connect_to_coordinator()
+        .ok()
+        .and_then(|mut session| daemon_running(&mut *session).ok())
+        .unwrap_or(false)
+}
+
+/// Starts all dataflows that are not running (anymore).
+fn supervise_dataflows(
+    dataflows: &mut [SupervisedDataflow],
+    max_backoff: Duration,
+) -> eyre::Result<()> {
+    let mut session = connect_to_coordinator().wrap_err("failed to connect to coordinator")?;
+    if !daemon_running(&mut *session)? {
+        return Ok(());
+    }
+    let running = query_running_dataflows(&mut *session)?;
+    for dataflow in dataflows {
+        if !dataflow.needs_start(&running) {
+            continue;
+        }
+        match start(&dataflow.path, &dataflow.name, &mut *session) {
+            Ok(uuid) => {
+                println!("started dataflow `{}` as {uuid}", dataflow.path.display());
+                dataflow.uuid = Some(uuid);
+                dataflow.running = true;
+                dataflow.last_error = None;
+                dataflow.backoff = INITIAL_BACKOFF;
+            }
+            Err(err) => {
+                eprintln!(
+                    "failed to start dataflow `{}`, retrying in {:?}: {err:#}",
+                    dataflow.path.display(),
+                    dataflow.backoff
+                );
+                dataflow.last_error = Some(format!("{err:#}"));
+                dataflow.next_start = Instant::now() + dataflow.backoff;
+                dataflow.backoff = (dataflow.backoff * 2).min(max_backoff);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn start(path: &Path, name: &str, session: &mut TcpRequestReplyConnection) -> eyre::Result<Uuid> {
+    let descriptor = Descriptor::blocking_read(path).wrap_err("failed to read yaml dataflow")?;
+    let working_dir = path
+        .canonicalize()
+        .wrap_err("failed to canonicalize dataflow path")?
+        .parent()
+        .wrap_err("dataflow path has no parent dir")?
+        .to_owned();
+    descriptor
+        .check(&working_dir)
+        .wrap_err("could not validate yaml")?;
+    start_dataflow(descriptor, Some(name.to_owned()), working_dir, session)
+}
+
+/// Creates the supervised dataflows for the given paths, with the state of the
+/// previous session if there is one.
+///
+/// Dataflows are named after their file, with a numeric suffix if several
+/// files have the same name.
+fn supervised_dataflows(
+    paths: Vec<PathBuf>,
+    state: &SessionState,
+) -> eyre::Result<Vec<SupervisedDataflow>> {
+    let mut names = BTreeSet::new();
+    let mut dataflows = Vec::new();
+    for path in paths {
+        let path = path
+            .canonicalize()
+            .wrap_err_with(|| format!("failed to canonicalize `{}`", path.display()))?;
+        let dataflow = match state.dataflows.get(&path) {
+            Some(previous) => SupervisedDataflow::with_state(path, previous.clone()),
+            None => {
+                let stem = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "dataflow".to_owned());
+                let name = (1..)
+                    .map(|i| match i {
+                        1 => stem.clone(),
+                        i => format!("{stem}-{i}"),
+                    })
+                    .find(|name| {
+                        !names.contains(name) && state.dataflows.values().all(|d| &d.name != name)
+                    })
+                    .unwrap();
+                SupervisedDataflow::new(path, name)
+            }
+        };
+        names.insert(dataflow.name.clone());
+        dataflows.push(dataflow);
+    }
+    Ok(dataflows)
+}
+
+/// A process that is restarted when it exits.
+struct Supervised {
+    /// Name of the process in messages, e.g. `dora daemon`.
+    name: String,
+    program: PathBuf,
+    args: Vec<String>,
+    /// Checks whether an instance is reachable that the watchdog didn't start,
+    /// i.e. one that was started by a previous watchdog session.
+    running_elsewhere: fn() -> bool,
+    child: Option<Child>,
+    /// Whether a running instance that the watchdog didn't start is supervised.
+    adopted: bool,
+    started: Instant,
+    restarts: u32,
+    backoff: Duration,
+    next_start: Instant,
+    last_exit: Option<String>,
+}
+
+impl Supervised {
+    /// Supervises the given subcommand of the current `dora` executable.
+    fn dora(command: &str, restarts: u32, running_elsewhere: fn() -> bool) -> eyre::Result<Self> {
+        let program = std::env::current_exe().wrap_err("failed to get current executable path")?;
+        Ok(Self::new(
+            format!("dora {command}"),
+            program,
+            vec![command.to_owned()],
+            restarts,
+            running_elsewhere,
+        ))
+    }
+
+    fn new(
+        name: String,
+        program: PathBuf,
+        args: Vec<String>,
+        restarts: u32,
+        running_elsewhere: fn() -> bool,
+    ) -> Self {
+        Self {
+            name,
+            program,
+            args,
+            running_elsewhere,
+            child: None,
+            adopted: false,
+            started: Instant::now(),
+            restarts,
+            backoff: INITIAL_BACKOFF,
+            next_start: Instant::now(),
+            last_exit: None,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.child.is_some() || self.adopted
+    }
+
+    /// Checks whether the process exited and (re)starts it if its backoff elapsed.
+    fn poll(&mut self, max_backoff: Duration) -> eyre::Result<()> {
+        if self.adopted {
+            if (self.running_elsewhere)() {
+                return Ok(());
+            }
+            eprintln!(
+                "{} is no longer reachable, restarting in {:?}",
+                self.name, self.backoff
+            );
+            self.last_exit = Some("no longer reachable".into());
+            self.adopted = false;
+            self.restarts += 1;
+            self.next_start = Instant::now() + self.backoff;
+            self.backoff = (self.backoff * 2).min(max_backoff);
+        } else if let Some(child) = &mut self.child {
+            match child.try_wait() {
+                Ok(None) => {
+                    if self.started.elapsed() >= STABLE_AFTER {
+                        self.backoff = INITIAL_BACKOFF;
+                    }
+                    return Ok(());
+                }
+                Ok(Some(status)) => {
+                    eprintln!(
+                        "{} exited ({status}), restarting in {:?}",
+                        self.name, self.backoff
+                    );
+                    self.last_exit = Some(status.to_string());
+                }
+                Err(err) => {
+                    eprintln!("failed to check status of {}: {err}", self.name);
+                    self.last_exit = Some(err.to_string());
+                    let _ = child.kill();
+                }
+            }
+            self.child = None;
+            self.restarts += 1;
+            self.next_start = Instant::now() + self.backoff;
+            self.backoff = (self.backoff * 2).min(max_backoff);
+        }
+
+        // instances of a previous session are adopted when the watchdog starts
+        let first_start = self.last_exit.is_none();
+        if !self.is_running() && first_start && (self.running_elsewhere)() {
+            println!("{} is already running, supervising it", self.name);
+            self.adopted = true;
+            self.started = Instant::now();
+        } else if !self.is_running() && Instant::now() >= self.next_start {
+            let child = Command::new(&self.program)
+                .args(&self.args)
+                .spawn()
+                .wrap_err_with(|| format!("failed to run `{}`", self.name))?;
+            println!("started {} (pid {})", self.name, child.id());
+            self.child = Some(child);
+            self.started = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn shut_down(&mut self, deadline: Instant) {
+        self.adopted = false;
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        tracing::warn!("{} did not exit in time, killing it", self.name);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    fn health(&self) -> ProcessHealth {
+        ProcessHealth {
+            running: self.is_running(),
+            pid: self.child.as_ref().map(Child::id),
+            uptime_secs: self.is_running().then(|| self.started.elapsed().as_secs()),
+            restarts: self.restarts,
+            last_exit: self.last_exit.clone(),
+        }
+    }
+}
+
+struct SupervisedDataflow {
+    path: PathBuf,
+    name: String,
+    uuid: Option<Uuid>,
+    running: bool,
+    restarts: u32,
+    backoff: Duration,
+    next_start: Instant,
+    last_error: Option<String>,
+}
+
+impl SupervisedDataflow {
+    fn new(path: PathBuf, name: String) -> Self {
+        Self::with_state(
+            path,
+            DataflowState {
+                name,
+                uuid: None,
+                restarts: 0,
+            },
+        )
+    }
+
+    fn with_state(path: PathBuf, state: DataflowState) -> Self {
+        Self {
+            path,
+            name: state.name,
+            uuid: state.uuid,
+            running: false,
+            restarts: state.restarts,
+            backoff: INITIAL_BACKOFF,
+            next_start: Instant::now(),
+            last_error: None,
+        }
+    }
+
+    /// Updates the state from the given list of running dataflows and returns
+    /// whether the dataflow needs to be (re)started.
+    fn needs_start(&mut self, running: &[DataflowId]) -> bool {
+        self.running = self
+            .uuid
+            .is_some_and(|uuid| running.iter().any(|id| id.uuid == uuid));
+        if self.running || Instant::now() < self.next_start {
+            return false;
+        }
+        if let Some(uuid) = self.uuid.take() {
+            eprintln!(
+                "dataflow `{}` ({uuid}) is no longer running, restarting it",
+                self.path.display()
+            );
+            self.restarts += 1;
+        }
+        true
+    }
+
+    fn health(&self) -> DataflowHealth {
+        DataflowHealth {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            uuid: self.uuid,
+            running: self.running,
+            restarts: self.restarts,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+struct Health {
+    healthy: bool,
+    coordinator: ProcessHealth,
+    daemon: ProcessHealth,
+    dataflows: Vec<DataflowHealth>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+struct ProcessHealth {
+    running: bool,
+    pid: Option<u32>,
+    uptime_secs: Option<u64>,
+    restarts: u32,
+    last_exit: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DataflowHealth {
+    path: PathBuf,
+    name: String,
+    uuid: Option<Uuid>,
+    running: bool,
+    restarts: u32,
+    last_error: Option<String>,
+}
+
+/// State of a watchdog session that is kept across restarts of the watchdog.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct SessionState {
+    coordinator_restarts: u32,
+    daemon_restarts: u32,
+    /// Supervised dataflows by their canonical path.
+    dataflows: BTreeMap<PathBuf, DataflowState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct DataflowState {
+    name: String,
+    /// UUID of the last started instance of the dataflow.
+    uuid: Option<Uuid>,
+    restarts: u32,
+}
+
+impl SessionState {
+    fn new(
+        coordinator: &Supervised,
+        daemon: &Supervised,
+        dataflows: &[SupervisedDataflow],
+    ) -> Self {
+        Self {
+            coordinator_restarts: coordinator.restarts,
+            daemon_restarts: daemon.restarts,
+            dataflows: dataflows
+                .iter()
+                .map(|dataflow| {
+                    let state = DataflowState {
+                        name: dataflow.name.clone(),
+                        uuid: dataflow.uuid,
+                        restarts: dataflow.restarts,
+                    };
+                    (dataflow.path.clone(), state)
+                })
+                .collect(),
+        }
+    }
+
+    /// Loads the state of the previous session, or an empty state if there
+    /// is none.
+    fn load(path: &Path) -> eyre::Result<Self> {
+        match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .wrap_err_with(|| format!("failed to parse watchdog state `{}`", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err)
+                .wrap_err_with(|| format!("failed to read watchdog state `{}`", path.display())),
+        }
+    }
+
+    /// Writes the state to a temporary file first, so that a crash never
+    /// leaves a partially written state behind.
+    fn save(&self, path: &Path) -> eyre::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .wrap_err_with(|| format!("failed to write `{}`", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .wrap_err_with(|| format!("failed to replace `{}`", path.display()))
+    }
+}
+
+/// Answers every HTTP request with the current health report.
+///
+/// Responds with status `503` while the watchdog is not healthy, so that the
+/// endpoint can be used for liveness probes directly. Each connection is
+/// answered on its own thread, so that a slow client doesn't delay the others.
+fn serve_health(listener: TcpListener, health: Arc<Mutex<Health>>) {
+    for connection in listener.incoming() {
+        let stream = match connection {
+            Ok(stream) => stream,
+            Err(err) => {
+                tracing::debug!("failed to accept health request: {err}");
+                continue;
+            }
+        };
+        let health = health.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = respond(stream, &health) {
+                tracing::debug!("failed to answer health request: {err}");
+            }
+        });
+    }
+}
+
+fn respond(mut stream: TcpStream, health: &Mutex<Health>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+    // the request itself is not relevant, so only read the start of it
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request)?;
+    // report the health at the time of the request, not of the connection
+    let (status, body) = {
+        let health = health.lock().unwrap();
+        let status = if health.healthy {
+            "200 OK"
+        } else {
+            "503 Service Unavailable"
+        };
+        (status, serde_json::to_string(&*health).unwrap_or_default())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_session_state() {
+        let dir = std::env::temp_dir().join(format!("dora-watchdog-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a");
+        std::fs::create_dir_all(&a).unwrap();
+        std::fs::write(a.join("dataflow.yml"), "").unwrap();
+        std::fs::write(dir.join("dataflow.yml"), "").unwrap();
+        let paths = vec![a.join("dataflow.yml"), dir.join("dataflow.yml")];
+
+        let mut dataflows = supervised_dataflows(paths.clone(), &SessionState::default()).unwrap();
+        let names: Vec<_> = dataflows.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["dataflow", "dataflow-2"]);
+
+        // the first dataflow is running, the second one crashed
+        let uuid = Uuid::new_v7(uuid::Timestamp::now(uuid::NoContext));
+        dataflows[0].uuid = Some(uuid);
+        dataflows[1].restarts = 3;
+        let not_running = || false;
+        let coordinator =
+            Supervised::new("coordinator".into(), "true".into(), vec![], 1, not_running);
+        let daemon = Supervised::new("daemon".into(), "true".into(), vec![], 2, not_running);
+        let state_file = dir.join("state.json");
+        SessionState::new(&coordinator, &daemon, &dataflows)
+            .save(&state_file)
+            .unwrap();
+
+        // a restarted watchdog adopts the running dataflow and keeps the names
+        let state = SessionState::load(&state_file).unwrap();
+        assert_eq!((state.coordinator_restarts, state.daemon_restarts), (1, 2));
+        let mut paths = paths;
+        paths.reverse();
+        let mut restored = supervised_dataflows(paths, &state).unwrap();
+        assert_eq!(restored[0].name, "dataflow-2");
+        assert_eq!(restored[0].restarts, 3);
+        assert_eq!(restored[1].name, "dataflow");
+        let running = [DataflowId {
+            uuid,
+            name: Some("dataflow".into()),
+        }];
+        assert!(!restored[1].needs_start(&running));
+        assert!(restored[1].running);
+        assert_eq!(restored[1].restarts, 0);
+
+        // the dataflow is started again once it stops
+        assert!(restored[1].needs_start(&[]));
+        assert_eq!(restored[1].uuid, None);
+        assert_eq!(restored[1].restarts, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            SessionState::load(&state_file).unwrap(),
+            SessionState::default()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_crashed_process_with_backoff() {
+        let mut process = Supervised::new(
+            "crashing process".into(),
+            "sh".into(),
+            vec!["-c".into(), "exit 3".into()],
+            0,
+            || false,
+        );
+        let max_backoff = Duration::from_secs(3);
+        process.poll(max_backoff).unwrap();
+        assert!(process.is_running());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while process.restarts == 0 {
+            assert!(Instant::now() < deadline, "process did not exit");
+            std::thread::sleep(Duration::from_millis(10));
+            process.poll(max_backoff).unwrap();
+        }
+        assert_eq!(process.restarts, 1);
+        assert!(process.last_exit.as_deref().unwrap().contains('3'));
+        // the process is started again after the backoff only
+        assert!(!process.is_running());
+        assert_eq!(process.backoff, INITIAL_BACKOFF * 2);
+
+        process.next_start = Instant::now();
+        process.backoff = max_backoff;
+        process.poll(max_backoff).unwrap();
+        assert!(process.is_running());
+        process.shut_down(Instant::now() + Duration::from_secs(5));
+        assert!(!process.is_running());
+    }
+
+    #[test]
+    fn slow_client_does_not_block_health_requests() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let health = Arc::new(Mutex::new(Health::default()));
+        std::thread::spawn(move || serve_health(listener, health));
+
+        // connects, but never sends a request
+        let _slow = TcpStream::connect(addr).unwrap();
+
+        let mut probe = TcpStream::connect(addr).unwrap();
+        probe
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        probe.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        probe.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    }
+}
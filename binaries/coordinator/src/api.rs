@@ -0,0 +1,633 @@
+//! REST control API for external orchestration, e.g. fleet-management tools
+//! that launch dataflows without shelling out to the `dora` CLI.
+//!
+//! Enabled through the `api` section of the coordinator configuration file
+//! (`dora coordinator --config <file>`). The API listens on a loopback address
+//! by default, other addresses require a TLS certificate. Every request needs
+//! an `Authorization: Bearer <token>` header with the configured token. Request
+//! and response bodies are JSON, errors are reported as
+//! `{"error": "<message>"}`:
+//!
+//! - `GET /api/dataflows` lists the running dataflows.
+//! - `POST /api/dataflows` starts a dataflow, the body is a [`StartRequest`].
+//!   Responds with `201 Created` and the UUID of the new dataflow.
+//! - `GET /api/dataflows/{uuid}` responds with the node states, restarts and
+//!   parameters of a running dataflow.
+//! - `POST /api/dataflows/{uuid}/stop` stops a dataflow and responds with its
+//!   result once it finished.
+//! - `GET /api/dataflows/{uuid}/events` streams the node state changes of a
+//!   dataflow as server-sent `node_state` events, followed by a `finished`
+//!   event once the dataflow is no longer running.
+//! - `GET /api/dataflows/{uuid}/logs/{node}` streams the log of a node as
+//!   chunked `text/plain` until the dataflow is no longer running.
+
+use crate::{
+    control::{handle_request, ControlEvent},
+    Event,
+};
+use dora_core::{
+    descriptor::Descriptor,
+    http::{self, Request},
+    topics::{ControlRequest, ControlRequestReply},
+    transport::{tokens_match, ApiConfig, ApiTlsConfig, API_TOKEN_ENV},
+};
+use eyre::{bail, Context, ContextCompat};
+use futures::{Stream, StreamExt};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    TlsAcceptor,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// Interval in which the streaming endpoints check for updates.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Body of `POST /api/dataflows`.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StartRequest {
+    /// The dataflow descriptor, in its JSON representation.
+    ///
+    /// Parsed like a descriptor file in the working directory, i.e. included
+    /// dataflows are resolved relative to it.
+    dataflow: serde_json::Value,
+    /// Name of the dataflow, a random name is generated if not set.
+    #[serde(default)]
+    name: Option<String>,
+    /// Directory that relative paths of the dataflow are resolved against,
+    /// relative to the `dataflow_root` of the API config.
+    ///
+    /// Defaults to the `dataflow_root`.
+    #[serde(default)]
+    working_dir: Option<PathBuf>,
+}
+
+/// A plain TCP or TLS connection of an API client.
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T> Connection for T where T: AsyncRead + AsyncWrite + Unpin + Send {}
+
+/// Listens for control API requests on the configured address.
+pub async fn api_events(config: ApiConfig) -> eyre::Result<impl Stream<Item = Event>> {
+    let token = match config.token {
+        Some(token) => token,
+        None => std::env::var(API_TOKEN_ENV).wrap_err_with(|| {
+            format!(
+                "the control API requires a token, set `api.token` in the \
+                coordinator config or the `{API_TOKEN_ENV}` environment variable"
+            )
+        })?,
+    };
+    if token.is_empty() {
+        bail!("the token of the control API must not be empty");
+    }
+    let token: Arc<str> = token.into();
+
+    let tls = match &config.tls {
+        Some(tls) => Some(load_tls(tls)?),
+        None if config.bind.is_loopback() => None,
+        None => bail!(
+            "the control API only serves plaintext HTTP on loopback addresses, \
+            set `api.tls` to listen on `{}`",
+            config.bind
+        ),
+    };
+    let dataflow_root = match config.dataflow_root {
+        Some(root) => root,
+        None => std::env::current_dir().wrap_err("failed to get working directory")?,
+    };
+    let dataflow_root: Arc<Path> = dataflow_root
+        .canonicalize()
+        .wrap_err_with(|| format!("invalid dataflow root `{}`", dataflow_root.display()))?
+        .into();
+
+    let address = (config.bind, config.port);
+    let socket = TcpListener::bind(address).await.wrap_err_with(|| {
+        format!(
+            "failed to listen for control API requests on {}:{}",
+            config.bind, config.port
+        )
+    })?;
+    tracing::info!(
+        "listening for control API requests on {}:{}",
+        config.bind,
+        config.port
+    );
+
+    let (tx, rx) = mpsc::channel(10);
+    tokio::spawn(async move {
+        loop {
+            let connection = match socket.accept().await {
+                Ok((connection, _)) => connection,
+                Err(err) => {
+                    tracing::warn!("failed to accept control API connection: {err}");
+                    continue;
+                }
+            };
+            if tx.is_closed() {
+                // coordinator was stopped
+                break;
+            }
+            let tx = tx.clone();
+            let token = token.clone();
+            let tls = tls.clone();
+            let dataflow_root = dataflow_root.clone();
+            tokio::spawn(async move {
+                let connection: Box<dyn Connection> = match tls {
+                    Some(tls) => {
+                        match tokio::time::timeout(http::READ_TIMEOUT, tls.accept(connection)).await
+                        {
+                            Ok(Ok(connection)) => Box::new(connection),
+                            Ok(Err(err)) => {
+                                tracing::debug!("control API TLS handshake failed: {err}");
+                                return;
+                            }
+                            Err(_) => {
+                                tracing::debug!("control API TLS handshake timed out");
+                                return;
+                            }
+                        }
+                    }
+                    None => Box::new(connection),
+                };
+                if let Err(err) = serve(connection, &token, &dataflow_root, tx).await {
+                    tracing::debug!("failed to answer control API request: {err:?}");
+                }
+            });
+        }
+    });
+    Ok(ReceiverStream::new(rx).map(Event::Control))
+}
+
+fn load_tls(config: &ApiTlsConfig) -> eyre::Result<TlsAcceptor> {
+    let path = &config.certificate;
+    let file = File::open(path)
+        .wrap_err_with(|| format!("failed to open certificate `{}`", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .wrap_err_with(|| format!("failed to parse certificate `{}`", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificate found in `{}`", path.display());
+    }
+
+    let path = &config.key;
+    let file = File::open(path)
+        .wrap_err_with(|| format!("failed to open private key `{}`", path.display()))?;
+    let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(file))
+        .wrap_err_with(|| format!("failed to parse private key `{}`", path.display()))?
+        .into_iter()
+        .next()
+        .wrap_err_with(|| format!("no PKCS#8 private key found in `{}`", path.display()))?;
+
+    let server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certs.into_iter().map(Certificate).collect(),
+            PrivateKey(key),
+        )
+        .wrap_err("invalid control API certificate")?;
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    List,
+    Start,
+    Inspect(Uuid),
+    Stop(Uuid),
+    Events(Uuid),
+    Logs(Uuid, String),
+}
+
+struct ApiError {
+    status: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: "400 Bad Request",
+            message: message.into(),
+        }
+    }
+
+    fn unexpected_reply(reply: ControlRequestReply) -> Self {
+        Self {
+            status: "500 Internal Server Error",
+            message: format!("unexpected reply from coordinator: {reply:?}"),
+        }
+    }
+}
+
+async fn serve(
+    mut connection: Box<dyn Connection>,
+    token: &str,
+    dataflow_root: &Path,
+    control: mpsc::Sender<ControlEvent>,
+) -> eyre::Result<()> {
+    let request = match http::read_request(&mut connection, MAX_BODY_SIZE).await {
+        Ok(request) => request,
+        Err(err) => {
+            let err = ApiError::bad_request(format!("{err:#}"));
+            return respond_error(&mut connection, err).await;
+        }
+    };
+    if !authorized(&request, token) {
+        let err = ApiError {
+            status: "401 Unauthorized",
+            message: "missing or invalid bearer token".into(),
+        };
+        return respond_error(&mut connection, err).await;
+    }
+    let route = match parse_route(&request.method, &request.path) {
+        Ok(route) => route,
+        Err(err) => {
+            let err = ApiError {
+                status: "404 Not Found",
+                message: err.to_string(),
+            };
+            return respond_error(&mut connection, err).await;
+        }
+    };
+    tracing::debug!("control API request `{} {}`", request.method, request.path);
+
+    match route {
+        Route::Events(uuid) => stream_events(&mut connection, uuid, &control).await,
+        Route::Logs(uuid, node) => stream_logs(&mut connection, uuid, node, &control).await,
+        route => match handle_route(route, &request.body, dataflow_root, &control).await {
+            Ok((status, body)) => http::respond_json(&mut connection, status, &body).await,
+            Err(err) => respond_error(&mut connection, err).await,
+        },
+    }
+}
+
+async fn handle_route(
+    route: Route,
+    body: &[u8],
+    dataflow_root: &Path,
+    control: &mpsc::Sender<ControlEvent>,
+) -> Result<(&'static str, serde_json::Value), ApiError> {
+    match route {
+        Route::List => match control_request(ControlRequest::List, control).await? {
+            ControlRequestReply::DataflowList { dataflows } => {
+                Ok(("200 OK", serde_json::json!({ "dataflows": dataflows })))
+            }
+            other => Err(ApiError::unexpected_reply(other)),
+        },
+        Route::Start => {
+            let start: StartRequest = serde_json::from_slice(body)
+                .map_err(|err| ApiError::bad_request(format!("invalid start request: {err}")))?;
+            let local_working_dir =
+                resolve_working_dir(dataflow_root, start.working_dir.as_deref())
+                    .map_err(|err| ApiError::bad_request(format!("{err:#}")))?;
+            let dataflow = parse_dataflow(&start.dataflow, &local_working_dir)
+                .map_err(|err| ApiError::bad_request(format!("{err:#}")))?;
+            let request = ControlRequest::Start {
+                dataflow,
+                name: start.name,
+                local_working_dir,
+            };
+            match control_request(request, control).await? {
+                ControlRequestReply::DataflowStarted { uuid } => {
+                    Ok(("201 Created", serde_json::json!({ "uuid": uuid })))
+                }
+                other => Err(ApiError::unexpected_reply(other)),
+            }
+        }
+        Route::Inspect(dataflow_uuid) => {
+            let nodes = match control_request(ControlRequest::NodeStates { dataflow_uuid }, control)
+                .await?
+            {
+                ControlRequestReply::NodeStates(nodes) => nodes,
+                other => return Err(ApiError::unexpected_reply(other)),
+            };
+            let restarts =
+                match control_request(ControlRequest::Restarts { dataflow_uuid }, control).await? {
+                    ControlRequestReply::Restarts(restarts) => restarts,
+                    other => return Err(ApiError::unexpected_reply(other)),
+                };
+            let parameters =
+                match control_request(ControlRequest::Parameters { dataflow_uuid }, control).await?
+                {
+                    ControlRequestReply::Parameters(parameters) => parameters,
+                    other => return Err(ApiError::unexpected_reply(other)),
+                };
+            let body = serde_json::json!({
+                "uuid": dataflow_uuid,
+                "nodes": nodes,
+                "restarts": restarts,
+                "parameters": parameters,
+            });
+            Ok(("200 OK", body))
+        }
+        Route::Stop(dataflow_uuid) => {
+            match control_request(ControlRequest::Stop { dataflow_uuid }, control).await? {
+                ControlRequestReply::DataflowStopped {
+                    uuid,
+                    result,
+                    report,
+                } => {
+                    let body = serde_json::json!({
+                        "uuid": uuid,
+                        "error": result.err(),
+                        "report": report,
+                    });
+                    Ok(("200 OK", body))
+                }
+                other => Err(ApiError::unexpected_reply(other)),
+            }
+        }
+        Route::Events(_) | Route::Logs(..) => {
+            unreachable!("streaming routes are handled by `serve`")
+        }
+    }
+}
+
+/// Resolves the requested working directory within the dataflow root,
+/// rejecting directories outside of it, e.g. through `..` or symlinks.
+fn resolve_working_dir(dataflow_root: &Path, requested: Option<&Path>) -> eyre::Result<PathBuf> {
+    let Some(requested) = requested else {
+        return Ok(dataflow_root.to_owned());
+    };
+    if requested.is_absolute() {
+        bail!(
+            "working directory `{}` must be relative to the dataflow root",
+            requested.display()
+        );
+    }
+    let working_dir = dataflow_root
+        .join(requested)
+        .canonicalize()
+        .wrap_err_with(|| format!("invalid working directory `{}`", requested.display()))?;
+    if !working_dir.starts_with(dataflow_root) {
+        bail!(
+            "working directory `{}` is outside of the dataflow root",
+            requested.display()
+        );
+    }
+    Ok(working_dir)
+}
+
+/// Parses the descriptor like a descriptor file in the working directory, i.e.
+/// with the same checks for unknown fields and with resolved includes.
+fn parse_dataflow(dataflow: &serde_json::Value, working_dir: &Path) -> eyre::Result<Descriptor> {
+    // JSON is valid YAML
+    let raw = serde_json::to_vec(dataflow)?;
+    Descriptor::parse_in(raw, working_dir).wrap_err("invalid dataflow")
+}
+
+async fn control_request(
+    request: ControlRequest,
+    control: &mpsc::Sender<ControlEvent>,
+) -> Result<ControlRequestReply, ApiError> {
+    match handle_request(request, control).await {
+        Ok(ControlRequestReply::CoordinatorStopped) => Err(ApiError {
+            status: "503 Service Unavailable",
+            message: "coordinator stopped".into(),
+        }),
+        Ok(ControlRequestReply::Error(err)) => Err(ApiError::bad_request(err)),
+        Ok(reply) => Ok(reply),
+        Err(err) => Err(ApiError::bad_request(format!("{err:#}"))),
+    }
+}
+
+/// Streams the node state changes of the dataflow as server-sent events.
+async fn stream_events(
+    connection: &mut impl Connection,
+    dataflow_uuid: Uuid,
+    control: &mpsc::Sender<ControlEvent>,
+) -> eyre::Result<()> {
+    let node_states = || async move {
+        match control_request(ControlRequest::NodeStates { dataflow_uuid }, control).await? {
+            ControlRequestReply::NodeStates(states) => Ok(states),
+            other => Err(ApiError::unexpected_reply(other)),
+        }
+    };
+    let mut states = match node_states().await {
+        Ok(states) => states,
+        Err(err) => return respond_error(connection, err).await,
+    };
+
+    let headers = [
+        ("Content-Type", "text/event-stream"),
+        ("Cache-Control", "no-cache"),
+        ("Connection", "close"),
+    ];
+    http::write_head(connection, "200 OK", &headers).await?;
+    for (node, state) in &states {
+        let data = serde_json::json!({ "node": node, "state": state });
+        send_event(connection, "node_state", &data).await?;
+    }
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        // fails once the dataflow is no longer running
+        let Ok(new_states) = node_states().await else {
+            break;
+        };
+        for (node, state) in &new_states {
+            if states.get(node) != Some(state) {
+                let data = serde_json::json!({ "node": node, "state": state });
+                send_event(connection, "node_state", &data).await?;
+            }
+        }
+        states = new_states;
+    }
+    send_event(
+        connection,
+        "finished",
+        &serde_json::json!({ "uuid": dataflow_uuid }),
+    )
+    .await
+}
+
+async fn send_event(
+    connection: &mut impl Connection,
+    event: &str,
+    data: &serde_json::Value,
+) -> eyre::Result<()> {
+    let message = format!("event: {event}\ndata: {data}\n\n");
+    connection.write_all(message.as_bytes()).await?;
+    Ok(())
+}
+
+/// Streams the log of the node using chunked transfer encoding.
+///
+/// Only the part of the log that was not sent yet is retrieved on each poll.
+async fn stream_logs(
+    connection: &mut impl Connection,
+    uuid: Uuid,
+    node: String,
+    control: &mpsc::Sender<ControlEvent>,
+) -> eyre::Result<()> {
+    let node = &node;
+    let logs = |offset| async move {
+        let request = ControlRequest::Logs {
+            uuid: Some(uuid),
+            name: None,
+            node: node.clone(),
+            offset,
+        };
+        match control_request(request, control).await? {
+            ControlRequestReply::Logs(logs) => Ok(logs),
+            other => Err(ApiError::unexpected_reply(other)),
+        }
+    };
+    let running = || async move {
+        match control_request(ControlRequest::List, control).await {
+            Ok(ControlRequestReply::DataflowList { dataflows }) => {
+                dataflows.iter().any(|d| d.uuid == uuid)
+            }
+            _ => false,
+        }
+    };
+    let mut sent = match logs(0).await {
+        Ok(logs) => {
+            let headers = [
+                ("Content-Type", "text/plain; charset=utf-8"),
+                ("Transfer-Encoding", "chunked"),
+                ("Connection", "close"),
+            ];
+            http::write_head(connection, "200 OK", &headers).await?;
+            send_chunk(connection, &logs).await?;
+            logs.len() as u64
+        }
+        Err(err) => return respond_error(connection, err).await,
+    };
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        // check before retrieving the logs to include everything that was
+        // logged until the dataflow finished
+        let running = running().await;
+        let Ok(new) = logs(sent).await else {
+            break;
+        };
+        send_chunk(connection, &new).await?;
+        sent += new.len() as u64;
+        if !running {
+            break;
+        }
+    }
+    connection.write_all(b"0\r\n\r\n").await?;
+    Ok(())
+}
+
+async fn send_chunk(connection: &mut impl Connection, data: &[u8]) -> eyre::Result<()> {
+    // an empty chunk would end the response
+    if !data.is_empty() {
+        connection
+            .write_all(format!("{:x}\r\n", data.len()).as_bytes())
+            .await?;
+        connection.write_all(data).await?;
+        connection.write_all(b"\r\n").await?;
+    }
+    Ok(())
+}
+
+fn parse_route(method: &str, path: &str) -> eyre::Result<Route> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let uuid =
+        |s: &str| Uuid::parse_str(s).wrap_err_with(|| format!("invalid dataflow UUID `{s}`"));
+    match (method, segments.as_slice()) {
+        ("GET", ["api", "dataflows"]) => Ok(Route::List),
+        ("POST", ["api", "dataflows"]) => Ok(Route::Start),
+        ("GET", ["api", "dataflows", id]) => Ok(Route::Inspect(uuid(id)?)),
+        ("POST", ["api", "dataflows", id, "stop"]) => Ok(Route::Stop(uuid(id)?)),
+        ("GET", ["api", "dataflows", id, "events"]) => Ok(Route::Events(uuid(id)?)),
+        ("GET", ["api", "dataflows", id, "logs", node]) => {
+            Ok(Route::Logs(uuid(id)?, (*node).to_owned()))
+        }
+        _ => bail!("unknown route `{method} {path}`"),
+    }
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    let Some(provided) = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    tokens_match(provided, token)
+}
+
+async fn respond_error(connection: &mut impl Connection, err: ApiError) -> eyre::Result<()> {
+    let body = serde_json::json!({ "error": err.message });
+    http::respond_json(connection, err.status, &body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_requests() {
+        let uuid = Uuid::new_v4();
+        let request = Request::parse_head(&format!(
+            "POST /api/dataflows/{uuid}/stop?force=1 HTTP/1.1\r\nHost: localhost\r\n\
+            authorization: Bearer secret\r\nContent-Length: 2"
+        ))
+        .unwrap();
+        assert!(authorized(&request, "secret"));
+        assert!(!authorized(&request, "secret2"));
+        assert!(!authorized(&request, "other!"));
+        assert_eq!(
+            parse_route(&request.method, &request.path).unwrap(),
+            Route::Stop(uuid)
+        );
+
+        assert_eq!(parse_route("GET", "/api/dataflows/").unwrap(), Route::List);
+        assert_eq!(
+            parse_route("GET", &format!("/api/dataflows/{uuid}/logs/camera")).unwrap(),
+            Route::Logs(uuid, "camera".into())
+        );
+        assert!(parse_route("GET", "/api/dataflows/not-a-uuid").is_err());
+        assert!(parse_route("DELETE", &format!("/api/dataflows/{uuid}")).is_err());
+    }
+
+    #[test]
+    fn start_dataflows_within_root() {
+        let root = std::env::temp_dir().join(format!("dora-api-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("robot")).unwrap();
+        let root = root.canonicalize().unwrap();
+        std::fs::write(
+            root.join("robot/camera.yml"),
+            "nodes:\n  - id: camera\n    custom:\n      source: ./camera\n",
+        )
+        .unwrap();
+
+        assert_eq!(resolve_working_dir(&root, None).unwrap(), root);
+        let working_dir = resolve_working_dir(&root, Some(Path::new("robot"))).unwrap();
+        assert_eq!(working_dir, root.join("robot"));
+        assert!(resolve_working_dir(&root, Some(Path::new("robot/../.."))).is_err());
+        assert!(resolve_working_dir(&root, Some(&std::env::temp_dir())).is_err());
+
+        let dataflow = serde_json::json!({
+            "include": [{ "id": "front", "path": "camera.yml" }],
+            "nodes": [],
+        });
+        let descriptor = parse_dataflow(&dataflow, &working_dir).unwrap();
+        assert_eq!(descriptor.nodes[0].id.to_string(), "front_camera");
+
+        let typo = serde_json::json!({ "nodes": [], "node": [] });
+        assert!(parse_dataflow(&typo, &working_dir).is_err());
+    }
+}
@@ -135,7 +135,7 @@ async fn handle_requests(
     }
 }
 
-async fn handle_request(
+pub(crate) async fn handle_request(
     request: ControlRequest,
     tx: &mpsc::Sender<ControlEvent>,
 ) -> eyre::Result<ControlRequestReply> {
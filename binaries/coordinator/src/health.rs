@@ -0,0 +1,246 @@
+//! HTTP health endpoint for liveness and readiness probes, e.g. of systemd or
+//! Kubernetes.
+//!
+//! Enabled through `dora coordinator --healthcheck-port <port>`. Both routes
+//! respond with a JSON [`HealthReport`]:
+//!
+//! - `GET /health/live` responds with `200 OK` as long as the coordinator
+//!   handles events.
+//! - `GET /health/ready` responds with `200 OK` if at least one daemon is
+//!   connected, all connected daemons sent a recent heartbeat, and all nodes
+//!   of the running dataflows are ready, and with `503 Service Unavailable`
+//!   otherwise.
+
+use crate::{DaemonConnection, Event, RunningDataflow};
+use dora_core::{
+    config::NodeId,
+    coordinator_messages::NodeState,
+    http::{read_request, respond_json, Request},
+};
+use eyre::{bail, Context};
+use futures::Stream;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::Ipv4Addr,
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+/// Time after which the coordinator is reported as unresponsive.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Daemons send a heartbeat every 5 seconds, so daemons without a heartbeat
+/// for this long missed at least one and are reported as unresponsive.
+const MAX_HEARTBEAT_AGE: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub struct HealthRequest {
+    reply_sender: oneshot::Sender<HealthReport>,
+}
+
+impl HealthRequest {
+    pub(crate) fn reply(
+        self,
+        running_dataflows: &HashMap<Uuid, RunningDataflow>,
+        daemon_connections: &HashMap<String, DaemonConnection>,
+    ) {
+        let mut dataflows: Vec<_> = running_dataflows
+            .values()
+            .map(DataflowHealth::new)
+            .collect();
+        dataflows.sort_by(|a, b| (&a.name, a.uuid).cmp(&(&b.name, b.uuid)));
+        let daemons: BTreeMap<_, _> = daemon_connections
+            .iter()
+            .map(|(machine_id, connection)| (machine_id.clone(), DaemonHealth::new(connection)))
+            .collect();
+        let report = HealthReport {
+            ready: !daemons.is_empty()
+                && daemons.values().all(|d| d.responsive)
+                && dataflows.iter().all(|d| d.ready),
+            daemons,
+            running_dataflows: dataflows.len(),
+            dataflows,
+        };
+        let _ = self.reply_sender.send(report);
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct HealthReport {
+    ready: bool,
+    /// The connected daemons, by machine ID.
+    daemons: BTreeMap<String, DaemonHealth>,
+    running_dataflows: usize,
+    dataflows: Vec<DataflowHealth>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DaemonHealth {
+    /// Whether the daemon sent a heartbeat within [`MAX_HEARTBEAT_AGE`].
+    responsive: bool,
+    /// Time since the last heartbeat of the daemon, in milliseconds.
+    last_heartbeat_ms: u64,
+}
+
+impl DaemonHealth {
+    fn new(connection: &DaemonConnection) -> Self {
+        let age = connection.last_heartbeat.elapsed();
+        Self {
+            responsive: age <= MAX_HEARTBEAT_AGE,
+            last_heartbeat_ms: age.as_millis().try_into().unwrap_or(u64::MAX),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DataflowHealth {
+    uuid: Uuid,
+    name: Option<String>,
+    /// Whether all machines started the dataflow and all nodes are ready.
+    ready: bool,
+    nodes: BTreeMap<NodeId, NodeHealth>,
+}
+
+impl DataflowHealth {
+    fn new(dataflow: &RunningDataflow) -> Self {
+        let nodes: BTreeMap<_, _> = dataflow
+            .node_states
+            .iter()
+            .map(|(id, state)| {
+                let health = NodeHealth {
+                    state: *state,
+                    ready: is_ready(*state),
+                };
+                (id.clone(), health)
+            })
+            .collect();
+        Self {
+            uuid: dataflow.uuid,
+            name: dataflow.name.clone(),
+            ready: dataflow.pending_machines.is_empty() && nodes.values().all(|n| n.ready),
+            nodes,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct NodeHealth {
+    state: NodeState,
+    ready: bool,
+}
+
+/// Nodes are ready once they subscribed to their events, nodes that exited
+/// successfully stay ready.
+fn is_ready(state: NodeState) -> bool {
+    matches!(state, NodeState::Active | NodeState::Finalized)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    Liveness,
+    Readiness,
+}
+
+/// Listens for health probes on the given port.
+pub async fn health_events(port: u16) -> eyre::Result<impl Stream<Item = Event>> {
+    let socket = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .await
+        .wrap_err_with(|| format!("failed to listen for health checks on port {port}"))?;
+    tracing::info!("listening for health checks on port {port}");
+
+    let (tx, rx) = mpsc::channel(10);
+    tokio::spawn(async move {
+        loop {
+            let connection = match socket.accept().await {
+                Ok((connection, _)) => connection,
+                Err(err) => {
+                    tracing::warn!("failed to accept health check connection: {err}");
+                    continue;
+                }
+            };
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Err(err) = serve(connection, tx).await {
+                    tracing::debug!("failed to answer health check: {err:?}");
+                }
+            });
+        }
+    });
+    Ok(ReceiverStream::new(rx))
+}
+
+async fn serve(mut connection: TcpStream, events: mpsc::Sender<Event>) -> eyre::Result<()> {
+    let request = read_request(&mut connection, 0).await?;
+    let probe = match parse_request(&request) {
+        Ok(probe) => probe,
+        Err(err) => {
+            let body = serde_json::json!({ "error": err.to_string() });
+            return respond_json(&mut connection, "404 Not Found", &body).await;
+        }
+    };
+
+    let (reply_sender, reply) = oneshot::channel();
+    let report = tokio::time::timeout(REPLY_TIMEOUT, async {
+        events
+            .send(Event::Health(HealthRequest { reply_sender }))
+            .await
+            .ok()?;
+        reply.await.ok()
+    })
+    .await
+    .ok()
+    .flatten();
+
+    let Some(report) = report else {
+        let body = serde_json::json!({ "error": "coordinator is not responding" });
+        return respond_json(&mut connection, "503 Service Unavailable", &body).await;
+    };
+    let healthy = match probe {
+        Probe::Liveness => true,
+        Probe::Readiness => report.ready,
+    };
+    let status = if healthy {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    respond_json(&mut connection, status, &serde_json::to_value(report)?).await
+}
+
+fn parse_request(request: &Request) -> eyre::Result<Probe> {
+    if request.method != "GET" {
+        bail!("unsupported method `{}`", request.method);
+    }
+    match request.path.trim_end_matches('/') {
+        "/health/live" => Ok(Probe::Liveness),
+        "/health/ready" => Ok(Probe::Readiness),
+        other => bail!("unknown path `{other}` (expected `/health/live` or `/health/ready`)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_probe_requests() {
+        let request = |line: &str| {
+            let head = format!("{line}\r\nHost: localhost");
+            parse_request(&Request::parse_head(&head).unwrap())
+        };
+        assert_eq!(
+            request("GET /health/live HTTP/1.1").unwrap(),
+            Probe::Liveness
+        );
+        assert_eq!(
+            request("GET /health/ready/?verbose=1 HTTP/1.1").unwrap(),
+            Probe::Readiness
+        );
+        assert!(request("GET /health HTTP/1.1").is_err());
+        assert!(request("POST /health/live HTTP/1.1").is_err());
+    }
+}
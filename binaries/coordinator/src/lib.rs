@@ -4,27 +4,33 @@ use crate::{
 };
 pub use control::ControlEvent;
 use dora_core::{
-    config::{NodeId, OperatorId},
-    coordinator_messages::RegisterResult,
+    config::{NodeId, OperatorId, RestartScope},
+    coordinator_messages::{NodeState, RegisterResult},
     daemon_messages::{DaemonCoordinatorEvent, DaemonCoordinatorReply, Timestamped},
     descriptor::{Descriptor, ResolvedNode},
-    message::uhlc::{self, HLC},
+    message::{
+        uhlc::{self, HLC},
+        MetadataValue,
+    },
     topics::{
-        control_socket_addr, ControlRequest, ControlRequestReply, DataflowId,
-        DORA_COORDINATOR_PORT_DEFAULT,
+        control_socket_addr, ControlRequest, ControlRequestReply, DataflowId, DataflowStats,
+        NodeRestart, RateReport, DORA_COORDINATOR_PORT_DEFAULT,
     },
+    transport::ApiConfig,
 };
 use eyre::{bail, eyre, ContextCompat, WrapErr};
 use futures::{stream::FuturesUnordered, Future, Stream, StreamExt};
 use futures_concurrency::stream::Merge;
+pub use placement::{MachineInfo, PlacementPlugin, PlacementReply, PlacementRequest};
 use run::SpawnedDataflow;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     net::SocketAddr,
     path::PathBuf,
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
+use supervision::{DataflowRestart, RestartEvent};
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::mpsc,
@@ -33,13 +39,20 @@ use tokio::{
 use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
 use uuid::Uuid;
 
+mod api;
 mod control;
+mod health;
 mod listener;
+mod placement;
 mod run;
+mod supervision;
 mod tcp_utils;
 
 pub async fn start(
     port: Option<u16>,
+    placement: Option<PlacementPlugin>,
+    healthcheck_port: Option<u16>,
+    api: Option<ApiConfig>,
     external_events: impl Stream<Item = Event> + Unpin,
 ) -> Result<(u16, impl Future<Output = eyre::Result<()>>), eyre::ErrReport> {
     let port = port.unwrap_or(DORA_COORDINATOR_PORT_DEFAULT);
@@ -50,11 +63,28 @@ pub async fn start(
         .port();
     let mut tasks = FuturesUnordered::new();
 
+    let health_events = match healthcheck_port {
+        Some(port) => Some(Box::pin(health::health_events(port).await?)),
+        None => None,
+    };
+    let health_events = futures::stream::iter(health_events).flatten();
+    let api_events = match api {
+        Some(config) => Some(Box::pin(api::api_events(config).await?)),
+        None => None,
+    };
+    let api_events = futures::stream::iter(api_events).flatten();
+
     // Setup ctrl-c handler
     let ctrlc_events = set_up_ctrlc_handler()?;
 
     let future = async move {
-        start_inner(listener, &tasks, (ctrlc_events, external_events).merge()).await?;
+        start_inner(
+            listener,
+            &tasks,
+            placement,
+            (ctrlc_events, external_events, health_events, api_events).merge(),
+        )
+        .await?;
 
         tracing::debug!("coordinator main loop finished, waiting on spawned tasks");
         while let Some(join_result) = tasks.next().await {
@@ -106,6 +136,7 @@ fn resolve_name(
 async fn start_inner(
     listener: TcpListener,
     tasks: &FuturesUnordered<JoinHandle<()>>,
+    placement: Option<PlacementPlugin>,
     external_events: impl Stream<Item = Event> + Unpin,
 ) -> eyre::Result<()> {
     let clock = Arc::new(HLC::default());
@@ -128,6 +159,9 @@ async fn start_inner(
         tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(Duration::from_secs(3)))
             .map(|_| Event::DaemonHeartbeatInterval);
 
+    let (restart_tx, restart_events) = mpsc::channel(10);
+    let restart_events = ReceiverStream::new(restart_events);
+
     // events that should be aborted on `dora destroy`
     let (abortable_events, abort_handle) = futures::stream::abortable(
         (
@@ -135,6 +169,7 @@ async fn start_inner(
             new_daemon_connections,
             external_events,
             daemon_heartbeat_interval,
+            restart_events,
         )
             .merge(),
     );
@@ -143,6 +178,7 @@ async fn start_inner(
 
     let mut running_dataflows: HashMap<Uuid, RunningDataflow> = HashMap::new();
     let mut dataflow_results: HashMap<Uuid, BTreeMap<String, Result<(), String>>> = HashMap::new();
+    let mut dataflow_reports: HashMap<Uuid, RateReport> = HashMap::new();
     let mut archived_dataflows: HashMap<Uuid, ArchivedDataflow> = HashMap::new();
     let mut daemon_connections: HashMap<_, DaemonConnection> = HashMap::new();
 
@@ -261,7 +297,11 @@ async fn start_inner(
                         }
                     }
                 }
-                DataflowEvent::DataflowFinishedOnMachine { machine_id, result } => {
+                DataflowEvent::DataflowFinishedOnMachine {
+                    machine_id,
+                    result,
+                    report,
+                } => {
                     match running_dataflows.entry(uuid) {
                         std::collections::hash_map::Entry::Occupied(mut entry) => {
                             // Archive finished dataflow
@@ -282,14 +322,31 @@ async fn start_inner(
                                 .entry(uuid)
                                 .or_default()
                                 .insert(machine_id, result.map_err(|err| format!("{err:?}")));
+                            dataflow_reports.entry(uuid).or_default().merge(report);
                             if entry.get_mut().machines.is_empty() {
                                 let finished_dataflow = entry.remove();
+                                if let Some(delay) = finished_dataflow.restart_delay {
+                                    if !finished_dataflow.stop_requested {
+                                        supervision::schedule(
+                                            delay,
+                                            RestartEvent::Dataflow(DataflowRestart::new(
+                                                finished_dataflow,
+                                            )),
+                                            &restart_tx,
+                                        );
+                                        continue;
+                                    }
+                                }
                                 let reply = ControlRequestReply::DataflowStopped {
                                     uuid,
                                     result: dataflow_results
                                         .get(&uuid)
                                         .map(|r| dataflow_result(r, uuid))
                                         .unwrap_or(Ok(())),
+                                    report: dataflow_reports
+                                        .get(&uuid)
+                                        .cloned()
+                                        .unwrap_or_default(),
                                 };
                                 for sender in finished_dataflow.reply_senders {
                                     let _ = sender.send(Ok(reply.clone()));
@@ -301,8 +358,103 @@ async fn start_inner(
                         }
                     }
                 }
+                DataflowEvent::NodeStateChanged { node_id, state } => {
+                    match running_dataflows.get_mut(&uuid) {
+                        Some(dataflow) => {
+                            tracing::debug!("node `{uuid}/{node_id}` is {state}");
+                            dataflow.node_states.insert(node_id.clone(), state);
+                            if state == NodeState::Errored {
+                                let result = supervision::handle_node_failure(
+                                    dataflow,
+                                    node_id,
+                                    &mut daemon_connections,
+                                    &restart_tx,
+                                    clock.new_timestamp(),
+                                )
+                                .await;
+                                if let Err(err) = result {
+                                    tracing::warn!("{err:?}");
+                                }
+                            }
+                        }
+                        None => {
+                            tracing::warn!("dataflow not running on NodeStateChanged");
+                        }
+                    }
+                }
             },
 
+            Event::Restart(RestartEvent::Node {
+                uuid,
+                node_id,
+                attempt,
+            }) => {
+                let Some(dataflow) = running_dataflows.get_mut(&uuid) else {
+                    tracing::debug!("dataflow `{uuid}` finished before restart of `{node_id}`");
+                    continue;
+                };
+                if dataflow.stop_requested {
+                    continue;
+                }
+                let result = supervision::restart_node(
+                    dataflow,
+                    node_id.clone(),
+                    &mut daemon_connections,
+                    clock.new_timestamp(),
+                )
+                .await;
+                if let Err(err) = &result {
+                    tracing::warn!("{err:?}");
+                }
+                dataflow.restarts.push(NodeRestart {
+                    node_id,
+                    dataflow_uuid: uuid,
+                    time: SystemTime::now(),
+                    attempt,
+                    scope: RestartScope::Node,
+                    error: result.err().map(|err| format!("{err:?}")),
+                });
+            }
+            Event::Restart(RestartEvent::Dataflow(restart)) => {
+                let previous_uuid = restart.previous_uuid;
+                let result = start_dataflow(
+                    restart.descriptor.clone(),
+                    restart.working_dir.clone(),
+                    restart.name.clone(),
+                    &mut daemon_connections,
+                    &clock,
+                )
+                .await;
+                match result {
+                    Ok(mut dataflow) => {
+                        tracing::info!(
+                            "restarted dataflow `{previous_uuid}` as `{}`",
+                            dataflow.uuid
+                        );
+                        dataflow.restarts = restart.restarts;
+                        dataflow.restart_counts = restart.restart_counts;
+                        dataflow.reply_senders = restart.reply_senders;
+                        running_dataflows.insert(dataflow.uuid, dataflow);
+                    }
+                    Err(err) => {
+                        let err =
+                            err.wrap_err(format!("failed to restart dataflow `{previous_uuid}`"));
+                        tracing::error!("{err:?}");
+                        let reply = ControlRequestReply::DataflowStopped {
+                            uuid: previous_uuid,
+                            result: Err(format!("{err:?}")),
+                            report: dataflow_reports
+                                .get(&previous_uuid)
+                                .cloned()
+                                .unwrap_or_default(),
+                        };
+                        for sender in restart.reply_senders {
+                            let _ = sender.send(Ok(reply.clone()));
+                        }
+                    }
+                }
+            }
+
             Event::Control(event) => match event {
                 ControlEvent::IncomingRequest {
                     request,
@@ -326,6 +478,13 @@ async fn start_inner(
                                         bail!("there is already a running dataflow with name `{name}`");
                                     }
                                 }
+                                let dataflow = match &placement {
+                                    Some(placement) => placement
+                                        .place(dataflow, &daemon_connections, &running_dataflows)
+                                        .await
+                                        .wrap_err("failed to place nodes on machines")?,
+                                    None => dataflow,
+                                };
                                 let dataflow = start_dataflow(
                                     dataflow,
                                     local_working_dir,
@@ -354,6 +513,10 @@ async fn start_inner(
                                         .get(&dataflow_uuid)
                                         .map(|r| dataflow_result(r, dataflow_uuid))
                                         .unwrap_or(Ok(())),
+                                    report: dataflow_reports
+                                        .get(&dataflow_uuid)
+                                        .cloned()
+                                        .unwrap_or_default(),
                                 },
                             };
                             let _ = reply_sender.send(Ok(status));
@@ -387,6 +550,7 @@ async fn start_inner(
                             stop_dataflow_by_uuid(
                                 &mut running_dataflows,
                                 &dataflow_results,
+                                &dataflow_reports,
                                 dataflow_uuid,
                                 &mut daemon_connections,
                                 reply_sender,
@@ -400,6 +564,7 @@ async fn start_inner(
                                     stop_dataflow_by_uuid(
                                         &mut running_dataflows,
                                         &dataflow_results,
+                                        &dataflow_reports,
                                         uuid,
                                         &mut daemon_connections,
                                         reply_sender,
@@ -412,7 +577,12 @@ async fn start_inner(
                                 }
                             }
                         }
-                        ControlRequest::Logs { uuid, name, node } => {
+                        ControlRequest::Logs {
+                            uuid,
+                            name,
+                            node,
+                            offset,
+                        } => {
                             let dataflow_uuid = if let Some(uuid) = uuid {
                                 uuid
                             } else if let Some(name) = name {
@@ -426,6 +596,7 @@ async fn start_inner(
                                 &archived_dataflows,
                                 dataflow_uuid,
                                 node.into(),
+                                offset,
                                 &mut daemon_connections,
                                 clock.new_timestamp(),
                             )
@@ -433,6 +604,100 @@ async fn start_inner(
                             .map(ControlRequestReply::Logs);
                             let _ = reply_sender.send(reply);
                         }
+                        ControlRequest::NodeStates { dataflow_uuid } => {
+                            let reply = match running_dataflows.get(&dataflow_uuid) {
+                                Some(dataflow) => Ok(ControlRequestReply::NodeStates(
+                                    dataflow.node_states.clone(),
+                                )),
+                                None => Err(eyre!(
+                                    "No running dataflow found with UUID `{dataflow_uuid}`"
+                                )),
+                            };
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Restarts { dataflow_uuid } => {
+                            let reply = match running_dataflows.get(&dataflow_uuid) {
+                                Some(dataflow) => {
+                                    Ok(ControlRequestReply::Restarts(dataflow.restarts.clone()))
+                                }
+                                None => Err(eyre!(
+                                    "No running dataflow found with UUID `{dataflow_uuid}`"
+                                )),
+                            };
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::DataflowStats { dataflow_uuid } => {
+                            let reply = retrieve_stats(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(ControlRequestReply::DataflowStats);
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::DeactivateNode {
+                            dataflow_uuid,
+                            node_id,
+                        } => {
+                            let reply = deactivate_node(
+                                &running_dataflows,
+                                dataflow_uuid,
+                                node_id.clone(),
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::NodeDeactivated {
+                                    uuid: dataflow_uuid,
+                                    node_id,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::SetParameter {
+                            dataflow_uuid,
+                            node_id,
+                            key,
+                            value,
+                        } => {
+                            let reply = set_parameter(
+                                &mut running_dataflows,
+                                dataflow_uuid,
+                                node_id.clone(),
+                                key.clone(),
+                                value,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await
+                            .map(|()| {
+                                ControlRequestReply::ParameterSet {
+                                    uuid: dataflow_uuid,
+                                    node_id,
+                                    key,
+                                }
+                            });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Parameters { dataflow_uuid } => {
+                            let reply = match running_dataflows.get(&dataflow_uuid) {
+                                Some(dataflow) => Ok(ControlRequestReply::Parameters(
+                                    dataflow
+                                        .nodes
+                                        .iter()
+                                        .map(|node| (node.id.clone(), node.parameters()))
+                                        .filter(|(_, parameters)| !parameters.is_empty())
+                                        .collect(),
+                                )),
+                                None => Err(eyre!(
+                                    "No running dataflow found with UUID `{dataflow_uuid}`"
+                                )),
+                            };
+                            let _ = reply_sender.send(reply);
+                        }
                         ControlRequest::Destroy => {
                             tracing::info!("Received destroy command");
 
@@ -477,6 +742,7 @@ async fn start_inner(
                 }
                 ControlEvent::Error(err) => tracing::error!("{err:?}"),
             },
+            Event::Health(request) => request.reply(&running_dataflows, &daemon_connections),
             Event::DaemonHeartbeatInterval => {
                 let mut disconnected = BTreeSet::new();
                 for (machine_id, connection) in &mut daemon_connections {
@@ -533,6 +799,7 @@ async fn start_inner(
 async fn stop_dataflow_by_uuid(
     running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
     dataflow_results: &HashMap<Uuid, BTreeMap<String, Result<(), String>>>,
+    dataflow_reports: &HashMap<Uuid, RateReport>,
     dataflow_uuid: Uuid,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     reply_sender: tokio::sync::oneshot::Sender<Result<ControlRequestReply, eyre::ErrReport>>,
@@ -543,12 +810,17 @@ async fn stop_dataflow_by_uuid(
             let reply = ControlRequestReply::DataflowStopped {
                 uuid: dataflow_uuid,
                 result: dataflow_result(result, dataflow_uuid),
+                report: dataflow_reports
+                    .get(&dataflow_uuid)
+                    .cloned()
+                    .unwrap_or_default(),
             };
             let _ = reply_sender.send(Ok(reply));
             return Ok(());
         }
         bail!("no known dataflow found with UUID `{dataflow_uuid}`")
     };
+    dataflow.stop_requested = true;
     let stop = async {
         stop_dataflow(dataflow, dataflow_uuid, daemon_connections, timestamp).await?;
         Result::<_, eyre::Report>::Ok(())
@@ -631,6 +903,19 @@ struct RunningDataflow {
     pending_machines: BTreeSet<String>,
     init_success: bool,
     nodes: Vec<ResolvedNode>,
+    /// The last reported lifecycle state of each node.
+    node_states: BTreeMap<NodeId, NodeState>,
+    descriptor: Descriptor,
+    working_dir: PathBuf,
+    /// Whether the dataflow was stopped by the user.
+    stop_requested: bool,
+    /// Delay before the dataflow is started again, set when a node with a
+    /// `dataflow` restart scope failed.
+    restart_delay: Option<Duration>,
+    /// Restarts of failed nodes, including restarts of previous runs.
+    restarts: Vec<NodeRestart>,
+    /// Number of restarts of each node, used to enforce the restart budget.
+    restart_counts: BTreeMap<NodeId, u32>,
 
     reply_senders: Vec<tokio::sync::oneshot::Sender<eyre::Result<ControlRequestReply>>>,
 }
@@ -740,11 +1025,162 @@ async fn reload_dataflow(
     Ok(())
 }
 
+async fn deactivate_node(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let machine_id = dataflow
+        .nodes
+        .iter()
+        .find(|node| node.id == node_id)
+        .map(|node| node.deploy.machine.clone())
+        .wrap_err_with(|| format!("dataflow `{dataflow_id}` has no node `{node_id}`"))?;
+
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::StopNode {
+            dataflow_id,
+            node_id: node_id.clone(),
+        },
+        timestamp,
+    })?;
+
+    let daemon_connection = daemon_connections
+        .get_mut(machine_id.as_str())
+        .wrap_err("no daemon connection")?;
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send stop node message to daemon")?;
+
+    // wait for reply
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive stop node reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize stop node reply from daemon")?
+    {
+        DaemonCoordinatorReply::StopNodeResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to deactivate node `{node_id}`"))?,
+        other => bail!("unexpected reply after sending stop node: {other:?}"),
+    }
+    tracing::info!("successfully deactivated node `{dataflow_id}/{node_id}`");
+
+    Ok(())
+}
+
+async fn set_parameter(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    key: String,
+    value: MetadataValue,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let node = dataflow
+        .nodes
+        .iter_mut()
+        .find(|node| node.id == node_id)
+        .wrap_err_with(|| format!("dataflow `{dataflow_id}` has no node `{node_id}`"))?;
+    let value = node
+        .check_parameter(&key, &value)
+        .wrap_err_with(|| format!("cannot set parameter `{key}` of node `{node_id}`"))?;
+
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::SetParameter {
+            dataflow_id,
+            node_id: node_id.clone(),
+            key: key.clone(),
+            value: value.clone(),
+        },
+        timestamp,
+    })?;
+
+    let daemon_connection = daemon_connections
+        .get_mut(node.deploy.machine.as_str())
+        .wrap_err("no daemon connection")?;
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send set parameter message to daemon")?;
+
+    // wait for reply
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive set parameter reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize set parameter reply from daemon")?
+    {
+        DaemonCoordinatorReply::SetParameterResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to set parameter `{key}` of node `{node_id}`"))?,
+        other => bail!("unexpected reply after sending set parameter: {other:?}"),
+    }
+
+    // keep the value when the node or the whole dataflow is restarted
+    node.set_parameter(&key, &value);
+    dataflow.descriptor.set_parameter(&node_id, &key, &value);
+    tracing::info!("set parameter `{key}` of node `{dataflow_id}/{node_id}` to {value:?}");
+
+    Ok(())
+}
+
+async fn retrieve_stats(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<DataflowStats> {
+    let Some(dataflow) = running_dataflows.get(&dataflow_id) else {
+        bail!("No running dataflow found with UUID `{dataflow_id}`")
+    };
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::Stats { dataflow_id },
+        timestamp,
+    })?;
+
+    let mut stats = DataflowStats::default();
+    for machine_id in &dataflow.machines {
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id.as_str())
+            .wrap_err("no daemon connection")?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send stats message to daemon")?;
+
+        // wait for reply
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive stats reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize stats reply from daemon")?
+        {
+            DaemonCoordinatorReply::Stats(result) => stats.merge(
+                result
+                    .map_err(|e| eyre!(e))
+                    .wrap_err_with(|| format!("failed to get stats of machine `{machine_id}`"))?,
+            ),
+            other => bail!("unexpected reply after sending stats: {other:?}"),
+        }
+    }
+
+    Ok(stats)
+}
+
 async fn retrieve_logs(
     running_dataflows: &HashMap<Uuid, RunningDataflow>,
     archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
     dataflow_id: Uuid,
     node_id: NodeId,
+    offset: u64,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     timestamp: uhlc::Timestamp,
 ) -> eyre::Result<Vec<u8>> {
@@ -760,6 +1196,7 @@ async fn retrieve_logs(
         inner: DaemonCoordinatorEvent::Logs {
             dataflow_id,
             node_id: node_id.clone(),
+            offset,
         },
         timestamp,
     })?;
@@ -815,7 +1252,13 @@ async fn start_dataflow(
         uuid,
         machines,
         nodes,
-    } = spawn_dataflow(dataflow, working_dir, daemon_connections, clock).await?;
+    } = spawn_dataflow(
+        dataflow.clone(),
+        working_dir.clone(),
+        daemon_connections,
+        clock,
+    )
+    .await?;
     Ok(RunningDataflow {
         uuid,
         name,
@@ -826,7 +1269,17 @@ async fn start_dataflow(
         },
         init_success: true,
         machines,
+        node_states: nodes
+            .iter()
+            .map(|node| (node.id.clone(), NodeState::Configuring))
+            .collect(),
         nodes,
+        descriptor: dataflow,
+        working_dir,
+        stop_requested: false,
+        restart_delay: None,
+        restarts: Vec::new(),
+        restart_counts: BTreeMap::new(),
         reply_senders: Vec::new(),
     })
 }
@@ -873,6 +1326,8 @@ pub enum Event {
     Control(ControlEvent),
     Daemon(DaemonEvent),
     DaemonHeartbeatInterval,
+    Restart(RestartEvent),
+    Health(health::HealthRequest),
     CtrlC,
 }
 
@@ -881,7 +1336,7 @@ impl Event {
     #[allow(clippy::match_like_matches_macro)]
     pub fn log(&self) -> bool {
         match self {
-            Event::DaemonHeartbeatInterval => false,
+            Event::DaemonHeartbeatInterval | Event::Health(_) => false,
             _ => true,
         }
     }
@@ -892,11 +1347,16 @@ pub enum DataflowEvent {
     DataflowFinishedOnMachine {
         machine_id: String,
         result: eyre::Result<()>,
+        report: RateReport,
     },
     ReadyOnMachine {
         machine_id: String,
         success: bool,
     },
+    NodeStateChanged {
+        node_id: NodeId,
+        state: NodeState,
+    },
 }
 
 #[derive(Debug)]
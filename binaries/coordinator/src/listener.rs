@@ -83,18 +83,33 @@ pub async fn handle_connection(
                 coordinator_messages::DaemonEvent::AllNodesFinished {
                     dataflow_id,
                     result,
+                    report,
                 } => {
                     let event = Event::Dataflow {
                         uuid: dataflow_id,
                         event: DataflowEvent::DataflowFinishedOnMachine {
                             machine_id,
                             result: result.map_err(|e| eyre!(e)),
+                            report,
                         },
                     };
                     if events_tx.send(event).await.is_err() {
                         break;
                     }
                 }
+                coordinator_messages::DaemonEvent::NodeStateChanged {
+                    dataflow_id,
+                    node_id,
+                    state,
+                } => {
+                    let event = Event::Dataflow {
+                        uuid: dataflow_id,
+                        event: DataflowEvent::NodeStateChanged { node_id, state },
+                    };
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
                 coordinator_messages::DaemonEvent::Heartbeat => {
                     let event = Event::DaemonHeartbeat { machine_id };
                     if events_tx.send(event).await.is_err() {
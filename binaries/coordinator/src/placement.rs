@@ -0,0 +1,175 @@
+//! External placement of nodes on machines.
+//!
+//! The coordinator can delegate the decision which machine runs which node to a
+//! placement plugin, e.g. to implement GPU bin-packing or power budgets. The
+//! plugin receives a [`PlacementRequest`] with the dataflow descriptor and the
+//! connected machines and answers with a [`PlacementReply`]. Nodes that are not
+//! part of the reply keep the machine of the dataflow descriptor.
+//!
+//! Plugins are either webhooks, which receive the request as JSON `POST`
+//! request, or shared libraries that export the following functions:
+//!
+//! ```c
+//! // Returns the JSON-encoded reply for the given JSON-encoded request.
+//! char *dora_place_nodes(const char *request);
+//! // Frees a reply returned by `dora_place_nodes`.
+//! void dora_free_placement(char *reply);
+//! ```
+
+use crate::{DaemonConnection, RunningDataflow};
+use dora_core::{config::NodeId, descriptor::Descriptor};
+use eyre::{bail, eyre, Context};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{c_char, CStr, CString},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlacementRequest {
+    pub dataflow: Descriptor,
+    pub machines: Vec<MachineInfo>,
+}
+
+/// A machine with a connected daemon.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MachineInfo {
+    pub id: String,
+    pub address: SocketAddr,
+    /// Number of nodes of other running dataflows that are placed on this machine.
+    pub running_nodes: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlacementReply {
+    /// The machine that each node should run on.
+    pub assignments: BTreeMap<NodeId, String>,
+}
+
+type PlaceNodes = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+type FreePlacement = unsafe extern "C" fn(*mut c_char);
+
+pub enum PlacementPlugin {
+    Webhook(String),
+    Library(Arc<libloading::Library>),
+}
+
+impl PlacementPlugin {
+    /// Creates a webhook plugin for `http(s)://` URLs and loads the shared
+    /// library at the given path otherwise.
+    pub fn new(source: &str) -> eyre::Result<Self> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(Self::Webhook(source.to_owned()));
+        }
+        let path = PathBuf::from(source);
+        let library = unsafe {
+            libloading::Library::new(&path).wrap_err_with(|| {
+                format!("failed to load placement plugin at `{}`", path.display())
+            })?
+        };
+        unsafe {
+            library
+                .get::<PlaceNodes>(b"dora_place_nodes")
+                .wrap_err("failed to get `dora_place_nodes`")?;
+            library
+                .get::<FreePlacement>(b"dora_free_placement")
+                .wrap_err("failed to get `dora_free_placement`")?;
+        }
+        Ok(Self::Library(Arc::new(library)))
+    }
+
+    /// Assigns the nodes of the given dataflow to the connected machines.
+    ///
+    /// Returns the dataflow with the `_unstable_deploy` machine of the nodes set
+    /// according to the plugin reply.
+    pub(crate) async fn place(
+        &self,
+        mut dataflow: Descriptor,
+        daemon_connections: &HashMap<String, DaemonConnection>,
+        running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    ) -> eyre::Result<Descriptor> {
+        let mut machines: Vec<_> = daemon_connections
+            .iter()
+            .map(|(id, connection)| MachineInfo {
+                id: id.clone(),
+                address: connection.listen_socket,
+                running_nodes: running_dataflows
+                    .values()
+                    .flat_map(|dataflow| &dataflow.nodes)
+                    .filter(|node| &node.deploy.machine == id)
+                    .count(),
+            })
+            .collect();
+        machines.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let request = PlacementRequest {
+            dataflow: dataflow.clone(),
+            machines,
+        };
+        let reply = match self {
+            PlacementPlugin::Webhook(url) => place_with_webhook(url, &request).await?,
+            PlacementPlugin::Library(library) => {
+                // plugins might do blocking work, so they don't run on the event loop
+                let library = library.clone();
+                tokio::task::spawn_blocking(move || place_with_library(&library, &request))
+                    .await
+                    .wrap_err("placement plugin panicked")??
+            }
+        };
+
+        for (node_id, machine) in reply.assignments {
+            if !daemon_connections.contains_key(&machine) {
+                bail!("placement plugin assigned node `{node_id}` to unknown machine `{machine}`");
+            }
+            let node = dataflow
+                .nodes
+                .iter_mut()
+                .find(|node| node.id == node_id)
+                .ok_or_else(|| eyre!("placement plugin returned unknown node `{node_id}`"))?;
+            tracing::debug!("placing node `{node_id}` on machine `{machine}`");
+            node.deploy.machine = Some(machine);
+        }
+        Ok(dataflow)
+    }
+}
+
+async fn place_with_webhook(url: &str, request: &PlacementRequest) -> eyre::Result<PlacementReply> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .wrap_err("failed to create HTTP client")?;
+    client
+        .post(url)
+        .json(request)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .wrap_err_with(|| format!("placement request to `{url}` failed"))?
+        .json()
+        .await
+        .wrap_err("failed to parse placement reply")
+}
+
+fn place_with_library(
+    library: &libloading::Library,
+    request: &PlacementRequest,
+) -> eyre::Result<PlacementReply> {
+    let request = CString::new(serde_json::to_vec(request)?)
+        .wrap_err("placement request contains a nul byte")?;
+    let reply = unsafe {
+        let place_nodes = library.get::<PlaceNodes>(b"dora_place_nodes")?;
+        let free_placement = library.get::<FreePlacement>(b"dora_free_placement")?;
+        let reply_ptr = place_nodes(request.as_ptr());
+        if reply_ptr.is_null() {
+            bail!("placement plugin returned no reply");
+        }
+        let reply = CStr::from_ptr(reply_ptr).to_bytes().to_owned();
+        free_placement(reply_ptr);
+        reply
+    };
+    serde_json::from_slice(&reply).wrap_err("failed to parse placement reply")
+}
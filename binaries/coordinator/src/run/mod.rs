@@ -46,6 +46,8 @@ pub(super) async fn spawn_dataflow(
         nodes: nodes.clone(),
         machine_listen_ports,
         dataflow_descriptor: dataflow,
+        // v4 UUIDs are generated from a cryptographically secure random source
+        token: Uuid::new_v4().simple().to_string(),
     };
     let message = serde_json::to_vec(&Timestamped {
         inner: DaemonCoordinatorEvent::Spawn(spawn_command),
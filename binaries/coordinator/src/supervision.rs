@@ -0,0 +1,225 @@
+//! Restarts of failed nodes according to their `restart` policy.
+//!
+//! Daemons keep failed nodes that have a restart policy around instead of
+//! finishing them. The coordinator then either restarts the node after the
+//! backoff delay, restarts the whole dataflow, or releases the node when the
+//! restart budget is used up.
+
+use crate::{
+    stop_dataflow,
+    tcp_utils::{tcp_receive, tcp_send},
+    DaemonConnection, Event, RunningDataflow,
+};
+use dora_core::{
+    config::{NodeId, RestartScope},
+    daemon_messages::{DaemonCoordinatorEvent, DaemonCoordinatorReply, Timestamped},
+    descriptor::{CoreNodeKind, Descriptor},
+    message::uhlc,
+    topics::{ControlRequestReply, NodeRestart},
+};
+use eyre::{bail, eyre, ContextCompat, WrapErr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum RestartEvent {
+    Node {
+        uuid: Uuid,
+        node_id: NodeId,
+        attempt: u32,
+    },
+    Dataflow(DataflowRestart),
+}
+
+/// A dataflow that is started again after it was stopped because of a failed node.
+#[derive(Debug)]
+pub struct DataflowRestart {
+    pub previous_uuid: Uuid,
+    pub name: Option<String>,
+    pub descriptor: Descriptor,
+    pub working_dir: PathBuf,
+    pub restarts: Vec<NodeRestart>,
+    pub restart_counts: BTreeMap<NodeId, u32>,
+    pub reply_senders: Vec<tokio::sync::oneshot::Sender<eyre::Result<ControlRequestReply>>>,
+}
+
+impl DataflowRestart {
+    pub(crate) fn new(dataflow: RunningDataflow) -> Self {
+        Self {
+            previous_uuid: dataflow.uuid,
+            name: dataflow.name,
+            descriptor: dataflow.descriptor,
+            working_dir: dataflow.working_dir,
+            restarts: dataflow.restarts,
+            restart_counts: dataflow.restart_counts,
+            reply_senders: dataflow.reply_senders,
+        }
+    }
+}
+
+/// Decides how to handle a node that reported an error.
+pub async fn handle_node_failure(
+    dataflow: &mut RunningDataflow,
+    node_id: NodeId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    restart_tx: &mpsc::Sender<Event>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let uuid = dataflow.uuid;
+    let Some(node) = dataflow.nodes.iter().find(|node| node.id == node_id) else {
+        return Ok(());
+    };
+    let Some(policy) = node.restart.clone() else {
+        return Ok(());
+    };
+    if matches!(&node.kind, CoreNodeKind::Custom(custom) if custom.shared) {
+        return Ok(());
+    }
+    if dataflow.stop_requested || dataflow.restart_delay.is_some() {
+        // the daemons release failed nodes when the dataflow is stopped
+        return Ok(());
+    }
+
+    let restarts = dataflow.restart_counts.entry(node_id.clone()).or_default();
+    if *restarts >= policy.max_restarts {
+        tracing::warn!(
+            "node `{uuid}/{node_id}` failed, but its restart budget of {} restarts is used up",
+            policy.max_restarts
+        );
+        return release_node(dataflow, node_id, daemon_connections, timestamp).await;
+    }
+    let delay = policy.delay(*restarts);
+    *restarts += 1;
+    let attempt = *restarts;
+
+    match policy.scope {
+        RestartScope::Node => {
+            tracing::info!(
+                "restarting node `{uuid}/{node_id}` in {delay:?} (attempt {attempt}/{})",
+                policy.max_restarts
+            );
+            schedule(
+                delay,
+                RestartEvent::Node {
+                    uuid,
+                    node_id,
+                    attempt,
+                },
+                restart_tx,
+            );
+        }
+        RestartScope::Dataflow => {
+            tracing::info!(
+                "restarting dataflow `{uuid}` in {delay:?} because node `{node_id}` failed \
+                (attempt {attempt}/{})",
+                policy.max_restarts
+            );
+            dataflow.restart_delay = Some(delay);
+            dataflow.restarts.push(NodeRestart {
+                node_id,
+                dataflow_uuid: uuid,
+                time: SystemTime::now(),
+                attempt,
+                scope: RestartScope::Dataflow,
+                error: None,
+            });
+            stop_dataflow(dataflow, uuid, daemon_connections, timestamp).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends the given restart event after the delay.
+pub fn schedule(delay: Duration, event: RestartEvent, restart_tx: &mpsc::Sender<Event>) {
+    let restart_tx = restart_tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let _ = restart_tx.send(Event::Restart(event)).await;
+    });
+}
+
+/// Instructs the daemon of the given failed node to spawn it again.
+pub async fn restart_node(
+    dataflow: &RunningDataflow,
+    node_id: NodeId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let dataflow_id = dataflow.uuid;
+    let event = DaemonCoordinatorEvent::RestartNode {
+        dataflow_id,
+        node_id: node_id.clone(),
+    };
+    let reply = send_node_event(dataflow, &node_id, event, daemon_connections, timestamp)
+        .await
+        .wrap_err("failed to send restart node message")?;
+    match reply {
+        DaemonCoordinatorReply::RestartNodeResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to restart node `{node_id}`"))?,
+        other => bail!("unexpected reply after sending restart node: {other:?}"),
+    }
+    tracing::info!("successfully restarted node `{dataflow_id}/{node_id}`");
+
+    Ok(())
+}
+
+/// Instructs the daemon of the given failed node to finish it without restart.
+async fn release_node(
+    dataflow: &RunningDataflow,
+    node_id: NodeId,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let event = DaemonCoordinatorEvent::ReleaseNode {
+        dataflow_id: dataflow.uuid,
+        node_id: node_id.clone(),
+    };
+    let reply = send_node_event(dataflow, &node_id, event, daemon_connections, timestamp)
+        .await
+        .wrap_err("failed to send release node message")?;
+    match reply {
+        DaemonCoordinatorReply::ReleaseNodeResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to release node `{node_id}`")),
+        other => bail!("unexpected reply after sending release node: {other:?}"),
+    }
+}
+
+/// Sends the event to the daemon that runs the given node and waits for the reply.
+async fn send_node_event(
+    dataflow: &RunningDataflow,
+    node_id: &NodeId,
+    event: DaemonCoordinatorEvent,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<DaemonCoordinatorReply> {
+    let machine_id = dataflow
+        .nodes
+        .iter()
+        .find(|node| &node.id == node_id)
+        .map(|node| node.deploy.machine.clone())
+        .wrap_err_with(|| format!("dataflow `{}` has no node `{node_id}`", dataflow.uuid))?;
+    let message = serde_json::to_vec(&Timestamped {
+        inner: event,
+        timestamp,
+    })?;
+
+    let daemon_connection = daemon_connections
+        .get_mut(machine_id.as_str())
+        .wrap_err("no daemon connection")?;
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send message to daemon")?;
+
+    // wait for reply
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive reply from daemon")?;
+    serde_json::from_slice(&reply_raw).wrap_err("failed to deserialize reply from daemon")
+}
@@ -0,0 +1,92 @@
+//! Compression of the messages that are sent to other machines, configured
+//! through the `compression` option of inputs.
+//!
+//! The algorithm is sent along with each message, so receivers decompress
+//! them without knowing the configuration of the link.
+
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::config::{Compression, CompressionAlgorithm};
+use eyre::{bail, Context};
+use std::io::Read;
+
+/// Maximum size of a decompressed message, to reject corrupt or malicious
+/// messages that would exhaust the memory of the daemon.
+const MAX_DECOMPRESSED_SIZE: usize = 1 << 30;
+
+pub fn compress(compression: Compression, data: &[u8]) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    let compressed = match compression.algorithm {
+        CompressionAlgorithm::Zstd => {
+            let level = compression.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+            zstd::bulk::compress(data, level).wrap_err("zstd compression failed")?
+        }
+        CompressionAlgorithm::Lz4 => lz4_flex::compress_prepend_size(data),
+    };
+    Ok(AVec::from_slice(128, &compressed))
+}
+
+pub fn decompress(
+    algorithm: CompressionAlgorithm,
+    data: &[u8],
+) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    decompress_with_limit(algorithm, data, MAX_DECOMPRESSED_SIZE)
+}
+
+fn decompress_with_limit(
+    algorithm: CompressionAlgorithm,
+    data: &[u8],
+    max_size: usize,
+) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    let decompressed = match algorithm {
+        CompressionAlgorithm::Zstd => {
+            let mut decompressed = Vec::new();
+            zstd::stream::read::Decoder::new(data)
+                .wrap_err("failed to create zstd decoder")?
+                .take(max_size as u64 + 1)
+                .read_to_end(&mut decompressed)
+                .wrap_err("zstd decompression failed")?;
+            if decompressed.len() > max_size {
+                bail!("decompressed message exceeds the maximum size of {max_size} bytes");
+            }
+            decompressed
+        }
+        CompressionAlgorithm::Lz4 => {
+            // the size is prepended as little-endian `u32`
+            if data.len() < 4 {
+                bail!("lz4 message is missing its size");
+            }
+            let (size, compressed) = data.split_at(4);
+            let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+            if size > max_size {
+                bail!("decompressed message exceeds the maximum size of {max_size} bytes");
+            }
+            lz4_flex::decompress(compressed, size).wrap_err("lz4 decompression failed")?
+        }
+    };
+    Ok(AVec::from_slice(128, &decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+        for algorithm in [CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4] {
+            let compressed = compress(
+                Compression {
+                    algorithm,
+                    level: None,
+                },
+                &data,
+            )
+            .unwrap();
+            assert!(compressed.len() < data.len());
+            let decompressed = decompress(algorithm, &compressed).unwrap();
+            assert_eq!(&decompressed[..], &data[..]);
+
+            let err = decompress_with_limit(algorithm, &compressed, data.len() - 1).unwrap_err();
+            assert!(err.to_string().contains("maximum size"), "{err}");
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::tcp_utils::{tcp_receive, tcp_send};
+use crate::{
+    tcp_utils::{tcp_receive, tcp_send},
+    tls::{InterDaemonTls, Stream},
+};
 use dora_core::daemon_messages::{InterDaemonEvent, Timestamped};
 use eyre::{Context, ContextCompat};
 use std::{
@@ -6,23 +9,30 @@ use std::{
     io::ErrorKind,
     net::{Ipv4Addr, SocketAddr},
 };
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    io::AsyncRead,
+    net::{TcpListener, TcpStream},
+};
 
 pub struct InterDaemonConnection {
+    machine_id: String,
     socket: SocketAddr,
-    connection: Option<TcpStream>,
+    tls: Option<InterDaemonTls>,
+    connection: Option<Box<dyn Stream>>,
 }
 
 impl InterDaemonConnection {
-    pub fn new(socket: SocketAddr) -> Self {
+    pub fn new(machine_id: String, socket: SocketAddr, tls: Option<InterDaemonTls>) -> Self {
         Self {
+            machine_id,
             socket,
+            tls,
             connection: None,
         }
     }
 
     #[tracing::instrument(skip(self), fields(%self.socket))]
-    async fn connect(&mut self) -> eyre::Result<&mut TcpStream> {
+    async fn connect(&mut self) -> eyre::Result<&mut Box<dyn Stream>> {
         match &mut self.connection {
             Some(c) => Ok(c),
             entry @ None => {
@@ -32,6 +42,10 @@ impl InterDaemonConnection {
                 connection
                     .set_nodelay(true)
                     .wrap_err("failed to set nodelay")?;
+                let connection = match &self.tls {
+                    Some(tls) => tls.connect(connection, &self.machine_id).await?,
+                    None => Box::new(connection),
+                };
                 Ok(entry.insert(connection))
             }
         }
@@ -64,10 +78,7 @@ pub async fn send_inter_daemon_event(
     Ok(())
 }
 
-pub async fn spawn_listener_loop(
-    machine_id: String,
-    events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
-) -> eyre::Result<SocketAddr> {
+pub async fn create_listener() -> eyre::Result<(TcpListener, SocketAddr)> {
     let localhost = Ipv4Addr::new(127, 0, 0, 1);
     let socket = match TcpListener::bind((localhost, 0)).await {
         Ok(socket) => socket,
@@ -78,17 +89,27 @@ pub async fn spawn_listener_loop(
     let socket_addr = socket
         .local_addr()
         .wrap_err("failed to get local addr of socket")?;
+    Ok((socket, socket_addr))
+}
 
+/// Accepts connections of other daemons on the given listener.
+///
+/// If `tls` is set, only TLS connections of authenticated daemons are accepted.
+pub fn spawn_listener_loop(
+    listener: TcpListener,
+    machine_id: String,
+    tls: Option<InterDaemonTls>,
+    events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
+) {
     tokio::spawn(async move {
-        listener_loop(socket, events_tx).await;
+        listener_loop(listener, tls, events_tx).await;
         tracing::debug!("inter-daemon listener loop finished for machine `{machine_id}`");
     });
-
-    Ok(socket_addr)
 }
 
 async fn listener_loop(
     listener: TcpListener,
+    tls: Option<InterDaemonTls>,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) {
     loop {
@@ -100,21 +121,37 @@ async fn listener_loop(
             Err(err) => {
                 tracing::info!("{err}");
             }
-            Ok((connection, _)) => {
-                tokio::spawn(handle_connection_loop(connection, events_tx.clone()));
+            Ok((connection, peer)) => {
+                let tls = tls.clone();
+                let events_tx = events_tx.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = connection.set_nodelay(true) {
+                        tracing::warn!("failed to set nodelay for connection: {err}");
+                    }
+                    let connection: Box<dyn Stream> = match &tls {
+                        Some(tls) => match tls.accept(connection).await {
+                            Ok(connection) => connection,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "{:?}",
+                                    err.wrap_err(format!("rejected connection from {peer}"))
+                                );
+                                return;
+                            }
+                        },
+                        None => Box::new(connection),
+                    };
+                    handle_connection_loop(connection, events_tx).await
+                });
             }
         }
     }
 }
 
 async fn handle_connection_loop(
-    mut connection: TcpStream,
+    mut connection: Box<dyn Stream>,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) {
-    if let Err(err) = connection.set_nodelay(true) {
-        tracing::warn!("failed to set nodelay for connection: {err}");
-    }
-
     loop {
         match receive_message(&mut connection).await {
             Ok(Some(message)) => {
@@ -132,7 +169,7 @@ async fn handle_connection_loop(
 }
 
 async fn receive_message(
-    connection: &mut TcpStream,
+    connection: &mut (impl AsyncRead + Unpin),
 ) -> eyre::Result<Option<Timestamped<InterDaemonEvent>>> {
     let raw = match tcp_receive(connection).await {
         Ok(raw) => raw,
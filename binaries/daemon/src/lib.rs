@@ -4,36 +4,45 @@ use dora_core::config::{Input, OperatorId};
 use dora_core::coordinator_messages::CoordinatorRequest;
 use dora_core::daemon_messages::{DataMessage, InterDaemonEvent, Timestamped};
 use dora_core::message::uhlc::{self, HLC};
-use dora_core::message::{ArrowTypeInfo, Metadata, MetadataParameters};
+use dora_core::message::{ArrowTypeInfo, DataFormat, Metadata, MetadataParameters, MetadataValue};
 use dora_core::{
-    config::{DataId, InputMapping, NodeId},
-    coordinator_messages::DaemonEvent,
+    compaction::CompactionKey,
+    condition::Condition,
+    config::{Compression, DataId, InputMapping, NodeId, VideoCodec},
+    coordinator_messages::{DaemonEvent, NodeState},
     daemon_messages::{
-        self, DaemonCoordinatorEvent, DaemonCoordinatorReply, DaemonReply, DataflowId, DropToken,
-        SpawnDataflowNodes,
+        self, DaemonCoordinatorEvent, DaemonCoordinatorReply, DaemonReply, DataflowId,
+        DataflowToken, DropToken, SpawnDataflowNodes,
     },
     descriptor::{CoreNodeKind, Descriptor, ResolvedNode},
+    process_stats::{operator_thread_name, ProcessStats, ThreadStats},
+    topics::{DataflowStats, LinkStats, NodeStats, RateReport},
+    transport::{tokens_match, TransportConfig},
 };
 
 use eyre::{bail, eyre, Context, ContextCompat};
 use futures::{future, stream, FutureExt, TryFutureExt};
 use futures_concurrency::stream::Merge;
 use inter_daemon::InterDaemonConnection;
-use pending::PendingNodes;
+use pending::{HeldOutput, PendingNodes};
+use report::MessageRate;
 use shared_memory_server::ShmemConf;
+use sim_clock::{Advance, SimClock};
 use std::sync::Arc;
 use std::time::Instant;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     io,
     net::SocketAddr,
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, UNIX_EPOCH},
 };
 use tcp_utils::tcp_send;
+use throttle::InputThrottle;
+use tls::InterDaemonTls;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
@@ -41,14 +50,24 @@ use tokio::sync::{mpsc, oneshot};
 use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
 use tracing::error;
 use uuid::{NoContext, Timestamp, Uuid};
+use video::{VideoDecoder, VideoEncoder, VideoEvent};
 
+mod compression;
 mod coordinator;
 mod inter_daemon;
+mod limits;
 mod log;
 mod node_communication;
 mod pending;
+mod report;
+mod secrets;
+mod sim_clock;
 mod spawn;
 mod tcp_utils;
+mod throttle;
+mod tls;
+mod video;
+mod web_stream;
 
 #[cfg(feature = "telemetry")]
 use dora_tracing::telemetry::serialize_context;
@@ -66,6 +85,8 @@ pub struct Daemon {
     coordinator_connection: Option<TcpStream>,
     last_coordinator_heartbeat: Instant,
     inter_daemon_connections: BTreeMap<String, InterDaemonConnection>,
+    /// TLS settings for connections to other daemons, from the local config.
+    inter_daemon_tls: Option<InterDaemonTls>,
     machine_id: String,
 
     /// used for testing and examples
@@ -73,23 +94,34 @@ pub struct Daemon {
     /// used to record dataflow results when `exit_when_done` is used
     dataflow_errors: BTreeMap<Uuid, BTreeMap<NodeId, eyre::Report>>,
 
+    /// Running instances of nodes that are marked as `shared`.
+    shared_nodes: BTreeMap<SharedNodeKey, SharedNode>,
+
     clock: Arc<uhlc::HLC>,
 }
 
 impl Daemon {
-    pub async fn run(coordinator_addr: SocketAddr, machine_id: String) -> eyre::Result<()> {
+    /// Runs a daemon that connects to the given coordinator.
+    ///
+    /// The `transport` settings are local to this daemon, the coordinator can't
+    /// change them.
+    pub async fn run(
+        coordinator_addr: SocketAddr,
+        machine_id: String,
+        transport: TransportConfig,
+    ) -> eyre::Result<()> {
         let clock = Arc::new(HLC::default());
 
         let ctrlc_events = set_up_ctrlc_handler(clock.clone())?;
 
-        // spawn listen loop
-        let (events_tx, events_rx) = flume::bounded(10);
-        let listen_socket =
-            inter_daemon::spawn_listener_loop(machine_id.clone(), events_tx).await?;
-        let daemon_events = events_rx.into_stream().map(|e| Timestamped {
-            inner: Event::Daemon(e.inner),
-            timestamp: e.timestamp,
-        });
+        let inter_daemon_tls = transport
+            .tls
+            .as_ref()
+            .map(|config| InterDaemonTls::load(config, &machine_id))
+            .transpose()
+            .wrap_err("failed to load TLS certificates for inter-daemon connections")?;
+
+        let (listener, listen_socket) = inter_daemon::create_listener().await?;
 
         // connect to the coordinator
         let coordinator_events =
@@ -106,10 +138,24 @@ impl Daemon {
                     },
                 );
 
+        // spawn listen loop
+        let (events_tx, events_rx) = flume::bounded(10);
+        inter_daemon::spawn_listener_loop(
+            listener,
+            machine_id.clone(),
+            inter_daemon_tls.clone(),
+            events_tx,
+        );
+        let daemon_events = events_rx.into_stream().map(|e| Timestamped {
+            inner: Event::Daemon(e.inner),
+            timestamp: e.timestamp,
+        });
+
         Self::run_general(
             (coordinator_events, ctrlc_events, daemon_events).merge(),
             Some(coordinator_addr),
             machine_id,
+            inter_daemon_tls,
             None,
             clock,
         )
@@ -135,6 +181,8 @@ impl Daemon {
             nodes,
             machine_listen_ports: BTreeMap::new(),
             dataflow_descriptor: descriptor,
+            // there are no other daemons
+            token: String::new(),
         };
 
         let clock = Arc::new(HLC::default());
@@ -159,6 +207,7 @@ impl Daemon {
             Box::pin(coordinator_events),
             None,
             "".to_string(),
+            None,
             Some(exit_when_done),
             clock,
         );
@@ -194,6 +243,7 @@ impl Daemon {
         external_events: impl Stream<Item = Timestamped<Event>> + Unpin,
         coordinator_addr: Option<SocketAddr>,
         machine_id: String,
+        inter_daemon_tls: Option<InterDaemonTls>,
         exit_when_done: Option<BTreeSet<(Uuid, NodeId)>>,
         clock: Arc<HLC>,
     ) -> eyre::Result<BTreeMap<Uuid, BTreeMap<NodeId, eyre::Report>>> {
@@ -211,6 +261,16 @@ impl Daemon {
         };
 
         let (dora_events_tx, dora_events_rx) = mpsc::channel(5);
+        if let Err(err) =
+            node_communication::spawn_observer_listener(dora_events_tx.clone(), clock.clone()).await
+        {
+            tracing::warn!("observer connections are not available: {err:?}");
+        }
+        if let Err(err) =
+            web_stream::spawn_stream_listener(dora_events_tx.clone(), clock.clone()).await
+        {
+            tracing::warn!("output streaming is not available: {err:?}");
+        }
         let daemon = Self {
             running: HashMap::new(),
             working_dir: HashMap::new(),
@@ -218,9 +278,11 @@ impl Daemon {
             coordinator_connection,
             last_coordinator_heartbeat: Instant::now(),
             inter_daemon_connections: BTreeMap::new(),
+            inter_daemon_tls,
             machine_id,
             exit_when_done,
             dataflow_errors: BTreeMap::new(),
+            shared_nodes: BTreeMap::new(),
             clock,
         };
 
@@ -271,6 +333,25 @@ impl Daemon {
                     RunStatus::Continue => {}
                     RunStatus::Exit => break,
                 },
+                Event::Observer {
+                    dataflow_id,
+                    outputs,
+                    event_sender,
+                    reply_sender,
+                } => {
+                    let result = match self.running.get_mut(&dataflow_id) {
+                        Some(dataflow) => {
+                            dataflow.observers.push(Observer {
+                                outputs,
+                                channel: event_sender,
+                            });
+                            Ok(())
+                        }
+                        None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                    };
+                    let _ = reply_sender.send(DaemonReply::Result(result));
+                }
+                Event::Video(event) => self.handle_video_event(event).await,
                 Event::HeartbeatInterval => {
                     if let Some(connection) = &mut self.coordinator_connection {
                         let msg = serde_json::to_vec(&Timestamped {
@@ -288,10 +369,22 @@ impl Daemon {
                             bail!("lost connection to coordinator")
                         }
                     }
+                    self.expire_service_calls().await;
                 }
                 Event::CtrlC => {
                     for dataflow in self.running.values_mut() {
-                        dataflow.stop_all(&self.clock).await;
+                        let stopped = dataflow.stop_all(&self.clock, &BTreeSet::new()).await;
+                        for node_id in stopped {
+                            report_node_state(
+                                &mut self.coordinator_connection,
+                                &self.machine_id,
+                                dataflow.id,
+                                node_id,
+                                NodeState::Deactivating,
+                                &self.clock,
+                            )
+                            .await;
+                        }
                     }
                 }
             }
@@ -312,25 +405,37 @@ impl Daemon {
                 nodes,
                 machine_listen_ports,
                 dataflow_descriptor,
+                token,
             }) => {
                 match dataflow_descriptor.communication.remote {
                     dora_core::config::RemoteCommunicationConfig::Tcp => {}
                 }
                 for (machine_id, socket) in machine_listen_ports {
+                    let connection = InterDaemonConnection::new(
+                        machine_id.clone(),
+                        socket,
+                        self.inter_daemon_tls.clone(),
+                    );
                     match self.inter_daemon_connections.entry(machine_id) {
                         std::collections::btree_map::Entry::Vacant(entry) => {
-                            entry.insert(InterDaemonConnection::new(socket));
+                            entry.insert(connection);
                         }
                         std::collections::btree_map::Entry::Occupied(mut entry) => {
                             if entry.get().socket() != socket {
-                                entry.insert(InterDaemonConnection::new(socket));
+                                entry.insert(connection);
                             }
                         }
                     }
                 }
 
+                // with TLS, the token is derived from a secret that the coordinator
+                // doesn't know, so it can't be forged by reading the coordinator connection
+                let token = match &self.inter_daemon_tls {
+                    Some(tls) => tls.dataflow_token(dataflow_id, &token),
+                    None => token,
+                };
                 let result = self
-                    .spawn_dataflow(dataflow_id, working_dir, nodes, dataflow_descriptor)
+                    .spawn_dataflow(dataflow_id, token, working_dir, nodes, dataflow_descriptor)
                     .await;
                 if let Err(err) = &result {
                     tracing::error!("{err:?}");
@@ -354,7 +459,7 @@ impl Daemon {
                             .await?;
                         if success {
                             tracing::info!("coordinator reported that all nodes are ready, starting dataflow `{dataflow_id}`");
-                            dataflow.start(&self.events_tx, &self.clock).await?;
+                            self.start_dataflow(dataflow_id).await?;
                         }
                     }
                     None => {
@@ -371,6 +476,7 @@ impl Daemon {
             DaemonCoordinatorEvent::Logs {
                 dataflow_id,
                 node_id,
+                offset,
             } => {
                 match self.working_dir.get(&dataflow_id) {
                     Some(working_dir) => {
@@ -385,6 +491,9 @@ impl Daemon {
                                             log::log_path(&working_dir, &dataflow_id, &node_id)
                                         ))?;
 
+                                file.seek(SeekFrom::Start(offset))
+                                    .await
+                                    .wrap_err("Could not seek in log file")?;
                                 let mut contents = vec![];
                                 file.read_to_end(&mut contents)
                                     .await
@@ -411,6 +520,16 @@ impl Daemon {
                 }
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::Stats { dataflow_id } => {
+                let result = match self.running.get(&dataflow_id) {
+                    Some(dataflow) => Ok(dataflow.stats(&self.machine_id)),
+                    None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                };
+                let _ = reply_tx
+                    .send(Some(DaemonCoordinatorReply::Stats(result)))
+                    .map_err(|_| error!("could not send stats reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::ReloadDataflow {
                 dataflow_id,
                 node_id,
@@ -424,13 +543,53 @@ impl Daemon {
                     .map_err(|_| error!("could not send reload reply from daemon to coordinator"));
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::SetParameter {
+                dataflow_id,
+                node_id,
+                key,
+                value,
+            } => {
+                let result = self.set_parameter(dataflow_id, node_id, key, value);
+                let reply = DaemonCoordinatorReply::SetParameterResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send set parameter reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::StopDataflow { dataflow_id } => {
                 let stop = async {
+                    // shared nodes are kept running as long as other dataflows use them
+                    let mut retained = BTreeSet::new();
+                    for shared in self.shared_nodes.values_mut() {
+                        if shared.owner == dataflow_id && !shared.attached.is_empty() {
+                            shared.stop_requested = true;
+                            retained.insert(shared.node_id.clone());
+                        }
+                    }
                     let dataflow = self
                         .running
                         .get_mut(&dataflow_id)
                         .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
-                    dataflow.stop_all(&self.clock).await;
+                    let stopped = dataflow.stop_all(&self.clock, &retained).await;
+                    let failed = std::mem::take(&mut dataflow.failed_nodes);
+                    for (node_id, err) in failed {
+                        self.release_failed_node(dataflow_id, node_id, err).await?;
+                    }
+                    for node_id in stopped {
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id,
+                            NodeState::Deactivating,
+                            &self.clock,
+                        )
+                        .await;
+                    }
+                    self.detach_shared_nodes(dataflow_id).await?;
+                    self.finish_dataflow_if_done(dataflow_id).await?;
                     Result::<(), eyre::Report>::Ok(())
                 };
                 let reply = DaemonCoordinatorReply::StopResult(
@@ -441,6 +600,54 @@ impl Daemon {
                     .map_err(|_| error!("could not send stop reply from daemon to coordinator"));
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::StopNode {
+                dataflow_id,
+                node_id,
+            } => {
+                let result = self.stop_node(dataflow_id, node_id).await;
+                let reply = DaemonCoordinatorReply::StopNodeResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send stop node reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::RestartNode {
+                dataflow_id,
+                node_id,
+            } => {
+                let result = self.restart_node(dataflow_id, node_id).await;
+                let reply = DaemonCoordinatorReply::RestartNodeResult(
+                    result.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send restart node reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::ReleaseNode {
+                dataflow_id,
+                node_id,
+            } => {
+                let release = async {
+                    let err = self
+                        .running
+                        .get_mut(&dataflow_id)
+                        .and_then(|dataflow| dataflow.failed_nodes.remove(&node_id))
+                        .wrap_err_with(|| {
+                            format!("node `{dataflow_id}/{node_id}` is not waiting for a restart")
+                        })?;
+                    self.release_failed_node(dataflow_id, node_id, err).await
+                };
+                let reply = DaemonCoordinatorReply::ReleaseNodeResult(
+                    release.await.map_err(|err| format!("{err:?}")),
+                );
+                let _ = reply_tx.send(Some(reply)).map_err(|_| {
+                    error!("could not send release node reply from daemon to coordinator")
+                });
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::Destroy => {
                 tracing::info!("received destroy command -> exiting");
                 let (notify_tx, notify_rx) = oneshot::channel();
@@ -470,25 +677,72 @@ impl Daemon {
         match event {
             InterDaemonEvent::Output {
                 dataflow_id,
+                token,
                 node_id,
                 output_id,
                 metadata,
                 data,
+                encoding,
+                compression,
+                throttled_inputs,
             } => {
                 let inner = async {
                     let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
                         format!("send out failed: no running dataflow with ID `{dataflow_id}`")
                     })?;
-                    send_output_to_local_receivers(
-                        node_id.clone(),
-                        output_id.clone(),
-                        dataflow,
-                        &metadata,
-                        data.map(DataMessage::Vec),
-                        &self.clock,
-                    )
-                    .await?;
-                    Result::<_, eyre::Report>::Ok(())
+                    if !tokens_match(&token, &dataflow.token) {
+                        bail!(
+                            "rejected output `{node_id}/{output_id}` with invalid dataflow token"
+                        );
+                    }
+                    let data = match (compression, data) {
+                        (Some(algorithm), Some(data)) => Some(
+                            // don't stall the event loop on large messages
+                            tokio::task::spawn_blocking(move || {
+                                compression::decompress(algorithm, &data)
+                            })
+                            .await
+                            .wrap_err("decompression task panicked")?
+                            .wrap_err_with(|| {
+                                format!(
+                                    "failed to decompress {algorithm} output `{node_id}/{output_id}`"
+                                )
+                            })?,
+                        ),
+                        (_, data) => data,
+                    };
+                    let output_id = OutputId(node_id, output_id);
+                    match (encoding, data) {
+                        (Some(codec), Some(packet)) => {
+                            // the decoder thread reports the image as `VideoEvent::Decoded`
+                            let decoder = match dataflow.video_decoders.entry(output_id.clone()) {
+                                std::collections::hash_map::Entry::Occupied(entry) => {
+                                    entry.into_mut()
+                                }
+                                std::collections::hash_map::Entry::Vacant(entry) => {
+                                    entry.insert(VideoDecoder::spawn(
+                                        dataflow_id,
+                                        output_id,
+                                        codec,
+                                        self.events_tx.clone(),
+                                        self.clock.clone(),
+                                    )?)
+                                }
+                            };
+                            decoder.submit(metadata, packet, throttled_inputs);
+                            Result::<(), eyre::Report>::Ok(())
+                        }
+                        (_, data) => {
+                            self.send_remote_output_to_local_receivers(
+                                dataflow_id,
+                                output_id,
+                                metadata,
+                                data,
+                                throttled_inputs,
+                            )
+                            .await
+                        }
+                    }
                 };
                 if let Err(err) = inner
                     .await
@@ -500,6 +754,7 @@ impl Daemon {
             }
             InterDaemonEvent::InputsClosed {
                 dataflow_id,
+                token,
                 inputs,
             } => {
                 tracing::debug!(?dataflow_id, ?inputs, "received InputsClosed event");
@@ -507,6 +762,9 @@ impl Daemon {
                     let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
                         format!("send out failed: no running dataflow with ID `{dataflow_id}`")
                     })?;
+                    if !tokens_match(&token, &dataflow.token) {
+                        bail!("rejected InputsClosed event with invalid dataflow token");
+                    }
                     for (receiver_id, input_id) in &inputs {
                         close_input(dataflow, receiver_id, input_id, &self.clock);
                     }
@@ -520,17 +778,246 @@ impl Daemon {
                 }
                 Ok(())
             }
+            InterDaemonEvent::ServiceRequest {
+                dataflow_id,
+                token,
+                caller,
+                caller_machine,
+                node_id,
+                service,
+                request_id,
+                timeout,
+                metadata,
+                data,
+            } => {
+                let valid = self
+                    .running
+                    .get(&dataflow_id)
+                    .is_some_and(|dataflow| tokens_match(&token, &dataflow.token));
+                if !valid {
+                    tracing::warn!(
+                        "rejected request for service `{node_id}/{service}` with invalid \
+                        dataflow ID or token"
+                    );
+                    return Ok(());
+                }
+                let call = ServiceCall {
+                    caller: caller.clone(),
+                    caller_machine: Some(caller_machine.clone()),
+                    node_id,
+                    service,
+                    request_id,
+                    timeout,
+                };
+                if let Err(err) = self.call_service(dataflow_id, call, metadata, data).await {
+                    let event = Timestamped {
+                        inner: InterDaemonEvent::ServiceReply {
+                            dataflow_id,
+                            token,
+                            caller,
+                            request_id,
+                            result: Err(err),
+                        },
+                        timestamp: self.clock.new_timestamp(),
+                    };
+                    if let Err(err) = inter_daemon::send_inter_daemon_event(
+                        &[caller_machine],
+                        &mut self.inter_daemon_connections,
+                        &event,
+                    )
+                    .await
+                    .wrap_err("failed to send service error to remote caller")
+                    {
+                        tracing::warn!("{err:?}");
+                    }
+                }
+                Ok(())
+            }
+            InterDaemonEvent::ServiceReply {
+                dataflow_id,
+                token,
+                caller,
+                request_id,
+                result,
+            } => {
+                let valid = self
+                    .running
+                    .get(&dataflow_id)
+                    .is_some_and(|dataflow| tokens_match(&token, &dataflow.token));
+                if !valid {
+                    tracing::warn!("rejected service reply with invalid dataflow ID or token");
+                    return Ok(());
+                }
+                self.reply_service_call(dataflow_id, caller, request_id, result)
+                    .await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Forwards a service request to the node that serves it, through the
+    /// daemon of its machine if the node runs on another machine.
+    async fn call_service(
+        &mut self,
+        dataflow_id: Uuid,
+        call: ServiceCall,
+        metadata: Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+    ) -> Result<(), String> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .ok_or_else(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        let service = dataflow.resolve_service(&call.node_id, call.service)?;
+        let Some(machine) = dataflow.remote_service_nodes.get(&call.node_id).cloned() else {
+            return dataflow.request_local_service(
+                ServiceCall { service, ..call },
+                metadata,
+                data,
+                &self.clock,
+            );
+        };
+        if call.caller_machine.is_some() {
+            // don't forward requests between daemons in a loop
+            return Err(format!(
+                "node `{}` is not running on machine `{}`",
+                call.node_id, self.machine_id
+            ));
+        }
+        let event = Timestamped {
+            inner: InterDaemonEvent::ServiceRequest {
+                dataflow_id,
+                token: dataflow.token.clone(),
+                caller: call.caller.clone(),
+                caller_machine: self.machine_id.clone(),
+                node_id: call.node_id.clone(),
+                service,
+                request_id: call.request_id,
+                timeout: call.timeout,
+                metadata,
+                data,
+            },
+            timestamp: self.clock.new_timestamp(),
+        };
+        inter_daemon::send_inter_daemon_event(
+            &[machine],
+            &mut self.inter_daemon_connections,
+            &event,
+        )
+        .await
+        .map_err(|err| format!("failed to forward request to remote service: {err:?}"))?;
+        dataflow.service_calls.insert(
+            (call.caller, call.request_id),
+            PendingServiceCall {
+                server: call.node_id,
+                caller_machine: None,
+                deadline: Instant::now() + call.timeout,
+            },
+        );
+        Ok(())
+    }
+
+    /// Sends the answer to a service request to the calling node, through the
+    /// daemon of its machine if the caller runs on another machine.
+    async fn reply_service_call(
+        &mut self,
+        dataflow_id: Uuid,
+        caller: NodeId,
+        request_id: u64,
+        result: Result<daemon_messages::ServiceResponse, String>,
+    ) {
+        let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+            return;
+        };
+        let Some(call) = dataflow.service_calls.remove(&(caller.clone(), request_id)) else {
+            tracing::debug!(
+                "ignoring reply to unknown or expired service request {request_id} of `{caller}`"
+            );
+            return;
+        };
+        let Some(machine) = call.caller_machine else {
+            dataflow.deliver_service_reply(&caller, request_id, result, &self.clock);
+            return;
+        };
+        let event = Timestamped {
+            inner: InterDaemonEvent::ServiceReply {
+                dataflow_id,
+                token: dataflow.token.clone(),
+                caller,
+                request_id,
+                result,
+            },
+            timestamp: self.clock.new_timestamp(),
+        };
+        if let Err(err) = inter_daemon::send_inter_daemon_event(
+            &[machine],
+            &mut self.inter_daemon_connections,
+            &event,
+        )
+        .await
+        .wrap_err("failed to forward service reply to remote caller")
+        {
+            tracing::warn!("{err:?}");
+        }
+    }
+
+    /// Answers the pending requests to the services of the given node with an
+    /// error and forgets the pending requests that the node sent.
+    async fn fail_service_calls(&mut self, dataflow_id: Uuid, node_id: &NodeId) {
+        let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+            return;
+        };
+        dataflow
+            .service_calls
+            .retain(|(caller, _), _| caller != node_id);
+        let failed: Vec<_> = dataflow
+            .service_calls
+            .iter()
+            .filter(|(_, call)| &call.server == node_id)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for (caller, request_id) in failed {
+            let error = format!("node `{node_id}` exited before answering the request");
+            self.reply_service_call(dataflow_id, caller, request_id, Err(error))
+                .await;
+        }
+    }
+
+    /// Answers the service requests that were not answered within their
+    /// timeout with an error.
+    async fn expire_service_calls(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .running
+            .values()
+            .flat_map(|dataflow| {
+                dataflow
+                    .service_calls
+                    .iter()
+                    .filter(|(_, call)| call.deadline <= now)
+                    .map(move |((caller, request_id), _)| {
+                        (dataflow.id, caller.clone(), *request_id)
+                    })
+            })
+            .collect();
+        for (dataflow_id, caller, request_id) in expired {
+            let error = "service did not reply within the timeout".to_owned();
+            self.reply_service_call(dataflow_id, caller, request_id, Err(error))
+                .await;
         }
     }
 
     async fn spawn_dataflow(
         &mut self,
         dataflow_id: uuid::Uuid,
+        token: DataflowToken,
         working_dir: PathBuf,
         nodes: Vec<ResolvedNode>,
         dataflow_descriptor: Descriptor,
     ) -> eyre::Result<()> {
-        let dataflow = RunningDataflow::new(dataflow_id, self.machine_id.clone());
+        video::check_decoding_support(&nodes, &self.machine_id)?;
+
+        let dataflow = RunningDataflow::new(dataflow_id, token, self.machine_id.clone());
         let dataflow = match self.running.entry(dataflow_id) {
             std::collections::hash_map::Entry::Vacant(entry) => {
                 self.working_dir.insert(dataflow_id, working_dir.clone());
@@ -540,20 +1027,126 @@ impl Daemon {
                 bail!("there is already a running dataflow with ID `{dataflow_id}`")
             }
         };
+        dataflow.shared_memory_budget = dataflow_descriptor.shared_memory.dataflow_budget;
+        dataflow.descriptor = Some(dataflow_descriptor.clone());
+
+        let sim_clock_source =
+            dataflow_descriptor
+                .clock
+                .as_ref()
+                .and_then(|clock| match &clock.source {
+                    InputMapping::User(mapping) => {
+                        Some(OutputId(mapping.source.clone(), mapping.output.clone()))
+                    }
+                    InputMapping::Timer { .. } => None,
+                });
+        let local_sim_clock_source = sim_clock_source.as_ref().is_some_and(|source| {
+            nodes
+                .iter()
+                .any(|node| node.id == source.0 && node.deploy.machine == self.machine_id)
+        });
+        let remote_machines: BTreeSet<_> = nodes
+            .iter()
+            .map(|node| node.deploy.machine.clone())
+            .filter(|machine| machine != &self.machine_id)
+            .collect();
+        let local_nodes: BTreeSet<_> = nodes
+            .iter()
+            .filter(|node| node.deploy.machine == self.machine_id)
+            .map(|node| node.id.clone())
+            .collect();
 
         for node in nodes {
             let local = node.deploy.machine == self.machine_id;
+            if !local {
+                let services = node_services(&node);
+                if !services.is_empty() {
+                    dataflow.services.insert(node.id.clone(), services);
+                    dataflow
+                        .remote_service_nodes
+                        .insert(node.id.clone(), node.deploy.machine.clone());
+                }
+            }
+            if let (true, CoreNodeKind::Runtime(runtime)) = (local, &node.kind) {
+                dataflow.runtime_operators.insert(
+                    node.id.clone(),
+                    runtime.operators.iter().map(|op| op.id.clone()).collect(),
+                );
+            }
+
+            if local {
+                for (output_id, codec) in node_encoding(&node) {
+                    let output_id = OutputId(node.id.clone(), output_id);
+                    let encoder = VideoEncoder::spawn(
+                        dataflow_id,
+                        output_id.clone(),
+                        codec,
+                        self.events_tx.clone(),
+                        self.clock.clone(),
+                    )?;
+                    dataflow.video_encoders.insert(output_id, encoder);
+                }
+
+                let notified_outputs = match &node.kind {
+                    CoreNodeKind::Custom(custom) if custom.run_config.notify_unsubscribed => {
+                        custom.run_config.outputs.clone()
+                    }
+                    CoreNodeKind::Custom(_) => BTreeSet::new(),
+                    CoreNodeKind::Runtime(runtime) => runtime_node_notified_outputs(runtime),
+                };
+                dataflow.notify_unsubscribed.extend(
+                    notified_outputs
+                        .into_iter()
+                        .map(|output_id| OutputId(node.id.clone(), output_id)),
+                );
+            }
 
             let inputs = node_inputs(&node);
             for (input_id, input) in inputs {
                 if local {
+                    let deliverable_in_process = input.deliverable_in_process();
                     dataflow
                         .open_inputs
                         .entry(node.id.clone())
                         .or_default()
                         .insert(input_id.clone());
+                    // outputs of other machines are throttled by their daemon
+                    let remote_source = matches!(
+                        &input.mapping,
+                        InputMapping::User(mapping) if !local_nodes.contains(&mapping.source)
+                    );
+                    if let Some(throttle) = InputThrottle::new(&input).filter(|_| !remote_source) {
+                        dataflow
+                            .input_throttles
+                            .insert((node.id.clone(), input_id.clone()), throttle);
+                    }
+                    if let Some(format) = input.serialization_format() {
+                        dataflow
+                            .input_formats
+                            .insert((node.id.clone(), input_id.clone()), format);
+                    }
+                    if let Some(condition) = input.when {
+                        dataflow
+                            .input_conditions
+                            .insert((node.id.clone(), input_id.clone()), condition);
+                    }
+                    if let Some(compact_by) = input.compact_by {
+                        dataflow
+                            .input_compaction
+                            .insert((node.id.clone(), input_id.clone()), compact_by);
+                    }
                     match input.mapping {
                         InputMapping::User(mapping) => {
+                            // with simulated time, all messages are stamped by the daemon
+                            if mapping.source == node.id
+                                && matches!(node.kind, CoreNodeKind::Runtime(_))
+                                && dataflow_descriptor.clock.is_none()
+                                && deliverable_in_process
+                            {
+                                dataflow
+                                    .in_process_inputs
+                                    .insert((node.id.clone(), input_id.clone()));
+                            }
                             dataflow
                                 .mappings
                                 .entry(OutputId(mapping.source, mapping.output))
@@ -568,10 +1161,35 @@ impl Daemon {
                                 .insert((node.id.clone(), input_id));
                         }
                     }
-                } else if let InputMapping::User(mapping) = input.mapping {
+                } else if let InputMapping::User(mapping) = &input.mapping {
+                    let output_id = OutputId(mapping.source.clone(), mapping.output.clone());
+                    // throttled messages are not sent to the other machine at all
+                    if let Some(throttle) = InputThrottle::new(&input) {
+                        dataflow
+                            .input_throttles
+                            .insert((node.id.clone(), input_id.clone()), throttle);
+                    }
+                    if let Some(format) = input.serialization_format() {
+                        dataflow
+                            .input_formats
+                            .insert((node.id.clone(), input_id.clone()), format);
+                    }
+                    if let Some(condition) = input.when {
+                        dataflow
+                            .input_conditions
+                            .insert((node.id.clone(), input_id.clone()), condition);
+                    }
+                    if let Some(compression) = input.compression {
+                        dataflow
+                            .remote_compression
+                            .entry(output_id.clone())
+                            .or_default()
+                            .entry(node.deploy.machine.clone())
+                            .or_insert(compression);
+                    }
                     dataflow
                         .open_external_mappings
-                        .entry(OutputId(mapping.source, mapping.output))
+                        .entry(output_id)
                         .or_default()
                         .entry(node.deploy.machine.clone())
                         .or_default()
@@ -579,7 +1197,51 @@ impl Daemon {
                 }
             }
             if local {
+                let shared_key = match &node.kind {
+                    CoreNodeKind::Custom(custom) if custom.shared => {
+                        Some(SharedNodeKey::new(node.id.clone(), custom))
+                    }
+                    _ => None,
+                };
+                if let Some(shared) = shared_key
+                    .as_ref()
+                    .and_then(|key| self.shared_nodes.get_mut(key))
+                {
+                    tracing::info!(
+                        "attaching dataflow `{dataflow_id}` to shared node `{}` of dataflow `{}`",
+                        node.id,
+                        shared.owner
+                    );
+                    shared.attached.insert(dataflow_id);
+                    if let Some(pid) = shared.pid {
+                        dataflow.node_pids.insert(node.id.clone(), pid);
+                    }
+                    dataflow.running_nodes.insert(node.id.clone());
+                    report_node_state(
+                        &mut self.coordinator_connection,
+                        &self.machine_id,
+                        dataflow_id,
+                        node.id,
+                        NodeState::Active,
+                        &self.clock,
+                    )
+                    .await;
+                    continue;
+                }
+
                 dataflow.pending_nodes.insert(node.id.clone());
+                if node.ready_signal {
+                    dataflow.pending_nodes.expect_ready_signal(node.id.clone());
+                }
+                let services = node_services(&node);
+                if !services.is_empty() {
+                    dataflow.services.insert(node.id.clone(), services);
+                }
+                if node.restart.is_some() && shared_key.is_none() {
+                    dataflow
+                        .restartable_nodes
+                        .insert(node.id.clone(), node.clone());
+                }
 
                 let node_id = node.id.clone();
                 match spawn::spawn_node(
@@ -593,11 +1255,44 @@ impl Daemon {
                 .await
                 .wrap_err_with(|| format!("failed to spawn node `{node_id}`"))
                 {
-                    Ok(()) => {
-                        dataflow.running_nodes.insert(node_id);
+                    Ok(pid) => {
+                        if let Some(pid) = pid {
+                            dataflow.node_pids.insert(node_id.clone(), pid);
+                        }
+                        if let Some(key) = shared_key {
+                            self.shared_nodes.insert(
+                                key,
+                                SharedNode {
+                                    owner: dataflow_id,
+                                    node_id: node_id.clone(),
+                                    pid,
+                                    attached: BTreeSet::new(),
+                                    stop_requested: false,
+                                },
+                            );
+                        }
+                        dataflow.running_nodes.insert(node_id.clone());
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id,
+                            NodeState::Configuring,
+                            &self.clock,
+                        )
+                        .await;
                     }
                     Err(err) => {
                         tracing::error!("{err:?}");
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id.clone(),
+                            NodeState::Errored,
+                            &self.clock,
+                        )
+                        .await;
                         dataflow
                             .pending_nodes
                             .handle_node_stop(
@@ -613,6 +1308,21 @@ impl Daemon {
             }
         }
 
+        if let Some(source) = sim_clock_source {
+            // all machines need the simulated time for their timers
+            if local_sim_clock_source {
+                for machine in remote_machines {
+                    dataflow
+                        .open_external_mappings
+                        .entry(source.clone())
+                        .or_default()
+                        .entry(machine)
+                        .or_default();
+                }
+            }
+            dataflow.sim_clock = Some(SimClock::new(source, dataflow.timers.keys().copied()));
+        }
+
         Ok(())
     }
 
@@ -627,17 +1337,45 @@ impl Daemon {
                 event_sender,
                 reply_sender,
             } => {
-                let dataflow = self.running.get_mut(&dataflow_id).ok_or_else(|| {
-                    format!("subscribe failed: no running dataflow with ID `{dataflow_id}`")
-                });
-
-                match dataflow {
+                // restarted nodes join the running dataflow directly
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    if dataflow.restarted_nodes.remove(&node_id) {
+                        tracing::info!("restarted node `{node_id}` is ready");
+                        Self::subscribe(dataflow, node_id.clone(), event_sender, &self.clock).await;
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id,
+                            NodeState::Active,
+                            &self.clock,
+                        )
+                        .await;
+                        let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+                        return Ok(());
+                    }
+                }
+
+                let dataflow = self.running.get_mut(&dataflow_id).ok_or_else(|| {
+                    format!("subscribe failed: no running dataflow with ID `{dataflow_id}`")
+                });
+
+                match dataflow {
                     Err(err) => {
                         let _ = reply_sender.send(DaemonReply::Result(Err(err)));
                     }
                     Ok(dataflow) => {
                         tracing::debug!("node `{node_id}` is ready");
                         Self::subscribe(dataflow, node_id.clone(), event_sender, &self.clock).await;
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id.clone(),
+                            NodeState::Active,
+                            &self.clock,
+                        )
+                        .await;
 
                         let status = dataflow
                             .pending_nodes
@@ -653,13 +1391,32 @@ impl Daemon {
                                 tracing::info!(
                                     "all nodes are ready, starting dataflow `{dataflow_id}`"
                                 );
-                                dataflow.start(&self.events_tx, &self.clock).await?;
+                                self.start_dataflow(dataflow_id).await?;
                             }
                             DataflowStatus::Pending => {}
                         }
                     }
                 }
             }
+            DaemonNodeEvent::Ready { reply_sender } => {
+                let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+                    format!(
+                        "failed to signal readiness: no running dataflow with ID `{dataflow_id}`"
+                    )
+                })?;
+                let status = dataflow
+                    .pending_nodes
+                    .handle_node_ready(&node_id, &mut self.coordinator_connection, &self.clock)
+                    .await?;
+                let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+                match status {
+                    DataflowStatus::AllNodesReady => {
+                        tracing::info!("all nodes are ready, starting dataflow `{dataflow_id}`");
+                        self.start_dataflow(dataflow_id).await?;
+                    }
+                    DataflowStatus::Pending => {}
+                }
+            }
             DaemonNodeEvent::SubscribeDrop {
                 event_sender,
                 reply_sender,
@@ -715,6 +1472,17 @@ impl Daemon {
                 self.send_out(dataflow_id, node_id, output_id, metadata, data)
                     .await?
             }
+            DaemonNodeEvent::InputsDropped { counts } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    for (input_id, count) in counts {
+                        if let Some(counters) =
+                            dataflow.link_stats.get_mut(&(node_id.clone(), input_id))
+                        {
+                            counters.dropped += count;
+                        }
+                    }
+                }
+            }
             DaemonNodeEvent::ReportDrop { tokens } => {
                 let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
                     format!(
@@ -751,6 +1519,61 @@ impl Daemon {
                 let reply = inner.await.map_err(|err| format!("{err:?}"));
                 let _ = reply_sender.send(DaemonReply::Result(reply));
             }
+            DaemonNodeEvent::ReserveSharedMemory { len, reply_sender } => {
+                let reply = match self.running.get_mut(&dataflow_id) {
+                    Some(dataflow) => dataflow.reserve_shared_memory(&node_id, len),
+                    None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                };
+                let _ = reply_sender.send(DaemonReply::Result(reply));
+            }
+            DaemonNodeEvent::ReleaseSharedMemory { len, reply_sender } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    if let Some(reserved) = dataflow.shared_memory_reserved.get_mut(&node_id) {
+                        *reserved = reserved.saturating_sub(len);
+                    }
+                }
+                let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+            }
+            DaemonNodeEvent::CallService {
+                node_id: service_node,
+                service,
+                request_id,
+                timeout,
+                metadata,
+                data,
+                reply_sender,
+            } => {
+                let result = match data {
+                    None => Ok(None),
+                    Some(DataMessage::Vec(data)) => Ok(Some(data)),
+                    Some(DataMessage::SharedMemory { .. }) => {
+                        Err("service requests must not use shared memory".to_owned())
+                    }
+                };
+                let result = match result {
+                    Ok(data) => {
+                        let call = ServiceCall {
+                            caller: node_id,
+                            caller_machine: None,
+                            node_id: service_node,
+                            service,
+                            request_id,
+                            timeout,
+                        };
+                        self.call_service(dataflow_id, call, metadata, data).await
+                    }
+                    Err(err) => Err(err),
+                };
+                let _ = reply_sender.send(DaemonReply::Result(result));
+            }
+            DaemonNodeEvent::ServiceReply {
+                caller,
+                request_id,
+                result,
+            } => {
+                self.reply_service_call(dataflow_id, caller, request_id, result)
+                    .await;
+            }
         }
         Ok(())
     }
@@ -779,6 +1602,145 @@ impl Daemon {
         Ok(())
     }
 
+    fn set_parameter(
+        &mut self,
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        key: String,
+        value: MetadataValue,
+    ) -> eyre::Result<()> {
+        let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+            format!("Set parameter failed: no running dataflow with ID `{dataflow_id}`")
+        })?;
+        // restarted nodes should start with the new value
+        if let Some(node) = dataflow.restartable_nodes.get_mut(&node_id) {
+            node.set_parameter(&key, &value);
+        }
+        if let Some(descriptor) = &mut dataflow.descriptor {
+            descriptor.set_parameter(&node_id, &key, &value);
+        }
+        if let Some(channel) = dataflow.subscribe_channels.get(&node_id) {
+            let event = daemon_messages::NodeEvent::ParameterUpdate { key, value };
+            if send_with_timestamp(channel, event, &self.clock).is_err() {
+                dataflow.subscribe_channels.remove(&node_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn stop_node(&mut self, dataflow_id: Uuid, node_id: NodeId) -> eyre::Result<()> {
+        let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+            format!("Stop failed: no running dataflow with ID `{dataflow_id}`")
+        })?;
+        let channel = dataflow
+            .subscribe_channels
+            .remove(&node_id)
+            .wrap_err_with(|| format!("node `{node_id}` is not active"))?;
+        let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, &self.clock);
+        report_node_state(
+            &mut self.coordinator_connection,
+            &self.machine_id,
+            dataflow_id,
+            node_id,
+            NodeState::Deactivating,
+            &self.clock,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Spawns a failed node again.
+    async fn restart_node(&mut self, dataflow_id: Uuid, node_id: NodeId) -> eyre::Result<()> {
+        let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+            format!("Restart failed: no running dataflow with ID `{dataflow_id}`")
+        })?;
+        let err = dataflow
+            .failed_nodes
+            .remove(&node_id)
+            .wrap_err_with(|| format!("node `{node_id}` is not waiting for a restart"))?;
+        if dataflow.stop_sent {
+            self.release_failed_node(dataflow_id, node_id, err).await?;
+            bail!("dataflow `{dataflow_id}` is stopping");
+        }
+        let node = dataflow.restartable_nodes.get(&node_id).cloned();
+        let descriptor = dataflow.descriptor.clone();
+        let working_dir = self.working_dir.get(&dataflow_id).cloned();
+        let (Some(node), Some(descriptor), Some(working_dir)) = (node, descriptor, working_dir)
+        else {
+            self.release_failed_node(dataflow_id, node_id, err).await?;
+            bail!("no restart information found for node `{dataflow_id}`");
+        };
+
+        tracing::info!("restarting node `{dataflow_id}/{node_id}`");
+        let spawn_result = spawn::spawn_node(
+            dataflow_id,
+            &working_dir,
+            node,
+            self.events_tx.clone(),
+            descriptor,
+            self.clock.clone(),
+        )
+        .await
+        .wrap_err_with(|| format!("failed to restart node `{node_id}`"));
+        match spawn_result {
+            Ok(pid) => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    dataflow.node_restarted(&node_id, pid, &self.clock);
+                }
+                report_node_state(
+                    &mut self.coordinator_connection,
+                    &self.machine_id,
+                    dataflow_id,
+                    node_id,
+                    NodeState::Restarting,
+                    &self.clock,
+                )
+                .await;
+                Ok(())
+            }
+            Err(spawn_err) => {
+                let message = format!("{spawn_err:?}");
+                self.release_failed_node(dataflow_id, node_id, spawn_err)
+                    .await?;
+                bail!(message)
+            }
+        }
+    }
+
+    /// Handles the exit of a failed node that is not restarted.
+    async fn release_failed_node(
+        &mut self,
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        err: eyre::Report,
+    ) -> eyre::Result<()> {
+        self.dataflow_errors
+            .entry(dataflow_id)
+            .or_default()
+            .insert(node_id.clone(), err);
+        self.handle_node_stop(dataflow_id, &node_id).await
+    }
+
+    /// Starts the timers of the given dataflow and delivers the outputs that
+    /// nodes with a `ready_signal` sent during their initialization.
+    async fn start_dataflow(&mut self, dataflow_id: Uuid) -> eyre::Result<()> {
+        let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+            format!("failed to start: no running dataflow with ID `{dataflow_id}`")
+        })?;
+        dataflow.start(&self.events_tx, &self.clock).await?;
+        for output in dataflow.pending_nodes.start() {
+            let HeldOutput {
+                node_id,
+                output_id,
+                metadata,
+                data,
+            } = output;
+            self.send_out(dataflow_id, node_id, output_id, metadata, data)
+                .await?;
+        }
+        Ok(())
+    }
+
     async fn send_out(
         &mut self,
         dataflow_id: Uuid,
@@ -790,40 +1752,321 @@ impl Daemon {
         let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
             format!("send out failed: no running dataflow with ID `{dataflow_id}`")
         })?;
+        if !dataflow.pending_nodes.is_started() {
+            let output = HeldOutput {
+                node_id,
+                output_id,
+                metadata,
+                data,
+            };
+            if let Some(dropped) = dataflow.pending_nodes.hold_output(output) {
+                tracing::warn!(
+                    "dropping output `{}/{}` because too many outputs were sent \
+                    before the start of dataflow `{dataflow_id}`",
+                    dropped.node_id,
+                    dropped.output_id
+                );
+                if let Some(token) = dropped.data.as_ref().and_then(|d| d.drop_token()) {
+                    dataflow
+                        .pending_drop_tokens
+                        .entry(token)
+                        .or_insert_with(|| DropTokenInformation {
+                            owner: dropped.node_id.clone(),
+                            pending_nodes: Default::default(),
+                        });
+                    dataflow.check_drop_token(token, &self.clock).await?;
+                }
+            }
+            return Ok(());
+        }
+        let mut sim_time_advance = None;
+        let metadata = match &mut dataflow.sim_clock {
+            Some(sim_clock) => {
+                let output = OutputId(node_id.clone(), output_id.clone());
+                match sim_clock.advance(&output, &metadata, data.as_ref()) {
+                    Ok(advance) => sim_time_advance = advance,
+                    Err(err) => tracing::warn!(
+                        "failed to read simulated time from `{node_id}/{output_id}`: {err:?}"
+                    ),
+                }
+                sim_clock.stamp(metadata)
+            }
+            None => metadata,
+        };
         let data_bytes = send_output_to_local_receivers(
             node_id.clone(),
             output_id.clone(),
             dataflow,
             &metadata,
             data,
+            &BTreeSet::new(),
+            &self.clock,
+        )
+        .await?;
+        if let Some(advance) = sim_time_advance {
+            dataflow.advance_sim_time(advance, &self.clock);
+        }
+
+        // dataflows that are attached to a shared node get a copy of its outputs
+        let attached: Vec<_> = self
+            .shared_nodes
+            .values()
+            .filter(|shared| shared.owner == dataflow_id && shared.node_id == node_id)
+            .flat_map(|shared| shared.attached.iter().copied())
+            .collect();
+        for attached_id in attached {
+            let Some(dataflow) = self.running.get_mut(&attached_id) else {
+                continue;
+            };
+            let data_bytes = send_output_to_local_receivers(
+                node_id.clone(),
+                output_id.clone(),
+                dataflow,
+                &metadata,
+                data_bytes.clone().map(DataMessage::Vec),
+                &BTreeSet::new(),
+                &self.clock,
+            )
+            .await?;
+            self.send_to_remote_receivers(
+                attached_id,
+                OutputId(node_id.clone(), output_id.clone()),
+                metadata.clone(),
+                data_bytes,
+            )
+            .await?;
+        }
+
+        self.send_to_remote_receivers(
+            dataflow_id,
+            OutputId(node_id, output_id),
+            metadata,
+            data_bytes,
+        )
+        .await
+    }
+
+    async fn handle_video_event(&mut self, event: VideoEvent) {
+        let result = match event {
+            VideoEvent::Encoded {
+                dataflow_id,
+                output_id,
+                metadata,
+                data,
+                encoding,
+                throttled_inputs,
+            } => self
+                .forward_to_remote_receivers(
+                    dataflow_id,
+                    output_id,
+                    metadata,
+                    Some(data),
+                    encoding,
+                    throttled_inputs,
+                )
+                .await
+                .wrap_err("failed to send encoded output to remote receivers"),
+            VideoEvent::Decoded {
+                dataflow_id,
+                output_id,
+                metadata,
+                data,
+                throttled_inputs,
+            } => self
+                .send_remote_output_to_local_receivers(
+                    dataflow_id,
+                    output_id,
+                    metadata,
+                    Some(data),
+                    throttled_inputs,
+                )
+                .await
+                .wrap_err("failed to forward decoded output to local receivers"),
+        };
+        if let Err(err) = result {
+            tracing::warn!("{err:?}");
+        }
+    }
+
+    async fn send_remote_output_to_local_receivers(
+        &mut self,
+        dataflow_id: Uuid,
+        output_id: OutputId,
+        metadata: dora_core::message::Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+        throttled_inputs: BTreeSet<InputId>,
+    ) -> eyre::Result<()> {
+        let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
+            format!("send out failed: no running dataflow with ID `{dataflow_id}`")
+        })?;
+        let data = data.map(DataMessage::Vec);
+        // the messages are stamped with the simulated time by their sender already
+        let sim_time_advance = match &mut dataflow.sim_clock {
+            Some(sim_clock) => match sim_clock.advance(&output_id, &metadata, data.as_ref()) {
+                Ok(advance) => advance,
+                Err(err) => {
+                    let OutputId(node_id, output_id) = &output_id;
+                    tracing::warn!(
+                        "failed to read simulated time from `{node_id}/{output_id}`: {err:?}"
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        let OutputId(node_id, output_id) = output_id;
+        send_output_to_local_receivers(
+            node_id,
+            output_id,
+            dataflow,
+            &metadata,
+            data,
+            &throttled_inputs,
             &self.clock,
         )
         .await?;
+        if let Some(advance) = sim_time_advance {
+            dataflow.advance_sim_time(advance, &self.clock);
+        }
+        Ok(())
+    }
+
+    async fn send_to_remote_receivers(
+        &mut self,
+        dataflow_id: Uuid,
+        output_id: OutputId,
+        metadata: dora_core::message::Metadata,
+        data_bytes: Option<AVec<u8, ConstAlign<128>>>,
+    ) -> eyre::Result<()> {
+        let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+            return Ok(());
+        };
+        let Some(throttled_inputs) = dataflow.throttle_remote_inputs(&output_id, &metadata) else {
+            // no remote input receives the output
+            return Ok(());
+        };
+        match (dataflow.video_encoders.get(&output_id), data_bytes) {
+            (Some(encoder), Some(data)) => {
+                // the encoder thread reports the result as `VideoEvent::Encoded`
+                if !encoder.submit(metadata, data, throttled_inputs) {
+                    tracing::warn!(
+                        "dropping image of output `{}/{}` because the encoder is too slow",
+                        output_id.0,
+                        output_id.1
+                    );
+                }
+                Ok(())
+            }
+            (_, data_bytes) => {
+                self.forward_to_remote_receivers(
+                    dataflow_id,
+                    output_id,
+                    metadata,
+                    data_bytes,
+                    None,
+                    throttled_inputs,
+                )
+                .await
+            }
+        }
+    }
 
-        let output_id = OutputId(node_id, output_id);
+    async fn forward_to_remote_receivers(
+        &mut self,
+        dataflow_id: Uuid,
+        output_id: OutputId,
+        metadata: dora_core::message::Metadata,
+        mut data_bytes: Option<AVec<u8, ConstAlign<128>>>,
+        encoding: Option<VideoCodec>,
+        throttled_inputs: BTreeSet<InputId>,
+    ) -> eyre::Result<()> {
+        let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+            return Ok(());
+        };
         let remote_receivers: Vec<_> = dataflow
             .open_external_mappings
             .get(&output_id)
-            .map(|m| m.keys().cloned().collect())
+            .map(|m| {
+                m.iter()
+                    // encoded streams can only be decoded if no packet is missing
+                    .filter(|(_, inputs)| {
+                        encoding.is_some()
+                            || inputs.iter().any(|input| !throttled_inputs.contains(input))
+                    })
+                    .map(|(machine, _)| machine.clone())
+                    .collect()
+            })
             .unwrap_or_default();
         if !remote_receivers.is_empty() {
-            let event = Timestamped {
-                inner: InterDaemonEvent::Output {
-                    dataflow_id,
-                    node_id: output_id.0,
-                    output_id: output_id.1,
-                    metadata,
-                    data: data_bytes,
-                },
-                timestamp: self.clock.new_timestamp(),
-            };
-            inter_daemon::send_inter_daemon_event(
-                &remote_receivers,
-                &mut self.inter_daemon_connections,
-                &event,
-            )
-            .await
-            .wrap_err("failed to forward output to remote receivers")?;
+            // group the receiving machines by the compression of their link
+            let mut links: Vec<(Option<Compression>, Vec<String>)> = Vec::new();
+            for machine in remote_receivers {
+                let compression = dataflow
+                    .remote_compression
+                    .get(&output_id)
+                    .and_then(|links| links.get(&machine))
+                    .copied()
+                    // encoded video doesn't get any smaller
+                    .filter(|_| encoding.is_none());
+                match links.iter_mut().find(|(c, _)| *c == compression) {
+                    Some((_, machines)) => machines.push(machine),
+                    None => links.push((compression, vec![machine])),
+                }
+            }
+
+            let link_count = links.len();
+            for (index, (compression, machines)) in links.into_iter().enumerate() {
+                let data = if index + 1 == link_count {
+                    data_bytes.take()
+                } else {
+                    data_bytes.clone()
+                };
+                let (data, compression) = match (compression, data) {
+                    (Some(compression), Some(data)) => {
+                        // don't stall the event loop on large messages
+                        let (data, result) = tokio::task::spawn_blocking(move || {
+                            let result = compression::compress(compression, &data);
+                            (data, result)
+                        })
+                        .await
+                        .wrap_err("compression task panicked")?;
+                        match result {
+                            Ok(compressed) => (Some(compressed), Some(compression.algorithm)),
+                            Err(err) => {
+                                tracing::warn!(
+                                    "failed to compress output `{}/{}`, sending it \
+                                    uncompressed: {err:?}",
+                                    output_id.0,
+                                    output_id.1
+                                );
+                                (Some(data), None)
+                            }
+                        }
+                    }
+                    (_, data) => (data, None),
+                };
+                let event = Timestamped {
+                    inner: InterDaemonEvent::Output {
+                        dataflow_id,
+                        token: dataflow.token.clone(),
+                        node_id: output_id.0.clone(),
+                        output_id: output_id.1.clone(),
+                        metadata: metadata.clone(),
+                        data,
+                        encoding,
+                        compression,
+                        throttled_inputs: throttled_inputs.clone(),
+                    },
+                    timestamp: self.clock.new_timestamp(),
+                };
+                inter_daemon::send_inter_daemon_event(
+                    &machines,
+                    &mut self.inter_daemon_connections,
+                    &event,
+                )
+                .await
+                .wrap_err("failed to forward output to remote receivers")?;
+            }
         }
 
         Ok(())
@@ -835,6 +2078,32 @@ impl Daemon {
         event_sender: UnboundedSender<Timestamped<daemon_messages::NodeEvent>>,
         clock: &HLC,
     ) {
+        if let Some(time) = dataflow.sim_clock.as_ref().and_then(SimClock::now) {
+            let _ = send_with_timestamp(
+                &event_sender,
+                daemon_messages::NodeEvent::SimTime { time },
+                clock,
+            );
+        }
+
+        // catch up on the latest state of compacted inputs, e.g. after a restart
+        for ((receiver_id, input_id), history) in &dataflow.compacted_history {
+            if receiver_id != &node_id {
+                continue;
+            }
+            for (_, metadata, data) in history {
+                let _ = send_with_timestamp(
+                    &event_sender,
+                    daemon_messages::NodeEvent::Input {
+                        id: input_id.clone(),
+                        metadata: metadata.clone(),
+                        data: data.as_deref().cloned().map(DataMessage::Vec),
+                    },
+                    clock,
+                );
+            }
+        }
+
         // some inputs might have been closed already -> report those events
         let closed_inputs = dataflow
             .mappings
@@ -866,6 +2135,21 @@ impl Daemon {
             );
         }
 
+        // some consumers of the outputs might have stopped already
+        for OutputId(_, output_id) in dataflow
+            .unsubscribed_outputs
+            .iter()
+            .filter(|OutputId(source, _)| source == &node_id)
+        {
+            let _ = send_with_timestamp(
+                &event_sender,
+                daemon_messages::NodeEvent::OutputUnsubscribed {
+                    id: output_id.clone(),
+                },
+                clock,
+            );
+        }
+
         // if a stop event was already sent for the dataflow, send it to
         // the newly connected node too
         if dataflow.stop_sent {
@@ -912,6 +2196,68 @@ impl Daemon {
         .await?;
 
         dataflow.running_nodes.remove(node_id);
+        dataflow.node_pids.remove(node_id);
+        // the shared memory of the node is freed when its process exits
+        dataflow.shared_memory_reserved.remove(node_id);
+        dataflow.update_output_subscriptions(&self.clock);
+
+        // shared nodes of other dataflows don't keep this dataflow running
+        let only_attached_left = dataflow.running_nodes.iter().all(|node_id| {
+            self.shared_nodes
+                .values()
+                .any(|shared| &shared.node_id == node_id && shared.attached.contains(&dataflow_id))
+        });
+        self.fail_service_calls(dataflow_id, node_id).await;
+        if only_attached_left {
+            self.detach_shared_nodes(dataflow_id).await?;
+        }
+
+        self.finish_dataflow_if_done(dataflow_id).await
+    }
+
+    /// Detaches the given dataflow from all shared nodes that it uses.
+    ///
+    /// Shared nodes whose owner dataflow was stopped already are stopped when
+    /// their last attached dataflow is detached.
+    async fn detach_shared_nodes(&mut self, dataflow_id: Uuid) -> eyre::Result<()> {
+        let mut detached = Vec::new();
+        let mut to_stop = Vec::new();
+        for shared in self.shared_nodes.values_mut() {
+            if shared.attached.remove(&dataflow_id) {
+                detached.push(shared.node_id.clone());
+                if shared.attached.is_empty() && shared.stop_requested {
+                    to_stop.push((shared.owner, shared.node_id.clone()));
+                }
+            }
+        }
+        for (owner, node_id) in to_stop {
+            tracing::info!("stopping shared node `{node_id}` because it is no longer used");
+            if let Err(err) = self.stop_node(owner, node_id).await {
+                tracing::warn!("{err:?}");
+            }
+        }
+
+        if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+            for node_id in detached {
+                Self::handle_outputs_done(
+                    dataflow,
+                    &mut self.inter_daemon_connections,
+                    &node_id,
+                    &self.clock,
+                )
+                .await?;
+                dataflow.running_nodes.remove(&node_id);
+                dataflow.node_pids.remove(&node_id);
+            }
+            dataflow.update_output_subscriptions(&self.clock);
+        }
+        Ok(())
+    }
+
+    async fn finish_dataflow_if_done(&mut self, dataflow_id: Uuid) -> eyre::Result<()> {
+        let Some(dataflow) = self.running.get(&dataflow_id) else {
+            return Ok(());
+        };
         if dataflow.running_nodes.is_empty() {
             let result = match self.dataflow_errors.get(&dataflow.id) {
                 None => Ok(()),
@@ -928,6 +2274,13 @@ impl Daemon {
                 "Dataflow `{dataflow_id}` finished on machine `{}`",
                 self.machine_id
             );
+            let report = dataflow.rate_report();
+            if let Some(working_dir) = self.working_dir.get(&dataflow_id) {
+                let path = report::report_path(working_dir, &dataflow_id);
+                if let Err(err) = tokio::fs::write(&path, report.to_string()).await {
+                    tracing::warn!("failed to write rate report to `{}`: {err}", path.display());
+                }
+            }
             if let Some(connection) = &mut self.coordinator_connection {
                 let msg = serde_json::to_vec(&Timestamped {
                     inner: CoordinatorRequest::Event {
@@ -935,6 +2288,7 @@ impl Daemon {
                         event: DaemonEvent::AllNodesFinished {
                             dataflow_id,
                             result,
+                            report,
                         },
                     },
                     timestamp: self.clock.new_timestamp(),
@@ -960,35 +2314,7 @@ impl Daemon {
                     return Ok(RunStatus::Continue);
                 };
 
-                let Some(subscribers) = dataflow.timers.get(&interval) else {
-                    return Ok(RunStatus::Continue);
-                };
-
-                let mut closed = Vec::new();
-                for (receiver_id, input_id) in subscribers {
-                    let Some(channel) = dataflow.subscribe_channels.get(receiver_id) else {
-                        continue;
-                    };
-
-                    let send_result = send_with_timestamp(
-                        channel,
-                        daemon_messages::NodeEvent::Input {
-                            id: input_id.clone(),
-                            metadata: metadata.clone(),
-                            data: None,
-                        },
-                        &self.clock,
-                    );
-                    match send_result {
-                        Ok(()) => {}
-                        Err(_) => {
-                            closed.push(receiver_id);
-                        }
-                    }
-                }
-                for id in closed {
-                    dataflow.subscribe_channels.remove(id);
-                }
+                dataflow.send_timer_tick(interval, metadata, &self.clock);
             }
             DoraEvent::Logs {
                 dataflow_id,
@@ -1041,6 +2367,7 @@ impl Daemon {
                 dataflow_id,
                 node_id,
                 exit_status,
+                resource_violation,
             } => {
                 let node_error = match exit_status {
                     NodeExitStatus::Success => {
@@ -1109,6 +2436,60 @@ impl Daemon {
                         Some(err)
                     }
                 };
+                let node_error = match (node_error, resource_violation) {
+                    (Some(err), Some(violation)) => Some(err.wrap_err(violation)),
+                    (None, Some(violation)) => {
+                        tracing::warn!("node {dataflow_id}/{node_id}: {violation}");
+                        None
+                    }
+                    (node_error, None) => node_error,
+                };
+
+                let state = if node_error.is_some() {
+                    NodeState::Errored
+                } else {
+                    NodeState::Finalized
+                };
+
+                // nodes with a restart policy wait for the coordinator to decide
+                // whether they are restarted
+                let restartable = self.coordinator_connection.is_some()
+                    && self.running.get(&dataflow_id).is_some_and(|dataflow| {
+                        dataflow.restartable_nodes.contains_key(&node_id)
+                            && !dataflow.stop_sent
+                            && !dataflow.pending_nodes.is_pending(&node_id)
+                    });
+                let node_error = match node_error {
+                    Some(err) if restartable => {
+                        tracing::info!(
+                            "waiting for coordinator to restart node `{dataflow_id}/{node_id}`"
+                        );
+                        if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                            dataflow.wait_for_restart(&node_id, err, &self.clock);
+                        }
+                        self.fail_service_calls(dataflow_id, &node_id).await;
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            dataflow_id,
+                            node_id,
+                            state,
+                            &self.clock,
+                        )
+                        .await;
+                        return Ok(RunStatus::Continue);
+                    }
+                    other => other,
+                };
+                report_node_state(
+                    &mut self.coordinator_connection,
+                    &self.machine_id,
+                    dataflow_id,
+                    node_id.clone(),
+                    state,
+                    &self.clock,
+                )
+                .await;
 
                 if let Some(err) = node_error {
                     self.dataflow_errors
@@ -1119,6 +2500,27 @@ impl Daemon {
 
                 self.handle_node_stop(dataflow_id, &node_id).await?;
 
+                // the outputs of a shared node are closed for all attached dataflows too
+                let shared_key = self
+                    .shared_nodes
+                    .iter()
+                    .find(|(_, shared)| shared.owner == dataflow_id && shared.node_id == node_id)
+                    .map(|(key, _)| key.clone());
+                if let Some(shared) = shared_key.and_then(|key| self.shared_nodes.remove(&key)) {
+                    for attached_id in shared.attached {
+                        report_node_state(
+                            &mut self.coordinator_connection,
+                            &self.machine_id,
+                            attached_id,
+                            node_id.clone(),
+                            state,
+                            &self.clock,
+                        )
+                        .await;
+                        self.handle_node_stop(attached_id, &node_id).await?;
+                    }
+                }
+
                 if let Some(exit_when_done) = &mut self.exit_when_done {
                     exit_when_done.remove(&(dataflow_id, node_id));
                     if exit_when_done.is_empty() {
@@ -1140,15 +2542,49 @@ async fn send_output_to_local_receivers(
     dataflow: &mut RunningDataflow,
     metadata: &dora_core::message::Metadata,
     data: Option<DataMessage>,
+    throttled_inputs: &BTreeSet<InputId>,
     clock: &HLC,
 ) -> Result<Option<AVec<u8, ConstAlign<128>>>, eyre::ErrReport> {
     let timestamp = metadata.timestamp();
     let empty_set = BTreeSet::new();
     let output_id = OutputId(node_id, output_id);
     let local_receivers = dataflow.mappings.get(&output_id).unwrap_or(&empty_set);
-    let OutputId(node_id, _) = output_id;
+    let OutputId(node_id, output_id) = output_id;
+    let data_len = match &data {
+        None => 0,
+        Some(DataMessage::Vec(v)) => v.len(),
+        Some(DataMessage::SharedMemory { len, .. }) => *len,
+    };
     let mut closed = Vec::new();
+    let mut compacted = Vec::new();
     for (receiver_id, input_id) in local_receivers {
+        let input = (receiver_id.clone(), input_id.clone());
+        if dataflow.in_process_inputs.contains(&input)
+            || throttled_inputs.contains(&input)
+            || !dataflow.receives_format(&input, metadata)
+        {
+            continue;
+        }
+        if let Some(condition) = dataflow
+            .input_conditions
+            .get(&(receiver_id.clone(), input_id.clone()))
+        {
+            if !condition.matches(&metadata.parameters) {
+                continue;
+            }
+        }
+        if let Some(throttle) = dataflow.input_throttles.get_mut(&input) {
+            if !throttle.forward(message_time(metadata)) {
+                continue;
+            }
+        }
+        if let Some(key) = dataflow
+            .input_compaction
+            .get(&(receiver_id.clone(), input_id.clone()))
+            .and_then(|compact_by| compact_by.key_of(&metadata.parameters))
+        {
+            compacted.push(((receiver_id.clone(), input_id.clone()), key));
+        }
         if let Some(channel) = dataflow.subscribe_channels.get(receiver_id) {
             let item = daemon_messages::NodeEvent::Input {
                 id: input_id.clone(),
@@ -1160,6 +2596,19 @@ async fn send_output_to_local_receivers(
                 timestamp,
             }) {
                 Ok(()) => {
+                    let counters = dataflow
+                        .link_stats
+                        .entry((receiver_id.clone(), input_id.clone()))
+                        .or_insert_with(|| LinkCounters {
+                            source: OutputId(node_id.clone(), output_id.clone()),
+                            messages: 0,
+                            bytes: 0,
+                            dropped: 0,
+                            rate: Default::default(),
+                        });
+                    counters.messages += 1;
+                    counters.rate.record(Instant::now());
+                    counters.bytes += data_len as u64;
                     if let Some(token) = data.as_ref().and_then(|d| d.drop_token()) {
                         dataflow
                             .pending_drop_tokens
@@ -1197,6 +2646,43 @@ async fn send_output_to_local_receivers(
         }
         Some(DataMessage::Vec(v)) => (Some(v), None),
     };
+    if !compacted.is_empty() {
+        // a single copy of the data is shared by all compacted inputs
+        let data = data_bytes.clone().map(Arc::new);
+        for (input, key) in compacted {
+            let history = dataflow.compacted_history.entry(input).or_default();
+            history.retain(|(existing, ..)| *existing != key);
+            if history.len() >= MAX_COMPACTED_KEYS {
+                // forget the key that was updated least recently
+                history.pop_front();
+            }
+            history.push_back((key, metadata.clone(), data.clone()));
+        }
+    }
+    // observers get a copy of the data, so that they don't need to report drop tokens
+    dataflow.observers.retain(|observer| {
+        // serialized copies are only meant for the inputs that request them
+        let observed = metadata.parameters.format.is_none()
+            && (observer.outputs.is_empty()
+                || observer
+                    .outputs
+                    .contains(&(node_id.clone(), output_id.clone())));
+        if !observed {
+            return true;
+        }
+        let item = daemon_messages::NodeEvent::Input {
+            id: DataId::from(format!("{node_id}/{output_id}")),
+            metadata: metadata.clone(),
+            data: data_bytes.clone().map(DataMessage::Vec),
+        };
+        observer
+            .channel
+            .send(Timestamped {
+                inner: item,
+                timestamp,
+            })
+            .is_ok()
+    });
     if let Some(token) = drop_token {
         // insert token into `pending_drop_tokens` even if there are no local subscribers
         dataflow
@@ -1233,6 +2719,59 @@ fn runtime_node_inputs(n: &dora_core::descriptor::RuntimeNode) -> BTreeMap<DataI
         .collect()
 }
 
+fn node_services(node: &ResolvedNode) -> BTreeSet<DataId> {
+    match &node.kind {
+        CoreNodeKind::Custom(n) => n.run_config.services.clone(),
+        CoreNodeKind::Runtime(n) => runtime_node_services(n),
+    }
+}
+
+fn runtime_node_services(n: &dora_core::descriptor::RuntimeNode) -> BTreeSet<DataId> {
+    n.operators
+        .iter()
+        .flat_map(|operator| {
+            operator
+                .config
+                .services
+                .iter()
+                .map(|service| DataId::from(format!("{}/{service}", operator.id)))
+        })
+        .collect()
+}
+
+fn node_encoding(node: &ResolvedNode) -> BTreeMap<DataId, VideoCodec> {
+    match &node.kind {
+        CoreNodeKind::Custom(n) => n.run_config.encoding.clone(),
+        CoreNodeKind::Runtime(n) => runtime_node_encoding(n),
+    }
+}
+
+fn runtime_node_encoding(n: &dora_core::descriptor::RuntimeNode) -> BTreeMap<DataId, VideoCodec> {
+    n.operators
+        .iter()
+        .flat_map(|operator| {
+            operator.config.encoding.iter().map(|(output_id, codec)| {
+                (DataId::from(format!("{}/{output_id}", operator.id)), *codec)
+            })
+        })
+        .collect()
+}
+
+/// Outputs of the operators that set `notify_unsubscribed`.
+fn runtime_node_notified_outputs(n: &dora_core::descriptor::RuntimeNode) -> BTreeSet<DataId> {
+    n.operators
+        .iter()
+        .filter(|operator| operator.config.notify_unsubscribed)
+        .flat_map(|operator| {
+            operator
+                .config
+                .outputs
+                .iter()
+                .map(|output_id| DataId::from(format!("{}/{output_id}", operator.id)))
+        })
+        .collect()
+}
+
 fn runtime_node_outputs(n: &dora_core::descriptor::RuntimeNode) -> BTreeSet<DataId> {
     n.operators
         .iter()
@@ -1246,6 +2785,27 @@ fn runtime_node_outputs(n: &dora_core::descriptor::RuntimeNode) -> BTreeSet<Data
         .collect()
 }
 
+/// Reads the CPU time of the threads that run the given operators of a runtime node.
+///
+/// The first operator runs on the main thread of the runtime, the others run on
+/// threads that are named after their index, see [`operator_thread_name`].
+fn operator_cpu_times(pid: u32, operators: &[OperatorId]) -> BTreeMap<OperatorId, Duration> {
+    let threads = ThreadStats::read_all(pid);
+    operators
+        .iter()
+        .enumerate()
+        .filter_map(|(index, operator_id)| {
+            let thread = if index == 0 {
+                threads.iter().find(|thread| thread.tid == pid)
+            } else {
+                let name = operator_thread_name(index);
+                threads.iter().find(|thread| thread.name == name)
+            }?;
+            Some((operator_id.clone(), thread.cpu_time))
+        })
+        .collect()
+}
+
 async fn send_input_closed_events<F>(
     dataflow: &mut RunningDataflow,
     inter_daemon_connections: &mut BTreeMap<String, InterDaemonConnection>,
@@ -1277,6 +2837,7 @@ where
             let event = Timestamped {
                 inner: InterDaemonEvent::InputsClosed {
                     dataflow_id: dataflow.id,
+                    token: dataflow.token.clone(),
                     inputs,
                 },
                 timestamp: clock.new_timestamp(),
@@ -1320,15 +2881,50 @@ fn close_input(
     }
 }
 
+/// Time of the given message since the UNIX epoch, which throttled inputs are
+/// rate limited by.
+fn message_time(metadata: &Metadata) -> Duration {
+    let time = metadata.timestamp().get_time().to_system_time();
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Key, metadata, and data of a retained message of a compacted input.
+type CompactedMessage = (String, Metadata, Option<Arc<AVec<u8, ConstAlign<128>>>>);
+
+/// Maximum number of keys whose latest message is retained per compacted input.
+const MAX_COMPACTED_KEYS: usize = 1024;
+
 pub struct RunningDataflow {
     id: Uuid,
+    /// Secret that identifies the messages of this dataflow from other daemons.
+    token: DataflowToken,
     /// Local nodes that are not started yet
     pending_nodes: PendingNodes,
 
     subscribe_channels: HashMap<NodeId, UnboundedSender<Timestamped<daemon_messages::NodeEvent>>>,
     drop_channels: HashMap<NodeId, UnboundedSender<Timestamped<daemon_messages::NodeDropEvent>>>,
     mappings: HashMap<OutputId, BTreeSet<InputId>>,
+    /// Inputs of runtime nodes that are connected to an operator of the same
+    /// runtime. The runtime delivers their messages directly.
+    in_process_inputs: BTreeSet<InputId>,
+    /// Conditions that messages must match to be forwarded to an input.
+    input_conditions: BTreeMap<InputId, Condition>,
+    /// Inputs that receive the serialized copies of their source output in
+    /// the given format instead of the Arrow data.
+    input_formats: BTreeMap<InputId, DataFormat>,
+    /// Metadata fields that identify the keys of compacted inputs.
+    input_compaction: BTreeMap<InputId, CompactionKey>,
+    /// Rate limits of inputs with `max_rate` or `every_nth` option.
+    input_throttles: BTreeMap<InputId, InputThrottle>,
+    /// Latest message per key of compacted inputs, which is replayed to nodes
+    /// that subscribe late, e.g. after a restart.
+    ///
+    /// Ordered by the time of the last update, at most [`MAX_COMPACTED_KEYS`]
+    /// per input.
+    compacted_history: BTreeMap<InputId, VecDeque<CompactedMessage>>,
     timers: BTreeMap<Duration, BTreeSet<InputId>>,
+    /// Simulated time of dataflows with a `clock`, which drives the timers.
+    sim_clock: Option<SimClock>,
     open_inputs: BTreeMap<NodeId, BTreeSet<DataId>>,
     running_nodes: BTreeSet<NodeId>,
 
@@ -1336,6 +2932,53 @@ pub struct RunningDataflow {
 
     pending_drop_tokens: HashMap<DropToken, DropTokenInformation>,
 
+    /// Read-only connections that receive copies of the outputs.
+    observers: Vec<Observer>,
+
+    /// Process IDs of the running local nodes.
+    node_pids: BTreeMap<NodeId, u32>,
+    /// Operators of the local runtime nodes, used to find their threads.
+    runtime_operators: BTreeMap<NodeId, Vec<OperatorId>>,
+    /// Message statistics of the inputs of local nodes.
+    link_stats: BTreeMap<InputId, LinkCounters>,
+
+    /// Maximum shared memory that the local nodes can allocate together.
+    shared_memory_budget: Option<u64>,
+    /// Shared memory that is currently reserved by each local node.
+    shared_memory_reserved: BTreeMap<NodeId, u64>,
+
+    /// Local nodes with a restart policy, which are restarted on request of
+    /// the coordinator when they fail.
+    restartable_nodes: BTreeMap<NodeId, ResolvedNode>,
+    /// Descriptor of the dataflow, used to restart nodes.
+    descriptor: Option<Descriptor>,
+    /// Failed nodes that wait for the coordinator to restart or release them.
+    failed_nodes: BTreeMap<NodeId, eyre::Report>,
+    /// Restarted nodes that did not subscribe to their events yet.
+    restarted_nodes: BTreeSet<NodeId>,
+
+    /// Services of the nodes of all machines. Services of runtime nodes are
+    /// prefixed with the operator ID.
+    services: BTreeMap<NodeId, BTreeSet<DataId>>,
+    /// Machines of the nodes of other machines that serve services.
+    remote_service_nodes: BTreeMap<NodeId, String>,
+    /// Forwarded service requests that are not answered yet, by caller and
+    /// request ID.
+    service_calls: BTreeMap<(NodeId, u64), PendingServiceCall>,
+
+    /// Encoders of the local outputs that specify an `encoding`.
+    video_encoders: HashMap<OutputId, VideoEncoder>,
+    /// Decoders of the encoded remote outputs.
+    video_decoders: HashMap<OutputId, VideoDecoder>,
+    /// Compression of the links from local outputs to other machines, by
+    /// machine ID.
+    remote_compression: HashMap<OutputId, BTreeMap<String, Compression>>,
+
+    /// Local outputs whose nodes are notified when all their consumers stopped.
+    notify_unsubscribed: HashSet<OutputId>,
+    /// Outputs of `notify_unsubscribed` that have no running consumer.
+    unsubscribed_outputs: HashSet<OutputId>,
+
     /// Keep handles to all timer tasks of this dataflow to cancel them on drop.
     _timer_handles: Vec<futures::future::RemoteHandle<()>>,
     stop_sent: bool,
@@ -1347,29 +2990,262 @@ pub struct RunningDataflow {
 }
 
 impl RunningDataflow {
-    fn new(dataflow_id: Uuid, machine_id: String) -> RunningDataflow {
+    /// Applies the conditions and rate limits of the inputs on other machines
+    /// that receive the given output.
+    ///
+    /// Returns the inputs that skip the message, or `None` if no input
+    /// receives it.
+    fn throttle_remote_inputs(
+        &mut self,
+        output_id: &OutputId,
+        metadata: &Metadata,
+    ) -> Option<BTreeSet<InputId>> {
+        let receivers = self.open_external_mappings.get(output_id)?;
+        let time = message_time(metadata);
+        let mut throttled = BTreeSet::new();
+        let mut received = false;
+        for input in receivers.values().flatten() {
+            let matches = self.receives_format(input, metadata)
+                && self
+                    .input_conditions
+                    .get(input)
+                    .map_or(true, |condition| condition.matches(&metadata.parameters));
+            let forward = matches
+                && self
+                    .input_throttles
+                    .get_mut(input)
+                    .map_or(true, |throttle| throttle.forward(time));
+            if forward {
+                received = true;
+            } else {
+                throttled.insert(input.clone());
+            }
+        }
+        received.then_some(throttled)
+    }
+
+    /// Whether the given input receives messages in the format of the given
+    /// message, i.e. the Arrow data or one of the serialized copies.
+    fn receives_format(&self, input: &InputId, metadata: &Metadata) -> bool {
+        self.input_formats.get(input).copied() == metadata.parameters.format
+    }
+
+    fn new(dataflow_id: Uuid, token: DataflowToken, machine_id: String) -> RunningDataflow {
         Self {
             id: dataflow_id,
+            token,
             pending_nodes: PendingNodes::new(dataflow_id, machine_id),
             subscribe_channels: HashMap::new(),
             drop_channels: HashMap::new(),
             mappings: HashMap::new(),
+            in_process_inputs: BTreeSet::new(),
+            input_conditions: BTreeMap::new(),
+            input_formats: BTreeMap::new(),
+            input_compaction: BTreeMap::new(),
+            input_throttles: BTreeMap::new(),
+            compacted_history: BTreeMap::new(),
             timers: BTreeMap::new(),
+            sim_clock: None,
             open_inputs: BTreeMap::new(),
             running_nodes: BTreeSet::new(),
             open_external_mappings: HashMap::new(),
             pending_drop_tokens: HashMap::new(),
+            observers: Vec::new(),
+            node_pids: BTreeMap::new(),
+            runtime_operators: BTreeMap::new(),
+            link_stats: BTreeMap::new(),
+            shared_memory_budget: None,
+            shared_memory_reserved: BTreeMap::new(),
+            restartable_nodes: BTreeMap::new(),
+            descriptor: None,
+            failed_nodes: BTreeMap::new(),
+            restarted_nodes: BTreeSet::new(),
+            services: BTreeMap::new(),
+            remote_service_nodes: BTreeMap::new(),
+            service_calls: BTreeMap::new(),
+            video_encoders: HashMap::new(),
+            video_decoders: HashMap::new(),
+            remote_compression: HashMap::new(),
+            notify_unsubscribed: HashSet::new(),
+            unsubscribed_outputs: HashSet::new(),
             _timer_handles: Vec::new(),
             stop_sent: false,
             empty_set: BTreeSet::new(),
         }
     }
 
+    /// Sends a tick of the timer with the given interval to its subscribers.
+    fn send_timer_tick(&mut self, interval: Duration, metadata: Metadata, clock: &HLC) {
+        let Some(subscribers) = self.timers.get(&interval) else {
+            return;
+        };
+
+        let mut closed = Vec::new();
+        for (receiver_id, input_id) in subscribers {
+            let Some(channel) = self.subscribe_channels.get(receiver_id) else {
+                continue;
+            };
+
+            let send_result = send_with_timestamp(
+                channel,
+                daemon_messages::NodeEvent::Input {
+                    id: input_id.clone(),
+                    metadata: metadata.clone(),
+                    data: None,
+                },
+                clock,
+            );
+            match send_result {
+                Ok(()) => {}
+                Err(_) => {
+                    closed.push(receiver_id.clone());
+                }
+            }
+        }
+        for id in closed {
+            self.subscribe_channels.remove(&id);
+        }
+    }
+
+    /// Sends the timer ticks that are due after the simulated time advanced
+    /// and notifies the local nodes about the new time.
+    fn advance_sim_time(&mut self, advance: Advance, clock: &HLC) {
+        let Some(sim_clock) = &mut self.sim_clock else {
+            return;
+        };
+        let ticks: Vec<_> = advance
+            .ticks
+            .into_iter()
+            .map(|(time, interval)| {
+                let metadata = timer_metadata(sim_clock.timestamp_at(time, *clock.get_id()));
+                (interval, metadata)
+            })
+            .collect();
+        for (interval, metadata) in ticks {
+            self.send_timer_tick(interval, metadata, clock);
+        }
+        for channel in self.subscribe_channels.values() {
+            let _ = send_with_timestamp(
+                channel,
+                daemon_messages::NodeEvent::SimTime { time: advance.time },
+                clock,
+            );
+        }
+    }
+
+    /// Resolves the ID of the given service of the given node.
+    ///
+    /// Services can be referenced without operator ID for single-operator nodes.
+    fn resolve_service(&self, node_id: &NodeId, service: DataId) -> Result<DataId, String> {
+        let Some(services) = self.services.get(node_id) else {
+            return Err(format!("node `{node_id}` has no services"));
+        };
+        if services.contains(&service) {
+            return Ok(service);
+        }
+        let aliased = DataId::from(format!("op/{service}"));
+        if services.contains(&aliased) {
+            Ok(aliased)
+        } else {
+            Err(format!("node `{node_id}` has no service `{service}`"))
+        }
+    }
+
+    /// Forwards a service request to the local node that serves it.
+    fn request_local_service(
+        &mut self,
+        call: ServiceCall,
+        metadata: Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+        clock: &HLC,
+    ) -> Result<(), String> {
+        let node_id = call.node_id;
+        let channel = self
+            .subscribe_channels
+            .get(&node_id)
+            .ok_or_else(|| format!("node `{node_id}` is not running"))?;
+        let request = daemon_messages::NodeEvent::ServiceRequest {
+            service: call.service,
+            caller: call.caller.clone(),
+            request_id: call.request_id,
+            metadata,
+            data: data.map(DataMessage::Vec),
+        };
+        send_with_timestamp(channel, request, clock)
+            .map_err(|_| format!("node `{node_id}` is not running"))?;
+        self.service_calls.insert(
+            (call.caller, call.request_id),
+            PendingServiceCall {
+                server: node_id,
+                caller_machine: call.caller_machine,
+                deadline: Instant::now() + call.timeout,
+            },
+        );
+        Ok(())
+    }
+
+    /// Delivers the answer to a service request to the local calling node.
+    fn deliver_service_reply(
+        &self,
+        caller: &NodeId,
+        request_id: u64,
+        result: Result<daemon_messages::ServiceResponse, String>,
+        clock: &HLC,
+    ) {
+        let Some(channel) = self.drop_channels.get(caller) else {
+            tracing::debug!("node `{caller}` exited before receiving service reply {request_id}");
+            return;
+        };
+        let reply = daemon_messages::NodeDropEvent::ServiceReply { request_id, result };
+        let _ = send_with_timestamp(channel, reply, clock);
+    }
+
+    /// Collects the resource usage of the local nodes and the statistics of their inputs.
+    fn stats(&self, machine_id: &str) -> DataflowStats {
+        let nodes = self
+            .running_nodes
+            .iter()
+            .map(|node_id| {
+                let pid = self.node_pids.get(node_id).copied();
+                let stats = NodeStats {
+                    machine: machine_id.to_owned(),
+                    pid,
+                    process: pid.and_then(ProcessStats::read_for_pid),
+                    operators: match (pid, self.runtime_operators.get(node_id)) {
+                        (Some(pid), Some(operators)) => operator_cpu_times(pid, operators),
+                        _ => BTreeMap::new(),
+                    },
+                };
+                (node_id.clone(), stats)
+            })
+            .collect();
+        let links = self
+            .link_stats
+            .iter()
+            .map(|(input_id, counters)| counters.stats(input_id))
+            .collect();
+        DataflowStats { nodes, links }
+    }
+
+    /// Summarizes the message statistics of the inputs of the local nodes.
+    fn rate_report(&self) -> RateReport {
+        let links = self
+            .link_stats
+            .iter()
+            .map(|(input_id, counters)| counters.rate.report(counters.stats(input_id)))
+            .collect();
+        RateReport { links }
+    }
+
     async fn start(
         &mut self,
         events_tx: &mpsc::Sender<Timestamped<Event>>,
         clock: &Arc<HLC>,
     ) -> eyre::Result<()> {
+        if self.sim_clock.is_some() {
+            // the timers tick when the simulated time advances
+            return Ok(());
+        }
         for interval in self.timers.keys().copied() {
             let events_tx = events_tx.clone();
             let dataflow_id = self.id;
@@ -1380,21 +3256,7 @@ impl RunningDataflow {
                 loop {
                     interval_stream.tick().await;
 
-                    let span = tracing::span!(tracing::Level::TRACE, "tick");
-                    let _ = span.enter();
-
-                    let metadata = dora_core::message::Metadata::from_parameters(
-                        hlc.new_timestamp(),
-                        ArrowTypeInfo::empty(),
-                        MetadataParameters {
-                            watermark: 0,
-                            deadline: 0,
-                            #[cfg(feature = "telemetry")]
-                            open_telemetry_context: serialize_context(&span.context()),
-                            #[cfg(not(feature = "telemetry"))]
-                            open_telemetry_context: "".into(),
-                        },
-                    );
+                    let metadata = timer_metadata(hlc.new_timestamp());
 
                     let event = Timestamped {
                         inner: DoraEvent::Timer {
@@ -1418,17 +3280,110 @@ impl RunningDataflow {
         Ok(())
     }
 
-    async fn stop_all(&mut self, clock: &HLC) {
-        for (_node_id, channel) in self.subscribe_channels.drain() {
-            let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, clock);
+    /// Sends a stop event to all subscribed nodes, except the `retained` ones,
+    /// and returns their IDs.
+    async fn stop_all(&mut self, clock: &HLC, retained: &BTreeSet<NodeId>) -> Vec<NodeId> {
+        let stopped: Vec<_> = self
+            .subscribe_channels
+            .keys()
+            .filter(|node_id| !retained.contains(*node_id))
+            .cloned()
+            .collect();
+        for node_id in &stopped {
+            if let Some(channel) = self.subscribe_channels.remove(node_id) {
+                let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, clock);
+            }
         }
         self.stop_sent = true;
+        stopped
+    }
+
+    /// Sends `OutputUnsubscribed` events for outputs whose consumers all
+    /// stopped and `OutputSubscribed` events for outputs that have a running
+    /// consumer again.
+    ///
+    /// Consumers on other machines are not tracked, so outputs with remote
+    /// receivers always count as subscribed.
+    fn update_output_subscriptions(&mut self, clock: &HLC) {
+        for output_id in &self.notify_unsubscribed {
+            let Some(receivers) = self.mappings.get(output_id) else {
+                continue;
+            };
+            let subscribed = receivers.iter().any(|(receiver, _)| {
+                self.running_nodes.contains(receiver) && !self.failed_nodes.contains_key(receiver)
+            }) || self
+                .open_external_mappings
+                .get(output_id)
+                .is_some_and(|mapping| !mapping.is_empty());
+            let changed = if subscribed {
+                self.unsubscribed_outputs.remove(output_id)
+            } else {
+                self.unsubscribed_outputs.insert(output_id.clone())
+            };
+            if !changed {
+                continue;
+            }
+
+            let OutputId(node_id, id) = output_id;
+            if subscribed {
+                tracing::debug!("output `{node_id}/{id}` has a running consumer again");
+            } else {
+                tracing::debug!("all consumers of output `{node_id}/{id}` stopped");
+            }
+            if let Some(channel) = self.subscribe_channels.get(node_id) {
+                let id = id.clone();
+                let event = if subscribed {
+                    daemon_messages::NodeEvent::OutputSubscribed { id }
+                } else {
+                    daemon_messages::NodeEvent::OutputUnsubscribed { id }
+                };
+                let _ = send_with_timestamp(channel, event, clock);
+            }
+        }
+    }
+
+    /// Releases the resources of a failed node until the coordinator restarts
+    /// or releases it.
+    fn wait_for_restart(&mut self, node_id: &NodeId, err: eyre::Report, clock: &HLC) {
+        self.subscribe_channels.remove(node_id);
+        self.drop_channels.remove(node_id);
+        self.node_pids.remove(node_id);
+        self.shared_memory_reserved.remove(node_id);
+        self.failed_nodes.insert(node_id.clone(), err);
+        self.update_output_subscriptions(clock);
+    }
+
+    /// Records that a failed node was spawned again.
+    fn node_restarted(&mut self, node_id: &NodeId, pid: Option<u32>, clock: &HLC) {
+        if let Some(pid) = pid {
+            self.node_pids.insert(node_id.clone(), pid);
+        }
+        self.restarted_nodes.insert(node_id.clone());
+        self.update_output_subscriptions(clock);
     }
 
     fn open_inputs(&self, node_id: &NodeId) -> &BTreeSet<DataId> {
         self.open_inputs.get(node_id).unwrap_or(&self.empty_set)
     }
 
+    /// Reserves shared memory for the given node if the dataflow's budget allows it.
+    fn reserve_shared_memory(&mut self, node_id: &NodeId, len: u64) -> Result<(), String> {
+        if let Some(budget) = self.shared_memory_budget {
+            let reserved: u64 = self.shared_memory_reserved.values().sum();
+            if reserved.saturating_add(len) > budget {
+                return Err(format!(
+                    "shared memory budget of dataflow exceeded: cannot allocate {len} bytes \
+                    ({reserved} of {budget} bytes are in use)"
+                ));
+            }
+        }
+        *self
+            .shared_memory_reserved
+            .entry(node_id.clone())
+            .or_default() += len;
+        Ok(())
+    }
+
     async fn check_drop_token(&mut self, token: DropToken, clock: &HLC) -> eyre::Result<()> {
         match self.pending_drop_tokens.entry(token) {
             std::collections::hash_map::Entry::Occupied(entry) => {
@@ -1466,6 +3421,102 @@ impl RunningDataflow {
 pub struct OutputId(NodeId, DataId);
 type InputId = (NodeId, DataId);
 
+struct LinkCounters {
+    source: OutputId,
+    messages: u64,
+    bytes: u64,
+    dropped: u64,
+    rate: MessageRate,
+}
+
+impl LinkCounters {
+    fn stats(&self, (target, input): &InputId) -> LinkStats {
+        LinkStats {
+            source: self.source.0.clone(),
+            output: self.source.1.clone(),
+            target: target.clone(),
+            input: input.clone(),
+            messages: self.messages,
+            bytes: self.bytes,
+            dropped: self.dropped,
+        }
+    }
+}
+
+struct Observer {
+    /// The observed outputs, or all outputs if empty.
+    outputs: BTreeSet<(NodeId, DataId)>,
+    channel: UnboundedSender<Timestamped<daemon_messages::NodeEvent>>,
+}
+
+/// Identifies instances of `shared` nodes that dataflows can attach to.
+///
+/// Only nodes that are spawned with the same configuration and whose inputs
+/// are mapped to the same sources are shared.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SharedNodeKey {
+    node_id: NodeId,
+    source: String,
+    args: Option<String>,
+    envs: BTreeMap<String, String>,
+    inputs: BTreeMap<DataId, InputMapping>,
+}
+
+impl SharedNodeKey {
+    fn new(node_id: NodeId, node: &dora_core::descriptor::CustomNode) -> Self {
+        Self {
+            node_id,
+            source: node.source.clone(),
+            args: node.args.clone(),
+            envs: node
+                .envs
+                .iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect(),
+            inputs: node
+                .run_config
+                .inputs
+                .iter()
+                .map(|(id, input)| (id.clone(), input.mapping.clone()))
+                .collect(),
+        }
+    }
+}
+
+struct SharedNode {
+    /// The dataflow that spawned the node.
+    owner: DataflowId,
+    node_id: NodeId,
+    pid: Option<u32>,
+    /// Other dataflows that receive the outputs of the node.
+    attached: BTreeSet<DataflowId>,
+    /// Whether the owner dataflow was stopped while other dataflows were attached.
+    stop_requested: bool,
+}
+
+/// A service request of a node.
+struct ServiceCall {
+    caller: NodeId,
+    /// Machine of the calling node, if it runs on another machine.
+    caller_machine: Option<String>,
+    /// The node that serves the service.
+    node_id: NodeId,
+    service: DataId,
+    request_id: u64,
+    timeout: Duration,
+}
+
+/// A forwarded service request that is not answered yet.
+struct PendingServiceCall {
+    /// The node that serves the request.
+    server: NodeId,
+    /// Machine of the calling node, if it runs on another machine.
+    caller_machine: Option<String>,
+    /// Time after which the request is answered with an error.
+    deadline: Instant,
+}
+
 struct DropTokenInformation {
     /// The node that created the associated drop token.
     owner: NodeId,
@@ -1484,6 +3535,15 @@ pub enum Event {
     Coordinator(CoordinatorEvent),
     Daemon(InterDaemonEvent),
     Dora(DoraEvent),
+    /// A read-only observer connection wants to subscribe to a dataflow.
+    Observer {
+        dataflow_id: DataflowId,
+        /// The observed outputs, or all outputs if empty.
+        outputs: BTreeSet<(NodeId, DataId)>,
+        event_sender: UnboundedSender<Timestamped<daemon_messages::NodeEvent>>,
+        reply_sender: oneshot::Sender<DaemonReply>,
+    },
+    Video(VideoEvent),
     HeartbeatInterval,
     CtrlC,
 }
@@ -1499,6 +3559,9 @@ pub enum DaemonNodeEvent {
     OutputsDone {
         reply_sender: oneshot::Sender<DaemonReply>,
     },
+    Ready {
+        reply_sender: oneshot::Sender<DaemonReply>,
+    },
     Subscribe {
         event_sender: UnboundedSender<Timestamped<daemon_messages::NodeEvent>>,
         reply_sender: oneshot::Sender<DaemonReply>,
@@ -1519,9 +3582,35 @@ pub enum DaemonNodeEvent {
     ReportDrop {
         tokens: Vec<DropToken>,
     },
+    /// Number of messages per input that were dropped because the queue was full.
+    InputsDropped {
+        counts: BTreeMap<DataId, u64>,
+    },
     EventStreamDropped {
         reply_sender: oneshot::Sender<DaemonReply>,
     },
+    ReserveSharedMemory {
+        len: u64,
+        reply_sender: oneshot::Sender<DaemonReply>,
+    },
+    ReleaseSharedMemory {
+        len: u64,
+        reply_sender: oneshot::Sender<DaemonReply>,
+    },
+    CallService {
+        node_id: NodeId,
+        service: DataId,
+        request_id: u64,
+        timeout: Duration,
+        metadata: Metadata,
+        data: Option<DataMessage>,
+        reply_sender: oneshot::Sender<DaemonReply>,
+    },
+    ServiceReply {
+        caller: NodeId,
+        request_id: u64,
+        result: Result<daemon_messages::ServiceResponse, String>,
+    },
 }
 
 #[derive(Debug)]
@@ -1541,6 +3630,8 @@ pub enum DoraEvent {
         dataflow_id: DataflowId,
         node_id: NodeId,
         exit_status: NodeExitStatus,
+        /// How the node violated its resource limits, if it did.
+        resource_violation: Option<String>,
     },
 }
 
@@ -1583,6 +3674,68 @@ enum RunStatus {
     Exit,
 }
 
+/// Informs the coordinator about a lifecycle state change of a local node.
+///
+/// Errors are only logged because the state is purely informational.
+async fn report_node_state(
+    coordinator_connection: &mut Option<TcpStream>,
+    machine_id: &str,
+    dataflow_id: DataflowId,
+    node_id: NodeId,
+    state: NodeState,
+    clock: &HLC,
+) {
+    tracing::debug!("node `{dataflow_id}/{node_id}` is {state}");
+    let Some(connection) = coordinator_connection else {
+        return;
+    };
+    let result = async {
+        let msg = serde_json::to_vec(&Timestamped {
+            inner: CoordinatorRequest::Event {
+                machine_id: machine_id.to_owned(),
+                event: DaemonEvent::NodeStateChanged {
+                    dataflow_id,
+                    node_id,
+                    state,
+                },
+            },
+            timestamp: clock.new_timestamp(),
+        })?;
+        tcp_send(connection, &msg)
+            .await
+            .wrap_err("failed to send NodeStateChanged message to dora-coordinator")
+    };
+    if let Err(err) = result.await {
+        tracing::warn!("{err:?}");
+    }
+}
+
+fn timer_metadata(timestamp: uhlc::Timestamp) -> Metadata {
+    let span = tracing::span!(tracing::Level::TRACE, "tick");
+    let _ = span.enter();
+
+    Metadata::from_parameters(
+        timestamp,
+        ArrowTypeInfo::empty(),
+        MetadataParameters {
+            watermark: 0,
+            deadline: 0,
+            #[cfg(feature = "telemetry")]
+            open_telemetry_context: serialize_context(&span.context()),
+            #[cfg(not(feature = "telemetry"))]
+            open_telemetry_context: "".into(),
+            sequence: None,
+            device: None,
+            annotates: None,
+            checksum: None,
+            format: None,
+            logical_time: None,
+            lineage: Vec::new(),
+            values: Default::default(),
+        },
+    )
+}
+
 fn send_with_timestamp<T>(
     sender: &UnboundedSender<Timestamped<T>>,
     event: T,
@@ -1623,3 +3776,106 @@ fn set_up_ctrlc_handler(
 
     Ok(ReceiverStream::new(ctrlc_rx))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_producer_when_consumer_exits_and_restarts() {
+        let clock = HLC::default();
+        let camera = NodeId::from("camera".to_owned());
+        let viewer = NodeId::from("viewer".to_owned());
+        let image = DataId::from("image".to_owned());
+        let output = OutputId(camera.clone(), image.clone());
+
+        let mut dataflow = RunningDataflow::new(Uuid::nil(), "token".into(), "A".into());
+        dataflow
+            .mappings
+            .insert(output.clone(), [(viewer.clone(), image.clone())].into());
+        dataflow.notify_unsubscribed.insert(output);
+        dataflow
+            .running_nodes
+            .extend([camera.clone(), viewer.clone()]);
+        let (events_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        dataflow.subscribe_channels.insert(camera, events_tx);
+        let mut next_event = move || events.try_recv().ok().map(|event| event.inner);
+
+        dataflow.update_output_subscriptions(&clock);
+        assert!(next_event().is_none());
+
+        // the consumer fails and waits for its restart
+        dataflow.wait_for_restart(&viewer, eyre!("crashed"), &clock);
+        assert!(matches!(
+            next_event(),
+            Some(daemon_messages::NodeEvent::OutputUnsubscribed { id }) if id == image
+        ));
+
+        dataflow.failed_nodes.remove(&viewer);
+        dataflow.node_restarted(&viewer, Some(42), &clock);
+        assert!(matches!(
+            next_event(),
+            Some(daemon_messages::NodeEvent::OutputSubscribed { id }) if id == image
+        ));
+
+        // the consumer exits for good
+        dataflow.running_nodes.remove(&viewer);
+        dataflow.update_output_subscriptions(&clock);
+        assert!(matches!(
+            next_event(),
+            Some(daemon_messages::NodeEvent::OutputUnsubscribed { id }) if id == image
+        ));
+        assert!(next_event().is_none());
+    }
+
+    #[test]
+    fn shared_node_key_includes_envs_and_inputs() {
+        let node = |yaml: &str| {
+            let node: dora_core::descriptor::CustomNode = serde_yaml::from_str(yaml).unwrap();
+            SharedNodeKey::new(NodeId::from("camera".to_owned()), &node)
+        };
+        let base = "source: ./camera\nshared: true\noutputs: [image]\n";
+
+        let key = node(&format!("{base}inputs:\n  tick: dora/timer/millis/100\n"));
+        assert_eq!(
+            key,
+            node(&format!("{base}inputs:\n  tick: dora/timer/millis/100\n"))
+        );
+        assert_ne!(
+            key,
+            node(&format!("{base}inputs:\n  tick: dora/timer/millis/50\n"))
+        );
+        assert_ne!(
+            key,
+            node(&format!(
+                "{base}inputs:\n  tick: dora/timer/millis/100\nenvs:\n  DEVICE: 1\n"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_all_keeps_retained_nodes() {
+        let clock = HLC::default();
+        let camera = NodeId::from("camera".to_owned());
+        let viewer = NodeId::from("viewer".to_owned());
+
+        let mut dataflow = RunningDataflow::new(Uuid::nil(), "token".into(), "A".into());
+        let (camera_tx, mut camera_events) = tokio::sync::mpsc::unbounded_channel();
+        let (viewer_tx, mut viewer_events) = tokio::sync::mpsc::unbounded_channel();
+        dataflow
+            .subscribe_channels
+            .insert(camera.clone(), camera_tx);
+        dataflow
+            .subscribe_channels
+            .insert(viewer.clone(), viewer_tx);
+
+        let stopped = dataflow.stop_all(&clock, &[camera.clone()].into()).await;
+        assert_eq!(stopped, vec![viewer]);
+        assert!(matches!(
+            viewer_events.try_recv().map(|event| event.inner),
+            Ok(daemon_messages::NodeEvent::Stop)
+        ));
+        assert!(camera_events.try_recv().is_err());
+        assert!(dataflow.subscribe_channels.contains_key(&camera));
+    }
+}
@@ -0,0 +1,603 @@
+//! Resource limits of node processes, configured through the `cpu_affinity`,
+//! `memory_limit`, and `nice` options of nodes.
+//!
+//! On Unix, the CPU affinity and the scheduling priority are set in the child
+//! process before it executes the node, so that they also apply to all threads
+//! that the node creates. Memory is limited through a cgroup v2 group below the
+//! cgroup of the daemon on Linux and through a job object on Windows, which also
+//! applies the other limits there. Nodes that reach their memory limit are
+//! reported as warnings while they run and as part of their error when they fail.
+
+use dora_core::{config::NodeId, daemon_messages::DataflowId, descriptor::ResolvedNode};
+use std::{sync::Arc, time::Duration};
+
+const VIOLATION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct ResourceLimits {
+    cpu_affinity: Vec<usize>,
+    memory_limit: Option<u64>,
+    nice: Option<i8>,
+    #[cfg(target_os = "linux")]
+    cgroup: Option<linux::Cgroup>,
+    #[cfg(windows)]
+    job: Option<windows::JobObject>,
+}
+
+impl ResourceLimits {
+    /// Prepares the limits of the given node, e.g. by creating its cgroup.
+    ///
+    /// Limits that are not supported on this platform are skipped with a warning.
+    pub fn new(dataflow_id: DataflowId, node: &ResolvedNode) -> Option<Self> {
+        if node.cpu_affinity.is_empty() && node.memory_limit.is_none() && node.nice.is_none() {
+            return None;
+        }
+        #[allow(unused_mut)]
+        let mut limits = Self {
+            cpu_affinity: node.cpu_affinity.clone(),
+            memory_limit: node.memory_limit,
+            nice: node.nice,
+            #[cfg(target_os = "linux")]
+            cgroup: None,
+            #[cfg(windows)]
+            job: None,
+        };
+        let node_id = &node.id;
+
+        #[cfg(target_os = "linux")]
+        if let Some(memory_limit) = limits.memory_limit {
+            let name = format!("dora-{dataflow_id}-{node_id}");
+            match linux::Cgroup::create(&name, memory_limit) {
+                Ok(cgroup) => limits.cgroup = Some(cgroup),
+                Err(err) => tracing::warn!(
+                    "failed to apply memory limit of node `{dataflow_id}/{node_id}`: {err:?}"
+                ),
+            }
+        }
+        #[cfg(windows)]
+        match windows::JobObject::new(&limits) {
+            Ok(job) => limits.job = Some(job),
+            Err(err) => tracing::warn!(
+                "failed to apply resource limits of node `{dataflow_id}/{node_id}`: {err:?}"
+            ),
+        }
+        #[cfg(not(any(target_os = "linux", windows)))]
+        {
+            if !limits.cpu_affinity.is_empty() {
+                tracing::warn!(
+                    "ignoring `cpu_affinity` of node `{dataflow_id}/{node_id}`, which is not \
+                    supported on this platform"
+                );
+            }
+            if limits.memory_limit.is_some() {
+                tracing::warn!(
+                    "ignoring `memory_limit` of node `{dataflow_id}/{node_id}`, which is not \
+                    supported on this platform"
+                );
+            }
+        }
+        Some(limits)
+    }
+
+    /// Sets up the command to apply the limits when the node process starts.
+    ///
+    /// On Windows, the process is created suspended so that it can't start
+    /// threads or child processes before it is assigned to the job object,
+    /// see [`Self::attach`].
+    pub fn apply_to_command(&self, command: &mut tokio::process::Command) {
+        #[cfg(unix)]
+        {
+            let cpu_affinity = self.cpu_affinity.clone();
+            let nice = self.nice;
+            #[cfg(target_os = "linux")]
+            let cgroup_procs = self.cgroup.as_ref().map(linux::Cgroup::procs_fd);
+
+            // SAFETY: the closure only uses async-signal-safe system calls and
+            // doesn't allocate
+            unsafe {
+                command.pre_exec(move || {
+                    #[cfg(target_os = "linux")]
+                    {
+                        if let Some(fd) = cgroup_procs {
+                            // writing `0` moves the writing process into the cgroup
+                            if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                                return Err(std::io::Error::last_os_error());
+                            }
+                        }
+                        if !cpu_affinity.is_empty() {
+                            let mut set: libc::cpu_set_t = std::mem::zeroed();
+                            for &cpu in &cpu_affinity {
+                                libc::CPU_SET(cpu, &mut set);
+                            }
+                            let size = std::mem::size_of::<libc::cpu_set_t>();
+                            if libc::sched_setaffinity(0, size, &set) != 0 {
+                                return Err(std::io::Error::last_os_error());
+                            }
+                        }
+                    }
+                    #[cfg(not(target_os = "linux"))]
+                    let _ = &cpu_affinity;
+                    if let Some(nice) = nice {
+                        if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice.into()) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+        #[cfg(windows)]
+        if self.job.is_some() {
+            command.creation_flags(windows_sys::Win32::System::Threading::CREATE_SUSPENDED);
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = command;
+    }
+
+    /// Applies the limits that can only be set on the running node process
+    /// and resumes the process on Windows.
+    ///
+    /// Only fails if the process could not be resumed.
+    pub fn attach(
+        &self,
+        dataflow_id: DataflowId,
+        node_id: &NodeId,
+        child: &tokio::process::Child,
+    ) -> eyre::Result<()> {
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            if let Err(err) = job.assign(child) {
+                tracing::warn!(
+                    "failed to apply resource limits of node `{dataflow_id}/{node_id}`: {err:?}"
+                );
+            }
+            windows::resume(child)?;
+        }
+        #[cfg(not(windows))]
+        let _ = (dataflow_id, node_id, child);
+        Ok(())
+    }
+
+    /// Describes how the node violated its limits, e.g. to explain why it
+    /// was killed.
+    pub fn violation(&self) -> Option<String> {
+        let memory_limit = self.memory_limit?;
+        let limit = dora_core::config::byte_size_to_string(memory_limit);
+        #[cfg(target_os = "linux")]
+        {
+            let events = self.cgroup.as_ref()?.memory_events()?;
+            if events.oom_kill > 0 {
+                Some(format!(
+                    "the node exceeded its memory limit of {limit} and was killed"
+                ))
+            } else if events.max > 0 {
+                Some(format!("the node reached its memory limit of {limit}"))
+            } else {
+                None
+            }
+        }
+        #[cfg(windows)]
+        {
+            let peak = self.job.as_ref()?.peak_memory()?;
+            (peak as u64 >= memory_limit)
+                .then(|| format!("the node reached its memory limit of {limit}"))
+        }
+        #[cfg(not(any(target_os = "linux", windows)))]
+        {
+            let _ = limit;
+            None
+        }
+    }
+
+    /// Periodically reports when the node reaches its memory limit.
+    pub async fn monitor(self: Arc<Self>, dataflow_id: DataflowId, node_id: NodeId) {
+        let Some(memory_limit) = self.memory_limit else {
+            return std::future::pending().await;
+        };
+        let limit = dora_core::config::byte_size_to_string(memory_limit);
+
+        let mut interval = tokio::time::interval(VIOLATION_CHECK_INTERVAL);
+        #[cfg(target_os = "linux")]
+        let mut last = self.cgroup.as_ref().and_then(linux::Cgroup::memory_events);
+        #[cfg(windows)]
+        let mut reported = false;
+        loop {
+            interval.tick().await;
+            #[cfg(target_os = "linux")]
+            {
+                let current = self.cgroup.as_ref().and_then(linux::Cgroup::memory_events);
+                if let (Some(last), Some(current)) = (&last, &current) {
+                    let reached = current.max.saturating_sub(last.max);
+                    let killed = current.oom_kill.saturating_sub(last.oom_kill);
+                    if killed > 0 {
+                        tracing::warn!(
+                            "a process of node `{dataflow_id}/{node_id}` was killed because \
+                            the node exceeded its memory limit of {limit}"
+                        );
+                    } else if reached > 0 {
+                        tracing::warn!(
+                            "node `{dataflow_id}/{node_id}` reached its memory limit of {limit} \
+                            {reached} times in the last {VIOLATION_CHECK_INTERVAL:?}"
+                        );
+                    }
+                }
+                last = current;
+            }
+            #[cfg(windows)]
+            {
+                let peak = self.job.as_ref().and_then(windows::JobObject::peak_memory);
+                if !reported && peak.is_some_and(|peak| peak as u64 >= memory_limit) {
+                    tracing::warn!(
+                        "node `{dataflow_id}/{node_id}` reached its memory limit of {limit}"
+                    );
+                    reported = true;
+                }
+            }
+            #[cfg(not(any(target_os = "linux", windows)))]
+            let _ = (&dataflow_id, &node_id, &limit);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use eyre::{Context, ContextCompat};
+    use std::{
+        fs::{self, File},
+        os::fd::{AsRawFd, RawFd},
+        path::{Path, PathBuf},
+    };
+
+    const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+    /// Leaf cgroup that the daemon moves itself and its child processes to,
+    /// because cgroups that contain processes can't delegate controllers to
+    /// child groups.
+    const DAEMON_CGROUP: &str = "dora-daemon";
+
+    pub struct Cgroup {
+        path: PathBuf,
+        /// The `cgroup.procs` file, which is written by the node process to
+        /// join the cgroup.
+        procs: File,
+    }
+
+    impl Cgroup {
+        /// Creates a cgroup with the given memory limit next to the cgroup of
+        /// the daemon.
+        pub fn create(name: &str, memory_limit: u64) -> eyre::Result<Self> {
+            let proc_cgroup = fs::read_to_string("/proc/self/cgroup")
+                .context("failed to read cgroup of the daemon")?;
+            let own = proc_cgroup
+                .lines()
+                .find_map(|line| line.strip_prefix("0::"))
+                .context("the daemon is not part of a cgroup v2 hierarchy")?;
+            let mut parent = Path::new(CGROUP_ROOT).join(own.trim_start_matches('/'));
+            if parent.ends_with(DAEMON_CGROUP) {
+                parent.pop();
+            }
+            enable_memory_controller(&parent)?;
+
+            let path = parent.join(name);
+            fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create cgroup `{}`", path.display()))?;
+            fs::write(path.join("memory.max"), memory_limit.to_string())
+                .context("failed to set `memory.max` of cgroup")?;
+            // don't let the node circumvent the limit by swapping
+            let _ = fs::write(path.join("memory.swap.max"), "0");
+            let procs = File::options()
+                .write(true)
+                .open(path.join("cgroup.procs"))
+                .context("failed to open `cgroup.procs` of cgroup")?;
+            Ok(Self { path, procs })
+        }
+
+        pub fn procs_fd(&self) -> RawFd {
+            self.procs.as_raw_fd()
+        }
+
+        pub fn memory_events(&self) -> Option<MemoryEvents> {
+            let events = fs::read_to_string(self.path.join("memory.events")).ok()?;
+            parse_memory_events(&events)
+        }
+    }
+
+    impl Drop for Cgroup {
+        fn drop(&mut self) {
+            // fails if processes of the node are still running
+            if let Err(err) = fs::remove_dir(&self.path) {
+                tracing::debug!("failed to remove cgroup `{}`: {err}", self.path.display());
+            }
+        }
+    }
+
+    fn enable_memory_controller(parent: &Path) -> eyre::Result<()> {
+        let subtree_control = parent.join("cgroup.subtree_control");
+        let enabled = fs::read_to_string(&subtree_control).unwrap_or_default();
+        if enabled.split_whitespace().any(|c| c == "memory") {
+            return Ok(());
+        }
+        if fs::write(&subtree_control, "+memory").is_ok() {
+            return Ok(());
+        }
+
+        // The cgroup contains processes, so the daemon has to move them to a
+        // leaf before it can enable the controller. Processes that were not
+        // started by the daemon are never moved, i.e. the cgroup must be
+        // delegated to the daemon.
+        let procs = fs::read_to_string(parent.join("cgroup.procs"))
+            .context("failed to read processes of the daemon's cgroup")?;
+        let daemon = std::process::id();
+        let pids: Vec<u32> = procs.lines().filter_map(|pid| pid.parse().ok()).collect();
+        let foreign: Vec<_> = pids
+            .iter()
+            .filter(|&&pid| !is_descendant_of(pid, daemon))
+            .map(|pid| pid.to_string())
+            .collect();
+        if !foreign.is_empty() {
+            eyre::bail!(
+                "the cgroup `{}` of the daemon contains processes that were not started by \
+                the daemon ({}), run the daemon in a cgroup that is delegated to it \
+                (e.g. through `Delegate=yes` in systemd)",
+                parent.display(),
+                foreign.join(", ")
+            );
+        }
+
+        let leaf = parent.join(DAEMON_CGROUP);
+        fs::create_dir_all(&leaf).with_context(|| {
+            format!(
+                "failed to create cgroup `{}`, the cgroup of the daemon must be \
+                delegated to it (e.g. through `Delegate=yes` in systemd)",
+                leaf.display()
+            )
+        })?;
+        for pid in pids {
+            // processes may have exited in the meantime
+            let _ = fs::write(leaf.join("cgroup.procs"), pid.to_string());
+        }
+        fs::write(&subtree_control, "+memory")
+            .context("failed to enable the memory controller for the daemon's cgroup")
+    }
+
+    /// Checks whether the given process is the given ancestor or one of its
+    /// (transitive) child processes.
+    fn is_descendant_of(mut pid: u32, ancestor: u32) -> bool {
+        loop {
+            if pid == ancestor {
+                return true;
+            }
+            let stat = fs::read_to_string(format!("/proc/{pid}/stat")).unwrap_or_default();
+            match parse_parent_pid(&stat) {
+                Some(parent) if parent > 1 => pid = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Parses the parent process ID from the contents of `/proc/<pid>/stat`.
+    fn parse_parent_pid(stat: &str) -> Option<u32> {
+        // the process name is in parentheses and may contain spaces
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct MemoryEvents {
+        /// Number of times the memory usage reached the limit.
+        pub max: u64,
+        /// Number of processes that were killed by the OOM killer.
+        pub oom_kill: u64,
+    }
+
+    fn parse_memory_events(events: &str) -> Option<MemoryEvents> {
+        let value = |key: &str| {
+            events.lines().find_map(|line| {
+                let (k, v) = line.split_once(' ')?;
+                (k == key).then(|| v.trim().parse().ok())?
+            })
+        };
+        Some(MemoryEvents {
+            max: value("max")?,
+            oom_kill: value("oom_kill")?,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn find_child_processes() {
+            assert_eq!(parse_parent_pid("42 (node (1) x) S 7 42 42 0"), Some(7));
+            assert_eq!(parse_parent_pid(""), None);
+
+            let daemon = std::process::id();
+            assert!(is_descendant_of(daemon, daemon));
+            let mut child = std::process::Command::new("sleep")
+                .arg("10")
+                .spawn()
+                .unwrap();
+            assert!(is_descendant_of(child.id(), daemon));
+            assert!(!is_descendant_of(1, daemon));
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+
+        #[test]
+        fn parse_events() {
+            let events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 0\n";
+            assert_eq!(
+                parse_memory_events(events),
+                Some(MemoryEvents {
+                    max: 12,
+                    oom_kill: 1
+                })
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::ResourceLimits;
+    use eyre::{bail, ContextCompat};
+    use std::{mem, ptr};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE},
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD,
+                THREADENTRY32,
+            },
+            JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                QueryInformationJobObject, SetInformationJobObject,
+                JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_AFFINITY,
+                JOB_OBJECT_LIMIT_PRIORITY_CLASS, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+            },
+            Threading::{
+                OpenThread, ResumeThread, ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS,
+                HIGH_PRIORITY_CLASS, IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+                THREAD_SUSPEND_RESUME,
+            },
+        },
+    };
+
+    pub struct JobObject(HANDLE);
+
+    impl JobObject {
+        pub fn new(limits: &ResourceLimits) -> eyre::Result<Self> {
+            // SAFETY: creates an unnamed job object with default security attributes
+            let handle = unsafe { CreateJobObjectW(ptr::null(), ptr::null()) };
+            if handle == 0 {
+                bail!(
+                    "failed to create job object: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            let job = Self(handle);
+
+            // SAFETY: the structure is plain old data
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+            let basic = &mut info.BasicLimitInformation;
+            if !limits.cpu_affinity.is_empty() {
+                let mut mask = 0usize;
+                for &cpu in &limits.cpu_affinity {
+                    let bit = 1usize
+                        .checked_shl(cpu as u32)
+                        .context("CPUs above 63 are not supported on Windows")?;
+                    mask |= bit;
+                }
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_AFFINITY;
+                basic.Affinity = mask;
+            }
+            if let Some(nice) = limits.nice {
+                basic.LimitFlags |= JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+                basic.PriorityClass = priority_class(nice);
+            }
+            if let Some(memory_limit) = limits.memory_limit {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                info.ProcessMemoryLimit = memory_limit.try_into().unwrap_or(usize::MAX);
+            }
+
+            // SAFETY: the information has the size that is passed
+            let result = unsafe {
+                SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                )
+            };
+            if result == 0 {
+                bail!(
+                    "failed to set job object limits: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            Ok(job)
+        }
+
+        pub fn assign(&self, child: &tokio::process::Child) -> eyre::Result<()> {
+            let process = child.raw_handle().context("node process already exited")?;
+            // SAFETY: both handles are valid while the child is not awaited
+            let result = unsafe { AssignProcessToJobObject(self.0, process as HANDLE) };
+            if result == 0 {
+                bail!(
+                    "failed to assign node process to job object: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            Ok(())
+        }
+
+        pub fn peak_memory(&self) -> Option<usize> {
+            // SAFETY: the structure is plain old data and has the size that is passed
+            unsafe {
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+                let result = QueryInformationJobObject(
+                    self.0,
+                    JobObjectExtendedLimitInformation,
+                    &mut info as *mut _ as *mut _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                    ptr::null_mut(),
+                );
+                (result != 0).then_some(info.PeakProcessMemoryUsed)
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            // SAFETY: the handle is owned by this struct
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// Resumes the threads of a process that was created suspended.
+    pub fn resume(child: &tokio::process::Child) -> eyre::Result<()> {
+        let pid = child.id().context("node process already exited")?;
+        // SAFETY: the snapshot and thread handles are closed before returning
+        // and the entry has the size that is passed
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                bail!(
+                    "failed to list threads of node process: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            let mut entry: THREADENTRY32 = mem::zeroed();
+            entry.dwSize = mem::size_of::<THREADENTRY32>() as u32;
+            let mut result = Ok(());
+            let mut found = Thread32First(snapshot, &mut entry) != 0;
+            while found {
+                if entry.th32OwnerProcessID == pid {
+                    let thread = OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID);
+                    if thread == 0 || ResumeThread(thread) == u32::MAX {
+                        result = Err(eyre::eyre!(
+                            "failed to resume node process: {}",
+                            std::io::Error::last_os_error()
+                        ));
+                    }
+                    if thread != 0 {
+                        CloseHandle(thread);
+                    }
+                }
+                found = Thread32Next(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+            result
+        }
+    }
+
+    /// Maps Unix nice values to the closest Windows priority class.
+    fn priority_class(nice: i8) -> u32 {
+        match nice {
+            i8::MIN..=-10 => HIGH_PRIORITY_CLASS,
+            -9..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+            0 => NORMAL_PRIORITY_CLASS,
+            1..=9 => BELOW_NORMAL_PRIORITY_CLASS,
+            10.. => IDLE_PRIORITY_CLASS,
+        }
+    }
+}
@@ -1,21 +1,26 @@
 use crate::{DaemonNodeEvent, Event};
 use dora_core::{
-    config::{DataId, LocalCommunicationConfig, NodeId},
+    config::{DataId, InputQueueConfig, LocalCommunicationConfig, NodeId},
     daemon_messages::{
         DaemonCommunication, DaemonReply, DaemonRequest, DataflowId, NodeDropEvent, NodeEvent,
         Timestamped,
     },
     message::uhlc,
+    topics::DORA_DAEMON_OBSERVER_PORT_DEFAULT,
 };
 use eyre::{eyre, Context};
 use futures::{future, task, Future};
 use shared_memory_server::{ShmemConf, ShmemServer};
 use std::{
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     mem,
     net::Ipv4Addr,
-    sync::Arc,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc,
+    },
     task::Poll,
+    time::{Duration, Instant},
 };
 use tokio::{
     net::TcpListener,
@@ -34,8 +39,9 @@ pub async fn spawn_listener_loop(
     node_id: &NodeId,
     daemon_tx: &mpsc::Sender<Timestamped<Event>>,
     config: LocalCommunicationConfig,
-    queue_sizes: BTreeMap<DataId, usize>,
+    queue_configs: BTreeMap<DataId, InputQueueConfig>,
     clock: Arc<uhlc::HLC>,
+    activity: Arc<NodeActivity>,
 ) -> eyre::Result<DaemonCommunication> {
     match config {
         LocalCommunicationConfig::Tcp => {
@@ -55,7 +61,7 @@ pub async fn spawn_listener_loop(
             let event_loop_node_id = format!("{dataflow_id}/{node_id}");
             let daemon_tx = daemon_tx.clone();
             tokio::spawn(async move {
-                tcp::listener_loop(socket, daemon_tx, queue_sizes, clock).await;
+                tcp::listener_loop(socket, daemon_tx, queue_configs, clock, activity).await;
                 tracing::debug!("event listener loop finished for `{event_loop_node_id}`");
             });
 
@@ -87,9 +93,16 @@ pub async fn spawn_listener_loop(
                 let server = unsafe { ShmemServer::new(daemon_control_region) }
                     .wrap_err("failed to create control server")?;
                 let daemon_tx = daemon_tx.clone();
-                let queue_sizes = queue_sizes.clone();
+                let queue_configs = queue_configs.clone();
                 let clock = clock.clone();
-                tokio::spawn(shmem::listener_loop(server, daemon_tx, queue_sizes, clock));
+                let activity = activity.clone();
+                tokio::spawn(shmem::listener_loop(
+                    server,
+                    daemon_tx,
+                    queue_configs,
+                    clock,
+                    activity,
+                ));
             }
 
             {
@@ -97,10 +110,11 @@ pub async fn spawn_listener_loop(
                     .wrap_err("failed to create events server")?;
                 let event_loop_node_id = format!("{dataflow_id}/{node_id}");
                 let daemon_tx = daemon_tx.clone();
-                let queue_sizes = queue_sizes.clone();
+                let queue_configs = queue_configs.clone();
                 let clock = clock.clone();
+                let activity = activity.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_configs, clock, activity).await;
                     tracing::debug!("event listener loop finished for `{event_loop_node_id}`");
                 });
             }
@@ -110,10 +124,11 @@ pub async fn spawn_listener_loop(
                     .wrap_err("failed to create drop server")?;
                 let drop_loop_node_id = format!("{dataflow_id}/{node_id}");
                 let daemon_tx = daemon_tx.clone();
-                let queue_sizes = queue_sizes.clone();
+                let queue_configs = queue_configs.clone();
                 let clock = clock.clone();
+                let activity = activity.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_configs, clock, activity).await;
                     tracing::debug!("drop listener loop finished for `{drop_loop_node_id}`");
                 });
             }
@@ -125,7 +140,7 @@ pub async fn spawn_listener_loop(
                 let daemon_tx = daemon_tx.clone();
                 let clock = clock.clone();
                 tokio::task::spawn(async move {
-                    shmem::listener_loop(server, daemon_tx, queue_sizes, clock).await;
+                    shmem::listener_loop(server, daemon_tx, queue_configs, clock, activity).await;
                     tracing::debug!(
                         "events close listener loop finished for `{drop_loop_node_id}`"
                     );
@@ -142,23 +157,118 @@ pub async fn spawn_listener_loop(
     }
 }
 
+/// Listens for read-only observer connections on the local observer port.
+pub async fn spawn_observer_listener(
+    daemon_tx: mpsc::Sender<Timestamped<Event>>,
+    clock: Arc<uhlc::HLC>,
+) -> eyre::Result<()> {
+    let localhost = Ipv4Addr::new(127, 0, 0, 1);
+    let socket = TcpListener::bind((localhost, DORA_DAEMON_OBSERVER_PORT_DEFAULT))
+        .await
+        .wrap_err("failed to create observer listener")?;
+    tokio::spawn(async move {
+        let activity = Arc::new(NodeActivity::default());
+        tcp::listener_loop(socket, daemon_tx, BTreeMap::new(), clock, activity).await;
+        tracing::debug!("observer listener loop finished");
+    });
+    Ok(())
+}
+
+/// Maximum number of queued messages per output for observer connections.
+const OBSERVER_QUEUE_SIZE: usize = 10;
+
+fn check_version(api_version: &str) -> Result<(), String> {
+    let daemon_version = env!("CARGO_PKG_VERSION");
+    if api_version == daemon_version {
+        Ok(())
+    } else {
+        Err(format!(
+            "version mismatch: node API v{api_version} is not compatible \
+            with daemon v{daemon_version}"
+        ))
+    }
+}
+
+/// Requests of a node on all its connections, which act as its heartbeats.
+///
+/// A node is responsive while it waits for its next event or while it keeps
+/// sending requests, e.g. outputs. Nodes that didn't send any request yet are
+/// considered responsive too, e.g. while they load their dependencies.
+#[derive(Debug, Default)]
+pub struct NodeActivity {
+    last_request: std::sync::Mutex<Option<Instant>>,
+    /// Number of connections on which the node waits for its next event.
+    waiting: AtomicUsize,
+}
+
+impl NodeActivity {
+    fn record_request(&self) {
+        *self.last_request.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Marks the node as waiting for an event until the returned guard is
+    /// dropped.
+    fn wait_for_event(&self) -> WaitingForEvent<'_> {
+        self.waiting.fetch_add(1, atomic::Ordering::SeqCst);
+        WaitingForEvent(self)
+    }
+
+    /// Returns how long the node has been unresponsive.
+    pub fn silence(&self) -> Duration {
+        if self.waiting.load(atomic::Ordering::SeqCst) > 0 {
+            return Duration::ZERO;
+        }
+        self.last_request
+            .lock()
+            .unwrap()
+            .map(|last| last.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Completes when the node has been unresponsive for the given time and
+    /// returns for how long it has been unresponsive.
+    pub async fn unresponsive(&self, timeout: Duration) -> Duration {
+        loop {
+            let silence = self.silence();
+            if silence >= timeout {
+                break silence;
+            }
+            tokio::time::sleep(timeout - silence).await;
+        }
+    }
+}
+
+struct WaitingForEvent<'a>(&'a NodeActivity);
+
+impl Drop for WaitingForEvent<'_> {
+    fn drop(&mut self) {
+        // the time spent waiting doesn't count as silence
+        self.0.record_request();
+        self.0.waiting.fetch_sub(1, atomic::Ordering::SeqCst);
+    }
+}
+
 struct Listener {
     dataflow_id: DataflowId,
     node_id: NodeId,
+    /// Observer connections are read-only and receive copies of the outputs.
+    observer: bool,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     subscribed_events: Option<UnboundedReceiver<Timestamped<NodeEvent>>>,
     subscribed_drop_events: Option<UnboundedReceiver<Timestamped<NodeDropEvent>>>,
     queue: VecDeque<Box<Option<Timestamped<NodeEvent>>>>,
-    queue_sizes: BTreeMap<DataId, usize>,
+    queue_configs: BTreeMap<DataId, InputQueueConfig>,
     clock: Arc<uhlc::HLC>,
+    activity: Arc<NodeActivity>,
 }
 
 impl Listener {
     pub(crate) async fn run<C: Connection>(
         mut connection: C,
         daemon_tx: mpsc::Sender<Timestamped<Event>>,
-        queue_sizes: BTreeMap<DataId, usize>,
+        queue_configs: BTreeMap<DataId, InputQueueConfig>,
         hlc: Arc<uhlc::HLC>,
+        activity: Arc<NodeActivity>,
     ) {
         // receive the first message
         let message = match connection
@@ -187,15 +297,7 @@ impl Listener {
                 node_id,
                 dora_version: node_api_version,
             } => {
-                let daemon_version = env!("CARGO_PKG_VERSION");
-                let result = if node_api_version == daemon_version {
-                    Ok(())
-                } else {
-                    Err(format!(
-                        "version mismatch: node API v{node_api_version} is not compatible \
-                        with daemon v{daemon_version}"
-                    ))
-                };
+                let result = check_version(&node_api_version);
                 let send_result = connection
                     .send_reply(DaemonReply::Result(result.clone()))
                     .await
@@ -205,12 +307,14 @@ impl Listener {
                         let mut listener = Listener {
                             dataflow_id,
                             node_id,
+                            observer: false,
                             daemon_tx,
                             subscribed_events: None,
                             subscribed_drop_events: None,
-                            queue_sizes,
+                            queue_configs,
                             queue: VecDeque::new(),
                             clock: hlc.clone(),
+                            activity,
                         };
                         match listener
                             .run_inner(connection)
@@ -231,6 +335,74 @@ impl Listener {
                     }
                 }
             }
+            DaemonRequest::RegisterObserver {
+                dataflow_id,
+                outputs,
+                dora_version,
+            } => {
+                let (event_sender, events) = mpsc::unbounded_channel();
+                let result = match check_version(&dora_version) {
+                    Ok(()) => {
+                        let (reply_sender, reply) = oneshot::channel();
+                        let event = Timestamped {
+                            inner: Event::Observer {
+                                dataflow_id,
+                                outputs,
+                                event_sender,
+                                reply_sender,
+                            },
+                            timestamp: hlc.new_timestamp(),
+                        };
+                        if daemon_tx.send(event).await.is_err() {
+                            Err("failed to send observer event to daemon".to_owned())
+                        } else {
+                            match reply.await {
+                                Ok(DaemonReply::Result(result)) => result,
+                                Ok(other) => {
+                                    Err(format!("unexpected reply from daemon: {other:?}"))
+                                }
+                                Err(_) => Err("failed to receive reply from daemon".to_owned()),
+                            }
+                        }
+                    }
+                    Err(err) => Err(err),
+                };
+                let send_result = connection
+                    .send_reply(DaemonReply::Result(result.clone()))
+                    .await
+                    .wrap_err("failed to send register reply");
+                match (result, send_result) {
+                    (Ok(()), Ok(())) => {
+                        let mut listener = Listener {
+                            dataflow_id,
+                            node_id: NodeId::from("observer".to_owned()),
+                            observer: true,
+                            daemon_tx,
+                            subscribed_events: Some(events),
+                            subscribed_drop_events: None,
+                            queue_configs,
+                            queue: VecDeque::new(),
+                            clock: hlc.clone(),
+                            activity,
+                        };
+                        if let Err(err) = listener
+                            .run_inner(connection)
+                            .await
+                            .wrap_err("observer listener failed")
+                        {
+                            tracing::error!("{err:?}");
+                        }
+                    }
+                    (Err(err), _) => {
+                        tracing::warn!("failed to register observer for {dataflow_id}: {err}");
+                    }
+                    (Ok(()), Err(err)) => {
+                        tracing::warn!(
+                            "failed send register reply to observer of {dataflow_id}: {err:?}"
+                        );
+                    }
+                }
+            }
             other => {
                 tracing::warn!("expected register message, got `{other:?}`");
                 let reply = DaemonReply::Result(Err("must send register message first".into()));
@@ -291,24 +463,68 @@ impl Listener {
         Ok(())
     }
 
+    /// Drops the oldest input events that exceed the queue size of their input,
+    /// and input events that are older than the `ttl` of their input.
     #[tracing::instrument(skip(self), fields(%self.node_id), level = "trace")]
     async fn drop_oldest_inputs(&mut self) -> Result<(), eyre::ErrReport> {
-        let mut queue_size_remaining = self.queue_sizes.clone();
+        let mut queue_size_remaining: BTreeMap<_, _> = self
+            .queue_configs
+            .iter()
+            .map(|(id, config)| (id.clone(), config.effective_queue_size()))
+            .collect();
+        let now = self.clock.new_timestamp().get_time().to_system_time();
         let mut dropped = 0;
+        let mut expired = 0;
+        let mut dropped_per_input = BTreeMap::new();
         let mut drop_tokens = Vec::new();
+        let mut compacted = 0;
+        let mut retained_keys: BTreeMap<DataId, BTreeSet<String>> = BTreeMap::new();
 
         // iterate over queued events, newest first
         for event in self.queue.iter_mut().rev() {
             let Some(Timestamped {
-                inner: NodeEvent::Input { id, data, .. },
+                inner: NodeEvent::Input { id, data, metadata },
                 ..
             }) = event.as_mut()
             else {
                 continue;
             };
+
+            // only the newest message per key is kept for compacted inputs
+            let key = self
+                .queue_configs
+                .get(id)
+                .and_then(|config| config.compact_by.as_ref())
+                .and_then(|compact_by| compact_by.key_of(&metadata.parameters));
+            if let Some(key) = key {
+                if !retained_keys.entry(id.clone()).or_default().insert(key) {
+                    compacted += 1;
+                    if let Some(drop_token) = data.as_ref().and_then(|d| d.drop_token()) {
+                        drop_tokens.push(drop_token);
+                    }
+                    *event.as_mut() = None;
+                    continue;
+                }
+            }
+
+            let ttl = self.queue_configs.get(id).and_then(|config| config.ttl);
+            if let Some(ttl) = ttl {
+                let sent = metadata.timestamp().get_time().to_system_time();
+                if now.duration_since(sent).unwrap_or_default() > ttl {
+                    expired += 1;
+                    *dropped_per_input.entry(id.clone()).or_insert(0) += 1;
+                    if let Some(drop_token) = data.as_ref().and_then(|d| d.drop_token()) {
+                        drop_tokens.push(drop_token);
+                    }
+                    *event.as_mut() = None;
+                    continue;
+                }
+            }
+
             match queue_size_remaining.get_mut(id) {
                 Some(0) => {
                     dropped += 1;
+                    *dropped_per_input.entry(id.clone()).or_insert(0) += 1;
                     if let Some(drop_token) = data.as_ref().and_then(|d| d.drop_token()) {
                         drop_tokens.push(drop_token);
                     }
@@ -317,6 +533,9 @@ impl Listener {
                 Some(size_remaining) => {
                     *size_remaining = size_remaining.saturating_sub(1);
                 }
+                None if self.observer => {
+                    queue_size_remaining.insert(id.clone(), OBSERVER_QUEUE_SIZE - 1);
+                }
                 None => {
                     tracing::warn!("no queue size known for received input `{id}`");
                 }
@@ -324,9 +543,18 @@ impl Listener {
         }
         self.report_drop_tokens(drop_tokens).await?;
 
+        if compacted > 0 {
+            tracing::trace!("dropped {compacted} inputs that were superseded by newer messages");
+        }
+        if expired > 0 {
+            tracing::debug!("dropped {expired} inputs that were older than their ttl");
+        }
         if dropped > 0 {
             tracing::debug!("dropped {dropped} inputs because event queue was too full");
         }
+        if dropped + expired > 0 && !self.observer {
+            self.report_dropped_inputs(dropped_per_input).await?;
+        }
         Ok(())
     }
 
@@ -340,8 +568,14 @@ impl Listener {
         if let Err(err) = self.clock.update_with_timestamp(&timestamp) {
             tracing::warn!("failed to update HLC: {err}");
         }
+        if self.observer {
+            return self
+                .handle_observer_message(message.inner, connection)
+                .await;
+        }
+        self.activity.record_request();
         match message.inner {
-            DaemonRequest::Register { .. } => {
+            DaemonRequest::Register { .. } | DaemonRequest::RegisterObserver { .. } => {
                 let reply = DaemonReply::Result(Err("unexpected register message".into()));
                 self.send_reply(reply, connection)
                     .await
@@ -356,6 +590,15 @@ impl Listener {
                 )
                 .await?
             }
+            DaemonRequest::Ready => {
+                let (reply_sender, reply) = oneshot::channel();
+                self.process_daemon_event(
+                    DaemonNodeEvent::Ready { reply_sender },
+                    Some(reply),
+                    connection,
+                )
+                .await?
+            }
             DaemonRequest::CloseOutputs(outputs) => {
                 let (reply_sender, reply) = oneshot::channel();
                 self.process_daemon_event(
@@ -380,6 +623,60 @@ impl Listener {
                 };
                 self.process_daemon_event(event, None, connection).await?;
             }
+            DaemonRequest::CallService {
+                node_id,
+                service,
+                request_id,
+                timeout,
+                metadata,
+                data,
+            } => {
+                let (reply_sender, reply) = oneshot::channel();
+                self.process_daemon_event(
+                    DaemonNodeEvent::CallService {
+                        node_id,
+                        service,
+                        request_id,
+                        timeout,
+                        metadata,
+                        data,
+                        reply_sender,
+                    },
+                    Some(reply),
+                    connection,
+                )
+                .await?
+            }
+            DaemonRequest::ServiceReply {
+                caller,
+                request_id,
+                result,
+            } => {
+                let event = DaemonNodeEvent::ServiceReply {
+                    caller,
+                    request_id,
+                    result,
+                };
+                self.process_daemon_event(event, None, connection).await?;
+            }
+            DaemonRequest::ReserveSharedMemory { len } => {
+                let (reply_sender, reply) = oneshot::channel();
+                self.process_daemon_event(
+                    DaemonNodeEvent::ReserveSharedMemory { len, reply_sender },
+                    Some(reply),
+                    connection,
+                )
+                .await?
+            }
+            DaemonRequest::ReleaseSharedMemory { len } => {
+                let (reply_sender, reply) = oneshot::channel();
+                self.process_daemon_event(
+                    DaemonNodeEvent::ReleaseSharedMemory { len, reply_sender },
+                    Some(reply),
+                    connection,
+                )
+                .await?
+            }
             DaemonRequest::Subscribe => {
                 let (tx, rx) = mpsc::unbounded_channel();
                 let (reply_sender, reply) = oneshot::channel();
@@ -410,6 +707,8 @@ impl Listener {
             }
             DaemonRequest::NextEvent { drop_tokens } => {
                 self.report_drop_tokens(drop_tokens).await?;
+                // queued events might have expired in the meantime
+                self.drop_oldest_inputs().await?;
 
                 // try to take the queued events first
                 let queued_events: Vec<_> = mem::take(&mut self.queue)
@@ -419,10 +718,13 @@ impl Listener {
                 let reply = if queued_events.is_empty() {
                     match self.subscribed_events.as_mut() {
                         // wait for next event
-                        Some(events) => match events.recv().await {
-                            Some(event) => DaemonReply::NextEvents(vec![event]),
-                            None => DaemonReply::NextEvents(vec![]),
-                        },
+                        Some(events) => {
+                            let _waiting = self.activity.wait_for_event();
+                            match events.recv().await {
+                                Some(event) => DaemonReply::NextEvents(vec![event]),
+                                None => DaemonReply::NextEvents(vec![]),
+                            }
+                        }
                         None => {
                             DaemonReply::Result(Err("Ignoring event request because no subscribe \
                                 message was sent yet"
@@ -475,6 +777,60 @@ impl Listener {
         Ok(())
     }
 
+    /// Handles requests of read-only observer connections.
+    ///
+    /// Observers can only receive events. All other requests are rejected.
+    async fn handle_observer_message<C: Connection>(
+        &mut self,
+        message: DaemonRequest,
+        connection: &mut C,
+    ) -> eyre::Result<()> {
+        match message {
+            DaemonRequest::NextEvent { drop_tokens: _ } => {
+                // observers receive copies of the data, so there are no drop tokens
+                let queued_events: Vec<_> = mem::take(&mut self.queue)
+                    .into_iter()
+                    .filter_map(|e| *e)
+                    .collect();
+                let reply = if queued_events.is_empty() {
+                    match self.subscribed_events.as_mut() {
+                        Some(events) => {
+                            DaemonReply::NextEvents(events.recv().await.into_iter().collect())
+                        }
+                        None => DaemonReply::NextEvents(vec![]),
+                    }
+                } else {
+                    DaemonReply::NextEvents(queued_events)
+                };
+                self.send_reply(reply, connection)
+                    .await
+                    .wrap_err("failed to send NextEvent reply to observer")?;
+            }
+            DaemonRequest::EventStreamDropped => {
+                self.subscribed_events = None;
+                self.send_reply(DaemonReply::Result(Ok(())), connection)
+                    .await
+                    .wrap_err("failed to send EventStreamDropped reply to observer")?;
+            }
+            other => {
+                let error = format!(
+                    "observer connections are read-only, `{}` requests are not allowed",
+                    request_name(&other)
+                );
+                tracing::warn!(
+                    "rejecting request of observer of {}: {error}",
+                    self.dataflow_id
+                );
+                if other.expects_tcp_reply() {
+                    self.send_reply(DaemonReply::Result(Err(error)), connection)
+                        .await
+                        .wrap_err("failed to send reply to observer")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn report_drop_tokens(
         &mut self,
         drop_tokens: Vec<dora_core::daemon_messages::DropToken>,
@@ -499,6 +855,23 @@ impl Listener {
         Ok(())
     }
 
+    /// Reports the number of dropped messages per input for the dataflow statistics.
+    async fn report_dropped_inputs(&mut self, counts: BTreeMap<DataId, u64>) -> eyre::Result<()> {
+        let event = Event::Node {
+            dataflow_id: self.dataflow_id,
+            node_id: self.node_id.clone(),
+            event: DaemonNodeEvent::InputsDropped { counts },
+        };
+        let event = Timestamped {
+            inner: event,
+            timestamp: self.clock.new_timestamp(),
+        };
+        self.daemon_tx
+            .send(event)
+            .await
+            .map_err(|_| eyre!("failed to report dropped inputs to daemon"))
+    }
+
     async fn process_daemon_event<C: Connection>(
         &mut self,
         event: DaemonNodeEvent,
@@ -566,3 +939,127 @@ trait Connection {
     async fn receive_message(&mut self) -> eyre::Result<Option<Timestamped<DaemonRequest>>>;
     async fn send_reply(&mut self, message: DaemonReply) -> eyre::Result<()>;
 }
+
+fn request_name(request: &DaemonRequest) -> &'static str {
+    match request {
+        DaemonRequest::Register { .. } => "Register",
+        DaemonRequest::RegisterObserver { .. } => "RegisterObserver",
+        DaemonRequest::Subscribe => "Subscribe",
+        DaemonRequest::SendMessage { .. } => "SendMessage",
+        DaemonRequest::CloseOutputs(_) => "CloseOutputs",
+        DaemonRequest::OutputsDone => "OutputsDone",
+        DaemonRequest::Ready => "Ready",
+        DaemonRequest::NextEvent { .. } => "NextEvent",
+        DaemonRequest::ReportDropTokens { .. } => "ReportDropTokens",
+        DaemonRequest::SubscribeDrop => "SubscribeDrop",
+        DaemonRequest::NextFinishedDropTokens => "NextFinishedDropTokens",
+        DaemonRequest::EventStreamDropped => "EventStreamDropped",
+        DaemonRequest::ReserveSharedMemory { .. } => "ReserveSharedMemory",
+        DaemonRequest::ReleaseSharedMemory { .. } => "ReleaseSharedMemory",
+        DaemonRequest::CallService { .. } => "CallService",
+        DaemonRequest::ServiceReply { .. } => "ServiceReply",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn detect_unresponsive_nodes() {
+        let activity = NodeActivity::default();
+        // nodes are responsive until they send their first request
+        assert_eq!(activity.silence(), Duration::ZERO);
+
+        activity.record_request();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(activity.silence() >= Duration::from_millis(20));
+
+        // waiting for events doesn't count as silence
+        {
+            let _waiting = activity.wait_for_event();
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(activity.silence(), Duration::ZERO);
+        }
+        assert!(activity.silence() < Duration::from_millis(20));
+
+        let timeout = Duration::from_millis(50);
+        assert!(activity.unresponsive(timeout).await >= timeout);
+    }
+
+    #[tokio::test]
+    async fn drop_expired_and_overflowing_inputs() {
+        use dora_core::{
+            config::QueuePolicy,
+            message::{ArrowTypeInfo, Metadata},
+        };
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let clock = Arc::new(uhlc::HLC::default());
+        let input = |age: Duration| {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() - age;
+            let timestamp = uhlc::Timestamp::new(uhlc::NTP64::from(time), *clock.get_id());
+            Box::new(Some(Timestamped {
+                inner: NodeEvent::Input {
+                    id: DataId::from("image".to_owned()),
+                    metadata: Metadata::new(timestamp, ArrowTypeInfo::empty()),
+                    data: None,
+                },
+                timestamp,
+            }))
+        };
+        let (daemon_tx, mut daemon_rx) = mpsc::channel(10);
+        let config = InputQueueConfig {
+            queue_size: 1,
+            policy: QueuePolicy::DropOldest,
+            ttl: Some(Duration::from_secs(1)),
+            order_by_key: false,
+            deadline: None,
+            batch: None,
+            compact_by: None,
+        };
+        let mut listener = Listener {
+            dataflow_id: DataflowId::nil(),
+            node_id: NodeId::from("viewer".to_owned()),
+            observer: false,
+            daemon_tx,
+            subscribed_events: None,
+            subscribed_drop_events: None,
+            queue: VecDeque::from([input(Duration::from_secs(10)), input(Duration::ZERO)]),
+            queue_configs: BTreeMap::from([(DataId::from("image".to_owned()), config.clone())]),
+            clock: clock.clone(),
+            activity: Arc::new(NodeActivity::default()),
+        };
+        let queued = |listener: &Listener| listener.queue.iter().filter(|e| e.is_some()).count();
+
+        // the expired message doesn't take up space in the queue
+        listener.drop_oldest_inputs().await.unwrap();
+        assert_eq!(queued(&listener), 1);
+        let Some(Timestamped {
+            inner:
+                Event::Node {
+                    event: DaemonNodeEvent::InputsDropped { counts },
+                    ..
+                },
+            ..
+        }) = daemon_rx.recv().await
+        else {
+            panic!("expected InputsDropped event");
+        };
+        assert_eq!(counts.values().sum::<u64>(), 1);
+
+        // deterministic dataflows keep all messages
+        listener.queue_configs.insert(
+            DataId::from("image".to_owned()),
+            InputQueueConfig {
+                policy: QueuePolicy::KeepAll,
+                ttl: None,
+                ..config
+            },
+        );
+        listener.queue.push_back(input(Duration::from_secs(10)));
+        listener.queue.push_back(input(Duration::ZERO));
+        listener.drop_oldest_inputs().await.unwrap();
+        assert_eq!(queued(&listener), 3);
+    }
+}
@@ -1,9 +1,9 @@
 use std::{collections::BTreeMap, sync::Arc};
 
-use super::{Connection, Listener};
+use super::{Connection, Listener, NodeActivity};
 use crate::Event;
 use dora_core::{
-    config::DataId,
+    config::{DataId, InputQueueConfig},
     daemon_messages::{DaemonReply, DaemonRequest, Timestamped},
     message::uhlc::HLC,
 };
@@ -11,12 +11,13 @@ use eyre::eyre;
 use shared_memory_server::ShmemServer;
 use tokio::sync::{mpsc, oneshot};
 
-#[tracing::instrument(skip(server, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(server, daemon_tx, clock, activity), level = "trace")]
 pub async fn listener_loop(
     mut server: ShmemServer<Timestamped<DaemonRequest>, DaemonReply>,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
-    queue_sizes: BTreeMap<DataId, usize>,
+    queue_configs: BTreeMap<DataId, InputQueueConfig>,
     clock: Arc<HLC>,
+    activity: Arc<NodeActivity>,
 ) {
     let (tx, rx) = flume::bounded(0);
     tokio::task::spawn_blocking(move || {
@@ -40,7 +41,7 @@ pub async fn listener_loop(
         }
     });
     let connection = ShmemConnection(tx);
-    Listener::run(connection, daemon_tx, queue_sizes, clock).await
+    Listener::run(connection, daemon_tx, queue_configs, clock, activity).await
 }
 
 enum Operation {
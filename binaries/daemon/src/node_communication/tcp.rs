@@ -1,12 +1,12 @@
 use std::{collections::BTreeMap, io::ErrorKind, sync::Arc};
 
-use super::{Connection, Listener};
+use super::{Connection, Listener, NodeActivity};
 use crate::{
     tcp_utils::{tcp_receive, tcp_send},
     Event,
 };
 use dora_core::{
-    config::DataId,
+    config::{DataId, InputQueueConfig},
     daemon_messages::{DaemonReply, DaemonRequest, Timestamped},
     message::uhlc::HLC,
 };
@@ -16,12 +16,13 @@ use tokio::{
     sync::mpsc,
 };
 
-#[tracing::instrument(skip(listener, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(listener, daemon_tx, clock, activity), level = "trace")]
 pub async fn listener_loop(
     listener: TcpListener,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
-    queue_sizes: BTreeMap<DataId, usize>,
+    queue_configs: BTreeMap<DataId, InputQueueConfig>,
     clock: Arc<HLC>,
+    activity: Arc<NodeActivity>,
 ) {
     loop {
         match listener
@@ -36,26 +37,35 @@ pub async fn listener_loop(
                 tokio::spawn(handle_connection_loop(
                     connection,
                     daemon_tx.clone(),
-                    queue_sizes.clone(),
+                    queue_configs.clone(),
                     clock.clone(),
+                    activity.clone(),
                 ));
             }
         }
     }
 }
 
-#[tracing::instrument(skip(connection, daemon_tx, clock), level = "trace")]
+#[tracing::instrument(skip(connection, daemon_tx, clock, activity), level = "trace")]
 async fn handle_connection_loop(
     connection: TcpStream,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
-    queue_sizes: BTreeMap<DataId, usize>,
+    queue_configs: BTreeMap<DataId, InputQueueConfig>,
     clock: Arc<HLC>,
+    activity: Arc<NodeActivity>,
 ) {
     if let Err(err) = connection.set_nodelay(true) {
         tracing::warn!("failed to set nodelay for connection: {err}");
     }
 
-    Listener::run(TcpConnection(connection), daemon_tx, queue_sizes, clock).await
+    Listener::run(
+        TcpConnection(connection),
+        daemon_tx,
+        queue_configs,
+        clock,
+        activity,
+    )
+    .await
 }
 
 struct TcpConnection(TcpStream);
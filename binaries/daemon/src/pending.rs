@@ -1,16 +1,22 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use dora_core::{
-    config::NodeId,
+    config::{DataId, NodeId},
     coordinator_messages::{CoordinatorRequest, DaemonEvent},
-    daemon_messages::{DaemonReply, DataflowId, Timestamped},
-    message::uhlc::{Timestamp, HLC},
+    daemon_messages::{DaemonReply, DataMessage, DataflowId, Timestamped},
+    message::{
+        uhlc::{Timestamp, HLC},
+        Metadata,
+    },
 };
 use eyre::{bail, Context};
 use tokio::{net::TcpStream, sync::oneshot};
 
 use crate::tcp_utils::tcp_send;
 
+/// Maximum number of outputs that are held back until the dataflow starts.
+const MAX_HELD_OUTPUTS: usize = 1024;
+
 pub struct PendingNodes {
     dataflow_id: DataflowId,
     machine_id: String,
@@ -19,6 +25,13 @@ pub struct PendingNodes {
     local_nodes: HashSet<NodeId>,
     /// Whether there are external nodes for this dataflow.
     external_nodes: bool,
+    /// Local nodes that signal their readiness explicitly.
+    ///
+    /// The subscribe requests of these nodes are answered right away, so that
+    /// they can finish their initialization and signal readiness afterwards.
+    ready_signal_nodes: HashSet<NodeId>,
+    /// Local nodes that subscribed already, but did not signal readiness yet.
+    unready_nodes: HashSet<NodeId>,
 
     /// Used to synchronize node starts.
     ///
@@ -32,6 +45,19 @@ pub struct PendingNodes {
 
     /// Whether the local init result was already reported to the coordinator.
     reported_init_to_coordinator: bool,
+
+    /// Whether the dataflow was started, i.e. whether outputs are delivered.
+    started: bool,
+    /// Outputs that nodes with a `ready_signal` sent before the dataflow started.
+    held_outputs: VecDeque<HeldOutput>,
+}
+
+/// An output that is held back until the dataflow starts.
+pub struct HeldOutput {
+    pub node_id: NodeId,
+    pub output_id: DataId,
+    pub metadata: Metadata,
+    pub data: Option<DataMessage>,
 }
 
 impl PendingNodes {
@@ -41,9 +67,13 @@ impl PendingNodes {
             machine_id,
             local_nodes: HashSet::new(),
             external_nodes: false,
+            ready_signal_nodes: HashSet::new(),
+            unready_nodes: HashSet::new(),
             waiting_subscribers: HashMap::new(),
             exited_before_subscribe: HashSet::new(),
             reported_init_to_coordinator: false,
+            started: false,
+            held_outputs: VecDeque::new(),
         }
     }
 
@@ -51,10 +81,48 @@ impl PendingNodes {
         self.local_nodes.insert(node_id);
     }
 
+    /// Holds back the start of the dataflow until the given node signals readiness.
+    pub fn expect_ready_signal(&mut self, node_id: NodeId) {
+        self.ready_signal_nodes.insert(node_id);
+    }
+
+    /// Whether the dataflow was started already.
+    ///
+    /// Only nodes with a `ready_signal` can send outputs before the start.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Holds back the given output until the dataflow starts, so that it isn't
+    /// lost for the nodes that are not subscribed yet.
+    ///
+    /// Returns the oldest held output if too many outputs are held already.
+    pub fn hold_output(&mut self, output: HeldOutput) -> Option<HeldOutput> {
+        self.held_outputs.push_back(output);
+        if self.held_outputs.len() > MAX_HELD_OUTPUTS {
+            self.held_outputs.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Marks the dataflow as started and returns the held outputs in the order
+    /// in which they were sent.
+    pub fn start(&mut self) -> VecDeque<HeldOutput> {
+        self.started = true;
+        std::mem::take(&mut self.held_outputs)
+    }
+
     pub fn set_external_nodes(&mut self, value: bool) {
         self.external_nodes = value;
     }
 
+    /// Whether the given local node did not subscribe to its events or did
+    /// not signal its readiness yet.
+    pub fn is_pending(&self, node_id: &NodeId) -> bool {
+        self.local_nodes.contains(node_id) || self.unready_nodes.contains(node_id)
+    }
+
     pub async fn handle_node_subscription(
         &mut self,
         node_id: NodeId,
@@ -62,6 +130,13 @@ impl PendingNodes {
         coordinator_connection: &mut Option<TcpStream>,
         clock: &HLC,
     ) -> eyre::Result<DataflowStatus> {
+        if self.ready_signal_nodes.contains(&node_id) && self.local_nodes.remove(&node_id) {
+            // the node is still initializing, so the dataflow can't be ready yet
+            let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+            self.unready_nodes.insert(node_id);
+            return Ok(DataflowStatus::Pending);
+        }
+
         self.waiting_subscribers
             .insert(node_id.clone(), reply_sender);
         self.local_nodes.remove(&node_id);
@@ -70,6 +145,21 @@ impl PendingNodes {
             .await
     }
 
+    pub async fn handle_node_ready(
+        &mut self,
+        node_id: &NodeId,
+        coordinator_connection: &mut Option<TcpStream>,
+        clock: &HLC,
+    ) -> eyre::Result<DataflowStatus> {
+        if self.unready_nodes.remove(node_id) {
+            tracing::debug!("node `{node_id}` signaled readiness");
+            self.update_dataflow_status(coordinator_connection, clock)
+                .await
+        } else {
+            Ok(DataflowStatus::Pending)
+        }
+    }
+
     pub async fn handle_node_stop(
         &mut self,
         node_id: &NodeId,
@@ -81,12 +171,17 @@ impl PendingNodes {
             self.exited_before_subscribe.insert(node_id.clone());
             self.update_dataflow_status(coordinator_connection, clock)
                 .await?;
+        } else if self.unready_nodes.remove(node_id) {
+            tracing::warn!("node `{node_id}` exited before signaling readiness");
+            self.exited_before_subscribe.insert(node_id.clone());
+            self.update_dataflow_status(coordinator_connection, clock)
+                .await?;
         }
         Ok(())
     }
 
     pub async fn handle_external_all_nodes_ready(&mut self, success: bool) -> eyre::Result<()> {
-        if !self.local_nodes.is_empty() {
+        if !self.local_nodes.is_empty() || !self.unready_nodes.is_empty() {
             bail!("received external `all_nodes_ready` event before local nodes were ready");
         }
         let external_error = if success {
@@ -104,7 +199,7 @@ impl PendingNodes {
         coordinator_connection: &mut Option<TcpStream>,
         clock: &HLC,
     ) -> eyre::Result<DataflowStatus> {
-        if self.local_nodes.is_empty() {
+        if self.local_nodes.is_empty() && self.unready_nodes.is_empty() {
             if self.external_nodes {
                 if !self.reported_init_to_coordinator {
                     self.report_nodes_ready(coordinator_connection, clock.new_timestamp())
@@ -185,3 +280,77 @@ pub enum DataflowStatus {
     AllNodesReady,
     Pending,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::ArrowTypeInfo;
+    use uuid::Uuid;
+
+    fn output(node_id: &NodeId, index: usize) -> HeldOutput {
+        HeldOutput {
+            node_id: node_id.clone(),
+            output_id: DataId::from(format!("out_{index}")),
+            metadata: Metadata::new(HLC::default().new_timestamp(), ArrowTypeInfo::empty()),
+            data: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn hold_outputs_of_ready_signal_nodes() {
+        let clock = HLC::default();
+        let model = NodeId::from("model".to_owned());
+        let camera = NodeId::from("camera".to_owned());
+        let mut pending = PendingNodes::new(Uuid::nil(), String::new());
+        pending.insert(model.clone());
+        pending.insert(camera.clone());
+        pending.expect_ready_signal(model.clone());
+
+        // the model is allowed to run its initialization, including sending outputs
+        let (reply_sender, mut model_reply) = oneshot::channel();
+        let status = pending
+            .handle_node_subscription(model.clone(), reply_sender, &mut None, &clock)
+            .await
+            .unwrap();
+        assert!(matches!(status, DataflowStatus::Pending));
+        assert!(model_reply.try_recv().is_ok());
+        assert!(pending.hold_output(output(&model, 0)).is_none());
+
+        let (reply_sender, mut camera_reply) = oneshot::channel();
+        let status = pending
+            .handle_node_subscription(camera.clone(), reply_sender, &mut None, &clock)
+            .await
+            .unwrap();
+        assert!(matches!(status, DataflowStatus::Pending));
+        assert!(camera_reply.try_recv().is_err());
+
+        let status = pending
+            .handle_node_ready(&model, &mut None, &clock)
+            .await
+            .unwrap();
+        assert!(matches!(status, DataflowStatus::AllNodesReady));
+        assert!(camera_reply.try_recv().is_ok());
+
+        assert!(!pending.is_started());
+        let held = pending.start();
+        assert!(pending.is_started());
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].output_id, DataId::from("out_0".to_owned()));
+    }
+
+    #[test]
+    fn drop_oldest_held_outputs() {
+        let node = NodeId::from("model".to_owned());
+        let mut pending = PendingNodes::new(Uuid::nil(), String::new());
+        for index in 0..MAX_HELD_OUTPUTS {
+            assert!(pending.hold_output(output(&node, index)).is_none());
+        }
+        let dropped = pending
+            .hold_output(output(&node, MAX_HELD_OUTPUTS))
+            .unwrap();
+        assert_eq!(dropped.output_id, DataId::from("out_0".to_owned()));
+        let held = pending.start();
+        assert_eq!(held.len(), MAX_HELD_OUTPUTS);
+        assert_eq!(held[0].output_id, DataId::from("out_1".to_owned()));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use dora_core::topics::{LinkReport, LinkStats};
+use uuid::Uuid;
+
+/// The rate report is stored next to the node logs.
+pub fn report_path(working_dir: &Path, dataflow_id: &Uuid) -> PathBuf {
+    let dataflow_dir = working_dir.join("out").join(dataflow_id.to_string());
+    dataflow_dir.join("rate_report.txt")
+}
+
+/// Number of seconds that the rate percentiles are computed over.
+const RATE_WINDOW: usize = 600;
+
+/// Records when the messages of a link were delivered.
+#[derive(Default)]
+pub struct MessageRate {
+    first: Option<Instant>,
+    last: Option<Instant>,
+    /// Second of the last message, counted from the first message.
+    last_second: usize,
+    /// Number of messages in each of the last [`RATE_WINDOW`] seconds, ending
+    /// with `last_second`.
+    per_second: VecDeque<u32>,
+}
+
+impl MessageRate {
+    pub fn record(&mut self, now: Instant) {
+        let first = *self.first.get_or_insert(now);
+        self.last = Some(now);
+        let second = now.saturating_duration_since(first).as_secs() as usize;
+        let new_seconds = if self.per_second.is_empty() {
+            1
+        } else {
+            second.saturating_sub(self.last_second)
+        };
+        if new_seconds > 0 {
+            let len = self.per_second.len() + new_seconds.min(RATE_WINDOW);
+            self.per_second.resize(len, 0);
+            let outdated = self.per_second.len().saturating_sub(RATE_WINDOW);
+            self.per_second.drain(..outdated);
+            self.last_second = second;
+        }
+        if let Some(count) = self.per_second.back_mut() {
+            *count += 1;
+        }
+    }
+
+    pub fn report(&self, stats: LinkStats) -> LinkReport {
+        let duration = match (self.first, self.last) {
+            (Some(first), Some(last)) => last - first,
+            _ => Default::default(),
+        };
+        let mean_rate = (stats.messages > 1 && !duration.is_zero())
+            .then(|| (stats.messages - 1) as f64 / duration.as_secs_f64());
+
+        // the second of the last message is not over yet
+        let mut full_seconds: Vec<_> = self.per_second.iter().copied().collect();
+        full_seconds.pop();
+        full_seconds.sort_unstable();
+        let percentile = |p: usize| {
+            let rank = (p * full_seconds.len()).div_ceil(100).max(1);
+            full_seconds.get(rank - 1).map(|&count| count as f64)
+        };
+
+        LinkReport {
+            duration,
+            mean_rate,
+            p5_rate: percentile(5),
+            median_rate: percentile(50),
+            p95_rate: percentile(95),
+            stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stats(messages: u64) -> LinkStats {
+        LinkStats {
+            source: "camera".to_owned().into(),
+            output: "image".to_owned().into(),
+            target: "plot".to_owned().into(),
+            input: "image".to_owned().into(),
+            messages,
+            bytes: 0,
+            dropped: 0,
+        }
+    }
+
+    #[test]
+    fn percentile_rates() {
+        let start = Instant::now();
+        let mut rate = MessageRate::default();
+        // 10 messages per second for 19 seconds, then one second with a
+        // single message
+        let mut timestamps: Vec<_> = (0..190).map(|i| Duration::from_millis(i * 100)).collect();
+        timestamps.push(Duration::from_millis(19_500));
+        timestamps.push(Duration::from_millis(20_000));
+        for timestamp in &timestamps {
+            rate.record(start + *timestamp);
+        }
+
+        let report = rate.report(stats(timestamps.len() as u64));
+        assert_eq!(report.duration, Duration::from_secs(20));
+        assert_eq!(report.mean_rate, Some(9.55));
+        assert_eq!(report.p5_rate, Some(1.));
+        assert_eq!(report.median_rate, Some(10.));
+        assert_eq!(report.p95_rate, Some(10.));
+    }
+
+    #[test]
+    fn rates_of_last_seconds() {
+        let start = Instant::now();
+        let mut rate = MessageRate::default();
+        // one message per second, followed by the busy last seconds
+        for second in 0..2 * RATE_WINDOW as u64 {
+            rate.record(start + Duration::from_secs(second));
+        }
+        let end = start + Duration::from_secs(2 * RATE_WINDOW as u64);
+        for i in 0..10 * RATE_WINDOW as u32 {
+            rate.record(end + Duration::from_millis(100) * i);
+        }
+        assert_eq!(rate.per_second.len(), RATE_WINDOW);
+
+        let report = rate.report(stats(12 * RATE_WINDOW as u64));
+        assert_eq!(report.duration, Duration::from_millis(1_799_900));
+        assert_eq!(report.p5_rate, Some(10.));
+    }
+
+    #[test]
+    fn short_links() {
+        let mut rate = MessageRate::default();
+        rate.record(Instant::now());
+        let report = rate.report(stats(1));
+        assert_eq!(report.mean_rate, None);
+        assert_eq!(report.median_rate, None);
+    }
+}
@@ -0,0 +1,235 @@
+use dora_core::descriptor::{KeyringSecret, SecretSource, VaultSecret};
+use eyre::{bail, eyre, Context, ContextCompat};
+use std::{collections::BTreeMap, path::Path};
+
+/// Resolves the given secret references, which map environment variable names
+/// to secret names.
+///
+/// Returns a map from environment variable name to secret value.
+pub async fn resolve_secrets(
+    references: &BTreeMap<String, String>,
+    secrets: &BTreeMap<String, SecretSource>,
+    working_dir: &Path,
+) -> eyre::Result<BTreeMap<String, String>> {
+    let mut resolved = BTreeMap::new();
+    for (env_var, secret_name) in references {
+        let source = secrets
+            .get(secret_name)
+            .ok_or_else(|| eyre!("secret `{secret_name}` is not defined"))?;
+        let value = resolve_secret(source, working_dir)
+            .await
+            .wrap_err_with(|| format!("failed to read secret `{secret_name}` from {source}"))?;
+        resolved.insert(env_var.clone(), value);
+    }
+    Ok(resolved)
+}
+
+async fn resolve_secret(source: &SecretSource, working_dir: &Path) -> eyre::Result<String> {
+    match source {
+        SecretSource::Env { env } => {
+            std::env::var(env).wrap_err_with(|| format!("env variable `{env}` is not set"))
+        }
+        SecretSource::File { file } => {
+            let content = tokio::fs::read_to_string(working_dir.join(file))
+                .await
+                .wrap_err("failed to read file")?;
+            Ok(strip_newline(&content).to_owned())
+        }
+        SecretSource::Vault { vault } => read_vault_secret(vault).await,
+        SecretSource::DotEnv { dotenv, key } => {
+            let content = tokio::fs::read_to_string(working_dir.join(dotenv))
+                .await
+                .wrap_err("failed to read env file")?;
+            dotenv_value(&content, key)
+                .with_context(|| format!("env file has no entry for `{key}`"))
+        }
+        SecretSource::Keyring { keyring } => read_keyring_secret(keyring).await,
+    }
+}
+
+fn strip_newline(value: &str) -> &str {
+    value
+        .strip_suffix('\n')
+        .map(|c| c.strip_suffix('\r').unwrap_or(c))
+        .unwrap_or(value)
+}
+
+/// Looks up the given key in the content of a `.env` file.
+///
+/// Supports comments, `export` prefixes, and single or double quoted values.
+/// If a key is defined multiple times, the last definition wins.
+fn dotenv_value(content: &str, key: &str) -> Option<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with('#') {
+                return None;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            (name.trim() == key).then(|| unquote(value.trim()))
+        })
+        .last()
+}
+
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner.to_owned();
+        }
+    }
+    // unquoted values may be followed by a comment
+    match value.split_once(" #") {
+        Some((value, _comment)) => value.trim_end().to_owned(),
+        None => value.to_owned(),
+    }
+}
+
+async fn read_keyring_secret(secret: &KeyringSecret) -> eyre::Result<String> {
+    let (program, args) = if cfg!(target_os = "macos") {
+        (
+            "security",
+            vec![
+                "find-generic-password",
+                "-s",
+                secret.service.as_str(),
+                "-a",
+                secret.user.as_str(),
+                "-w",
+            ],
+        )
+    } else if cfg!(target_os = "linux") {
+        (
+            "secret-tool",
+            vec![
+                "lookup",
+                "service",
+                secret.service.as_str(),
+                "user",
+                secret.user.as_str(),
+            ],
+        )
+    } else {
+        bail!("keyring secrets are not supported on this platform");
+    };
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .wrap_err_with(|| format!("failed to run `{program}`"))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!(
+            "`{program}` found no matching keyring entry: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let value = String::from_utf8(output.stdout).wrap_err("keyring secret is not valid UTF-8")?;
+    Ok(strip_newline(&value).to_owned())
+}
+
+async fn read_vault_secret(secret: &VaultSecret) -> eyre::Result<String> {
+    let address = std::env::var("VAULT_ADDR").wrap_err("`VAULT_ADDR` env variable is not set")?;
+    let token = std::env::var("VAULT_TOKEN").wrap_err("`VAULT_TOKEN` env variable is not set")?;
+    let url = format!(
+        "{}/v1/{}",
+        address.trim_end_matches('/'),
+        secret.path.trim_start_matches('/')
+    );
+
+    let response: serde_json::Value = reqwest::Client::new()
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .wrap_err_with(|| format!("failed to send request to `{url}`"))?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("failed to parse vault response")?;
+
+    // the KV v2 engine nests the secret data in another `data` object
+    let data = &response["data"];
+    let data = if data["data"].is_object() {
+        &data["data"]
+    } else {
+        data
+    };
+    data[&secret.field]
+        .as_str()
+        .map(ToOwned::to_owned)
+        .with_context(|| format!("vault secret has no string field `{}`", secret.field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dotenv_files() {
+        let content = "# api keys\n\
+            export OPENAI_KEY=\"sk-1 # not a comment\"\n\
+            TOKEN = 'abc'\n\
+            PLAIN=value # comment\n\
+            TOKEN=def\n";
+        assert_eq!(
+            dotenv_value(content, "OPENAI_KEY").as_deref(),
+            Some("sk-1 # not a comment")
+        );
+        assert_eq!(dotenv_value(content, "TOKEN").as_deref(), Some("def"));
+        assert_eq!(dotenv_value(content, "PLAIN").as_deref(), Some("value"));
+        assert_eq!(dotenv_value(content, "api"), None);
+    }
+
+    #[tokio::test]
+    async fn resolve_files_in_working_dir() {
+        let working_dir =
+            std::env::temp_dir().join(format!("dora-secrets-test-{}", std::process::id()));
+        std::fs::create_dir_all(&working_dir).unwrap();
+        std::fs::write(working_dir.join("token.txt"), "file-secret\r\n").unwrap();
+        std::fs::write(working_dir.join(".env"), "KEY=dotenv-secret\n").unwrap();
+
+        let secrets = BTreeMap::from([
+            (
+                "token".to_owned(),
+                SecretSource::File {
+                    file: "token.txt".into(),
+                },
+            ),
+            (
+                "key".to_owned(),
+                SecretSource::DotEnv {
+                    dotenv: ".env".into(),
+                    key: "KEY".into(),
+                },
+            ),
+        ]);
+        let references = BTreeMap::from([
+            ("TOKEN".to_owned(), "token".to_owned()),
+            ("API_KEY".to_owned(), "key".to_owned()),
+        ]);
+        let resolved = resolve_secrets(&references, &secrets, &working_dir)
+            .await
+            .unwrap();
+        assert_eq!(resolved["TOKEN"], "file-secret");
+        assert_eq!(resolved["API_KEY"], "dotenv-secret");
+
+        // relative paths must not depend on the current directory of the daemon
+        let other_dir = working_dir.join("other");
+        std::fs::create_dir_all(&other_dir).unwrap();
+        assert!(resolve_secrets(&references, &secrets, &other_dir)
+            .await
+            .is_err());
+
+        let undefined = BTreeMap::from([("X".to_owned(), "missing".to_owned())]);
+        let err = resolve_secrets(&undefined, &secrets, &working_dir)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`missing` is not defined"));
+
+        std::fs::remove_dir_all(&working_dir).unwrap();
+    }
+}
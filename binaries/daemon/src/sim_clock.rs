@@ -0,0 +1,203 @@
+//! Simulated time of dataflows with a `clock`.
+//!
+//! The clock source publishes the simulated time as output. The daemon stamps
+//! the messages of the local nodes with this time and fires the timers when it
+//! advances, instead of following the wall clock. This works faster than real
+//! time, e.g. for replaying recordings.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use aligned_vec::AVec;
+use dora_core::{
+    daemon_messages::DataMessage,
+    message::{
+        uhlc::{self, NTP64},
+        Metadata,
+    },
+};
+use dora_node_api::{
+    arrow::{array::UInt64Array, datatypes::DataType},
+    RawData,
+};
+use eyre::{bail, Context, ContextCompat};
+use shared_memory_server::ShmemConf;
+
+use crate::OutputId;
+
+pub struct SimClock {
+    /// The output that publishes the simulated time.
+    source: OutputId,
+    /// `None` until the first time arrives.
+    now: Option<Duration>,
+    /// The time of the next tick of each timer interval.
+    next_ticks: BTreeMap<Duration, Duration>,
+    /// The time of the last timestamp, which keeps timestamps unique.
+    last_timestamp: NTP64,
+}
+
+/// Timer ticks that are due after the simulated time advanced.
+pub struct Advance {
+    pub time: Duration,
+    /// Tick times and timer intervals, ordered by time.
+    pub ticks: Vec<(Duration, Duration)>,
+}
+
+impl SimClock {
+    pub fn new(source: OutputId, timer_intervals: impl IntoIterator<Item = Duration>) -> Self {
+        Self {
+            source,
+            now: None,
+            next_ticks: timer_intervals
+                .into_iter()
+                .filter(|interval| !interval.is_zero())
+                .map(|interval| (interval, Duration::ZERO))
+                .collect(),
+            last_timestamp: NTP64(0),
+        }
+    }
+
+    /// The latest simulated time, if any arrived yet.
+    pub fn now(&self) -> Option<Duration> {
+        self.now
+    }
+
+    /// Advances the simulated time if the given output is the clock source.
+    pub fn advance(
+        &mut self,
+        output_id: &OutputId,
+        metadata: &Metadata,
+        data: Option<&DataMessage>,
+    ) -> eyre::Result<Option<Advance>> {
+        if output_id != &self.source {
+            return Ok(None);
+        }
+        let time = read_time(metadata, data)?;
+        let first = match self.now {
+            Some(now) if time < now => {
+                bail!("simulated time went backwards from {now:?} to {time:?}")
+            }
+            Some(_) => false,
+            None => true,
+        };
+        self.now = Some(time);
+
+        let mut ticks = Vec::new();
+        for (&interval, next_tick) in &mut self.next_ticks {
+            // timers tick right away when they start, like the wall-clock timers
+            if first {
+                *next_tick = time;
+            }
+            while *next_tick <= time {
+                ticks.push((*next_tick, interval));
+                *next_tick += interval;
+            }
+        }
+        ticks.sort();
+        Ok(Some(Advance { time, ticks }))
+    }
+
+    /// Returns a unique timestamp at the given simulated time.
+    pub fn timestamp_at(&mut self, time: Duration, id: uhlc::ID) -> uhlc::Timestamp {
+        let time = NTP64::from(time).max(NTP64(self.last_timestamp.0 + 1));
+        self.last_timestamp = time;
+        uhlc::Timestamp::new(time, id)
+    }
+
+    /// Replaces the timestamp of the given message by the current simulated time.
+    pub fn stamp(&mut self, metadata: Metadata) -> Metadata {
+        let id = *metadata.timestamp().get_id();
+        let timestamp = self.timestamp_at(self.now.unwrap_or_default(), id);
+        Metadata::from_parameters(timestamp, metadata.type_info, metadata.parameters)
+    }
+}
+
+/// Reads the time in nanoseconds from the last value of a `UInt64` array.
+fn read_time(metadata: &Metadata, data: Option<&DataMessage>) -> eyre::Result<Duration> {
+    let raw = match data {
+        None => RawData::Empty,
+        Some(DataMessage::Vec(data)) => RawData::Vec(data.clone()),
+        Some(DataMessage::SharedMemory {
+            shared_memory_id,
+            len,
+            ..
+        }) => {
+            let memory = ShmemConf::new()
+                .os_id(shared_memory_id)
+                .open()
+                .wrap_err("failed to map shared memory of clock message")?;
+            RawData::Vec(AVec::from_slice(128, &unsafe { memory.as_slice() }[..*len]))
+        }
+    };
+    let array = raw.into_arrow_array(&metadata.type_info)?;
+    if array.data_type() != &DataType::UInt64 {
+        bail!(
+            "clock message must be a `UInt64` array, got {}",
+            array.data_type()
+        );
+    }
+    let values = UInt64Array::from(array);
+    let nanos = values
+        .values()
+        .last()
+        .context("clock message contains no time")?;
+    Ok(Duration::from_nanos(*nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::config::DataId;
+    use dora_node_api::{
+        arrow::array::Array,
+        arrow_utils::{copy_array_into_sample, required_data_size},
+    };
+
+    fn clock_message(nanos: u64) -> (Metadata, DataMessage) {
+        let array = UInt64Array::from(vec![nanos]).into_data();
+        let mut sample = vec![0; required_data_size(&array)];
+        let type_info = copy_array_into_sample(&mut sample, &array);
+        let data = AVec::from_slice(128, &sample);
+        let timestamp = uhlc::HLC::default().new_timestamp();
+        (Metadata::new(timestamp, type_info), DataMessage::Vec(data))
+    }
+
+    #[test]
+    fn timers_follow_simulated_time() {
+        let source = OutputId("sim".to_owned().into(), DataId::from("clock".to_owned()));
+        let mut clock = SimClock::new(source.clone(), [Duration::from_millis(100)]);
+
+        let (metadata, data) = clock_message(1_000_000_000);
+        let advance = clock
+            .advance(&source, &metadata, Some(&data))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            advance.ticks,
+            vec![(Duration::from_secs(1), Duration::from_millis(100))]
+        );
+
+        let (metadata, data) = clock_message(1_250_000_000);
+        let advance = clock
+            .advance(&source, &metadata, Some(&data))
+            .unwrap()
+            .unwrap();
+        assert_eq!(advance.time, Duration::from_millis(1250));
+        assert_eq!(
+            advance.ticks,
+            vec![
+                (Duration::from_millis(1100), Duration::from_millis(100)),
+                (Duration::from_millis(1200), Duration::from_millis(100))
+            ]
+        );
+
+        let stamped = clock.stamp(metadata);
+        assert_eq!(
+            stamped.timestamp().get_time().to_duration(),
+            Duration::from_millis(1250)
+        );
+        assert!(clock.stamp(stamped.clone()).timestamp() > stamped.timestamp());
+
+        let (metadata, data) = clock_message(0);
+        assert!(clock.advance(&source, &metadata, Some(&data)).is_err());
+    }
+}
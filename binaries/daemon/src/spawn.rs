@@ -1,11 +1,17 @@
 use crate::{
-    log, node_communication::spawn_listener_loop, node_inputs, runtime_node_inputs,
-    runtime_node_outputs, DoraEvent, Event, NodeExitStatus, OutputId,
+    limits::ResourceLimits,
+    log,
+    node_communication::{spawn_listener_loop, NodeActivity},
+    node_inputs, runtime_node_encoding, runtime_node_inputs, runtime_node_outputs,
+    runtime_node_services,
+    secrets::resolve_secrets,
+    DoraEvent, Event, NodeExitStatus, OutputId,
 };
 use aligned_vec::{AVec, ConstAlign};
 use dora_arrow_convert::IntoArrow;
 use dora_core::{
-    config::{DataId, NodeRunConfig},
+    cgroup::CpuThrottling,
+    config::{DataId, NodeId, NodeRunConfig},
     daemon_messages::{DataMessage, DataflowId, NodeConfig, RuntimeConfig, Timestamped},
     descriptor::{
         resolve_path, source_is_url, Descriptor, OperatorDefinition, OperatorSource, PythonSource,
@@ -21,14 +27,16 @@ use dora_node_api::{
     Metadata,
 };
 use eyre::{ContextCompat, WrapErr};
+use futures::FutureExt;
 use std::{
+    collections::BTreeMap,
     env::consts::EXE_EXTENSION,
     path::{Path, PathBuf},
     process::Stdio,
     sync::Arc,
+    time::Duration,
 };
 use tokio::{
-    fs::File,
     io::{AsyncBufReadExt, AsyncWriteExt},
     sync::{mpsc, oneshot},
 };
@@ -42,26 +50,37 @@ pub async fn spawn_node(
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     dataflow_descriptor: Descriptor,
     clock: Arc<HLC>,
-) -> eyre::Result<()> {
+) -> eyre::Result<Option<u32>> {
     let node_id = node.id.clone();
     tracing::debug!("Spawning node `{dataflow_id}/{node_id}`");
 
-    let queue_sizes = node_inputs(&node)
+    let queue_configs = node_inputs(&node)
         .into_iter()
-        .map(|(k, v)| (k, v.queue_size.unwrap_or(10)))
+        .map(|(k, v)| (k, v.queue_config(dataflow_descriptor.deterministic)))
         .collect();
+    let activity = Arc::new(NodeActivity::default());
+    let heartbeat_timeout = node
+        .restart
+        .as_ref()
+        .and_then(|policy| policy.heartbeat_timeout);
     let daemon_communication = spawn_listener_loop(
         &dataflow_id,
         &node_id,
         &daemon_tx,
         dataflow_descriptor.communication.local,
-        queue_sizes,
+        queue_configs,
         clock.clone(),
+        activity.clone(),
     )
     .await?;
     let send_stdout_to = node
         .send_stdout_as()
         .context("Could not resolve `send_stdout_as` configuration")?;
+    let secrets = resolve_secrets(&node.secrets, &dataflow_descriptor.secrets, working_dir)
+        .await
+        .wrap_err_with(|| format!("failed to resolve secrets of node `{node_id}`"))?;
+
+    let limits = ResourceLimits::new(dataflow_id, &node);
 
     let mut child = match node.kind {
         dora_core::descriptor::CoreNodeKind::Custom(n) => {
@@ -136,6 +155,10 @@ pub async fn spawn_node(
                     command.env(key, value.to_string());
                 }
             }
+            command.envs(&secrets);
+            if let Some(limits) = &limits {
+                limits.apply_to_command(&mut command);
+            }
             command
                 .stdin(Stdio::null())
                 .stdout(Stdio::piped())
@@ -178,8 +201,8 @@ pub async fn spawn_node(
                     .context("Runtime had no operators definition.")?;
 
                 if let OperatorSource::Python(PythonSource {
-                    source: _,
                     conda_env: Some(conda_env),
+                    ..
                 }) = &python_operator.config.source
                 {
                     let conda = which::which("conda").context(
@@ -216,6 +239,44 @@ pub async fn spawn_node(
             };
             command.current_dir(working_dir);
 
+            // the runtime passes the node-level and the operator-level variables
+            // to each operator explicitly, without exposing the variables of an
+            // operator to the other operators of the node
+            let node_env: BTreeMap<_, _> = node
+                .env
+                .iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .chain(secrets.clone())
+                .collect();
+            let mut operator_env = BTreeMap::new();
+            for operator in &n.operators {
+                let mut env = node_env.clone();
+                env.extend(
+                    operator
+                        .config
+                        .env
+                        .iter()
+                        .flatten()
+                        .map(|(key, value)| (key.clone(), value.to_string())),
+                );
+                env.extend(
+                    resolve_secrets(
+                        &operator.config.secrets,
+                        &dataflow_descriptor.secrets,
+                        working_dir,
+                    )
+                    .await
+                    .wrap_err_with(|| {
+                        format!(
+                            "failed to resolve secrets of operator `{node_id}/{}`",
+                            operator.id
+                        )
+                    })?,
+                );
+                operator_env.insert(operator.id.clone(), env);
+            }
+
             let runtime_config = RuntimeConfig {
                 node: NodeConfig {
                     dataflow_id,
@@ -223,11 +284,18 @@ pub async fn spawn_node(
                     run_config: NodeRunConfig {
                         inputs: runtime_node_inputs(&n),
                         outputs: runtime_node_outputs(&n),
+                        services: runtime_node_services(&n),
+                        encoding: runtime_node_encoding(&n),
+                        notify_unsubscribed: n
+                            .operators
+                            .iter()
+                            .any(|operator| operator.config.notify_unsubscribed),
                     },
                     daemon_communication,
                     dataflow_descriptor,
                 },
                 operators: n.operators,
+                operator_env,
             };
             command.env(
                 "DORA_RUNTIME_CONFIG",
@@ -241,6 +309,10 @@ pub async fn spawn_node(
                     command.env(key, value.to_string());
                 }
             }
+            command.envs(&secrets);
+            if let Some(limits) = &limits {
+                limits.apply_to_command(&mut command);
+            }
 
             command
                 .stdin(Stdio::null())
@@ -254,12 +326,35 @@ pub async fn spawn_node(
         }
     };
 
+    let pid = child.id();
+    let limits = limits.map(Arc::new);
+    let limits_monitor = match &limits {
+        Some(limits) => {
+            if let Err(err) = limits.attach(dataflow_id, &node_id, &child) {
+                let _ = child.start_kill();
+                return Err(err)
+                    .wrap_err_with(|| format!("failed to start node `{dataflow_id}/{node_id}`"));
+            }
+            let (task, handle) = limits
+                .clone()
+                .monitor(dataflow_id, node_id.clone())
+                .remote_handle();
+            tokio::spawn(task);
+            Some(handle)
+        }
+        None => None,
+    };
     let dataflow_dir = PathBuf::from(working_dir.join("out").join(dataflow_id.to_string()));
     if !dataflow_dir.exists() {
         std::fs::create_dir_all(&dataflow_dir).context("could not create dataflow_dir")?;
     }
     let (tx, mut rx) = mpsc::channel(10);
-    let mut file = File::create(log::log_path(working_dir, &dataflow_id, &node_id))
+    // restarted nodes append to their previous log, so that followers of the
+    // log can request only the new part of it
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log::log_path(working_dir, &dataflow_id, &node_id))
         .await
         .expect("Failed to create log file");
     let mut child_stdout =
@@ -349,15 +444,46 @@ pub async fn spawn_node(
         }
     });
 
+    // the monitoring task is cancelled when the handle is dropped
+    let throttling_monitor = child.id().map(|pid| {
+        let (task, handle) =
+            monitor_cpu_throttling(dataflow_id, node.id.clone(), pid).remote_handle();
+        tokio::spawn(task);
+        handle
+    });
+
     let node_id = node.id.clone();
     let (log_finish_tx, log_finish_rx) = oneshot::channel();
     tokio::spawn(async move {
-        let exit_status = NodeExitStatus::from(child.wait().await);
+        let mut missed_heartbeats = None;
+        let exit_status = match heartbeat_timeout {
+            Some(timeout) => tokio::select! {
+                status = child.wait() => status,
+                silence = activity.unresponsive(timeout) => {
+                    tracing::warn!(
+                        "node `{dataflow_id}/{node_id}` did not respond for {silence:?}, \
+                        killing it"
+                    );
+                    missed_heartbeats = Some(format!("node did not respond for {silence:?}"));
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            },
+            None => child.wait().await,
+        };
+        let exit_status = NodeExitStatus::from(exit_status);
+        drop(throttling_monitor);
+        drop(limits_monitor);
+        // the limits are released when the last reference is dropped
+        let resource_violation = limits
+            .and_then(|limits| limits.violation())
+            .or(missed_heartbeats);
         let _ = log_finish_rx.await;
         let event = DoraEvent::SpawnedNodeResult {
             dataflow_id,
             node_id,
             exit_status,
+            resource_violation,
         }
         .into();
         let event = Timestamped {
@@ -417,5 +543,39 @@ pub async fn spawn_node(
             .send(())
             .map_err(|_| error!("Could not inform that log file thread finished"));
     });
-    Ok(())
+    Ok(pid)
+}
+
+const CPU_THROTTLING_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically checks whether the node process is CPU-throttled by its cgroup.
+///
+/// Throttling is not visible in the CPU usage of the node, but it is a common cause
+/// of latency spikes when running in containers with CPU limits.
+async fn monitor_cpu_throttling(dataflow_id: DataflowId, node_id: NodeId, pid: u32) {
+    let Some(mut last) = CpuThrottling::read_for_pid(pid) else {
+        tracing::trace!("no cgroup CPU statistics available for node `{dataflow_id}/{node_id}`");
+        return;
+    };
+
+    let mut interval = tokio::time::interval(CPU_THROTTLING_CHECK_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let Some(current) = CpuThrottling::read_for_pid(pid) else {
+            break;
+        };
+        let throttled = current.since(&last);
+        if throttled.nr_throttled > 0 {
+            tracing::warn!(
+                "node `{dataflow_id}/{node_id}` was CPU-throttled by its cgroup in {} of the \
+                last {} scheduling periods (throttled for {:?} in total), which can cause \
+                latency spikes",
+                throttled.nr_throttled,
+                throttled.nr_periods,
+                throttled.throttled_time,
+            );
+        }
+        last = current;
+    }
 }
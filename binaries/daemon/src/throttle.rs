@@ -0,0 +1,147 @@
+//! Rate limiting of inputs, configured through the `max_rate` and `every_nth`
+//! input options.
+//!
+//! Throttled messages are dropped before they are queued for the receiver, so
+//! that fast producers can feed slow consumers without a throttling node in
+//! between. Inputs on other machines are throttled by the sending daemon, so
+//! that dropped messages don't use any network bandwidth.
+
+use dora_core::config::{Input, MaxRate};
+use std::{num::NonZeroU32, time::Duration};
+
+pub struct InputThrottle {
+    min_interval: Option<Duration>,
+    every_nth: Option<NonZeroU32>,
+    /// Number of messages that were sent to the input, including dropped ones.
+    received: u64,
+    /// Earliest time at which the next message is forwarded.
+    next: Option<Duration>,
+}
+
+impl InputThrottle {
+    pub fn new(input: &Input) -> Option<Self> {
+        if input.max_rate.is_none() && input.every_nth.is_none() {
+            return None;
+        }
+        Some(Self {
+            min_interval: input.max_rate.as_ref().map(MaxRate::min_interval),
+            every_nth: input.every_nth,
+            received: 0,
+            next: None,
+        })
+    }
+
+    /// Decides whether a message with the given timestamp is forwarded.
+    ///
+    /// The timestamp is the message time since the UNIX epoch.
+    pub fn forward(&mut self, timestamp: Duration) -> bool {
+        if let Some(n) = self.every_nth {
+            let skip = self.received % u64::from(n.get()) != 0;
+            self.received += 1;
+            if skip {
+                return false;
+            }
+        }
+        if let Some(interval) = self.min_interval {
+            match self.next {
+                Some(next) if timestamp < next => return false,
+                // keep the average rate when the messages don't arrive exactly
+                // at the interval, but don't allow bursts after pauses
+                Some(next) if next + interval > timestamp => self.next = Some(next + interval),
+                _ => self.next = Some(timestamp + interval),
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutputId, RunningDataflow};
+    use dora_core::{
+        config::{DataId, NodeId},
+        message::{uhlc, ArrowTypeInfo, Metadata, MetadataParameters},
+    };
+    use std::collections::BTreeSet;
+    use uuid::Uuid;
+
+    fn throttle(max_rate: Option<&str>, every_nth: Option<u32>) -> InputThrottle {
+        InputThrottle {
+            min_interval: max_rate.map(|rate| rate.parse::<MaxRate>().unwrap().min_interval()),
+            every_nth: every_nth.and_then(NonZeroU32::new),
+            received: 0,
+            next: None,
+        }
+    }
+
+    fn forwarded(throttle: &mut InputThrottle, timestamps: impl Iterator<Item = u64>) -> usize {
+        timestamps
+            .filter(|&ms| throttle.forward(Duration::from_millis(ms)))
+            .count()
+    }
+
+    #[test]
+    fn max_rate() {
+        // 60 fps for one second, limited to 10 Hz
+        let mut limited = throttle(Some("10hz"), None);
+        let frames = (0..60).map(|i| 1000 + i * 1000 / 60);
+        assert_eq!(forwarded(&mut limited, frames), 10);
+
+        // no burst after a pause
+        let mut limited = throttle(Some("10hz"), None);
+        assert_eq!(
+            forwarded(&mut limited, [0, 5000, 5016, 5033].into_iter()),
+            2
+        );
+    }
+
+    #[test]
+    fn every_nth() {
+        let mut sampled = throttle(None, Some(5));
+        assert_eq!(forwarded(&mut sampled, 0..23), 5);
+    }
+
+    #[test]
+    fn throttle_before_sending_to_other_machines() {
+        let mut dataflow = RunningDataflow::new(Uuid::nil(), String::new(), "robot".into());
+        let output = OutputId("camera".to_string().into(), "image".to_string().into());
+        let uploader = (
+            NodeId::from("uploader".to_string()),
+            DataId::from("image".to_string()),
+        );
+        let viewer = (
+            NodeId::from("viewer".to_string()),
+            DataId::from("image".to_string()),
+        );
+        let machines = dataflow
+            .open_external_mappings
+            .entry(output.clone())
+            .or_default();
+        machines.insert("cloud".into(), [uploader.clone()].into());
+        machines.insert("desktop".into(), [viewer.clone()].into());
+        dataflow
+            .input_throttles
+            .insert(uploader.clone(), throttle(None, Some(2)));
+
+        let clock = uhlc::HLC::default();
+        let message = || {
+            Metadata::from_parameters(
+                clock.new_timestamp(),
+                ArrowTypeInfo::empty(),
+                MetadataParameters::default(),
+            )
+        };
+        let throttled = dataflow.throttle_remote_inputs(&output, &message());
+        assert_eq!(throttled, Some(BTreeSet::new()));
+        let throttled = dataflow.throttle_remote_inputs(&output, &message());
+        assert_eq!(throttled, Some([uploader].into()));
+
+        // nothing is sent if every remote input skips the message
+        let machines = dataflow.open_external_mappings.get_mut(&output).unwrap();
+        machines.remove("desktop");
+        let throttled = dataflow.throttle_remote_inputs(&output, &message());
+        assert_eq!(throttled, Some(BTreeSet::new()));
+        assert_eq!(dataflow.throttle_remote_inputs(&output, &message()), None);
+    }
+}
@@ -0,0 +1,142 @@
+use dora_core::{
+    daemon_messages::{DataflowId, DataflowToken},
+    transport::{daemon_server_name, TlsConfig},
+};
+use eyre::{Context, ContextCompat};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{
+        server::AllowAnyAuthenticatedClient, Certificate, ClientConfig, PrivateKey, RootCertStore,
+        ServerConfig, ServerName,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+/// A connection to another daemon, either plain TCP or TLS.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T> Stream for T where T: AsyncRead + AsyncWrite + Unpin + Send {}
+
+/// TLS settings of the connections between daemons.
+///
+/// Both sides present the certificate of their machine and only accept peers
+/// with a certificate that is signed by the configured certificate authority.
+/// Clients also check that the server certificate belongs to the machine that
+/// they want to connect to.
+#[derive(Clone)]
+pub struct InterDaemonTls {
+    connector: TlsConnector,
+    acceptor: TlsAcceptor,
+    /// Secret that is shared by all daemons, see [`Self::dataflow_token`].
+    token_secret: Arc<Vec<u8>>,
+}
+
+impl InterDaemonTls {
+    pub fn load(config: &TlsConfig, machine_id: &str) -> eyre::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for ca_cert in read_certs(&config.ca_cert())? {
+            roots
+                .add(&ca_cert)
+                .wrap_err("failed to add CA certificate to root store")?;
+        }
+        let certs = read_certs(&config.daemon_cert())?;
+        let key = read_key(&config.daemon_key())?;
+        let token_secret = std::fs::read(config.token_secret()).wrap_err_with(|| {
+            format!(
+                "failed to read token secret `{}`",
+                config.token_secret().display()
+            )
+        })?;
+        tracing::debug!(
+            "using TLS certificate of `{}` for inter-daemon connections",
+            daemon_server_name(machine_id)
+        );
+
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots.clone()).boxed())
+            .with_single_cert(certs.clone(), key.clone())
+            .wrap_err("invalid daemon certificate")?;
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(certs, key)
+            .wrap_err("invalid daemon certificate")?;
+
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(client_config)),
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            token_secret: Arc::new(token_secret),
+        })
+    }
+
+    /// Derives the token of a dataflow from the token of the coordinator and
+    /// the secret of the daemons.
+    ///
+    /// The coordinator connection is not encrypted, so the token that the
+    /// coordinator sends is only used as input.
+    pub fn dataflow_token(
+        &self,
+        dataflow_id: DataflowId,
+        coordinator_token: &str,
+    ) -> DataflowToken {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.token_secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(dataflow_id.as_bytes());
+        mac.update(coordinator_token.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Connects to the daemon of the given machine.
+    pub async fn connect(
+        &self,
+        stream: TcpStream,
+        machine_id: &str,
+    ) -> eyre::Result<Box<dyn Stream>> {
+        let server_name = ServerName::try_from(daemon_server_name(machine_id).as_str())
+            .wrap_err("invalid daemon server name")?;
+        let stream = self
+            .connector
+            .connect(server_name, stream)
+            .await
+            .wrap_err("TLS handshake failed")?;
+        Ok(Box::new(stream))
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> eyre::Result<Box<dyn Stream>> {
+        let stream = self
+            .acceptor
+            .accept(stream)
+            .await
+            .wrap_err("TLS handshake failed")?;
+        Ok(Box::new(stream))
+    }
+}
+
+fn read_certs(path: &Path) -> eyre::Result<Vec<Certificate>> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("failed to open certificate `{}`", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .wrap_err_with(|| format!("failed to parse certificate `{}`", path.display()))?;
+    if certs.is_empty() {
+        eyre::bail!("no certificate found in `{}`", path.display());
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> eyre::Result<PrivateKey> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("failed to open private key `{}`", path.display()))?;
+    let keys = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(file))
+        .wrap_err_with(|| format!("failed to parse private key `{}`", path.display()))?;
+    keys.into_iter()
+        .next()
+        .map(PrivateKey)
+        .wrap_err_with(|| format!("no PKCS#8 private key found in `{}`", path.display()))
+}
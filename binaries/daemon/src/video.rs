@@ -0,0 +1,841 @@
+//! Video encoding of image outputs that are sent to other machines, configured
+//! through the `encoding` option of nodes and operators.
+//!
+//! The sending daemon encodes the images with a hardware encoder if one is
+//! available (NVENC, VAAPI, or VideoToolbox) and falls back to the software
+//! encoders of FFmpeg otherwise. The receiving daemon decodes them before they
+//! are delivered, so nodes only ever see raw images. The encoders are
+//! configured for low latency without B-frames, so that every image can be
+//! decoded as soon as it arrives.
+//!
+//! Every encoded output has its own encoder and decoder thread, so that the
+//! codecs don't block the event loop of the daemon. The threads report their
+//! results as [`VideoEvent`]s.
+//!
+//! Encoding requires the `video` feature. Without it, outputs are sent
+//! unencoded. Daemons without the feature refuse to spawn dataflows in which
+//! they would receive encoded outputs.
+
+use crate::{node_encoding, node_inputs, Event, InputId, OutputId};
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{
+    config::{InputMapping, VideoCodec},
+    daemon_messages::{DataflowId, Timestamped},
+    descriptor::ResolvedNode,
+    message::{uhlc::HLC, Metadata, MetadataValue},
+};
+use dora_node_api::arrow::datatypes::DataType;
+use eyre::{bail, eyre};
+use std::{
+    collections::BTreeSet,
+    sync::{mpsc as std_mpsc, Arc},
+};
+use tokio::sync::mpsc;
+
+/// Number of images that can wait for their encoding. Newer images are dropped
+/// when the encoder falls behind.
+const ENCODER_QUEUE_SIZE: usize = 2;
+
+/// Result of an encoder or decoder thread.
+#[derive(Debug)]
+pub enum VideoEvent {
+    /// An image was encoded, or is sent unencoded because encoding failed.
+    Encoded {
+        dataflow_id: DataflowId,
+        output_id: OutputId,
+        metadata: Metadata,
+        data: AVec<u8, ConstAlign<128>>,
+        encoding: Option<VideoCodec>,
+        throttled_inputs: BTreeSet<InputId>,
+    },
+    /// An image of a remote output was decoded.
+    Decoded {
+        dataflow_id: DataflowId,
+        output_id: OutputId,
+        metadata: Metadata,
+        data: AVec<u8, ConstAlign<128>>,
+        throttled_inputs: BTreeSet<InputId>,
+    },
+}
+
+/// Fails if a local node receives an encoded output from another machine, but
+/// this daemon was built without the `video` feature.
+pub fn check_decoding_support(nodes: &[ResolvedNode], machine_id: &str) -> eyre::Result<()> {
+    if cfg!(feature = "video") {
+        return Ok(());
+    }
+    let encoded: Vec<_> = nodes
+        .iter()
+        .filter(|node| node.deploy.machine != machine_id)
+        .flat_map(|node| {
+            node_encoding(node)
+                .into_iter()
+                .map(|(output_id, codec)| (node.id.clone(), output_id, codec))
+        })
+        .collect();
+    for node in nodes.iter().filter(|n| n.deploy.machine == machine_id) {
+        for (input_id, input) in node_inputs(node) {
+            let InputMapping::User(mapping) = input.mapping else {
+                continue;
+            };
+            if let Some((_, _, codec)) = encoded
+                .iter()
+                .find(|(source, output, _)| source == &mapping.source && output == &mapping.output)
+            {
+                bail!(
+                    "input `{}/{input_id}` receives {codec} encoded output `{}/{}` from another \
+                    machine, but the daemon of this machine was built without the `video` feature",
+                    node.id,
+                    mapping.source,
+                    mapping.output
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pixel layout and size of an image message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLayout {
+    width: u32,
+    height: u32,
+    bgr: bool,
+}
+
+impl ImageLayout {
+    /// Reads the image layout from the `width`, `height`, and `encoding`
+    /// metadata values of the given message.
+    pub fn from_metadata(metadata: &Metadata) -> eyre::Result<Self> {
+        let values = &metadata.parameters.values;
+        let dimension = |key: &str| match values.get(key) {
+            Some(MetadataValue::Integer(value)) => {
+                u32::try_from(*value).map_err(|_| eyre!("invalid `{key}` metadata value `{value}`"))
+            }
+            Some(other) => bail!("expected integer `{key}` metadata value, got `{other:?}`"),
+            None => bail!("image message has no `{key}` metadata value"),
+        };
+        let bgr = match values.get("encoding") {
+            None => false,
+            Some(MetadataValue::String(encoding)) => match encoding.to_lowercase().as_str() {
+                "rgb8" => false,
+                "bgr8" => true,
+                other => bail!("unsupported pixel encoding `{other}`, expected rgb8 or bgr8"),
+            },
+            Some(other) => bail!("expected string `encoding` metadata value, got `{other:?}`"),
+        };
+        let layout = Self {
+            width: dimension("width")?,
+            height: dimension("height")?,
+            bgr,
+        };
+
+        let type_info = &metadata.type_info;
+        let contiguous = type_info.data_type == DataType::UInt8
+            && type_info.offset == 0
+            && type_info.validity.is_none()
+            && matches!(type_info.buffer_offsets.as_slice(), [buffer] if buffer.offset == 0);
+        if !contiguous {
+            bail!("expected a contiguous `UInt8` image array");
+        }
+        if type_info.len != layout.size() {
+            bail!(
+                "image has {} bytes, expected {} bytes for {}x{} pixels",
+                type_info.len,
+                layout.size(),
+                layout.width,
+                layout.height
+            );
+        }
+        Ok(layout)
+    }
+
+    /// Size of the packed image in bytes.
+    fn size(&self) -> usize {
+        self.width as usize * self.height as usize * 3
+    }
+}
+
+/// An image or encoded packet of a video output, with its `throttled_inputs`.
+type VideoMessage = (Metadata, AVec<u8, ConstAlign<128>>, BTreeSet<InputId>);
+
+/// Encodes the images of an output on a separate thread.
+///
+/// The thread stops when the encoder is dropped.
+pub struct VideoEncoder {
+    images: std_mpsc::SyncSender<VideoMessage>,
+}
+
+impl VideoEncoder {
+    pub fn spawn(
+        dataflow_id: DataflowId,
+        output_id: OutputId,
+        codec: VideoCodec,
+        events_tx: mpsc::Sender<Timestamped<Event>>,
+        clock: Arc<HLC>,
+    ) -> eyre::Result<Self> {
+        let (images, images_rx) = std_mpsc::sync_channel::<VideoMessage>(ENCODER_QUEUE_SIZE);
+        std::thread::Builder::new()
+            .name(format!("encoder {}/{}", output_id.0, output_id.1))
+            .spawn(move || {
+                let mut encoder = Some(ImageEncoder::new(codec));
+                for (metadata, data, throttled_inputs) in images_rx {
+                    let (data, encoding) =
+                        match encoder.as_mut().map(|e| e.encode(&metadata, &data)) {
+                            Some(Ok(encoded)) => (encoded, Some(codec)),
+                            Some(Err(err)) => {
+                                tracing::warn!(
+                                    "failed to encode output `{}/{}`, sending it unencoded \
+                                    from now on: {err:?}",
+                                    output_id.0,
+                                    output_id.1
+                                );
+                                encoder = None;
+                                (data, None)
+                            }
+                            None => (data, None),
+                        };
+                    let event = Timestamped {
+                        inner: Event::Video(VideoEvent::Encoded {
+                            dataflow_id,
+                            output_id: output_id.clone(),
+                            metadata,
+                            data,
+                            encoding,
+                            throttled_inputs,
+                        }),
+                        timestamp: clock.new_timestamp(),
+                    };
+                    if events_tx.blocking_send(event).is_err() {
+                        break;
+                    }
+                }
+            })
+            .map_err(|err| eyre!("failed to spawn encoder thread: {err}"))?;
+        Ok(Self { images })
+    }
+
+    /// Queues the given image for encoding.
+    ///
+    /// Returns `false` if the image was dropped because the encoder is too slow.
+    pub fn submit(
+        &self,
+        metadata: Metadata,
+        data: AVec<u8, ConstAlign<128>>,
+        throttled_inputs: BTreeSet<InputId>,
+    ) -> bool {
+        self.images
+            .try_send((metadata, data, throttled_inputs))
+            .is_ok()
+    }
+}
+
+/// Decodes the images of a remote output on a separate thread.
+///
+/// Unlike the encoder, the decoder never drops packets, because the following
+/// packets of the stream could not be decoded without them.
+pub struct VideoDecoder {
+    packets: std_mpsc::Sender<VideoMessage>,
+}
+
+impl VideoDecoder {
+    pub fn spawn(
+        dataflow_id: DataflowId,
+        output_id: OutputId,
+        codec: VideoCodec,
+        events_tx: mpsc::Sender<Timestamped<Event>>,
+        clock: Arc<HLC>,
+    ) -> eyre::Result<Self> {
+        let (packets, packets_rx) = std_mpsc::channel::<VideoMessage>();
+        std::thread::Builder::new()
+            .name(format!("decoder {}/{}", output_id.0, output_id.1))
+            .spawn(move || {
+                let mut decoder = ImageDecoder::new(codec);
+                for (mut metadata, packet, throttled_inputs) in packets_rx {
+                    let data = match decoder.decode(&metadata, &packet) {
+                        Ok(data) => data,
+                        Err(err) => {
+                            tracing::warn!(
+                                "failed to decode {codec} output `{}/{}`: {err:?}",
+                                output_id.0,
+                                output_id.1
+                            );
+                            continue;
+                        }
+                    };
+                    // the checksum was calculated for the original image, which
+                    // differs from the decoded one because the codecs are lossy
+                    metadata.parameters.checksum = None;
+                    let event = Timestamped {
+                        inner: Event::Video(VideoEvent::Decoded {
+                            dataflow_id,
+                            output_id: output_id.clone(),
+                            metadata,
+                            data,
+                            throttled_inputs,
+                        }),
+                        timestamp: clock.new_timestamp(),
+                    };
+                    if events_tx.blocking_send(event).is_err() {
+                        break;
+                    }
+                }
+            })
+            .map_err(|err| eyre!("failed to spawn decoder thread: {err}"))?;
+        Ok(Self { packets })
+    }
+
+    /// Queues the given packet for decoding.
+    pub fn submit(
+        &self,
+        metadata: Metadata,
+        packet: AVec<u8, ConstAlign<128>>,
+        throttled_inputs: BTreeSet<InputId>,
+    ) {
+        let _ = self.packets.send((metadata, packet, throttled_inputs));
+    }
+}
+
+/// Encodes the images of an output.
+struct ImageEncoder {
+    codec: VideoCodec,
+    #[cfg(feature = "video")]
+    inner: Option<ffmpeg_codec::Encoder>,
+}
+
+impl ImageEncoder {
+    fn new(codec: VideoCodec) -> Self {
+        Self {
+            codec,
+            #[cfg(feature = "video")]
+            inner: None,
+        }
+    }
+
+    /// Encodes the given image into a single packet.
+    ///
+    /// The encoder is (re)opened when the image layout changes. The first
+    /// packet after that is a key frame.
+    fn encode(
+        &mut self,
+        metadata: &Metadata,
+        data: &[u8],
+    ) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+        let layout = ImageLayout::from_metadata(metadata)?;
+        #[cfg(feature = "video")]
+        {
+            let encoder = match &mut self.inner {
+                Some(encoder) if encoder.layout() == layout => encoder,
+                inner => inner.insert(ffmpeg_codec::Encoder::open(self.codec, layout)?),
+            };
+            let packet = encoder.encode(data.get(..layout.size()).unwrap_or(data))?;
+            Ok(AVec::from_slice(128, &packet))
+        }
+        #[cfg(not(feature = "video"))]
+        {
+            let _ = (layout, data);
+            bail!(
+                "cannot encode output as {}: dora-daemon was built without the `video` feature",
+                self.codec
+            )
+        }
+    }
+}
+
+/// Decodes the images of a remote output.
+struct ImageDecoder {
+    codec: VideoCodec,
+    #[cfg(feature = "video")]
+    inner: Option<ffmpeg_codec::Decoder>,
+}
+
+impl ImageDecoder {
+    fn new(codec: VideoCodec) -> Self {
+        Self {
+            codec,
+            #[cfg(feature = "video")]
+            inner: None,
+        }
+    }
+
+    /// Decodes the given packet into a packed image with the layout given in
+    /// the metadata.
+    fn decode(
+        &mut self,
+        metadata: &Metadata,
+        packet: &[u8],
+    ) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+        let layout = ImageLayout::from_metadata(metadata)?;
+        #[cfg(feature = "video")]
+        {
+            let decoder = match &mut self.inner {
+                Some(decoder) if decoder.layout() == layout => decoder,
+                inner => inner.insert(ffmpeg_codec::Decoder::open(self.codec, layout)?),
+            };
+            decoder.decode(packet)
+        }
+        #[cfg(not(feature = "video"))]
+        {
+            let _ = (layout, packet);
+            bail!(
+                "cannot decode {} output: dora-daemon was built without the `video` feature",
+                self.codec
+            )
+        }
+    }
+}
+
+#[cfg(feature = "video")]
+mod ffmpeg_codec {
+    use super::ImageLayout;
+    use aligned_vec::{AVec, ConstAlign};
+    use dora_core::config::VideoCodec;
+    use eyre::{bail, eyre, Context};
+    use ffmpeg_next::{
+        self as ffmpeg,
+        codec::{self, context::Context as CodecContext},
+        format::Pixel,
+        frame,
+        software::scaling,
+        Dictionary, Packet,
+    };
+    use std::ptr;
+
+    /// Frame rate that the encoders are configured for.
+    ///
+    /// Only affects the rate control, images are encoded as they arrive.
+    const FRAME_RATE: i32 = 30;
+    /// Interval of key frames, which allow decoding to recover from lost state.
+    const GOP_SIZE: u32 = 30;
+
+    fn hardware_encoders(codec: VideoCodec) -> &'static [&'static str] {
+        match codec {
+            VideoCodec::H264 => &["h264_nvenc", "h264_vaapi", "h264_videotoolbox"],
+            VideoCodec::H265 => &["hevc_nvenc", "hevc_vaapi", "hevc_videotoolbox"],
+        }
+    }
+
+    fn software_encoder(codec: VideoCodec) -> &'static str {
+        match codec {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+        }
+    }
+
+    fn codec_id(codec: VideoCodec) -> codec::Id {
+        match codec {
+            VideoCodec::H264 => codec::Id::H264,
+            VideoCodec::H265 => codec::Id::HEVC,
+        }
+    }
+
+    fn pixel(layout: ImageLayout) -> Pixel {
+        if layout.bgr {
+            Pixel::BGR24
+        } else {
+            Pixel::RGB24
+        }
+    }
+
+    pub struct Encoder {
+        layout: ImageLayout,
+        encoder: ffmpeg::encoder::Video,
+        scaler: scaling::Context,
+        /// Frames in GPU memory, for encoders that don't accept frames in host
+        /// memory (VAAPI).
+        hw_frames: Option<HwFrames>,
+        frame_index: i64,
+    }
+
+    impl Encoder {
+        /// Opens the first available hardware encoder for the given codec, or
+        /// the software encoder if there is none.
+        pub fn open(codec: VideoCodec, layout: ImageLayout) -> eyre::Result<Self> {
+            ffmpeg::init().context("failed to initialize FFmpeg")?;
+            for name in hardware_encoders(codec) {
+                match Self::open_with(name, layout) {
+                    Ok(encoder) => {
+                        tracing::info!(
+                            "encoding {}x{} images with `{name}`",
+                            layout.width,
+                            layout.height
+                        );
+                        return Ok(encoder);
+                    }
+                    Err(err) => tracing::debug!("encoder `{name}` is not available: {err:?}"),
+                }
+            }
+            let name = software_encoder(codec);
+            tracing::warn!("no hardware encoder available for {codec}, falling back to `{name}`");
+            Self::open_with(name, layout)
+        }
+
+        fn open_with(name: &str, layout: ImageLayout) -> eyre::Result<Self> {
+            let codec = ffmpeg::encoder::find_by_name(name)
+                .ok_or_else(|| eyre!("FFmpeg was built without `{name}`"))?;
+            let mut encoder = CodecContext::new_with_codec(codec).encoder().video()?;
+            encoder.set_width(layout.width);
+            encoder.set_height(layout.height);
+            encoder.set_time_base((1, FRAME_RATE));
+            encoder.set_frame_rate(Some((FRAME_RATE, 1)));
+            encoder.set_gop(GOP_SIZE);
+            encoder.set_max_b_frames(0);
+            // about 6 Mbit/s for 1080p
+            encoder.set_bit_rate(layout.width as usize * layout.height as usize * 3);
+
+            let hardware = !name.starts_with("lib");
+            let sw_format = if hardware {
+                Pixel::NV12
+            } else {
+                Pixel::YUV420P
+            };
+            let hw_frames = if name.ends_with("_vaapi") {
+                let hw_frames = HwFrames::vaapi(layout)?;
+                encoder.set_format(Pixel::VAAPI);
+                // SAFETY: the encoder takes its own reference to the frames context
+                unsafe {
+                    (*encoder.as_mut_ptr()).hw_frames_ctx =
+                        ffmpeg::ffi::av_buffer_ref(hw_frames.frames);
+                }
+                Some(hw_frames)
+            } else {
+                encoder.set_format(sw_format);
+                None
+            };
+
+            let mut options = Dictionary::new();
+            match name {
+                n if n.ends_with("_nvenc") => {
+                    options.set("preset", "p1");
+                    options.set("tune", "ull");
+                    options.set("zerolatency", "1");
+                    options.set("delay", "0");
+                }
+                n if n.ends_with("_vaapi") => options.set("async_depth", "1"),
+                n if n.ends_with("_videotoolbox") => options.set("realtime", "1"),
+                _ => {
+                    options.set("preset", "ultrafast");
+                    options.set("tune", "zerolatency");
+                }
+            }
+            let encoder = encoder
+                .open_with(options)
+                .wrap_err_with(|| format!("failed to open encoder `{name}`"))?;
+
+            let scaler = scaling::Context::get(
+                pixel(layout),
+                layout.width,
+                layout.height,
+                sw_format,
+                layout.width,
+                layout.height,
+                scaling::Flags::FAST_BILINEAR,
+            )?;
+            Ok(Self {
+                layout,
+                encoder,
+                scaler,
+                hw_frames,
+                frame_index: 0,
+            })
+        }
+
+        pub fn layout(&self) -> ImageLayout {
+            self.layout
+        }
+
+        pub fn encode(&mut self, image: &[u8]) -> eyre::Result<Vec<u8>> {
+            let input = packed_frame(self.layout, image);
+            let mut frame = frame::Video::empty();
+            self.scaler.run(&input, &mut frame)?;
+            if let Some(hw_frames) = &self.hw_frames {
+                frame = hw_frames.upload(&frame)?;
+            }
+            frame.set_pts(Some(self.frame_index));
+            self.frame_index += 1;
+
+            self.encoder.send_frame(&frame)?;
+            let mut encoded = Vec::new();
+            let mut packet = Packet::empty();
+            while self.encoder.receive_packet(&mut packet).is_ok() {
+                encoded.extend_from_slice(packet.data().unwrap_or_default());
+            }
+            if encoded.is_empty() {
+                bail!("encoder did not produce a packet for the image");
+            }
+            Ok(encoded)
+        }
+    }
+
+    pub struct Decoder {
+        layout: ImageLayout,
+        decoder: ffmpeg::decoder::Video,
+        scaler: Option<scaling::Context>,
+    }
+
+    impl Decoder {
+        pub fn open(codec: VideoCodec, layout: ImageLayout) -> eyre::Result<Self> {
+            ffmpeg::init().context("failed to initialize FFmpeg")?;
+            let decoder = ffmpeg::decoder::find(codec_id(codec))
+                .ok_or_else(|| eyre!("FFmpeg was built without a {codec} decoder"))?;
+            let mut context = CodecContext::new_with_codec(decoder);
+            // SAFETY: the context is not opened yet
+            unsafe {
+                (*context.as_mut_ptr()).flags |= ffmpeg::ffi::AV_CODEC_FLAG_LOW_DELAY as i32;
+            }
+            let decoder = context.decoder().video()?;
+            Ok(Self {
+                layout,
+                decoder,
+                scaler: None,
+            })
+        }
+
+        pub fn layout(&self) -> ImageLayout {
+            self.layout
+        }
+
+        pub fn decode(&mut self, packet: &[u8]) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+            self.decoder.send_packet(&Packet::copy(packet))?;
+            let mut decoded = frame::Video::empty();
+            let mut received = false;
+            while self.decoder.receive_frame(&mut decoded).is_ok() {
+                received = true;
+            }
+            if !received {
+                bail!("packet did not contain a complete image");
+            }
+
+            let layout = self.layout;
+            let scaler = match &mut self.scaler {
+                Some(scaler) if scaler.input().format == decoded.format() => scaler,
+                scaler => scaler.insert(scaling::Context::get(
+                    decoded.format(),
+                    decoded.width(),
+                    decoded.height(),
+                    pixel(layout),
+                    layout.width,
+                    layout.height,
+                    scaling::Flags::FAST_BILINEAR,
+                )?),
+            };
+            let mut image = frame::Video::empty();
+            scaler.run(&decoded, &mut image)?;
+
+            // copy the rows without the padding of the frame
+            let row = layout.width as usize * 3;
+            let stride = image.stride(0);
+            let mut data = AVec::with_capacity(128, layout.size());
+            for y in 0..layout.height as usize {
+                data.extend_from_slice(&image.data(0)[y * stride..][..row]);
+            }
+            Ok(data)
+        }
+    }
+
+    fn packed_frame(layout: ImageLayout, image: &[u8]) -> frame::Video {
+        let mut frame = frame::Video::new(pixel(layout), layout.width, layout.height);
+        let row = layout.width as usize * 3;
+        let stride = frame.stride(0);
+        for (y, pixels) in image.chunks_exact(row).enumerate() {
+            frame.data_mut(0)[y * stride..][..row].copy_from_slice(pixels);
+        }
+        frame
+    }
+
+    /// A VAAPI frames context that frames are uploaded to before encoding.
+    struct HwFrames {
+        frames: *mut ffmpeg::ffi::AVBufferRef,
+    }
+
+    // SAFETY: the frames context is reference counted and thread-safe
+    unsafe impl Send for HwFrames {}
+
+    impl HwFrames {
+        fn vaapi(layout: ImageLayout) -> eyre::Result<Self> {
+            use ffmpeg::ffi::*;
+            // SAFETY: the created device and frames contexts are checked for errors
+            // and released through their reference counts
+            unsafe {
+                let mut device = ptr::null_mut();
+                let result = av_hwdevice_ctx_create(
+                    &mut device,
+                    AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+                    ptr::null(),
+                    ptr::null_mut(),
+                    0,
+                );
+                if result < 0 {
+                    bail!(
+                        "failed to open VAAPI device: {}",
+                        ffmpeg::Error::from(result)
+                    );
+                }
+                let frames = av_hwframe_ctx_alloc(device);
+                av_buffer_unref(&mut device);
+                if frames.is_null() {
+                    bail!("failed to allocate VAAPI frames context");
+                }
+                let hw_frames = Self { frames };
+                let context = (*frames).data as *mut AVHWFramesContext;
+                (*context).format = AVPixelFormat::AV_PIX_FMT_VAAPI;
+                (*context).sw_format = AVPixelFormat::AV_PIX_FMT_NV12;
+                (*context).width = layout.width as i32;
+                (*context).height = layout.height as i32;
+                (*context).initial_pool_size = 4;
+                let result = av_hwframe_ctx_init(frames);
+                if result < 0 {
+                    bail!(
+                        "failed to initialize VAAPI frames context: {}",
+                        ffmpeg::Error::from(result)
+                    );
+                }
+                Ok(hw_frames)
+            }
+        }
+
+        fn upload(&self, frame: &frame::Video) -> eyre::Result<frame::Video> {
+            use ffmpeg::ffi::*;
+            let mut hw_frame = frame::Video::empty();
+            // SAFETY: the frame is allocated from the frames context before the
+            // data is transferred to it
+            unsafe {
+                let result = av_hwframe_get_buffer(self.frames, hw_frame.as_mut_ptr(), 0);
+                if result < 0 {
+                    bail!(
+                        "failed to allocate VAAPI frame: {}",
+                        ffmpeg::Error::from(result)
+                    );
+                }
+                let result = av_hwframe_transfer_data(hw_frame.as_mut_ptr(), frame.as_ptr(), 0);
+                if result < 0 {
+                    bail!("failed to upload frame: {}", ffmpeg::Error::from(result));
+                }
+            }
+            Ok(hw_frame)
+        }
+    }
+
+    impl Drop for HwFrames {
+        fn drop(&mut self) {
+            // SAFETY: releases the reference that was created in `vaapi`
+            unsafe { ffmpeg::ffi::av_buffer_unref(&mut self.frames) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::{uhlc, MetadataParameters};
+    use dora_node_api::{
+        arrow::array::{Array, UInt8Array},
+        arrow_utils::{copy_array_into_sample, required_data_size},
+    };
+
+    fn image_metadata(len: usize, values: &[(&str, MetadataValue)]) -> Metadata {
+        let array = UInt8Array::from(vec![0; len]).into_data();
+        let mut sample = vec![0; required_data_size(&array)];
+        let type_info = copy_array_into_sample(&mut sample, &array);
+        let parameters = MetadataParameters {
+            values: values
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        Metadata::from_parameters(uhlc::HLC::default().new_timestamp(), type_info, parameters)
+    }
+
+    #[test]
+    fn image_layout() {
+        let size = [
+            ("width", MetadataValue::Integer(4)),
+            ("height", MetadataValue::Integer(2)),
+        ];
+        let layout = ImageLayout::from_metadata(&image_metadata(24, &size)).unwrap();
+        assert_eq!(layout.size(), 24);
+        assert!(!layout.bgr);
+
+        let bgr = [
+            size[0].clone(),
+            size[1].clone(),
+            ("encoding", MetadataValue::String("bgr8".into())),
+        ];
+        assert!(
+            ImageLayout::from_metadata(&image_metadata(24, &bgr))
+                .unwrap()
+                .bgr
+        );
+
+        assert!(ImageLayout::from_metadata(&image_metadata(23, &size)).is_err());
+        assert!(ImageLayout::from_metadata(&image_metadata(24, &size[..1])).is_err());
+    }
+
+    #[test]
+    fn encoder_falls_back_to_unencoded_data() {
+        let (events_tx, mut events_rx) = mpsc::channel(1);
+        let output_id = OutputId("camera".to_string().into(), "image".to_string().into());
+        let encoder = VideoEncoder::spawn(
+            DataflowId::nil(),
+            output_id.clone(),
+            VideoCodec::H264,
+            events_tx,
+            Arc::new(HLC::default()),
+        )
+        .unwrap();
+
+        // no `width` and `height` metadata -> the image can't be encoded
+        let data = AVec::from_slice(128, &[1, 2, 3]);
+        assert!(encoder.submit(image_metadata(3, &[]), data, BTreeSet::new()));
+        let event = events_rx.blocking_recv().unwrap().inner;
+        let Event::Video(VideoEvent::Encoded {
+            output_id: encoded_id,
+            data,
+            encoding,
+            ..
+        }) = event
+        else {
+            panic!("expected encoded event, got {event:?}");
+        };
+        assert_eq!(encoded_id, output_id);
+        assert_eq!(&data[..], &[1, 2, 3]);
+        assert_eq!(encoding, None);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn encode_decode() {
+        let (width, height) = (64, 48);
+        let metadata = image_metadata(
+            width * height * 3,
+            &[
+                ("width", MetadataValue::Integer(width as i64)),
+                ("height", MetadataValue::Integer(height as i64)),
+            ],
+        );
+        let mut encoder = ImageEncoder::new(VideoCodec::H264);
+        let mut decoder = ImageDecoder::new(VideoCodec::H264);
+        for frame in 0..5 {
+            // a horizontal gradient that moves to the right
+            let image: Vec<u8> = (0..height)
+                .flat_map(|_| (0..width).flat_map(move |x| [((x * 4 + frame) % 256) as u8; 3]))
+                .collect();
+            let packet = encoder.encode(&metadata, &image).unwrap();
+            assert!(packet.len() < image.len());
+
+            let decoded = decoder.decode(&metadata, &packet).unwrap();
+            assert_eq!(decoded.len(), image.len());
+            let error: u64 = image
+                .iter()
+                .zip(decoded.iter())
+                .map(|(a, b)| a.abs_diff(*b) as u64)
+                .sum();
+            // the codec is lossy, but the image should be close to the original
+            assert!(
+                error / (image.len() as u64) < 8,
+                "frame {frame} differs too much"
+            );
+        }
+    }
+}
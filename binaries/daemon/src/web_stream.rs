@@ -0,0 +1,313 @@
+//! Streams dataflow outputs to browser dashboards as server-sent events.
+//!
+//! Clients connect to `http://127.0.0.1:<port>/<dataflow-id>` (e.g. through an
+//! `EventSource`) and receive one `message` event per output. The optional
+//! `outputs` query parameter limits the stream to a comma-separated list of
+//! `node/output` pairs. With `encoding=arrow`, the data is sent as a
+//! base64-encoded Arrow IPC stream that can be decoded with Arrow JS instead
+//! of as JSON values.
+//!
+//! Slow clients only receive the latest pending message of each output.
+
+use crate::Event;
+use dora_core::{
+    config::{DataId, NodeId},
+    daemon_messages::{DaemonReply, DataMessage, DataflowId, NodeEvent, Timestamped},
+    http::{read_request, respond, write_head, Request},
+    message::{uhlc, Metadata},
+    topics::DORA_DAEMON_STREAM_PORT_DEFAULT,
+};
+use dora_node_api::{
+    arrow::{
+        array::{make_array, ArrayRef},
+        datatypes::{Field, Schema},
+        ipc::writer::StreamWriter,
+        json::ArrayWriter,
+        record_batch::RecordBatch,
+    },
+    RawData,
+};
+use eyre::{bail, eyre, Context};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::Ipv4Addr,
+    sync::Arc,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+
+/// Listens for server-sent event connections on the local stream port.
+pub async fn spawn_stream_listener(
+    daemon_tx: mpsc::Sender<Timestamped<Event>>,
+    clock: Arc<uhlc::HLC>,
+) -> eyre::Result<()> {
+    let localhost = Ipv4Addr::new(127, 0, 0, 1);
+    let socket = TcpListener::bind((localhost, DORA_DAEMON_STREAM_PORT_DEFAULT))
+        .await
+        .wrap_err("failed to create output stream listener")?;
+    tokio::spawn(async move {
+        loop {
+            let connection = match socket.accept().await {
+                Ok((connection, _)) => connection,
+                Err(err) => {
+                    tracing::warn!("failed to accept output stream connection: {err}");
+                    continue;
+                }
+            };
+            let daemon_tx = daemon_tx.clone();
+            let clock = clock.clone();
+            tokio::spawn(async move {
+                if let Err(err) = serve(connection, daemon_tx, clock).await {
+                    tracing::debug!("output stream connection closed: {err:?}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    Arrow,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct StreamRequest {
+    dataflow_id: DataflowId,
+    /// The requested outputs, or all outputs if empty.
+    outputs: BTreeSet<(NodeId, DataId)>,
+    encoding: Encoding,
+}
+
+async fn serve(
+    mut connection: TcpStream,
+    daemon_tx: mpsc::Sender<Timestamped<Event>>,
+    clock: Arc<uhlc::HLC>,
+) -> eyre::Result<()> {
+    let request = read_request(&mut connection, 0).await?;
+    let request = match parse_request(&request) {
+        Ok(request) => request,
+        Err(err) => {
+            respond_error(&mut connection, "400 Bad Request", &format!("{err}")).await?;
+            return Ok(());
+        }
+    };
+
+    let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+    let (reply_sender, reply) = oneshot::channel();
+    let event = Event::Observer {
+        dataflow_id: request.dataflow_id,
+        outputs: request.outputs,
+        event_sender,
+        reply_sender,
+    };
+    daemon_tx
+        .send(Timestamped {
+            inner: event,
+            timestamp: clock.new_timestamp(),
+        })
+        .await
+        .map_err(|_| eyre!("daemon is not running"))?;
+    match reply.await {
+        Ok(DaemonReply::Result(Ok(()))) => {}
+        Ok(DaemonReply::Result(Err(err))) => {
+            respond_error(&mut connection, "404 Not Found", &err).await?;
+            return Ok(());
+        }
+        Ok(other) => bail!("unexpected observer reply: {other:?}"),
+        Err(_) => bail!("daemon dropped observer request"),
+    }
+
+    let headers = [
+        ("Content-Type", "text/event-stream"),
+        ("Cache-Control", "no-cache"),
+        ("Access-Control-Allow-Origin", "*"),
+        ("Connection", "keep-alive"),
+    ];
+    write_head(&mut connection, "200 OK", &headers).await?;
+
+    while let Some(event) = event_receiver.recv().await {
+        // only forward the latest message of each output if the client is lagging behind
+        let mut latest = BTreeMap::new();
+        let mut next = Some(event);
+        while let Some(event) = next {
+            if let NodeEvent::Input { id, metadata, data } = event.inner {
+                latest.insert(id, (metadata, data));
+            }
+            next = event_receiver.try_recv().ok();
+        }
+        for (id, (metadata, data)) in latest {
+            let message = match encode_message(&id, &metadata, data, request.encoding) {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::warn!("failed to encode output `{id}` for output stream: {err:?}");
+                    continue;
+                }
+            };
+            connection
+                .write_all(format!("data: {message}\n\n").as_bytes())
+                .await?;
+        }
+        connection.flush().await?;
+    }
+    Ok(())
+}
+
+fn parse_request(request: &Request) -> eyre::Result<StreamRequest> {
+    if request.method != "GET" {
+        bail!("unsupported method `{}`", request.method);
+    }
+    let dataflow_id = request
+        .path
+        .trim_matches('/')
+        .parse()
+        .wrap_err("path must be the UUID of a running dataflow")?;
+
+    let mut outputs = BTreeSet::new();
+    let mut encoding = Encoding::Json;
+    for (key, value) in request.query_pairs()? {
+        match key.as_str() {
+            "outputs" => {
+                for output in value.split(',').filter(|o| !o.is_empty()) {
+                    let (node, output) = output
+                        .split_once('/')
+                        .ok_or_else(|| eyre!("output `{output}` is not of form `node/output`"))?;
+                    outputs.insert((node.to_owned().into(), output.to_owned().into()));
+                }
+            }
+            "encoding" => {
+                encoding = match value.as_str() {
+                    "json" => Encoding::Json,
+                    "arrow" => Encoding::Arrow,
+                    other => bail!("unknown encoding `{other}` (expected `json` or `arrow`)"),
+                }
+            }
+            other => bail!("unknown query parameter `{other}`"),
+        }
+    }
+
+    Ok(StreamRequest {
+        dataflow_id,
+        outputs,
+        encoding,
+    })
+}
+
+async fn respond_error(connection: &mut TcpStream, status: &str, body: &str) -> eyre::Result<()> {
+    let headers = [
+        ("Content-Type", "text/plain"),
+        ("Access-Control-Allow-Origin", "*"),
+    ];
+    respond(connection, status, &headers, body.as_bytes()).await
+}
+
+fn encode_message(
+    id: &DataId,
+    metadata: &Metadata,
+    data: Option<DataMessage>,
+    encoding: Encoding,
+) -> eyre::Result<String> {
+    let data = match data {
+        None => RawData::Empty,
+        Some(DataMessage::Vec(data)) => RawData::Vec(data),
+        Some(DataMessage::SharedMemory { .. }) => {
+            bail!("observers must receive copies of shared memory outputs")
+        }
+    };
+    let array = make_array(data.into_arrow_array(&metadata.type_info)?);
+    let value = match encoding {
+        Encoding::Json => serde_json::Value::Array(array_to_json(array)?),
+        Encoding::Arrow => serde_json::Value::String(base64_encode(&array_to_ipc(array)?)),
+    };
+    let message = serde_json::json!({
+        "id": id,
+        "timestamp": metadata.timestamp().to_string(),
+        "parameters": metadata.parameters,
+        "value": value,
+    });
+    Ok(message.to_string())
+}
+
+/// Wraps the array in a single-column record batch, named `value`.
+fn to_record_batch(array: ArrayRef) -> eyre::Result<RecordBatch> {
+    let schema = Schema::new(vec![Field::new("value", array.data_type().clone(), true)]);
+    RecordBatch::try_new(Arc::new(schema), vec![array]).wrap_err("failed to create record batch")
+}
+
+fn array_to_json(array: ArrayRef) -> eyre::Result<Vec<serde_json::Value>> {
+    let batch = to_record_batch(array)?;
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write(&batch)?;
+    writer.finish()?;
+    let rows: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_slice(&writer.into_inner()).wrap_err("failed to parse JSON rows")?;
+    // null values are omitted from the rows
+    Ok(rows
+        .into_iter()
+        .map(|mut row| row.remove("value").unwrap_or_default())
+        .collect())
+}
+
+fn array_to_ipc(array: ArrayRef) -> eyre::Result<Vec<u8>> {
+    let batch = to_record_batch(array)?;
+    let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(writer.into_inner()?)
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stream_request() {
+        let parse = |head: &str| parse_request(&Request::parse_head(head).unwrap());
+        let head = "GET /01890000-0000-7000-8000-000000000000?outputs=camera/image,\
+            detector%2Fbbox&encoding=arrow HTTP/1.1\r\nHost: localhost";
+        let request = parse(head).unwrap();
+        assert_eq!(request.encoding, Encoding::Arrow);
+        assert_eq!(
+            request.outputs,
+            BTreeSet::from([
+                ("camera".to_owned().into(), "image".to_owned().into()),
+                ("detector".to_owned().into(), "bbox".to_owned().into()),
+            ])
+        );
+        assert!(parse("POST / HTTP/1.1").is_err());
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,290 @@
+//! Detection of operators that busy-loop.
+//!
+//! An operator that uses a full CPU core while it doesn't receive any events is
+//! most likely stuck in a loop, e.g. because it polls for something instead of
+//! waiting for it. The [`BusyLoopMonitor`] samples the CPU time of the operator
+//! threads periodically and measures their CPU usage since the last event that
+//! was delivered to them. Operators that keep a full core busy for at least
+//! [`MIN_BUSY_DURATION`] without receiving events are reported, so that
+//! callbacks that just take a few seconds are not mistaken for busy loops. Operators with the
+//! `busy_loop_penalty` option additionally get a lower scheduling priority
+//! until they stop busy-looping. Restoring the original priority requires the
+//! `CAP_SYS_NICE` capability or a matching `RLIMIT_NICE` limit.
+
+use dora_core::{config::OperatorId, process_stats::ThreadStats};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Interval at which the CPU time of the operator threads is sampled.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Share of a CPU core above which an operator without events is considered busy.
+const BUSY_THRESHOLD: f64 = 0.9;
+
+/// Time without events after which a busy operator is considered to busy-loop.
+pub const MIN_BUSY_DURATION: Duration = Duration::from_secs(30);
+
+/// Nice value that is applied to penalized operator threads.
+#[cfg(target_os = "linux")]
+const PENALTY_NICE_VALUE: i32 = 19;
+
+/// Shared handle to the thread that runs an operator.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorThread {
+    /// Kernel thread ID, `0` if not known (yet).
+    tid: Arc<AtomicU32>,
+    /// Number of events that were delivered to the operator.
+    events: Arc<AtomicU64>,
+}
+
+impl OperatorThread {
+    /// Records the calling thread as the thread that runs the operator.
+    pub fn register_current(&self) {
+        if let Some(tid) = current_thread_id() {
+            self.tid.store(tid, Ordering::Relaxed);
+        }
+    }
+
+    /// Counter that is incremented for every event delivered to the operator.
+    pub fn event_counter(&self) -> Arc<AtomicU64> {
+        self.events.clone()
+    }
+}
+
+pub struct BusyLoopMonitor {
+    node_id: String,
+    operators: BTreeMap<OperatorId, MonitoredOperator>,
+}
+
+struct MonitoredOperator {
+    thread: OperatorThread,
+    penalty: bool,
+    /// Sample that was taken after the last event was delivered.
+    last_event: Option<Sample>,
+    busy: bool,
+    /// Original nice value of the thread if it is currently penalized.
+    penalized: Option<i32>,
+}
+
+struct Sample {
+    time: Instant,
+    cpu_time: Duration,
+    events: u64,
+}
+
+impl BusyLoopMonitor {
+    pub fn new(node_id: String) -> Self {
+        Self {
+            node_id,
+            operators: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_operator(&mut self, operator_id: OperatorId, thread: OperatorThread, penalty: bool) {
+        self.operators.insert(
+            operator_id,
+            MonitoredOperator {
+                thread,
+                penalty,
+                last_event: None,
+                busy: false,
+                penalized: None,
+            },
+        );
+    }
+
+    /// Samples the operator threads every [`SAMPLE_INTERVAL`].
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.sample();
+        }
+    }
+
+    fn sample(&mut self) {
+        let pid = std::process::id();
+        for (operator_id, operator) in &mut self.operators {
+            let tid = operator.thread.tid.load(Ordering::Relaxed);
+            if tid == 0 {
+                continue;
+            }
+            let Some(stats) = ThreadStats::read(pid, tid) else {
+                continue;
+            };
+            let sample = Sample {
+                time: Instant::now(),
+                cpu_time: stats.cpu_time,
+                events: operator.thread.events.load(Ordering::Relaxed),
+            };
+            let since_last_event = match operator.last_event.take() {
+                Some(previous) if previous.events == sample.events => previous,
+                // an event was delivered since the previous sample, so we start
+                // measuring again
+                _ => {
+                    operator.last_event = Some(sample);
+                    if operator.busy {
+                        operator.busy = false;
+                        stop_busy_loop(&self.node_id, operator_id, operator, tid);
+                    }
+                    continue;
+                }
+            };
+            let usage = cpu_usage(&since_last_event, &sample);
+            let busy = is_busy(usage, sample.time.duration_since(since_last_event.time));
+            operator.last_event = Some(since_last_event);
+
+            if busy && !operator.busy {
+                tracing::warn!(
+                    "operator `{}/{operator_id}` used {:.0}% of a CPU core without receiving \
+                    any events for {:?}, it might be stuck in a busy loop",
+                    self.node_id,
+                    usage * 100.,
+                    MIN_BUSY_DURATION,
+                );
+                if operator.penalty && operator.penalized.is_none() {
+                    operator.penalized = penalize(tid);
+                }
+            } else if !busy && operator.busy {
+                stop_busy_loop(&self.node_id, operator_id, operator, tid);
+            }
+            operator.busy = busy;
+        }
+    }
+}
+
+fn stop_busy_loop(
+    node_id: &str,
+    operator_id: &OperatorId,
+    operator: &mut MonitoredOperator,
+    tid: u32,
+) {
+    tracing::info!("operator `{node_id}/{operator_id}` is no longer busy-looping");
+    if let Some(nice) = operator.penalized.take() {
+        set_nice_value(tid, nice);
+    }
+}
+
+/// Share of a CPU core that the thread used between the two samples.
+fn cpu_usage(previous: &Sample, current: &Sample) -> f64 {
+    let elapsed = current.time.duration_since(previous.time).as_secs_f64();
+    if elapsed == 0. {
+        return 0.;
+    }
+    let cpu_time = current.cpu_time.saturating_sub(previous.cpu_time);
+    cpu_time.as_secs_f64() / elapsed
+}
+
+/// Checks whether an operator busy-loops, based on its CPU usage during the
+/// given time without events.
+fn is_busy(cpu_usage: f64, without_events: Duration) -> bool {
+    without_events >= MIN_BUSY_DURATION && cpu_usage >= BUSY_THRESHOLD
+}
+
+#[cfg(target_os = "linux")]
+fn current_thread_id() -> Option<u32> {
+    // SAFETY: `gettid` has no preconditions and cannot fail
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+    u32::try_from(tid).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_thread_id() -> Option<u32> {
+    None
+}
+
+/// Lowers the priority of the given thread and returns its previous nice value.
+#[cfg(target_os = "linux")]
+fn penalize(tid: u32) -> Option<i32> {
+    // SAFETY: `getpriority` has no preconditions, errors are reported through `errno`
+    let previous = unsafe {
+        *libc::__errno_location() = 0;
+        libc::getpriority(libc::PRIO_PROCESS, tid)
+    };
+    if previous == -1 && std::io::Error::last_os_error().raw_os_error() != Some(0) {
+        tracing::warn!(
+            "failed to read priority of thread {tid}: {}",
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    set_nice_value(tid, PENALTY_NICE_VALUE).then_some(previous)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn penalize(_tid: u32) -> Option<i32> {
+    tracing::warn!("busy loop penalty is only supported on Linux");
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice_value(tid: u32, nice: i32) -> bool {
+    // SAFETY: `setpriority` has no preconditions
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) };
+    if result != 0 {
+        tracing::warn!(
+            "failed to set nice value of thread {tid} to {nice}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    result == 0
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice_value(_tid: u32, _nice: i32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_check() {
+        let start = Instant::now();
+        let previous = Sample {
+            time: start,
+            cpu_time: Duration::from_secs(10),
+            events: 3,
+        };
+        let current = Sample {
+            time: start + Duration::from_secs(5),
+            cpu_time: Duration::from_millis(14_900),
+            events: 3,
+        };
+        let usage = cpu_usage(&previous, &current);
+        assert!((usage - 0.98).abs() < 1e-9);
+        assert!(is_busy(usage, MIN_BUSY_DURATION));
+        // long callbacks are not flagged
+        assert!(!is_busy(usage, Duration::from_secs(5)));
+        assert!(!is_busy(0.5, MIN_BUSY_DURATION));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn measure_since_last_event() {
+        let thread = OperatorThread::default();
+        thread.register_current();
+        let mut monitor = BusyLoopMonitor::new("node".into());
+        monitor.add_operator(OperatorId::from("op".to_owned()), thread.clone(), false);
+        let operator = monitor.operators.values_mut().next().unwrap();
+        operator.busy = true;
+        operator.last_event = Some(Sample {
+            time: Instant::now() - MIN_BUSY_DURATION,
+            cpu_time: Duration::ZERO,
+            events: 0,
+        });
+
+        thread.events.fetch_add(1, Ordering::Relaxed);
+        monitor.sample();
+        let operator = monitor.operators.values().next().unwrap();
+        assert!(!operator.busy);
+        assert_eq!(operator.last_event.as_ref().unwrap().events, 1);
+    }
+}
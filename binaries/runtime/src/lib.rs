@@ -1,16 +1,26 @@
 #![warn(unsafe_op_in_unsafe_fn)]
 
+use aligned_vec::{AVec, ConstAlign};
+use busy_loop::{BusyLoopMonitor, OperatorThread};
 use dora_core::{
-    config::{DataId, OperatorId},
-    daemon_messages::{NodeConfig, RuntimeConfig},
-    descriptor::OperatorConfig,
+    condition::Condition,
+    config::{DataId, Input, InputBatch, InputMapping, NodeId, OperatorId},
+    daemon_messages::{DataflowId, NodeConfig, RuntimeConfig},
+    descriptor::{CoreNodeKind, Descriptor, OperatorConfig, OperatorDefinition},
+    message::lineage::extend_lineage,
+    process_stats::operator_thread_name,
 };
 use dora_metrics::init_meter_provider;
-use dora_node_api::{DoraNode, Event};
+use dora_node_api::{uhlc, DoraNode, Event, LocalInputSender, Metadata};
 use eyre::{bail, Context, Result};
 use futures::{Stream, StreamExt};
 use futures_concurrency::stream::Merge;
-use operator::{run_operator, OperatorEvent, StopReason};
+use operator::{
+    channel::Deterministic,
+    run_operator,
+    worker::{fork_workers, Worker},
+    LogStream, OperatorEvent, StopReason,
+};
 
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
@@ -23,7 +33,9 @@ use tokio::{
     sync::{mpsc, oneshot},
 };
 use tokio_stream::wrappers::ReceiverStream;
+mod busy_loop;
 mod operator;
+pub mod testing;
 
 pub fn main() -> eyre::Result<()> {
     let config: RuntimeConfig = {
@@ -34,28 +46,29 @@ pub fn main() -> eyre::Result<()> {
     let RuntimeConfig {
         node: config,
         operators,
+        mut operator_env,
     } = config;
     let node_id = config.node_id.clone();
-    #[cfg(feature = "tracing")]
-    set_up_tracing(&node_id.to_string()).context("failed to set up tracing subscriber")?;
+    let dataflow_id = config.dataflow_id;
 
     let dataflow_descriptor = config.dataflow_descriptor.clone();
 
-    let operator_definition = if operators.is_empty() {
+    if operators.is_empty() {
         bail!("no operators");
-    } else if operators.len() > 1 {
-        bail!("multiple operators are not supported");
-    } else {
-        let mut ops = operators;
-        ops.remove(0)
-    };
+    }
+    // the worker processes only contain the forking thread, so they need to be
+    // forked before any other thread is spawned, including the threads of the
+    // tracing subscriber
+    let mut workers = fork_workers(
+        dataflow_id,
+        &node_id,
+        &operators,
+        &operator_env,
+        &dataflow_descriptor,
+    )?;
 
-    let (operator_events_tx, events) = mpsc::channel(1);
-    let operator_id = operator_definition.id.clone();
-    let operator_events = ReceiverStream::new(events).map(move |event| RuntimeEvent::Operator {
-        id: operator_id.clone(),
-        event,
-    });
+    #[cfg(feature = "tracing")]
+    set_up_tracing(&node_id.to_string()).context("failed to set up tracing subscriber")?;
 
     let tokio_runtime = Builder::new_current_thread()
         .enable_all()
@@ -63,20 +76,66 @@ pub fn main() -> eyre::Result<()> {
         .wrap_err("Could not build a tokio runtime.")?;
 
     let mut operator_channels = HashMap::new();
-    let queue_sizes = queue_sizes(&operator_definition.config);
-    let (operator_channel, incoming_events) =
-        operator::channel::channel(tokio_runtime.handle(), queue_sizes);
-    operator_channels.insert(operator_definition.id.clone(), operator_channel);
+    let mut operator_event_streams = Vec::new();
+    let mut init_done = Vec::new();
+    let mut operator_runs = Vec::new();
+    let mut busy_loop_monitor = BusyLoopMonitor::new(node_id.to_string());
+    for operator_definition in operators {
+        let (operator_events_tx, events) = mpsc::channel(1);
+        let operator_id = operator_definition.id.clone();
+        operator_event_streams.push(ReceiverStream::new(events).map(move |event| {
+            RuntimeEvent::Operator {
+                id: operator_id.clone(),
+                event,
+            }
+        }));
+
+        let thread = OperatorThread::default();
+        busy_loop_monitor.add_operator(
+            operator_definition.id.clone(),
+            thread.clone(),
+            operator_definition.config.busy_loop_penalty,
+        );
+
+        let deterministic = dataflow_descriptor.deterministic.then(|| Deterministic {
+            hold_timeout: dataflow_descriptor.deterministic_hold_timeout(),
+        });
+        let queue_sizes = queue_sizes(&operator_definition.config, &dataflow_descriptor);
+        let (operator_channel, incoming_events) = operator::channel::channel(
+            tokio_runtime.handle(),
+            queue_sizes,
+            operator_definition.config.sync,
+            input_batches(&operator_definition.config),
+            thread.event_counter(),
+            deterministic,
+        );
+        operator_channels.insert(operator_definition.id.clone(), operator_channel);
+
+        let (init_done_tx, init_done_rx) = oneshot::channel();
+        init_done.push(init_done_rx);
+        let env = operator_env
+            .remove(&operator_definition.id)
+            .unwrap_or_default();
+        let worker = workers.remove(&operator_definition.id);
+        operator_runs.push((
+            operator_definition,
+            incoming_events,
+            operator_events_tx,
+            init_done_tx,
+            env,
+            thread,
+            worker,
+        ));
+    }
+    let operator_events = futures::stream::select_all(operator_event_streams);
 
     tracing::info!("spawning main task");
-    let operator_config = [(
-        operator_definition.id.clone(),
-        operator_definition.config.clone(),
-    )]
-    .into_iter()
-    .collect();
-    let (init_done_tx, init_done) = oneshot::channel();
+    let operator_config = operator_runs
+        .iter()
+        .map(|(definition, ..)| (definition.id.clone(), definition.config.clone()))
+        .collect();
     let main_task = std::thread::spawn(move || -> Result<()> {
+        tokio_runtime.spawn(busy_loop_monitor.run());
         tokio_runtime.block_on(run(
             operator_config,
             config,
@@ -86,17 +145,71 @@ pub fn main() -> eyre::Result<()> {
         ))
     });
 
-    let operator_id = operator_definition.id.clone();
-    run_operator(
-        &node_id,
+    // the first operator runs on the main thread, which is required for Python
+    // operators, the others get their own threads, named after the operator index
+    let mut runs = operator_runs.into_iter();
+    let main_operator = runs.next();
+    let mut operator_threads = Vec::new();
+    for (
+        index,
+        (operator_definition, incoming_events, events_tx, init_done_tx, env, thread, worker),
+    ) in runs.enumerate()
+    {
+        let node_id = node_id.clone();
+        let dataflow_descriptor = dataflow_descriptor.clone();
+        let operator_id = operator_definition.id.clone();
+        let handle = std::thread::Builder::new()
+            .name(operator_thread_name(index + 1))
+            .spawn(move || -> Result<()> {
+                thread.register_current();
+                run_operator_or_worker(
+                    worker,
+                    dataflow_id,
+                    &node_id,
+                    operator_definition,
+                    incoming_events,
+                    events_tx,
+                    init_done_tx,
+                    &dataflow_descriptor,
+                    env,
+                )
+                .wrap_err_with(|| format!("failed to run operator {operator_id}"))
+            })
+            .wrap_err("failed to spawn operator thread")?;
+        operator_threads.push(handle);
+    }
+    if let Some((
         operator_definition,
         incoming_events,
-        operator_events_tx,
+        events_tx,
         init_done_tx,
-        &dataflow_descriptor,
-    )
-    .wrap_err_with(|| format!("failed to run operator {operator_id}"))?;
+        env,
+        thread,
+        worker,
+    )) = main_operator
+    {
+        thread.register_current();
+        let operator_id = operator_definition.id.clone();
+        run_operator_or_worker(
+            worker,
+            dataflow_id,
+            &node_id,
+            operator_definition,
+            incoming_events,
+            events_tx,
+            init_done_tx,
+            &dataflow_descriptor,
+            env,
+        )
+        .wrap_err_with(|| format!("failed to run operator {operator_id}"))?;
+    }
 
+    for thread in operator_threads {
+        match thread.join() {
+            Ok(result) => result?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
     match main_task.join() {
         Ok(result) => result.wrap_err("main task failed")?,
         Err(panic) => std::panic::resume_unwind(panic),
@@ -105,32 +218,91 @@ pub fn main() -> eyre::Result<()> {
     Ok(())
 }
 
-fn queue_sizes(config: &OperatorConfig) -> std::collections::BTreeMap<DataId, usize> {
+/// Runs the given operator on the current thread, or forwards its events to
+/// and from its worker process if it is isolated.
+#[allow(clippy::too_many_arguments)]
+fn run_operator_or_worker(
+    worker: Option<Worker>,
+    dataflow_id: DataflowId,
+    node_id: &NodeId,
+    operator_definition: OperatorDefinition,
+    incoming_events: flume::Receiver<Event>,
+    events_tx: mpsc::Sender<OperatorEvent>,
+    init_done: oneshot::Sender<Result<()>>,
+    dataflow_descriptor: &Descriptor,
+    env: BTreeMap<String, String>,
+) -> Result<()> {
+    match worker {
+        Some(worker) => worker.run(incoming_events, events_tx, init_done),
+        None => run_operator(
+            dataflow_id,
+            node_id,
+            operator_definition,
+            incoming_events,
+            events_tx,
+            init_done,
+            dataflow_descriptor,
+            env,
+        ),
+    }
+}
+
+fn queue_sizes(
+    config: &OperatorConfig,
+    dataflow_descriptor: &Descriptor,
+) -> std::collections::BTreeMap<DataId, usize> {
     let mut sizes = BTreeMap::new();
     for (input_id, input) in &config.inputs {
-        let queue_size = input.queue_size.unwrap_or(10);
+        let queue_size = input
+            .queue_config(dataflow_descriptor.deterministic)
+            .effective_queue_size();
         sizes.insert(input_id.clone(), queue_size);
     }
     sizes
 }
 
+fn input_batches(config: &OperatorConfig) -> BTreeMap<DataId, InputBatch> {
+    config
+        .inputs
+        .iter()
+        .filter_map(|(input_id, input)| Some((input_id.clone(), input.batch?)))
+        .collect()
+}
+
 #[tracing::instrument(skip(operator_events, operator_channels), level = "trace")]
 async fn run(
     operators: HashMap<OperatorId, OperatorConfig>,
     config: NodeConfig,
     operator_events: impl Stream<Item = RuntimeEvent> + Unpin,
     mut operator_channels: HashMap<OperatorId, flume::Sender<Event>>,
-    init_done: oneshot::Receiver<Result<()>>,
+    init_done: Vec<oneshot::Receiver<Result<()>>>,
 ) -> eyre::Result<()> {
     #[cfg(feature = "metrics")]
     let _meter_provider = init_meter_provider(config.node_id.to_string());
-    init_done
-        .await
-        .wrap_err("the `init_done` channel was closed unexpectedly")?
-        .wrap_err("failed to init an operator")?;
+    for init_done in init_done {
+        init_done
+            .await
+            .wrap_err("the `init_done` channel was closed unexpectedly")?
+            .wrap_err("failed to init an operator")?;
+    }
     tracing::info!("All operators are ready, starting runtime");
 
+    // with simulated time, the daemon stamps the messages, so they must not bypass it
+    let in_process_edges = if config.dataflow_descriptor.clock.is_some() {
+        HashMap::new()
+    } else {
+        in_process_edges(&operators, &config.node_id)
+    };
+    let daemon_outputs = daemon_outputs(&config.dataflow_descriptor, &config.node_id);
+    let track_lineage = config.dataflow_descriptor.track_lineage();
+    let clock = uhlc::HLC::default();
+
     let (mut node, mut daemon_events) = DoraNode::init(config)?;
+    let local_inputs = daemon_events.local_input_sender();
+    // the operators process their inputs concurrently, so the lineage is tracked per operator
+    node.disable_input_lineage();
+    // the operators are initialized already, so nodes with a `ready_signal` are ready now
+    node.ready()?;
     let (daemon_events_tx, daemon_event_stream) = flume::bounded(1);
     tokio::task::spawn_blocking(move || {
         while let Some(event) = daemon_events.recv() {
@@ -162,6 +334,16 @@ async fn run(
                     OperatorEvent::Panic(payload) => {
                         bail!("operator {operator_id} panicked: {payload:?}");
                     }
+                    OperatorEvent::Log { stream, message } => {
+                        // tagged lines are collected in the node's log file by the daemon
+                        let timestamp =
+                            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                        let line = format!("{timestamp} [{}/{operator_id}] {message}", node.id());
+                        match stream {
+                            LogStream::Stdout => println!("{line}"),
+                            LogStream::Stderr => eprintln!("{line}"),
+                        }
+                    }
                     OperatorEvent::Finished { reason } => {
                         if let StopReason::ExplicitStopAll = reason {
                             // let hlc = dora_core::message::uhlc::HLC::default();
@@ -209,13 +391,58 @@ async fn run(
                             tracing::warn!("output sample requested, but operator {operator_id} exited already");
                         }
                     }
+                    OperatorEvent::DiscardOutputSample { sample } => node.discard_sample(sample),
                     OperatorEvent::Output {
                         output_id,
                         type_info,
                         parameters,
                         data,
                     } => {
+                        // deliver to the operators of this runtime directly, the daemon
+                        // only forwards the output to other nodes
+                        let edge = (operator_id.clone(), output_id.clone());
                         let output_id = operator_output_id(&operator_id, &output_id);
+                        let data = match in_process_edges.get(&edge) {
+                            Some(receivers) => {
+                                let send_to_daemon = daemon_outputs.contains(&output_id);
+                                let timestamp = clock.new_timestamp();
+                                let mut in_process_parameters = parameters.clone();
+                                if track_lineage {
+                                    in_process_parameters.lineage = extend_lineage(
+                                        &node.id().to_string(),
+                                        &output_id,
+                                        timestamp,
+                                        &parameters.lineage,
+                                    );
+                                }
+                                // the data is not transmitted, so it needs no checksum
+                                in_process_parameters.checksum = None;
+                                let metadata = Metadata::from_parameters(
+                                    timestamp,
+                                    type_info.clone(),
+                                    in_process_parameters,
+                                );
+                                let (in_process_data, data) = match data {
+                                    Some(sample) if send_to_daemon => {
+                                        (Some(AVec::from_slice(128, &sample)), Some(sample))
+                                    }
+                                    Some(sample) => (Some(node.take_sample_data(sample)), None),
+                                    None => (None, None),
+                                };
+                                send_in_process(
+                                    receivers,
+                                    &local_inputs,
+                                    metadata,
+                                    in_process_data,
+                                );
+                                if !send_to_daemon {
+                                    continue;
+                                }
+                                data
+                            }
+                            None => data,
+                        };
+
                         let result;
                         (node, result) = tokio::task::spawn_blocking(move || {
                             let result =
@@ -226,6 +453,23 @@ async fn run(
                         .wrap_err("failed to wait for send_output task")?;
                         result.wrap_err("failed to send node output")?;
                     }
+                    OperatorEvent::ServiceReply { request, reply } => {
+                        let result;
+                        (node, result) = tokio::task::spawn_blocking(move || {
+                            let result = match reply {
+                                Ok((parameters, data)) => node.send_service_reply(
+                                    request,
+                                    parameters,
+                                    arrow::array::make_array(data),
+                                ),
+                                Err(err) => node.send_service_error(request, err),
+                            };
+                            (node, result)
+                        })
+                        .await
+                        .wrap_err("failed to wait for send_service_reply task")?;
+                        result.wrap_err("failed to send service reply")?;
+                    }
                 }
             }
             RuntimeEvent::Event(Event::Stop) => {
@@ -310,6 +554,152 @@ async fn run(
                     }
                 }
             }
+            RuntimeEvent::Event(Event::DeadlineMissed { id, elapsed }) => {
+                let Some((operator_id, input_id)) = id.as_str().split_once('/') else {
+                    tracing::warn!("received DeadlineMissed event for non-operator input {id}");
+                    continue;
+                };
+                let operator_id = OperatorId::from(operator_id.to_owned());
+                let input_id = DataId::from(input_id.to_owned());
+
+                let Some(operator_channel) = operator_channels.get(&operator_id) else {
+                    tracing::warn!("received DeadlineMissed event {id} for unknown operator");
+                    continue;
+                };
+                if let Err(err) = operator_channel
+                    .send_async(Event::DeadlineMissed {
+                        id: input_id.clone(),
+                        elapsed,
+                    })
+                    .await
+                    .wrap_err_with(|| {
+                        format!(
+                            "failed to send DeadlineMissed({input_id}) to operator `{operator_id}`"
+                        )
+                    })
+                {
+                    tracing::warn!("{err}");
+                }
+            }
+            RuntimeEvent::Event(Event::LatencyBudgetExceeded { id, alert }) => {
+                let Some((operator_id, input_id)) = id.as_str().split_once('/') else {
+                    tracing::warn!(
+                        "received LatencyBudgetExceeded event for non-operator input {id}"
+                    );
+                    continue;
+                };
+                let operator_id = OperatorId::from(operator_id.to_owned());
+                let input_id = DataId::from(input_id.to_owned());
+
+                let Some(operator_channel) = operator_channels.get(&operator_id) else {
+                    tracing::warn!(
+                        "received LatencyBudgetExceeded event {id} for unknown operator"
+                    );
+                    continue;
+                };
+                if let Err(err) = operator_channel
+                    .send_async(Event::LatencyBudgetExceeded {
+                        id: input_id.clone(),
+                        alert,
+                    })
+                    .await
+                    .wrap_err_with(|| {
+                        format!(
+                            "failed to send LatencyBudgetExceeded({input_id}) to operator `{operator_id}`"
+                        )
+                    })
+                {
+                    tracing::warn!("{err}");
+                }
+            }
+            RuntimeEvent::Event(Event::OutputUnsubscribed { id }) => {
+                let Some((operator_id, output_id)) = id.as_str().split_once('/') else {
+                    tracing::warn!(
+                        "received OutputUnsubscribed event for non-operator output {id}"
+                    );
+                    continue;
+                };
+                let operator_id = OperatorId::from(operator_id.to_owned());
+                let output_id = DataId::from(output_id.to_owned());
+
+                // the channel is closed already if all inputs of the operator were closed
+                if let Some(operator_channel) = operator_channels.get(&operator_id) {
+                    let _ = operator_channel
+                        .send_async(Event::OutputUnsubscribed { id: output_id })
+                        .await;
+                }
+            }
+            RuntimeEvent::Event(Event::OutputSubscribed { id }) => {
+                let Some((operator_id, output_id)) = id.as_str().split_once('/') else {
+                    tracing::warn!("received OutputSubscribed event for non-operator output {id}");
+                    continue;
+                };
+                let operator_id = OperatorId::from(operator_id.to_owned());
+                let output_id = DataId::from(output_id.to_owned());
+
+                // the channel is closed already if all inputs of the operator were closed
+                if let Some(operator_channel) = operator_channels.get(&operator_id) {
+                    let _ = operator_channel
+                        .send_async(Event::OutputSubscribed { id: output_id })
+                        .await;
+                }
+            }
+            RuntimeEvent::Event(Event::ParameterUpdate { key, value }) => {
+                // only the operators that declare the parameter are notified
+                for (operator_id, config) in &operators {
+                    if !config.live_parameters.contains_key(&key) {
+                        continue;
+                    }
+                    let Some(channel) = operator_channels.get(operator_id) else {
+                        continue;
+                    };
+                    let _ = channel
+                        .send_async(Event::ParameterUpdate {
+                            key: key.clone(),
+                            value: value.clone(),
+                        })
+                        .await;
+                }
+            }
+            RuntimeEvent::Event(Event::ServiceRequest {
+                service,
+                request,
+                metadata,
+                data,
+            }) => {
+                let target = service
+                    .as_str()
+                    .split_once('/')
+                    .and_then(|(operator_id, id)| {
+                        let operator_id = OperatorId::from(operator_id.to_owned());
+                        let channel = operator_channels.get(&operator_id)?;
+                        Some((channel, DataId::from(id.to_owned())))
+                    });
+                let sent = match target {
+                    Some((channel, service_id)) => channel
+                        .send_async(Event::ServiceRequest {
+                            service: service_id,
+                            request: request.clone(),
+                            metadata,
+                            data,
+                        })
+                        .await
+                        .is_ok(),
+                    None => false,
+                };
+                if !sent {
+                    tracing::warn!("received request for service {service} of unknown operator");
+                    let error = format!("no running operator serves `{service}`");
+                    let result;
+                    (node, result) = tokio::task::spawn_blocking(move || {
+                        let result = node.send_service_error(request, error);
+                        (node, result)
+                    })
+                    .await
+                    .wrap_err("failed to wait for send_service_error task")?;
+                    result.wrap_err("failed to send service error")?;
+                }
+            }
             RuntimeEvent::Event(Event::Error(err)) => eyre::bail!("received error event: {err}"),
             RuntimeEvent::Event(other) => {
                 tracing::warn!("received unknown event `{other:?}`");
@@ -322,6 +712,118 @@ async fn run(
     Ok(())
 }
 
+/// Maps the outputs of the operators to the inputs of other operators of the
+/// same runtime node that they are connected to.
+fn in_process_edges(
+    operators: &HashMap<OperatorId, OperatorConfig>,
+    node_id: &NodeId,
+) -> HashMap<(OperatorId, DataId), Vec<InProcessReceiver>> {
+    let mut edges: HashMap<_, Vec<_>> = HashMap::new();
+    for (operator_id, operator_config) in operators {
+        for (input_id, input) in &operator_config.inputs {
+            let InputMapping::User(mapping) = &input.mapping else {
+                continue;
+            };
+            if &mapping.source != node_id || !input.deliverable_in_process() {
+                continue;
+            }
+            let Some((source_operator, output_id)) = mapping.output.as_str().split_once('/') else {
+                continue;
+            };
+            edges
+                .entry((
+                    OperatorId::from(source_operator.to_owned()),
+                    DataId::from(output_id.to_owned()),
+                ))
+                .or_default()
+                .push(InProcessReceiver {
+                    operator_id: operator_id.clone(),
+                    input_id: input_id.clone(),
+                    condition: input.when.clone(),
+                });
+        }
+    }
+    edges
+}
+
+/// An operator input of this runtime that receives an output of another operator.
+#[derive(Debug)]
+struct InProcessReceiver {
+    operator_id: OperatorId,
+    input_id: DataId,
+    condition: Option<Condition>,
+}
+
+/// Outputs of the operators that must be sent to the daemon because they are
+/// consumed by other nodes, or by inputs that the runtime can't deliver directly.
+fn daemon_outputs(descriptor: &Descriptor, node_id: &NodeId) -> BTreeSet<DataId> {
+    let mut outputs = BTreeSet::new();
+    for node in descriptor.resolve_aliases_and_set_defaults() {
+        let inputs: Vec<Input> = match node.kind {
+            CoreNodeKind::Custom(custom) => custom.run_config.inputs.into_values().collect(),
+            CoreNodeKind::Runtime(runtime) => runtime
+                .operators
+                .into_iter()
+                .flat_map(|operator| operator.config.inputs.into_values())
+                .collect(),
+        };
+        for input in inputs {
+            let InputMapping::User(mapping) = &input.mapping else {
+                continue;
+            };
+            if &mapping.source == node_id
+                && (&node.id != node_id || !input.deliverable_in_process())
+            {
+                outputs.insert(mapping.output.clone());
+            }
+        }
+    }
+    for mapping in descriptor.outputs.values() {
+        if let InputMapping::User(mapping) = mapping {
+            if &mapping.source == node_id {
+                outputs.insert(mapping.output.clone());
+            }
+        }
+    }
+    outputs
+}
+
+/// Sends an output to the given operators of this runtime through the event
+/// stream, which applies the same input options as for messages of the daemon.
+///
+/// Messages are dropped if the event stream is full, so that an operator that is
+/// connected to its own output can't block the runtime.
+fn send_in_process(
+    receivers: &[InProcessReceiver],
+    local_inputs: &LocalInputSender,
+    metadata: Metadata,
+    data: Option<AVec<u8, ConstAlign<128>>>,
+) {
+    let receivers: Vec<_> = receivers
+        .iter()
+        .filter(|receiver| match &receiver.condition {
+            Some(condition) => condition.matches(&metadata.parameters),
+            None => true,
+        })
+        .collect();
+    let mut data = data;
+    for (index, receiver) in receivers.iter().enumerate() {
+        let data = if index + 1 == receivers.len() {
+            data.take()
+        } else {
+            data.clone()
+        };
+        let input_id = operator_output_id(&receiver.operator_id, &receiver.input_id);
+        if let Err(err) = local_inputs.try_send(input_id, metadata.clone(), data) {
+            tracing::warn!(
+                "dropping message for input `{}` of operator `{}`: {err}",
+                receiver.input_id,
+                receiver.operator_id
+            );
+        }
+    }
+}
+
 fn operator_output_id(operator_id: &OperatorId, output_id: &DataId) -> DataId {
     DataId::from(format!("{operator_id}/{output_id}"))
 }
@@ -334,3 +836,67 @@ enum RuntimeEvent {
     },
     Event(Event),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_for_other_nodes_are_sent_to_daemon() {
+        let descriptor = Descriptor::parse(
+            r#"
+nodes:
+  - id: runtime
+    operators:
+      - id: a
+        python: a.py
+        outputs: [local, remote, ordered]
+      - id: b
+        python: b.py
+        inputs:
+          local: runtime/a/local
+          ordered:
+            source: runtime/a/ordered
+            order_by_key: true
+  - id: sink
+    custom:
+      source: sink
+      inputs:
+        remote: runtime/a/remote
+"#
+            .as_bytes()
+            .to_vec(),
+        )
+        .unwrap();
+        let node_id = NodeId::from("runtime".to_owned());
+
+        let outputs = daemon_outputs(&descriptor, &node_id);
+        assert_eq!(
+            outputs,
+            BTreeSet::from([
+                DataId::from("a/ordered".to_owned()),
+                DataId::from("a/remote".to_owned())
+            ])
+        );
+
+        let operators = descriptor
+            .resolve_aliases_and_set_defaults()
+            .into_iter()
+            .find(|node| node.id == node_id)
+            .map(|node| match node.kind {
+                CoreNodeKind::Runtime(runtime) => runtime
+                    .operators
+                    .into_iter()
+                    .map(|operator| (operator.id, operator.config))
+                    .collect(),
+                CoreNodeKind::Custom(_) => unreachable!(),
+            })
+            .unwrap();
+        let edges = in_process_edges(&operators, &node_id);
+        assert_eq!(edges.len(), 1);
+        assert!(edges.contains_key(&(
+            OperatorId::from("a".to_owned()),
+            DataId::from("local".to_owned())
+        )));
+    }
+}
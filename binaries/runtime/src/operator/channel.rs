@@ -1,63 +1,148 @@
-use dora_core::config::DataId;
-use dora_node_api::Event;
+use arrow::{
+    array::{Array, ListArray},
+    buffer::OffsetBuffer,
+    datatypes::Field,
+};
+use dora_core::config::{DataId, InputBatch, InputSync};
+use dora_node_api::{ArrowData, Event, Metadata};
 use futures::{
     future::{self, FusedFuture},
     FutureExt,
 };
-use std::collections::{BTreeMap, VecDeque};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Input ordering of operators in `deterministic` dataflows.
+pub struct Deterministic {
+    /// Maximum time that inputs are held back while waiting for the other
+    /// inputs of the operator.
+    pub hold_timeout: Duration,
+}
 
+/// Creates the event channel of an operator.
+///
+/// The `deterministic` ordering is set for operators of `deterministic`
+/// dataflows, which receive their inputs in the order of their logical time.
+///
+/// Control events such as [`Event::Stop`] are queued in a separate lane and
+/// delivered before any queued data, so that a backed-up input doesn't delay them.
+/// Pending batches and [`Event::InputClosed`] events are still delivered before
+/// a stop event.
 pub fn channel(
     runtime: &tokio::runtime::Handle,
     queue_sizes: BTreeMap<DataId, usize>,
+    sync: Option<InputSync>,
+    batches: BTreeMap<DataId, InputBatch>,
+    delivered_events: Arc<AtomicU64>,
+    deterministic: Option<Deterministic>,
 ) -> (flume::Sender<Event>, flume::Receiver<Event>) {
     let (incoming_tx, incoming_rx) = flume::bounded(10);
     let (outgoing_tx, outgoing_rx) = flume::bounded(0);
 
-    runtime.spawn(async {
-        let mut buffer = InputBuffer::new(queue_sizes);
-        buffer.run(incoming_rx, outgoing_tx).await;
+    runtime.spawn(async move {
+        let mut buffer = InputBuffer::new(queue_sizes, sync, batches, deterministic);
+        buffer.run(incoming_rx, outgoing_tx, delivered_events).await;
     });
 
     (incoming_tx, outgoing_rx)
 }
 
 struct InputBuffer {
+    /// Pending control events, which take priority over the `queue`.
+    control: VecDeque<Event>,
     queue: VecDeque<Option<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    sync: Option<InputSynchronizer>,
+    batcher: InputBatcher,
+    logical_order: Option<LogicalOrder>,
 }
 
 impl InputBuffer {
-    pub fn new(queue_sizes: BTreeMap<DataId, usize>) -> Self {
+    pub fn new(
+        queue_sizes: BTreeMap<DataId, usize>,
+        sync: Option<InputSync>,
+        batches: BTreeMap<DataId, InputBatch>,
+        deterministic: Option<Deterministic>,
+    ) -> Self {
+        let sync = sync.map(|policy| InputSynchronizer::new(policy, queue_sizes.clone()));
+        let logical_order = deterministic
+            .map(|d| LogicalOrder::new(queue_sizes.keys().cloned().collect(), d.hold_timeout));
         Self {
+            control: VecDeque::new(),
             queue: VecDeque::new(),
             queue_sizes,
+            sync,
+            batcher: InputBatcher::new(batches),
+            logical_order,
         }
     }
 
-    pub async fn run(&mut self, incoming: flume::Receiver<Event>, outgoing: flume::Sender<Event>) {
+    pub async fn run(
+        &mut self,
+        incoming: flume::Receiver<Event>,
+        outgoing: flume::Sender<Event>,
+        delivered_events: Arc<AtomicU64>,
+    ) {
         let mut send_out_buf = future::Fuse::terminated();
         let mut incoming_closed = false;
         loop {
             let next_incoming = if incoming_closed {
                 future::Fuse::terminated()
             } else {
-                incoming.recv_async().fuse()
+                // wake up when the oldest pending batch or held input is due
+                let order_deadline = self.logical_order.as_ref().and_then(|o| o.next_deadline());
+                let deadline = match (self.batcher.next_deadline(), order_deadline) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                let recv = incoming.recv_async();
+                async move {
+                    match deadline {
+                        Some(deadline) => tokio::time::timeout_at(deadline.into(), recv).await.ok(),
+                        None => Some(recv.await),
+                    }
+                }
+                .boxed()
+                .fuse()
             };
             match future::select(next_incoming, send_out_buf).await {
                 future::Either::Left((event, mut send_out)) => {
                     match event {
-                        Ok(event) => {
+                        Some(Ok(event)) => {
                             // received a new event -> push it to the queue
                             self.add_event(event);
-
-                            // if outgoing queue is empty, fill it again
-                            if send_out.is_terminated() {
-                                send_out = self.send_next_queued(&outgoing);
-                            }
                         }
-                        Err(flume::RecvError::Disconnected) => {
+                        Some(Err(flume::RecvError::Disconnected)) => {
                             incoming_closed = true;
+                            if let Some(order) = &mut self.logical_order {
+                                for event in order.flush_all() {
+                                    self.enqueue(event);
+                                }
+                            }
+                            let batches = self.batcher.flush_all();
+                            self.queue.extend(batches.into_iter().map(Some));
                         }
+                        None => {
+                            let now = Instant::now();
+                            if let Some(order) = &mut self.logical_order {
+                                for event in order.release_expired(now) {
+                                    self.enqueue(event);
+                                }
+                            }
+                            let batches = self.batcher.flush_expired(now);
+                            self.queue.extend(batches.into_iter().map(Some));
+                        }
+                    }
+
+                    // if outgoing queue is empty, fill it again
+                    if send_out.is_terminated() {
+                        send_out = self.send_next_queued(&outgoing);
                     }
 
                     // reassign the send_out future, which might be still in progress
@@ -65,12 +150,17 @@ impl InputBuffer {
                 }
                 future::Either::Right((send_result, _)) => match send_result {
                     Ok(()) => {
+                        delivered_events.fetch_add(1, Ordering::Relaxed);
                         send_out_buf = self.send_next_queued(&outgoing);
                     }
                     Err(flume::SendError(_)) => break,
                 },
             };
-            if incoming_closed && send_out_buf.is_terminated() && self.queue.is_empty() {
+            if incoming_closed
+                && send_out_buf.is_terminated()
+                && self.control.is_empty()
+                && self.queue.is_empty()
+            {
                 break;
             }
         }
@@ -80,18 +170,96 @@ impl InputBuffer {
         &mut self,
         outgoing: &'a flume::Sender<Event>,
     ) -> future::Fuse<flume::r#async::SendFut<'a, Event>> {
+        match self.next_queued() {
+            Some(next) => outgoing.send_async(next).fuse(),
+            None => future::Fuse::terminated(),
+        }
+    }
+
+    /// Takes the next event to deliver, preferring pending control events.
+    fn next_queued(&mut self) -> Option<Event> {
+        if let Some(control) = self.control.pop_front() {
+            return Some(control);
+        }
         loop {
-            match self.queue.pop_front() {
-                Some(Some(next)) => break outgoing.send_async(next).fuse(),
-                Some(None) => {
+            match self.queue.pop_front()? {
+                Some(next) => break Some(next),
+                None => {
                     // dropped event, try again with next one
                 }
-                None => break future::Fuse::terminated(),
             }
         }
     }
 
     fn add_event(&mut self, event: Event) {
+        match &mut self.logical_order {
+            Some(order) => {
+                for event in order.push(event, Instant::now()) {
+                    self.enqueue(event);
+                }
+            }
+            None => self.enqueue(event),
+        }
+    }
+
+    fn enqueue(&mut self, event: Event) {
+        let event = match event {
+            Event::Input { id, metadata, data } if self.batcher.is_batched(&id) => {
+                // full batches are delivered, partial batches are held back
+                let batches = self.batcher.push(id, metadata, data);
+                self.queue.extend(batches.into_iter().map(Some));
+                self.drop_oldest_inputs();
+                return;
+            }
+            Event::InputClosed { id } => {
+                let batches = self.batcher.flush(&id);
+                self.queue.extend(batches.into_iter().map(Some));
+                if let Some(sync) = &mut self.sync {
+                    self.queue.extend(sync.close(&id).into_iter().map(Some));
+                }
+                Event::InputClosed { id }
+            }
+            Event::Stop => {
+                // the stop event overtakes queued inputs, but batches and closed
+                // inputs are delivered before it, in their original order
+                let closed: BTreeSet<_> = self
+                    .queue
+                    .iter()
+                    .filter_map(|event| match event {
+                        Some(Event::InputClosed { id }) => Some(id.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                for event in std::mem::take(&mut self.queue) {
+                    let keep_order = match &event {
+                        Some(Event::InputBatch { .. } | Event::InputClosed { .. }) => true,
+                        Some(Event::Input { id, .. }) => closed.contains(id),
+                        _ => false,
+                    };
+                    if keep_order {
+                        self.control.extend(event);
+                    } else {
+                        self.queue.push_back(event);
+                    }
+                }
+                self.control.extend(self.batcher.flush_all());
+                Event::Stop
+            }
+            event => event,
+        };
+        let event = match (&mut self.sync, event) {
+            (Some(sync), Event::Input { id, metadata, data }) => {
+                match sync.push(id, metadata, data) {
+                    Some(synced) => synced,
+                    None => return,
+                }
+            }
+            (_, event) => event,
+        };
+        if is_control(&event) {
+            self.control.push_back(event);
+            return;
+        }
         self.queue.push_back(Some(event));
 
         // drop oldest input events to maintain max queue length queue
@@ -104,7 +272,9 @@ impl InputBuffer {
 
         // iterate over queued events, newest first
         for event in self.queue.iter_mut().rev() {
-            let Some(Event::Input { id: input_id, .. }) = event.as_mut() else {
+            let Some(Event::Input { id: input_id, .. } | Event::InputBatch { id: input_id, .. }) =
+                event.as_mut()
+            else {
                 continue;
             };
             match queue_size_remaining.get_mut(input_id) {
@@ -126,3 +296,605 @@ impl InputBuffer {
         }
     }
 }
+
+/// Whether the event is delivered through the priority lane of the operator.
+fn is_control(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Stop | Event::Reload { .. } | Event::ParameterUpdate { .. }
+    )
+}
+
+/// Orders the inputs of an operator by their logical time, for `deterministic`
+/// dataflows.
+///
+/// Messages are held back until every open input has a pending message. Then
+/// no message with a smaller logical time can arrive anymore, assuming that the
+/// logical time of every input is increasing. Ties are broken by input ID, so
+/// the order doesn't depend on the arrival time of the messages.
+///
+/// Sparse inputs and cycles can leave an input without pending messages for a
+/// long time, so messages are held back for at most the `hold_timeout`. The
+/// order of the released messages is still by logical time, but it is only
+/// reproducible if no message with a smaller logical time arrives later.
+struct LogicalOrder {
+    open: BTreeSet<DataId>,
+    /// Pending events of each input with their arrival time.
+    pending: BTreeMap<DataId, VecDeque<(Instant, Event)>>,
+    /// Logical time of the last message of each input.
+    last_logical_time: BTreeMap<DataId, u64>,
+    hold_timeout: Duration,
+    warned_about_timeout: bool,
+}
+
+impl LogicalOrder {
+    fn new(inputs: BTreeSet<DataId>, hold_timeout: Duration) -> Self {
+        Self {
+            open: inputs,
+            pending: BTreeMap::new(),
+            last_logical_time: BTreeMap::new(),
+            hold_timeout,
+            warned_about_timeout: false,
+        }
+    }
+
+    /// Adds the given event and returns the events that can be delivered now.
+    fn push(&mut self, mut event: Event, now: Instant) -> Vec<Event> {
+        let id = match &mut event {
+            Event::Input { id, metadata, .. } if self.open.contains(id) => {
+                // messages of senders that don't propagate logical times inherit
+                // the logical time of the previous message of the input, which
+                // doesn't depend on the arrival time
+                let time = metadata
+                    .parameters
+                    .logical_time
+                    .or_else(|| self.last_logical_time.get(id).copied())
+                    .unwrap_or(0);
+                metadata.parameters.logical_time = Some(time);
+                self.last_logical_time.insert(id.clone(), time);
+                id.clone()
+            }
+            Event::InputClosed { id } if self.open.contains(id) => id.clone(),
+            Event::Stop => {
+                let mut events = self.flush_all();
+                events.push(Event::Stop);
+                return events;
+            }
+            _ => return vec![event],
+        };
+        self.pending.entry(id).or_default().push_back((now, event));
+        self.release(now, false)
+    }
+
+    /// Returns the time at which the oldest held message times out.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .filter_map(|queue| queue.front())
+            .map(|(arrival, _)| *arrival + self.hold_timeout)
+            .min()
+    }
+
+    /// Returns the messages that can be delivered after held messages timed out.
+    fn release_expired(&mut self, now: Instant) -> Vec<Event> {
+        self.release(now, false)
+    }
+
+    /// Returns all pending events in logical order.
+    fn flush_all(&mut self) -> Vec<Event> {
+        self.release(Instant::now(), true)
+    }
+
+    fn release(&mut self, now: Instant, flush: bool) -> Vec<Event> {
+        let mut released = Vec::new();
+        loop {
+            // closed inputs don't hold back other inputs anymore
+            let closed: Vec<_> = self
+                .pending
+                .iter()
+                .filter(|(_, queue)| matches!(queue.front(), Some((_, Event::InputClosed { .. }))))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in closed {
+                if let Some((_, event)) = self.pending.get_mut(&id).and_then(|q| q.pop_front()) {
+                    released.push(event);
+                }
+                self.open.remove(&id);
+            }
+
+            let waiting = self
+                .open
+                .iter()
+                .any(|id| self.pending.get(id).map_or(true, |queue| queue.is_empty()));
+            if waiting && !flush {
+                let expired = self.next_deadline().is_some_and(|deadline| deadline <= now);
+                if !expired {
+                    break;
+                }
+                if !self.warned_about_timeout {
+                    tracing::warn!(
+                        "inputs were held back for more than {:?} while waiting for other \
+                        inputs, so their order might not be reproducible",
+                        self.hold_timeout
+                    );
+                    self.warned_about_timeout = true;
+                }
+            }
+            let next = self
+                .pending
+                .iter()
+                .filter_map(|(id, queue)| match queue.front()? {
+                    (_, Event::Input { metadata, .. }) => {
+                        Some((metadata.parameters.logical_time, id.clone()))
+                    }
+                    _ => None,
+                })
+                .min();
+            let Some((_, id)) = next else {
+                break;
+            };
+            if let Some((_, event)) = self.pending.get_mut(&id).and_then(|q| q.pop_front()) {
+                released.push(event);
+            }
+        }
+        released
+    }
+}
+
+/// Aligns the inputs of an operator with a `sync` policy.
+///
+/// Messages are held back per input until every input has a message whose
+/// timestamp is close enough to the newest message. The matched messages are then
+/// combined into a single [`Event::SyncedInput`]. Older messages are discarded.
+/// Closed inputs are no longer part of the sync, so that the remaining inputs
+/// are still delivered.
+struct InputSynchronizer {
+    window: Duration,
+    queue_sizes: BTreeMap<DataId, usize>,
+    held: BTreeMap<DataId, VecDeque<(Metadata, ArrowData)>>,
+}
+
+impl InputSynchronizer {
+    fn new(policy: InputSync, queue_sizes: BTreeMap<DataId, usize>) -> Self {
+        let InputSync::ApproximateTime { window } = policy;
+        let held = queue_sizes
+            .keys()
+            .map(|id| (id.clone(), VecDeque::new()))
+            .collect();
+        Self {
+            window,
+            queue_sizes,
+            held,
+        }
+    }
+
+    /// Adds the given input and returns the synced event if all inputs align.
+    fn push(&mut self, id: DataId, metadata: Metadata, data: ArrowData) -> Option<Event> {
+        let Some(queue) = self.held.get_mut(&id) else {
+            tracing::warn!("received input `{id}` that is not part of the operator sync");
+            return Some(Event::Input { id, metadata, data });
+        };
+        let pivot = timestamp(&metadata);
+        queue.push_back((metadata, data));
+        let max_held = self.queue_sizes.get(&id).copied().unwrap_or(10).max(1);
+        while queue.len() > max_held {
+            queue.pop_front();
+        }
+
+        // find the message closest to the new message for every other input
+        let mut matches = BTreeMap::new();
+        for (input_id, queue) in &self.held {
+            if *input_id == id {
+                matches.insert(input_id.clone(), queue.len() - 1);
+                continue;
+            }
+            let closest = queue
+                .iter()
+                .map(|(metadata, _)| distance(timestamp(metadata), pivot))
+                .enumerate()
+                .min_by_key(|(_, distance)| *distance);
+            match closest {
+                Some((index, distance)) if distance <= self.window => {
+                    matches.insert(input_id.clone(), index);
+                }
+                _ => return None,
+            }
+        }
+
+        let mut inputs = BTreeMap::new();
+        for (input_id, index) in matches {
+            let queue = self.held.get_mut(&input_id)?;
+            // older messages can't be matched anymore
+            queue.drain(..index);
+            if let Some(message) = queue.pop_front() {
+                inputs.insert(input_id, message);
+            }
+        }
+        Some(Event::SyncedInput { inputs })
+    }
+
+    /// Stops syncing the given input and returns its held messages as regular
+    /// inputs, since they can't be matched anymore.
+    fn close(&mut self, id: &DataId) -> Vec<Event> {
+        let held = self.held.remove(id).unwrap_or_default();
+        held.into_iter()
+            .map(|(metadata, data)| Event::Input {
+                id: id.clone(),
+                metadata,
+                data,
+            })
+            .collect()
+    }
+}
+
+/// Coalesces consecutive messages of inputs with a `batch` option.
+struct InputBatcher {
+    configs: BTreeMap<DataId, InputBatch>,
+    pending: BTreeMap<DataId, PendingBatch>,
+}
+
+struct PendingBatch {
+    started: Instant,
+    metadata: Vec<Metadata>,
+    data: Vec<ArrowData>,
+}
+
+impl InputBatcher {
+    fn new(configs: BTreeMap<DataId, InputBatch>) -> Self {
+        Self {
+            configs,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    fn is_batched(&self, id: &DataId) -> bool {
+        self.configs.contains_key(id)
+    }
+
+    /// Adds the given message and returns the batch events if the batch is full.
+    fn push(&mut self, id: DataId, metadata: Metadata, data: ArrowData) -> Vec<Event> {
+        let max_size = self.configs.get(&id).map(|c| c.max_size).unwrap_or(1);
+        let batch = self
+            .pending
+            .entry(id.clone())
+            .or_insert_with(|| PendingBatch {
+                started: Instant::now(),
+                metadata: Vec::new(),
+                data: Vec::new(),
+            });
+        batch.metadata.push(metadata);
+        batch.data.push(data);
+        if batch.data.len() >= max_size {
+            self.flush(&id)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns the time at which the oldest pending batch must be delivered.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending
+            .iter()
+            .filter_map(|(id, batch)| Some(batch.started + self.configs.get(id)?.max_latency()))
+            .min()
+    }
+
+    fn flush_expired(&mut self, now: Instant) -> Vec<Event> {
+        let expired: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(id, batch)| {
+                self.configs
+                    .get(*id)
+                    .map(|config| batch.started + config.max_latency() <= now)
+                    .unwrap_or(true)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired.iter().flat_map(|id| self.flush(id)).collect()
+    }
+
+    fn flush_all(&mut self) -> Vec<Event> {
+        let ids: Vec<_> = self.pending.keys().cloned().collect();
+        ids.iter().flat_map(|id| self.flush(id)).collect()
+    }
+
+    fn flush(&mut self, id: &DataId) -> Vec<Event> {
+        match self.pending.remove(id) {
+            Some(batch) => batch_events(id.clone(), batch),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Combines the messages of the batch into a list array.
+///
+/// Falls back to separate input events if the messages have different types.
+fn batch_events(id: DataId, batch: PendingBatch) -> Vec<Event> {
+    let PendingBatch { metadata, data, .. } = batch;
+    let arrays: Vec<&dyn Array> = data.iter().map(|data| data.0.as_ref()).collect();
+    let list = arrow::compute::concat(&arrays).and_then(|values| {
+        let offsets = OffsetBuffer::from_lengths(data.iter().map(|data| data.len()));
+        let field = Arc::new(Field::new("item", values.data_type().clone(), true));
+        ListArray::try_new(field, offsets, values, None)
+    });
+    match list {
+        Ok(list) => vec![Event::InputBatch {
+            id,
+            metadata,
+            data: ArrowData(Arc::new(list)),
+        }],
+        Err(err) => {
+            tracing::warn!("failed to batch messages of input `{id}`: {err}");
+            metadata
+                .into_iter()
+                .zip(data)
+                .map(|(metadata, data)| Event::Input {
+                    id: id.clone(),
+                    metadata,
+                    data,
+                })
+                .collect()
+        }
+    }
+}
+
+fn timestamp(metadata: &Metadata) -> Duration {
+    metadata.timestamp().get_time().to_duration()
+}
+
+fn distance(a: Duration, b: Duration) -> Duration {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::{uhlc, ArrowTypeInfo, MetadataParameters};
+
+    fn input(millis: u64) -> (Metadata, ArrowData) {
+        let id = *uhlc::HLC::default().new_timestamp().get_id();
+        let time = uhlc::NTP64::from(Duration::from_millis(millis));
+        let metadata = Metadata::from_parameters(
+            uhlc::Timestamp::new(time, id),
+            ArrowTypeInfo::empty(),
+            MetadataParameters::default(),
+        );
+        let data = ArrowData(arrow::array::new_empty_array(
+            &arrow::datatypes::DataType::Null,
+        ));
+        (metadata, data)
+    }
+
+    fn synchronizer() -> InputSynchronizer {
+        let queue_sizes = ["left", "right"]
+            .into_iter()
+            .map(|id| (DataId::from(id.to_owned()), 10))
+            .collect();
+        let policy = InputSync::ApproximateTime {
+            window: Duration::from_millis(50),
+        };
+        InputSynchronizer::new(policy, queue_sizes)
+    }
+
+    fn push(sync: &mut InputSynchronizer, id: &str, millis: u64) -> Option<Vec<u64>> {
+        let (metadata, data) = input(millis);
+        match sync.push(DataId::from(id.to_owned()), metadata, data)? {
+            Event::SyncedInput { inputs } => Some(
+                inputs
+                    .values()
+                    .map(|(metadata, _)| timestamp(metadata).as_millis() as u64)
+                    .collect(),
+            ),
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn batch_messages() {
+        let config = InputBatch {
+            max_size: 2,
+            max_latency: None,
+        };
+        let id = DataId::from("imu".to_owned());
+        let mut batcher = InputBatcher::new([(id.clone(), config)].into());
+        let mut push = |value: i32| {
+            let (metadata, _) = input(0);
+            let data = ArrowData(Arc::new(arrow::array::Int32Array::from(vec![value])));
+            batcher.push(id.clone(), metadata, data)
+        };
+        assert!(push(1).is_empty());
+        let events = push(2);
+        let [Event::InputBatch { metadata, data, .. }] = events.as_slice() else {
+            panic!("expected a single batch, got {events:?}");
+        };
+        assert_eq!(metadata.len(), 2);
+        let list = data.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(list.len(), 2);
+
+        assert!(push(3).is_empty());
+        assert!(batcher.next_deadline().is_some());
+        assert_eq!(batcher.flush_all().len(), 1);
+        assert!(batcher.next_deadline().is_none());
+    }
+
+    #[test]
+    fn deliver_control_events_first() {
+        let mut buffer = InputBuffer::new(
+            [(DataId::from("image".to_owned()), 10)].into(),
+            None,
+            BTreeMap::new(),
+            None,
+        );
+        for millis in [1, 2] {
+            let (metadata, data) = input(millis);
+            let id = DataId::from("image".to_owned());
+            buffer.add_event(Event::Input { id, metadata, data });
+        }
+        buffer.add_event(Event::Stop);
+
+        assert!(matches!(buffer.next_queued(), Some(Event::Stop)));
+        assert!(matches!(buffer.next_queued(), Some(Event::Input { .. })));
+        assert!(matches!(buffer.next_queued(), Some(Event::Input { .. })));
+        assert!(buffer.next_queued().is_none());
+    }
+
+    #[test]
+    fn deliver_batches_and_closed_inputs_before_stop() {
+        let imu = DataId::from("imu".to_owned());
+        let image = DataId::from("image".to_owned());
+        let config = InputBatch {
+            max_size: 10,
+            max_latency: None,
+        };
+        let mut buffer = InputBuffer::new(
+            [(imu.clone(), 10), (image.clone(), 10)].into(),
+            None,
+            [(imu.clone(), config)].into(),
+            None,
+        );
+        let camera = DataId::from("camera".to_owned());
+        buffer.queue_sizes.insert(camera.clone(), 10);
+        for id in [&camera, &image, &imu, &imu] {
+            let (metadata, data) = input(1);
+            let id = id.clone();
+            buffer.add_event(Event::Input { id, metadata, data });
+        }
+        buffer.add_event(Event::InputClosed { id: image.clone() });
+        buffer.add_event(Event::Stop);
+
+        let mut order = Vec::new();
+        while let Some(event) = buffer.next_queued() {
+            order.push(match event {
+                Event::Input { id, .. } => format!("{id}"),
+                Event::InputBatch { id, metadata, .. } => format!("{id} x{}", metadata.len()),
+                Event::InputClosed { id } => format!("{id} closed"),
+                Event::Stop => "stop".to_owned(),
+                other => panic!("unexpected event {other:?}"),
+            });
+        }
+        // only the input of the open `camera` input is overtaken by the stop event
+        assert_eq!(order, ["image", "image closed", "imu x2", "stop", "camera"]);
+    }
+
+    #[test]
+    fn deliver_in_logical_order() {
+        let left = DataId::from("left".to_owned());
+        let right = DataId::from("right".to_owned());
+        let mut order =
+            LogicalOrder::new([left.clone(), right.clone()].into(), Duration::from_secs(1));
+        let now = Instant::now();
+        // the timestamp is ignored, only the logical time matters
+        let mut push = |id: &DataId, time: u64| {
+            let event = logical_input(id, Some(time), 1000 - time);
+            released(order.push(event, now))
+        };
+
+        assert!(push(&left, 30).is_empty());
+        assert_eq!(push(&right, 20), ["right@20"]);
+        assert_eq!(push(&right, 40), ["left@30"]);
+        let closed = order.push(Event::InputClosed { id: left.clone() }, now);
+        assert_eq!(released(closed), ["left closed", "right@40"]);
+    }
+
+    #[test]
+    fn release_held_inputs_after_timeout() {
+        let fast = DataId::from("fast".to_owned());
+        let sparse = DataId::from("sparse".to_owned());
+        let timeout = Duration::from_millis(100);
+        let mut order = LogicalOrder::new([fast.clone(), sparse.clone()].into(), timeout);
+        let start = Instant::now();
+
+        assert!(order
+            .push(logical_input(&fast, Some(1), 0), start)
+            .is_empty());
+        let later = start + timeout / 2;
+        assert!(order
+            .push(logical_input(&fast, Some(2), 0), later)
+            .is_empty());
+        assert_eq!(order.next_deadline(), Some(start + timeout));
+        assert!(order.release_expired(start + timeout / 4).is_empty());
+
+        // only the message that timed out is released
+        let events = order.release_expired(start + timeout);
+        assert_eq!(released(events), ["fast@1"]);
+        assert_eq!(order.next_deadline(), Some(later + timeout));
+
+        // without timeout, messages are ordered again once all inputs have one
+        let events = order.push(logical_input(&sparse, Some(3), 0), later);
+        assert_eq!(released(events), ["fast@2"]);
+    }
+
+    #[test]
+    fn inherit_missing_logical_times() {
+        let left = DataId::from("left".to_owned());
+        let right = DataId::from("right".to_owned());
+        let run = |left_millis: u64, right_millis: u64| {
+            let mut order =
+                LogicalOrder::new([left.clone(), right.clone()].into(), Duration::from_secs(1));
+            let now = Instant::now();
+            let mut events = Vec::new();
+            events.extend(order.push(logical_input(&left, Some(5), left_millis), now));
+            events.extend(order.push(logical_input(&left, None, left_millis + 1), now));
+            events.extend(order.push(logical_input(&right, None, right_millis), now));
+            events.extend(order.push(logical_input(&right, Some(7), right_millis), now));
+            events.extend(order.flush_all());
+            released(events)
+        };
+
+        // the order doesn't depend on the timestamps of the messages
+        assert_eq!(run(10, 20), ["right@0", "left@5", "left@5", "right@7"]);
+        assert_eq!(run(20, 10), run(10, 20));
+    }
+
+    fn logical_input(id: &DataId, logical_time: Option<u64>, millis: u64) -> Event {
+        let (mut metadata, data) = input(millis);
+        metadata.parameters.logical_time = logical_time;
+        let id = id.clone();
+        Event::Input { id, metadata, data }
+    }
+
+    fn released(events: Vec<Event>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                Event::Input { id, metadata, .. } => {
+                    format!("{id}@{}", metadata.parameters.logical_time.unwrap())
+                }
+                Event::InputClosed { id } => format!("{id} closed"),
+                other => format!("{other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn align_closest_messages() {
+        let mut sync = synchronizer();
+        assert_eq!(push(&mut sync, "left", 1000), None);
+        assert_eq!(push(&mut sync, "left", 1100), None);
+        assert_eq!(push(&mut sync, "right", 1090), Some(vec![1100, 1090]));
+        // the message at 1000 was discarded because a newer message was matched
+        assert_eq!(push(&mut sync, "right", 1010), None);
+        assert_eq!(push(&mut sync, "left", 1200), None);
+        assert_eq!(push(&mut sync, "right", 1180), Some(vec![1200, 1180]));
+    }
+
+    #[test]
+    fn stop_syncing_closed_inputs() {
+        let mut sync = synchronizer();
+        let right = DataId::from("right".to_owned());
+        assert_eq!(push(&mut sync, "left", 1000), None);
+        assert_eq!(push(&mut sync, "right", 1200), None);
+
+        let flushed = sync.close(&right);
+        assert!(
+            matches!(flushed.as_slice(), [Event::Input { id, .. }] if *id == right),
+            "{flushed:?}"
+        );
+        assert_eq!(push(&mut sync, "left", 1300), Some(vec![1300]));
+    }
+}
@@ -1,33 +1,54 @@
 use dora_core::{
     config::{DataId, NodeId},
+    daemon_messages::DataflowId,
     descriptor::{Descriptor, OperatorDefinition, OperatorSource},
-    message::{ArrowTypeInfo, MetadataParameters},
+    message::{lineage::LineageEntry, ArrowTypeInfo, MetadataParameters},
 };
-use dora_node_api::{DataSample, Event};
+use dora_node_api::{arrow::array::ArrayData, DataSample, Event, ServiceRequestId};
 use eyre::{Context, Result};
-use std::any::Any;
+use serde::{Deserialize, Serialize};
+use std::{any::Any, collections::BTreeMap};
 use tokio::sync::{mpsc::Sender, oneshot};
 
 pub mod channel;
 #[cfg(feature = "python")]
+mod profile;
+#[cfg(feature = "python")]
 mod python;
 mod shared_lib;
+pub(crate) mod worker;
 
-#[allow(unused_variables)]
+#[allow(unused_variables, clippy::too_many_arguments)]
 pub fn run_operator(
+    dataflow_id: DataflowId,
     node_id: &NodeId,
     operator_definition: OperatorDefinition,
     incoming_events: flume::Receiver<Event>,
     events_tx: Sender<OperatorEvent>,
     init_done: oneshot::Sender<Result<()>>,
     dataflow_descriptor: &Descriptor,
+    env: BTreeMap<String, String>,
 ) -> eyre::Result<()> {
+    let params = serde_json::to_value(&operator_definition.config.parameters)
+        .wrap_err("failed to convert operator params to JSON")?;
+    let input_config: BTreeMap<_, _> = operator_definition
+        .config
+        .inputs
+        .iter()
+        .map(|(id, input)| {
+            let config = input.queue_config(dataflow_descriptor.deterministic);
+            (id.clone(), config)
+        })
+        .collect();
     match &operator_definition.config.source {
         OperatorSource::SharedLibrary(source) => {
             shared_lib::run(
                 node_id,
                 &operator_definition.id,
                 source,
+                env,
+                &params,
+                &input_config,
                 events_tx,
                 incoming_events,
                 init_done,
@@ -43,6 +64,7 @@ pub fn run_operator(
         OperatorSource::Python(source) => {
             #[cfg(feature = "python")]
             python::run(
+                dataflow_id,
                 node_id,
                 &operator_definition.id,
                 source,
@@ -50,6 +72,15 @@ pub fn run_operator(
                 incoming_events,
                 init_done,
                 dataflow_descriptor,
+                env,
+                params,
+                input_config,
+                operator_definition
+                    .config
+                    .live_parameters
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.0.clone()))
+                    .collect(),
             )
             .wrap_err_with(|| {
                 format!(
@@ -82,6 +113,21 @@ pub enum OperatorEvent {
         parameters: MetadataParameters,
         data: Option<DataSample>,
     },
+    /// Sample allocated through [`OperatorEvent::AllocateOutputSample`] that is
+    /// not sent, e.g. because the operator aborted the output.
+    DiscardOutputSample {
+        sample: DataSample,
+    },
+    /// Answer of the operator to an [`Event::ServiceRequest`].
+    ServiceReply {
+        request: ServiceRequestId,
+        reply: Result<(MetadataParameters, ArrayData), String>,
+    },
+    /// Output that the operator printed to stdout or stderr.
+    Log {
+        stream: LogStream,
+        message: String,
+    },
     Error(eyre::Error),
     Panic(Box<dyn Any + Send>),
     Finished {
@@ -89,9 +135,97 @@ pub enum OperatorEvent {
     },
 }
 
-#[derive(Debug)]
+/// Parameters that outputs inherit from the input that the operator is
+/// processing, unless the operator sets them itself.
+///
+/// Taken from the event before it is passed to the operator, so outputs are
+/// stamped on the operator thread that sends them.
+#[derive(Debug, Clone, Default)]
+pub struct InputContext {
+    logical_time: Option<u64>,
+    lineage: Vec<LineageEntry>,
+}
+
+impl InputContext {
+    pub fn of(event: &Event) -> Self {
+        match event {
+            Event::Input { metadata, .. } => Self {
+                logical_time: metadata.parameters.logical_time,
+                lineage: metadata.parameters.lineage.clone(),
+            },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn apply(&self, parameters: &mut MetadataParameters) {
+        if parameters.logical_time.is_none() {
+            parameters.logical_time = self.logical_time;
+        }
+        if parameters.lineage.is_empty() {
+            parameters.lineage = self.lineage.clone();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum StopReason {
     InputsClosed,
     ExplicitStop,
     ExplicitStopAll,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::InputContext;
+    use dora_core::message::{lineage::LineageEntry, uhlc, ArrowTypeInfo, MetadataParameters};
+    use dora_node_api::{ArrowData, Event, Metadata};
+
+    #[test]
+    fn outputs_inherit_parameters_of_input() {
+        let clock = uhlc::HLC::default();
+        let lineage = vec![LineageEntry {
+            node: "camera".to_owned(),
+            output: "image".to_owned(),
+            timestamp: clock.new_timestamp(),
+        }];
+        let parameters = MetadataParameters {
+            logical_time: Some(7),
+            lineage: lineage.clone(),
+            ..Default::default()
+        };
+        let event = Event::Input {
+            id: "image".to_owned().into(),
+            metadata: Metadata::from_parameters(
+                clock.new_timestamp(),
+                ArrowTypeInfo::empty(),
+                parameters,
+            ),
+            data: ArrowData(arrow::array::new_empty_array(
+                &arrow::datatypes::DataType::Null,
+            )),
+        };
+        let input = InputContext::of(&event);
+
+        let mut output = MetadataParameters::default();
+        input.apply(&mut output);
+        assert_eq!(output.logical_time, Some(7));
+        assert_eq!(output.lineage, lineage);
+
+        // explicitly set logical times are kept
+        output.logical_time = Some(9);
+        input.apply(&mut output);
+        assert_eq!(output.logical_time, Some(9));
+
+        let mut output = MetadataParameters::default();
+        InputContext::of(&Event::Stop).apply(&mut output);
+        assert_eq!(output.logical_time, None);
+        assert!(output.lineage.is_empty());
+    }
+}
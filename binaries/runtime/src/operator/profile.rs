@@ -0,0 +1,346 @@
+//! Opt-in profiling of Python operators.
+//!
+//! Enabled by setting the `DORA_PROFILE` environment variable of the node:
+//!
+//! - `python` (or `cProfile`) records the call stacks of the `on_event` calls
+//!   of each operator, using `sys.monitoring` on Python 3.12 and later and
+//!   `sys.setprofile` on older versions. `sys.monitoring` allows only one
+//!   profiler per process, so all operators of the node share one collector.
+//!   When an operator stops, its profile is written as folded stacks to
+//!   `out/<dataflow_id>/profiles/<node>.<operator>.folded` and the profiles of
+//!   all operators of the node are aggregated in
+//!   `out/<dataflow_id>/profiles/<node>.operators.folded`.
+//! - `py-spy` samples the whole runtime process with the external `py-spy`
+//!   tool, which needs permission to trace the process (e.g. `sudo` or
+//!   `kernel.yama.ptrace_scope=0`). The folded stacks of all operators of the
+//!   node are written to `out/<dataflow_id>/profiles/<node>.folded` when the
+//!   process exits.
+//!
+//! Folded stacks can be turned into flamegraphs with `flamegraph.pl`, `inferno`,
+//! or `speedscope`. The values are in microseconds for `python` and in samples
+//! for `py-spy`.
+
+use dora_core::{
+    config::{NodeId, OperatorId},
+    daemon_messages::DataflowId,
+};
+use eyre::{bail, Context};
+use pyo3::{sync::GILOnceCell, types::PyModule, Py, PyAny, Python};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Once,
+};
+
+/// Records the call stacks of the profiled operators.
+///
+/// The time between two call events is attributed to the stack that was active
+/// in between, so every event is processed in time linear to the stack depth.
+/// Stacks are aggregated per operator while recording.
+const COLLECTOR: &str = r#"
+import functools
+import os
+import sys
+import threading
+import time
+import types
+
+MAX_DEPTH = 64
+
+
+def frame_name(code):
+    name = f"{code.co_name} ({os.path.basename(code.co_filename)}:{code.co_firstlineno})"
+    return name.replace(";", ":")
+
+
+class Collector:
+    def __init__(self):
+        self.local = threading.local()
+        self.lock = threading.Lock()
+        # operator -> stack -> nanoseconds
+        self.stacks = {}
+        self.tool_id = None
+        if hasattr(sys, "monitoring"):
+            self.tool_id = self.register_tool()
+
+    def register_tool(self):
+        monitoring = sys.monitoring
+        # the low IDs are reserved for debuggers, coverage tools, and profilers
+        # like cProfile, which should keep working alongside
+        for tool_id in reversed(range(6)):
+            if monitoring.get_tool(tool_id) is None:
+                monitoring.use_tool_id(tool_id, "dora profiler")
+                break
+        else:
+            raise RuntimeError("no free sys.monitoring tool ID")
+        events = monitoring.events
+        callbacks = {
+            events.PY_START: self.on_start,
+            events.PY_RESUME: self.on_start,
+            events.PY_RETURN: self.on_end,
+            events.PY_YIELD: self.on_end,
+            events.PY_UNWIND: self.on_end,
+        }
+        for event, callback in callbacks.items():
+            monitoring.register_callback(tool_id, event, callback)
+        monitoring.set_events(tool_id, 0)
+        self.events = functools.reduce(lambda a, b: a | b, callbacks)
+        self.active = 0
+        return tool_id
+
+    def state(self):
+        return getattr(self.local, "state", None)
+
+    def advance(self, state):
+        # attributes the time since the previous event to the current stack
+        now = time.perf_counter_ns()
+        if state["stack"]:
+            stack = state["stack"][-1]
+            stacks = state["stacks"]
+            stacks[stack] = stacks.get(stack, 0) + now - state["last"]
+        state["last"] = now
+
+    def push(self, code):
+        state = self.state()
+        if state is None:
+            return
+        self.advance(state)
+        parent = state["stack"][-1] if state["stack"] else ()
+        if len(parent) >= MAX_DEPTH:
+            state["stack"].append(parent)
+        else:
+            state["stack"].append(parent + (frame_name(code),))
+
+    def pop(self):
+        state = self.state()
+        if state is None or not state["stack"]:
+            return
+        self.advance(state)
+        state["stack"].pop()
+
+    def on_start(self, code, offset):
+        self.push(code)
+
+    def on_end(self, code, offset, value):
+        self.pop()
+
+    def on_profile(self, frame, event, arg):
+        if event == "call":
+            self.push(frame.f_code)
+        elif event == "return":
+            self.pop()
+
+    def enable(self, operator):
+        with self.lock:
+            stacks = self.stacks.setdefault(operator, {})
+            if self.tool_id is not None:
+                self.active += 1
+                sys.monitoring.set_events(self.tool_id, self.events)
+        if self.tool_id is None:
+            sys.setprofile(self.on_profile)
+        self.local.state = {"stack": [], "stacks": stacks, "last": time.perf_counter_ns()}
+
+    def disable(self):
+        self.local.state = None
+        if self.tool_id is None:
+            sys.setprofile(None)
+            return
+        with self.lock:
+            self.active -= 1
+            if self.active == 0:
+                sys.monitoring.set_events(self.tool_id, 0)
+
+    def folded(self, operator=None):
+        with self.lock:
+            if operator is not None:
+                stacks = {stack: value for stack, value in self.stacks.get(operator, {}).items()}
+            else:
+                stacks = {
+                    (name,) + stack: value
+                    for name, operator_stacks in self.stacks.items()
+                    for stack, value in operator_stacks.items()
+                }
+        lines = []
+        for stack, value in sorted(stacks.items()):
+            micros = value // 1000
+            if micros > 0:
+                lines.append(f"{';'.join(stack)} {micros}\n")
+        return "".join(lines)
+
+
+COLLECTOR = Collector()
+
+
+def profile(operator):
+    # binds the collector methods directly, so that no wrapper frames are recorded
+    return types.SimpleNamespace(
+        enable=functools.partial(COLLECTOR.enable, operator),
+        disable=COLLECTOR.disable,
+    )
+"#;
+
+/// The module that defines the [`COLLECTOR`], shared by all operators of the
+/// process.
+static COLLECTOR_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profiler {
+    Python,
+    PySpy,
+}
+
+impl Profiler {
+    /// Reads the profiler from the `DORA_PROFILE` variable of the operator
+    /// environment or, if not set there, of the process environment.
+    pub fn from_env(env: &BTreeMap<String, String>) -> eyre::Result<Option<Self>> {
+        let value = match env.get("DORA_PROFILE") {
+            Some(value) => value.clone(),
+            None => match std::env::var("DORA_PROFILE") {
+                Ok(value) => value,
+                Err(_) => return Ok(None),
+            },
+        };
+        Self::parse(&value)
+    }
+
+    fn parse(value: &str) -> eyre::Result<Option<Self>> {
+        match value.trim() {
+            "" => Ok(None),
+            "python" | "cProfile" | "cprofile" => Ok(Some(Self::Python)),
+            "py-spy" => Ok(Some(Self::PySpy)),
+            other => bail!("unknown profiler `{other}` (expected `python` or `py-spy`)"),
+        }
+    }
+}
+
+pub fn profile_dir(dataflow_id: DataflowId) -> PathBuf {
+    Path::new("out")
+        .join(dataflow_id.to_string())
+        .join("profiles")
+}
+
+/// Starts `py-spy` for the current process, unless it was started already
+/// for another operator of the node.
+pub fn start_py_spy(dataflow_id: DataflowId, node_id: &NodeId) {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        let output = profile_dir(dataflow_id).join(format!("{node_id}.folded"));
+        if let Err(err) = spawn_py_spy(&output) {
+            tracing::warn!("failed to start py-spy profiler: {err:?}");
+        }
+    });
+}
+
+fn spawn_py_spy(output: &Path) -> eyre::Result<()> {
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir).wrap_err("failed to create profile directory")?;
+    }
+    // py-spy writes its output when the profiled process exits
+    Command::new("py-spy")
+        .arg("record")
+        .arg("--pid")
+        .arg(std::process::id().to_string())
+        .args(["--format", "raw", "--output"])
+        .arg(output)
+        .stdout(Stdio::null())
+        .spawn()
+        .wrap_err("failed to spawn `py-spy`, is it installed?")?;
+    Ok(())
+}
+
+fn collector_module(py: Python) -> eyre::Result<&PyModule> {
+    let module = COLLECTOR_MODULE.get_or_try_init(py, || {
+        PyModule::from_code(py, COLLECTOR, "dora_profile.py", "dora_profile")
+            .map(|module| module.into())
+            .wrap_err("failed to load profiler")
+    })?;
+    Ok(module.as_ref(py))
+}
+
+/// Creates a profile for the given operator, which records the call stacks
+/// between its `enable` and `disable` calls.
+pub fn new_profile(py: Python, operator_id: &OperatorId) -> eyre::Result<Py<PyAny>> {
+    let profile = collector_module(py)?
+        .call_method1("profile", (operator_id.as_ref(),))
+        .wrap_err("failed to create profiler")?;
+    Ok(profile.into())
+}
+
+/// Writes the folded stacks of the given operator and the aggregated stacks of
+/// all operators of the node to the profile directory of the dataflow.
+pub fn write_profile(
+    py: Python,
+    dataflow_id: DataflowId,
+    node_id: &NodeId,
+    operator_id: &OperatorId,
+) -> eyre::Result<PathBuf> {
+    let collector = collector_module(py)?
+        .getattr("COLLECTOR")
+        .wrap_err("failed to load profiler")?;
+    let fold = |operator: Option<&str>| -> eyre::Result<String> {
+        collector
+            .call_method1("folded", (operator,))
+            .and_then(|folded| folded.extract())
+            .wrap_err("failed to fold profiled stacks")
+    };
+
+    let dir = profile_dir(dataflow_id);
+    std::fs::create_dir_all(&dir).wrap_err("failed to create profile directory")?;
+    let path = dir.join(format!("{node_id}.{operator_id}.folded"));
+    std::fs::write(&path, fold(Some(operator_id.as_ref()))?)
+        .wrap_err_with(|| format!("failed to write profile to `{}`", path.display()))?;
+    let aggregated = dir.join(format!("{node_id}.operators.folded"));
+    std::fs::write(&aggregated, fold(None)?)
+        .wrap_err_with(|| format!("failed to write profile to `{}`", aggregated.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_profiler() {
+        assert_eq!(Profiler::parse("").unwrap(), None);
+        assert_eq!(Profiler::parse("python").unwrap(), Some(Profiler::Python));
+        assert_eq!(Profiler::parse("cProfile").unwrap(), Some(Profiler::Python));
+        assert_eq!(Profiler::parse("py-spy").unwrap(), Some(Profiler::PySpy));
+        assert!(Profiler::parse("perf").is_err());
+    }
+
+    #[test]
+    fn record_stacks() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let operator_id = OperatorId::from("op".to_owned());
+            let profile = new_profile(py, &operator_id).unwrap();
+            let code = "def inner():\n    sum(range(100000))\n\ndef outer():\n    inner()\n";
+            let module = PyModule::from_code(py, code, "profiled.py", "profiled").unwrap();
+
+            profile.call_method0(py, "enable").unwrap();
+            for _ in 0..10 {
+                module.call_method0("outer").unwrap();
+            }
+            profile.call_method0(py, "disable").unwrap();
+
+            let collector = collector_module(py).unwrap().getattr("COLLECTOR").unwrap();
+            let folded: String = collector
+                .call_method1("folded", ("op",))
+                .and_then(|folded| folded.extract())
+                .unwrap();
+            assert!(
+                folded.contains("outer (profiled.py:4);inner (profiled.py:1) "),
+                "{folded}"
+            );
+            let aggregated: String = collector
+                .call_method1("folded", (None::<&str>,))
+                .and_then(|folded| folded.extract())
+                .unwrap();
+            assert!(
+                aggregated.contains("op;outer (profiled.py:4)"),
+                "{aggregated}"
+            );
+        });
+    }
+}
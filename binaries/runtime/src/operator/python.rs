@@ -1,27 +1,138 @@
 #![allow(clippy::borrow_deref_ref)] // clippy warns about code generated by #[pymethods]
 
-use super::{OperatorEvent, StopReason};
+use super::{
+    profile::{self, Profiler},
+    InputContext, LogStream, OperatorEvent, StopReason,
+};
 use dora_core::{
-    config::{NodeId, OperatorId},
+    config::{DataId, InputQueueConfig, NodeId, OperatorId},
+    daemon_messages::DataflowId,
     descriptor::{source_is_url, Descriptor, PythonSource},
 };
 use dora_download::download_file;
-use dora_node_api::Event;
-use dora_operator_api_python::PyEvent;
+use dora_node_api::{
+    arrow::array::ArrayData, DataSample, Event, MetadataParameters, MetadataValue, ServiceRequestId,
+};
+use dora_operator_api_python::{metadata_value_to_py, PyEvent};
 use dora_operator_api_types::DoraStatus;
 use eyre::{bail, eyre, Context, Result};
 use pyo3::{
     pyclass,
-    types::{IntoPyDict, PyDict},
-    Py, PyAny, Python,
+    types::{PyDict, PyModule},
+    IntoPy, Py, PyAny, Python,
 };
+use sha2::{Digest, Sha256};
 use std::{
+    cell::RefCell,
+    collections::BTreeMap,
     panic::{catch_unwind, AssertUnwindSafe},
-    path::Path,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
 };
 use tokio::sync::{mpsc::Sender, oneshot};
 use tracing::{error, field, span, warn};
 
+/// Bounds of the delay between retries of output allocations with a `timeout`.
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Resolves the imports of an operator from its virtual environment and its
+/// directory, without changing the `sys.path` that is shared by all operators of
+/// the node.
+///
+/// The scope is only active while the code of the operator runs. Modules are
+/// still cached in `sys.modules`, so operators with conflicting requirements
+/// need to run in different nodes if they import the same module.
+const IMPORT_SCOPE: &str = r#"
+import importlib.machinery
+import importlib.util
+import sys
+
+
+class PathFinder:
+    def __init__(self, paths):
+        self.paths = paths
+
+    def find_spec(self, fullname, path=None, target=None):
+        # submodules are found through the `__path__` of their package
+        if path is not None:
+            return None
+        return importlib.machinery.PathFinder.find_spec(fullname, self.paths)
+
+
+class ImportScope:
+    def __init__(self, site_packages, operator_dir):
+        self.site_packages = PathFinder([site_packages] if site_packages else [])
+        self.operator_dir = PathFinder([operator_dir])
+
+    def activate(self):
+        # like entries at the start and at the end of `sys.path`
+        sys.meta_path.insert(0, self.site_packages)
+        sys.meta_path.append(self.operator_dir)
+
+    def deactivate(self):
+        sys.meta_path.remove(self.site_packages)
+        sys.meta_path.remove(self.operator_dir)
+
+    def load(self, name, path):
+        spec = importlib.util.spec_from_file_location(name, path)
+        module = importlib.util.module_from_spec(spec)
+        sys.modules[name] = module
+        try:
+            spec.loader.exec_module(module)
+        except BaseException:
+            del sys.modules[name]
+            raise
+        return module
+"#;
+
+/// Runs `f` with the given `ImportScope` activated.
+fn with_import_scope<T>(scope: &PyAny, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    scope.call_method0("activate").map_err(traceback)?;
+    let result = f();
+    scope.call_method0("deactivate").map_err(traceback)?;
+    result
+}
+
+fn import_scope<'py>(
+    py: Python<'py>,
+    site_packages: Option<&Path>,
+    operator_dir: &Path,
+) -> Result<&'py PyAny> {
+    let site_packages = site_packages
+        .map(|path| {
+            path.to_str()
+                .ok_or_else(|| eyre!("site-packages path is not valid utf8"))
+        })
+        .transpose()?;
+    let operator_dir = operator_dir
+        .to_str()
+        .ok_or_else(|| eyre!("module path is not valid utf8"))?;
+    PyModule::from_code(
+        py,
+        IMPORT_SCOPE,
+        "dora_import_scope.py",
+        "dora_import_scope",
+    )
+    .and_then(|module| module.getattr("ImportScope"))
+    .and_then(|class| class.call1((site_packages, operator_dir)))
+    .map_err(traceback)
+    .wrap_err("failed to create import scope")
+}
+
+/// Executes the operator file as module with the given name.
+fn load_module<'py>(scope: &'py PyAny, module_name: &str, path: &Path) -> Result<&'py PyAny> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| eyre!("module path is not valid utf8"))?;
+    with_import_scope(scope, || {
+        scope
+            .call_method1("load", (module_name, path))
+            .map_err(traceback)
+    })
+}
+
 fn traceback(err: pyo3::PyErr) -> eyre::Report {
     let traceback = Python::with_gil(|py| err.traceback(py).and_then(|t| t.format().ok()));
     if let Some(traceback) = traceback {
@@ -31,8 +142,134 @@ fn traceback(err: pyo3::PyErr) -> eyre::Report {
     }
 }
 
+/// Converts the operator params to a Python object, using an empty dict if no
+/// params are set.
+fn python_params<'py>(py: Python<'py>, params: &serde_json::Value) -> Result<&'py PyAny> {
+    if params.is_null() {
+        return Ok(PyDict::new(py));
+    }
+    let params = pythonize::pythonize(py, params).wrap_err("failed to convert operator params")?;
+    Ok(params.into_ref(py))
+}
+
+/// Instantiates the given `Operator` class.
+///
+/// The params are passed to the constructor if it takes an argument, i.e. if it
+/// is defined as `def __init__(self, params)`.
+fn construct_operator<'py>(
+    py: Python<'py>,
+    operator_class: &'py PyAny,
+    params: &'py PyAny,
+) -> Result<&'py PyAny> {
+    let parameter_count = py
+        .import("inspect")
+        .and_then(|inspect| inspect.call_method1("signature", (operator_class,)))
+        .and_then(|signature| signature.getattr("parameters"))
+        .and_then(|parameters| parameters.len())
+        .wrap_err("failed to inspect `Operator` constructor")?;
+    let operator = if parameter_count > 0 {
+        operator_class.call1((params,))
+    } else {
+        operator_class.call0()
+    };
+    operator.map_err(traceback)
+}
+
+/// Creates a virtual environment with the given requirements, or reuses a
+/// cached one, and returns its `site-packages` directory.
+fn prepare_virtualenv(requirements: &Path) -> eyre::Result<PathBuf> {
+    let python = Python::with_gil(|py| {
+        py.import("sys")
+            .and_then(|sys| sys.getattr("executable"))
+            .and_then(|executable| executable.extract::<String>())
+    })
+    .wrap_err("failed to get Python executable")?;
+    let content = std::fs::read(requirements)
+        .wrap_err_with(|| format!("failed to read `{}`", requirements.display()))?;
+
+    // environments are shared by all operators with the same requirements
+    let venvs = Path::new("build").join("venvs");
+    let venv = venvs.join(virtualenv_id(&python, &content));
+    let venv_python = |venv: &Path| {
+        if cfg!(windows) {
+            venv.join("Scripts").join("python.exe")
+        } else {
+            venv.join("bin").join("python")
+        }
+    };
+
+    if !venv.exists() {
+        // build the environment in a temporary directory to not use half-installed
+        // environments when multiple operators with the same requirements start
+        let tmp = venvs.join(format!("tmp-{}", std::process::id()));
+        tracing::info!(
+            "installing `{}` into virtual environment `{}`",
+            requirements.display(),
+            venv.display()
+        );
+        run_command(
+            Command::new(&python).args(["-m", "venv"]).arg(&tmp),
+            "create virtual environment",
+        )?;
+        run_command(
+            Command::new(venv_python(&tmp))
+                .args(["-m", "pip", "install", "--requirement"])
+                .arg(requirements),
+            "install requirements",
+        )?;
+        if std::fs::rename(&tmp, &venv).is_err() {
+            // another operator created the environment in the meantime
+            let _ = std::fs::remove_dir_all(&tmp);
+        }
+    }
+
+    let output = Command::new(venv_python(&venv))
+        .args([
+            "-c",
+            "import sysconfig; print(sysconfig.get_paths()['purelib'])",
+        ])
+        .output()
+        .wrap_err("failed to query site-packages of virtual environment")?;
+    if !output.status.success() {
+        bail!(
+            "failed to query site-packages of virtual environment `{}`",
+            venv.display()
+        );
+    }
+    let site_packages = String::from_utf8(output.stdout)
+        .wrap_err("site-packages path is not valid utf8")?
+        .trim()
+        .to_owned();
+    Ok(site_packages.into())
+}
+
+/// Identifies the environment of the given interpreter and requirements.
+///
+/// The ID is stable across runs and toolchains, so that cached environments
+/// are reused.
+fn virtualenv_id(python: &str, requirements: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(python.as_bytes());
+    hasher.update([0]);
+    hasher.update(requirements);
+    let digest = format!("{:x}", hasher.finalize());
+    digest[..16].to_owned()
+}
+
+fn run_command(command: &mut Command, action: &str) -> eyre::Result<()> {
+    let status = command
+        .status()
+        .wrap_err_with(|| format!("failed to {action}"))?;
+    if !status.success() {
+        bail!("failed to {action}: {status}");
+    }
+    Ok(())
+}
+
 #[tracing::instrument(skip(events_tx, incoming_events), level = "trace")]
+#[allow(clippy::too_many_arguments)]
 pub fn run(
+    dataflow_id: DataflowId,
     node_id: &NodeId,
     operator_id: &OperatorId,
     python_source: &PythonSource,
@@ -40,6 +277,10 @@ pub fn run(
     incoming_events: flume::Receiver<Event>,
     init_done: oneshot::Sender<Result<()>>,
     dataflow_descriptor: &Descriptor,
+    env: BTreeMap<String, String>,
+    params: serde_json::Value,
+    input_config: BTreeMap<DataId, InputQueueConfig>,
+    parameters: BTreeMap<String, MetadataValue>,
 ) -> eyre::Result<()> {
     let path = if source_is_url(&python_source.source) {
         let target_path = Path::new("build")
@@ -67,48 +308,75 @@ pub fn run(
         .ok_or_else(|| eyre!("module path has no file stem"))?
         .to_str()
         .ok_or_else(|| eyre!("module file stem is not valid utf8"))?;
-    let path_parent = path.parent();
+    let module_path = path.as_path();
+    let operator_dir = path
+        .parent()
+        .ok_or_else(|| eyre!("module path has no parent directory"))?
+        .to_owned();
+
+    let site_packages = python_source
+        .requirements
+        .as_deref()
+        .map(|requirements| prepare_virtualenv(Path::new(requirements)))
+        .transpose()
+        .wrap_err("failed to prepare virtual environment of Python operator")?;
+
+    let profiler = Profiler::from_env(&env)?;
+    if profiler == Some(Profiler::PySpy) {
+        profile::start_py_spy(dataflow_id, node_id);
+    }
 
     let send_output = SendOutputCallback {
         events_tx: events_tx.clone(),
+        input: InputContext::default(),
     };
 
+    let log_events_tx = events_tx.clone();
+
+    let reload_params = params.clone();
     let init_operator = move |py: Python| {
-        if let Some(parent_path) = path_parent {
-            let parent_path = parent_path
-                .to_str()
-                .ok_or_else(|| eyre!("module path is not valid utf8"))?;
-            let sys = py.import("sys").wrap_err("failed to import `sys` module")?;
-            let sys_path = sys
-                .getattr("path")
-                .wrap_err("failed to import `sys.path` module")?;
-            let sys_path_append = sys_path
-                .getattr("append")
-                .wrap_err("`sys.path.append` was not found")?;
-            sys_path_append
-                .call1((parent_path,))
-                .wrap_err("failed to append module path to python search path")?;
-        }
-
-        let module = py.import(module_name).map_err(traceback)?;
+        // forward everything that the operator prints to the runtime
+        redirect_output(py, log_events_tx).wrap_err("failed to redirect operator output")?;
+
+        let scope = import_scope(py, site_packages.as_deref(), &operator_dir)?;
+        let module = load_module(scope, module_name, module_path)?;
         let operator_class = module
             .getattr("Operator")
             .wrap_err("no `Operator` class found in module")?;
 
-        let locals = [("Operator", operator_class)].into_py_dict(py);
-        let operator = py
-            .eval("Operator()", None, Some(locals))
-            .map_err(traceback)?;
+        let params = python_params(py, &params)?;
+        let operator = with_import_scope(scope, || construct_operator(py, operator_class, params))?;
+        // set before `on_init`, so that operators can size their buffers accordingly
+        operator.setattr("input_config", pythonize::pythonize(py, &input_config)?)?;
+        // the variables and secrets of the operator, which are kept out of
+        // `os.environ` because it is shared by all operators of the node
+        operator.setattr("env", env.clone().into_py(py))?;
+        // current values of the declared parameters, kept up to date on `PARAMETER_UPDATE`
+        let parameters_dict = PyDict::new(py);
+        for (key, value) in &parameters {
+            parameters_dict.set_item(key, metadata_value_to_py(value, py))?;
+        }
+        operator.setattr("parameters", parameters_dict)?;
+        if operator
+            .hasattr("on_init")
+            .wrap_err("failed to check for `on_init` method")?
+        {
+            with_import_scope(scope, || {
+                operator
+                    .call_method1("on_init", (params,))
+                    .map_err(traceback)
+            })?;
+        }
         operator.setattr(
             "dataflow_descriptor",
             pythonize::pythonize(py, dataflow_descriptor)?,
         )?;
 
-        Result::<_, eyre::Report>::Ok(Py::from(operator))
+        Result::<_, eyre::Report>::Ok((Py::from(operator), Py::from(scope)))
     };
 
     let python_runner = move || {
-        let mut operator =
+        let (mut operator, scope) =
             match Python::with_gil(init_operator).wrap_err("failed to init python operator") {
                 Ok(op) => {
                     let _ = init_done.send(Ok(()));
@@ -119,6 +387,12 @@ pub fn run(
                     bail!("Could not init python operator")
                 }
             };
+        let profile = match profiler {
+            Some(Profiler::Python) => Some(Python::with_gil(|py| {
+                profile::new_profile(py, operator_id)
+            })?),
+            _ => None,
+        };
 
         let mut reload = false;
         let reason = loop {
@@ -139,27 +413,20 @@ pub fn run(
                         .extract::<&PyDict>(py)
                         .wrap_err("could not extract operator state as a PyDict")?;
                     // Reload module
-                    let module = py
-                        .import(module_name)
-                        .map_err(traceback)
-                        .wrap_err(format!("Could not retrieve {module_name} while reloading"))?;
-                    let importlib = py
-                        .import("importlib")
-                        .wrap_err("failed to import `importlib` module")?;
-                    let module = importlib
-                        .call_method("reload", (module,), None)
+                    let scope = scope.as_ref(py);
+                    let module = load_module(scope, module_name, module_path)
                         .wrap_err(format!("Could not reload {module_name} while reloading"))?;
                     let reloaded_operator_class = module
                         .getattr("Operator")
                         .wrap_err("no `Operator` class found in module")?;
 
                     // Create a new reloaded operator
-                    let locals = [("Operator", reloaded_operator_class)].into_py_dict(py);
-                    let operator: Py<pyo3::PyAny> = py
-                        .eval("Operator()", None, Some(locals))
-                        .map_err(traceback)
-                        .wrap_err("Could not initialize reloaded operator")?
-                        .into();
+                    let params = python_params(py, &reload_params)?;
+                    let operator: Py<pyo3::PyAny> = with_import_scope(scope, || {
+                        construct_operator(py, reloaded_operator_class, params)
+                    })
+                    .wrap_err("Could not initialize reloaded operator")?
+                    .into();
 
                     // Replace initialized state with current state
                     operator
@@ -181,6 +448,44 @@ pub fn run(
                 }
             }
 
+            if let Event::ParameterUpdate { key, value } = &event {
+                let result = Python::with_gil(|py| {
+                    operator
+                        .getattr(py, "parameters")
+                        .and_then(|parameters| {
+                            parameters.call_method1(
+                                py,
+                                "__setitem__",
+                                (key, metadata_value_to_py(value, py)),
+                            )
+                        })
+                        .map_err(traceback)
+                });
+                if let Err(err) = result {
+                    warn!("failed to update parameter `{key}`: {err}");
+                }
+            }
+
+            if let Event::ServiceRequest { request, .. } = &event {
+                let reply = ServiceReplyCallback {
+                    events_tx: send_output.events_tx.clone(),
+                    request: request.clone(),
+                };
+                Python::with_gil(|py| {
+                    let py_event = PyEvent::from(event);
+                    let result = with_import_scope(scope.as_ref(py), || {
+                        operator
+                            .call_method1(py, "on_service_request", (py_event, reply.clone()))
+                            .map_err(traceback)
+                    });
+                    if let Err(err) = result {
+                        warn!("failed to handle service request: {err}");
+                        py.allow_threads(|| reply.send(Err(err.to_string())));
+                    }
+                });
+                continue;
+            }
+
             let status = Python::with_gil(|py| -> Result<i32> {
                 let span = span!(tracing::Level::TRACE, "on_event", input_id = field::Empty);
                 let _ = span.enter();
@@ -207,6 +512,7 @@ pub fn run(
                 {
                     use dora_tracing::telemetry::{deserialize_context, serialize_context};
                     use tracing_opentelemetry::OpenTelemetrySpanExt;
+
                     span.record("input_id", input_id.as_str());
 
                     let cx = deserialize_context(&metadata.parameters.open_telemetry_context);
@@ -216,11 +522,28 @@ pub fn run(
                     metadata.parameters.open_telemetry_context = string_cx;
                 }
 
+                // outputs of the operator inherit the parameters of the current input
+                let send_output = SendOutputCallback {
+                    input: InputContext::of(&event),
+                    ..send_output.clone()
+                };
                 let py_event = PyEvent::from(event);
 
-                let status_enum = operator
-                    .call_method1(py, "on_event", (py_event, send_output.clone()))
-                    .map_err(traceback);
+                if let Some(profile) = &profile {
+                    profile
+                        .call_method0(py, "enable")
+                        .wrap_err("failed to enable profiler")?;
+                }
+                let status_enum = with_import_scope(scope.as_ref(py), || {
+                    operator
+                        .call_method1(py, "on_event", (py_event, send_output))
+                        .map_err(traceback)
+                });
+                if let Some(profile) = &profile {
+                    profile
+                        .call_method0(py, "disable")
+                        .wrap_err("failed to disable profiler")?;
+                }
                 match status_enum {
                     Ok(status_enum) => {
                         let status_val = Python::with_gil(|py| status_enum.getattr(py, "value"))
@@ -247,6 +570,20 @@ pub fn run(
             }
         };
 
+        if profile.is_some() {
+            Python::with_gil(|py| {
+                match profile::write_profile(py, dataflow_id, node_id, operator_id) {
+                    Ok(path) => tracing::info!(
+                        "wrote profile of operator `{operator_id}` to `{}`",
+                        path.display()
+                    ),
+                    Err(err) => {
+                        warn!("failed to write profile of operator `{operator_id}`: {err:?}")
+                    }
+                }
+            });
+        }
+
         // Dropping the operator using Python garbage collector.
         // Locking the GIL for immediate release.
         Python::with_gil(|_py| {
@@ -279,6 +616,143 @@ pub fn run(
 #[derive(Clone)]
 struct SendOutputCallback {
     events_tx: Sender<OperatorEvent>,
+    /// Input that the operator processed when it received this callback.
+    input: InputContext,
+}
+
+/// Output of the operator that is filled incrementally, see
+/// `SendOutputCallback::begin_output`.
+#[pyclass]
+struct PendingOutput {
+    callback: SendOutputCallback,
+    output_id: String,
+    /// `None` after the output was committed or aborted.
+    sample: Option<DataSample>,
+}
+
+/// Answers a service request, see [`Event::ServiceRequest`].
+///
+/// Passed to the `on_service_request(self, dora_event, reply)` method of the operator.
+#[pyclass]
+#[derive(Clone)]
+struct ServiceReplyCallback {
+    events_tx: Sender<OperatorEvent>,
+    request: ServiceRequestId,
+}
+
+impl ServiceReplyCallback {
+    fn send(&self, reply: Result<(MetadataParameters, ArrayData), String>) {
+        let event = OperatorEvent::ServiceReply {
+            request: self.request.clone(),
+            reply,
+        };
+        if self.events_tx.blocking_send(event).is_err() {
+            warn!("failed to send service reply to runtime");
+        }
+    }
+}
+
+thread_local! {
+    /// Writers for the stdout and stderr of the Python operator that runs on
+    /// the current thread.
+    static LOG_WRITERS: RefCell<Option<[LogWriter; 2]>> = RefCell::new(None);
+}
+
+/// Forwards everything that the Python operator of the current thread prints
+/// as [`OperatorEvent::Log`].
+///
+/// All operators of a node share `sys.stdout` and `sys.stderr`, so they are
+/// replaced by a [`LogDispatcher`] once, which looks up the writer of the
+/// printing thread. Output of other threads, e.g. threads that an operator
+/// starts, goes to the original streams.
+fn redirect_output(py: Python, events_tx: Sender<OperatorEvent>) -> Result<()> {
+    let sys = py.import("sys").wrap_err("failed to import `sys` module")?;
+    for (name, stream) in [("stdout", LogStream::Stdout), ("stderr", LogStream::Stderr)] {
+        let current = sys.getattr(name)?;
+        if !current.is_instance_of::<LogDispatcher>() {
+            let dispatcher = LogDispatcher {
+                stream,
+                fallback: current.into(),
+            };
+            sys.setattr(name, dispatcher.into_py(py))
+                .wrap_err_with(|| format!("failed to redirect `sys.{name}`"))?;
+        }
+    }
+    LOG_WRITERS.with(|writers| {
+        *writers.borrow_mut() = Some([
+            LogWriter::new(events_tx.clone(), LogStream::Stdout),
+            LogWriter::new(events_tx, LogStream::Stderr),
+        ])
+    });
+    Ok(())
+}
+
+/// Python text stream that replaces `sys.stdout` or `sys.stderr`, see
+/// [`redirect_output`].
+#[pyclass]
+struct LogDispatcher {
+    stream: LogStream,
+    /// The replaced stream.
+    fallback: pyo3::PyObject,
+}
+
+impl LogDispatcher {
+    /// Runs the given function with the writer of the current thread.
+    ///
+    /// Returns `None` if no operator runs on the current thread.
+    fn with_writer<T>(&self, f: impl FnOnce(&mut LogWriter) -> T) -> Option<T> {
+        LOG_WRITERS.with(|writers| {
+            let mut writers = writers.borrow_mut();
+            let [stdout, stderr] = writers.as_mut()?;
+            Some(match self.stream {
+                LogStream::Stdout => f(stdout),
+                LogStream::Stderr => f(stderr),
+            })
+        })
+    }
+}
+
+/// Collects written text and forwards it line by line.
+struct LogWriter {
+    events_tx: Sender<OperatorEvent>,
+    stream: LogStream,
+    buffer: String,
+}
+
+impl LogWriter {
+    fn new(events_tx: Sender<OperatorEvent>, stream: LogStream) -> Self {
+        Self {
+            events_tx,
+            stream,
+            buffer: String::new(),
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.buffer.push_str(text);
+        while let Some(index) = self.buffer.find('\n') {
+            let line = self.buffer[..index].to_owned();
+            self.buffer.drain(..=index);
+            self.send(line);
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            self.send(line);
+        }
+    }
+
+    fn send(&self, message: String) {
+        let event = OperatorEvent::Log {
+            stream: self.stream,
+            message,
+        };
+        if self.events_tx.blocking_send(event).is_err() {
+            eprintln!("failed to forward operator log message to runtime");
+        }
+    }
 }
 
 #[allow(unsafe_op_in_unsafe_fn)]
@@ -286,89 +760,183 @@ mod callback_impl {
 
     use crate::operator::OperatorEvent;
 
-    use super::SendOutputCallback;
+    use super::{
+        LogDispatcher, PendingOutput, SendOutputCallback, ServiceReplyCallback, MAX_RETRY_DELAY,
+        MIN_RETRY_DELAY,
+    };
+    use crate::operator::LogStream;
     use aligned_vec::{AVec, ConstAlign};
-    use arrow::{array::ArrayData, pyarrow::FromPyArrow};
-    use dora_core::message::ArrowTypeInfo;
+    use arrow::{
+        array::{Array, ArrayData, UInt8Array},
+        pyarrow::FromPyArrow,
+    };
+    use dora_core::message::{ArrowTypeInfo, MetadataParameters};
     use dora_node_api::{
         arrow_utils::{copy_array_into_sample, required_data_size},
-        ZERO_COPY_THRESHOLD,
+        DataSample, ZERO_COPY_THRESHOLD,
     };
-    use dora_operator_api_python::pydict_to_metadata;
+    use dora_operator_api_python::{dlpack_to_arrow, pydict_to_metadata, BackpressureError};
     use dora_tracing::telemetry::deserialize_context;
     use eyre::{eyre, Context, Result};
     use pyo3::{
+        exceptions::PyValueError,
         pymethods,
-        types::{PyBytes, PyDict},
-        PyObject, Python,
+        types::{PyBytes, PyDict, PyTuple, PyType},
+        PyAny, PyErr, PyObject, PyResult, Python,
     };
+    use std::time::{Duration, Instant};
     use tokio::sync::oneshot;
     use tracing::{field, span};
     use tracing_opentelemetry::OpenTelemetrySpanExt;
 
     /// Send an output from the operator:
     /// - the first argument is the `output_id` as defined in your dataflow.
-    /// - the second argument is the data as either bytes or pyarrow.Array for zero copy.
+    /// - the second argument is the data as either bytes or pyarrow.Array for zero copy, or
+    ///   a tensor that implements `__dlpack__` (non-CPU tensors are copied to host memory).
     /// - the third argument is dora metadata if you want ot link the tracing from one input into an output.
+    /// - the optional `timeout` (in seconds) retries the allocation of the output for up to
+    ///   the given duration if no memory is available.
+    ///
+    /// Raises a `BackpressureError` if no memory could be allocated for the output, e.g. to
+    /// skip the current frame.
     /// `e.g.:  send_output("bbox", pa.array([100], type=pa.uint8()), dora_event["metadata"])`
     #[pymethods]
     impl SendOutputCallback {
+        #[pyo3(signature = (output, data, metadata=None, timeout=None))]
         fn __call__(
             &mut self,
             output: &str,
             data: PyObject,
             metadata: Option<&PyDict>,
+            timeout: Option<f64>,
             py: Python,
-        ) -> Result<()> {
-            let parameters = pydict_to_metadata(metadata)
+        ) -> PyResult<()> {
+            let mut parameters = pydict_to_metadata(metadata)
                 .wrap_err("failed to parse metadata")?
                 .into_owned();
-            let span = span!(
-                tracing::Level::TRACE,
-                "send_output",
-                output_id = field::Empty
-            );
-            span.record("output_id", output);
-
-            let cx = deserialize_context(&parameters.open_telemetry_context);
-            span.set_parent(cx);
-            let _ = span.enter();
-
-            let allocate_sample = |data_len| {
-                if data_len > ZERO_COPY_THRESHOLD {
-                    let (tx, rx) = oneshot::channel();
-                    self.events_tx
-                        .blocking_send(OperatorEvent::AllocateOutputSample {
-                            len: data_len,
-                            sample: tx,
-                        })
-                        .map_err(|_| eyre!("failed to send output to runtime"))?;
-                    rx.blocking_recv()
-                        .wrap_err("failed to request output sample")?
-                        .wrap_err("failed to allocate output sample")
-                } else {
-                    let avec: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, data_len);
-
-                    Ok(avec.into())
-                }
-            };
-
             let (sample, type_info) = if let Ok(py_bytes) = data.downcast::<PyBytes>(py) {
                 let data = py_bytes.as_bytes();
-                let mut sample = allocate_sample(data.len())?;
+                let mut sample = self.allocate_sample(data.len(), timeout, py)?;
                 sample.copy_from_slice(data);
                 (sample, ArrowTypeInfo::byte_array(data.len()))
             } else if let Ok(arrow_array) = ArrayData::from_pyarrow(data.as_ref(py)) {
                 let total_len = required_data_size(&arrow_array);
-                let mut sample = allocate_sample(total_len)?;
+                let mut sample = self.allocate_sample(total_len, timeout, py)?;
+
+                let type_info = copy_array_into_sample(&mut sample, &arrow_array);
+
+                (sample, type_info)
+            } else if let Some((arrow_array, device)) = dlpack_to_arrow(data.as_ref(py))? {
+                parameters.device = device;
+                let total_len = required_data_size(&arrow_array);
+                let mut sample = self.allocate_sample(total_len, timeout, py)?;
 
                 let type_info = copy_array_into_sample(&mut sample, &arrow_array);
 
                 (sample, type_info)
             } else {
-                eyre::bail!("invalid `data` type, must by `PyBytes` or arrow array")
+                return Err(eyre!(
+                    "invalid `data` type, must by `PyBytes`, arrow array, or DLPack tensor"
+                )
+                .into());
             };
 
+            self.send(output, type_info, parameters, sample, py)
+        }
+
+        /// Allocates a byte sample of `len` bytes for the given output, which is filled
+        /// incrementally before it is sent:
+        /// - the optional `timeout` (in seconds) retries the allocation like for `send_output`.
+        ///
+        /// Returns a `PendingOutput`, which is filled through its `write(offset, data)` method
+        /// and then either sent through `commit(metadata=None)` or discarded through
+        /// `abort()`, e.g. when an error occurs while filling it. Dropping it discards it too.
+        ///
+        /// `e.g.:  frame = send_output.begin_output("frame", 3 * width * height)`
+        #[pyo3(signature = (output, len, timeout=None))]
+        fn begin_output(
+            &self,
+            output: &str,
+            len: usize,
+            timeout: Option<f64>,
+            py: Python,
+        ) -> PyResult<PendingOutput> {
+            let sample = self.allocate_sample(len, timeout, py)?;
+            Ok(PendingOutput {
+                callback: self.clone(),
+                output_id: output.to_owned(),
+                sample: Some(sample),
+            })
+        }
+    }
+
+    impl SendOutputCallback {
+        fn allocate_sample(
+            &self,
+            data_len: usize,
+            timeout: Option<f64>,
+            py: Python,
+        ) -> PyResult<DataSample> {
+            if data_len <= ZERO_COPY_THRESHOLD {
+                let avec: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, data_len);
+                return Ok(avec.into());
+            }
+            let deadline = timeout
+                .map(Duration::try_from_secs_f64)
+                .transpose()
+                .map_err(|err| eyre!("invalid timeout: {err}"))?
+                .map(|timeout| Instant::now() + timeout);
+            let request_sample = || -> Result<Result<DataSample>> {
+                let (tx, rx) = oneshot::channel();
+                self.events_tx
+                    .blocking_send(OperatorEvent::AllocateOutputSample {
+                        len: data_len,
+                        sample: tx,
+                    })
+                    .map_err(|_| eyre!("failed to send output to runtime"))?;
+                rx.blocking_recv()
+                    .wrap_err("failed to request output sample")
+            };
+            let mut retry_delay = MIN_RETRY_DELAY;
+            loop {
+                let err = match py.allow_threads(request_sample)? {
+                    Ok(sample) => return Ok(sample),
+                    Err(err) => err,
+                };
+                match deadline {
+                    Some(deadline) if Instant::now() + retry_delay < deadline => {
+                        py.allow_threads(|| std::thread::sleep(retry_delay));
+                        retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    }
+                    _ => {
+                        return Err(BackpressureError::new_err(format!(
+                            "failed to allocate output sample of {data_len} bytes: {err:#}"
+                        )))
+                    }
+                }
+            }
+        }
+
+        fn send(
+            &self,
+            output: &str,
+            type_info: ArrowTypeInfo,
+            mut parameters: MetadataParameters,
+            sample: DataSample,
+            py: Python,
+        ) -> PyResult<()> {
+            self.input.apply(&mut parameters);
+            let span = span!(
+                tracing::Level::TRACE,
+                "send_output",
+                output_id = field::Empty
+            );
+            span.record("output_id", output);
+
+            let cx = deserialize_context(&parameters.open_telemetry_context);
+            span.set_parent(cx);
+            let _ = span.enter();
+
             py.allow_threads(|| {
                 let event = OperatorEvent::Output {
                     output_id: output.to_owned().into(),
@@ -383,5 +951,429 @@ mod callback_impl {
 
             Ok(())
         }
+
+        fn discard(&self, sample: DataSample) {
+            // only fails if the runtime exited already
+            let _ = self
+                .events_tx
+                .blocking_send(OperatorEvent::DiscardOutputSample { sample });
+        }
+    }
+
+    /// Output sample that is filled incrementally before it is sent, see
+    /// `send_output.begin_output`.
+    #[pymethods]
+    impl PendingOutput {
+        fn __len__(&self) -> usize {
+            self.sample.as_ref().map_or(0, |sample| sample.len())
+        }
+
+        /// Copies the given bytes into the sample, starting at the given offset.
+        fn write(&mut self, offset: usize, data: &[u8]) -> PyResult<()> {
+            let Some(sample) = self.sample.as_mut() else {
+                return Err(self.finished_error());
+            };
+            let len = sample.len();
+            let Some(target) = offset
+                .checked_add(data.len())
+                .and_then(|end| sample.get_mut(offset..end))
+            else {
+                return Err(PyValueError::new_err(format!(
+                    "cannot write {} bytes at offset {offset} into output of {len} bytes",
+                    data.len(),
+                )));
+            };
+            target.copy_from_slice(data);
+            Ok(())
+        }
+
+        /// Sends the sample as a byte array, with optional dora metadata.
+        #[pyo3(signature = (metadata=None))]
+        fn commit(&mut self, metadata: Option<&PyDict>, py: Python) -> PyResult<()> {
+            let parameters = pydict_to_metadata(metadata)
+                .wrap_err("failed to parse metadata")?
+                .into_owned();
+            let sample = self.take_sample()?;
+            let type_info = ArrowTypeInfo::byte_array(sample.len());
+            self.callback
+                .send(&self.output_id, type_info, parameters, sample, py)
+        }
+
+        /// Discards the sample without sending it.
+        fn abort(&mut self, py: Python) -> PyResult<()> {
+            let sample = self.take_sample()?;
+            py.allow_threads(|| self.callback.discard(sample));
+            Ok(())
+        }
+    }
+
+    impl PendingOutput {
+        fn take_sample(&mut self) -> PyResult<DataSample> {
+            self.sample.take().ok_or_else(|| self.finished_error())
+        }
+
+        fn finished_error(&self) -> PyErr {
+            PyValueError::new_err(format!(
+                "output `{}` was already committed or aborted",
+                self.output_id
+            ))
+        }
+    }
+
+    impl Drop for PendingOutput {
+        fn drop(&mut self) {
+            if let Some(sample) = self.sample.take() {
+                self.callback.discard(sample);
+            }
+        }
+    }
+
+    /// Answer a service request:
+    /// - the first argument is the reply data as either bytes, pyarrow.Array, or a tensor
+    ///   that implements `__dlpack__`.
+    /// - the optional second argument is dora metadata.
+    ///
+    /// `e.g.:  reply(pa.array([1, 2, 3]))`
+    #[pymethods]
+    impl ServiceReplyCallback {
+        #[pyo3(signature = (data, metadata=None))]
+        fn __call__(
+            &mut self,
+            data: PyObject,
+            metadata: Option<&PyDict>,
+            py: Python,
+        ) -> PyResult<()> {
+            let mut parameters = pydict_to_metadata(metadata)
+                .wrap_err("failed to parse metadata")?
+                .into_owned();
+            let data = if let Ok(py_bytes) = data.downcast::<PyBytes>(py) {
+                UInt8Array::from(py_bytes.as_bytes().to_vec()).into_data()
+            } else if let Ok(arrow_array) = ArrayData::from_pyarrow(data.as_ref(py)) {
+                arrow_array
+            } else if let Some((arrow_array, device)) = dlpack_to_arrow(data.as_ref(py))? {
+                parameters.device = device;
+                arrow_array
+            } else {
+                return Err(eyre!(
+                    "invalid `data` type, must be `PyBytes`, arrow array, or DLPack tensor"
+                )
+                .into());
+            };
+            py.allow_threads(|| self.send(Ok((parameters, data))));
+            Ok(())
+        }
+    }
+
+    /// Implements the `io.TextIOBase` interface of a write-only stream.
+    #[pymethods]
+    impl LogDispatcher {
+        /// Returns the number of written characters.
+        fn write(&self, text: &str, py: Python) -> PyResult<usize> {
+            let forwarded = py.allow_threads(|| self.with_writer(|writer| writer.write(text)));
+            if forwarded.is_none() {
+                self.fallback.call_method1(py, "write", (text,))?;
+            }
+            Ok(text.chars().count())
+        }
+
+        fn writelines(&self, lines: &PyAny, py: Python) -> PyResult<()> {
+            for line in lines.iter()? {
+                self.write(line?.extract()?, py)?;
+            }
+            Ok(())
+        }
+
+        fn flush(&self, py: Python) -> PyResult<()> {
+            if py
+                .allow_threads(|| self.with_writer(|writer| writer.flush()))
+                .is_none()
+            {
+                self.fallback.call_method0(py, "flush")?;
+            }
+            Ok(())
+        }
+
+        /// Returns the file descriptor of the replaced stream, which bypasses
+        /// the forwarding.
+        fn fileno(&self, py: Python) -> PyResult<PyObject> {
+            self.fallback.call_method0(py, "fileno")
+        }
+
+        fn isatty(&self) -> bool {
+            false
+        }
+
+        fn readable(&self) -> bool {
+            false
+        }
+
+        fn writable(&self) -> bool {
+            true
+        }
+
+        fn seekable(&self) -> bool {
+            false
+        }
+
+        /// The stream stays open, as it is shared by all operators.
+        fn close(&self) {}
+
+        #[getter]
+        fn closed(&self) -> bool {
+            false
+        }
+
+        #[getter]
+        fn encoding(&self) -> &'static str {
+            "utf-8"
+        }
+
+        #[getter]
+        fn errors(&self) -> &'static str {
+            "strict"
+        }
+
+        #[getter]
+        fn newlines(&self) -> Option<&'static str> {
+            None
+        }
+
+        #[getter]
+        fn line_buffering(&self) -> bool {
+            true
+        }
+
+        #[getter]
+        fn mode(&self) -> &'static str {
+            "w"
+        }
+
+        #[getter]
+        fn name(&self) -> &'static str {
+            match self.stream {
+                LogStream::Stdout => "<stdout>",
+                LogStream::Stderr => "<stderr>",
+            }
+        }
+
+        #[pyo3(signature = (*_args))]
+        fn read(&self, _args: &PyTuple, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "read"))
+        }
+
+        #[pyo3(signature = (*_args))]
+        fn readline(&self, _args: &PyTuple, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "readline"))
+        }
+
+        #[pyo3(signature = (*_args))]
+        fn readlines(&self, _args: &PyTuple, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "readlines"))
+        }
+
+        #[pyo3(signature = (*_args))]
+        fn seek(&self, _args: &PyTuple, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "seek"))
+        }
+
+        fn tell(&self, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "tell"))
+        }
+
+        #[pyo3(signature = (*_args))]
+        fn truncate(&self, _args: &PyTuple, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "truncate"))
+        }
+
+        fn detach(&self, py: Python) -> PyResult<()> {
+            Err(unsupported(py, "detach"))
+        }
+    }
+
+    /// Creates an `io.UnsupportedOperation` error.
+    fn unsupported(py: Python, operation: &str) -> PyErr {
+        let error_type = py
+            .import("io")
+            .and_then(|io| io.getattr("UnsupportedOperation"))
+            .and_then(|ty| Ok(ty.downcast::<PyType>()?));
+        match error_type {
+            Ok(ty) => PyErr::from_type(ty, format!("{operation} is not supported")),
+            Err(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::IntoPyDict;
+
+    #[test]
+    fn stable_virtualenv_ids() {
+        let id = virtualenv_id("/usr/bin/python3", b"numpy==1.26.4\n");
+        assert_eq!(id, "6824ba652f347544");
+        assert_ne!(virtualenv_id("/usr/bin/python3.12", b"numpy==1.26.4\n"), id);
+    }
+
+    #[test]
+    fn scope_imports_to_operator() {
+        let dir = std::env::temp_dir().join(format!("dora-import-scope-{}", std::process::id()));
+        let site_packages = dir.join("site-packages");
+        let operator_dir = dir.join("operator");
+        std::fs::create_dir_all(&site_packages).unwrap();
+        std::fs::create_dir_all(&operator_dir).unwrap();
+        std::fs::write(site_packages.join("dora_scoped_dep.py"), "VALUE = 'venv'\n").unwrap();
+        std::fs::write(site_packages.join("dora_scoped_lazy.py"), "").unwrap();
+        std::fs::write(
+            operator_dir.join("dora_scoped_helper.py"),
+            "VALUE = 'helper'\n",
+        )
+        .unwrap();
+        let operator = operator_dir.join("dora_scoped_operator.py");
+        std::fs::write(
+            &operator,
+            "import dora_scoped_dep, dora_scoped_helper\n\
+            VALUE = dora_scoped_dep.VALUE + '/' + dora_scoped_helper.VALUE\n",
+        )
+        .unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let scope = import_scope(py, Some(&site_packages), &operator_dir).unwrap();
+            let module = load_module(scope, "dora_scoped_operator", &operator).unwrap();
+            let value: String = module.getattr("VALUE").unwrap().extract().unwrap();
+            assert_eq!(value, "venv/helper");
+
+            // the directories of the operator are only searched within its scope
+            let sys = py.import("sys").unwrap();
+            let sys_path: Vec<String> = sys.getattr("path").unwrap().extract().unwrap();
+            assert!(!sys_path.iter().any(|p| Path::new(p).starts_with(&dir)));
+            assert!(py.import("dora_scoped_lazy").is_err());
+            with_import_scope(scope, || py.import("dora_scoped_lazy").map_err(traceback)).unwrap();
+        });
+    }
+
+    #[test]
+    fn pass_params_to_operator() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                "class WithParams:\n    def __init__(self, params):\n        self.params = params\n\
+                class WithoutParams:\n    def __init__(self):\n        self.params = None\n",
+                None,
+                Some(locals),
+            )
+            .unwrap();
+            let class = |name: &str| locals.get_item(name).unwrap().unwrap();
+            let params = serde_json::json!({ "model": "yolov8n.pt", "conf": 0.4 });
+            let params = python_params(py, &params).unwrap();
+
+            let operator = construct_operator(py, class("WithParams"), params).unwrap();
+            let operator_params = operator.getattr("params").unwrap();
+            let model: String = operator_params
+                .get_item("model")
+                .unwrap()
+                .extract()
+                .unwrap();
+            let conf: f64 = operator_params.get_item("conf").unwrap().extract().unwrap();
+            assert_eq!((model.as_str(), conf), ("yolov8n.pt", 0.4));
+
+            let operator = construct_operator(py, class("WithoutParams"), params).unwrap();
+            assert!(operator.getattr("params").unwrap().is_none());
+
+            // operators without params get an empty dict
+            let empty = python_params(py, &serde_json::Value::Null).unwrap();
+            let operator = construct_operator(py, class("WithParams"), empty).unwrap();
+            assert_eq!(operator.getattr("params").unwrap().len().unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn forward_output_of_operator_thread() {
+        let (events_tx, mut events) = tokio::sync::mpsc::channel(10);
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            redirect_output(py, events_tx).unwrap();
+            let locals = PyDict::new(py);
+            py.run(
+                "import sys, threading\n\
+                print('hello')\n\
+                written = sys.stdout.write('äb')\n\
+                sys.stdout.flush()\n\
+                thread = threading.Thread(target=lambda: print('other thread'))\n\
+                thread.start()\n\
+                thread.join()\n\
+                encoding = sys.stderr.encoding\n\
+                tty = sys.stderr.isatty()\n",
+                None,
+                Some(locals),
+            )
+            .unwrap();
+            let get = |name: &str| locals.get_item(name).unwrap().unwrap();
+            assert_eq!(get("written").extract::<usize>().unwrap(), 2);
+            assert_eq!(get("encoding").extract::<String>().unwrap(), "utf-8");
+            assert!(!get("tty").extract::<bool>().unwrap());
+        });
+
+        let mut messages = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            let OperatorEvent::Log { message, .. } = event else {
+                panic!("unexpected event");
+            };
+            messages.push(message);
+        }
+        // the output of other threads is not attributed to the operator
+        assert_eq!(messages, ["hello", "äb"]);
+    }
+
+    #[test]
+    fn commit_and_abort_pending_outputs() {
+        let (events_tx, mut events) = tokio::sync::mpsc::channel(10);
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let send_output = Py::new(
+                py,
+                SendOutputCallback {
+                    events_tx,
+                    input: Default::default(),
+                },
+            )
+            .unwrap();
+            let locals = [("send_output", send_output)].into_py_dict(py);
+            py.run(
+                "aborted = send_output.begin_output('image', 4)\n\
+                aborted.write(0, b'ab')\n\
+                try:\n    aborted.write(3, b'ab')\n    raise AssertionError('no error')\n\
+                except ValueError:\n    pass\n\
+                aborted.abort()\n\
+                committed = send_output.begin_output('image', 2)\n\
+                committed.write(0, b'cd')\n\
+                committed.commit()\n\
+                send_output.begin_output('image', 1)\n",
+                None,
+                Some(locals),
+            )
+            .map_err(traceback)
+            .unwrap();
+        });
+
+        let mut next = || events.try_recv().unwrap();
+        assert!(
+            matches!(next(), OperatorEvent::DiscardOutputSample { sample } if sample.len() == 4)
+        );
+        let OperatorEvent::Output {
+            output_id, data, ..
+        } = next()
+        else {
+            panic!("expected output");
+        };
+        assert_eq!(output_id, DataId::from("image".to_owned()));
+        assert_eq!(data.as_deref(), Some(&b"cd"[..]));
+        // dropped outputs are discarded too
+        assert!(
+            matches!(next(), OperatorEvent::DiscardOutputSample { sample } if sample.len() == 1)
+        );
+        assert!(events.try_recv().is_err());
     }
 }
@@ -1,22 +1,24 @@
-use super::{OperatorEvent, StopReason};
+use super::{InputContext, OperatorEvent, StopReason};
 use aligned_vec::{AVec, ConstAlign};
 use dora_core::{
     adjust_shared_library_path,
-    config::{DataId, NodeId, OperatorId},
+    config::{DataId, InputQueueConfig, LatencyPath, NodeId, OperatorId},
     descriptor::source_is_url,
 };
 use dora_download::download_file;
 use dora_node_api::{
     arrow_utils::{copy_array_into_sample, required_data_size},
-    Event, MetadataParameters,
+    ArrowData, Event, MetadataParameters,
 };
 use dora_operator_api_types::{
-    safer_ffi::closure::ArcDynFn1, DoraDropOperator, DoraInitOperator, DoraInitResult, DoraOnEvent,
-    DoraResult, DoraStatus, Metadata, OnEventResult, Output, SendOutput,
+    safer_ffi::closure::ArcDynFn1, DoraDropOperator, DoraInitContext, DoraInitOperator,
+    DoraInitOperatorWithContext, DoraInitResult, DoraOnEvent, DoraResult, DoraStatus, EnvVar,
+    Metadata, OnEventResult, Output, SendOutput,
 };
 use eyre::{bail, eyre, Context, Result};
 use libloading::Symbol;
 use std::{
+    collections::{BTreeMap, VecDeque},
     ffi::c_void,
     panic::{catch_unwind, AssertUnwindSafe},
     path::Path,
@@ -25,14 +27,20 @@ use std::{
 use tokio::sync::{mpsc::Sender, oneshot};
 use tracing::{field, span};
 
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     node_id: &NodeId,
     operator_id: &OperatorId,
     source: &str,
+    env: BTreeMap<String, String>,
+    params: &serde_json::Value,
+    input_config: &BTreeMap<DataId, InputQueueConfig>,
     events_tx: Sender<OperatorEvent>,
     incoming_events: flume::Receiver<Event>,
     init_done: oneshot::Sender<Result<()>>,
 ) -> eyre::Result<()> {
+    let input_config =
+        serde_json::to_string(input_config).wrap_err("failed to serialize input config")?;
     let path = if source_is_url(source) {
         let target_path = adjust_shared_library_path(
             &Path::new("build")
@@ -60,6 +68,9 @@ pub fn run(
 
         let operator = SharedLibraryOperator {
             incoming_events,
+            env,
+            params: params.to_string(),
+            input_config,
             bindings,
             events_tx: events_tx.clone(),
         };
@@ -83,6 +94,11 @@ pub fn run(
 
 struct SharedLibraryOperator<'lib> {
     incoming_events: flume::Receiver<Event>,
+    env: BTreeMap<String, String>,
+    /// JSON-encoded operator params.
+    params: String,
+    /// JSON-encoded queue configuration of the operator inputs.
+    input_config: String,
     events_tx: Sender<OperatorEvent>,
 
     bindings: Bindings<'lib>,
@@ -94,7 +110,25 @@ impl<'lib> SharedLibraryOperator<'lib> {
             let DoraInitResult {
                 result,
                 operator_context,
-            } = unsafe { (self.bindings.init_operator.init_operator)() };
+            } = match &self.bindings.init_operator {
+                InitOperator::WithContext(init) => {
+                    let env: Vec<_> = self
+                        .env
+                        .iter()
+                        .map(|(key, value)| EnvVar {
+                            key: key.clone().into(),
+                            value: value.clone().into(),
+                        })
+                        .collect();
+                    let context = DoraInitContext {
+                        env: env.into(),
+                        params: self.params.clone().into(),
+                        input_config: self.input_config.clone().into(),
+                    };
+                    unsafe { (init.init_operator_with_context)(&context) }
+                }
+                InitOperator::WithoutContext(init) => unsafe { (init.init_operator)() },
+            };
             let raw = match result.error {
                 Some(error) => {
                     let _ = init_done.send(Err(eyre!(error.to_string())));
@@ -110,7 +144,8 @@ impl<'lib> SharedLibraryOperator<'lib> {
 
         let _ = init_done.send(Ok(()));
 
-        let send_output_closure = Arc::new(move |output: Output| {
+        let reply_tx = self.events_tx.clone();
+        let send_output = move |input: &InputContext, output: Output| {
             let Output {
                 id: output_id,
                 data_array,
@@ -119,10 +154,11 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     open_telemetry_context,
                 },
             } = output;
-            let parameters = MetadataParameters {
+            let mut parameters = MetadataParameters {
                 open_telemetry_context: open_telemetry_context.into(),
                 ..Default::default()
             };
+            input.apply(&mut parameters);
 
             let arrow_array = match arrow::ffi::from_ffi(data_array, &schema) {
                 Ok(a) => a,
@@ -150,12 +186,29 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 Ok(()) => DoraResult::SUCCESS,
                 Err(_) => DoraResult::from_error("runtime process closed unexpectedly".into()),
             }
+        };
+        // the closure is called from foreign code, so we must not unwind through it
+        let send_output_closure = Arc::new(move |input: &InputContext, output: Output| {
+            catch_unwind(AssertUnwindSafe(|| send_output(input, output))).unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".into());
+                DoraResult::from_error(format!("send_output panicked: {message}"))
+            })
         });
 
+        // inputs of synced events, which are passed to the operator one by one
+        let mut pending_inputs = VecDeque::new();
         let reason = loop {
             #[allow(unused_mut)]
-            let Ok(mut event) = self.incoming_events.recv() else {
-                break StopReason::InputsClosed;
+            let mut event = match pending_inputs.pop_front() {
+                Some(event) => event,
+                None => match self.incoming_events.recv() {
+                    Ok(event) => event,
+                    Err(_) => break StopReason::InputsClosed,
+                },
             };
 
             let span = span!(tracing::Level::TRACE, "on_event", input_id = field::Empty);
@@ -180,12 +233,14 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 metadata.parameters.open_telemetry_context = string_cx;
             }
 
+            let input = InputContext::of(&event);
             let mut operator_event = match event {
                 Event::Stop => dora_operator_api_types::RawEvent {
                     input: None,
                     input_closed: None,
                     stop: true,
                     error: None,
+                    deadline_missed: None,
                 },
                 Event::Input {
                     id: input_id,
@@ -210,6 +265,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                         input_closed: None,
                         stop: false,
                         error: None,
+                        deadline_missed: None,
                     }
                 }
                 Event::InputClosed { id: input_id } => dora_operator_api_types::RawEvent {
@@ -217,16 +273,91 @@ impl<'lib> SharedLibraryOperator<'lib> {
                     input: None,
                     stop: false,
                     error: None,
+                    deadline_missed: None,
                 },
                 Event::Reload { .. } => {
                     // Reloading shared lib operator is not supported. See: https://github.com/dora-rs/dora/pull/239#discussion_r1154313139
                     continue;
                 }
+                Event::SyncedInput { inputs } => {
+                    // the shared library operator API has no combined event, so
+                    // the aligned inputs are delivered back to back instead
+                    pending_inputs.extend(
+                        inputs
+                            .into_iter()
+                            .map(|(id, (metadata, data))| Event::Input { id, metadata, data }),
+                    );
+                    continue;
+                }
+                Event::InputBatch { id, metadata, data } => {
+                    // batches are delivered as separate inputs, like synced inputs
+                    let Some(list) = data.as_any().downcast_ref::<arrow::array::ListArray>() else {
+                        tracing::warn!("batch of input `{id}` is not a list array");
+                        continue;
+                    };
+                    pending_inputs.extend(metadata.into_iter().enumerate().map(
+                        |(index, metadata)| Event::Input {
+                            id: id.clone(),
+                            metadata,
+                            data: ArrowData(list.value(index)),
+                        },
+                    ));
+                    continue;
+                }
+                Event::DeadlineMissed { id, elapsed } => {
+                    let deadline_missed = dora_operator_api_types::DeadlineMissed {
+                        input_id: id.to_string().into(),
+                        elapsed_ns: elapsed.as_nanos().try_into().unwrap_or(u64::MAX),
+                    };
+                    dora_operator_api_types::RawEvent {
+                        input: None,
+                        input_closed: None,
+                        stop: false,
+                        error: None,
+                        deadline_missed: Some(Box::new(deadline_missed).into()),
+                    }
+                }
+                Event::LatencyBudgetExceeded { id, alert } => {
+                    // not supported by the shared library operator API yet
+                    tracing::debug!(
+                        "input `{id}` exceeded the latency budget of `{}` ({:?})",
+                        LatencyPath(alert.path),
+                        alert.latency
+                    );
+                    continue;
+                }
+                Event::ParameterUpdate { key, .. } => {
+                    // rejected by the coordinator, as the shared library
+                    // operator API doesn't support live parameters yet
+                    tracing::warn!("ignoring update of parameter `{key}`");
+                    continue;
+                }
+                Event::OutputUnsubscribed { id } | Event::OutputSubscribed { id } => {
+                    // not supported by the shared library operator API yet
+                    tracing::debug!("ignoring subscription change of output `{id}`");
+                    continue;
+                }
+                Event::ServiceRequest {
+                    service, request, ..
+                } => {
+                    // not supported by the shared library operator API yet
+                    let reply = OperatorEvent::ServiceReply {
+                        request,
+                        reply: Err(format!(
+                            "shared library operators don't support services (`{service}`)"
+                        )),
+                    };
+                    if reply_tx.blocking_send(reply).is_err() {
+                        tracing::warn!("failed to send service error to runtime");
+                    }
+                    continue;
+                }
                 Event::Error(err) => dora_operator_api_types::RawEvent {
                     error: Some(err.into()),
                     input_closed: None,
                     input: None,
                     stop: false,
+                    deadline_missed: None,
                 },
                 other => {
                     tracing::warn!("unexpected event: {other:?}");
@@ -234,8 +365,12 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 }
             };
 
+            // outputs of the operator inherit the parameters of the current input
+            let send_output_closure = send_output_closure.clone();
             let send_output = SendOutput {
-                send_output: ArcDynFn1::new(send_output_closure.clone()),
+                send_output: ArcDynFn1::new(Arc::new(move |output: Output| {
+                    send_output_closure(&input, output)
+                })),
             };
             let OnEventResult {
                 result: DoraResult { error },
@@ -248,7 +383,8 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 )
             };
             match error {
-                Some(error) => bail!("on_input failed: {}", *error),
+                // exceptions of C++ operators are converted to errors by `dora_operator.hpp`
+                Some(error) => bail!("on_event failed: {}", *error),
                 None => match status {
                     DoraStatus::Continue => {}
                     DoraStatus::Stop => break StopReason::ExplicitStop,
@@ -267,12 +403,15 @@ struct OperatorContext<'lib> {
 
 impl<'lib> Drop for OperatorContext<'lib> {
     fn drop(&mut self) {
-        unsafe { (self.drop_fn.drop_operator)(self.raw) };
+        let result = unsafe { (self.drop_fn.drop_operator)(self.raw) };
+        if let Err(err) = result.into_result() {
+            tracing::warn!("failed to drop operator: {err}");
+        }
     }
 }
 
 struct Bindings<'lib> {
-    init_operator: Symbol<'lib, DoraInitOperator>,
+    init_operator: InitOperator<'lib>,
     drop_operator: Symbol<'lib, DoraDropOperator>,
     on_event: Symbol<'lib, DoraOnEvent>,
 }
@@ -280,10 +419,16 @@ struct Bindings<'lib> {
 impl<'lib> Bindings<'lib> {
     fn init(library: &'lib libloading::Library) -> Result<Self, eyre::Error> {
         let bindings = unsafe {
+            let init_operator = match library.get(b"dora_init_operator_with_context") {
+                Ok(init) => InitOperator::WithContext(init),
+                Err(_) => InitOperator::WithoutContext(
+                    library
+                        .get(b"dora_init_operator")
+                        .wrap_err("failed to get `dora_init_operator`")?,
+                ),
+            };
             Bindings {
-                init_operator: library
-                    .get(b"dora_init_operator")
-                    .wrap_err("failed to get `dora_init_operator`")?,
+                init_operator,
                 drop_operator: library
                     .get(b"dora_drop_operator")
                     .wrap_err("failed to get `dora_drop_operator`")?,
@@ -295,3 +440,8 @@ impl<'lib> Bindings<'lib> {
         Ok(bindings)
     }
 }
+
+enum InitOperator<'lib> {
+    WithContext(Symbol<'lib, DoraInitOperatorWithContext>),
+    WithoutContext(Symbol<'lib, DoraInitOperator>),
+}
@@ -0,0 +1,938 @@
+//! Runs operators with `isolation: process` in forked worker processes.
+//!
+//! All Python operators of a runtime node share a single GIL, so a busy operator
+//! slows down the others. Isolated operators run in their own process instead,
+//! which is forked from the runtime before any other thread is started. The
+//! worker runs the operator through [`run_operator`], like the runtime does for
+//! other operators, and forwards its events over two shared memory channels:
+//!
+//! - on the _events_ channel, the worker requests the next event whenever the
+//!   operator is ready for it, so the queue of the operator stays in the runtime
+//!   and input queue sizes are applied as usual
+//! - on the _operator events_ channel, the worker sends the outputs, logs, and
+//!   the final status of the operator to the runtime
+//!
+//! Data that is larger than [`ZERO_COPY_THRESHOLD`] is passed in separate shared
+//! memory regions, which are reused for later messages.
+
+use super::{run_operator, LogStream, OperatorEvent, StopReason};
+use aligned_vec::{AVec, ConstAlign};
+use dora_core::{
+    config::{DataId, NodeId, OperatorId},
+    daemon_messages::DataflowId,
+    descriptor::{Descriptor, OperatorDefinition, OperatorIsolation},
+    message::{ArrowTypeInfo, Metadata, MetadataParameters, MetadataValue},
+};
+use dora_node_api::{
+    arrow::array::{make_array, Array, ArrayData},
+    arrow_utils::{copy_array_into_sample, required_data_size},
+    ArrowData, DataSample, Event, LatencyAlert, RawData, ServiceRequestId, ZERO_COPY_THRESHOLD,
+};
+use eyre::{bail, eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use shared_memory_server::{Shmem, ShmemClient, ShmemConf, ShmemServer};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
+
+/// Size of the shared memory regions of the channels, which only carry
+/// metadata and small messages.
+const CHANNEL_SIZE: usize = 1 << 20;
+/// Interval in which workers check whether the runtime is still running.
+const PARENT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of free regions that a [`RegionPool`] keeps for reuse.
+const MAX_FREE_REGIONS: usize = 4;
+/// Number of regions that a [`RegionCache`] keeps open.
+const MAX_OPEN_REGIONS: usize = 8;
+
+/// Forks a worker process for every operator with `isolation: process`.
+///
+/// Must be called before the runtime spawns any thread, as the worker processes
+/// only contain the calling thread.
+pub fn fork_workers(
+    dataflow_id: DataflowId,
+    node_id: &NodeId,
+    operators: &[OperatorDefinition],
+    operator_env: &BTreeMap<OperatorId, BTreeMap<String, String>>,
+    dataflow_descriptor: &Descriptor,
+) -> Result<HashMap<OperatorId, Worker>> {
+    let mut workers = HashMap::new();
+    for operator in operators {
+        if operator.config.isolation != OperatorIsolation::Process {
+            continue;
+        }
+        let env = operator_env.get(&operator.id).cloned().unwrap_or_default();
+        let worker = fork_worker(&operator.id, fork, |region_ids| {
+            run_worker(region_ids, |incoming_events, events_tx, init_done_tx| {
+                run_operator(
+                    dataflow_id,
+                    node_id,
+                    operator.clone(),
+                    incoming_events,
+                    events_tx,
+                    init_done_tx,
+                    dataflow_descriptor,
+                    env,
+                )
+            })
+        })
+        .wrap_err_with(|| {
+            format!(
+                "failed to fork worker process for operator `{}`",
+                operator.id
+            )
+        })?;
+        workers.insert(operator.id.clone(), worker);
+    }
+    Ok(workers)
+}
+
+/// Forks a worker process, which calls `run` with the IDs of the regions of its
+/// channels and exits afterwards.
+fn fork_worker(
+    operator_id: &OperatorId,
+    fork: impl FnOnce() -> Result<i32>,
+    run: impl FnOnce(&[String; 2]) -> Result<()>,
+) -> Result<Worker> {
+    let events_region = ShmemConf::new()
+        .size(CHANNEL_SIZE)
+        .create()
+        .wrap_err("failed to allocate worker events region")?;
+    let operator_events_region = ShmemConf::new()
+        .size(CHANNEL_SIZE)
+        .create()
+        .wrap_err("failed to allocate worker operator events region")?;
+    let region_ids = [
+        events_region.get_os_id().to_owned(),
+        operator_events_region.get_os_id().to_owned(),
+    ];
+    let events = unsafe { ShmemServer::new(events_region) }
+        .wrap_err("failed to create worker events channel")?;
+    let operator_events = unsafe { ShmemServer::new(operator_events_region) }
+        .wrap_err("failed to create worker operator events channel")?;
+
+    let parent = std::process::id();
+    let pid = fork()?;
+    if pid == 0 {
+        // never returns, so the shared memory regions of the runtime are not
+        // dropped (and unlinked) in the worker
+        let result = exit_with_parent(parent).and_then(|()| run(&region_ids));
+        let code = match result {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("worker process failed: {err:?}");
+                1
+            }
+        };
+        std::process::exit(code)
+    }
+    tracing::debug!("forked worker process {pid} for operator `{operator_id}`");
+    Ok(Worker {
+        operator_id: operator_id.clone(),
+        pid,
+        events,
+        operator_events,
+        region_ids,
+    })
+}
+
+/// Makes the worker exit when the runtime exits. Otherwise it would wait for
+/// its next event forever.
+#[cfg(unix)]
+fn exit_with_parent(parent: u32) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: only changes a setting of the calling process
+        if unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .wrap_err("failed to set parent death signal");
+        }
+    }
+    // the parent might have exited before the signal was set up, and other
+    // platforms don't support the signal at all
+    std::thread::Builder::new()
+        .name("parent-watcher".into())
+        .spawn(move || loop {
+            if std::os::unix::process::parent_id() != parent {
+                std::process::exit(1);
+            }
+            std::thread::sleep(PARENT_CHECK_INTERVAL);
+        })
+        .wrap_err("failed to spawn parent watcher thread")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn exit_with_parent(_parent: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Forks the process through Python's `os.fork`, which takes care of the
+/// interpreter state, e.g. of the GIL.
+#[cfg(feature = "python")]
+fn fork() -> Result<i32> {
+    use pyo3::Python;
+
+    Python::with_gil(|py| {
+        py.import("os")
+            .and_then(|os| os.call_method0("fork"))
+            .and_then(|pid| pid.extract())
+    })
+    .map_err(|err| eyre!("{err}"))
+}
+
+#[cfg(not(feature = "python"))]
+fn fork() -> Result<i32> {
+    bail!("`isolation: process` is only supported for Python operators")
+}
+
+/// The runtime side of a worker process.
+pub struct Worker {
+    operator_id: OperatorId,
+    pid: i32,
+    events: ShmemServer<WorkerRequest, WorkerReply>,
+    operator_events: ShmemServer<WorkerRequest, WorkerReply>,
+    region_ids: [String; 2],
+}
+
+impl Worker {
+    /// Forwards the incoming events to the worker and the events of the worker
+    /// operator to `events_tx`, until the worker exits.
+    pub fn run(
+        self,
+        incoming_events: flume::Receiver<Event>,
+        events_tx: mpsc::Sender<OperatorEvent>,
+        init_done: oneshot::Sender<Result<()>>,
+    ) -> Result<()> {
+        let Worker {
+            operator_id,
+            pid,
+            events,
+            mut operator_events,
+            region_ids,
+        } = self;
+
+        let event_thread = std::thread::Builder::new()
+            .name(format!("{operator_id}-events"))
+            .spawn(move || serve_events(events, incoming_events))
+            .wrap_err("failed to spawn worker event thread")?;
+        let exit_watcher = std::thread::Builder::new()
+            .name(format!("{operator_id}-watcher"))
+            .spawn(move || {
+                let status = wait_for_exit(pid);
+                // wake up the channels in case the worker exited without closing them
+                for region_id in &region_ids {
+                    disconnect(region_id);
+                }
+                status
+            })
+            .wrap_err("failed to spawn worker exit watcher")?;
+
+        let mut regions = RegionCache::default();
+        let mut init_done = Some(init_done);
+        let mut finished = false;
+        while let Some(request) = operator_events.listen()? {
+            let event = match request {
+                WorkerRequest::InitDone(result) => {
+                    if let Some(init_done) = init_done.take() {
+                        let _ = init_done.send(result.map_err(|err| eyre!(err)));
+                    }
+                    None
+                }
+                WorkerRequest::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data,
+                } => Some(OperatorEvent::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data: data
+                        .map(|data| data.read(&mut regions))
+                        .transpose()?
+                        .map(DataSample::from),
+                }),
+                WorkerRequest::ServiceReply { request, reply } => {
+                    let reply = match reply {
+                        Ok((parameters, data)) => Ok((parameters, data.into_array(&mut regions)?)),
+                        Err(err) => Err(err),
+                    };
+                    Some(OperatorEvent::ServiceReply { request, reply })
+                }
+                WorkerRequest::Log { stream, message } => {
+                    Some(OperatorEvent::Log { stream, message })
+                }
+                WorkerRequest::Error(err) => {
+                    finished = true;
+                    Some(OperatorEvent::Error(eyre!(err)))
+                }
+                WorkerRequest::Panic(message) => {
+                    finished = true;
+                    Some(OperatorEvent::Panic(Box::new(message)))
+                }
+                WorkerRequest::Finished { reason } => {
+                    finished = true;
+                    Some(OperatorEvent::Finished { reason })
+                }
+                WorkerRequest::NextEvent => bail!("unexpected `NextEvent` on operator channel"),
+            };
+            if let Some(event) = event {
+                if events_tx.blocking_send(event).is_err() {
+                    tracing::warn!("runtime stopped listening to operator `{operator_id}`");
+                }
+            }
+            operator_events.send_reply(&WorkerReply::Ack)?;
+        }
+
+        let status = match exit_watcher.join() {
+            Ok(status) => status?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        };
+        if !finished {
+            let err =
+                eyre!("worker process of operator `{operator_id}` exited unexpectedly ({status})");
+            match init_done.take() {
+                Some(init_done) => {
+                    let _ = init_done.send(Err(err));
+                }
+                None => {
+                    let _ = events_tx.blocking_send(OperatorEvent::Error(err));
+                }
+            }
+        }
+        drop(events_tx);
+        match event_thread.join() {
+            Ok(result) => result.wrap_err("failed to forward events to worker")?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+        Ok(())
+    }
+}
+
+/// Answers the `NextEvent` requests of the worker with the incoming events.
+fn serve_events(
+    mut server: ShmemServer<WorkerRequest, WorkerReply>,
+    incoming_events: flume::Receiver<Event>,
+) -> Result<()> {
+    // the regions of the last event are in use until the worker requests the
+    // next one, which means that it has copied the data
+    let mut regions = RegionPool::default();
+    while let Some(request) = server.listen()? {
+        regions.release();
+        let WorkerRequest::NextEvent = request else {
+            bail!("unexpected request on events channel: {request:?}");
+        };
+        let event = match incoming_events.recv() {
+            Ok(event) => Some(WorkerEvent::new(event, &mut regions)?),
+            Err(flume::RecvError::Disconnected) => None,
+        };
+        if server.send_reply(&WorkerReply::Event(event)).is_err() {
+            // the worker exited
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the worker side of the channels with the given regions, and the
+/// operator through `run_operator`.
+fn run_worker(
+    region_ids: &[String; 2],
+    run_operator: impl FnOnce(
+        flume::Receiver<Event>,
+        mpsc::Sender<OperatorEvent>,
+        oneshot::Sender<Result<()>>,
+    ) -> Result<()>,
+) -> Result<()> {
+    let mut events = connect(&region_ids[0]).wrap_err("failed to open worker events channel")?;
+    let mut operator_events =
+        connect(&region_ids[1]).wrap_err("failed to open worker operator events channel")?;
+
+    // the operator only receives the next event once it is ready for it
+    let (incoming_tx, incoming_rx) = flume::bounded(0);
+    let (events_tx, mut events_rx) = mpsc::channel(1);
+    let (init_done_tx, init_done_rx) = oneshot::channel::<Result<()>>();
+
+    let receiver = std::thread::spawn(move || -> Result<()> {
+        let mut regions = RegionCache::default();
+        loop {
+            let event = match events.request(&WorkerRequest::NextEvent)? {
+                WorkerReply::Event(Some(event)) => event.into_event(&mut regions)?,
+                WorkerReply::Event(None) => break,
+                other => bail!("unexpected reply on events channel: {other:?}"),
+            };
+            if incoming_tx.send(event).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+
+    let sender = std::thread::spawn(move || -> Result<()> {
+        let init_result = match init_done_rx.blocking_recv() {
+            Ok(result) => result.map_err(|err| format!("{err:?}")),
+            Err(_) => Err("operator exited during init".to_owned()),
+        };
+        operator_events.request(&WorkerRequest::InitDone(init_result))?;
+        let mut regions = RegionPool::default();
+        while let Some(event) = events_rx.blocking_recv() {
+            let request = match event {
+                OperatorEvent::AllocateOutputSample { len, sample } => {
+                    let data: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, len);
+                    let _ = sample.send(Ok(data.into()));
+                    continue;
+                }
+                OperatorEvent::DiscardOutputSample { .. } => continue,
+                OperatorEvent::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data,
+                } => WorkerRequest::Output {
+                    output_id,
+                    type_info,
+                    parameters,
+                    data: data
+                        .map(|data| {
+                            WorkerData::new(data.len(), &mut regions, |buffer| {
+                                buffer.copy_from_slice(&data[..])
+                            })
+                            .map(|(data, ())| data)
+                        })
+                        .transpose()?,
+                },
+                OperatorEvent::ServiceReply { request, reply } => WorkerRequest::ServiceReply {
+                    request,
+                    reply: match reply {
+                        Ok((parameters, data)) => {
+                            Ok((parameters, WorkerArray::new(&data, &mut regions)?))
+                        }
+                        Err(err) => Err(err),
+                    },
+                },
+                OperatorEvent::Log { stream, message } => WorkerRequest::Log { stream, message },
+                OperatorEvent::Error(err) => WorkerRequest::Error(format!("{err:?}")),
+                OperatorEvent::Panic(payload) => WorkerRequest::Panic(panic_message(&*payload)),
+                OperatorEvent::Finished { reason } => WorkerRequest::Finished { reason },
+            };
+            operator_events.request(&request)?;
+            // the runtime has copied the data when it acknowledges the request
+            regions.release();
+        }
+        Ok(())
+    });
+
+    // Python operators expect to run on the main thread, like in the runtime
+    let error_tx = events_tx.clone();
+    if let Err(err) = run_operator(incoming_rx, events_tx, init_done_tx) {
+        let _ = error_tx.blocking_send(OperatorEvent::Error(err));
+    }
+    drop(error_tx);
+
+    match sender.join() {
+        Ok(result) => result.wrap_err("failed to send operator events to runtime")?,
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+    // the receiver thread might still wait for the next event, so it's not joined
+    // if the operator stopped on its own
+    if receiver.is_finished() {
+        match receiver.join() {
+            Ok(result) => result.wrap_err("failed to receive events from runtime")?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+    Ok(())
+}
+
+fn connect(region_id: &str) -> Result<ShmemClient<WorkerRequest, WorkerReply>> {
+    let region = ShmemConf::new()
+        .os_id(region_id)
+        .open()
+        .wrap_err("failed to open shared memory region")?;
+    unsafe { ShmemClient::new(region, None) }
+}
+
+/// Wakes up the runtime side of the given channel by connecting to it and
+/// disconnecting again.
+fn disconnect(region_id: &str) {
+    if let Ok(client) = connect(region_id) {
+        drop(client);
+    }
+}
+
+#[cfg(unix)]
+fn wait_for_exit(pid: i32) -> Result<std::process::ExitStatus> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: the worker is a child process of the runtime, which doesn't reap
+    // its children otherwise
+    let result = unsafe { libc::waitpid(pid, &mut status, 0) };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error())
+            .wrap_err_with(|| format!("failed to wait for worker process {pid}"));
+    }
+    Ok(std::process::ExitStatus::from_raw(status))
+}
+
+#[cfg(not(unix))]
+fn wait_for_exit(_pid: i32) -> Result<std::process::ExitStatus> {
+    bail!("worker processes are only supported on Unix platforms")
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_owned()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerRequest {
+    NextEvent,
+    InitDone(Result<(), String>),
+    Output {
+        output_id: DataId,
+        type_info: ArrowTypeInfo,
+        parameters: MetadataParameters,
+        data: Option<WorkerData>,
+    },
+    ServiceReply {
+        request: ServiceRequestId,
+        reply: Result<(MetadataParameters, WorkerArray), String>,
+    },
+    Log {
+        stream: LogStream,
+        message: String,
+    },
+    Error(String),
+    Panic(String),
+    Finished {
+        reason: StopReason,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerReply {
+    /// The next event, or `None` if the inputs of the operator are closed.
+    Event(Option<WorkerEvent>),
+    Ack,
+}
+
+/// Serializable version of [`Event`].
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerEvent {
+    Stop,
+    Reload {
+        operator_id: Option<OperatorId>,
+    },
+    Input {
+        id: DataId,
+        metadata: Metadata,
+        data: WorkerArray,
+    },
+    InputClosed {
+        id: DataId,
+    },
+    SyncedInput {
+        inputs: BTreeMap<DataId, (Metadata, WorkerArray)>,
+    },
+    InputBatch {
+        id: DataId,
+        metadata: Vec<Metadata>,
+        data: WorkerArray,
+    },
+    ServiceRequest {
+        service: DataId,
+        request: ServiceRequestId,
+        metadata: Metadata,
+        data: WorkerArray,
+    },
+    DeadlineMissed {
+        id: DataId,
+        elapsed: Duration,
+    },
+    LatencyBudgetExceeded {
+        id: DataId,
+        alert: LatencyAlert,
+    },
+    ParameterUpdate {
+        key: String,
+        value: MetadataValue,
+    },
+    OutputUnsubscribed {
+        id: DataId,
+    },
+    OutputSubscribed {
+        id: DataId,
+    },
+    Error(String),
+}
+
+impl WorkerEvent {
+    fn new(event: Event, regions: &mut RegionPool) -> Result<Self> {
+        let event = match event {
+            Event::Stop => WorkerEvent::Stop,
+            Event::Reload { operator_id } => WorkerEvent::Reload { operator_id },
+            Event::Input { id, metadata, data } => WorkerEvent::Input {
+                id,
+                metadata,
+                data: WorkerArray::new(&data.to_data(), regions)?,
+            },
+            Event::InputClosed { id } => WorkerEvent::InputClosed { id },
+            Event::SyncedInput { inputs } => WorkerEvent::SyncedInput {
+                inputs: inputs
+                    .into_iter()
+                    .map(|(id, (metadata, data))| {
+                        let data = WorkerArray::new(&data.to_data(), regions)?;
+                        Ok((id, (metadata, data)))
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            Event::InputBatch { id, metadata, data } => WorkerEvent::InputBatch {
+                id,
+                metadata,
+                data: WorkerArray::new(&data.to_data(), regions)?,
+            },
+            Event::ServiceRequest {
+                service,
+                request,
+                metadata,
+                data,
+            } => WorkerEvent::ServiceRequest {
+                service,
+                request,
+                metadata,
+                data: WorkerArray::new(&data.to_data(), regions)?,
+            },
+            Event::DeadlineMissed { id, elapsed } => WorkerEvent::DeadlineMissed { id, elapsed },
+            Event::LatencyBudgetExceeded { id, alert } => {
+                WorkerEvent::LatencyBudgetExceeded { id, alert }
+            }
+            Event::ParameterUpdate { key, value } => WorkerEvent::ParameterUpdate { key, value },
+            Event::OutputUnsubscribed { id } => WorkerEvent::OutputUnsubscribed { id },
+            Event::OutputSubscribed { id } => WorkerEvent::OutputSubscribed { id },
+            Event::Error(err) => WorkerEvent::Error(err),
+            other => bail!("event is not supported by worker processes: {other:?}"),
+        };
+        Ok(event)
+    }
+
+    fn into_event(self, regions: &mut RegionCache) -> Result<Event> {
+        let event = match self {
+            WorkerEvent::Stop => Event::Stop,
+            WorkerEvent::Reload { operator_id } => Event::Reload { operator_id },
+            WorkerEvent::Input { id, metadata, data } => Event::Input {
+                id,
+                metadata,
+                data: data.into_arrow_data(regions)?,
+            },
+            WorkerEvent::InputClosed { id } => Event::InputClosed { id },
+            WorkerEvent::SyncedInput { inputs } => Event::SyncedInput {
+                inputs: inputs
+                    .into_iter()
+                    .map(|(id, (metadata, data))| {
+                        Ok((id, (metadata, data.into_arrow_data(regions)?)))
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            WorkerEvent::InputBatch { id, metadata, data } => Event::InputBatch {
+                id,
+                metadata,
+                data: data.into_arrow_data(regions)?,
+            },
+            WorkerEvent::ServiceRequest {
+                service,
+                request,
+                metadata,
+                data,
+            } => Event::ServiceRequest {
+                service,
+                request,
+                metadata,
+                data: data.into_arrow_data(regions)?,
+            },
+            WorkerEvent::DeadlineMissed { id, elapsed } => Event::DeadlineMissed { id, elapsed },
+            WorkerEvent::LatencyBudgetExceeded { id, alert } => {
+                Event::LatencyBudgetExceeded { id, alert }
+            }
+            WorkerEvent::ParameterUpdate { key, value } => Event::ParameterUpdate { key, value },
+            WorkerEvent::OutputUnsubscribed { id } => Event::OutputUnsubscribed { id },
+            WorkerEvent::OutputSubscribed { id } => Event::OutputSubscribed { id },
+            WorkerEvent::Error(err) => Event::Error(err),
+        };
+        Ok(event)
+    }
+}
+
+/// An Arrow array in the layout of [`copy_array_into_sample`].
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerArray {
+    type_info: ArrowTypeInfo,
+    data: WorkerData,
+}
+
+impl WorkerArray {
+    fn new(array: &ArrayData, regions: &mut RegionPool) -> Result<Self> {
+        let (data, type_info) = WorkerData::new(required_data_size(array), regions, |buffer| {
+            copy_array_into_sample(buffer, array)
+        })?;
+        Ok(Self { type_info, data })
+    }
+
+    fn into_array(self, regions: &mut RegionCache) -> Result<ArrayData> {
+        RawData::Vec(self.data.read(regions)?).into_arrow_array(&self.type_info)
+    }
+
+    fn into_arrow_data(self, regions: &mut RegionCache) -> Result<ArrowData> {
+        Ok(ArrowData(make_array(self.into_array(regions)?)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerData {
+    Vec(Vec<u8>),
+    /// Data in a separate shared memory region, which is owned by the sender.
+    SharedMemory {
+        region_id: String,
+        len: usize,
+    },
+}
+
+impl WorkerData {
+    /// Allocates a buffer of the given length and fills it through `write`.
+    ///
+    /// Large buffers are allocated in a region of the given pool, which must not
+    /// be released until the receiver has read the data.
+    fn new<T>(
+        len: usize,
+        regions: &mut RegionPool,
+        write: impl FnOnce(&mut [u8]) -> T,
+    ) -> Result<(Self, T)> {
+        if len <= ZERO_COPY_THRESHOLD {
+            let mut buffer = vec![0; len];
+            let result = write(&mut buffer);
+            return Ok((Self::Vec(buffer), result));
+        }
+        let region = regions.allocate(len)?;
+        let result = write(unsafe { &mut region.as_slice_mut()[..len] });
+        let data = Self::SharedMemory {
+            region_id: region.get_os_id().to_owned(),
+            len,
+        };
+        Ok((data, result))
+    }
+
+    fn read(self, regions: &mut RegionCache) -> Result<AVec<u8, ConstAlign<128>>> {
+        match self {
+            Self::Vec(data) => Ok(AVec::from_slice(128, &data)),
+            Self::SharedMemory { region_id, len } => regions.read(&region_id, len),
+        }
+    }
+}
+
+/// Shared memory regions for data that is larger than [`ZERO_COPY_THRESHOLD`].
+///
+/// The regions are reused once the receiver has read their data, instead of
+/// creating a new region for every large message.
+#[derive(Default)]
+struct RegionPool {
+    free: Vec<Shmem>,
+    in_use: Vec<Shmem>,
+}
+
+impl RegionPool {
+    /// Returns a region with at least `len` bytes, which is in use until the
+    /// next [`Self::release`].
+    fn allocate(&mut self, len: usize) -> Result<&mut Shmem> {
+        let smallest_fitting = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, region)| region.len() >= len)
+            .min_by_key(|(_, region)| region.len())
+            .map(|(index, _)| index);
+        let region = match smallest_fitting {
+            Some(index) => self.free.swap_remove(index),
+            None => ShmemConf::new()
+                .size(len.next_power_of_two())
+                .create()
+                .wrap_err("failed to allocate shared memory for worker data")?,
+        };
+        self.in_use.push(region);
+        Ok(self.in_use.last_mut().expect("region was just added"))
+    }
+
+    /// Makes the regions that are in use available again.
+    fn release(&mut self) {
+        self.free.append(&mut self.in_use);
+        if self.free.len() > MAX_FREE_REGIONS {
+            self.free
+                .sort_by_key(|region| std::cmp::Reverse(region.len()));
+            self.free.truncate(MAX_FREE_REGIONS);
+        }
+    }
+}
+
+/// The regions of a [`RegionPool`] that the receiver has opened.
+#[derive(Default)]
+struct RegionCache {
+    open: HashMap<String, Shmem>,
+}
+
+impl RegionCache {
+    fn read(&mut self, region_id: &str, len: usize) -> Result<AVec<u8, ConstAlign<128>>> {
+        if !self.open.contains_key(region_id) {
+            if self.open.len() >= MAX_OPEN_REGIONS {
+                // the sender has dropped some of the regions
+                self.open.clear();
+            }
+            let region = ShmemConf::new()
+                .os_id(region_id)
+                .open()
+                .wrap_err("failed to open shared memory region of worker data")?;
+            self.open.insert(region_id.to_owned(), region);
+        }
+        let region = &self.open[region_id];
+        if len > region.len() {
+            bail!("worker data exceeds its shared memory region");
+        }
+        Ok(AVec::from_slice(128, unsafe { &region.as_slice()[..len] }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_node_api::{arrow::array::UInt8Array, uhlc};
+
+    fn input_event(clock: &uhlc::HLC, len: usize) -> (Event, ArrayData) {
+        let array = UInt8Array::from_iter_values((0..len).map(|i| i as u8));
+        let metadata = Metadata::new(
+            clock.new_timestamp(),
+            ArrowTypeInfo::byte_array(array.len()),
+        );
+        let event = Event::Input {
+            id: DataId::from("image".to_owned()),
+            metadata,
+            data: ArrowData(make_array(array.to_data())),
+        };
+        (event, array.to_data())
+    }
+
+    #[test]
+    fn transfer_input_events() {
+        let clock = uhlc::HLC::default();
+        let mut regions = RegionPool::default();
+        let mut receiver_regions = RegionCache::default();
+        for len in [16, 2 * ZERO_COPY_THRESHOLD, 2 * ZERO_COPY_THRESHOLD] {
+            let (event, array) = input_event(&clock, len);
+
+            regions.release();
+            let event = WorkerEvent::new(event, &mut regions).unwrap();
+            assert_eq!(regions.in_use.len(), usize::from(len > ZERO_COPY_THRESHOLD));
+            // the region of the previous large event is reused
+            assert!(regions.free.is_empty());
+            let serialized = bincode::serialize(&event).unwrap();
+            let event: WorkerEvent = bincode::deserialize(&serialized).unwrap();
+
+            let Event::Input { id, data, .. } = event.into_event(&mut receiver_regions).unwrap()
+            else {
+                panic!("expected input event");
+            };
+            assert_eq!(id.as_str(), "image");
+            assert_eq!(data.to_data(), array);
+        }
+        assert_eq!(receiver_regions.open.len(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn run_worker_process() {
+        fn fork() -> Result<i32> {
+            // SAFETY: the child process only runs `run_worker` and exits
+            match unsafe { libc::fork() } {
+                -1 => Err(std::io::Error::last_os_error()).wrap_err("fork failed"),
+                pid => Ok(pid),
+            }
+        }
+
+        /// Sends every input back as output.
+        fn echo_operator(
+            incoming_events: flume::Receiver<Event>,
+            events_tx: mpsc::Sender<OperatorEvent>,
+            init_done: oneshot::Sender<Result<()>>,
+        ) -> Result<()> {
+            let _ = init_done.send(Ok(()));
+            let send = |event| {
+                events_tx
+                    .blocking_send(event)
+                    .map_err(|_| eyre!("runtime stopped listening"))
+            };
+            for event in incoming_events {
+                let Event::Input { id, metadata, data } = event else {
+                    continue;
+                };
+                let array = data.to_data();
+                let mut sample = AVec::__from_elem(128, 0, required_data_size(&array));
+                let type_info = copy_array_into_sample(&mut sample, &array);
+                send(OperatorEvent::Output {
+                    output_id: id,
+                    type_info,
+                    parameters: metadata.parameters,
+                    data: Some(sample.into()),
+                })?;
+            }
+            send(OperatorEvent::Finished {
+                reason: StopReason::InputsClosed,
+            })
+        }
+
+        let operator_id = OperatorId::from("echo".to_owned());
+        let worker = fork_worker(&operator_id, fork, |region_ids| {
+            run_worker(region_ids, echo_operator)
+        })
+        .unwrap();
+        let (incoming_tx, incoming_rx) = flume::unbounded();
+        let (events_tx, mut events_rx) = mpsc::channel(1);
+        let (init_done_tx, init_done_rx) = oneshot::channel();
+        let runtime = std::thread::spawn(move || worker.run(incoming_rx, events_tx, init_done_tx));
+        init_done_rx.blocking_recv().unwrap().unwrap();
+
+        let clock = uhlc::HLC::default();
+        for len in [16, 2 * ZERO_COPY_THRESHOLD, 2 * ZERO_COPY_THRESHOLD] {
+            let (event, array) = input_event(&clock, len);
+            incoming_tx.send(event).unwrap();
+            let Some(OperatorEvent::Output {
+                output_id,
+                type_info,
+                data: Some(data),
+                ..
+            }) = events_rx.blocking_recv()
+            else {
+                panic!("expected output event");
+            };
+            assert_eq!(output_id.as_str(), "image");
+            let output = RawData::Vec(AVec::from_slice(128, &data))
+                .into_arrow_array(&type_info)
+                .unwrap();
+            assert_eq!(output, array);
+        }
+
+        drop(incoming_tx);
+        assert!(matches!(
+            events_rx.blocking_recv(),
+            Some(OperatorEvent::Finished {
+                reason: StopReason::InputsClosed
+            })
+        ));
+        runtime.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+//! Runs a single operator outside of a runtime node, for the `dora-test`
+//! harness.
+//!
+//! The operator receives its events through the same input queues as the
+//! operators of a runtime node, so `queue_size`, `sync`, and `batch` options
+//! of its inputs behave as in a dataflow.
+
+use crate::{
+    input_batches,
+    operator::{
+        channel::{self, Deterministic},
+        run_operator,
+    },
+    queue_sizes,
+};
+use dora_core::{
+    config::NodeId,
+    daemon_messages::DataflowId,
+    descriptor::{Descriptor, OperatorDefinition},
+};
+use dora_node_api::Event;
+use eyre::Context;
+use std::{
+    collections::BTreeMap,
+    sync::{atomic::AtomicU64, Arc},
+    thread::JoinHandle,
+};
+use tokio::sync::{mpsc, oneshot};
+
+pub use crate::operator::{LogStream, OperatorEvent, StopReason};
+
+/// An operator that runs in its own thread.
+pub struct TestOperator {
+    /// Events for the operator, which pass through its input queues.
+    pub incoming: flume::Sender<Event>,
+    /// Events that the operator emits, e.g. its outputs.
+    pub events: mpsc::Receiver<OperatorEvent>,
+    /// Resolves when the operator is initialized.
+    pub init_done: oneshot::Receiver<eyre::Result<()>>,
+    pub thread: JoinHandle<eyre::Result<()>>,
+}
+
+/// Starts the given operator as part of a node with ID `test`.
+///
+/// The input queues run on the given runtime, which must keep running
+/// independently of the caller, e.g. a multi-threaded runtime.
+pub fn spawn_operator(
+    runtime: &tokio::runtime::Handle,
+    dataflow_id: DataflowId,
+    operator: OperatorDefinition,
+    dataflow_descriptor: Descriptor,
+    env: BTreeMap<String, String>,
+) -> eyre::Result<TestOperator> {
+    let deterministic = dataflow_descriptor.deterministic.then(|| Deterministic {
+        hold_timeout: dataflow_descriptor.deterministic_hold_timeout(),
+    });
+    let (incoming, incoming_events) = channel::channel(
+        runtime,
+        queue_sizes(&operator.config, &dataflow_descriptor),
+        operator.config.sync,
+        input_batches(&operator.config),
+        Arc::new(AtomicU64::new(0)),
+        deterministic,
+    );
+    let (events_tx, events) = mpsc::channel(1);
+    let (init_done_tx, init_done) = oneshot::channel();
+
+    let thread = std::thread::Builder::new()
+        .name(operator.id.to_string())
+        .spawn(move || {
+            run_operator(
+                dataflow_id,
+                &NodeId::from("test".to_owned()),
+                operator,
+                incoming_events,
+                events_tx,
+                init_done_tx,
+                &dataflow_descriptor,
+                env,
+            )
+        })
+        .wrap_err("failed to spawn operator thread")?;
+
+    Ok(TestOperator {
+        incoming,
+        events,
+        init_done,
+        thread,
+    })
+}
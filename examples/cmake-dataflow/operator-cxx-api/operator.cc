@@ -0,0 +1,45 @@
+#include "../../../apis/c/operator/dora_operator.hpp"
+
+#include <iostream>
+#include <vector>
+
+class Operator
+{
+public:
+    dora::Status on_event(dora::Event &event, dora::OutputSender &output_sender)
+    {
+        switch (event.kind())
+        {
+        case dora::Event::Kind::Input:
+        {
+            dora::Data data = event.data();
+            if (data.empty())
+            {
+                throw std::runtime_error("received empty input `" + event.id() + "`");
+            }
+            std::cout << "C++ Operator (C++-API) received input `" << event.id()
+                      << "` with data: [";
+            for (uint8_t value : data)
+            {
+                std::cout << (unsigned int)value << ", ";
+            }
+            std::cout << "]" << std::endl;
+
+            counter_ += 1;
+            output_sender.send("counter", std::vector<uint8_t>{counter_});
+            break;
+        }
+        case dora::Event::Kind::Stop:
+            std::cout << "C++ Operator (C++-API) received stop event" << std::endl;
+            break;
+        default:
+            break;
+        }
+        return dora::Status::Continue;
+    }
+
+private:
+    uint8_t counter_ = 0;
+};
+
+DORA_REGISTER_OPERATOR(Operator)
@@ -1,8 +1,10 @@
+import json
 import numpy as np
 import pyarrow as pa
 
 from dora import DoraStatus
 from ultralytics import YOLO
+from utils import LABELS
 
 
 CAMERA_WIDTH = 640
@@ -37,4 +39,24 @@ class Operator:
 
             send_output("bbox", pa.array(arrays.ravel()), dora_event["metadata"])
 
+            # the same detections as annotations of the image, for display
+            annotations = [
+                {
+                    "type": "bounding_box",
+                    "x": float(min_x),
+                    "y": float(min_y),
+                    "width": float(max_x - min_x),
+                    "height": float(max_y - min_y),
+                    "label": LABELS[int(label)],
+                    "score": float(confidence),
+                }
+                for min_x, min_y, max_x, max_y, confidence, label in arrays
+            ]
+            data = np.frombuffer(json.dumps(annotations).encode(), dtype=np.uint8)
+            send_output(
+                "annotations",
+                pa.array(data),
+                {"annotates": dora_event["metadata"]["timestamp"]},
+            )
+
         return DoraStatus.CONTINUE
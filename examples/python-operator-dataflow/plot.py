@@ -1,9 +1,9 @@
 import os
 import cv2
+import json
 import time
 
 from dora import DoraStatus
-from utils import LABELS
 
 
 CI = os.environ.get("CI")
@@ -20,10 +20,12 @@ class Operator:
     """
 
     def __init__(self):
-        self.bboxs = []
         self.buffer = ""
         self.submitted = []
         self.lines = []
+        # latest frame and its timestamp, held back until its annotations arrive
+        self.held = None
+        self.annotated = False
 
     def on_event(
         self,
@@ -34,75 +36,24 @@ class Operator:
             id = dora_event["id"]
             value = dora_event["value"]
             if id == "image":
-
                 image = (
                     value.to_numpy().reshape((CAMERA_HEIGHT, CAMERA_WIDTH, 3)).copy()
                 )
-
-                for bbox in self.bboxs:
-                    [
-                        min_x,
-                        min_y,
-                        max_x,
-                        max_y,
-                        confidence,
-                        label,
-                    ] = bbox
-                    cv2.rectangle(
-                        image,
-                        (int(min_x), int(min_y)),
-                        (int(max_x), int(max_y)),
-                        (0, 255, 0),
-                    )
-                    cv2.putText(
-                        image,
-                        f"{LABELS[int(label)]}, {confidence:0.2f}",
-                        (int(max_x), int(max_y)),
-                        FONT,
-                        0.5,
-                        (0, 255, 0),
-                    )
-
-                cv2.putText(
-                    image, self.buffer, (20, 14 + 21 * 14), FONT, 0.5, (190, 250, 0), 1
-                )
-
-                i = 0
-                for text in self.submitted[::-1]:
-                    color = (
-                        (0, 255, 190)
-                        if text["role"] == "user_message"
-                        else (0, 190, 255)
-                    )
-                    cv2.putText(
-                        image,
-                        text["content"],
-                        (
-                            20,
-                            14 + (19 - i) * 14,
-                        ),
-                        FONT,
-                        0.5,
-                        color,
-                        1,
-                    )
-                    i += 1
-
-                for line in self.lines:
-                    cv2.line(
-                        image,
-                        (int(line[0]), int(line[1])),
-                        (int(line[2]), int(line[3])),
-                        (0, 0, 255),
-                        2,
-                    )
-
-                if CI != "true":
-                    cv2.imshow("frame", image)
-                    if cv2.waitKey(1) & 0xFF == ord("q"):
-                        return DoraStatus.STOP
-            elif id == "bbox":
-                self.bboxs = value.to_numpy().reshape((-1, 6))
+                if not self.annotated:
+                    return self.show(image, [])
+                # the previous frame was not annotated in time
+                status = DoraStatus.CONTINUE
+                if self.held is not None:
+                    status = self.show(self.held[1], [])
+                self.held = (dora_event["metadata"]["timestamp"], image)
+                return status
+            elif id == "annotations":
+                self.annotated = True
+                annotates = dora_event["metadata"].get("annotates")
+                if self.held is not None and self.held[0] == annotates:
+                    image = self.held[1]
+                    self.held = None
+                    return self.show(image, json.loads(value.to_numpy().tobytes()))
             elif id == "keyboard_buffer":
                 self.buffer = value[0].as_py()
             elif id == "line":
@@ -116,3 +67,68 @@ class Operator:
                 ]
 
         return DoraStatus.CONTINUE
+
+    def show(self, image, annotations) -> DoraStatus:
+        """
+        Draw the overlays and the given message annotations on the image and
+        display it
+        """
+        for annotation in annotations:
+            if annotation["type"] == "bounding_box":
+                x, y = int(annotation["x"]), int(annotation["y"])
+                width, height = int(annotation["width"]), int(annotation["height"])
+                cv2.rectangle(image, (x, y), (x + width, y + height), (0, 255, 0))
+                text = annotation.get("label", "")
+                if "score" in annotation:
+                    text += f", {annotation['score']:0.2f}"
+                cv2.putText(
+                    image, text, (x + width, y + height), FONT, 0.5, (0, 255, 0)
+                )
+            elif annotation["type"] == "marker":
+                x, y = int(annotation["x"]), int(annotation["y"])
+                cv2.drawMarker(image, (x, y), (0, 255, 0))
+                if "label" in annotation:
+                    cv2.putText(
+                        image, annotation["label"], (x, y), FONT, 0.5, (0, 255, 0)
+                    )
+            elif annotation["type"] == "label":
+                cv2.putText(
+                    image, annotation["text"], (20, 14), FONT, 0.5, (0, 255, 0), 1
+                )
+
+        cv2.putText(
+            image, self.buffer, (20, 14 + 21 * 14), FONT, 0.5, (190, 250, 0), 1
+        )
+
+        i = 0
+        for text in self.submitted[::-1]:
+            color = (0, 255, 190) if text["role"] == "user_message" else (0, 190, 255)
+            cv2.putText(
+                image,
+                text["content"],
+                (
+                    20,
+                    14 + (19 - i) * 14,
+                ),
+                FONT,
+                0.5,
+                color,
+                1,
+            )
+            i += 1
+
+        for line in self.lines:
+            cv2.line(
+                image,
+                (int(line[0]), int(line[1])),
+                (int(line[2]), int(line[3])),
+                (0, 0, 255),
+                2,
+            )
+
+        if CI != "true":
+            cv2.imshow("frame", image)
+            if cv2.waitKey(1) & 0xFF == ord("q"):
+                return DoraStatus.STOP
+
+        return DoraStatus.CONTINUE
@@ -0,0 +1,574 @@
+//! Provides [`ChunkedCommunicationLayer`], which splits large messages into
+//! chunks that fit through the underlying backend.
+//!
+//! Backends that send messages over the network often limit the size of
+//! messages or queue them in fixed-size buffers. The chunked layer wraps any
+//! [`CommunicationLayer`] and transparently splits published messages into
+//! chunks of at most [`ChunkConfig::max_chunk_size`] bytes. Subscribers
+//! reassemble the chunks, verify the CRC32 checksum of the reassembled
+//! message, and drop incomplete messages after
+//! [`ChunkConfig::reassembly_timeout`]. The memory that subscribers allocate
+//! for reassembly is bounded by [`ChunkConfig::max_message_size`] and
+//! [`ChunkConfig::max_pending_size`].
+//!
+//! All publishers and subscribers of a topic must use the chunked layer since
+//! each chunk is prefixed with a header.
+
+use crate::{BoxError, CommunicationLayer, PublishSample, Publisher, ReceivedSample, Subscriber};
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::{Entry, RandomState},
+        HashMap,
+    },
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"DCHK");
+const HEADER_LEN: usize = 48;
+
+/// Configuration of a [`ChunkedCommunicationLayer`].
+#[derive(Debug, Clone)]
+pub struct ChunkConfig {
+    /// Maximum size of a published chunk, including its header.
+    pub max_chunk_size: usize,
+    /// Incomplete messages are dropped if their chunks don't arrive within this time.
+    pub reassembly_timeout: Duration,
+    /// Subscribers reject chunks of messages that are larger than this.
+    pub max_message_size: usize,
+    /// Maximum total size of the incomplete messages of a subscriber.
+    ///
+    /// The oldest incomplete messages are dropped to make room for new ones.
+    pub max_pending_size: usize,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            max_chunk_size: 64 * 1024,
+            reassembly_timeout: Duration::from_secs(5),
+            max_message_size: 256 * 1024 * 1024,
+            max_pending_size: 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// Wraps a [`CommunicationLayer`] to support messages of arbitrary size.
+pub struct ChunkedCommunicationLayer<L> {
+    inner: L,
+    config: ChunkConfig,
+}
+
+impl<L: CommunicationLayer> ChunkedCommunicationLayer<L> {
+    /// Wraps the given communication layer.
+    ///
+    /// Returns an error if the `max_chunk_size` leaves no space for data after
+    /// the chunk header or if a message of `max_message_size` doesn't fit into
+    /// `max_pending_size`.
+    pub fn new(inner: L, config: ChunkConfig) -> Result<Self, BoxError> {
+        if config.max_chunk_size <= HEADER_LEN {
+            return Err(format!("max_chunk_size must be larger than {HEADER_LEN} bytes").into());
+        }
+        if config.max_pending_size < config.max_message_size {
+            return Err("max_pending_size must not be smaller than max_message_size".into());
+        }
+        Ok(Self { inner, config })
+    }
+}
+
+impl<L: CommunicationLayer> CommunicationLayer for ChunkedCommunicationLayer<L> {
+    fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        Ok(Box::new(ChunkedPublisher {
+            inner: self.inner.publisher(topic)?.into(),
+            publisher_id: random_id(),
+            next_message_id: Default::default(),
+            max_payload: self.config.max_chunk_size - HEADER_LEN,
+        }))
+    }
+
+    fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+        Ok(Box::new(ChunkedSubscriber {
+            inner: self.inner.subscribe(topic)?,
+            pending: HashMap::new(),
+            reassembly_timeout: self.config.reassembly_timeout,
+            max_message_size: self.config.max_message_size,
+            max_pending_size: self.config.max_pending_size,
+        }))
+    }
+}
+
+struct ChunkedPublisher {
+    inner: Arc<dyn Publisher>,
+    /// Distinguishes the messages of different publishers of the same topic.
+    publisher_id: u64,
+    next_message_id: Arc<AtomicU64>,
+    max_payload: usize,
+}
+
+impl Publisher for ChunkedPublisher {
+    fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+        Ok(Box::new(ChunkedPublishSample {
+            data: vec![0; len],
+            publisher: self,
+        }))
+    }
+
+    fn dyn_clone(&self) -> Box<dyn Publisher> {
+        Box::new(ChunkedPublisher {
+            inner: self.inner.dyn_clone().into(),
+            publisher_id: self.publisher_id,
+            next_message_id: self.next_message_id.clone(),
+            max_payload: self.max_payload,
+        })
+    }
+
+    fn publish(&self, data: &[u8]) -> Result<(), BoxError> {
+        let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+        let checksum = crc32fast::hash(data);
+        // empty messages are sent as a single empty chunk
+        let count = data.len().div_ceil(self.max_payload).max(1);
+        let count = u32::try_from(count).map_err(|_| "message is too large")?;
+        for (index, offset) in (0..count).zip((0..).step_by(self.max_payload)) {
+            let payload = &data[offset..data.len().min(offset + self.max_payload)];
+            let header = ChunkHeader {
+                index,
+                count,
+                checksum,
+                publisher_id: self.publisher_id,
+                message_id,
+                total_len: data.len() as u64,
+                offset: offset as u64,
+            };
+            let mut sample = self.inner.prepare(HEADER_LEN + payload.len())?;
+            let chunk = sample.as_mut_slice();
+            header.write(&mut chunk[..HEADER_LEN]);
+            chunk[HEADER_LEN..].copy_from_slice(payload);
+            sample.publish()?;
+        }
+        Ok(())
+    }
+}
+
+struct ChunkedPublishSample<'a> {
+    data: Vec<u8>,
+    publisher: &'a ChunkedPublisher,
+}
+
+impl<'a> PublishSample<'a> for ChunkedPublishSample<'a> {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    fn publish(self: Box<Self>) -> Result<(), BoxError> {
+        self.publisher.publish(&self.data)
+    }
+}
+
+struct ChunkedSubscriber {
+    inner: Box<dyn Subscriber>,
+    /// Incomplete messages, identified by publisher and message ID.
+    pending: HashMap<(u64, u64), PartialMessage>,
+    reassembly_timeout: Duration,
+    max_message_size: usize,
+    max_pending_size: usize,
+}
+
+impl ChunkedSubscriber {
+    /// Drops the oldest incomplete messages until a new message of the given
+    /// size fits into `max_pending_size`.
+    fn make_room(&mut self, len: usize) {
+        let mut pending_size: usize = self.pending.values().map(|m| m.data.len()).sum();
+        while pending_size + len > self.max_pending_size {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|((_, message_id), m)| (m.started, *message_id));
+            let Some((&oldest, _)) = oldest else {
+                break;
+            };
+            if let Some(message) = self.pending.remove(&oldest) {
+                pending_size -= message.data.len();
+            }
+        }
+    }
+}
+
+impl Subscriber for ChunkedSubscriber {
+    fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+        loop {
+            let Some(sample) = self.inner.recv()? else {
+                return Ok(None);
+            };
+            let now = Instant::now();
+            let timeout = self.reassembly_timeout;
+            self.pending
+                .retain(|_, message| now.duration_since(message.started) < timeout);
+
+            let chunk = sample.get();
+            let header = ChunkHeader::read(&chunk)?;
+            header.validate(self.max_message_size)?;
+            let payload = &chunk[HEADER_LEN..];
+            let key = (header.publisher_id, header.message_id);
+            if !self.pending.contains_key(&key) {
+                self.make_room(header.total_len as usize);
+            }
+            let message = match self.pending.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(PartialMessage::new(&header, now)),
+            };
+            match message.insert(&header, payload) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(err) => {
+                    self.pending.remove(&key);
+                    return Err(err);
+                }
+            }
+
+            let message = self.pending.remove(&key).expect("message is pending");
+            if crc32fast::hash(&message.data) != header.checksum {
+                return Err(format!(
+                    "checksum mismatch in reassembled message {} of publisher {:x}",
+                    header.message_id, header.publisher_id
+                )
+                .into());
+            }
+            return Ok(Some(Box::new(ReassembledSample(message.data))));
+        }
+    }
+}
+
+struct PartialMessage {
+    data: Vec<u8>,
+    received: Vec<bool>,
+    missing: u32,
+    checksum: u32,
+    started: Instant,
+}
+
+impl PartialMessage {
+    /// Creates an empty message of the size announced by the given
+    /// [validated](ChunkHeader::validate) header.
+    fn new(header: &ChunkHeader, now: Instant) -> Self {
+        Self {
+            data: vec![0; header.total_len as usize],
+            received: vec![false; header.count as usize],
+            missing: header.count,
+            checksum: header.checksum,
+            started: now,
+        }
+    }
+
+    /// Inserts the given chunk and returns whether the message is complete.
+    ///
+    /// Fails if the chunk header doesn't match the header of the first chunk.
+    fn insert(&mut self, header: &ChunkHeader, payload: &[u8]) -> Result<bool, BoxError> {
+        if header.count as usize != self.received.len()
+            || header.total_len != self.data.len() as u64
+            || header.checksum != self.checksum
+        {
+            return Err(format!(
+                "chunk {} of message {} of publisher {:x} does not match the previous chunks",
+                header.index, header.message_id, header.publisher_id
+            )
+            .into());
+        }
+        let start = header.offset as usize;
+        let end = start + payload.len();
+        let (Some(received), Some(target)) = (
+            self.received.get_mut(header.index as usize),
+            self.data.get_mut(start..end),
+        ) else {
+            return Err("chunk does not fit into message".into());
+        };
+        if !*received {
+            *received = true;
+            self.missing -= 1;
+            target.copy_from_slice(payload);
+        }
+        Ok(self.missing == 0)
+    }
+}
+
+struct ReassembledSample(Vec<u8>);
+
+impl ReceivedSample for ReassembledSample {
+    fn get(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ChunkHeader {
+    index: u32,
+    count: u32,
+    /// CRC32 checksum of the whole message.
+    checksum: u32,
+    publisher_id: u64,
+    message_id: u64,
+    total_len: u64,
+    offset: u64,
+}
+
+impl ChunkHeader {
+    fn write(&self, target: &mut [u8]) {
+        let fields = [
+            &MAGIC.to_le_bytes()[..],
+            &self.index.to_le_bytes(),
+            &self.count.to_le_bytes(),
+            &self.checksum.to_le_bytes(),
+            &self.publisher_id.to_le_bytes(),
+            &self.message_id.to_le_bytes(),
+            &self.total_len.to_le_bytes(),
+            &self.offset.to_le_bytes(),
+        ];
+        let mut position = 0;
+        for field in fields {
+            target[position..][..field.len()].copy_from_slice(field);
+            position += field.len();
+        }
+    }
+
+    /// Checks that the announced message size is within the limit and
+    /// consistent with the chunk count.
+    fn validate(&self, max_message_size: usize) -> Result<(), BoxError> {
+        if self.total_len > max_message_size as u64 {
+            return Err(format!(
+                "message of {} bytes exceeds the maximum message size of {max_message_size} bytes",
+                self.total_len
+            )
+            .into());
+        }
+        // every chunk of a non-empty message contains at least one byte
+        if self.count == 0 || u64::from(self.count) > self.total_len.max(1) {
+            return Err(format!(
+                "invalid chunk count {} for message of {} bytes",
+                self.count, self.total_len
+            )
+            .into());
+        }
+        if self.index >= self.count {
+            return Err(format!("chunk index {} out of range", self.index).into());
+        }
+        Ok(())
+    }
+
+    fn read(chunk: &[u8]) -> Result<Self, BoxError> {
+        if chunk.len() < HEADER_LEN {
+            return Err("received chunk is shorter than the chunk header".into());
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(chunk[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(chunk[i..i + 8].try_into().unwrap());
+        if u32_at(0) != MAGIC {
+            return Err("received message is not a chunk".into());
+        }
+        Ok(Self {
+            index: u32_at(4),
+            count: u32_at(8),
+            checksum: u32_at(12),
+            publisher_id: u64_at(16),
+            message_id: u64_at(24),
+            total_len: u64_at(32),
+            offset: u64_at(40),
+        })
+    }
+}
+
+/// Random ID based on the randomly seeded hasher of the standard library.
+fn random_id() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Subscribers of each topic.
+    type Topics = Arc<Mutex<HashMap<String, Vec<flume::Sender<Vec<u8>>>>>>;
+
+    /// In-memory backend that delivers published messages in order.
+    #[derive(Default)]
+    struct MemoryLayer {
+        topics: Topics,
+    }
+
+    impl CommunicationLayer for MemoryLayer {
+        fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+            Ok(Box::new(MemoryPublisher {
+                topics: self.topics.clone(),
+                topic: topic.to_owned(),
+            }))
+        }
+
+        fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+            let (tx, rx) = flume::unbounded();
+            let mut topics = self.topics.lock().unwrap();
+            topics.entry(topic.to_owned()).or_default().push(tx);
+            Ok(Box::new(MemorySubscriber(rx)))
+        }
+    }
+
+    #[derive(Clone)]
+    struct MemoryPublisher {
+        topics: Topics,
+        topic: String,
+    }
+
+    impl Publisher for MemoryPublisher {
+        fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+            Ok(Box::new(MemorySample(vec![0; len], self)))
+        }
+
+        fn dyn_clone(&self) -> Box<dyn Publisher> {
+            Box::new(self.clone())
+        }
+    }
+
+    struct MemorySample<'a>(Vec<u8>, &'a MemoryPublisher);
+
+    impl<'a> PublishSample<'a> for MemorySample<'a> {
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+
+        fn publish(self: Box<Self>) -> Result<(), BoxError> {
+            let topics = self.1.topics.lock().unwrap();
+            for subscriber in topics.get(&self.1.topic).into_iter().flatten() {
+                subscriber.send(self.0.clone())?;
+            }
+            Ok(())
+        }
+    }
+
+    struct MemorySubscriber(flume::Receiver<Vec<u8>>);
+
+    impl Subscriber for MemorySubscriber {
+        fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+            Ok(self
+                .0
+                .try_recv()
+                .ok()
+                .map(|data| Box::new(ReassembledSample(data)) as Box<dyn ReceivedSample>))
+        }
+    }
+
+    fn layer() -> ChunkedCommunicationLayer<MemoryLayer> {
+        let config = ChunkConfig {
+            max_chunk_size: HEADER_LEN + 1000,
+            reassembly_timeout: Duration::from_secs(5),
+            max_message_size: 20_000,
+            max_pending_size: 30_000,
+        };
+        ChunkedCommunicationLayer::new(MemoryLayer::default(), config).unwrap()
+    }
+
+    #[test]
+    fn reassemble_large_messages() {
+        let mut layer = layer();
+        let mut subscriber = layer.subscribe("image").unwrap();
+        let publisher = layer.publisher("image").unwrap();
+
+        let large: Vec<u8> = (0..10_500).map(|i| i as u8).collect();
+        publisher.publish(&large).unwrap();
+        publisher.publish(&[]).unwrap();
+        let mut sample = publisher.prepare(3).unwrap();
+        sample.as_mut_slice().copy_from_slice(&[1, 2, 3]);
+        sample.publish().unwrap();
+
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &large[..]);
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &b""[..]);
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[1, 2, 3][..]);
+        assert!(subscriber.recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn bound_pending_messages() {
+        let mut layer = layer();
+        let mut subscriber = ChunkedSubscriber {
+            inner: layer.inner.subscribe("image").unwrap(),
+            pending: HashMap::new(),
+            reassembly_timeout: layer.config.reassembly_timeout,
+            max_message_size: layer.config.max_message_size,
+            max_pending_size: layer.config.max_pending_size,
+        };
+        let raw = layer.inner.publisher("image").unwrap();
+        let publish_first_chunk = |message_id, total_len| {
+            let header = ChunkHeader {
+                index: 0,
+                count: 2,
+                checksum: 0,
+                publisher_id: 1,
+                message_id,
+                total_len,
+                offset: 0,
+            };
+            let mut chunk = vec![0; HEADER_LEN + 1];
+            header.write(&mut chunk);
+            raw.publish(&chunk).unwrap();
+        };
+
+        publish_first_chunk(0, 20_001);
+        assert!(subscriber.recv().is_err());
+        assert!(subscriber.pending.is_empty());
+
+        // the oldest message is dropped to make room for the third one
+        for message_id in 0..3 {
+            publish_first_chunk(message_id, 12_000);
+        }
+        assert!(subscriber.recv().unwrap().is_none());
+        let mut pending: Vec<_> = subscriber.pending.keys().map(|(_, id)| *id).collect();
+        pending.sort();
+        assert_eq!(pending, [1, 2]);
+    }
+
+    #[test]
+    fn reject_corrupted_chunks() {
+        let header = ChunkHeader {
+            index: 1,
+            count: 2,
+            checksum: 42,
+            publisher_id: 7,
+            message_id: 3,
+            total_len: 10,
+            offset: 5,
+        };
+        let mut chunk = vec![0; HEADER_LEN + 5];
+        header.write(&mut chunk);
+        assert_eq!(ChunkHeader::read(&chunk).unwrap(), header);
+
+        header.validate(10).unwrap();
+        let mut message = PartialMessage::new(&header, Instant::now());
+        assert!(!message.insert(&header, &chunk[HEADER_LEN..]).unwrap());
+        // chunks that exceed the message length are rejected
+        let overflowing = ChunkHeader {
+            offset: 6,
+            ..header
+        };
+        assert!(message.insert(&overflowing, &[0; 5]).is_err());
+        // later chunks must match the first chunk
+        let inconsistent = ChunkHeader {
+            index: 0,
+            total_len: 11,
+            offset: 0,
+            ..header
+        };
+        assert!(message.insert(&inconsistent, &[0; 5]).is_err());
+
+        // sizes are bounded before allocating the message
+        assert!(header.validate(9).is_err());
+        let too_many_chunks = ChunkHeader {
+            count: 11,
+            ..header
+        };
+        assert!(too_many_chunks.validate(10).is_err());
+
+        chunk[0] = 0;
+        assert!(ChunkHeader::read(&chunk).is_err());
+    }
+}
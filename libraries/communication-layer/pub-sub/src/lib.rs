@@ -9,14 +9,49 @@
 //! - **[Zenoh](https://zenoh.io/):** The zenoh project implements a distributed
 //!   publisher/subscriber system with automated routing. To use zenoh, use the
 //!   [`ZenohCommunicationLayer`][zenoh::ZenohCommunicationLayer] struct.
+//! - **Shared memory:** Communication between the processes of a single machine through
+//!   memory-mapped files, which works natively on Windows. To use it, use the
+//!   [`SharedMemoryCommunicationLayer`][shared_memory::SharedMemoryCommunicationLayer] struct.
+//!
+//! The [`init_local`] function selects the backend for the current platform.
+//!
+//! Messages that are too large for a backend can be split into chunks by wrapping it in a
+//! [`ChunkedCommunicationLayer`][chunking::ChunkedCommunicationLayer].
 
 use std::borrow::Cow;
 
+pub mod chunking;
+pub mod shared_memory;
 #[cfg(feature = "zenoh")]
 pub mod zenoh;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
+/// Initializes the default backend for communication on the local machine.
+///
+/// Uses shared memory on Windows, or if the `zenoh` feature is disabled, and
+/// zenoh otherwise. The `prefix` separates the topics of different users of
+/// the layer, e.g. of different dataflows. The backend is wrapped in a
+/// [`ChunkedCommunicationLayer`][chunking::ChunkedCommunicationLayer], so
+/// messages of any size can be published.
+pub fn init_local(prefix: String) -> Result<Box<dyn CommunicationLayer>, BoxError> {
+    let chunk_config = chunking::ChunkConfig::default();
+    #[cfg(all(feature = "zenoh", not(windows)))]
+    let layer = zenoh::ZenohCommunicationLayer::init(Default::default(), prefix)?;
+    #[cfg(any(not(feature = "zenoh"), windows))]
+    let layer = shared_memory::SharedMemoryCommunicationLayer::init(
+        prefix,
+        shared_memory::SharedMemoryConfig {
+            slot_size: chunk_config.max_chunk_size,
+            ..Default::default()
+        },
+    )?;
+    Ok(Box::new(chunking::ChunkedCommunicationLayer::new(
+        layer,
+        chunk_config,
+    )?))
+}
+
 /// Abstraction trait for different publisher/subscriber implementations.
 pub trait CommunicationLayer: Send + Sync {
     /// Creates a publisher for the given topic.
@@ -85,3 +120,20 @@ pub trait ReceivedSample: Send + Sync {
     /// Access the message's data.
     fn get(&self) -> Cow<[u8]>;
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(any(not(feature = "zenoh"), windows))]
+    fn init_local_uses_shared_memory() {
+        let prefix = format!("init-local-{}", std::process::id());
+        let mut layer = super::init_local(prefix).unwrap();
+        let publisher = layer.publisher("image").unwrap();
+        let mut subscriber = layer.subscribe("image").unwrap();
+
+        // larger than a shared memory slot, so it is split into chunks
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        publisher.publish(&data).unwrap();
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &data[..]);
+    }
+}
@@ -0,0 +1,709 @@
+//! Provides [`SharedMemoryCommunicationLayer`] to communicate between the
+//! processes of a single machine through shared memory.
+//!
+//! Each topic is backed by a named shared memory region, which is a
+//! memory-mapped file on Windows. The region contains a ring buffer of message
+//! slots and a table of subscribers. Every subscriber registers an event in the
+//! table (a named event on Windows), which publishers signal after writing a
+//! message.
+//!
+//! Subscribers that fall behind by more than [`SharedMemoryConfig::slots`]
+//! messages skip the overwritten messages. Messages that are larger than
+//! [`SharedMemoryConfig::slot_size`] are rejected, wrap the layer in a
+//! [`ChunkedCommunicationLayer`](crate::chunking::ChunkedCommunicationLayer)
+//! to send them.
+//!
+//! Processes that crash don't block the others: a publisher that crashes while
+//! writing a message releases the topic after a timeout, and the entries
+//! of crashed subscribers are reused for new subscribers once the table is full.
+
+use crate::{BoxError, CommunicationLayer, PublishSample, Publisher, ReceivedSample, Subscriber};
+use raw_sync_2::{
+    events::{Event, EventImpl, EventInit, EventState},
+    Timeout,
+};
+use shared_memory_extended::{Shmem, ShmemConf, ShmemError};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    mem, slice,
+    sync::{
+        atomic::{fence, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"DSHM");
+/// Maximum number of subscribers of a topic, across all processes.
+const MAX_SUBSCRIBERS: usize = 64;
+/// Time to wait for another process to initialize a region that it created.
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Publishers take over the write lock of a topic if it is held for longer than
+/// this, e.g. because the publisher that holds it crashed.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+/// Interval in which blocked subscribers refresh their heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// Subscriber entries whose heartbeat is older than this are reused when all
+/// entries are taken.
+const STALE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const FREE: u32 = 0;
+const INITIALIZING: u32 = 1;
+const ACTIVE: u32 = 2;
+
+/// Configuration of a [`SharedMemoryCommunicationLayer`].
+///
+/// Only applies to the topics that are created by the layer. Topics that
+/// another process created already keep their configuration.
+#[derive(Debug, Clone)]
+pub struct SharedMemoryConfig {
+    /// Number of messages that are buffered per topic.
+    pub slots: usize,
+    /// Maximum size of a message.
+    pub slot_size: usize,
+}
+
+impl Default for SharedMemoryConfig {
+    fn default() -> Self {
+        Self {
+            slots: 64,
+            slot_size: 64 * 1024,
+        }
+    }
+}
+
+/// Allows communication between the processes of a single machine over shared memory.
+pub struct SharedMemoryCommunicationLayer {
+    prefix: String,
+    config: SharedMemoryConfig,
+    regions: HashMap<String, Arc<Region>>,
+}
+
+impl SharedMemoryCommunicationLayer {
+    /// Initializes a new shared memory layer with the given configuration.
+    ///
+    /// Only layers with the same `prefix` can communicate with each other.
+    pub fn init(prefix: String, config: SharedMemoryConfig) -> Result<Self, BoxError> {
+        if config.slots == 0 || config.slot_size == 0 {
+            return Err("slots and slot_size must be larger than zero".into());
+        }
+        Ok(Self {
+            prefix,
+            config,
+            regions: HashMap::new(),
+        })
+    }
+
+    fn region(&mut self, topic: &str) -> Result<Arc<Region>, BoxError> {
+        if let Some(region) = self.regions.get(topic) {
+            return Ok(region.clone());
+        }
+        // names of shared memory regions are restricted on some platforms, e.g.
+        // to 31 characters on macOS, so the topic is hashed
+        let mut hasher = DefaultHasher::new();
+        (&self.prefix, topic).hash(&mut hasher);
+        let os_id = format!("dora-ps-{:016x}", hasher.finish());
+
+        let region = Arc::new(Region::open_or_create(&os_id, &self.config)?);
+        self.regions.insert(topic.to_owned(), region.clone());
+        Ok(region)
+    }
+}
+
+impl CommunicationLayer for SharedMemoryCommunicationLayer {
+    fn publisher(&mut self, topic: &str) -> Result<Box<dyn Publisher>, BoxError> {
+        Ok(Box::new(SharedMemoryPublisher {
+            region: self.region(topic)?,
+            events: Default::default(),
+        }))
+    }
+
+    fn subscribe(&mut self, topic: &str) -> Result<Box<dyn Subscriber>, BoxError> {
+        let region = self.region(topic)?;
+        let index = region
+            .claim_free_subscriber()
+            .or_else(|| region.claim_stale_subscriber());
+        let index = index.ok_or_else(|| {
+            format!("topic `{topic}` has more than {MAX_SUBSCRIBERS} subscribers")
+        })?;
+
+        let (entry, event_ptr) = region.subscriber(index);
+        let event = match unsafe { Event::new(event_ptr, true) } {
+            Ok((event, _)) => event,
+            Err(err) => {
+                entry.state.store(FREE, Ordering::Release);
+                return Err(format!("failed to create subscriber event: {err}").into());
+            }
+        };
+        let generation = entry.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        entry.heartbeat.store(now_millis(), Ordering::Relaxed);
+        entry.state.store(ACTIVE, Ordering::Release);
+
+        // only messages that are published after subscribing are received
+        let next = region.header().sequence.load(Ordering::Acquire) + 1;
+        Ok(Box::new(SharedMemorySubscriber {
+            region,
+            index,
+            generation,
+            event: EventHandle(event),
+            next,
+        }))
+    }
+}
+
+#[derive(Clone)]
+struct SharedMemoryPublisher {
+    region: Arc<Region>,
+    /// Opened events of the subscribers, by subscriber index.
+    events: Arc<Mutex<HashMap<usize, SubscriberEvent>>>,
+}
+
+struct SubscriberEvent {
+    /// Generation of the subscriber entry that the event was opened for.
+    generation: u64,
+    event: EventHandle,
+}
+
+impl SharedMemoryPublisher {
+    fn notify_subscribers(&self) -> Result<(), BoxError> {
+        let mut events = self
+            .events
+            .lock()
+            .map_err(|_| "subscriber events were poisoned")?;
+        for index in 0..MAX_SUBSCRIBERS {
+            let (entry, event_ptr) = self.region.subscriber(index);
+            if entry.state.load(Ordering::Acquire) != ACTIVE {
+                continue;
+            }
+            // the entry might have been reused by another subscriber
+            let generation = entry.generation.load(Ordering::Acquire);
+            if events.get(&index).map(|event| event.generation) != Some(generation) {
+                match unsafe { Event::from_existing(event_ptr) } {
+                    Ok((event, _)) => {
+                        let event = EventHandle(event);
+                        events.insert(index, SubscriberEvent { generation, event });
+                    }
+                    // the subscriber was dropped in the meantime
+                    Err(_) => continue,
+                }
+            }
+            if let Some(subscriber) = events.get(&index) {
+                subscriber
+                    .event
+                    .0
+                    .set(EventState::Signaled)
+                    .map_err(|err| format!("failed to notify subscriber: {err}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Publisher for SharedMemoryPublisher {
+    fn prepare(&self, len: usize) -> Result<Box<dyn PublishSample + '_>, BoxError> {
+        Ok(Box::new(SharedMemoryPublishSample {
+            data: vec![0; len],
+            publisher: self,
+        }))
+    }
+
+    fn dyn_clone(&self) -> Box<dyn Publisher> {
+        Box::new(self.clone())
+    }
+
+    fn publish(&self, data: &[u8]) -> Result<(), BoxError> {
+        let slot_size = self.region.layout.slot_size;
+        if data.len() > slot_size {
+            return Err(format!(
+                "message of {} bytes does not fit into a shared memory slot of {slot_size} bytes",
+                data.len()
+            )
+            .into());
+        }
+
+        let header = self.region.header();
+        {
+            let _guard = header.lock();
+            let sequence = header.sequence.load(Ordering::Relaxed) + 1;
+            let (slot, slot_data) = self.region.slot(sequence);
+            // mark the slot as invalid while it is written, see `Region::read`
+            slot.sequence.store(0, Ordering::Relaxed);
+            fence(Ordering::Release);
+            unsafe { slot_data.copy_from_nonoverlapping(data.as_ptr(), data.len()) };
+            slot.len.store(data.len() as u64, Ordering::Relaxed);
+            slot.sequence.store(sequence, Ordering::Release);
+            header.sequence.store(sequence, Ordering::Release);
+        }
+
+        self.notify_subscribers()
+    }
+}
+
+struct SharedMemoryPublishSample<'a> {
+    data: Vec<u8>,
+    publisher: &'a SharedMemoryPublisher,
+}
+
+impl<'a> PublishSample<'a> for SharedMemoryPublishSample<'a> {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    fn publish(self: Box<Self>) -> Result<(), BoxError> {
+        self.publisher.publish(&self.data)
+    }
+}
+
+struct SharedMemorySubscriber {
+    region: Arc<Region>,
+    index: usize,
+    /// Generation of the subscriber entry, changes if the entry is reused.
+    generation: u64,
+    event: EventHandle,
+    /// Sequence number of the next message to receive.
+    next: u64,
+}
+
+impl Subscriber for SharedMemorySubscriber {
+    fn recv(&mut self) -> Result<Option<Box<dyn ReceivedSample>>, BoxError> {
+        let slots = self.region.layout.slots as u64;
+        loop {
+            let (entry, _) = self.region.subscriber(self.index);
+            if entry.generation.load(Ordering::Acquire) != self.generation {
+                return Err("subscriber was removed because it stopped receiving messages".into());
+            }
+            entry.heartbeat.store(now_millis(), Ordering::Relaxed);
+
+            let latest = self.region.header().sequence.load(Ordering::Acquire);
+            if latest >= self.next {
+                // skip messages that were overwritten already
+                self.next = self.next.max((latest + 1).saturating_sub(slots));
+                let sequence = self.next;
+                self.next += 1;
+                match self.region.read(sequence) {
+                    Some(data) => return Ok(Some(Box::new(SharedMemorySample(data)))),
+                    None => continue,
+                }
+            }
+            // the event stays signaled if a message was published since the check above
+            let start = Instant::now();
+            if let Err(err) = self.event.0.wait(Timeout::Val(HEARTBEAT_INTERVAL)) {
+                // timeouts are reported as errors too, they only refresh the heartbeat
+                if start.elapsed() < HEARTBEAT_INTERVAL / 2 {
+                    return Err(format!("failed to wait for message: {err}").into());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SharedMemorySubscriber {
+    fn drop(&mut self) {
+        let (entry, _) = self.region.subscriber(self.index);
+        // the entry might have been reused if this subscriber was considered stale
+        if entry.generation.load(Ordering::Acquire) == self.generation {
+            entry.state.store(FREE, Ordering::Release);
+        }
+    }
+}
+
+struct SharedMemorySample(Vec<u8>);
+
+impl ReceivedSample for SharedMemorySample {
+    fn get(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+}
+
+/// Event in shared memory.
+///
+/// The events are synchronization primitives between processes, so they can
+/// be used from any thread.
+struct EventHandle(Box<dyn EventImpl>);
+
+unsafe impl Send for EventHandle {}
+unsafe impl Sync for EventHandle {}
+
+/// Start of a shared memory region.
+#[repr(C)]
+struct Header {
+    /// Set to [`MAGIC`] after the region is initialized.
+    magic: AtomicU32,
+    /// Spin lock that serializes the publishers of the topic.
+    ///
+    /// Contains the time at which the lock was taken, in milliseconds since the
+    /// Unix epoch, or 0 if it is free.
+    write_lock: AtomicU64,
+    slots: AtomicU64,
+    slot_size: AtomicU64,
+    /// Sequence number of the last published message, starting at 1.
+    sequence: AtomicU64,
+}
+
+impl Header {
+    /// Takes the write lock, or takes it over if its holder didn't release it
+    /// within [`LOCK_TIMEOUT`].
+    fn lock(&self) -> WriteGuard<'_> {
+        loop {
+            let now = now_millis().max(1);
+            let locked_at = self.write_lock.load(Ordering::Relaxed);
+            let expired = now.saturating_sub(locked_at) > LOCK_TIMEOUT.as_millis() as u64;
+            if (locked_at == 0 || expired)
+                && self
+                    .write_lock
+                    .compare_exchange_weak(locked_at, now, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return WriteGuard {
+                    lock: &self.write_lock,
+                    locked_at: now,
+                };
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+struct WriteGuard<'a> {
+    lock: &'a AtomicU64,
+    locked_at: u64,
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        // don't release the lock if another publisher took it over in the meantime
+        let _ = self
+            .lock
+            .compare_exchange(self.locked_at, 0, Ordering::Release, Ordering::Relaxed);
+    }
+}
+
+#[repr(C)]
+struct SubscriberEntry {
+    /// [`FREE`], [`INITIALIZING`], or [`ACTIVE`].
+    state: AtomicU32,
+    /// Incremented on every registration, so that publishers reopen the event.
+    generation: AtomicU64,
+    /// Time of the last activity of the subscriber, in milliseconds since the Unix epoch.
+    heartbeat: AtomicU64,
+}
+
+#[repr(C)]
+struct SlotHeader {
+    /// Sequence number of the message in the slot, or 0 while it is written.
+    sequence: AtomicU64,
+    len: AtomicU64,
+}
+
+/// Offsets of the parts of a region.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    slots: usize,
+    slot_size: usize,
+    event_size: usize,
+}
+
+impl Layout {
+    fn new(slots: usize, slot_size: usize) -> Self {
+        Self {
+            slots,
+            slot_size,
+            event_size: align(Event::size_of(None)),
+        }
+    }
+
+    fn subscribers_offset(&self) -> usize {
+        align(mem::size_of::<Header>())
+    }
+
+    fn subscriber_stride(&self) -> usize {
+        align(mem::size_of::<SubscriberEntry>()) + self.event_size
+    }
+
+    fn slots_offset(&self) -> usize {
+        self.subscribers_offset() + MAX_SUBSCRIBERS * self.subscriber_stride()
+    }
+
+    fn slot_stride(&self) -> usize {
+        align(mem::size_of::<SlotHeader>() + self.slot_size)
+    }
+
+    fn len(&self) -> usize {
+        self.slots_offset() + self.slots * self.slot_stride()
+    }
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(16)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The shared memory region of a topic.
+///
+/// The region is removed when the layer that created it is dropped. On
+/// Windows, it stays alive until all processes closed it.
+struct Region {
+    memory: Shmem,
+    layout: Layout,
+}
+
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    fn open_or_create(os_id: &str, config: &SharedMemoryConfig) -> Result<Self, BoxError> {
+        let layout = Layout::new(config.slots, config.slot_size);
+        match ShmemConf::new().os_id(os_id).size(layout.len()).create() {
+            Ok(memory) => {
+                let region = Self { memory, layout };
+                region.init();
+                Ok(region)
+            }
+            Err(ShmemError::LinkExists | ShmemError::MappingIdExists) => Self::open(os_id),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn init(&self) {
+        let header = Header {
+            magic: AtomicU32::new(0),
+            write_lock: AtomicU64::new(0),
+            slots: AtomicU64::new(self.layout.slots as u64),
+            slot_size: AtomicU64::new(self.layout.slot_size as u64),
+            sequence: AtomicU64::new(0),
+        };
+        unsafe { self.memory.as_ptr().cast::<Header>().write(header) };
+        for index in 0..MAX_SUBSCRIBERS {
+            let entry = SubscriberEntry {
+                state: AtomicU32::new(FREE),
+                generation: AtomicU64::new(0),
+                heartbeat: AtomicU64::new(0),
+            };
+            let offset = self.layout.subscribers_offset() + index * self.layout.subscriber_stride();
+            unsafe {
+                let ptr = self.memory.as_ptr().add(offset);
+                ptr.cast::<SubscriberEntry>().write(entry);
+            }
+        }
+        for index in 0..self.layout.slots {
+            let slot = SlotHeader {
+                sequence: AtomicU64::new(0),
+                len: AtomicU64::new(0),
+            };
+            let offset = self.layout.slots_offset() + index * self.layout.slot_stride();
+            unsafe {
+                self.memory
+                    .as_ptr()
+                    .add(offset)
+                    .cast::<SlotHeader>()
+                    .write(slot)
+            };
+        }
+        self.header().magic.store(MAGIC, Ordering::Release);
+    }
+
+    /// Opens a region that was created by another layer, waiting until it is
+    /// initialized.
+    fn open(os_id: &str) -> Result<Self, BoxError> {
+        let start = Instant::now();
+        loop {
+            // the region might not be sized yet if it was just created
+            let opened = ShmemConf::new().os_id(os_id).open();
+            if let Ok(memory) = opened {
+                if memory.len() >= mem::size_of::<Header>() {
+                    let header = unsafe { &*memory.as_ptr().cast::<Header>() };
+                    if header.magic.load(Ordering::Acquire) == MAGIC {
+                        let layout = Layout::new(
+                            header.slots.load(Ordering::Relaxed) as usize,
+                            header.slot_size.load(Ordering::Relaxed) as usize,
+                        );
+                        if memory.len() < layout.len() {
+                            return Err(
+                                format!("shared memory region `{os_id}` is too small").into()
+                            );
+                        }
+                        return Ok(Self { memory, layout });
+                    }
+                }
+            }
+            if start.elapsed() > INIT_TIMEOUT {
+                return Err(format!("shared memory region `{os_id}` was not initialized").into());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.memory.as_ptr().cast::<Header>() }
+    }
+
+    /// Returns the entry of the given subscriber and a pointer to its event.
+    fn subscriber(&self, index: usize) -> (&SubscriberEntry, *mut u8) {
+        let offset = self.layout.subscribers_offset() + index * self.layout.subscriber_stride();
+        unsafe {
+            let ptr = self.memory.as_ptr().add(offset);
+            let event = ptr.add(align(mem::size_of::<SubscriberEntry>()));
+            (&*ptr.cast::<SubscriberEntry>(), event)
+        }
+    }
+
+    /// Claims the first free subscriber entry.
+    fn claim_free_subscriber(&self) -> Option<usize> {
+        (0..MAX_SUBSCRIBERS).find(|&index| {
+            self.subscriber(index)
+                .0
+                .state
+                .compare_exchange(FREE, INITIALIZING, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    /// Claims the entry of a subscriber that didn't refresh its heartbeat
+    /// within [`STALE_TIMEOUT`], e.g. because its process crashed.
+    fn claim_stale_subscriber(&self) -> Option<usize> {
+        let now = now_millis();
+        (0..MAX_SUBSCRIBERS).find(|&index| {
+            let (entry, _) = self.subscriber(index);
+            let heartbeat = entry.heartbeat.load(Ordering::Relaxed);
+            now.saturating_sub(heartbeat) > STALE_TIMEOUT.as_millis() as u64
+                && entry
+                    .state
+                    .compare_exchange(ACTIVE, INITIALIZING, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+        })
+    }
+
+    /// Returns the slot of the given message and a pointer to its data.
+    fn slot(&self, sequence: u64) -> (&SlotHeader, *mut u8) {
+        let index = (sequence % self.layout.slots as u64) as usize;
+        let offset = self.layout.slots_offset() + index * self.layout.slot_stride();
+        unsafe {
+            let ptr = self.memory.as_ptr().add(offset);
+            let data = ptr.add(mem::size_of::<SlotHeader>());
+            (&*ptr.cast::<SlotHeader>(), data)
+        }
+    }
+
+    /// Copies the given message out of its slot.
+    ///
+    /// Returns `None` if the message was overwritten, which is detected by
+    /// checking the sequence number of the slot before and after copying.
+    fn read(&self, sequence: u64) -> Option<Vec<u8>> {
+        let (slot, data) = self.slot(sequence);
+        if slot.sequence.load(Ordering::Acquire) != sequence {
+            return None;
+        }
+        let len = (slot.len.load(Ordering::Relaxed) as usize).min(self.layout.slot_size);
+        let copy = unsafe { slice::from_raw_parts(data, len) }.to_vec();
+        fence(Ordering::Acquire);
+        (slot.sequence.load(Ordering::Relaxed) == sequence).then_some(copy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(test: &str, config: SharedMemoryConfig) -> SharedMemoryCommunicationLayer {
+        let prefix = format!("{test}-{}", std::process::id());
+        SharedMemoryCommunicationLayer::init(prefix, config).unwrap()
+    }
+
+    #[test]
+    fn deliver_to_all_subscribers() {
+        let mut publishing = layer("deliver", SharedMemoryConfig::default());
+        let mut subscribing = layer("deliver", SharedMemoryConfig::default());
+        let publisher = publishing.publisher("image").unwrap();
+        let mut first = subscribing.subscribe("image").unwrap();
+        let mut second = subscribing.subscribe("image").unwrap();
+
+        publisher.publish(&[1, 2, 3]).unwrap();
+        let mut sample = publisher.prepare(2).unwrap();
+        sample.as_mut_slice().copy_from_slice(&[4, 5]);
+        sample.publish().unwrap();
+
+        for subscriber in [&mut first, &mut second] {
+            assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[1, 2, 3][..]);
+            assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[4, 5][..]);
+        }
+    }
+
+    #[test]
+    fn skip_overwritten_messages() {
+        let config = SharedMemoryConfig {
+            slots: 4,
+            slot_size: 16,
+        };
+        let mut layer = layer("overwrite", config);
+        let publisher = layer.publisher("counter").unwrap();
+        let mut subscriber = layer.subscribe("counter").unwrap();
+
+        for i in 0..10u8 {
+            publisher.publish(&[i]).unwrap();
+        }
+        for i in 6..10u8 {
+            assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[i][..]);
+        }
+
+        let err = publisher.publish(&[0; 17]).unwrap_err();
+        assert!(err.to_string().contains("does not fit"), "{err}");
+    }
+
+    #[test]
+    fn take_over_abandoned_write_lock() {
+        let mut layer = layer("lock", SharedMemoryConfig::default());
+        let publisher = layer.publisher("image").unwrap();
+        let mut subscriber = layer.subscribe("image").unwrap();
+
+        // a publisher that crashed while holding the lock
+        let region = layer.region("image").unwrap();
+        let locked_at = now_millis() - 2 * LOCK_TIMEOUT.as_millis() as u64;
+        region
+            .header()
+            .write_lock
+            .store(locked_at, Ordering::Release);
+
+        publisher.publish(&[1]).unwrap();
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[1][..]);
+        assert_eq!(region.header().write_lock.load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn reuse_entries_of_stale_subscribers() {
+        let mut layer = layer("stale", SharedMemoryConfig::default());
+        let publisher = layer.publisher("image").unwrap();
+        let mut subscribers: Vec<_> = (0..MAX_SUBSCRIBERS)
+            .map(|_| layer.subscribe("image").unwrap())
+            .collect();
+        assert!(layer.subscribe("image").is_err());
+
+        // a subscriber whose process crashed
+        let region = layer.region("image").unwrap();
+        let heartbeat = now_millis() - 2 * STALE_TIMEOUT.as_millis() as u64;
+        region
+            .subscriber(3)
+            .0
+            .heartbeat
+            .store(heartbeat, Ordering::Relaxed);
+
+        let mut subscriber = layer.subscribe("image").unwrap();
+        publisher.publish(&[1]).unwrap();
+        assert_eq!(subscriber.recv().unwrap().unwrap().get(), &[1][..]);
+
+        let Err(err) = subscribers[3].recv() else {
+            panic!("expected the removed subscriber to fail");
+        };
+        assert!(err.to_string().contains("removed"), "{err}");
+        // dropping the stale subscriber doesn't free the reused entry
+        drop(subscribers.remove(3));
+        assert!(layer.subscribe("image").is_err());
+    }
+}
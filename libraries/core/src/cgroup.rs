@@ -0,0 +1,164 @@
+//! Detection of CPU throttling applied by Linux control groups.
+//!
+//! Containers and systemd slices often limit the CPU bandwidth of a process
+//! through the cgroup `cpu.max` (v2) or `cpu.cfs_quota_us` (v1) settings. When
+//! the quota is exhausted, the kernel stops scheduling the process until the
+//! next period starts, which shows up as latency spikes in the dataflow. The
+//! kernel reports the accumulated throttling in the `cpu.stat` file of the
+//! cgroup.
+
+use std::{path::PathBuf, time::Duration};
+
+/// Accumulated throttling statistics of a cgroup, as reported by `cpu.stat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CpuThrottling {
+    /// Number of enforcement periods that elapsed.
+    pub nr_periods: u64,
+    /// Number of periods in which the cgroup was throttled.
+    pub nr_throttled: u64,
+    /// Total time that the cgroup was throttled.
+    pub throttled_time: Duration,
+}
+
+impl CpuThrottling {
+    /// Reads the throttling statistics of the cgroup that the given process belongs to.
+    ///
+    /// Returns `None` if the process is not part of a cgroup with CPU accounting
+    /// or if the platform does not support cgroups (i.e. on non-Linux systems).
+    pub fn read_for_pid(pid: u32) -> Option<Self> {
+        Self::read(&format!("/proc/{pid}/cgroup"))
+    }
+
+    /// Reads the throttling statistics of the cgroup of the current process.
+    pub fn read_for_current_process() -> Option<Self> {
+        Self::read("/proc/self/cgroup")
+    }
+
+    /// Returns the throttling that happened since the given earlier measurement.
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            nr_periods: self.nr_periods.saturating_sub(earlier.nr_periods),
+            nr_throttled: self.nr_throttled.saturating_sub(earlier.nr_throttled),
+            throttled_time: self.throttled_time.saturating_sub(earlier.throttled_time),
+        }
+    }
+
+    fn read(proc_cgroup_path: &str) -> Option<Self> {
+        let proc_cgroup = std::fs::read_to_string(proc_cgroup_path).ok()?;
+        let cpu_stat_path = cpu_stat_path(&proc_cgroup)?;
+        let cpu_stat = std::fs::read_to_string(cpu_stat_path).ok()?;
+        parse_cpu_stat(&cpu_stat)
+    }
+}
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Finds the `cpu.stat` file for the cgroup described by a `/proc/<pid>/cgroup` file.
+///
+/// Prefers the `cpu` controller of cgroup v1 if present, otherwise falls back to
+/// the unified cgroup v2 hierarchy.
+fn cpu_stat_path(proc_cgroup: &str) -> Option<PathBuf> {
+    let mut unified = None;
+    for line in proc_cgroup.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(_id), Some(controllers), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let path = path.trim_start_matches('/');
+        if controllers.is_empty() {
+            unified = Some(PathBuf::from(CGROUP_ROOT).join(path).join("cpu.stat"));
+        } else if controllers.split(',').any(|c| c == "cpu") {
+            return Some(
+                PathBuf::from(CGROUP_ROOT)
+                    .join(controllers)
+                    .join(path)
+                    .join("cpu.stat"),
+            );
+        }
+    }
+    unified
+}
+
+/// Parses the content of a cgroup v1 or v2 `cpu.stat` file.
+fn parse_cpu_stat(cpu_stat: &str) -> Option<CpuThrottling> {
+    let mut nr_periods = None;
+    let mut nr_throttled = None;
+    let mut throttled_time = None;
+    for line in cpu_stat.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<u64>() else {
+            continue;
+        };
+        match key {
+            "nr_periods" => nr_periods = Some(value),
+            "nr_throttled" => nr_throttled = Some(value),
+            // cgroup v2
+            "throttled_usec" => throttled_time = Some(Duration::from_micros(value)),
+            // cgroup v1
+            "throttled_time" => throttled_time = Some(Duration::from_nanos(value)),
+            _ => {}
+        }
+    }
+
+    Some(CpuThrottling {
+        nr_periods: nr_periods?,
+        nr_throttled: nr_throttled?,
+        throttled_time: throttled_time?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cgroup_v2() {
+        let proc_cgroup = "0::/system.slice/docker-abc.scope\n";
+        assert_eq!(
+            cpu_stat_path(proc_cgroup),
+            Some(PathBuf::from(
+                "/sys/fs/cgroup/system.slice/docker-abc.scope/cpu.stat"
+            ))
+        );
+
+        let cpu_stat = "usage_usec 1000\nuser_usec 600\nsystem_usec 400\n\
+            nr_periods 20\nnr_throttled 5\nthrottled_usec 2500\n";
+        assert_eq!(
+            parse_cpu_stat(cpu_stat),
+            Some(CpuThrottling {
+                nr_periods: 20,
+                nr_throttled: 5,
+                throttled_time: Duration::from_micros(2500),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_cgroup_v1() {
+        let proc_cgroup = "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc\n0::/\n";
+        assert_eq!(
+            cpu_stat_path(proc_cgroup),
+            Some(PathBuf::from(
+                "/sys/fs/cgroup/cpu,cpuacct/docker/abc/cpu.stat"
+            ))
+        );
+
+        let cpu_stat = "nr_periods 7\nnr_throttled 3\nthrottled_time 1500000\n";
+        assert_eq!(
+            parse_cpu_stat(cpu_stat),
+            Some(CpuThrottling {
+                nr_periods: 7,
+                nr_throttled: 3,
+                throttled_time: Duration::from_nanos(1_500_000),
+            })
+        );
+    }
+
+    #[test]
+    fn no_cpu_accounting() {
+        assert_eq!(parse_cpu_stat("usage_usec 1000\n"), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Compaction of streams that carry keyed state updates.
+//!
+//! Inputs that receive updates of a keyed state, e.g. object tracks, can set
+//! `compact_by: metadata.track_id`. When messages of such an input are buffered,
+//! only the latest message per key is retained. This applies to the input queue
+//! of the receiver, to the history that the daemon replays to restarted nodes
+//! (up to 1024 keys per input), and to recordings of `dora-record`, which write
+//! the latest message per key once per second. So their size is proportional to
+//! the number of keys instead of the number of messages. Messages without the
+//! key are never compacted.
+
+use crate::condition::{metadata_field, Value};
+use dora_message::MetadataParameters;
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CompactionKey {
+    field: String,
+}
+
+impl CompactionKey {
+    /// Returns the key of the message with the given metadata, if it has one.
+    pub fn key_of(&self, metadata: &MetadataParameters) -> Option<String> {
+        let key = match metadata_field(metadata, &self.field)? {
+            Value::Number(number) => number.to_string(),
+            Value::String(string) => string,
+            Value::Bool(value) => value.to_string(),
+        };
+        Some(key)
+    }
+}
+
+impl FromStr for CompactionKey {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let field = s
+            .trim()
+            .strip_prefix("metadata.")
+            .filter(|field| !field.is_empty())
+            .ok_or_else(|| eyre!("expected a `metadata.<field>` compaction key, got `{s}`"))?;
+        Ok(Self {
+            field: field.to_owned(),
+        })
+    }
+}
+
+impl TryFrom<String> for CompactionKey {
+    type Error = eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for CompactionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "metadata.{}", self.field)
+    }
+}
+
+impl From<CompactionKey> for String {
+    fn from(value: CompactionKey) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_message::{MetadataValue, SequenceNumber};
+
+    #[test]
+    fn message_keys() {
+        let mut metadata = MetadataParameters::default();
+        metadata
+            .values
+            .insert("track_id".into(), MetadataValue::Integer(7));
+        metadata.sequence = Some(SequenceNumber {
+            key: "left".into(),
+            number: 3,
+        });
+
+        let key: CompactionKey = "metadata.track_id".parse().unwrap();
+        assert_eq!(key.key_of(&metadata).as_deref(), Some("7"));
+        let key: CompactionKey = "metadata.sequence.key".parse().unwrap();
+        assert_eq!(key.key_of(&metadata).as_deref(), Some("left"));
+        let key: CompactionKey = "metadata.object".parse().unwrap();
+        assert_eq!(key.key_of(&metadata), None);
+
+        assert!("track_id".parse::<CompactionKey>().is_err());
+    }
+}
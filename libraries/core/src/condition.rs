@@ -0,0 +1,316 @@
+//! Conditions for forwarding messages to an input.
+//!
+//! Conditions are configured through the `when` option of inputs and compare
+//! metadata fields of a message with constants, e.g.
+//! `when: metadata.sequence.number >= 10`. User-defined metadata values are
+//! accessible as `metadata.values.<key>`, e.g.
+//! `when: metadata.values.confidence > 0.5`, or through their key alone if it
+//! doesn't collide with a built-in field.
+//! Multiple comparisons can be combined with `&&`. Messages that don't match the
+//! condition are not forwarded to the input, which avoids trivial filter
+//! operators.
+
+use dora_message::{MetadataParameters, MetadataValue};
+use eyre::{bail, eyre};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Condition {
+    source: String,
+    comparisons: Vec<Comparison>,
+}
+
+impl Condition {
+    /// Checks whether a message with the given metadata should be forwarded.
+    ///
+    /// Comparisons with metadata fields that are not set never match.
+    pub fn matches(&self, metadata: &MetadataParameters) -> bool {
+        self.comparisons
+            .iter()
+            .all(|comparison| comparison.matches(metadata))
+    }
+}
+
+impl PartialEq for Condition {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Condition {}
+
+#[derive(Debug, Clone)]
+struct Comparison {
+    field: String,
+    operator: Operator,
+    value: Value,
+}
+
+impl Comparison {
+    fn matches(&self, metadata: &MetadataParameters) -> bool {
+        let Some(field) = metadata_field(metadata, &self.field) else {
+            return false;
+        };
+        let ordering = match (&field, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.operator {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::Ne => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A metadata field value or a constant of a condition.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+/// Returns the value of the metadata field with the given name, if it is set.
+///
+/// User-defined values are accessed as `values.<key>`. Names that don't match
+/// a built-in field are looked up in the user-defined values too.
+pub(crate) fn metadata_field(metadata: &MetadataParameters, field: &str) -> Option<Value> {
+    let value = match field {
+        "watermark" => Value::Number(metadata.watermark as f64),
+        "deadline" => Value::Number(metadata.deadline as f64),
+        "sequence.key" => Value::String(metadata.sequence.as_ref()?.key.clone()),
+        "sequence.number" => Value::Number(metadata.sequence.as_ref()?.number as f64),
+        "device.type" => Value::Number(metadata.device?.device_type.into()),
+        "device.id" => Value::Number(metadata.device?.device_id.into()),
+        key => match metadata
+            .values
+            .get(key.strip_prefix("values.").unwrap_or(key))?
+        {
+            MetadataValue::Bool(value) => Value::Bool(*value),
+            MetadataValue::Integer(value) => Value::Number(*value as f64),
+            MetadataValue::Float(value) => Value::Number(*value),
+            MetadataValue::String(value) => Value::String(value.clone()),
+            MetadataValue::Bytes(_) | MetadataValue::List(_) => return None,
+        },
+    };
+    Some(value)
+}
+
+impl FromStr for Condition {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let comparisons = split_unquoted(s, "&&")?
+            .into_iter()
+            .map(parse_comparison)
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self {
+            source: s.trim().to_owned(),
+            comparisons,
+        })
+    }
+}
+
+/// Splits the given string at all occurrences of `separator` that are not part
+/// of a single or double quoted string.
+fn split_unquoted<'a>(s: &'a str, separator: &str) -> eyre::Result<Vec<&'a str>> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if s[i..].starts_with(separator) => {
+                parts.push(&s[start..i]);
+                start = i + separator.len();
+                // skip the rest of the separator
+                for _ in 1..separator.chars().count() {
+                    chars.next();
+                }
+            }
+            None => {}
+        }
+    }
+    if let Some(quote) = quote {
+        bail!("unterminated {quote} quote in `{s}`");
+    }
+    parts.push(&s[start..]);
+    Ok(parts)
+}
+
+fn parse_comparison(s: &str) -> eyre::Result<Comparison> {
+    let s = s.trim();
+    let operator_start = s
+        .find(['=', '!', '<', '>'])
+        .ok_or_else(|| eyre!("expected a comparison like `metadata.<field> > 0`, got `{s}`"))?;
+    let (field, rest) = s.split_at(operator_start);
+    let (operator, value) = if let Some(value) = rest.strip_prefix("==") {
+        (Operator::Eq, value)
+    } else if let Some(value) = rest.strip_prefix("!=") {
+        (Operator::Ne, value)
+    } else if let Some(value) = rest.strip_prefix("<=") {
+        (Operator::Le, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Operator::Ge, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Operator::Lt, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Operator::Gt, value)
+    } else {
+        bail!("invalid comparison operator in `{s}`")
+    };
+
+    let field = field
+        .trim()
+        .strip_prefix("metadata.")
+        .ok_or_else(|| eyre!("left side of `{s}` must be a `metadata.<field>`"))?;
+    if field.is_empty() {
+        bail!("missing metadata field name in `{s}`");
+    }
+
+    let value = value.trim();
+    let value = if value == "true" || value == "false" {
+        Value::Bool(value == "true")
+    } else if let Some(string) = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+    {
+        Value::String(string.to_owned())
+    } else {
+        Value::Number(value.parse().map_err(|_| {
+            eyre!("expected a number, a quoted string, or a boolean, got `{value}`")
+        })?)
+    };
+
+    Ok(Comparison {
+        field: field.to_owned(),
+        operator,
+        value,
+    })
+}
+
+impl TryFrom<String> for Condition {
+    type Error = eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl From<Condition> for String {
+    fn from(value: Condition) -> Self {
+        value.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_message::SequenceNumber;
+
+    fn metadata(key: &str, number: u64) -> MetadataParameters {
+        MetadataParameters {
+            sequence: Some(SequenceNumber {
+                key: key.to_owned(),
+                number,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        let condition: Condition =
+            "metadata.sequence.number >= 10 && metadata.sequence.key == 'left'"
+                .parse()
+                .unwrap();
+        assert!(condition.matches(&metadata("left", 10)));
+        assert!(!condition.matches(&metadata("left", 9)));
+        assert!(!condition.matches(&metadata("right", 12)));
+        // unset fields never match
+        assert!(!condition.matches(&MetadataParameters::default()));
+
+        let condition: Condition = "metadata.confidence > 0.5".parse().unwrap();
+        let mut parameters = MetadataParameters::default();
+        parameters
+            .values
+            .insert("confidence".into(), MetadataValue::Float(0.7));
+        assert!(condition.matches(&parameters));
+        parameters
+            .values
+            .insert("confidence".into(), MetadataValue::Integer(0));
+        assert!(!condition.matches(&parameters));
+
+        // user-defined values don't shadow built-in fields
+        let condition: Condition = "metadata.values.watermark == 1".parse().unwrap();
+        parameters
+            .values
+            .insert("watermark".into(), MetadataValue::Integer(1));
+        assert!(condition.matches(&parameters));
+        let condition: Condition = "metadata.watermark == 1".parse().unwrap();
+        assert!(!condition.matches(&parameters));
+    }
+
+    #[test]
+    fn keep_separators_in_quoted_strings() {
+        let condition: Condition = "metadata.label == \"cat && dog\" && metadata.note != 'a&&b'"
+            .parse()
+            .unwrap();
+        let mut parameters = MetadataParameters::default();
+        parameters
+            .values
+            .insert("label".into(), MetadataValue::String("cat && dog".into()));
+        parameters
+            .values
+            .insert("note".into(), MetadataValue::String("c".into()));
+        assert!(condition.matches(&parameters));
+        parameters
+            .values
+            .insert("note".into(), MetadataValue::String("a&&b".into()));
+        assert!(!condition.matches(&parameters));
+
+        assert!("metadata.label == 'cat && dog"
+            .parse::<Condition>()
+            .is_err());
+    }
+
+    #[test]
+    fn reject_invalid_conditions() {
+        assert!("sequence.number > 1".parse::<Condition>().is_err());
+        assert!("metadata.sequence.number".parse::<Condition>().is_err());
+        assert!("metadata.sequence.number > high"
+            .parse::<Condition>()
+            .is_err());
+    }
+}
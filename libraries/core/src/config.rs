@@ -1,15 +1,24 @@
+use crate::compaction::CompactionKey;
+use crate::condition::Condition;
+use crate::descriptor::schema::{ByteSizeSchema, DurationSchema};
+use crate::message::{DataFormat, MetadataValue};
+use eyre::WrapErr;
 use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     borrow::Borrow,
     collections::{BTreeMap, BTreeSet},
     convert::Infallible,
     fmt,
+    num::NonZeroU32,
     str::FromStr,
     time::Duration,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct NodeId(String);
 
 impl FromStr for NodeId {
@@ -32,7 +41,9 @@ impl std::fmt::Display for NodeId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct OperatorId(String);
 
 impl FromStr for OperatorId {
@@ -61,7 +72,9 @@ impl AsRef<str> for OperatorId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct DataId(String);
 
 impl From<DataId> for String {
@@ -236,12 +249,168 @@ pub fn format_duration(interval: Duration) -> FormattedDuration {
     FormattedDuration(interval)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Parses a duration with a unit suffix, e.g. `100ms`, `1.5s`, or `500us`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(|| format!("duration `{s}` has no unit (e.g. `ms` or `s`)"))?;
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration value `{value}`"))?;
+    let secs = match unit.trim() {
+        "ns" => value / 1e9,
+        "us" => value / 1e6,
+        "ms" => value / 1e3,
+        "s" => value,
+        "min" => value * 60.,
+        other => {
+            return Err(format!(
+                "unknown duration unit `{other}` (expected `ns`, `us`, `ms`, `s`, or `min`)"
+            ))
+        }
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("duration `{s}` is out of range"))
+}
+
+/// Formats a duration in the format accepted by [`parse_duration`].
+pub fn duration_to_string(duration: Duration) -> String {
+    if duration.subsec_nanos() == 0 {
+        format!("{}s", duration.as_secs())
+    } else if duration.subsec_nanos() % 1_000_000 == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if duration.subsec_nanos() % 1_000 == 0 {
+        format!("{}us", duration.as_micros())
+    } else {
+        format!("{}ns", duration.as_nanos())
+    }
+}
+
+pub(crate) mod optional_duration {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(duration) => serializer.serialize_str(&super::duration_to_string(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| super::parse_duration(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Parses a size in bytes, e.g. `4096`, `512KB`, or `1.5GiB`.
+///
+/// Decimal (`KB`, `MB`, `GB`) and binary (`KiB`, `MiB`, `GiB`) units are supported.
+pub fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid byte size value `{value}`"))?;
+    let factor: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        other => {
+            return Err(format!(
+                "unknown byte size unit `{other}` (expected `B`, `KB`, `MB`, `GB`, `KiB`, `MiB`, or `GiB`)"
+            ))
+        }
+    };
+    Ok((value * factor as f64) as u64)
+}
+
+/// Formats a byte size in the format accepted by [`parse_byte_size`].
+pub fn byte_size_to_string(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("GB", 1_000_000_000),
+        ("MB", 1_000_000),
+        ("KB", 1_000),
+    ];
+    UNITS
+        .iter()
+        .find(|(_, factor)| bytes != 0 && bytes % factor == 0)
+        .map(|(unit, factor)| format!("{}{unit}", bytes / factor))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+pub(crate) mod optional_byte_size {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Bytes(u64),
+        String(String),
+    }
+
+    pub fn serialize<S>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(bytes) => serializer.serialize_str(&super::byte_size_to_string(*bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<Raw>::deserialize(deserializer)?
+            .map(|raw| match raw {
+                Raw::Bytes(bytes) => Ok(bytes),
+                Raw::String(s) => super::parse_byte_size(&s).map_err(serde::de::Error::custom),
+            })
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct NodeRunConfig {
     #[serde(default)]
     pub inputs: BTreeMap<DataId, Input>,
     #[serde(default)]
     pub outputs: BTreeSet<DataId>,
+    /// Request/response services that the node answers.
+    ///
+    /// Other nodes call them as `<node_id>/<service>`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub services: BTreeSet<DataId>,
+    /// Video codecs that outputs are encoded with when they are sent to other
+    /// machines, e.g. `image: h264`.
+    ///
+    /// Receivers get the decoded images.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub encoding: BTreeMap<DataId, VideoCodec>,
+    /// Deliver an `OutputUnsubscribed` event when all consumers of an output
+    /// stopped, and an `OutputSubscribed` event once a consumer runs again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub notify_unsubscribed: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -249,31 +418,252 @@ pub struct NodeRunConfig {
 pub struct Input {
     pub mapping: InputMapping,
     pub queue_size: Option<usize>,
+    /// Deliver messages in the order given by their `sequence` metadata instead of
+    /// their arrival order.
+    ///
+    /// Messages are ordered per sequence key, across all inputs of the node that
+    /// enable this option. This makes it possible to merge the outputs of
+    /// multiple parallel workers back into a single logically ordered stream.
+    pub order_by_key: bool,
+    /// Maximum time that `order_by_key` holds back a message while waiting
+    /// for the messages with lower sequence numbers.
+    ///
+    /// The missing messages are skipped when the timeout expires. Defaults to
+    /// [`DEFAULT_ORDER_TIMEOUT`].
+    pub order_timeout: Option<Duration>,
+    /// Expected maximum time between two consecutive messages on this input.
+    ///
+    /// If no message arrives within this time, the node receives a
+    /// `DeadlineMissed` event for the input.
+    pub deadline: Option<Duration>,
+    /// Builtin conversion that is applied to every message of this input
+    /// before it is delivered to the node.
+    ///
+    /// Messages that can't be converted are dropped with a warning.
+    pub convert: Option<Conversion>,
+    /// Only forward messages whose metadata matches this condition.
+    pub when: Option<Condition>,
+    /// Deliver consecutive messages of this input together as a single
+    /// `InputBatch` event. Only supported for operators.
+    pub batch: Option<InputBatch>,
+    /// Serialize the data of this input to the given format, for consumers
+    /// that don't use the Arrow libraries.
+    ///
+    /// The sender serializes its output once per requested format before
+    /// publishing it, so that the serialized data can be forwarded to other
+    /// machines and bridges as is.
+    pub format: Option<DataFormat>,
+    /// Retain only the latest message per key when messages of this input are
+    /// buffered, for streams of keyed state updates.
+    pub compact_by: Option<CompactionKey>,
+    /// Forward at most this many messages per second to this input, dropping
+    /// the others.
+    pub max_rate: Option<MaxRate>,
+    /// Forward only every n-th message to this input, dropping the others.
+    pub every_nth: Option<NonZeroU32>,
+    /// Compress the messages of this input while they are sent between
+    /// machines.
+    pub compression: Option<Compression>,
+    /// Drop messages of this input that are older than this duration when they
+    /// would be delivered, e.g. because the node was busy.
+    ///
+    /// Ignored in deterministic dataflows.
+    pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+// only used to (de)serialize `Input`, so the size difference doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum InputDef {
     MappingOnly(InputMapping),
     WithOptions {
         source: InputMapping,
         queue_size: Option<usize>,
+        #[serde(default)]
+        order_by_key: bool,
+        #[serde(
+            default,
+            with = "optional_duration",
+            skip_serializing_if = "Option::is_none"
+        )]
+        #[schemars(with = "Option<DurationSchema>")]
+        order_timeout: Option<Duration>,
+        #[serde(default, with = "optional_duration")]
+        #[schemars(with = "Option<DurationSchema>")]
+        deadline: Option<Duration>,
+        #[serde(default)]
+        convert: Option<Conversion>,
+        #[serde(default)]
+        when: Option<Condition>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        batch: Option<InputBatch>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(with = "Option<String>")]
+        format: Option<DataFormat>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compact_by: Option<CompactionKey>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_rate: Option<MaxRate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        every_nth: Option<NonZeroU32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(with = "Option<CompressionDef>")]
+        compression: Option<Compression>,
+        #[serde(
+            default,
+            with = "optional_duration",
+            skip_serializing_if = "Option::is_none"
+        )]
+        #[schemars(with = "Option<DurationSchema>")]
+        ttl: Option<Duration>,
     },
 }
 
+/// Queue size of inputs that don't specify a `queue_size`.
+pub const DEFAULT_QUEUE_SIZE: usize = 10;
+
+/// Hold timeout of `order_by_key` inputs that don't specify an `order_timeout`.
+pub const DEFAULT_ORDER_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl Input {
+    /// The resolved queue configuration of the input.
+    ///
+    /// Dropped messages would depend on timing, so the queues of deterministic
+    /// dataflows keep all messages.
+    pub fn queue_config(&self, deterministic: bool) -> InputQueueConfig {
+        InputQueueConfig {
+            queue_size: self.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE),
+            policy: if deterministic {
+                QueuePolicy::KeepAll
+            } else {
+                QueuePolicy::DropOldest
+            },
+            ttl: self.ttl.filter(|_| !deterministic),
+            order_by_key: self.order_by_key,
+            deadline: self.deadline,
+            batch: self.batch,
+            compact_by: self.compact_by.clone(),
+        }
+    }
+
+    /// Whether messages between operators of the same runtime node can be
+    /// delivered to this input by the runtime directly.
+    ///
+    /// The `max_rate`, `every_nth`, `compact_by`, and `order_by_key` options are
+    /// applied before the messages reach the event stream of the node, so inputs
+    /// that use them are always routed through the daemon. The same applies to
+    /// inputs with a `format`, which receive the serialized copy of the sender.
+    pub fn deliverable_in_process(&self) -> bool {
+        self.max_rate.is_none()
+            && self.every_nth.is_none()
+            && self.compact_by.is_none()
+            && !self.order_by_key
+            && self.serialization_format().is_none()
+    }
+
+    /// The `format` of the input, unless it's the default Arrow format.
+    pub fn serialization_format(&self) -> Option<DataFormat> {
+        self.format.filter(|format| *format != DataFormat::Arrow)
+    }
+}
+
+/// How the messages of an input are queued before they are delivered.
+///
+/// Lets nodes and operators adapt to the declared configuration, e.g. to
+/// preallocate buffers of the right size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputQueueConfig {
+    /// Maximum number of queued messages, unless the policy is
+    /// [`QueuePolicy::KeepAll`].
+    pub queue_size: usize,
+    /// What happens to new messages when the queue is full.
+    pub policy: QueuePolicy,
+    /// Maximum age of delivered messages. Older messages are dropped.
+    #[serde(with = "optional_duration")]
+    pub ttl: Option<Duration>,
+    /// Whether messages are delivered in the order of their `sequence` metadata.
+    pub order_by_key: bool,
+    /// Expected maximum time between two consecutive messages.
+    #[serde(with = "optional_duration")]
+    pub deadline: Option<Duration>,
+    /// Batching of consecutive messages, if enabled.
+    pub batch: Option<InputBatch>,
+    /// Metadata field that identifies the key of compacted messages.
+    pub compact_by: Option<CompactionKey>,
+}
+
+impl InputQueueConfig {
+    /// The maximum number of queued messages, or `usize::MAX` if the queue is
+    /// unbounded.
+    pub fn effective_queue_size(&self) -> usize {
+        match self.policy {
+            QueuePolicy::DropOldest => self.queue_size,
+            QueuePolicy::KeepAll => usize::MAX,
+        }
+    }
+}
+
+/// Behavior of an input queue when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// The oldest queued message is dropped to make room for the new one.
+    DropOldest,
+    /// No messages are dropped and the queue grows as needed. Used by
+    /// deterministic dataflows.
+    KeepAll,
+}
+
 impl From<Input> for InputDef {
     fn from(input: Input) -> Self {
         match input {
             Input {
                 mapping,
                 queue_size: None,
+                order_by_key: false,
+                order_timeout: None,
+                deadline: None,
+                convert: None,
+                when: None,
+                batch: None,
+                format: None,
+                compact_by: None,
+                max_rate: None,
+                every_nth: None,
+                compression: None,
+                ttl: None,
             } => Self::MappingOnly(mapping),
             Input {
                 mapping,
                 queue_size,
+                order_by_key,
+                order_timeout,
+                deadline,
+                convert,
+                when,
+                batch,
+                format,
+                compact_by,
+                max_rate,
+                every_nth,
+                compression,
+                ttl,
             } => Self::WithOptions {
                 source: mapping,
                 queue_size,
+                order_by_key,
+                order_timeout,
+                deadline,
+                convert,
+                when,
+                batch,
+                format,
+                compact_by,
+                max_rate,
+                every_nth,
+                compression,
+                ttl,
             },
         }
     }
@@ -285,16 +675,355 @@ impl From<InputDef> for Input {
             InputDef::MappingOnly(mapping) => Self {
                 mapping,
                 queue_size: None,
+                order_by_key: false,
+                order_timeout: None,
+                deadline: None,
+                convert: None,
+                when: None,
+                batch: None,
+                format: None,
+                compact_by: None,
+                max_rate: None,
+                every_nth: None,
+                compression: None,
+                ttl: None,
             },
-            InputDef::WithOptions { source, queue_size } => Self {
+            InputDef::WithOptions {
+                source,
+                queue_size,
+                order_by_key,
+                order_timeout,
+                deadline,
+                convert,
+                when,
+                batch,
+                format,
+                compact_by,
+                max_rate,
+                every_nth,
+                compression,
+                ttl,
+            } => Self {
                 mapping: source,
                 queue_size,
+                order_by_key,
+                order_timeout,
+                deadline,
+                convert,
+                when,
+                batch,
+                format,
+                compact_by,
+                max_rate,
+                every_nth,
+                compression,
+                ttl,
             },
         }
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+/// Coalesces consecutive messages of an input into a single event.
+///
+/// A batch is delivered when it contains `max_size` messages or when its first
+/// message is older than `max_latency`, whichever happens first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InputBatch {
+    #[serde(default = "default_batch_max_size")]
+    pub max_size: usize,
+    /// Defaults to `10ms`.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<DurationSchema>")]
+    pub max_latency: Option<Duration>,
+}
+
+impl InputBatch {
+    pub fn max_latency(&self) -> Duration {
+        self.max_latency.unwrap_or(Duration::from_millis(10))
+    }
+}
+
+fn default_batch_max_size() -> usize {
+    32
+}
+
+/// Maximum message rate of an input, specified as e.g. `max_rate: 10hz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MaxRate {
+    millihertz: u64,
+}
+
+impl MaxRate {
+    /// Minimum time between two forwarded messages.
+    pub fn min_interval(&self) -> Duration {
+        Duration::from_secs_f64(1000. / self.millihertz as f64)
+    }
+}
+
+impl FromStr for MaxRate {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s
+            .trim()
+            .strip_suffix("hz")
+            .or_else(|| s.trim().strip_suffix("Hz"))
+            .ok_or_else(|| eyre::eyre!("expected a rate in `hz`, e.g. `10hz`, got `{s}`"))?;
+        let hertz: f64 = value
+            .trim()
+            .parse()
+            .wrap_err_with(|| format!("invalid rate `{s}`"))?;
+        let millihertz = (hertz * 1000.).round();
+        if !millihertz.is_finite() || millihertz < 1. {
+            eyre::bail!("rate must be at least 0.001hz, got `{s}`");
+        }
+        Ok(Self {
+            millihertz: millihertz as u64,
+        })
+    }
+}
+
+impl TryFrom<String> for MaxRate {
+    type Error = eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for MaxRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.millihertz % 1000 == 0 {
+            write!(f, "{}hz", self.millihertz / 1000)
+        } else {
+            write!(f, "{}hz", self.millihertz as f64 / 1000.)
+        }
+    }
+}
+
+impl From<MaxRate> for String {
+    fn from(value: MaxRate) -> Self {
+        value.to_string()
+    }
+}
+
+/// Builtin conversions between common data encodings.
+///
+/// Conversions are specified as strings in the dataflow, e.g.
+/// `convert: bgr8-to-rgb8` or `convert: nv12-to-rgb8:640x480`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Conversion {
+    /// Swaps the first and third channel of interleaved 8-bit images.
+    Bgr8ToRgb8,
+    /// Swaps the first and third channel of interleaved 8-bit images.
+    Rgb8ToBgr8,
+    /// Converts NV12 (YUV 4:2:0) images of the given size to interleaved rgb8.
+    Nv12ToRgb8 { width: u32, height: u32 },
+    /// Converts a `float16` tensor to `float32`.
+    F16ToF32,
+    /// Converts a `float32` tensor to `float16`.
+    F32ToF16,
+    /// Converts `[x, y, z, w]` quaternions to `[roll, pitch, yaw]` euler angles.
+    QuaternionToEuler,
+    /// Converts `[roll, pitch, yaw]` euler angles to `[x, y, z, w]` quaternions.
+    EulerToQuaternion,
+    /// Reinterprets the values of a fixed-width array as raw bytes, for nodes
+    /// that still expect the byte arrays of the pre-Arrow API.
+    ToBytes,
+    /// Reinterprets raw bytes, e.g. from nodes that still send byte arrays, as
+    /// values of the given type.
+    FromBytes(ValueType),
+}
+
+/// Fixed-width value types that raw bytes can be reinterpreted as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+}
+
+impl ValueType {
+    const ALL: [ValueType; 10] = [
+        ValueType::UInt8,
+        ValueType::UInt16,
+        ValueType::UInt32,
+        ValueType::UInt64,
+        ValueType::Int8,
+        ValueType::Int16,
+        ValueType::Int32,
+        ValueType::Int64,
+        ValueType::Float32,
+        ValueType::Float64,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::UInt8 => "uint8",
+            ValueType::UInt16 => "uint16",
+            ValueType::UInt32 => "uint32",
+            ValueType::UInt64 => "uint64",
+            ValueType::Int8 => "int8",
+            ValueType::Int16 => "int16",
+            ValueType::Int32 => "int32",
+            ValueType::Int64 => "int64",
+            ValueType::Float32 => "float32",
+            ValueType::Float64 => "float64",
+        }
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|value_type| value_type.as_str() == s)
+            .ok_or_else(|| eyre::eyre!("unknown value type `{s}`"))
+    }
+}
+
+impl FromStr for Conversion {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let conversion = match s {
+            "bgr8-to-rgb8" => Self::Bgr8ToRgb8,
+            "rgb8-to-bgr8" => Self::Rgb8ToBgr8,
+            "f16-to-f32" => Self::F16ToF32,
+            "f32-to-f16" => Self::F32ToF16,
+            "quaternion-to-euler" => Self::QuaternionToEuler,
+            "euler-to-quaternion" => Self::EulerToQuaternion,
+            "to-bytes" => Self::ToBytes,
+            other if other.starts_with("bytes-to-") => {
+                Self::FromBytes(other.trim_start_matches("bytes-to-").parse()?)
+            }
+            other => {
+                let Some(size) = other.strip_prefix("nv12-to-rgb8:") else {
+                    eyre::bail!("unknown conversion `{other}`")
+                };
+                let (width, height) = size
+                    .split_once('x')
+                    .ok_or_else(|| eyre::eyre!("expected `<width>x<height>`, got `{size}`"))?;
+                let width = width.parse().wrap_err("invalid width")?;
+                let height = height.parse().wrap_err("invalid height")?;
+                if width % 2 != 0 || height % 2 != 0 {
+                    eyre::bail!("NV12 image size must be even, got `{size}`");
+                }
+                Self::Nv12ToRgb8 { width, height }
+            }
+        };
+        Ok(conversion)
+    }
+}
+
+impl TryFrom<String> for Conversion {
+    type Error = eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conversion::Bgr8ToRgb8 => f.write_str("bgr8-to-rgb8"),
+            Conversion::Rgb8ToBgr8 => f.write_str("rgb8-to-bgr8"),
+            Conversion::Nv12ToRgb8 { width, height } => {
+                write!(f, "nv12-to-rgb8:{width}x{height}")
+            }
+            Conversion::F16ToF32 => f.write_str("f16-to-f32"),
+            Conversion::F32ToF16 => f.write_str("f32-to-f16"),
+            Conversion::QuaternionToEuler => f.write_str("quaternion-to-euler"),
+            Conversion::EulerToQuaternion => f.write_str("euler-to-quaternion"),
+            Conversion::ToBytes => f.write_str("to-bytes"),
+            Conversion::FromBytes(value_type) => write!(f, "bytes-to-{}", value_type.as_str()),
+        }
+    }
+}
+
+impl From<Conversion> for String {
+    fn from(value: Conversion) -> Self {
+        value.to_string()
+    }
+}
+
+/// Policy for delivering the inputs of an operator together.
+///
+/// Specified as a string in the dataflow, e.g. `sync: approximate_time(window=50ms)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum InputSync {
+    /// Delivers one message per input once the timestamps of the newest message
+    /// and the closest message of every other input are at most `window` apart.
+    ApproximateTime { window: Duration },
+}
+
+impl FromStr for InputSync {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some(args) = s
+            .strip_prefix("approximate_time(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            eyre::bail!(
+                "unknown sync policy `{s}` (expected `approximate_time(window=<duration>)`)"
+            )
+        };
+        let window = args
+            .trim()
+            .strip_prefix("window")
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+            .ok_or_else(|| eyre::eyre!("expected `window=<duration>`, got `{args}`"))?;
+        let window = parse_duration(window).map_err(|err| eyre::eyre!(err))?;
+        Ok(Self::ApproximateTime { window })
+    }
+}
+
+impl TryFrom<String> for InputSync {
+    type Error = eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for InputSync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSync::ApproximateTime { window } => {
+                let window = duration_to_string(*window);
+                write!(f, "approximate_time(window={window})")
+            }
+        }
+    }
+}
+
+impl From<InputSync> for String {
+    fn from(value: InputSync) -> Self {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub struct CommunicationConfig {
     // see https://github.com/dtolnay/serde-yaml/issues/298
@@ -303,19 +1032,492 @@ pub struct CommunicationConfig {
         with = "serde_yaml::with::singleton_map",
         rename = "_unstable_local"
     )]
+    #[schemars(with = "LocalCommunicationConfig")]
     pub local: LocalCommunicationConfig,
     #[serde(
         default,
         with = "serde_yaml::with::singleton_map",
         rename = "_unstable_remote"
     )]
+    #[schemars(with = "RemoteCommunicationConfig")]
     pub remote: RemoteCommunicationConfig,
 
     // deprecated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
     pub zenoh: Option<serde_yaml::Value>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// Limits and pooling behavior of the shared memory that nodes allocate for their outputs.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SharedMemoryConfig {
+    /// Maximum shared memory that all nodes of the dataflow can allocate together
+    /// on a single machine.
+    #[serde(
+        default,
+        with = "optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<ByteSizeSchema>")]
+    pub dataflow_budget: Option<u64>,
+    /// Maximum shared memory that a single node can allocate.
+    ///
+    /// Can be overridden for individual nodes through their `shared_memory_budget` field.
+    #[serde(
+        default,
+        with = "optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<ByteSizeSchema>")]
+    pub node_budget: Option<u64>,
+    /// Number of freed shared memory regions that each node keeps for reuse.
+    ///
+    /// Reusing regions avoids the allocation latency for outputs of similar
+    /// size, e.g. fixed-size camera frames.
+    #[serde(default = "default_shared_memory_pool_size")]
+    pub pool_size: usize,
+}
+
+impl Default for SharedMemoryConfig {
+    fn default() -> Self {
+        Self {
+            dataflow_budget: None,
+            node_budget: None,
+            pool_size: default_shared_memory_pool_size(),
+        }
+    }
+}
+
+fn default_shared_memory_pool_size() -> usize {
+    20
+}
+
+/// Simulated time that replaces the wall-clock time of a dataflow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ClockConfig {
+    /// Output that publishes the simulated time, e.g. `simulator/clock`.
+    ///
+    /// Each message is a `UInt64` array whose last value is the simulated time
+    /// in nanoseconds. The time must not go backwards.
+    pub source: InputMapping,
+}
+
+/// The NUMA node that a node runs on.
+///
+/// Shared memory regions for the node's outputs are allocated on this NUMA node,
+/// which avoids cross-socket memory traffic on multi-socket machines. Specified
+/// as node number or as `auto`, which uses the NUMA node of the CPU that the
+/// node is started on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawNumaNode", into = "RawNumaNode")]
+pub enum NumaNode {
+    Auto,
+    Id(u32),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawNumaNode {
+    Id(u32),
+    String(String),
+}
+
+impl TryFrom<RawNumaNode> for NumaNode {
+    type Error = String;
+
+    fn try_from(value: RawNumaNode) -> Result<Self, Self::Error> {
+        match value {
+            RawNumaNode::Id(id) => Ok(NumaNode::Id(id)),
+            RawNumaNode::String(s) if s == "auto" => Ok(NumaNode::Auto),
+            RawNumaNode::String(s) => s
+                .parse()
+                .map(NumaNode::Id)
+                .map_err(|_| format!("invalid NUMA node `{s}` (expected a node number or `auto`)")),
+        }
+    }
+}
+
+impl From<NumaNode> for RawNumaNode {
+    fn from(value: NumaNode) -> Self {
+        match value {
+            NumaNode::Auto => RawNumaNode::String("auto".into()),
+            NumaNode::Id(id) => RawNumaNode::Id(id),
+        }
+    }
+}
+
+/// Value of an operator parameter, see
+/// [`OperatorConfig::live_parameters`](crate::descriptor::OperatorConfig::live_parameters).
+///
+/// Specified as plain YAML value, e.g. `0.5`, `true`, or `[1, 2, 3]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawParameterValue", into = "RawParameterValue")]
+pub struct ParameterValue(pub MetadataValue);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawParameterValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    List(Vec<RawParameterValue>),
+}
+
+impl From<RawParameterValue> for ParameterValue {
+    fn from(value: RawParameterValue) -> Self {
+        let value = match value {
+            RawParameterValue::Bool(value) => MetadataValue::Bool(value),
+            RawParameterValue::Integer(value) => MetadataValue::Integer(value),
+            RawParameterValue::Float(value) => MetadataValue::Float(value),
+            RawParameterValue::String(value) => MetadataValue::String(value),
+            RawParameterValue::List(values) => MetadataValue::List(
+                values
+                    .into_iter()
+                    .map(|value| ParameterValue::from(value).0)
+                    .collect(),
+            ),
+        };
+        Self(value)
+    }
+}
+
+impl From<ParameterValue> for RawParameterValue {
+    fn from(ParameterValue(value): ParameterValue) -> Self {
+        match value {
+            MetadataValue::Bool(value) => RawParameterValue::Bool(value),
+            MetadataValue::Integer(value) => RawParameterValue::Integer(value),
+            MetadataValue::Float(value) => RawParameterValue::Float(value),
+            MetadataValue::String(value) => RawParameterValue::String(value),
+            MetadataValue::Bytes(bytes) => RawParameterValue::List(
+                bytes
+                    .into_iter()
+                    .map(|byte| RawParameterValue::Integer(byte.into()))
+                    .collect(),
+            ),
+            MetadataValue::List(values) => RawParameterValue::List(
+                values
+                    .into_iter()
+                    .map(|value| ParameterValue(value).into())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl ParameterValue {
+    /// Checks that the given new value has the same type as this value and
+    /// returns the value that should be set.
+    ///
+    /// Integers are accepted for float parameters and converted to floats.
+    pub fn check_update(&self, value: &MetadataValue) -> eyre::Result<MetadataValue> {
+        check_parameter_type(&self.0, value)
+    }
+}
+
+fn check_parameter_type(
+    current: &MetadataValue,
+    value: &MetadataValue,
+) -> eyre::Result<MetadataValue> {
+    let checked = match (current, value) {
+        (MetadataValue::Float(_), MetadataValue::Integer(value)) => {
+            MetadataValue::Float(*value as f64)
+        }
+        (MetadataValue::List(current), MetadataValue::List(values)) => match current.first() {
+            // the element type of empty lists is unknown
+            None => value.clone(),
+            Some(element) => MetadataValue::List(
+                values
+                    .iter()
+                    .map(|value| check_parameter_type(element, value))
+                    .collect::<eyre::Result<_>>()?,
+            ),
+        },
+        (current, value) if std::mem::discriminant(current) == std::mem::discriminant(value) => {
+            value.clone()
+        }
+        (current, value) => eyre::bail!(
+            "expected {} value, got {} value `{}`",
+            parameter_type(current),
+            parameter_type(value),
+            ParameterValue(value.clone())
+        ),
+    };
+    Ok(checked)
+}
+
+fn parameter_type(value: &MetadataValue) -> &'static str {
+    match value {
+        MetadataValue::Bool(_) => "boolean",
+        MetadataValue::Integer(_) => "integer",
+        MetadataValue::Float(_) => "float",
+        MetadataValue::String(_) => "string",
+        MetadataValue::Bytes(_) => "bytes",
+        MetadataValue::List(_) => "list",
+    }
+}
+
+impl FromStr for ParameterValue {
+    type Err = Infallible;
+
+    /// Parses the given string as YAML value, falling back to a string value,
+    /// e.g. for `dora param set`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_yaml::from_str(s)
+            .unwrap_or_else(|_| ParameterValue(MetadataValue::String(s.to_owned()))))
+    }
+}
+
+impl fmt::Display for ParameterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&value)
+    }
+}
+
+/// Path of nodes that a latency budget applies to, e.g.
+/// `camera -> detector -> planner`.
+///
+/// Each node of the path must have an input from the previous node.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LatencyPath(pub Vec<NodeId>);
+
+impl FromStr for LatencyPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let nodes: Vec<_> = s.split("->").map(str::trim).collect();
+        if nodes.len() < 2 {
+            return Err(format!(
+                "latency path `{s}` must consist of at least two nodes, e.g. `camera -> detector`"
+            ));
+        }
+        if nodes.iter().any(|node| node.is_empty()) {
+            return Err(format!("latency path `{s}` contains an empty node ID"));
+        }
+        let nodes = nodes.into_iter().map(|node| node.to_owned().into());
+        Ok(Self(nodes.collect()))
+    }
+}
+
+impl fmt::Display for LatencyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, node) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" -> ")?;
+            }
+            write!(f, "{node}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes latency budgets as a map from paths to durations, e.g.
+/// `camera -> detector: 80ms`.
+pub(crate) mod latency_budgets {
+    use super::LatencyPath;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use std::{collections::BTreeMap, time::Duration};
+
+    pub fn serialize<S>(
+        value: &BTreeMap<LatencyPath, Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value
+            .iter()
+            .map(|(path, budget)| (path.to_string(), super::duration_to_string(*budget)))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BTreeMap<LatencyPath, Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(path, budget)| {
+                let path = path.parse().map_err(D::Error::custom)?;
+                let budget = super::parse_duration(&budget).map_err(D::Error::custom)?;
+                Ok((path, budget))
+            })
+            .collect()
+    }
+}
+
+/// Restart behavior of a node that exits with an error.
+///
+/// Failed nodes are restarted by the coordinator after an exponential backoff,
+/// until the restart budget is used up. Restarts are not supported for
+/// `shared` nodes and dataflows that are run without a coordinator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RestartPolicy {
+    /// Whether to restart only the failed node or the whole dataflow.
+    #[serde(default)]
+    pub scope: RestartScope,
+    /// Maximum number of restarts. Further failures are reported as usual.
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for each further restart.
+    ///
+    /// Defaults to `1s`.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<DurationSchema>")]
+    pub backoff: Option<Duration>,
+    /// Upper limit for the restart delay. Defaults to `1min`.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<DurationSchema>")]
+    pub max_backoff: Option<Duration>,
+    /// Treat the node as failed if it stops responding for this long.
+    ///
+    /// The requests of the node to its daemon act as heartbeats. A node is
+    /// responsive while it waits for its next event or while it sends
+    /// requests, e.g. outputs. A node that is busy with a single event or
+    /// stuck in a loop for longer than the timeout is killed and restarted
+    /// according to this policy.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<DurationSchema>")]
+    pub heartbeat_timeout: Option<Duration>,
+}
+
+impl RestartPolicy {
+    /// Returns the delay before restarting after the given number of previous restarts.
+    pub fn delay(&self, previous_restarts: u32) -> Duration {
+        let backoff = self.backoff.unwrap_or(Duration::from_secs(1));
+        let max_backoff = self.max_backoff.unwrap_or(Duration::from_secs(60));
+        backoff
+            .saturating_mul(2u32.saturating_pow(previous_restarts))
+            .min(max_backoff)
+    }
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RestartScope {
+    /// Restart only the failed node. The other nodes keep running and their
+    /// inputs stay connected to the restarted node.
+    #[default]
+    Node,
+    /// Stop the dataflow and start it again with a new UUID.
+    Dataflow,
+}
+
+impl fmt::Display for RestartScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartScope::Node => f.write_str("node"),
+            RestartScope::Dataflow => f.write_str("dataflow"),
+        }
+    }
+}
+
+/// Video codec that image outputs are encoded with before they are sent to
+/// other machines.
+///
+/// The images must be `UInt8` arrays of packed `rgb8` or `bgr8` pixels. Their
+/// size is read from the `width` and `height` metadata values and the pixel
+/// layout from the `encoding` value, which defaults to `rgb8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+}
+
+impl fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoCodec::H264 => f.write_str("h264"),
+            VideoCodec::H265 => f.write_str("h265"),
+        }
+    }
+}
+
+/// Compression of messages that are sent between machines, specified as e.g.
+/// `compression: zstd` or `compression: { algorithm: zstd, level: 3 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "CompressionDef", into = "CompressionDef")]
+pub struct Compression {
+    pub algorithm: CompressionAlgorithm,
+    /// Uses the default level of the algorithm if not set. Only supported by
+    /// `zstd`.
+    pub level: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionAlgorithm {
+    Zstd,
+    Lz4,
+}
+
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionAlgorithm::Zstd => f.write_str("zstd"),
+            CompressionAlgorithm::Lz4 => f.write_str("lz4"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum CompressionDef {
+    Algorithm(CompressionAlgorithm),
+    WithOptions {
+        algorithm: CompressionAlgorithm,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        level: Option<i32>,
+    },
+}
+
+impl From<CompressionDef> for Compression {
+    fn from(value: CompressionDef) -> Self {
+        match value {
+            CompressionDef::Algorithm(algorithm) => Self {
+                algorithm,
+                level: None,
+            },
+            CompressionDef::WithOptions { algorithm, level } => Self { algorithm, level },
+        }
+    }
+}
+
+impl From<Compression> for CompressionDef {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression {
+                algorithm,
+                level: None,
+            } => Self::Algorithm(algorithm),
+            Compression { algorithm, level } => Self::WithOptions { algorithm, level },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, JsonSchema)]
 pub enum LocalCommunicationConfig {
     Tcp,
     Shmem,
@@ -327,7 +1529,7 @@ impl Default for LocalCommunicationConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub enum RemoteCommunicationConfig {
     Tcp,
@@ -343,3 +1545,20 @@ impl Default for RemoteCommunicationConfig {
         Self::Tcp
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("100ms"), Ok(Duration::from_millis(100)));
+        assert_eq!(parse_duration(" 1.5 s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2min"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration("100").is_err());
+        assert!(parse_duration("1h").is_err());
+        // must not panic
+        assert!(parse_duration("1e400s").is_err());
+        assert!(parse_duration(&format!("{}s", u64::MAX)).is_err());
+    }
+}
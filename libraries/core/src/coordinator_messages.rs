@@ -1,6 +1,6 @@
-use crate::daemon_messages::DataflowId;
+use crate::{config::NodeId, daemon_messages::DataflowId, topics::RateReport};
 use eyre::eyre;
-use std::net::SocketAddr;
+use std::{fmt, net::SocketAddr};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum CoordinatorRequest {
@@ -24,10 +24,56 @@ pub enum DaemonEvent {
     AllNodesFinished {
         dataflow_id: DataflowId,
         result: Result<(), String>,
+        /// Message statistics of the inputs of the nodes on the machine.
+        report: RateReport,
+    },
+    NodeStateChanged {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        state: NodeState,
     },
     Heartbeat,
 }
 
+/// Lifecycle state of a node, as tracked by the daemon that runs it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum NodeState {
+    /// The node was spawned, but did not subscribe to its events yet.
+    Configuring,
+    /// The node subscribed to its events and is running.
+    Active,
+    /// The node was asked to stop and is shutting down.
+    Deactivating,
+    /// The node exited successfully.
+    Finalized,
+    /// The node failed to spawn or exited with an error.
+    Errored,
+    /// The node exited with an error and was restarted, but did not subscribe
+    /// to its events yet.
+    Restarting,
+}
+
+impl NodeState {
+    /// Whether the node exited already.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, NodeState::Finalized | NodeState::Errored)
+    }
+}
+
+impl fmt::Display for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            NodeState::Configuring => "configuring",
+            NodeState::Active => "active",
+            NodeState::Deactivating => "deactivating",
+            NodeState::Finalized => "finalized",
+            NodeState::Errored => "errored",
+            NodeState::Restarting => "restarting",
+        };
+        f.write_str(state)
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum RegisterResult {
     Ok,
@@ -3,14 +3,16 @@ use std::{
     fmt,
     net::SocketAddr,
     path::PathBuf,
+    time::Duration,
 };
 
 use crate::{
-    config::{DataId, NodeId, NodeRunConfig, OperatorId},
+    config::{CompressionAlgorithm, DataId, NodeId, NodeRunConfig, OperatorId, VideoCodec},
     descriptor::{Descriptor, OperatorDefinition, ResolvedNode},
+    topics::DataflowStats,
 };
 use aligned_vec::{AVec, ConstAlign};
-use dora_message::{uhlc, Metadata};
+use dora_message::{uhlc, Metadata, MetadataValue};
 use uuid::{NoContext, Timestamp, Uuid};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -39,6 +41,10 @@ pub enum DaemonCommunication {
 pub struct RuntimeConfig {
     pub node: NodeConfig,
     pub operators: Vec<OperatorDefinition>,
+    /// Environment variables that the runtime passes to each operator, including
+    /// the resolved secrets.
+    #[serde(default)]
+    pub operator_env: BTreeMap<OperatorId, BTreeMap<String, String>>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -48,6 +54,16 @@ pub enum DaemonRequest {
         node_id: NodeId,
         dora_version: String,
     },
+    /// Registers a read-only observer connection, e.g. for dashboards.
+    ///
+    /// Observers receive the given outputs of the dataflow as inputs named
+    /// `<node_id>/<output_id>`, or all outputs if the set is empty. They are not
+    /// allowed to send outputs or to close them.
+    RegisterObserver {
+        dataflow_id: DataflowId,
+        outputs: BTreeSet<(NodeId, DataId)>,
+        dora_version: String,
+    },
     Subscribe,
     SendMessage {
         output_id: DataId,
@@ -58,6 +74,11 @@ pub enum DaemonRequest {
     /// Signals that the node is finished sending outputs and that it received all
     /// required drop tokens.
     OutputsDone,
+    /// Signals that the node finished its initialization.
+    ///
+    /// Only relevant for nodes with `ready_signal` enabled. The daemon holds back
+    /// the start of the dataflow until all such nodes are ready.
+    Ready,
     NextEvent {
         drop_tokens: Vec<DropToken>,
     },
@@ -67,21 +88,57 @@ pub enum DaemonRequest {
     SubscribeDrop,
     NextFinishedDropTokens,
     EventStreamDropped,
+    /// Reserves shared memory of the given size from the dataflow's shared memory
+    /// budget before the node allocates a new region.
+    ReserveSharedMemory {
+        len: u64,
+    },
+    /// Returns previously reserved shared memory after the node freed a region.
+    ReleaseSharedMemory {
+        len: u64,
+    },
+    /// Calls a service of another node of the dataflow.
+    ///
+    /// The daemon replies once the request is forwarded. The response of the
+    /// service is delivered as [`NodeDropEvent::ServiceReply`] on the drop
+    /// stream, which is polled independently of the event stream.
+    CallService {
+        node_id: NodeId,
+        service: DataId,
+        request_id: u64,
+        /// Time after which the daemon answers the request with an error.
+        timeout: Duration,
+        metadata: Metadata,
+        data: Option<DataMessage>,
+    },
+    /// Answers a [`NodeEvent::ServiceRequest`].
+    ServiceReply {
+        caller: NodeId,
+        request_id: u64,
+        result: Result<ServiceResponse, String>,
+    },
 }
 
 impl DaemonRequest {
     pub fn expects_tcp_reply(&self) -> bool {
         #[allow(clippy::match_like_matches_macro)]
         match self {
-            DaemonRequest::SendMessage { .. } | DaemonRequest::ReportDropTokens { .. } => false,
+            DaemonRequest::SendMessage { .. }
+            | DaemonRequest::ReportDropTokens { .. }
+            | DaemonRequest::ServiceReply { .. } => false,
             DaemonRequest::Register { .. }
+            | DaemonRequest::RegisterObserver { .. }
             | DaemonRequest::Subscribe
             | DaemonRequest::CloseOutputs(_)
             | DaemonRequest::OutputsDone
+            | DaemonRequest::Ready
             | DaemonRequest::NextEvent { .. }
             | DaemonRequest::SubscribeDrop
             | DaemonRequest::NextFinishedDropTokens
-            | DaemonRequest::EventStreamDropped => true,
+            | DaemonRequest::EventStreamDropped
+            | DaemonRequest::ReserveSharedMemory { .. }
+            | DaemonRequest::ReleaseSharedMemory { .. }
+            | DaemonRequest::CallService { .. } => true,
         }
     }
 }
@@ -159,11 +216,56 @@ pub enum NodeEvent {
         id: DataId,
     },
     AllInputsClosed,
+    /// Another node called a service of this node.
+    ServiceRequest {
+        service: DataId,
+        caller: NodeId,
+        request_id: u64,
+        metadata: Metadata,
+        data: Option<DataMessage>,
+    },
+    /// A parameter of the node was changed, e.g. through `dora param set`.
+    ParameterUpdate {
+        key: String,
+        value: MetadataValue,
+    },
+    /// All consumers of the output stopped.
+    ///
+    /// Only sent to nodes that set `notify_unsubscribed`.
+    OutputUnsubscribed {
+        id: DataId,
+    },
+    /// A consumer of an unsubscribed output is running again.
+    OutputSubscribed {
+        id: DataId,
+    },
+    /// The simulated time of the dataflow advanced.
+    ///
+    /// Only sent for dataflows with a `clock`. Handled by the event stream,
+    /// which evaluates the input deadlines based on this time.
+    SimTime {
+        time: Duration,
+    },
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum NodeDropEvent {
-    OutputDropped { drop_token: DropToken },
+    OutputDropped {
+        drop_token: DropToken,
+    },
+    ServiceReply {
+        request_id: u64,
+        result: Result<ServiceResponse, String>,
+    },
+}
+
+/// The answer of a service to a request.
+///
+/// Service data is always sent inline, never through shared memory.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ServiceResponse {
+    pub metadata: Metadata,
+    pub data: Option<DataMessage>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -219,9 +321,36 @@ pub enum DaemonCoordinatorEvent {
         node_id: NodeId,
         operator_id: Option<OperatorId>,
     },
+    /// Change a parameter of a running node.
+    SetParameter {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        key: String,
+        value: MetadataValue,
+    },
     Logs {
         dataflow_id: DataflowId,
         node_id: NodeId,
+        #[serde(default)]
+        offset: u64,
+    },
+    Stats {
+        dataflow_id: DataflowId,
+    },
+    StopNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+    },
+    /// Restart a node that failed and has a restart policy.
+    RestartNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+    },
+    /// Finalize a failed node that is not restarted, e.g. because its restart
+    /// budget is used up.
+    ReleaseNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
     },
     Destroy,
     Heartbeat,
@@ -231,32 +360,78 @@ pub enum DaemonCoordinatorEvent {
 pub enum InterDaemonEvent {
     Output {
         dataflow_id: DataflowId,
+        token: DataflowToken,
         node_id: NodeId,
         output_id: DataId,
         metadata: Metadata,
         data: Option<AVec<u8, ConstAlign<128>>>,
+        /// Video codec that the data was encoded with by the sending daemon.
+        encoding: Option<VideoCodec>,
+        /// Algorithm that the data was compressed with by the sending daemon.
+        compression: Option<CompressionAlgorithm>,
+        /// Inputs that must not receive the output because the sending daemon
+        /// throttled them, see the `max_rate` and `every_nth` input options.
+        throttled_inputs: BTreeSet<(NodeId, DataId)>,
     },
     InputsClosed {
         dataflow_id: DataflowId,
+        token: DataflowToken,
         inputs: BTreeSet<(NodeId, DataId)>,
     },
+    /// Forwards a [`DaemonRequest::CallService`] to the daemon of the node
+    /// that serves the service.
+    ServiceRequest {
+        dataflow_id: DataflowId,
+        token: DataflowToken,
+        caller: NodeId,
+        /// Machine of the calling node, which receives the reply.
+        caller_machine: String,
+        node_id: NodeId,
+        service: DataId,
+        request_id: u64,
+        timeout: Duration,
+        metadata: Metadata,
+        data: Option<AVec<u8, ConstAlign<128>>>,
+    },
+    /// Answers an [`InterDaemonEvent::ServiceRequest`].
+    ServiceReply {
+        dataflow_id: DataflowId,
+        token: DataflowToken,
+        caller: NodeId,
+        request_id: u64,
+        result: Result<ServiceResponse, String>,
+    },
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum DaemonCoordinatorReply {
     SpawnResult(Result<(), String>),
     ReloadResult(Result<(), String>),
+    SetParameterResult(Result<(), String>),
     StopResult(Result<(), String>),
+    StopNodeResult(Result<(), String>),
+    RestartNodeResult(Result<(), String>),
+    ReleaseNodeResult(Result<(), String>),
     DestroyResult {
         result: Result<(), String>,
         #[serde(skip)]
         notify: Option<tokio::sync::oneshot::Sender<()>>,
     },
     Logs(Result<Vec<u8>, String>),
+    Stats(Result<DataflowStats, String>),
 }
 
 pub type DataflowId = Uuid;
 
+/// Random secret that is generated by the coordinator for each dataflow.
+///
+/// Daemons attach it to the messages that they send to other daemons and drop
+/// incoming messages with a wrong token, so they only accept messages from
+/// the nodes of their dataflow. With TLS, daemons don't use the token as sent
+/// by the coordinator, but derive the actual token from it and a secret that
+/// only the daemons know.
+pub type DataflowToken = String;
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct SpawnDataflowNodes {
     pub dataflow_id: DataflowId,
@@ -264,4 +439,7 @@ pub struct SpawnDataflowNodes {
     pub nodes: Vec<ResolvedNode>,
     pub machine_listen_ports: BTreeMap<String, SocketAddr>,
     pub dataflow_descriptor: Descriptor,
+    /// Secret that identifies the messages of this dataflow on the
+    /// connections between daemons.
+    pub token: DataflowToken,
 }
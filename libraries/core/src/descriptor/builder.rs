@@ -0,0 +1,588 @@
+//! Programmatic construction of dataflow descriptors.
+//!
+//! The [`DescriptorBuilder`] makes it possible to generate dataflows from code
+//! instead of writing the YAML file by hand, e.g. to create a node for each of
+//! `N` cameras or to generate parameterized dataflows for experiments:
+//!
+//! ```
+//! use dora_core::descriptor::DescriptorBuilder;
+//! use std::time::Duration;
+//!
+//! let mut builder = DescriptorBuilder::new();
+//! for i in 0..2 {
+//!     builder
+//!         .custom_node(format!("camera-{i}"), "./camera")
+//!         .args(format!("--device {i}"))
+//!         .timer_input("tick", Duration::from_millis(50))
+//!         .output("image");
+//!     builder
+//!         .custom_node(format!("plot-{i}"), "plot.py")
+//!         .input("image", format!("camera-{i}"), "image");
+//! }
+//! let yaml = builder.to_yaml().unwrap();
+//! ```
+
+use super::{
+    validate, CoreNodeKind, CustomNode, Deploy, Descriptor, EnvValue, Node, NodeKind,
+    OperatorConfig, OperatorSource, SingleOperatorDefinition,
+};
+use crate::config::{
+    ClockConfig, DataId, Input, InputDef, InputMapping, LatencyPath, NodeId, NodeRunConfig,
+    NumaNode, RestartPolicy, SharedMemoryConfig, UserInputMapping, VideoCodec,
+};
+use eyre::{bail, Context};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+/// Builds a [`Descriptor`] from code.
+#[derive(Debug, Clone)]
+pub struct DescriptorBuilder {
+    descriptor: Descriptor,
+    /// Options that were set on nodes that don't support them, reported by
+    /// [`Self::build`].
+    errors: Vec<String>,
+}
+
+impl DescriptorBuilder {
+    pub fn new() -> Self {
+        Self {
+            descriptor: Descriptor {
+                communication: Default::default(),
+                daemon_config: None,
+                deploy: Default::default(),
+                secrets: Default::default(),
+                shared_memory: Default::default(),
+                deterministic: false,
+                deterministic_hold_timeout: None,
+                latency_budgets: Default::default(),
+                lineage: false,
+                clock: None,
+                include: Vec::new(),
+                inputs: Default::default(),
+                outputs: Default::default(),
+                nodes: Vec::new(),
+            },
+            errors: Vec::new(),
+        }
+    }
+
+    /// Sets the machine that nodes are deployed on if they don't specify one.
+    pub fn machine(&mut self, machine: impl Into<String>) -> &mut Self {
+        self.descriptor.deploy.machine = Some(machine.into());
+        self
+    }
+
+    /// Sets the shared memory limits of the dataflow.
+    pub fn shared_memory(&mut self, config: SharedMemoryConfig) -> &mut Self {
+        self.descriptor.shared_memory = config;
+        self
+    }
+
+    /// Delivers operator inputs in the order of their logical time.
+    pub fn deterministic(&mut self) -> &mut Self {
+        self.descriptor.deterministic = true;
+        self
+    }
+
+    /// Runs the dataflow on the simulated time that the given output publishes.
+    pub fn sim_clock(&mut self, source: impl Into<String>, output: impl Into<String>) -> &mut Self {
+        self.descriptor.clock = Some(ClockConfig {
+            source: InputMapping::User(UserInputMapping {
+                source: NodeId::from(source.into()),
+                output: DataId::from(output.into()),
+            }),
+        });
+        self
+    }
+
+    /// Sets the end-to-end latency budget of the given path of nodes.
+    pub fn latency_budget<I>(
+        &mut self,
+        path: impl IntoIterator<Item = I>,
+        budget: Duration,
+    ) -> &mut Self
+    where
+        I: Into<String>,
+    {
+        let path = path.into_iter().map(|node| NodeId::from(node.into()));
+        self.descriptor
+            .latency_budgets
+            .insert(LatencyPath(path.collect()), budget);
+        self
+    }
+
+    /// Attaches the lineage of each message to its metadata.
+    pub fn lineage(&mut self) -> &mut Self {
+        self.descriptor.lineage = true;
+        self
+    }
+
+    /// Adds a custom node that runs the given executable, script, or URL.
+    pub fn custom_node(
+        &mut self,
+        id: impl Into<String>,
+        source: impl Into<String>,
+    ) -> NodeBuilder<'_> {
+        self.add_node(
+            id,
+            NodeKind::Custom(CustomNode {
+                source: source.into(),
+                args: None,
+                envs: None,
+                build: None,
+                send_stdout_as: None,
+                shared: false,
+                run_config: NodeRunConfig {
+                    inputs: Default::default(),
+                    outputs: Default::default(),
+                    services: Default::default(),
+                    encoding: Default::default(),
+                    notify_unsubscribed: false,
+                },
+            }),
+        )
+    }
+
+    /// Adds a node that runs a single operator.
+    ///
+    /// The outputs of the operator are referenced as `<node_id>/<output>` by
+    /// other nodes.
+    pub fn operator_node(
+        &mut self,
+        id: impl Into<String>,
+        source: OperatorSource,
+    ) -> NodeBuilder<'_> {
+        self.add_node(
+            id,
+            NodeKind::Operator(SingleOperatorDefinition {
+                id: None,
+                config: OperatorConfig {
+                    name: None,
+                    description: None,
+                    inputs: Default::default(),
+                    outputs: Default::default(),
+                    services: Default::default(),
+                    encoding: Default::default(),
+                    notify_unsubscribed: false,
+                    source,
+                    build: None,
+                    send_stdout_as: None,
+                    env: None,
+                    secrets: Default::default(),
+                    sync: None,
+                    parameters: None,
+                    config_schema: None,
+                    busy_loop_penalty: false,
+                    isolation: Default::default(),
+                    live_parameters: Default::default(),
+                },
+            }),
+        )
+    }
+
+    /// Adds a fully configured node, e.g. a runtime node with multiple operators.
+    pub fn node(&mut self, node: Node) -> &mut Self {
+        self.descriptor.nodes.push(node);
+        self
+    }
+
+    fn add_node(&mut self, id: impl Into<String>, kind: NodeKind) -> NodeBuilder<'_> {
+        self.descriptor.nodes.push(Node {
+            id: NodeId::from(id.into()),
+            name: None,
+            description: None,
+            env: None,
+            secrets: Default::default(),
+            shared_memory_budget: None,
+            numa_node: None,
+            cpu_affinity: Vec::new(),
+            memory_limit: None,
+            nice: None,
+            restart: None,
+            ready_signal: false,
+            deploy: Deploy::default(),
+            kind,
+        });
+        let node = self
+            .descriptor
+            .nodes
+            .last_mut()
+            .expect("node was just pushed");
+        NodeBuilder {
+            node,
+            errors: &mut self.errors,
+        }
+    }
+
+    /// Returns the descriptor after checking that node IDs are unique and that
+    /// all inputs are mapped to existing outputs.
+    ///
+    /// Unlike [`Descriptor::check`], this doesn't require the node sources to exist.
+    pub fn build(&self) -> eyre::Result<Descriptor> {
+        if let Some(err) = self.errors.first() {
+            bail!("{err}");
+        }
+        let mut ids = BTreeSet::new();
+        for node in &self.descriptor.nodes {
+            if !ids.insert(&node.id) {
+                bail!("node ID `{}` is used multiple times", node.id);
+            }
+        }
+
+        let nodes = self.descriptor.resolve_aliases_and_set_defaults();
+        for node in &nodes {
+            match &node.kind {
+                CoreNodeKind::Custom(custom_node) => {
+                    for (input_id, input) in &custom_node.run_config.inputs {
+                        let input_id = format!("{}/{input_id}", node.id);
+                        validate::check_input(input, &nodes, Some(node), &input_id)?;
+                    }
+                }
+                CoreNodeKind::Runtime(runtime_node) => {
+                    for operator in &runtime_node.operators {
+                        for (input_id, input) in &operator.config.inputs {
+                            validate::check_input(
+                                input,
+                                &nodes,
+                                Some(node),
+                                &format!("{}/{}/{input_id}", node.id, operator.id),
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        validate::check_latency_budgets(&self.descriptor, &nodes)?;
+        validate::check_clock(&self.descriptor, &nodes)?;
+        validate::check_resource_limits(&nodes)?;
+
+        Ok(self.descriptor.clone())
+    }
+
+    /// Builds the descriptor and serializes it as a dataflow YAML file.
+    pub fn to_yaml(&self) -> eyre::Result<String> {
+        let descriptor = self.build()?;
+        serde_yaml::to_string(&descriptor).context("failed to serialize dataflow descriptor")
+    }
+}
+
+impl Default for DescriptorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures a node that was added to a [`DescriptorBuilder`].
+#[derive(Debug)]
+pub struct NodeBuilder<'a> {
+    node: &'a mut Node,
+    errors: &'a mut Vec<String>,
+}
+
+impl NodeBuilder<'_> {
+    pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.node.name = Some(name.into());
+        self
+    }
+
+    pub fn description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.node.description = Some(description.into());
+        self
+    }
+
+    /// Sets the machine that the node is deployed on.
+    pub fn machine(&mut self, machine: impl Into<String>) -> &mut Self {
+        self.node.deploy.machine = Some(machine.into());
+        self
+    }
+
+    /// Sets an environment variable for the node.
+    pub fn env(&mut self, key: impl Into<String>, value: EnvValue) -> &mut Self {
+        self.node
+            .env
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.into(), value);
+        self
+    }
+
+    /// Maps the given input to the output `<source>/<output>` of another node.
+    pub fn input(
+        &mut self,
+        id: impl Into<String>,
+        source: impl Into<String>,
+        output: impl Into<String>,
+    ) -> &mut Self {
+        let mapping = InputMapping::User(UserInputMapping {
+            source: NodeId::from(source.into()),
+            output: DataId::from(output.into()),
+        });
+        self.input_with_options(id, InputDef::MappingOnly(mapping).into())
+    }
+
+    /// Maps the given input to a timer of the given interval.
+    pub fn timer_input(&mut self, id: impl Into<String>, interval: Duration) -> &mut Self {
+        let mapping = InputMapping::Timer { interval };
+        self.input_with_options(id, InputDef::MappingOnly(mapping).into())
+    }
+
+    /// Adds an input with options such as `queue_size`.
+    pub fn input_with_options(&mut self, id: impl Into<String>, input: Input) -> &mut Self {
+        let inputs = match &mut self.node.kind {
+            NodeKind::Custom(node) => &mut node.run_config.inputs,
+            NodeKind::Operator(operator) => &mut operator.config.inputs,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        };
+        inputs.insert(DataId::from(id.into()), input);
+        self
+    }
+
+    pub fn output(&mut self, id: impl Into<String>) -> &mut Self {
+        let outputs = match &mut self.node.kind {
+            NodeKind::Custom(node) => &mut node.run_config.outputs,
+            NodeKind::Operator(operator) => &mut operator.config.outputs,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        };
+        outputs.insert(DataId::from(id.into()));
+        self
+    }
+
+    /// Encodes the given output with a video codec when it is sent to other machines.
+    pub fn encoding(&mut self, output: impl Into<String>, codec: VideoCodec) -> &mut Self {
+        let encoding = match &mut self.node.kind {
+            NodeKind::Custom(node) => &mut node.run_config.encoding,
+            NodeKind::Operator(operator) => &mut operator.config.encoding,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        };
+        encoding.insert(DataId::from(output.into()), codec);
+        self
+    }
+
+    /// Notifies the node or operator when all consumers of one of its outputs
+    /// stopped.
+    pub fn notify_unsubscribed(&mut self) -> &mut Self {
+        match &mut self.node.kind {
+            NodeKind::Custom(node) => node.run_config.notify_unsubscribed = true,
+            NodeKind::Operator(operator) => operator.config.notify_unsubscribed = true,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        }
+        self
+    }
+
+    /// Declares a request/response service that the node or operator answers.
+    pub fn service(&mut self, id: impl Into<String>) -> &mut Self {
+        let services = match &mut self.node.kind {
+            NodeKind::Custom(node) => &mut node.run_config.services,
+            NodeKind::Operator(operator) => &mut operator.config.services,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        };
+        services.insert(DataId::from(id.into()));
+        self
+    }
+
+    /// Sets the command that builds the node or operator.
+    pub fn build(&mut self, command: impl Into<String>) -> &mut Self {
+        let command = Some(command.into());
+        match &mut self.node.kind {
+            NodeKind::Custom(node) => node.build = command,
+            NodeKind::Operator(operator) => operator.config.build = command,
+            NodeKind::Runtime(_) => unreachable!("builder doesn't create runtime nodes"),
+        }
+        self
+    }
+
+    /// Sets the command line arguments of a custom node.
+    ///
+    /// Operator nodes don't have command line arguments, so building the
+    /// descriptor fails if this is set on one.
+    pub fn args(&mut self, args: impl Into<String>) -> &mut Self {
+        match &mut self.node.kind {
+            NodeKind::Custom(node) => node.args = Some(args.into()),
+            _ => self.errors.push(format!(
+                "`args` are only supported by custom nodes, not by operator node `{}`",
+                self.node.id
+            )),
+        }
+        self
+    }
+
+    /// Sets the configuration values that are passed to an operator on initialization.
+    ///
+    /// Building the descriptor fails if this is set on a custom node.
+    pub fn params(&mut self, params: serde_yaml::Value) -> &mut Self {
+        match &mut self.node.kind {
+            NodeKind::Operator(operator) => operator.config.parameters = Some(params),
+            _ => self.errors.push(format!(
+                "`params` are only supported by operator nodes, not by custom node `{}`",
+                self.node.id
+            )),
+        }
+        self
+    }
+
+    /// Sets the maximum shared memory that the node can allocate.
+    pub fn shared_memory_budget(&mut self, bytes: u64) -> &mut Self {
+        self.node.shared_memory_budget = Some(bytes);
+        self
+    }
+
+    /// Restarts the node according to the given policy when it fails.
+    pub fn restart(&mut self, policy: RestartPolicy) -> &mut Self {
+        self.node.restart = Some(policy);
+        self
+    }
+
+    /// Sets the NUMA node that the node runs on.
+    pub fn numa_node(&mut self, numa_node: NumaNode) -> &mut Self {
+        self.node.numa_node = Some(numa_node);
+        self
+    }
+
+    /// Restricts the node process to the given CPU cores.
+    pub fn cpu_affinity(&mut self, cores: impl IntoIterator<Item = usize>) -> &mut Self {
+        self.node.cpu_affinity = cores.into_iter().collect();
+        self
+    }
+
+    /// Sets the maximum memory that the node process can use.
+    pub fn memory_limit(&mut self, bytes: u64) -> &mut Self {
+        self.node.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Sets the scheduling priority of the node process, from `-20` to `19`.
+    pub fn nice(&mut self, nice: i8) -> &mut Self {
+        self.node.nice = Some(nice);
+        self
+    }
+
+    /// Holds back the start of the dataflow until the node signals that it is ready.
+    pub fn ready_signal(&mut self) -> &mut Self {
+        self.node.ready_signal = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Compression, CompressionAlgorithm};
+
+    #[test]
+    fn build_dataflow() {
+        let mut builder = DescriptorBuilder::new();
+        for i in 0..3 {
+            builder
+                .custom_node(format!("camera-{i}"), "./camera")
+                .timer_input("tick", Duration::from_millis(20))
+                .output("image");
+        }
+        let mut detector =
+            builder.operator_node("detector", OperatorSource::Wasm("detector.wasm".into()));
+        for i in 0..3 {
+            detector.input(format!("image-{i}"), format!("camera-{i}"), "image");
+        }
+        detector.output("bbox");
+        builder
+            .custom_node("plot", "plot.py")
+            .input("bbox", "detector", "bbox");
+
+        let yaml = builder.to_yaml().unwrap();
+        let parsed = Descriptor::parse(yaml.into_bytes()).unwrap();
+        assert_eq!(parsed.nodes.len(), 5);
+
+        builder
+            .custom_node("sink", "sink.py")
+            .input("image", "camera-3", "image");
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn latency_budgets() {
+        let mut builder = DescriptorBuilder::new();
+        builder.custom_node("camera", "./camera").output("image");
+        builder
+            .custom_node("detector", "./detector")
+            .input("image", "camera", "image")
+            .output("bbox");
+        builder
+            .custom_node("planner", "./planner")
+            .input("bbox", "detector", "bbox");
+        builder.latency_budget(["camera", "detector", "planner"], Duration::from_millis(80));
+
+        let yaml = builder.to_yaml().unwrap();
+        let parsed = Descriptor::parse(yaml.into_bytes()).unwrap();
+        assert_eq!(
+            parsed.latency_budgets,
+            builder.build().unwrap().latency_budgets
+        );
+
+        builder.latency_budget(["camera", "planner"], Duration::from_millis(80));
+        let err = builder.build().unwrap_err().to_string();
+        assert!(err.contains("has no input from node `camera`"), "{err}");
+    }
+
+    #[test]
+    fn conflicting_remote_compression() {
+        let compressed = |algorithm| {
+            let mapping = InputMapping::User(UserInputMapping {
+                source: NodeId::from("camera".to_owned()),
+                output: DataId::from("image".to_owned()),
+            });
+            let mut input = Input::from(InputDef::MappingOnly(mapping));
+            input.compression = Some(Compression {
+                algorithm,
+                level: None,
+            });
+            input
+        };
+        let mut builder = DescriptorBuilder::new();
+        builder
+            .custom_node("camera", "./camera")
+            .machine("a")
+            .output("image");
+        builder
+            .custom_node("detector", "./detector")
+            .machine("b")
+            .input_with_options("image", compressed(CompressionAlgorithm::Zstd));
+        builder
+            .custom_node("recorder", "./recorder")
+            .machine("b")
+            .input_with_options("image", compressed(CompressionAlgorithm::Zstd));
+        assert!(builder.build().is_ok());
+
+        builder
+            .custom_node("plot", "./plot")
+            .machine("b")
+            .input_with_options("image", compressed(CompressionAlgorithm::Lz4));
+        let err = format!("{:?}", builder.build().unwrap_err());
+        assert!(err.contains("different `compression` options"), "{err}");
+    }
+
+    #[test]
+    fn reject_unsupported_node_options() {
+        let mut builder = DescriptorBuilder::new();
+        builder
+            .custom_node("camera", "./camera")
+            .args("--device 0")
+            .output("image");
+        builder
+            .operator_node("detector", OperatorSource::Wasm("detector.wasm".into()))
+            .params(serde_yaml::Value::Bool(true))
+            .input("image", "camera", "image");
+        assert!(builder.build().is_ok());
+
+        let mut args = builder.clone();
+        args.operator_node("plot", OperatorSource::Wasm("plot.wasm".into()))
+            .args("--fullscreen");
+        let err = args.build().unwrap_err().to_string();
+        assert!(err.contains("operator node `plot`"), "{err}");
+
+        builder
+            .custom_node("recorder", "./recorder")
+            .params(serde_yaml::Value::Bool(true));
+        let err = builder.build().unwrap_err().to_string();
+        assert!(err.contains("custom node `recorder`"), "{err}");
+    }
+}
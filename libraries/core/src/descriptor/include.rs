@@ -0,0 +1,401 @@
+//! Composition of dataflows from reusable sub-graphs.
+//!
+//! A dataflow can include other dataflow files through its `include` key:
+//!
+//! ```yaml
+//! include:
+//!   - id: front
+//!     path: perception/dataflow.yml
+//!     inputs:
+//!       image: camera_front/image
+//! nodes:
+//!   - id: planner
+//!     custom:
+//!       source: ./planner
+//!       inputs:
+//!         obstacles: front/obstacles
+//! ```
+//!
+//! The nodes of the included file are added to the dataflow with their IDs
+//! prefixed by `<id>_`, or by the given `prefix`. The included file declares
+//! the inputs that it expects under `inputs` and the outputs that it exposes
+//! under `outputs`:
+//!
+//! ```yaml
+//! inputs: [image]
+//! outputs:
+//!   obstacles: detector/bbox
+//! nodes:
+//!   - id: detector
+//!     custom:
+//!       source: ./detector
+//!       inputs:
+//!         image: inputs/image
+//! ```
+//!
+//! Its nodes use the inputs as `inputs/<name>`. The including dataflow uses
+//! the exposed outputs as `<id>/<name>`.
+//!
+//! Relative node and operator sources of the included file are adjusted to the
+//! directory of the including dataflow if they contain a path separator or exist
+//! next to the included file. `build` commands still run in the working directory
+//! of the dataflow. Secrets of the included file are added unless the including
+//! dataflow defines a secret with the same name. All other top-level settings of
+//! the included file are ignored, except for its default `_unstable_deploy`
+//! machine.
+
+use super::{source_is_url, Descriptor, NodeKind, OperatorConfig, OperatorSource, SHELL_SOURCE};
+use crate::config::{DataId, Input, InputMapping, NodeId};
+use eyre::{bail, eyre, Context};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+/// Source that the nodes of a sub-graph use to refer to the sub-graph inputs.
+pub const SUBGRAPH_INPUTS: &str = "inputs";
+
+/// Includes another dataflow file as a sub-graph.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Include {
+    /// Identifies the sub-graph, its exposed outputs are used as `<id>/<output>`.
+    pub id: NodeId,
+    /// Path of the included dataflow file, relative to the including file.
+    pub path: PathBuf,
+    /// Prefix of the IDs of the included nodes, defaults to `<id>_`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Connects the inputs of the sub-graph.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<DataId, InputMapping>,
+}
+
+impl Descriptor {
+    /// Replaces the `include` entries by the nodes of the included files.
+    ///
+    /// Paths are relative to `base_dir`, which is the directory of the dataflow
+    /// file.
+    pub fn resolve_includes(&mut self, base_dir: &Path) -> eyre::Result<()> {
+        self.resolve_includes_in(base_dir, Path::new(""), &mut Vec::new())
+    }
+
+    /// Resolves the includes of a file in `dir`, which is at `relative_dir`
+    /// relative to the top-level dataflow. The `stack` contains the files that
+    /// are currently being included, to detect cycles.
+    fn resolve_includes_in(
+        &mut self,
+        dir: &Path,
+        relative_dir: &Path,
+        stack: &mut Vec<PathBuf>,
+    ) -> eyre::Result<()> {
+        let mut exposed = BTreeMap::new();
+        let mut include_ids = BTreeSet::new();
+        for include in std::mem::take(&mut self.include) {
+            if include.id.to_string() == SUBGRAPH_INPUTS || include.id.to_string() == "dora" {
+                bail!(
+                    "`{}` is a reserved ID and can't be used for includes",
+                    include.id
+                );
+            }
+            if !include_ids.insert(include.id.clone()) {
+                bail!("include ID `{}` is used multiple times", include.id);
+            }
+
+            let path = dir.join(&include.path);
+            let canonical = path.canonicalize().wrap_err_with(|| {
+                format!("failed to find included dataflow `{}`", path.display())
+            })?;
+            if stack.contains(&canonical) {
+                bail!("dataflow `{}` includes itself", path.display());
+            }
+            let buf = std::fs::read(&path)
+                .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+            let mut subgraph = Descriptor::parse_without_includes(buf)
+                .wrap_err_with(|| format!("failed to parse `{}`", path.display()))?;
+
+            let sub_dir = path.parent().unwrap_or(Path::new(""));
+            let sub_relative_dir = relative_dir
+                .join(&include.path)
+                .parent()
+                .map(Path::to_owned)
+                .unwrap_or_default();
+            stack.push(canonical);
+            subgraph
+                .resolve_includes_in(sub_dir, &sub_relative_dir, stack)
+                .wrap_err_with(|| format!("failed to resolve includes of `{}`", path.display()))?;
+            stack.pop();
+
+            let prefix = include
+                .prefix
+                .clone()
+                .unwrap_or_else(|| format!("{}_", include.id));
+            let outputs = subgraph
+                .connect_subgraph(&include, &prefix, sub_dir, &sub_relative_dir)
+                .wrap_err_with(|| format!("failed to include `{}`", include.id))?;
+            for (output, mapping) in outputs {
+                exposed.insert((include.id.clone(), output), mapping);
+            }
+            for (name, secret) in subgraph.secrets {
+                self.secrets.entry(name).or_insert(secret);
+            }
+            self.nodes.extend(subgraph.nodes);
+        }
+        if include_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut node_ids = BTreeSet::new();
+        for node in &self.nodes {
+            if include_ids.contains(&node.id) {
+                bail!("node ID `{}` is also used as include ID", node.id);
+            }
+            if !node_ids.insert(&node.id) {
+                bail!("node ID `{}` is used multiple times", node.id);
+            }
+        }
+
+        // connect the inputs that use outputs of the sub-graphs
+        let connect = |mapping: &mut InputMapping| -> eyre::Result<()> {
+            if let InputMapping::User(user) = mapping {
+                if include_ids.contains(&user.source) {
+                    let key = (user.source.clone(), user.output.clone());
+                    *mapping = exposed.get(&key).cloned().ok_or_else(|| {
+                        eyre!("included dataflow `{}` has no output `{}`", key.0, key.1)
+                    })?;
+                }
+            }
+            Ok(())
+        };
+        for node in &mut self.nodes {
+            for input in inputs_mut(&mut node.kind) {
+                connect(&mut input.mapping)
+                    .wrap_err_with(|| format!("failed to connect inputs of `{}`", node.id))?;
+            }
+        }
+        for mapping in self.outputs.values_mut() {
+            connect(mapping)?;
+        }
+        Ok(())
+    }
+
+    /// Prefixes the node IDs of an included dataflow and connects its inputs.
+    ///
+    /// Returns the exposed outputs of the sub-graph.
+    fn connect_subgraph(
+        &mut self,
+        include: &Include,
+        prefix: &str,
+        dir: &Path,
+        relative_dir: &Path,
+    ) -> eyre::Result<BTreeMap<DataId, InputMapping>> {
+        if let Some(input) = include.inputs.keys().find(|i| !self.inputs.contains(*i)) {
+            bail!("the included dataflow has no input `{input}`");
+        }
+
+        let subgraph_inputs = NodeId::from(SUBGRAPH_INPUTS.to_owned());
+        let local_ids: BTreeSet<_> = self.nodes.iter().map(|n| n.id.clone()).collect();
+        let rename = |mapping: &mut InputMapping| -> eyre::Result<()> {
+            if let InputMapping::User(user) = mapping {
+                if user.source == subgraph_inputs {
+                    if !self.inputs.contains(&user.output) {
+                        bail!("`{mapping}` is not listed in the `inputs` of the dataflow");
+                    }
+                    *mapping = include.inputs.get(&user.output).cloned().ok_or_else(|| {
+                        eyre!(
+                            "input `{}` of the included dataflow is not connected",
+                            user.output
+                        )
+                    })?;
+                } else if local_ids.contains(&user.source) {
+                    user.source = NodeId::from(format!("{prefix}{}", user.source));
+                }
+            }
+            Ok(())
+        };
+
+        let default_machine = self.deploy.machine.clone();
+        for node in &mut self.nodes {
+            for input in inputs_mut(&mut node.kind) {
+                rename(&mut input.mapping)
+                    .wrap_err_with(|| format!("failed to connect inputs of `{}`", node.id))?;
+            }
+            node.id = NodeId::from(format!("{prefix}{}", node.id));
+            if node.deploy.machine.is_none() {
+                node.deploy.machine = default_machine.clone();
+            }
+            rebase_sources(&mut node.kind, dir, relative_dir);
+        }
+        let mut outputs = std::mem::take(&mut self.outputs);
+        for mapping in outputs.values_mut() {
+            rename(mapping)?;
+        }
+        Ok(outputs)
+    }
+}
+
+fn inputs_mut(kind: &mut NodeKind) -> Vec<&mut Input> {
+    match kind {
+        NodeKind::Runtime(node) => node
+            .operators
+            .iter_mut()
+            .flat_map(|op| op.config.inputs.values_mut())
+            .collect(),
+        NodeKind::Custom(node) => node.run_config.inputs.values_mut().collect(),
+        NodeKind::Operator(operator) => operator.config.inputs.values_mut().collect(),
+    }
+}
+
+/// Makes the relative sources of an included node relative to the top-level dataflow.
+fn rebase_sources(kind: &mut NodeKind, dir: &Path, relative_dir: &Path) {
+    if relative_dir.as_os_str().is_empty() {
+        return;
+    }
+    let rebase = |source: &mut String| {
+        let path = Path::new(source.as_str());
+        let is_path = path.components().count() > 1 || dir.join(path).exists();
+        if is_path && path.is_relative() && !source_is_url(source) && source != SHELL_SOURCE {
+            let rebased = relative_dir.join(path).to_string_lossy().into_owned();
+            *source = rebased;
+        }
+    };
+    let rebase_operator = |config: &mut OperatorConfig| {
+        match &mut config.source {
+            OperatorSource::SharedLibrary(source) | OperatorSource::Wasm(source) => rebase(source),
+            OperatorSource::Python(python) => {
+                rebase(&mut python.source);
+                if let Some(requirements) = &mut python.requirements {
+                    rebase(requirements);
+                }
+            }
+        }
+        if let Some(schema) = &mut config.config_schema {
+            if schema.is_relative() {
+                let rebased = relative_dir.join(&schema);
+                *schema = rebased;
+            }
+        }
+    };
+    match kind {
+        NodeKind::Custom(node) => rebase(&mut node.source),
+        NodeKind::Runtime(node) => {
+            for operator in &mut node.operators {
+                rebase_operator(&mut operator.config);
+            }
+        }
+        NodeKind::Operator(operator) => rebase_operator(&mut operator.config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERCEPTION: &str = r#"
+inputs: [image]
+outputs:
+  obstacles: detector/bbox
+nodes:
+  - id: detector
+    custom:
+      source: ./detector
+      inputs:
+        image: inputs/image
+  - id: tracker
+    custom:
+      source: ./tracker
+      inputs:
+        bbox: detector/bbox
+"#;
+
+    fn input_source(descriptor: &Descriptor, node: &str, input: &str) -> String {
+        let node = descriptor
+            .nodes
+            .iter()
+            .find(|n| n.id.to_string() == node)
+            .unwrap();
+        let NodeKind::Custom(custom) = &node.kind else {
+            panic!("`{}` is not a custom node", node.id);
+        };
+        custom.run_config.inputs[&DataId::from(input.to_owned())]
+            .mapping
+            .to_string()
+    }
+
+    #[test]
+    fn include_subgraphs() {
+        let dir = std::env::temp_dir().join(format!("dora-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("perception")).unwrap();
+        std::fs::write(dir.join("perception/dataflow.yml"), PERCEPTION).unwrap();
+
+        let dataflow = r#"
+include:
+  - id: front
+    path: perception/dataflow.yml
+    inputs:
+      image: camera/image
+  - id: rear
+    path: perception/dataflow.yml
+    prefix: back-
+    inputs:
+      image: camera/image
+nodes:
+  - id: camera
+    custom:
+      source: ./camera
+  - id: planner
+    custom:
+      source: ./planner
+      inputs:
+        front: front/obstacles
+        rear: rear/obstacles
+"#;
+        let mut descriptor = Descriptor::parse_without_includes(dataflow.into()).unwrap();
+        descriptor.resolve_includes(&dir).unwrap();
+
+        let ids: Vec<_> = descriptor.nodes.iter().map(|n| n.id.to_string()).collect();
+        assert_eq!(
+            ids,
+            [
+                "camera",
+                "planner",
+                "front_detector",
+                "front_tracker",
+                "back-detector",
+                "back-tracker"
+            ]
+        );
+        assert_eq!(
+            input_source(&descriptor, "front_detector", "image"),
+            "camera/image"
+        );
+        assert_eq!(
+            input_source(&descriptor, "back-tracker", "bbox"),
+            "back-detector/bbox"
+        );
+        assert_eq!(
+            input_source(&descriptor, "planner", "front"),
+            "front_detector/bbox"
+        );
+        assert_eq!(
+            input_source(&descriptor, "planner", "rear"),
+            "back-detector/bbox"
+        );
+        let NodeKind::Custom(detector) = &descriptor.nodes[2].kind else {
+            panic!("not a custom node");
+        };
+        assert_eq!(
+            Path::new(&detector.source),
+            Path::new("perception/detector")
+        );
+
+        // outputs that are not exposed can't be used
+        let unknown_output = dataflow.replace("front/obstacles", "front/bbox");
+        let mut descriptor = Descriptor::parse_without_includes(unknown_output.into()).unwrap();
+        assert!(descriptor.resolve_includes(&dir).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
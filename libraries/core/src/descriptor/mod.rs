@@ -1,7 +1,16 @@
-use crate::config::{
-    CommunicationConfig, DataId, Input, InputMapping, NodeId, NodeRunConfig, OperatorId,
+use crate::{
+    config::{
+        ClockConfig, CommunicationConfig, DataId, Input, InputMapping, InputSync, LatencyPath,
+        NodeId, NodeRunConfig, NumaNode, OperatorId, ParameterValue, RestartPolicy,
+        SharedMemoryConfig, VideoCodec,
+    },
+    message::MetadataValue,
 };
+pub use builder::{DescriptorBuilder, NodeBuilder};
 use eyre::{bail, eyre, Context, Result};
+pub use include::{Include, SUBGRAPH_INPUTS};
+use schema::DurationSchema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with_expand_env::with_expand_envs;
 use std::{
@@ -9,29 +18,115 @@ use std::{
     env::consts::EXE_EXTENSION,
     fmt,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tracing::warn;
 pub use visualize::collect_dora_timers;
 
+mod builder;
+mod include;
+pub mod schema;
 mod validate;
 mod visualize;
 pub const SHELL_SOURCE: &str = "shell";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Descriptor {
     #[serde(default)]
     pub communication: CommunicationConfig,
     // deprecated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
     pub daemon_config: Option<serde_yaml::Value>,
     #[serde(default, rename = "_unstable_deploy")]
     pub deploy: Deploy,
+    /// Named secrets that nodes can reference through their `secrets` field.
+    ///
+    /// Only the source of each secret is part of the dataflow description. The
+    /// values are resolved by the daemon when it spawns the nodes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, SecretSource>,
+    #[serde(default)]
+    pub shared_memory: SharedMemoryConfig,
+    /// Deliver the inputs of operators in the order of their logical time
+    /// instead of their arrival order.
+    ///
+    /// Makes replays of recordings reproducible, e.g. for regression tests. No
+    /// inputs are dropped in this mode, independent of their `queue_size`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deterministic: bool,
+    /// Maximum time that `deterministic` mode holds back an input while
+    /// waiting for messages of the other inputs.
+    ///
+    /// Keeps sparse inputs and cycles from stalling the operator. The order of
+    /// the inputs is only reproducible if the messages arrive within this time.
+    /// Defaults to `1s`.
+    #[serde(
+        default,
+        with = "crate::config::optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<DurationSchema>")]
+    pub deterministic_hold_timeout: Option<Duration>,
+    /// End-to-end latency budgets of paths through the dataflow, e.g.
+    /// `camera -> detector -> planner: 80ms`.
+    ///
+    /// The latency of a path is measured from the timestamp at which the first
+    /// node sent a message until the last node receives a message derived from
+    /// it, based on the lineage of the message. The last node receives a
+    /// `LatencyBudgetExceeded` event with the latency of each hop if the budget
+    /// is exceeded.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        with = "crate::config::latency_budgets"
+    )]
+    #[schemars(with = "BTreeMap<String, DurationSchema>")]
+    pub latency_budgets: BTreeMap<LatencyPath, Duration>,
+    /// Attach the lineage of each message to its metadata, e.g. for
+    /// `dora trace`.
+    ///
+    /// Always enabled if the dataflow has `latency_budgets`, which are
+    /// measured based on the lineage.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lineage: bool,
+    /// Runs the dataflow on simulated time, e.g. to replay recordings faster
+    /// than real time or to follow a simulator.
+    ///
+    /// Timers, input deadlines, and the timestamps of messages are based on the
+    /// time that the clock `source` publishes instead of the wall-clock time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<ClockConfig>,
+    /// Other dataflow files that are included as sub-graphs.
+    ///
+    /// Resolved when the dataflow is read, see [`Include`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<Include>,
+    /// Inputs that the including dataflow connects if this dataflow is used as
+    /// a sub-graph. The nodes use them as `inputs/<name>`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub inputs: BTreeSet<DataId>,
+    /// Outputs that the including dataflow can use if this dataflow is used as
+    /// a sub-graph.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<DataId, InputMapping>,
     pub nodes: Vec<Node>,
 }
 
 pub const SINGLE_OPERATOR_DEFAULT_ID: &str = "op";
 
 impl Descriptor {
+    /// Whether the lineage of messages is tracked, see [`Self::lineage`].
+    pub fn track_lineage(&self) -> bool {
+        self.lineage || !self.latency_budgets.is_empty()
+    }
+
+    pub fn deterministic_hold_timeout(&self) -> Duration {
+        self.deterministic_hold_timeout
+            .unwrap_or(Duration::from_secs(1))
+    }
+
     pub fn resolve_aliases_and_set_defaults(&self) -> Vec<ResolvedNode> {
         let default_op_id = OperatorId::from(SINGLE_OPERATOR_DEFAULT_ID.to_string());
 
@@ -85,6 +180,14 @@ impl Descriptor {
                 name: node.name,
                 description: node.description,
                 env: node.env,
+                secrets: node.secrets,
+                shared_memory_budget: node.shared_memory_budget,
+                numa_node: node.numa_node,
+                cpu_affinity: node.cpu_affinity,
+                memory_limit: node.memory_limit,
+                nice: node.nice,
+                restart: node.restart,
+                ready_signal: node.ready_signal,
                 deploy: ResolvedDeploy::new(node.deploy, self),
                 kind,
             });
@@ -100,20 +203,63 @@ impl Descriptor {
         Ok(flowchart)
     }
 
+    /// Renders the dataflow as Graphviz graph in the DOT language.
+    pub fn visualize_as_dot(&self) -> eyre::Result<String> {
+        let resolved = self.resolve_aliases_and_set_defaults();
+        let graph = visualize::visualize_nodes_as_dot(&resolved);
+
+        Ok(graph)
+    }
+
     pub async fn read(path: &Path) -> eyre::Result<Descriptor> {
         let buf = tokio::fs::read(path)
             .await
             .context("failed to open given file")?;
-        Descriptor::parse(buf)
+        Descriptor::parse_in(buf, path.parent().unwrap_or(Path::new("")))
     }
 
     pub fn blocking_read(path: &Path) -> eyre::Result<Descriptor> {
         let buf = std::fs::read(path).context("failed to open given file")?;
-        Descriptor::parse(buf)
+        Descriptor::parse_in(buf, path.parent().unwrap_or(Path::new("")))
     }
 
+    /// Parses the descriptor, resolving included files relative to the current
+    /// working directory.
     pub fn parse(buf: Vec<u8>) -> eyre::Result<Descriptor> {
-        serde_yaml::from_slice(&buf).context("failed to parse given descriptor")
+        Descriptor::parse_in(buf, Path::new(""))
+    }
+
+    /// Parses the descriptor, resolving included files relative to `base_dir`.
+    pub fn parse_in(buf: Vec<u8>, base_dir: &Path) -> eyre::Result<Descriptor> {
+        let mut descriptor = Descriptor::parse_without_includes(buf)?;
+        descriptor
+            .resolve_includes(base_dir)
+            .context("failed to resolve included dataflows")?;
+        Ok(descriptor)
+    }
+
+    fn parse_without_includes(buf: Vec<u8>) -> eyre::Result<Descriptor> {
+        let value: serde_yaml::Value =
+            serde_yaml::from_slice(&buf).context("failed to parse given descriptor")?;
+        schema::check_unknown_fields(&value).context("failed to parse given descriptor")?;
+        // report the path of the invalid key in addition to its location
+        serde_path_to_error::deserialize(serde_yaml::Deserializer::from_slice(&buf))
+            .context("failed to parse given descriptor")
+    }
+
+    /// Updates the given parameter in all operators of the given node that
+    /// declare it, e.g. to keep changed parameters when the dataflow restarts.
+    ///
+    /// Returns whether any operator declares the parameter.
+    pub fn set_parameter(&mut self, node_id: &NodeId, key: &str, value: &MetadataValue) -> bool {
+        let Some(node) = self.nodes.iter_mut().find(|node| &node.id == node_id) else {
+            return false;
+        };
+        match &mut node.kind {
+            NodeKind::Runtime(node) => node.set_parameter(key, value),
+            NodeKind::Operator(operator) => operator.config.set_parameter(key, value),
+            NodeKind::Custom(_) => false,
+        }
     }
 
     pub fn check(&self, working_dir: &Path) -> eyre::Result<()> {
@@ -121,18 +267,70 @@ impl Descriptor {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Deploy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub machine: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Node {
     pub id: NodeId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env: Option<BTreeMap<String, EnvValue>>,
+    /// Maps environment variable names to the names of the secrets that should be
+    /// injected through them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+    /// Overrides the `node_budget` of the dataflow's `shared_memory` config.
+    #[serde(
+        default,
+        with = "crate::config::optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<schema::ByteSizeSchema>")]
+    pub shared_memory_budget: Option<u64>,
+    /// The NUMA node that the node runs on.
+    ///
+    /// Shared memory samples are allocated on this NUMA node, unless all
+    /// receivers of an output specify the same other NUMA node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numa_node: Option<NumaNode>,
+    /// CPU cores that the node process is allowed to run on, e.g. `[2, 3]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_affinity: Vec<usize>,
+    /// Maximum memory that the node process can use, e.g. `2GB`.
+    ///
+    /// Applied through a cgroup on Linux and a job object on Windows. The
+    /// daemon warns when the node reaches the limit.
+    #[serde(
+        default,
+        with = "crate::config::optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<schema::ByteSizeSchema>")]
+    pub memory_limit: Option<u64>,
+    /// Scheduling priority of the node process, from `-20` (highest) to `19`
+    /// (lowest). Negative values usually require elevated privileges.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i8>,
+    /// Restarts the node when it exits with an error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Whether the node signals its readiness explicitly, instead of when it
+    /// connects to the daemon.
+    ///
+    /// The dataflow is only started, i.e. the other nodes only receive their
+    /// events and are allowed to send outputs, after all such nodes signaled
+    /// that they are ready. Outputs that the node sends before are held back
+    /// and delivered when the dataflow starts.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ready_signal: bool,
 
     #[serde(default, rename = "_unstable_deploy")]
     pub deploy: Deploy,
@@ -141,7 +339,7 @@ pub struct Node {
     pub kind: NodeKind,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum NodeKind {
     /// Dora runtime node
@@ -157,6 +355,30 @@ pub struct ResolvedNode {
     pub name: Option<String>,
     pub description: Option<String>,
     pub env: Option<BTreeMap<String, EnvValue>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+    #[serde(
+        default,
+        with = "crate::config::optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub shared_memory_budget: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numa_node: Option<NumaNode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_affinity: Vec<usize>,
+    #[serde(
+        default,
+        with = "crate::config::optional_byte_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ready_signal: bool,
 
     #[serde(default)]
     pub deploy: ResolvedDeploy,
@@ -166,6 +388,34 @@ pub struct ResolvedNode {
 }
 
 impl ResolvedNode {
+    /// Updates the given parameter in all operators of the node that declare it.
+    ///
+    /// Returns whether any operator declares the parameter.
+    pub fn set_parameter(&mut self, key: &str, value: &MetadataValue) -> bool {
+        match &mut self.kind {
+            CoreNodeKind::Runtime(node) => node.set_parameter(key, value),
+            CoreNodeKind::Custom(_) => false,
+        }
+    }
+
+    /// Checks whether the given parameter of the node can be set to the given
+    /// value and returns the value that should be set.
+    pub fn check_parameter(&self, key: &str, value: &MetadataValue) -> Result<MetadataValue> {
+        match &self.kind {
+            CoreNodeKind::Runtime(node) => node.check_parameter(key, value),
+            CoreNodeKind::Custom(_) => bail!("custom nodes have no live parameters"),
+        }
+    }
+
+    /// Returns the parameters that the operators of the node declare, with
+    /// their current values.
+    pub fn parameters(&self) -> BTreeMap<String, MetadataValue> {
+        match &self.kind {
+            CoreNodeKind::Runtime(node) => node.parameters(),
+            CoreNodeKind::Custom(_) => BTreeMap::new(),
+        }
+    }
+
     pub fn send_stdout_as(&self) -> Result<Option<String>> {
         match &self.kind {
             // TODO: Split stdout between operators
@@ -216,36 +466,89 @@ pub enum CoreNodeKind {
     Custom(CustomNode),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct RuntimeNode {
     pub operators: Vec<OperatorDefinition>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl RuntimeNode {
+    /// Updates the given parameter in all operators that declare it.
+    ///
+    /// Returns whether any operator declares the parameter.
+    pub fn set_parameter(&mut self, key: &str, value: &MetadataValue) -> bool {
+        let mut declared = false;
+        for operator in &mut self.operators {
+            declared |= operator.config.set_parameter(key, value);
+        }
+        declared
+    }
+
+    /// Checks whether the given parameter can be set to the given value in
+    /// all operators that declare it and returns the value that should be set.
+    pub fn check_parameter(&self, key: &str, value: &MetadataValue) -> Result<MetadataValue> {
+        let mut checked = None;
+        for operator in &self.operators {
+            if let Some(value) = operator
+                .config
+                .check_parameter(key, value)
+                .wrap_err_with(|| format!("operator `{}`", operator.id))?
+            {
+                checked = Some(value);
+            }
+        }
+        checked.ok_or_else(|| eyre!("no operator declares parameter `{key}`"))
+    }
+
+    pub fn parameters(&self) -> BTreeMap<String, MetadataValue> {
+        self.operators
+            .iter()
+            .flat_map(|operator| &operator.config.live_parameters)
+            .map(|(key, value)| (key.clone(), value.0.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct OperatorDefinition {
     pub id: OperatorId,
     #[serde(flatten)]
     pub config: OperatorConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct SingleOperatorDefinition {
     /// ID is optional if there is only a single operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub id: Option<OperatorId>,
     #[serde(flatten)]
     pub config: OperatorConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct OperatorConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
     #[serde(default)]
     pub inputs: BTreeMap<DataId, Input>,
     #[serde(default)]
     pub outputs: BTreeSet<DataId>,
+    /// Request/response services that the operator answers.
+    ///
+    /// Other nodes call them as `<node_id>/<operator_id>/<service>`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub services: BTreeSet<DataId>,
+    /// Video codecs that outputs are encoded with when they are sent to other
+    /// machines, e.g. `image: h264`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub encoding: BTreeMap<DataId, VideoCodec>,
+    /// Deliver an `OutputUnsubscribed` event when all consumers of an output
+    /// stopped, e.g. to pause expensive processing until a consumer runs again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub notify_unsubscribed: bool,
 
     #[serde(flatten)]
     pub source: OperatorSource,
@@ -254,9 +557,116 @@ pub struct OperatorConfig {
     pub build: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+
+    /// Environment variables that are only passed to this operator.
+    ///
+    /// They are not added to the environment of the runtime process, which is
+    /// shared by all operators of the node. Python operators find them in their
+    /// `env` attribute, shared-library operators in the init context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, EnvValue>>,
+    /// Maps environment variable names to the names of the secrets that should be
+    /// passed to this operator through them, like the `env` variables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, String>,
+
+    /// Delivers the inputs of the operator together according to the given policy,
+    /// instead of one by one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<InputSync>,
+
+    /// Operator-specific configuration values.
+    ///
+    /// Passed to the operator on initialization. Can also be specified as `params`.
+    #[serde(
+        default,
+        rename = "config",
+        alias = "params",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub parameters: Option<serde_yaml::Value>,
+    /// Path to a JSON schema that the `config` block is validated against.
+    ///
+    /// The schema can be written in JSON or YAML syntax.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_schema: Option<PathBuf>,
+    /// Lowers the scheduling priority of the operator thread while the operator
+    /// busy-loops, i.e. while it uses a full CPU core without receiving events.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub busy_loop_penalty: bool,
+    /// Runs the operator in its own worker process instead of a thread of the
+    /// runtime node. Only supported for Python operators.
+    #[serde(default, skip_serializing_if = "OperatorIsolation::is_thread")]
+    pub isolation: OperatorIsolation,
+    /// Parameters of the operator with their initial values.
+    ///
+    /// Unlike the static `config` block, they can be changed while the
+    /// dataflow is running through `dora param set`, which sends a
+    /// `ParameterUpdate` event to the operator. New values must have the
+    /// type of the initial value. Changed values are kept when the node is
+    /// restarted. Only supported for Python operators.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub live_parameters: BTreeMap<String, ParameterValue>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl OperatorConfig {
+    /// Updates the given parameter if the operator declares it.
+    ///
+    /// Returns whether the parameter is declared.
+    pub fn set_parameter(&mut self, key: &str, value: &MetadataValue) -> bool {
+        match self.live_parameters.get_mut(key) {
+            Some(current) => {
+                *current = ParameterValue(value.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks whether the given parameter of the operator can be set to the
+    /// given value and returns the value that should be set.
+    ///
+    /// Returns `None` if the operator doesn't declare the parameter.
+    pub fn check_parameter(
+        &self,
+        key: &str,
+        value: &MetadataValue,
+    ) -> Result<Option<MetadataValue>> {
+        let Some(current) = self.live_parameters.get(key) else {
+            return Ok(None);
+        };
+        if !matches!(self.source, OperatorSource::Python(_)) {
+            bail!("live parameters are only supported for Python operators");
+        }
+        current
+            .check_update(value)
+            .map(Some)
+            .wrap_err_with(|| format!("invalid value for parameter `{key}`"))
+    }
+}
+
+/// Where an operator runs, see [`OperatorConfig::isolation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OperatorIsolation {
+    /// Run the operator in a thread of the runtime node, sharing the Python GIL
+    /// with the other operators of the node.
+    #[default]
+    Thread,
+    /// Run the operator in a worker process that is forked from the runtime
+    /// node, so that it gets its own GIL. Events and outputs are exchanged
+    /// with the runtime through shared memory.
+    Process,
+}
+
+impl OperatorIsolation {
+    pub fn is_thread(&self) -> bool {
+        matches!(self, Self::Thread)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum OperatorSource {
     SharedLibrary(String),
@@ -272,15 +682,23 @@ pub enum OperatorSource {
 pub struct PythonSource {
     pub source: String,
     pub conda_env: Option<String>,
+    /// Path to a `requirements.txt` file, relative to the dataflow.
+    ///
+    /// The requirements are installed into a virtual environment that is only
+    /// used by this operator. Environments are cached in `build/venvs` and
+    /// reused as long as the requirements don't change.
+    pub requirements: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum PythonSourceDef {
     SourceOnly(String),
     WithOptions {
         source: String,
         conda_env: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requirements: Option<String>,
     },
 }
 
@@ -290,8 +708,17 @@ impl From<PythonSource> for PythonSourceDef {
             PythonSource {
                 source,
                 conda_env: None,
+                requirements: None,
             } => Self::SourceOnly(source),
-            PythonSource { source, conda_env } => Self::WithOptions { source, conda_env },
+            PythonSource {
+                source,
+                conda_env,
+                requirements,
+            } => Self::WithOptions {
+                source,
+                conda_env,
+                requirements,
+            },
         }
     }
 }
@@ -302,8 +729,17 @@ impl From<PythonSourceDef> for PythonSource {
             PythonSourceDef::SourceOnly(source) => Self {
                 source,
                 conda_env: None,
+                requirements: None,
+            },
+            PythonSourceDef::WithOptions {
+                source,
+                conda_env,
+                requirements,
+            } => Self {
+                source,
+                conda_env,
+                requirements,
             },
-            PythonSourceDef::WithOptions { source, conda_env } => Self { source, conda_env },
         }
     }
 }
@@ -341,16 +777,27 @@ pub struct PythonOperatorConfig {
     pub outputs: BTreeSet<DataId>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CustomNode {
     pub source: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub envs: Option<BTreeMap<String, EnvValue>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub build: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+    /// Share a single instance of this node between all dataflows on a machine.
+    ///
+    /// Dataflows that are started while a shared node with the same ID, source,
+    /// arguments, environment variables, and input mappings is already running
+    /// attach to the existing instance and receive its outputs, instead of
+    /// spawning their own. The inputs of the node are
+    /// only connected in the dataflow that spawned it. The instance is kept
+    /// running until all attached dataflows are finished.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared: bool,
 
     #[serde(flatten)]
     pub run_config: NodeRunConfig,
@@ -367,6 +814,70 @@ pub enum EnvValue {
     String(String),
 }
 
+/// Specifies where the daemon reads the value of a secret from.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum SecretSource {
+    /// Read the secret from an environment variable of the daemon.
+    Env { env: String },
+    /// Read the secret from a file on the daemon machine.
+    ///
+    /// Relative paths are resolved against the working directory of the dataflow.
+    /// A trailing newline is stripped from the file content.
+    File { file: PathBuf },
+    /// Read the secret from a HashiCorp Vault key-value store.
+    ///
+    /// The daemon connects to the server given in the `VAULT_ADDR` environment
+    /// variable and authenticates using the `VAULT_TOKEN` variable.
+    Vault { vault: VaultSecret },
+    /// Read the secret from a `.env` file on the daemon machine.
+    ///
+    /// Relative paths are resolved against the working directory of the dataflow.
+    DotEnv { dotenv: PathBuf, key: String },
+    /// Read the secret from the OS keyring of the daemon machine.
+    ///
+    /// Uses the `secret-tool` utility on Linux and the `security` utility on macOS.
+    Keyring { keyring: KeyringSecret },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VaultSecret {
+    /// API path of the secret, e.g. `secret/data/openai` for the KV v2 engine.
+    pub path: String,
+    /// Name of the field that contains the secret value.
+    pub field: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeyringSecret {
+    /// Name of the service that the secret is stored for.
+    pub service: String,
+    /// Name of the account that the secret is stored for.
+    pub user: String,
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::Env { env } => write!(f, "env variable `{env}`"),
+            SecretSource::File { file } => write!(f, "file `{}`", file.display()),
+            SecretSource::Vault { vault } => {
+                write!(f, "vault secret `{}` (field `{}`)", vault.path, vault.field)
+            }
+            SecretSource::DotEnv { dotenv, key } => {
+                write!(f, "key `{key}` of env file `{}`", dotenv.display())
+            }
+            SecretSource::Keyring { keyring } => write!(
+                f,
+                "keyring entry of service `{}` (user `{}`)",
+                keyring.service, keyring.user
+            ),
+        }
+    }
+}
+
 impl fmt::Display for EnvValue {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -376,3 +887,95 @@ impl fmt::Display for EnvValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_live_parameters() {
+        let yaml = r#"
+nodes:
+  - id: detector
+    operators:
+      - id: python
+        python: detector.py
+        live_parameters:
+          threshold: 0.5
+          labels: [person]
+      - id: native
+        shared-library: detector
+        live_parameters:
+          enabled: true
+"#;
+        let descriptor = Descriptor::parse(yaml.as_bytes().to_vec()).unwrap();
+        let NodeKind::Runtime(node) = &descriptor.nodes[0].kind else {
+            panic!("expected runtime node");
+        };
+
+        assert_eq!(
+            node.check_parameter("threshold", &MetadataValue::Integer(1))
+                .unwrap(),
+            MetadataValue::Float(1.0)
+        );
+        assert!(node
+            .check_parameter("threshold", &MetadataValue::String("high".into()))
+            .is_err());
+        assert!(node
+            .check_parameter(
+                "labels",
+                &MetadataValue::List(vec![MetadataValue::String("car".into())])
+            )
+            .is_ok());
+        assert!(node
+            .check_parameter(
+                "labels",
+                &MetadataValue::List(vec![MetadataValue::Bool(true)])
+            )
+            .is_err());
+        assert!(node
+            .check_parameter("unknown", &MetadataValue::Bool(true))
+            .is_err());
+        // the shared library operator API has no parameter updates
+        assert!(node
+            .check_parameter("enabled", &MetadataValue::Bool(false))
+            .is_err());
+    }
+
+    #[test]
+    fn parse_operator_params() {
+        let yaml = r#"
+nodes:
+  - id: detector
+    operators:
+      - id: yolo
+        python: yolo.py
+        params:
+          model: yolov8n.pt
+          conf: 0.4
+      - id: plot
+        python: plot.py
+        config:
+          width: 640
+      - id: sink
+        python: sink.py
+"#;
+        let descriptor = Descriptor::parse(yaml.as_bytes().to_vec()).unwrap();
+        let NodeKind::Runtime(node) = &descriptor.nodes[0].kind else {
+            panic!("expected runtime node");
+        };
+        let params: Vec<_> = node
+            .operators
+            .iter()
+            .map(|operator| serde_json::to_value(&operator.config.parameters).unwrap())
+            .collect();
+        assert_eq!(
+            params,
+            [
+                serde_json::json!({ "model": "yolov8n.pt", "conf": 0.4 }),
+                serde_json::json!({ "width": 640 }),
+                serde_json::Value::Null,
+            ]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! JSON schema of dataflow descriptors.
+//!
+//! The schema is printed by `dora schema`, e.g. for validation and
+//! autocompletion in editors. It is also used to reject unknown fields when a
+//! dataflow is parsed, which serde doesn't support for the flattened node and
+//! operator definitions.
+
+use super::{Descriptor, EnvValue, PythonSource, PythonSourceDef};
+use crate::{
+    compaction::CompactionKey,
+    condition::Condition,
+    config::{
+        Conversion, Input, InputDef, InputMapping, InputSync, MaxRate, NumaNode, ParameterValue,
+    },
+};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, SingleOrVec},
+    JsonSchema,
+};
+use serde_json::Value as Json;
+use serde_yaml::Value as Yaml;
+use std::{collections::BTreeMap, fmt, sync::OnceLock};
+
+/// Aliases of fields, as `(alias, field)`.
+///
+/// The schema doesn't include serde aliases, so they need to be listed here to
+/// be accepted. Keep this in sync with the `#[serde(alias = ..)]` attributes.
+const FIELD_ALIASES: &[(&str, &str)] = &[("params", "config")];
+
+/// Returns the JSON schema of dataflow descriptors.
+pub fn json_schema() -> Json {
+    let schema = schemars::schema_for!(Descriptor);
+    serde_json::to_value(schema).expect("failed to serialize JSON schema")
+}
+
+/// Fails if the given dataflow contains a field that is not part of the schema.
+///
+/// The error names the path of the unknown field and suggests the most
+/// similar known field, e.g. for typos like `ouputs`.
+pub(crate) fn check_unknown_fields(dataflow: &Yaml) -> eyre::Result<()> {
+    // generating the schema is expensive, so only do it once
+    static SCHEMA: OnceLock<Json> = OnceLock::new();
+    let schema = SCHEMA.get_or_init(json_schema);
+    match unknown_field(dataflow, schema, schema, "") {
+        Some(unknown) => Err(eyre::eyre!("{unknown}")),
+        None => Ok(()),
+    }
+}
+
+struct UnknownField {
+    path: String,
+    field: String,
+    suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown field `{}`", self.field)?;
+        if !self.path.is_empty() {
+            write!(f, " in `{}`", self.path)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+fn unknown_field(value: &Yaml, schema: &Json, root: &Json, path: &str) -> Option<UnknownField> {
+    let mut alternatives = Vec::new();
+    collect_alternatives(schema, root, &mut alternatives, 0);
+    match value {
+        Yaml::Mapping(mapping) => {
+            // a field is known if any alternative, e.g. any node kind, defines it
+            let mut properties: BTreeMap<&str, Vec<&Json>> = BTreeMap::new();
+            let mut additional = Vec::new();
+            let mut open = false;
+            for alternative in &alternatives {
+                match alternative {
+                    Json::Bool(true) => open = true,
+                    Json::Object(object) => {
+                        if let Some(Json::Object(fields)) = object.get("properties") {
+                            for (name, schema) in fields {
+                                properties.entry(name).or_default().push(schema);
+                            }
+                        }
+                        match object.get("additionalProperties") {
+                            Some(Json::Bool(true)) => open = true,
+                            Some(schema @ Json::Object(_)) => additional.push(schema),
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if properties.is_empty() && additional.is_empty() {
+                return None;
+            }
+
+            for (key, value) in mapping {
+                let Some(key) = key.as_str() else {
+                    continue;
+                };
+                let field = FIELD_ALIASES
+                    .iter()
+                    .find(|(alias, field)| *alias == key && properties.contains_key(field))
+                    .map_or(key, |(_, field)| *field);
+                let candidates = match properties.get(field) {
+                    Some(candidates) => candidates.as_slice(),
+                    None if !additional.is_empty() => additional.as_slice(),
+                    None if open => continue,
+                    None => {
+                        return Some(UnknownField {
+                            path: path.to_owned(),
+                            field: key.to_owned(),
+                            suggestion: closest(key, properties.keys().copied()),
+                        })
+                    }
+                };
+                let path = match path {
+                    "" => key.to_owned(),
+                    parent => format!("{parent}.{key}"),
+                };
+                if let Some(unknown) = unknown_in_all(value, candidates, root, &path) {
+                    return Some(unknown);
+                }
+            }
+            None
+        }
+        Yaml::Sequence(items) => {
+            let item_schemas: Vec<_> = alternatives
+                .iter()
+                .filter_map(|alternative| alternative.get("items"))
+                .filter(|items| !items.is_array())
+                .collect();
+            if item_schemas.is_empty() {
+                return None;
+            }
+            items.iter().enumerate().find_map(|(index, item)| {
+                unknown_in_all(item, &item_schemas, root, &format!("{path}[{index}]"))
+            })
+        }
+        Yaml::Tagged(tagged) => unknown_field(&tagged.value, schema, root, path),
+        _ => None,
+    }
+}
+
+/// Only reports an unknown field if the value doesn't match any of the schemas.
+fn unknown_in_all(
+    value: &Yaml,
+    schemas: &[&Json],
+    root: &Json,
+    path: &str,
+) -> Option<UnknownField> {
+    let mut first = None;
+    for schema in schemas {
+        match unknown_field(value, schema, root, path) {
+            Some(unknown) => {
+                first.get_or_insert(unknown);
+            }
+            None => return None,
+        }
+    }
+    first
+}
+
+/// Collects the given schema and the schemas that it references or combines.
+fn collect_alternatives<'a>(
+    schema: &'a Json,
+    root: &'a Json,
+    alternatives: &mut Vec<&'a Json>,
+    depth: usize,
+) {
+    // guards against cyclic references
+    if depth > 32 {
+        return;
+    }
+    alternatives.push(schema);
+    let Json::Object(object) = schema else {
+        return;
+    };
+    if let Some(target) = object
+        .get("$ref")
+        .and_then(Json::as_str)
+        .and_then(|reference| reference.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+    {
+        collect_alternatives(target, root, alternatives, depth + 1);
+    }
+    for combinator in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Json::Array(subschemas)) = object.get(combinator) {
+            for subschema in subschemas {
+                collect_alternatives(subschema, root, alternatives, depth + 1);
+            }
+        }
+    }
+}
+
+fn closest<'a>(field: &str, known: impl Iterator<Item = &'a str>) -> Option<String> {
+    known
+        .map(|candidate| (edit_distance(field, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 3)
+        .min()
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Schema of durations that are specified as strings, e.g. `100ms`.
+pub(crate) struct DurationSchema;
+
+/// Schema of byte sizes, e.g. `4096` or `512MiB`.
+pub(crate) struct ByteSizeSchema;
+
+fn described(instance_type: SingleOrVec<InstanceType>, description: &str) -> Schema {
+    SchemaObject {
+        instance_type: Some(instance_type),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_owned()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Implements `JsonSchema` for types with a custom serde implementation.
+macro_rules! custom_schema {
+    ($($ty:ty: $name:literal => [$($instance_type:ident),+], $description:literal;)*) => {$(
+        impl JsonSchema for $ty {
+            fn schema_name() -> String {
+                $name.to_owned()
+            }
+
+            fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+                described(vec![$(InstanceType::$instance_type),+].into(), $description)
+            }
+        }
+    )*};
+}
+
+custom_schema! {
+    InputMapping: "InputMapping" => [String],
+        "Output of another node as `<node>/<output>`, or a timer, e.g. `dora/timer/millis/100`.";
+    MaxRate: "MaxRate" => [String], "Maximum message rate, e.g. `10hz`.";
+    Conversion: "Conversion" => [String], "Builtin conversion, e.g. `bgr8-to-rgb8`.";
+    InputSync: "InputSync" => [String],
+        "Policy for aligning inputs, e.g. `approximate_time(window=50ms)`.";
+    Condition: "Condition" => [String],
+        "Condition on metadata fields, e.g. `metadata.sequence.number >= 10`.";
+    CompactionKey: "CompactionKey" => [String],
+        "Metadata field that identifies the key of a message, e.g. `metadata.track_id`.";
+    NumaNode: "NumaNode" => [Integer, String], "NUMA node number or `auto`.";
+    EnvValue: "EnvValue" => [Boolean, Integer, String], "Value of an environment variable.";
+    ParameterValue: "ParameterValue" => [Boolean, Integer, Number, String, Array],
+        "Value of a parameter.";
+    DurationSchema: "Duration" => [String], "Duration with unit, e.g. `100ms` or `1.5s`.";
+    ByteSizeSchema: "ByteSize" => [Integer, String], "Size in bytes, e.g. `4096` or `512MiB`.";
+}
+
+impl JsonSchema for Input {
+    fn schema_name() -> String {
+        "Input".to_owned()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        InputDef::json_schema(gen)
+    }
+}
+
+impl JsonSchema for PythonSource {
+    fn schema_name() -> String {
+        "PythonSource".to_owned()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        PythonSourceDef::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(dataflow: &str) -> eyre::Result<()> {
+        check_unknown_fields(&serde_yaml::from_str(dataflow).unwrap())
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let valid = r#"
+            nodes:
+              - id: camera
+                custom:
+                  source: ./camera
+                  inputs:
+                    tick:
+                      source: dora/timer/millis/100
+                      queue_size: 1
+                  outputs:
+                    - image
+              - id: detector
+                operators:
+                  - id: yolo
+                    python: detector.py
+                    config:
+                      anything: goes
+                    inputs:
+                      image: camera/image
+        "#;
+        check(valid).unwrap();
+
+        let typo = valid.replace("                  outputs:", "                  ouputs:");
+        let err = check(&typo).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "unknown field `ouputs` in `nodes[0].custom`, did you mean `outputs`?"
+        );
+
+        let input_typo = valid.replace("queue_size", "queue_sze");
+        let err = check(&input_typo).unwrap_err().to_string();
+        assert!(err.contains("`nodes[0].custom.inputs.tick`"), "{err}");
+
+        let alias = valid.replace("                    config:", "                    params:");
+        check(&alias).unwrap();
+    }
+}
@@ -1,12 +1,18 @@
 use crate::{
     adjust_shared_library_path,
-    config::{DataId, Input, InputMapping, OperatorId, UserInputMapping},
-    descriptor::{self, source_is_url, CoreNodeKind, OperatorSource},
+    config::{
+        Compression, CompressionAlgorithm, DataId, Input, InputDef, InputMapping, NodeId,
+        OperatorId, UserInputMapping,
+    },
+    descriptor::{
+        self, source_is_url, CoreNodeKind, OperatorIsolation, OperatorSource, ResolvedNode,
+    },
     get_python_path,
 };
 
 use eyre::{bail, eyre, Context};
-use std::{path::Path, process::Command};
+use jsonschema::JSONSchema;
+use std::{fmt::Write, path::Path, process::Command};
 use tracing::info;
 
 use super::{resolve_path, Descriptor, SHELL_SOURCE};
@@ -58,6 +64,11 @@ pub fn check_dataflow(dataflow: &Descriptor, working_dir: &Path) -> eyre::Result
                             } else if !working_dir.join(path).exists() {
                                 bail!("no Python library at `{path}`");
                             }
+                            if let Some(requirements) = &python_source.requirements {
+                                if !working_dir.join(requirements).exists() {
+                                    bail!("no requirements file at `{requirements}`");
+                                }
+                            }
                         }
                         OperatorSource::Wasm(path) => {
                             if source_is_url(path) {
@@ -77,7 +88,8 @@ pub fn check_dataflow(dataflow: &Descriptor, working_dir: &Path) -> eyre::Result
         match &node.kind {
             descriptor::CoreNodeKind::Custom(custom_node) => {
                 for (input_id, input) in &custom_node.run_config.inputs {
-                    check_input(input, &nodes, &format!("{}/{input_id}", node.id))?;
+                    let input_id = format!("{}/{input_id}", node.id);
+                    check_input(input, &nodes, Some(node), &input_id)?;
                 }
             }
             descriptor::CoreNodeKind::Runtime(runtime_node) => {
@@ -86,14 +98,109 @@ pub fn check_dataflow(dataflow: &Descriptor, working_dir: &Path) -> eyre::Result
                         check_input(
                             input,
                             &nodes,
+                            Some(node),
                             &format!("{}/{}/{input_id}", operator_definition.id, node.id),
                         )?;
                     }
+                    check_operator_parameters(&operator_definition.config, working_dir)
+                        .wrap_err_with(|| {
+                            format!(
+                                "invalid `config` for operator `{}/{}`",
+                                node.id, operator_definition.id
+                            )
+                        })?;
+                    if operator_definition.config.isolation == OperatorIsolation::Process
+                        && !matches!(operator_definition.config.source, OperatorSource::Python(_))
+                    {
+                        bail!(
+                            "operator `{}/{}` uses `isolation: process`, which is only \
+                            supported for Python operators",
+                            node.id,
+                            operator_definition.id
+                        );
+                    }
+                    if !operator_definition.config.live_parameters.is_empty()
+                        && !matches!(operator_definition.config.source, OperatorSource::Python(_))
+                    {
+                        bail!(
+                            "operator `{}/{}` declares `live_parameters`, which are only \
+                            supported for Python operators",
+                            node.id,
+                            operator_definition.id
+                        );
+                    }
+                    if operator_definition.config.sync.is_some()
+                        && operator_definition.config.inputs.len() < 2
+                    {
+                        bail!(
+                            "operator `{}/{}` uses `sync`, which requires at least two inputs",
+                            node.id,
+                            operator_definition.id
+                        );
+                    }
                 }
             }
         };
     }
 
+    // check that only declared outputs are encoded
+    for node in &nodes {
+        let outputs = match &node.kind {
+            CoreNodeKind::Custom(custom) => vec![(
+                node.id.to_string(),
+                &custom.run_config.outputs,
+                &custom.run_config.encoding,
+            )],
+            CoreNodeKind::Runtime(runtime) => runtime
+                .operators
+                .iter()
+                .map(|operator| {
+                    (
+                        format!("{}/{}", node.id, operator.id),
+                        &operator.config.outputs,
+                        &operator.config.encoding,
+                    )
+                })
+                .collect(),
+        };
+        for (name, outputs, encoding) in outputs {
+            if let Some(output) = encoding.keys().find(|id| !outputs.contains(*id)) {
+                bail!("`encoding` of `{name}` refers to unknown output `{output}`");
+            }
+        }
+    }
+
+    check_latency_budgets(dataflow, &nodes)?;
+    check_clock(dataflow, &nodes)?;
+    check_resource_limits(&nodes)?;
+
+    // check that all referenced secrets are defined
+    for node in &nodes {
+        for (env_var, secret) in &node.secrets {
+            if !dataflow.secrets.contains_key(secret) {
+                bail!(
+                    "secret `{secret}` used for env variable `{env_var}` of node `{}` \
+                    is not defined in the `secrets` section",
+                    node.id
+                );
+            }
+        }
+        if let CoreNodeKind::Runtime(runtime_node) = &node.kind {
+            for operator in &runtime_node.operators {
+                for (env_var, secret) in &operator.config.secrets {
+                    if !dataflow.secrets.contains_key(secret) {
+                        bail!(
+                            "secret `{secret}` used for env variable `{env_var}` of operator \
+                            `{}/{}` is not defined in the `secrets` section",
+                            node.id,
+                            operator.id
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     // Check that nodes can resolve `send_stdout_as`
     for node in &nodes {
         node.send_stdout_as()
@@ -107,13 +214,94 @@ pub fn check_dataflow(dataflow: &Descriptor, working_dir: &Path) -> eyre::Result
     Ok(())
 }
 
-fn check_input(
+/// Checks that the `clock` source is an existing output.
+pub(super) fn check_clock(dataflow: &Descriptor, nodes: &[ResolvedNode]) -> eyre::Result<()> {
+    let Some(clock) = &dataflow.clock else {
+        return Ok(());
+    };
+    if let InputMapping::Timer { .. } = clock.source {
+        bail!("the `clock` source must be a node output, not a timer");
+    }
+    let input = Input::from(InputDef::MappingOnly(clock.source.clone()));
+    check_input(&input, nodes, None, "clock")
+}
+
+/// Checks that the resource limits of the nodes are in the supported ranges.
+pub(super) fn check_resource_limits(nodes: &[ResolvedNode]) -> eyre::Result<()> {
+    for node in nodes {
+        if let Some(nice) = node.nice {
+            if !(-20..=19).contains(&nice) {
+                bail!("`nice` of node `{}` must be between -20 and 19", node.id);
+            }
+        }
+        if let Some(cpu) = node.cpu_affinity.iter().find(|&&cpu| cpu >= MAX_CPUS) {
+            bail!(
+                "`cpu_affinity` of node `{}` contains CPU {cpu}, but only CPUs below \
+                {MAX_CPUS} are supported",
+                node.id
+            );
+        }
+        if node.memory_limit == Some(0) {
+            bail!("`memory_limit` of node `{}` must not be zero", node.id);
+        }
+    }
+    Ok(())
+}
+
+/// Maximum number of CPUs in an affinity mask, the size of `cpu_set_t` on Linux.
+const MAX_CPUS: usize = 1024;
+
+/// Checks that every hop of the latency budgets is a connection of the dataflow.
+pub(super) fn check_latency_budgets(
+    dataflow: &Descriptor,
+    nodes: &[ResolvedNode],
+) -> eyre::Result<()> {
+    for path in dataflow.latency_budgets.keys() {
+        if path.0.len() < 2 {
+            bail!("latency budget `{path}` must span at least two nodes");
+        }
+        let find_node = |id: &NodeId| {
+            nodes
+                .iter()
+                .find(|node| &node.id == id)
+                .ok_or_else(|| eyre!("latency budget `{path}` refers to unknown node `{id}`"))
+        };
+        find_node(&path.0[0])?;
+        for hop in path.0.windows(2) {
+            let (from, to) = (&hop[0], &hop[1]);
+            let inputs: Vec<&Input> = match &find_node(to)?.kind {
+                CoreNodeKind::Custom(node) => node.run_config.inputs.values().collect(),
+                CoreNodeKind::Runtime(node) => node
+                    .operators
+                    .iter()
+                    .flat_map(|operator| operator.config.inputs.values())
+                    .collect(),
+            };
+            let connected = inputs.iter().any(|input| {
+                matches!(&input.mapping, InputMapping::User(mapping) if &mapping.source == from)
+            });
+            if !connected {
+                bail!("latency budget `{path}`: node `{to}` has no input from node `{from}`");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the given input of the `receiver` node, or of the dataflow clock if
+/// `receiver` is `None`.
+pub(super) fn check_input(
     input: &Input,
     nodes: &[super::ResolvedNode],
+    receiver: Option<&ResolvedNode>,
     input_id_str: &str,
 ) -> Result<(), eyre::ErrReport> {
     match &input.mapping {
-        InputMapping::Timer { interval: _ } => {}
+        InputMapping::Timer { interval: _ } => {
+            if input.serialization_format().is_some() {
+                bail!("input `{input_id_str}`: timer inputs don't support a `format`");
+            }
+        }
         InputMapping::User(UserInputMapping { source, output }) => {
             let source_node = nodes.iter().find(|n| &n.id == source).ok_or_else(|| {
                 eyre!("source node `{source}` mapped to input `{input_id_str}` does not exist",)
@@ -151,8 +339,112 @@ fn check_input(
                     }
                 }
             }
+            let remote = receiver.filter(|r| r.deploy.machine != source_node.deploy.machine);
+            if let (Some(receiver), Some(compression)) = (remote, input.compression) {
+                check_remote_compression(input, compression, nodes, receiver, input_id_str)?;
+            }
+        }
+    };
+    if let Some(Compression {
+        algorithm: CompressionAlgorithm::Lz4,
+        level: Some(_),
+    }) = input.compression
+    {
+        bail!("input `{input_id_str}`: `lz4` compression doesn't support a `level`");
+    }
+    if input.convert.is_some() && input.serialization_format().is_some() {
+        // the data is serialized by the sender, so it can't be converted anymore
+        bail!("input `{input_id_str}`: `convert` can't be combined with a `format`");
+    }
+    Ok(())
+}
+
+/// Outputs are sent to each machine only once, so all inputs of a machine
+/// that receive the same remote output must use the same `compression`.
+fn check_remote_compression(
+    input: &Input,
+    compression: Compression,
+    nodes: &[ResolvedNode],
+    receiver: &ResolvedNode,
+    input_id_str: &str,
+) -> eyre::Result<()> {
+    let machine_inputs = nodes
+        .iter()
+        .filter(|node| node.deploy.machine == receiver.deploy.machine)
+        .flat_map(|node| -> Vec<(String, &Input)> {
+            match &node.kind {
+                CoreNodeKind::Custom(custom) => custom
+                    .run_config
+                    .inputs
+                    .iter()
+                    .map(|(id, input)| (format!("{}/{id}", node.id), input))
+                    .collect(),
+                CoreNodeKind::Runtime(runtime) => runtime
+                    .operators
+                    .iter()
+                    .flat_map(|operator| {
+                        operator.config.inputs.iter().map(move |(id, input)| {
+                            (format!("{}/{}/{id}", node.id, operator.id), input)
+                        })
+                    })
+                    .collect(),
+            }
+        });
+    for (other_id, other) in machine_inputs {
+        if other.mapping != input.mapping {
+            continue;
+        }
+        if other.compression.is_some_and(|c| c != compression) {
+            bail!(
+                "inputs `{input_id_str}` and `{other_id}` on machine `{}` use different \
+                `compression` options for `{}`, which is sent to each machine only once",
+                receiver.deploy.machine,
+                input.mapping,
+            );
         }
+    }
+    Ok(())
+}
+
+/// Validates the `config` block of an operator against its `config_schema`, if set.
+fn check_operator_parameters(
+    config: &descriptor::OperatorConfig,
+    working_dir: &Path,
+) -> eyre::Result<()> {
+    let Some(schema_path) = &config.config_schema else {
+        return Ok(());
+    };
+    let schema_path = working_dir.join(schema_path);
+    let raw = std::fs::read_to_string(&schema_path)
+        .wrap_err_with(|| format!("failed to read config schema `{}`", schema_path.display()))?;
+    // YAML is a superset of JSON, so this supports schemas in both formats
+    let schema: serde_json::Value = serde_yaml::from_str(&raw)
+        .wrap_err_with(|| format!("failed to parse config schema `{}`", schema_path.display()))?;
+    let schema = JSONSchema::compile(&schema).map_err(|err| {
+        eyre!(
+            "config schema `{}` is not a valid JSON schema: {err}",
+            schema_path.display()
+        )
+    })?;
+
+    let parameters = serde_json::to_value(&config.parameters)
+        .context("failed to convert `config` block to JSON")?;
+    let parameters = match parameters {
+        serde_json::Value::Null => serde_json::Value::Object(Default::default()),
+        other => other,
     };
+    if let Err(errors) = schema.validate(&parameters) {
+        let mut message = String::new();
+        for error in errors {
+            let path = error.instance_path.to_string();
+            let path = if path.is_empty() { "/" } else { &path };
+            write!(message, "\n  - `{path}`: {error}").unwrap();
+        }
+        bail!(
+            "config does not match schema `{}`:{message}",
+            schema_path.display()
+        );
+    }
     Ok(())
 }
 
@@ -183,3 +475,54 @@ assert dora.__version__=='{VERSION}',  'Python dora-rs should be {VERSION}, but
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+type: object
+properties:
+  threshold:
+    type: number
+    minimum: 0
+  labels:
+    type: array
+    items:
+      type: string
+required: [threshold]
+"#;
+
+    fn check(operator: &str) -> eyre::Result<()> {
+        let dir = std::env::temp_dir().join(format!("dora-config-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("schema.yml"), SCHEMA).unwrap();
+        let config: descriptor::OperatorConfig = serde_yaml::from_str(operator).unwrap();
+        check_operator_parameters(&config, &dir)
+    }
+
+    #[test]
+    fn validate_operator_config() {
+        check("python: op.py").unwrap();
+        check("python: op.py\nconfig: { threshold: -1 }").unwrap();
+        check("python: op.py\nconfig_schema: schema.yml\nconfig: { threshold: 0.5 }").unwrap();
+        check("python: op.py\nconfig_schema: schema.yml\nparams: { threshold: 0.5 }").unwrap();
+
+        let err = check(
+            "python: op.py\nconfig_schema: schema.yml\nconfig: { threshold: -1, labels: [car, 3] }",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("`/threshold`"), "{err}");
+        assert!(err.contains("`/labels/1`"), "{err}");
+
+        // missing required fields are reported for the whole block
+        let err = check("python: op.py\nconfig_schema: schema.yml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`/`"), "{err}");
+        assert!(err.contains("threshold"), "{err}");
+
+        assert!(check("python: op.py\nconfig_schema: missing.yml").is_err());
+    }
+}
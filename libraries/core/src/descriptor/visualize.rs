@@ -34,6 +34,107 @@ pub fn visualize_nodes(nodes: &[ResolvedNode]) -> String {
     flowchart
 }
 
+/// Renders the given nodes as a Graphviz graph in the DOT language.
+pub fn visualize_nodes_as_dot(nodes: &[ResolvedNode]) -> String {
+    let mut graph = "digraph dataflow {\n  rankdir=TB;\n  node [shape=box];\n".to_owned();
+    let all_nodes: HashMap<_, _> = nodes.iter().map(|node| (&node.id, node)).collect();
+
+    for node in nodes {
+        let label = node_label(node);
+        match &node.kind {
+            CoreNodeKind::Custom(custom) => {
+                let shape = node_shape(&custom.run_config.inputs, &custom.run_config.outputs);
+                writeln!(
+                    graph,
+                    "  {} [label={}, shape={shape}];",
+                    quote(&node.id.to_string()),
+                    quote(&label)
+                )
+                .unwrap();
+            }
+            CoreNodeKind::Runtime(RuntimeNode { operators }) if is_single_operator(operators) => {
+                let operator = &operators[0];
+                let shape = node_shape(&operator.config.inputs, &operator.config.outputs);
+                writeln!(
+                    graph,
+                    "  {} [label={}, shape={shape}];",
+                    quote(&format!("{}/{}", node.id, operator.id)),
+                    quote(&label)
+                )
+                .unwrap();
+            }
+            CoreNodeKind::Runtime(RuntimeNode { operators }) => {
+                writeln!(
+                    graph,
+                    "  subgraph {} {{",
+                    quote(&format!("cluster_{}", node.id))
+                )
+                .unwrap();
+                writeln!(graph, "    label={};", quote(&label)).unwrap();
+                for operator in operators {
+                    let shape = node_shape(&operator.config.inputs, &operator.config.outputs);
+                    writeln!(
+                        graph,
+                        "    {} [label={}, shape={shape}];",
+                        quote(&format!("{}/{}", node.id, operator.id)),
+                        quote(operator.id.as_ref())
+                    )
+                    .unwrap();
+                }
+                graph.push_str("  }\n");
+            }
+        }
+    }
+
+    let dora_timers = collect_dora_timers(nodes);
+    if !dora_timers.is_empty() {
+        graph.push_str("  subgraph cluster___dora___ {\n    label=\"dora\";\n");
+        for interval in dora_timers {
+            let mapping = InputMapping::Timer { interval };
+            writeln!(
+                graph,
+                "    {} [label={}, shape=house];",
+                quote(&mapping.to_string()),
+                quote(&format!("timer {}", format_duration(interval)))
+            )
+            .unwrap();
+        }
+        graph.push_str("  }\n");
+    }
+
+    let mut missing = false;
+    for (target, inputs) in nodes.iter().flat_map(node_inputs) {
+        for (input_id, input) in inputs {
+            match resolve_source(&input.mapping, &all_nodes) {
+                Some((source, output)) => writeln!(
+                    graph,
+                    "  {} -> {} [label={}];",
+                    quote(&source),
+                    quote(&target),
+                    quote(&edge_label(&output, input_id, input))
+                )
+                .unwrap(),
+                None => {
+                    missing = true;
+                    writeln!(
+                        graph,
+                        "  missing -> {} [label={}, style=dashed, color=red];",
+                        quote(&target),
+                        quote(&edge_label(input_id.as_str(), input_id, input))
+                    )
+                    .unwrap()
+                }
+            }
+        }
+    }
+    if missing {
+        graph.push_str("  missing [shape=octagon, color=red];\n");
+    }
+
+    graph.push_str("}\n");
+    graph
+}
+
 pub fn collect_dora_timers(nodes: &[ResolvedNode]) -> BTreeSet<Duration> {
     let mut dora_timers = BTreeSet::new();
     for node in nodes {
@@ -65,49 +166,136 @@ fn collect_dora_nodes(
     }
 }
 
+/// Returns the node ID, followed by the machine that the node is deployed on (if set).
+fn node_label(node: &ResolvedNode) -> String {
+    if node.deploy.machine.is_empty() {
+        node.id.to_string()
+    } else {
+        format!("{} @ {}", node.id, node.deploy.machine)
+    }
+}
+
+fn is_single_operator(operators: &[OperatorDefinition]) -> bool {
+    operators.len() == 1 && operators[0].id.to_string() == "op"
+}
+
+/// Returns the graph IDs of the given node's inputs together with the inputs.
+fn node_inputs(node: &ResolvedNode) -> Vec<(String, &BTreeMap<DataId, Input>)> {
+    match &node.kind {
+        CoreNodeKind::Custom(custom) => vec![(node.id.to_string(), &custom.run_config.inputs)],
+        CoreNodeKind::Runtime(RuntimeNode { operators }) => operators
+            .iter()
+            .map(|operator| {
+                (
+                    format!("{}/{}", node.id, operator.id),
+                    &operator.config.inputs,
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Returns the graph ID of the node or operator that produces the given input and
+/// the name of its output, or `None` if the output doesn't exist.
+fn resolve_source(
+    mapping: &InputMapping,
+    nodes: &HashMap<&NodeId, &ResolvedNode>,
+) -> Option<(String, String)> {
+    let UserInputMapping { source, output } = match mapping {
+        InputMapping::Timer { .. } => return Some((mapping.to_string(), String::new())),
+        InputMapping::User(mapping) => mapping,
+    };
+    match &nodes.get(source)?.kind {
+        CoreNodeKind::Custom(custom_node) => custom_node
+            .run_config
+            .outputs
+            .contains(output)
+            .then(|| (source.to_string(), output.to_string())),
+        CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
+            let (operator_id, output) = output.split_once('/').unwrap_or(("", output));
+            let operator = operators.iter().find(|o| o.id.as_ref() == operator_id)?;
+            operator
+                .config
+                .outputs
+                .contains(output)
+                .then(|| (format!("{source}/{operator_id}"), output.to_owned()))
+        }
+    }
+}
+
+/// Describes the output and input of a link, including the queue size if set.
+fn edge_label(output: &str, input_id: &DataId, input: &Input) -> String {
+    let mut label = if output.is_empty() || output == input_id.as_str() {
+        input_id.to_string()
+    } else {
+        format!("{output} as {input_id}")
+    };
+    if let Some(queue_size) = input.queue_size {
+        write!(label, ", queue {queue_size}").unwrap();
+    }
+    label
+}
+
+fn node_shape(inputs: &BTreeMap<DataId, Input>, outputs: &BTreeSet<DataId>) -> &'static str {
+    if inputs.is_empty() {
+        "house"
+    } else if outputs.is_empty() {
+        "invhouse"
+    } else {
+        "box"
+    }
+}
+
+/// Quotes and escapes the given string for use as DOT identifier.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 fn visualize_node(node: &ResolvedNode, flowchart: &mut String) {
     let node_id = &node.id;
+    let label = node_label(node);
     match &node.kind {
-        CoreNodeKind::Custom(node) => visualize_custom_node(node_id, node, flowchart),
+        CoreNodeKind::Custom(node) => visualize_custom_node(node_id, &label, node, flowchart),
         CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
-            visualize_runtime_node(node_id, operators, flowchart)
+            visualize_runtime_node(node_id, &label, operators, flowchart)
         }
     }
 }
 
-fn visualize_custom_node(node_id: &NodeId, node: &CustomNode, flowchart: &mut String) {
+fn visualize_custom_node(node_id: &NodeId, label: &str, node: &CustomNode, flowchart: &mut String) {
     if node.run_config.inputs.is_empty() {
         // source node
-        writeln!(flowchart, "  {node_id}[\\{node_id}/]").unwrap();
+        writeln!(flowchart, "  {node_id}[\\{label}/]").unwrap();
     } else if node.run_config.outputs.is_empty() {
         // sink node
-        writeln!(flowchart, "  {node_id}[/{node_id}\\]").unwrap();
+        writeln!(flowchart, "  {node_id}[/{label}\\]").unwrap();
     } else {
         // normal node
-        writeln!(flowchart, "  {node_id}").unwrap();
+        writeln!(flowchart, "  {node_id}[{label}]").unwrap();
     }
 }
 
 fn visualize_runtime_node(
     node_id: &NodeId,
+    label: &str,
     operators: &[OperatorDefinition],
     flowchart: &mut String,
 ) {
-    if operators.len() == 1 && operators[0].id.to_string() == "op" {
+    if is_single_operator(operators) {
         let operator = &operators[0];
         // single operator node
         if operator.config.inputs.is_empty() {
             // source node
-            writeln!(flowchart, "  {node_id}/op[\\{node_id}/]").unwrap();
+            writeln!(flowchart, "  {node_id}/op[\\{label}/]").unwrap();
         } else if operator.config.outputs.is_empty() {
             // sink node
-            writeln!(flowchart, "  {node_id}/op[/{node_id}\\]").unwrap();
+            writeln!(flowchart, "  {node_id}/op[/{label}\\]").unwrap();
         } else {
             // normal node
-            writeln!(flowchart, "  {node_id}/op[{node_id}]").unwrap();
+            writeln!(flowchart, "  {node_id}/op[{label}]").unwrap();
         }
     } else {
-        writeln!(flowchart, "subgraph {node_id}").unwrap();
+        writeln!(flowchart, "subgraph {node_id} [{label}]").unwrap();
         for operator in operators {
             let operator_id = &operator.id;
             if operator.config.inputs.is_empty() {
@@ -130,85 +318,57 @@ fn visualize_node_inputs(
     flowchart: &mut String,
     nodes: &HashMap<&NodeId, &ResolvedNode>,
 ) {
-    let node_id = &node.id;
-    match &node.kind {
-        CoreNodeKind::Custom(node) => visualize_inputs(
-            &node_id.to_string(),
-            &node.run_config.inputs,
-            flowchart,
-            nodes,
-        ),
-        CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
-            for operator in operators {
-                visualize_inputs(
-                    &format!("{node_id}/{}", operator.id),
-                    &operator.config.inputs,
-                    flowchart,
-                    nodes,
-                )
-            }
-        }
-    }
-}
-
-fn visualize_inputs(
-    target: &str,
-    inputs: &BTreeMap<DataId, Input>,
-    flowchart: &mut String,
-    nodes: &HashMap<&NodeId, &ResolvedNode>,
-) {
-    for (input_id, input) in inputs {
-        match &input.mapping {
-            mapping @ InputMapping::Timer { .. } => {
-                writeln!(flowchart, "  {} -- {input_id} --> {target}", mapping).unwrap();
-            }
-            InputMapping::User(mapping) => {
-                visualize_user_mapping(mapping, target, nodes, input_id, flowchart)
-            }
-        }
-    }
-}
-
-fn visualize_user_mapping(
-    mapping: &UserInputMapping,
-    target: &str,
-    nodes: &HashMap<&NodeId, &ResolvedNode>,
-    input_id: &DataId,
-    flowchart: &mut String,
-) {
-    let UserInputMapping { source, output } = mapping;
-    let mut source_found = false;
-    if let Some(source_node) = nodes.get(source) {
-        match &source_node.kind {
-            CoreNodeKind::Custom(custom_node) => {
-                if custom_node.run_config.outputs.contains(output) {
-                    let data = if output == input_id {
-                        format!("{output}")
-                    } else {
-                        format!("{output} as {input_id}")
-                    };
+    for (target, inputs) in node_inputs(node) {
+        for (input_id, input) in inputs {
+            match resolve_source(&input.mapping, nodes) {
+                Some((source, output)) => {
+                    let data = edge_label(&output, input_id, input);
                     writeln!(flowchart, "  {source} -- {data} --> {target}").unwrap();
-                    source_found = true;
                 }
-            }
-            CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
-                let (operator_id, output) = output.split_once('/').unwrap_or(("", output));
-                if let Some(operator) = operators.iter().find(|o| o.id.as_ref() == operator_id) {
-                    if operator.config.outputs.contains(output) {
-                        let data = if output == input_id.as_str() {
-                            output.to_string()
-                        } else {
-                            format!("{output} as {input_id}")
-                        };
-                        writeln!(flowchart, "  {source}/{operator_id} -- {data} --> {target}")
-                            .unwrap();
-                        source_found = true;
-                    }
+                None => {
+                    let data = edge_label(input_id.as_str(), input_id, input);
+                    writeln!(flowchart, "  missing>missing] -- {data} --> {target}").unwrap();
                 }
             }
         }
     }
-    if !source_found {
-        writeln!(flowchart, "  missing>missing] -- {input_id} --> {target}").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::Descriptor;
+
+    #[test]
+    fn dot_output() {
+        let descriptor = Descriptor::parse(
+            br#"
+nodes:
+  - id: camera
+    _unstable_deploy:
+      machine: robot
+    custom:
+      source: camera.py
+      inputs:
+        tick: dora/timer/millis/50
+      outputs:
+        - image
+  - id: plot
+    custom:
+      source: plot.py
+      inputs:
+        frame:
+          source: camera/image
+          queue_size: 1
+"#
+            .to_vec(),
+        )
+        .unwrap();
+        let dot = visualize_nodes_as_dot(&descriptor.resolve_aliases_and_set_defaults());
+
+        assert!(dot.contains(r#""camera" [label="camera @ robot", shape=box];"#));
+        assert!(dot.contains(r#""plot" [label="plot", shape=invhouse];"#));
+        assert!(dot.contains(r#""dora/timer/millis/50" -> "camera" [label="tick"];"#));
+        assert!(dot.contains(r#""camera" -> "plot" [label="image as frame, queue 1"];"#));
     }
 }
@@ -0,0 +1,233 @@
+//! Minimal HTTP/1.1 support for the small HTTP endpoints of dora, i.e. the
+//! control API and the health checks of the coordinator and the output
+//! streams of the daemon.
+//!
+//! Every connection serves a single request and is closed afterwards.
+
+use eyre::{bail, eyre, Context};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Maximum size of the request line and headers.
+pub const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// Time that clients have to send the complete request.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Query string of the request target, without the leading `?`.
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Parses the request line and headers, without the terminating empty line.
+    pub fn parse_head(head: &str) -> eyre::Result<Self> {
+        let mut lines = head.lines();
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            bail!("invalid request line `{request_line}`");
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let mut headers = Vec::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let Some((name, value)) = line.split_once(':') else {
+                bail!("invalid header `{line}`");
+            };
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+        Ok(Self {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            query: query.to_owned(),
+            headers,
+            body: Vec::new(),
+        })
+    }
+
+    /// Returns the value of the first header with the given name, ignoring
+    /// ASCII case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn content_length(&self) -> eyre::Result<usize> {
+        match self.header("content-length") {
+            Some(value) => value
+                .parse()
+                .wrap_err_with(|| format!("invalid content length `{value}`")),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the percent-decoded key-value pairs of the query string.
+    pub fn query_pairs(&self) -> eyre::Result<Vec<(String, String)>> {
+        self.query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((percent_decode(key)?, percent_decode(value)?))
+            })
+            .collect()
+    }
+}
+
+/// Reads a request with a body of at most `max_body_size` bytes.
+///
+/// Fails if the client doesn't send the complete request within
+/// [`READ_TIMEOUT`].
+pub async fn read_request(
+    connection: &mut (impl AsyncRead + Unpin),
+    max_body_size: usize,
+) -> eyre::Result<Request> {
+    tokio::time::timeout(READ_TIMEOUT, read_request_inner(connection, max_body_size))
+        .await
+        .map_err(|_| eyre!("timed out waiting for request"))?
+}
+
+async fn read_request_inner(
+    connection: &mut (impl AsyncRead + Unpin),
+    max_body_size: usize,
+) -> eyre::Result<Request> {
+    let mut data = Vec::new();
+    let mut buffer = [0; 4096];
+    let head_end = loop {
+        if let Some(position) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break position;
+        }
+        if data.len() > MAX_HEAD_SIZE {
+            bail!("request head too large");
+        }
+        let read = connection.read(&mut buffer).await?;
+        if read == 0 {
+            bail!("connection closed before end of request head");
+        }
+        data.extend_from_slice(&buffer[..read]);
+    };
+    let head =
+        std::str::from_utf8(&data[..head_end]).wrap_err("request head is not valid UTF-8")?;
+    let mut request = Request::parse_head(head)?;
+
+    let content_length = request.content_length()?;
+    if content_length > max_body_size {
+        bail!("request body too large");
+    }
+    let mut body = data[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = connection.read(&mut buffer).await?;
+        if read == 0 {
+            bail!("connection closed before end of request body");
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    body.truncate(content_length);
+    request.body = body;
+    Ok(request)
+}
+
+/// Writes the status line and headers of a response whose body is streamed
+/// afterwards.
+pub async fn write_head(
+    connection: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    headers: &[(&str, &str)],
+) -> eyre::Result<()> {
+    let mut head = format!("HTTP/1.1 {status}\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    connection.write_all(head.as_bytes()).await?;
+    Ok(())
+}
+
+/// Writes a complete response and marks the connection as closed.
+pub async fn respond(
+    connection: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> eyre::Result<()> {
+    let content_length = body.len().to_string();
+    let mut all_headers = headers.to_vec();
+    all_headers.push(("Content-Length", &content_length));
+    all_headers.push(("Connection", "close"));
+    write_head(connection, status, &all_headers).await?;
+    connection.write_all(body).await?;
+    connection.flush().await?;
+    Ok(())
+}
+
+pub async fn respond_json(
+    connection: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    body: &serde_json::Value,
+) -> eyre::Result<()> {
+    let body = body.to_string();
+    let headers = [("Content-Type", "application/json")];
+    respond(connection, status, &headers, body.as_bytes()).await
+}
+
+pub fn percent_decode(value: &str) -> eyre::Result<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next().unwrap_or(0), bytes.next().unwrap_or(0)];
+                let hex = std::str::from_utf8(&hex).unwrap_or_default();
+                let byte = u8::from_str_radix(hex, 16)
+                    .map_err(|_| eyre!("invalid percent-encoding in `{value}`"))?;
+                decoded.push(byte);
+            }
+            b'+' => decoded.push(b' '),
+            other => decoded.push(other),
+        }
+    }
+    String::from_utf8(decoded).wrap_err("query parameter is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_requests() {
+        let raw = b"POST /api/dataflows?name=camera%20demo&x HTTP/1.1\r\nHost: localhost\r\n\
+            content-length: 4\r\n\r\nbodyignored";
+        let request = read_request(&mut &raw[..], 16).await.unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/dataflows");
+        assert_eq!(request.header("Content-Length"), Some("4"));
+        assert_eq!(request.body, b"body");
+        assert_eq!(
+            request.query_pairs().unwrap(),
+            [
+                ("name".to_owned(), "camera demo".to_owned()),
+                ("x".to_owned(), String::new())
+            ]
+        );
+
+        assert!(read_request(&mut &raw[..], 2).await.is_err());
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\n"[..], 0)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn time_out_incomplete_requests() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let err = read_request(&mut server, 0).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+}
@@ -7,11 +7,17 @@ use std::{
 
 pub use dora_message as message;
 
+pub mod cgroup;
+pub mod compaction;
+pub mod condition;
 pub mod config;
 pub mod coordinator_messages;
 pub mod daemon_messages;
 pub mod descriptor;
+pub mod http;
+pub mod process_stats;
 pub mod topics;
+pub mod transport;
 
 pub fn adjust_shared_library_path(path: &Path) -> Result<std::path::PathBuf, eyre::ErrReport> {
     let file_name = path
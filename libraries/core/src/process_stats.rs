@@ -0,0 +1,166 @@
+//! Resource usage of node processes, read from the Linux `/proc` filesystem.
+
+use crate::cgroup::CpuThrottling;
+use std::time::Duration;
+
+/// Resource usage of a process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ProcessStats {
+    /// CPU time that the process spent in user and kernel mode since it was started.
+    pub cpu_time: Duration,
+    /// Resident memory of the process in bytes.
+    pub memory_bytes: u64,
+    /// CPU throttling of the cgroup of the process, if it has CPU accounting.
+    #[serde(default)]
+    pub throttling: Option<CpuThrottling>,
+}
+
+impl ProcessStats {
+    /// Reads the resource usage of the process with the given ID.
+    ///
+    /// Returns `None` if the process does not exist (anymore) or if the platform
+    /// has no `/proc` filesystem (i.e. on non-Linux systems).
+    pub fn read_for_pid(pid: u32) -> Option<Self> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+        Some(Self {
+            cpu_time: parse_cpu_time(&stat)?,
+            memory_bytes: parse_resident_memory(&status)?,
+            throttling: CpuThrottling::read_for_pid(pid),
+        })
+    }
+}
+
+/// Name of the thread that runs the operator with the given index in a runtime
+/// node.
+///
+/// The first operator runs on the main thread of the runtime, which keeps the
+/// name of the process. The names of the other threads are unique within the
+/// runtime and short enough to not be truncated by the kernel, so that the
+/// daemon can find the threads in `/proc`.
+pub fn operator_thread_name(index: usize) -> String {
+    format!("dora-op-{index}")
+}
+
+/// CPU usage of a single thread of a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadStats {
+    pub tid: u32,
+    /// The name of the thread, truncated to 15 bytes by the kernel.
+    pub name: String,
+    /// CPU time that the thread spent in user and kernel mode since it was started.
+    pub cpu_time: Duration,
+}
+
+impl ThreadStats {
+    /// Reads the CPU usage of the given thread of the given process.
+    pub fn read(pid: u32, tid: u32) -> Option<Self> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/task/{tid}/stat")).ok()?;
+        let name = std::fs::read(format!("/proc/{pid}/task/{tid}/comm")).ok()?;
+        Some(Self {
+            tid,
+            name: String::from_utf8_lossy(&name).trim_end().to_owned(),
+            cpu_time: parse_cpu_time(&stat)?,
+        })
+    }
+
+    /// Reads the CPU usage of all threads of the given process.
+    ///
+    /// Returns an empty list if the process does not exist (anymore) or if the
+    /// platform has no `/proc` filesystem.
+    pub fn read_all(pid: u32) -> Vec<Self> {
+        let Ok(tasks) = std::fs::read_dir(format!("/proc/{pid}/task")) else {
+            return Vec::new();
+        };
+        tasks
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter_map(|tid| Self::read(pid, tid))
+            .collect()
+    }
+}
+
+/// Parses the `utime` and `stime` fields of a `/proc/<pid>/stat` file.
+fn parse_cpu_time(stat: &str) -> Option<Duration> {
+    // the command name in the second field might contain spaces, so we split
+    // after its closing parenthesis
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace();
+    // `utime` and `stime` are the 14th and 15th field, the first two fields
+    // (pid and command) are already skipped
+    let utime: u64 = fields.nth(11)?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    let ticks = utime + stime;
+    let ticks_per_second = clock_ticks_per_second();
+    Some(
+        Duration::from_secs(ticks / ticks_per_second)
+            + Duration::from_nanos(ticks % ticks_per_second * 1_000_000_000 / ticks_per_second),
+    )
+}
+
+/// Number of clock ticks per second that `/proc/<pid>/stat` uses for CPU times.
+#[cfg(unix)]
+fn clock_ticks_per_second() -> u64 {
+    // SAFETY: `sysconf` has no preconditions
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks_per_second() -> u64 {
+    100
+}
+
+/// Parses the `VmRSS` entry of a `/proc/<pid>/status` file.
+fn parse_resident_memory(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_files() {
+        let stat = "4242 (my node (1)) S 1 4242 4242 0 -1 4194560 1234 0 0 0 \
+            250 37 0 0 20 0 4 0 123456 1000000 2000 18446744073709551615";
+        let ticks_per_second = clock_ticks_per_second();
+        assert_eq!(
+            parse_cpu_time(stat),
+            Some(Duration::from_secs(287) / ticks_per_second as u32)
+        );
+
+        let status = "Name:\tnode\nVmPeak:\t  20000 kB\nVmRSS:\t   5120 kB\nThreads:\t4\n";
+        assert_eq!(parse_resident_memory(status), Some(5120 * 1024));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_threads_of_current_process() {
+        let pid = std::process::id();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::Builder::new()
+            .name("stats-test".into())
+            .spawn(move || {
+                started_tx.send(()).unwrap();
+                let _ = stop_rx.recv();
+            })
+            .unwrap();
+        started_rx.recv().unwrap();
+        let threads = ThreadStats::read_all(pid);
+        assert!(threads.iter().any(|thread| thread.tid == pid));
+        assert!(threads.iter().any(|thread| thread.name == "stats-test"));
+        drop(stop_tx);
+        handle.join().unwrap();
+    }
+}
@@ -1,17 +1,27 @@
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::Display,
     net::{Ipv4Addr, SocketAddr},
     path::PathBuf,
+    time::{Duration, SystemTime},
 };
 use uuid::Uuid;
 
 use crate::{
-    config::{NodeId, OperatorId},
+    config::{DataId, NodeId, OperatorId, RestartScope},
+    coordinator_messages::NodeState,
     descriptor::Descriptor,
+    message::MetadataValue,
+    process_stats::ProcessStats,
 };
 
 pub const DORA_COORDINATOR_PORT_DEFAULT: u16 = 0xD02A;
+/// Local port on which daemons accept read-only observer connections.
+pub const DORA_DAEMON_OBSERVER_PORT_DEFAULT: u16 = 0xD02B;
+/// Local port of the HTTP health endpoint of `dora watchdog`.
+pub const DORA_WATCHDOG_HEALTH_PORT_DEFAULT: u16 = 0xD02C;
+/// Local port on which daemons stream outputs as server-sent events.
+pub const DORA_DAEMON_STREAM_PORT_DEFAULT: u16 = 0xD02D;
 
 pub const MANUAL_STOP: &str = "dora/stop";
 
@@ -46,6 +56,37 @@ pub enum ControlRequest {
         uuid: Option<Uuid>,
         name: Option<String>,
         node: String,
+        /// Only return the log from this byte offset on, e.g. to follow a log.
+        #[serde(default)]
+        offset: u64,
+    },
+    /// Query the lifecycle states of the nodes of a running dataflow.
+    NodeStates {
+        dataflow_uuid: Uuid,
+    },
+    /// Query resource usage and message statistics of a running dataflow.
+    DataflowStats {
+        dataflow_uuid: Uuid,
+    },
+    /// Ask a single node of a running dataflow to stop.
+    DeactivateNode {
+        dataflow_uuid: Uuid,
+        node_id: NodeId,
+    },
+    /// Query the node restarts that were performed for a running dataflow.
+    Restarts {
+        dataflow_uuid: Uuid,
+    },
+    /// Change a parameter of a node of a running dataflow.
+    SetParameter {
+        dataflow_uuid: Uuid,
+        node_id: NodeId,
+        key: String,
+        value: MetadataValue,
+    },
+    /// Query the current parameter values of a running dataflow.
+    Parameters {
+        dataflow_uuid: Uuid,
     },
     Destroy,
     List,
@@ -66,6 +107,9 @@ pub enum ControlRequestReply {
     DataflowStopped {
         uuid: Uuid,
         result: Result<(), String>,
+        /// Message statistics of the finished dataflow.
+        #[serde(default)]
+        report: RateReport,
     },
 
     DataflowList {
@@ -75,6 +119,153 @@ pub enum ControlRequestReply {
     DaemonConnected(bool),
     ConnectedMachines(BTreeSet<String>),
     Logs(Vec<u8>),
+    NodeStates(BTreeMap<NodeId, NodeState>),
+    DataflowStats(DataflowStats),
+    NodeDeactivated {
+        uuid: Uuid,
+        node_id: NodeId,
+    },
+    Restarts(Vec<NodeRestart>),
+    ParameterSet {
+        uuid: Uuid,
+        node_id: NodeId,
+        key: String,
+    },
+    Parameters(BTreeMap<NodeId, BTreeMap<String, MetadataValue>>),
+}
+
+/// A restart of a failed node.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeRestart {
+    pub node_id: NodeId,
+    /// The dataflow instance that the node failed in.
+    ///
+    /// Dataflow restarts start the dataflow with a new UUID, so this might
+    /// differ from the UUID of the running dataflow.
+    pub dataflow_uuid: Uuid,
+    pub time: SystemTime,
+    /// Number of the restart, starting at 1.
+    pub attempt: u32,
+    pub scope: RestartScope,
+    /// Whether the restart failed, e.g. because the node could not be spawned.
+    pub error: Option<String>,
+}
+
+/// Resource usage and message statistics of a running dataflow.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct DataflowStats {
+    pub nodes: BTreeMap<NodeId, NodeStats>,
+    pub links: Vec<LinkStats>,
+}
+
+impl DataflowStats {
+    /// Adds the statistics reported by another daemon.
+    pub fn merge(&mut self, other: DataflowStats) {
+        self.nodes.extend(other.nodes);
+        self.links.extend(other.links);
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeStats {
+    pub machine: String,
+    pub pid: Option<u32>,
+    /// `None` if the node is not running or the platform doesn't support it.
+    pub process: Option<ProcessStats>,
+    /// CPU time of the threads that run the operators of runtime nodes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub operators: BTreeMap<OperatorId, Duration>,
+}
+
+/// Statistics of the messages sent from an output to an input.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LinkStats {
+    pub source: NodeId,
+    pub output: DataId,
+    pub target: NodeId,
+    pub input: DataId,
+    /// Number of messages that were delivered to the target node.
+    pub messages: u64,
+    pub bytes: u64,
+    /// Number of messages that were dropped because the input queue was full.
+    pub dropped: u64,
+}
+
+/// Message statistics of a finished dataflow, collected by the daemons.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RateReport {
+    pub links: Vec<LinkReport>,
+}
+
+impl RateReport {
+    /// Adds the report of another daemon.
+    pub fn merge(&mut self, other: RateReport) {
+        self.links.extend(other.links);
+        self.links.sort_by_key(|link| {
+            let s = &link.stats;
+            (
+                s.source.clone(),
+                s.output.clone(),
+                s.target.clone(),
+                s.input.clone(),
+            )
+        });
+    }
+}
+
+impl Display for RateReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<40} {:>9} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "LINK", "MESSAGES", "DROPPED", "BYTES", "MEAN/s", "P5/s", "P50/s", "P95/s"
+        )?;
+        for link in &self.links {
+            let LinkStats {
+                source,
+                output,
+                target,
+                input,
+                messages,
+                bytes,
+                dropped,
+            } = &link.stats;
+            let name = format!("{source}/{output} -> {target}/{input}");
+            let rate = |rate: Option<f64>| match rate {
+                Some(rate) => format!("{rate:.1}"),
+                None => "-".to_owned(),
+            };
+            writeln!(
+                f,
+                "{name:<40} {messages:>9} {dropped:>8} {bytes:>10} {:>10} {:>10} {:>10} {:>10}",
+                rate(link.mean_rate),
+                rate(link.p5_rate),
+                rate(link.median_rate),
+                rate(link.p95_rate),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of the messages sent from an output to an input over a whole run.
+///
+/// The percentiles are computed from the number of messages in each full
+/// second between the first and the last message. They are `None` for links
+/// that were active for less than that.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LinkReport {
+    #[serde(flatten)]
+    pub stats: LinkStats,
+    /// Time between the first and the last delivered message.
+    pub duration: Duration,
+    /// Mean messages per second, `None` if fewer than two messages were delivered.
+    pub mean_rate: Option<f64>,
+    /// Percentiles of the messages per second, over the last ten minutes of
+    /// the link.
+    pub p5_rate: Option<f64>,
+    pub median_rate: Option<f64>,
+    pub p95_rate: Option<f64>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
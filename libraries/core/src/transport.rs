@@ -0,0 +1,185 @@
+//! Security settings for the connections between the daemons of different machines.
+//!
+//! Each daemon reads the settings from its own configuration file, so that they
+//! can't be changed through the connection to the coordinator.
+
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+};
+
+/// Certificate of the certificate authority that signed the daemon certificate.
+pub const CA_CERT_FILE: &str = "ca.pem";
+/// Certificate that the daemon uses both as server and as client.
+pub const DAEMON_CERT_FILE: &str = "daemon.pem";
+/// Private key of the daemon certificate.
+pub const DAEMON_KEY_FILE: &str = "daemon.key";
+/// Secret that is shared by all daemons, from which the dataflow tokens are derived.
+pub const TOKEN_SECRET_FILE: &str = "token.secret";
+
+/// Server name in the certificate of the daemon of the given machine.
+///
+/// Daemons connect to each other by IP address, so they verify the name of the
+/// machine that they expect instead of the address.
+pub fn daemon_server_name(machine_id: &str) -> String {
+    let machine_id = if machine_id.is_empty() {
+        "default"
+    } else {
+        machine_id
+    };
+    format!("{machine_id}.dora-daemon")
+}
+
+/// Compares a received token with the expected one in constant time, to not
+/// leak the expected token through the response time.
+pub fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Configuration of `dora coordinator`, read from the file passed as `--config`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoordinatorConfig {
+    /// Serve the REST control API for external orchestration tools.
+    #[serde(default)]
+    pub api: Option<ApiConfig>,
+}
+
+impl CoordinatorConfig {
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+        serde_yaml::from_str(&raw)
+            .wrap_err_with(|| format!("failed to parse coordinator config `{}`", path.display()))
+    }
+}
+
+/// Environment variable that sets the token of the control API if the
+/// configuration file doesn't.
+pub const API_TOKEN_ENV: &str = "DORA_API_TOKEN";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Port of the HTTP server.
+    pub port: u16,
+    /// Address that the HTTP server listens on.
+    ///
+    /// Addresses other than loopback addresses require `tls`, as the bearer
+    /// token would be sent in plaintext otherwise.
+    #[serde(default = "default_api_bind")]
+    pub bind: IpAddr,
+    /// Serve the API over HTTPS.
+    #[serde(default)]
+    pub tls: Option<ApiTlsConfig>,
+    /// Bearer token that clients need to send in the `Authorization` header.
+    ///
+    /// Read from the `DORA_API_TOKEN` environment variable if not set.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Directory that the working directories of dataflows started through the
+    /// API must be within.
+    ///
+    /// Defaults to the working directory of the coordinator.
+    #[serde(default)]
+    pub dataflow_root: Option<PathBuf>,
+}
+
+fn default_api_bind() -> IpAddr {
+    Ipv4Addr::LOCALHOST.into()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiTlsConfig {
+    /// PEM file with the server certificate chain.
+    pub certificate: PathBuf,
+    /// PEM file with the PKCS#8 private key of the server certificate.
+    pub key: PathBuf,
+}
+
+/// Configuration of `dora daemon`, read from the file passed as `--config`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonConfig {
+    #[serde(default)]
+    pub transport: TransportConfig,
+}
+
+impl DaemonConfig {
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+        serde_yaml::from_str(&raw)
+            .wrap_err_with(|| format!("failed to parse daemon config `{}`", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransportConfig {
+    /// Encrypt the connections between daemons and require both sides to
+    /// authenticate with a certificate.
+    ///
+    /// Daemons with TLS don't accept plain TCP connections, so all daemons of
+    /// a dataflow need to enable it.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Directory with the files that `dora certgen` created for this machine.
+    pub certificates: PathBuf,
+}
+
+impl TlsConfig {
+    pub fn ca_cert(&self) -> PathBuf {
+        self.certificates.join(CA_CERT_FILE)
+    }
+
+    pub fn daemon_cert(&self) -> PathBuf {
+        self.certificates.join(DAEMON_CERT_FILE)
+    }
+
+    pub fn daemon_key(&self) -> PathBuf {
+        self.certificates.join(DAEMON_KEY_FILE)
+    }
+
+    pub fn token_secret(&self) -> PathBuf {
+        self.certificates.join(TOKEN_SECRET_FILE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_daemon_config() {
+        let config: DaemonConfig =
+            serde_yaml::from_str("transport:\n  tls:\n    certificates: /etc/dora/certs\n")
+                .unwrap();
+        let tls = config.transport.tls.unwrap();
+        assert_eq!(tls.daemon_key(), Path::new("/etc/dora/certs/daemon.key"));
+
+        let config: DaemonConfig = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(config.transport, TransportConfig::default());
+    }
+
+    #[test]
+    fn compare_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret2", "secret"));
+        assert_eq!(daemon_server_name(""), "default.dora-daemon");
+    }
+}
@@ -0,0 +1,130 @@
+//! Replays a recording of `dora-record`, e.g. for regression tests.
+//!
+//! The `REPLAY_DIR` environment variable points to the recording directory
+//! (`out/<dataflow_id>`). For every output of the node, the recorded messages of
+//! the input with the same ID are read from `<output>.parquet`, or from its
+//! chunk files if the recording was split into chunks.
+//!
+//! All messages are sent in the order of their recorded timestamps, which are
+//! passed on as their logical time. In dataflows with `deterministic: true`, the
+//! operators then receive the messages in the same order on every run.
+
+use dora_node_api::{
+    arrow::array::{Array, ArrayRef, AsArray, ListArray, UInt64Array},
+    dora_core::config::DataId,
+    DoraNode, MetadataParameters,
+};
+use eyre::{bail, Context, ContextCompat};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::path::{Path, PathBuf};
+
+struct RecordedMessage {
+    logical_time: u64,
+    output: DataId,
+    data: ArrayRef,
+}
+
+fn main() -> eyre::Result<()> {
+    let (mut node, _events) = DoraNode::init_from_env()?;
+    let dir: PathBuf = std::env::var("REPLAY_DIR")
+        .context("REPLAY_DIR must be set to the directory of the recording")?
+        .into();
+
+    let mut messages = Vec::new();
+    for output in node.node_config().outputs.clone() {
+        messages.extend(read_recording(&dir, &output)?);
+    }
+    // the sort is stable, so messages with the same timestamp keep their order
+    messages.sort_by(|a, b| (a.logical_time, &a.output).cmp(&(b.logical_time, &b.output)));
+
+    println!(
+        "Replaying {} messages from `{}`",
+        messages.len(),
+        dir.display()
+    );
+    for message in messages {
+        let parameters = MetadataParameters {
+            logical_time: Some(message.logical_time),
+            ..Default::default()
+        };
+        node.send_output(message.output, parameters, message.data)
+            .context("failed to send recorded message")?;
+    }
+
+    Ok(())
+}
+
+/// Reads the recorded messages of the input with the same ID as the given output.
+fn read_recording(dir: &Path, output: &DataId) -> eyre::Result<Vec<RecordedMessage>> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read recording directory `{}`", dir.display()))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| is_recording_of(path, output))
+        .collect();
+    if files.is_empty() {
+        bail!("no recording of `{output}` found in `{}`", dir.display());
+    }
+    // chunk numbers are zero-padded, so the file names sort chronologically
+    files.sort();
+
+    let mut messages = Vec::new();
+    for path in files {
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("failed to open `{}`", path.display()))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .and_then(|builder| builder.build())
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        for batch in reader {
+            let batch = batch.context("failed to read record batch")?;
+            let timestamps = batch
+                .column_by_name("timestamp_uhlc")
+                .and_then(|column| column.as_any().downcast_ref::<UInt64Array>())
+                .context("recording has no `timestamp_uhlc` column")?;
+            let data: &ListArray = batch
+                .column_by_name(output.as_str())
+                .and_then(|column| column.as_list_opt())
+                .with_context(|| format!("recording has no `{output}` column"))?;
+            for row in 0..batch.num_rows() {
+                messages.push(RecordedMessage {
+                    logical_time: timestamps.value(row),
+                    output: output.clone(),
+                    data: data.value(row),
+                });
+            }
+        }
+    }
+    Ok(messages)
+}
+
+/// Checks whether the file is `<output>.parquet` or a chunk `<output>.<n>.parquet`.
+fn is_recording_of(path: &Path, output: &DataId) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let Some(rest) = name
+        .strip_prefix(output.as_str())
+        .and_then(|rest| rest.strip_suffix(".parquet"))
+    else {
+        return false;
+    };
+    match rest.strip_prefix('.') {
+        Some(chunk) => !chunk.is_empty() && chunk.chars().all(|c| c.is_ascii_digit()),
+        None => rest.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_recording_files() {
+        let output = DataId::from("image".to_owned());
+        let matches = |name: &str| is_recording_of(Path::new(name), &output);
+        assert!(matches("image.parquet"));
+        assert!(matches("image.00003.parquet"));
+        assert!(!matches("image_raw.parquet"));
+        assert!(!matches("image..parquet"));
+        assert!(!matches("image.txt"));
+    }
+}
@@ -0,0 +1,344 @@
+//! Priority-based eviction of recorded data when the disk is nearly full.
+//!
+//! Eviction is enabled by setting at least one of the following environment
+//! variables of the record node:
+//!
+//! - `RECORD_MIN_FREE_SPACE`, e.g. `2GB`: free disk space to keep available.
+//! - `RECORD_MAX_SIZE`, e.g. `10GB`: maximum total size of the recording.
+//! - `RECORD_MAX_AGE`, e.g. `1h`: maximum age of recorded chunks.
+//!
+//! The recorder then splits each stream into chunk files of
+//! `RECORD_CHUNK_DURATION` (default `60s`) and checks the limits periodically.
+//! Chunks that are older than the maximum age are deleted. While the free disk
+//! space or the recording size exceed their limits, closed chunks are deleted,
+//! starting with the oldest chunk of the lowest priority.
+//!
+//! Stream priorities are set through `RECORD_PRIORITIES`, a comma-separated list
+//! of `<input>=<priority>` pairs, e.g. `image=low,safety_log=critical`. The
+//! priorities are `low`, `normal` (default), `high`, and `critical`. Chunks of
+//! `critical` streams are never evicted.
+
+use dora_node_api::dora_core::config::{parse_byte_size, parse_duration, DataId};
+use eyre::{bail, eyre, Context};
+use std::{
+    collections::HashMap,
+    path::Path,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    /// Safety-critical streams, which are never evicted.
+    Critical,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Critical => "critical",
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            "critical" => Ok(Priority::Critical),
+            other => bail!(
+                "unknown priority `{other}` (expected `low`, `normal`, `high`, or `critical`)"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EvictionConfig {
+    /// Chunks are evicted when the free disk space drops below this size.
+    pub min_free_space: Option<u64>,
+    /// Chunks are evicted when the closed chunks of all streams exceed this size.
+    pub max_size: Option<u64>,
+    /// Chunks are evicted when they were closed longer ago than this.
+    pub max_age: Option<Duration>,
+    pub chunk_duration: Duration,
+    priorities: HashMap<DataId, Priority>,
+}
+
+impl EvictionConfig {
+    /// Reads the eviction config from the environment.
+    ///
+    /// Returns `None` if none of the limits is set.
+    pub fn from_env() -> eyre::Result<Option<Self>> {
+        let byte_size = |var: &str| -> eyre::Result<Option<u64>> {
+            std::env::var(var)
+                .ok()
+                .map(|size| parse_byte_size(&size).map_err(|err| eyre!(err)))
+                .transpose()
+                .with_context(|| format!("invalid `{var}`"))
+        };
+        let duration = |var: &str| -> eyre::Result<Option<Duration>> {
+            std::env::var(var)
+                .ok()
+                .map(|duration| parse_duration(&duration).map_err(|err| eyre!(err)))
+                .transpose()
+                .with_context(|| format!("invalid `{var}`"))
+        };
+        let min_free_space = byte_size("RECORD_MIN_FREE_SPACE")?;
+        let max_size = byte_size("RECORD_MAX_SIZE")?;
+        let max_age = duration("RECORD_MAX_AGE")?;
+        if min_free_space.is_none() && max_size.is_none() && max_age.is_none() {
+            return Ok(None);
+        }
+        let chunk_duration = duration("RECORD_CHUNK_DURATION")?.unwrap_or(Duration::from_secs(60));
+        let priorities = match std::env::var("RECORD_PRIORITIES") {
+            Ok(priorities) => {
+                parse_priorities(&priorities).context("invalid `RECORD_PRIORITIES`")?
+            }
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(Some(Self {
+            min_free_space,
+            max_size,
+            max_age,
+            chunk_duration,
+            priorities,
+        }))
+    }
+
+    pub fn priority(&self, input: &DataId) -> Priority {
+        self.priorities.get(input).copied().unwrap_or_default()
+    }
+}
+
+fn parse_priorities(s: &str) -> eyre::Result<HashMap<DataId, Priority>> {
+    s.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (input, priority) = entry
+                .split_once('=')
+                .ok_or_else(|| eyre!("expected `<input>=<priority>`, got `{entry}`"))?;
+            Ok((DataId::from(input.trim().to_owned()), priority.parse()?))
+        })
+        .collect()
+}
+
+/// A closed chunk file of a recorded stream.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub input: DataId,
+    pub priority: Priority,
+    pub path: PathBuf,
+    pub size: u64,
+    pub closed: Instant,
+}
+
+/// Keeps track of the closed chunks that can be evicted.
+#[derive(Debug, Default)]
+pub struct ChunkIndex {
+    /// Chunks in the order they were closed.
+    chunks: Vec<Chunk>,
+    /// Total size of the chunks of critical streams, which are never evicted.
+    critical_size: u64,
+    /// Whether a limit is exceeded without any chunks left to evict, to only
+    /// warn once.
+    exhausted: bool,
+}
+
+impl ChunkIndex {
+    pub fn add(&mut self, chunk: Chunk) {
+        if chunk.priority == Priority::Critical {
+            self.critical_size += chunk.size;
+        } else {
+            self.chunks.push(chunk);
+        }
+    }
+
+    /// Total size of the closed chunks.
+    fn size(&self) -> u64 {
+        self.critical_size + self.chunks.iter().map(|chunk| chunk.size).sum::<u64>()
+    }
+
+    /// Removes and returns the oldest chunk of the lowest priority.
+    fn next_eviction(&mut self) -> Option<Chunk> {
+        let lowest = self.chunks.iter().map(|chunk| chunk.priority).min()?;
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.priority == lowest)?;
+        Some(self.chunks.remove(index))
+    }
+
+    /// Deletes the chunks that are older than the maximum age, and further
+    /// chunks until the recording is within the size and free disk space
+    /// limits.
+    ///
+    /// Returns the evicted chunks.
+    pub fn evict(&mut self, dir: &Path, config: &EvictionConfig) -> eyre::Result<Vec<Chunk>> {
+        let mut evicted = Vec::new();
+        if let Some(max_age) = config.max_age {
+            let (expired, kept) = std::mem::take(&mut self.chunks)
+                .into_iter()
+                .partition(|chunk| chunk.closed.elapsed() >= max_age);
+            self.chunks = kept;
+            for chunk in expired {
+                remove_chunk(&chunk)?;
+                evicted.push(chunk);
+            }
+        }
+        loop {
+            let Some(exceeded) = self.exceeded_limit(dir, config)? else {
+                self.exhausted = false;
+                break;
+            };
+            let Some(chunk) = self.next_eviction() else {
+                if !self.exhausted {
+                    tracing::warn!("{exceeded}, but there are no chunks left that can be evicted");
+                    self.exhausted = true;
+                }
+                break;
+            };
+            remove_chunk(&chunk)?;
+            evicted.push(chunk);
+        }
+        Ok(evicted)
+    }
+
+    /// Returns a description of the size or free disk space limit that is
+    /// exceeded, if any.
+    fn exceeded_limit(&self, dir: &Path, config: &EvictionConfig) -> eyre::Result<Option<String>> {
+        if let Some(max_size) = config.max_size {
+            let size = self.size();
+            if size > max_size {
+                return Ok(Some(format!(
+                    "recording size of {size} bytes exceeds the limit of {max_size} bytes"
+                )));
+            }
+        }
+        if let Some(min_free_space) = config.min_free_space {
+            let available = fs2::available_space(dir).with_context(|| {
+                format!("failed to query free disk space of `{}`", dir.display())
+            })?;
+            if available < min_free_space {
+                return Ok(Some(format!(
+                    "free disk space is below the limit ({available} bytes available)"
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn remove_chunk(chunk: &Chunk) -> eyre::Result<()> {
+    match std::fs::remove_file(&chunk.path) {
+        Ok(()) => Ok(()),
+        // deleted by the user already
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("failed to evict `{}`", chunk.path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_lowest_priority_first() {
+        let priorities = parse_priorities("image=low, log=critical,lidar=high").unwrap();
+        let mut index = ChunkIndex::default();
+        for (i, input) in ["lidar", "image", "log", "pose", "image"]
+            .iter()
+            .enumerate()
+        {
+            let input = DataId::from(input.to_string());
+            index.add(Chunk {
+                priority: priorities.get(&input).copied().unwrap_or_default(),
+                path: format!("{input}.{i}.parquet").into(),
+                input,
+                size: 0,
+                closed: Instant::now(),
+            });
+        }
+
+        let order: Vec<_> = std::iter::from_fn(|| index.next_eviction())
+            .map(|chunk| chunk.path)
+            .collect();
+        let expected = ["image.1", "image.4", "pose.3", "lidar.0"];
+        let expected: Vec<PathBuf> = expected
+            .iter()
+            .map(|name| format!("{name}.parquet").into())
+            .collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn evict_by_size_and_age() {
+        let dir = std::env::temp_dir().join(format!("dora-record-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let priorities = parse_priorities("image=low,log=critical").unwrap();
+        let mut index = ChunkIndex::default();
+        let mut add = |index: &mut ChunkIndex, input: &str, i: usize, closed: Instant| {
+            let input = DataId::from(input.to_owned());
+            let path = dir.join(format!("{input}.{i}.parquet"));
+            std::fs::write(&path, [0; 100]).unwrap();
+            index.add(Chunk {
+                priority: priorities.get(&input).copied().unwrap_or_default(),
+                path,
+                input,
+                size: 100,
+                closed,
+            });
+        };
+        let old = Instant::now() - Duration::from_secs(3600);
+        add(&mut index, "image", 0, old);
+        add(&mut index, "log", 1, old);
+        add(&mut index, "pose", 2, Instant::now());
+        add(&mut index, "image", 3, Instant::now());
+        add(&mut index, "pose", 4, Instant::now());
+        let names = |chunks: Vec<Chunk>| -> Vec<String> {
+            chunks
+                .iter()
+                .map(|chunk| chunk.path.file_name().unwrap().to_string_lossy().into())
+                .collect()
+        };
+
+        let config = EvictionConfig {
+            max_age: Some(Duration::from_secs(60)),
+            max_size: Some(300),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(index.evict(&dir, &config).unwrap()),
+            ["image.0.parquet", "image.3.parquet"]
+        );
+        assert!(!dir.join("image.0.parquet").exists());
+        assert!(dir.join("log.1.parquet").exists());
+
+        // critical chunks count towards the size, but are never evicted
+        let config = EvictionConfig {
+            max_size: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(index.evict(&dir, &config).unwrap()),
+            ["pose.2.parquet", "pose.4.parquet"]
+        );
+        assert!(index.exhausted);
+        assert!(dir.join("log.1.parquet").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,21 +9,77 @@ use dora_node_api::{
         datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     },
-    DoraNode, Event, Metadata,
+    dora_core::config::DataId,
+    DoraNode, Event, Metadata, MetadataParameters, MetadataValue,
 };
 use dora_tracing::telemetry::deserialize_to_hashmap;
+use eviction::{Chunk, ChunkIndex, EvictionConfig, Priority};
 use eyre::{Context, ContextCompat};
 use parquet::{arrow::AsyncArrowWriter, basic::BrotliLevel, file::properties::WriterProperties};
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc;
 
+mod eviction;
+
+/// Interval at which the latest messages per key of compacted inputs are written.
+///
+/// Only the latest message per key within an interval is recorded, so that a
+/// crash loses at most one interval of a compacted input.
+const COMPACTION_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of retained keys of a compacted input after which they are written
+/// before the end of the flush interval.
+const MAX_RETAINED_KEYS: usize = 4096;
+/// Interval at which the recording is checked against the eviction limits.
+const EVICTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
-    let (node, mut events) = DoraNode::init_from_env()?;
+    let (mut node, mut events) = DoraNode::init_from_env()?;
     let dataflow_id = node.dataflow_id();
     let mut writers = HashMap::new();
+    let dataflow_dir = PathBuf::from("out").join(dataflow_id.to_string());
+    if !dataflow_dir.exists() {
+        std::fs::create_dir_all(&dataflow_dir).context("could not create dataflow_dir")?;
+    }
+
+    let eviction = EvictionConfig::from_env()?;
+    let mut chunk_index = ChunkIndex::default();
+    let (closed_chunks_tx, closed_chunks) = std::sync::mpsc::channel();
+    let evicted_output = DataId::from("evicted".to_owned());
+
+    let mut eviction_check = tokio::time::interval(EVICTION_CHECK_INTERVAL);
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv_async() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            _ = eviction_check.tick(), if eviction.is_some() => {
+                let Some(config) = &eviction else { continue };
+                for chunk in closed_chunks.try_iter() {
+                    chunk_index.add(chunk);
+                }
+                for chunk in chunk_index.evict(&dataflow_dir, config)? {
+                    tracing::info!(
+                        "evicted {} chunk `{}` of input `{}`",
+                        chunk.priority.as_str(),
+                        chunk.path.display(),
+                        chunk.input
+                    );
+                    if node.node_config().outputs.contains(&evicted_output) {
+                        report_eviction(&mut node, evicted_output.clone(), &chunk)?;
+                    }
+                }
+                continue;
+            }
+        };
 
-    while let Some(event) = events.recv() {
         match event {
             Event::Input { id, data, metadata } => {
                 match writers.get(&id) {
@@ -50,40 +106,69 @@ async fn main() -> eyre::Result<()> {
                             field_utc_epoch,
                             field_data,
                         ]));
-                        let dataflow_dir = PathBuf::from("out").join(dataflow_id.to_string());
-                        if !dataflow_dir.exists() {
-                            std::fs::create_dir_all(&dataflow_dir)
-                                .context("could not create dataflow_dir")?;
-                        }
-                        let file =
-                            tokio::fs::File::create(dataflow_dir.join(format!("{id}.parquet")))
-                                .await
-                                .context("Couldn't create write file")?;
-                        let mut writer = AsyncArrowWriter::try_new(
-                            file,
-                            schema.clone(),
-                            0,
-                            Some(
-                                WriterProperties::builder()
-                                    .set_compression(parquet::basic::Compression::BROTLI(
-                                        BrotliLevel::default(),
-                                    ))
-                                    .build(),
-                            ),
+                        let priority = eviction
+                            .as_ref()
+                            .map(|config| config.priority(&id))
+                            .unwrap_or_default();
+                        let mut stream = StreamWriter::create(
+                            dataflow_dir.clone(),
+                            id.clone(),
+                            schema,
+                            eviction.as_ref().map(|config| config.chunk_duration),
                         )
-                        .context("Could not create parquet writer")?;
+                        .await?;
+                        let compact_by = node
+                            .node_config()
+                            .inputs
+                            .get(&id)
+                            .and_then(|input| input.compact_by.clone());
                         let (tx, mut rx) = mpsc::channel(10);
+                        let closed_chunks = closed_chunks_tx.clone();
 
                         // Per Input thread
                         let join_handle = tokio::spawn(async move {
-                            while let Some((data, metadata)) = rx.recv().await {
-                                if let Err(e) =
-                                    write_event(&mut writer, data, &metadata, schema.clone()).await
-                                {
-                                    println!("Error writing event data into parquet file: {:?}", e)
+                            // latest message per key of compacted inputs, written periodically
+                            let mut retained = BTreeMap::new();
+                            let mut flush = tokio::time::interval(COMPACTION_FLUSH_INTERVAL);
+                            loop {
+                                let (data, metadata) = tokio::select! {
+                                    message = rx.recv() => match message {
+                                        Some(message) => message,
+                                        None => break,
+                                    },
+                                    _ = flush.tick() => {
+                                        flush_retained(
+                                            &mut stream,
+                                            &mut retained,
+                                            &closed_chunks,
+                                            priority,
+                                        )
+                                        .await;
+                                        continue;
+                                    }
                                 };
+                                if let Some(key) = compact_by
+                                    .as_ref()
+                                    .and_then(|compact_by| compact_by.key_of(&metadata.parameters))
+                                {
+                                    retained.insert(key, (data, metadata));
+                                    if retained.len() >= MAX_RETAINED_KEYS {
+                                        flush_retained(
+                                            &mut stream,
+                                            &mut retained,
+                                            &closed_chunks,
+                                            priority,
+                                        )
+                                        .await;
+                                    }
+                                    continue;
+                                }
+                                record(&mut stream, data, &metadata, &closed_chunks, priority)
+                                    .await;
                             }
-                            writer.close().await
+                            flush_retained(&mut stream, &mut retained, &closed_chunks, priority)
+                                .await;
+                            stream.writer.close().await
                         });
                         tx.send((data.into(), metadata))
                             .await
@@ -118,6 +203,157 @@ async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
+/// Writes a recorded stream to a parquet file, which is split into chunks of
+/// `chunk_duration` if eviction is enabled.
+struct StreamWriter {
+    dataflow_dir: PathBuf,
+    id: DataId,
+    schema: Arc<Schema>,
+    chunk_duration: Option<Duration>,
+    chunk: usize,
+    chunk_start: Instant,
+    writer: AsyncArrowWriter<tokio::fs::File>,
+}
+
+impl StreamWriter {
+    async fn create(
+        dataflow_dir: PathBuf,
+        id: DataId,
+        schema: Arc<Schema>,
+        chunk_duration: Option<Duration>,
+    ) -> eyre::Result<Self> {
+        let path = chunk_path(&dataflow_dir, &id, chunk_duration.map(|_| 0));
+        let writer = create_writer(&path, schema.clone()).await?;
+        Ok(Self {
+            dataflow_dir,
+            id,
+            schema,
+            chunk_duration,
+            chunk: 0,
+            chunk_start: Instant::now(),
+            writer,
+        })
+    }
+
+    /// Starts a new chunk file if the current one is older than the chunk duration.
+    ///
+    /// Returns the path and the size of the closed chunk.
+    async fn rotate_if_needed(&mut self) -> eyre::Result<Option<(PathBuf, u64)>> {
+        let Some(chunk_duration) = self.chunk_duration else {
+            return Ok(None);
+        };
+        if self.chunk_start.elapsed() < chunk_duration {
+            return Ok(None);
+        }
+
+        let closed_path = chunk_path(&self.dataflow_dir, &self.id, Some(self.chunk));
+        self.chunk += 1;
+        self.chunk_start = Instant::now();
+        let path = chunk_path(&self.dataflow_dir, &self.id, Some(self.chunk));
+        let writer = create_writer(&path, self.schema.clone()).await?;
+        std::mem::replace(&mut self.writer, writer)
+            .close()
+            .await
+            .context("Could not close parquet chunk")?;
+
+        let size = tokio::fs::metadata(&closed_path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or_default();
+        Ok(Some((closed_path, size)))
+    }
+}
+
+/// Writes a message to the stream, starting a new chunk first if needed.
+async fn record(
+    stream: &mut StreamWriter,
+    data: Arc<dyn Array>,
+    metadata: &Metadata,
+    closed_chunks: &std::sync::mpsc::Sender<Chunk>,
+    priority: Priority,
+) {
+    match stream.rotate_if_needed().await {
+        Ok(Some((path, size))) => {
+            let _ = closed_chunks.send(Chunk {
+                input: stream.id.clone(),
+                priority,
+                path,
+                size,
+                closed: Instant::now(),
+            });
+        }
+        Ok(None) => {}
+        Err(err) => tracing::warn!("failed to start new chunk: {err:?}"),
+    }
+    if let Err(e) = write_event(&mut stream.writer, data, metadata, stream.schema.clone()).await {
+        println!("Error writing event data into parquet file: {:?}", e)
+    };
+}
+
+/// Writes the retained messages of a compacted input in the order they were
+/// sent.
+async fn flush_retained(
+    stream: &mut StreamWriter,
+    retained: &mut BTreeMap<String, (Arc<dyn Array>, Metadata)>,
+    closed_chunks: &std::sync::mpsc::Sender<Chunk>,
+    priority: Priority,
+) {
+    let mut messages: Vec<_> = std::mem::take(retained).into_values().collect();
+    messages.sort_by_key(|(_, metadata)| metadata.timestamp());
+    for (data, metadata) in messages {
+        record(stream, data, &metadata, closed_chunks, priority).await;
+    }
+}
+
+fn chunk_path(dataflow_dir: &std::path::Path, id: &DataId, chunk: Option<usize>) -> PathBuf {
+    match chunk {
+        Some(chunk) => dataflow_dir.join(format!("{id}.{chunk:05}.parquet")),
+        None => dataflow_dir.join(format!("{id}.parquet")),
+    }
+}
+
+async fn create_writer(
+    path: &std::path::Path,
+    schema: Arc<Schema>,
+) -> eyre::Result<AsyncArrowWriter<tokio::fs::File>> {
+    let file = tokio::fs::File::create(path)
+        .await
+        .context("Couldn't create write file")?;
+    AsyncArrowWriter::try_new(
+        file,
+        schema,
+        0,
+        Some(
+            WriterProperties::builder()
+                .set_compression(parquet::basic::Compression::BROTLI(BrotliLevel::default()))
+                .build(),
+        ),
+    )
+    .context("Could not create parquet writer")
+}
+
+/// Sends an event about an evicted chunk on the `evicted` output.
+///
+/// The data is the path of the deleted file; the input ID, priority, and size
+/// of the chunk are set as metadata values.
+fn report_eviction(node: &mut DoraNode, output: DataId, chunk: &Chunk) -> eyre::Result<()> {
+    let mut parameters = MetadataParameters::default();
+    parameters.values.insert(
+        "input".into(),
+        MetadataValue::String(chunk.input.to_string()),
+    );
+    parameters.values.insert(
+        "priority".into(),
+        MetadataValue::String(chunk.priority.as_str().into()),
+    );
+    parameters
+        .values
+        .insert("bytes".into(), MetadataValue::Integer(chunk.size as i64));
+    let path = StringArray::from(vec![chunk.path.display().to_string()]);
+    node.send_output(output, parameters, path)
+        .context("failed to report evicted chunk")
+}
+
 /// Write a row of data into the writer
 async fn write_event(
     writer: &mut AsyncArrowWriter<tokio::fs::File>,
@@ -6,14 +6,16 @@
 //! - CPU usage.
 //! - Memory and Virtual memory usage.
 //! - disk usage (read and write).
+//! - CPU throttling applied by the cgroup of the process (Linux only).
 //!
 //! [`sysinfo`]: https://github.com/GuillaumeGomez/sysinfo
 //! [`opentelemetry-rust`]: https://github.com/open-telemetry/opentelemetry-rust
 
 use std::time::Duration;
 
+use dora_core::cgroup::CpuThrottling;
 use eyre::{Context, Result};
-use opentelemetry::metrics::{self, MeterProvider as _};
+use opentelemetry::metrics::{self, Meter, MeterProvider as _, Unit};
 use opentelemetry_otlp::{ExportConfig, WithExportConfig};
 use opentelemetry_sdk::{metrics::SdkMeterProvider, runtime};
 use opentelemetry_system_metrics::init_process_observer;
@@ -44,6 +46,38 @@ pub fn init_metrics() -> metrics::Result<SdkMeterProvider> {
 pub fn init_meter_provider(meter_id: String) -> Result<SdkMeterProvider> {
     let meter_provider = init_metrics().context("Could not create opentelemetry meter")?;
     let meter = meter_provider.meter(meter_id);
+    init_cgroup_observer(&meter);
     let _ = init_process_observer(meter).context("could not initiale system metrics observer")?;
     Ok(meter_provider)
 }
+
+/// Observe the CPU throttling of the cgroup that the current process belongs to.
+///
+/// Throttling happens when a container or slice exhausts its CPU quota. It is not
+/// visible in the CPU usage metrics, but it often causes latency spikes.
+pub fn init_cgroup_observer(meter: &Meter) {
+    if CpuThrottling::read_for_current_process().is_none() {
+        // not running in a cgroup with CPU accounting
+        return;
+    }
+
+    meter
+        .u64_observable_counter("process.cpu.throttled_periods")
+        .with_description("Number of CPU periods in which the cgroup of the process was throttled")
+        .with_callback(|observer| {
+            if let Some(throttling) = CpuThrottling::read_for_current_process() {
+                observer.observe(throttling.nr_throttled, &[]);
+            }
+        })
+        .init();
+    meter
+        .f64_observable_counter("process.cpu.throttled_time")
+        .with_description("Total time that the cgroup of the process was throttled")
+        .with_unit(Unit::new("s"))
+        .with_callback(|observer| {
+            if let Some(throttling) = CpuThrottling::read_for_current_process() {
+                observer.observe(throttling.throttled_time.as_secs_f64(), &[]);
+            }
+        })
+        .init();
+}
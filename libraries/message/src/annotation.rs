@@ -0,0 +1,198 @@
+//! Lightweight annotations that refer to other messages.
+//!
+//! Operators can attach annotations such as bounding boxes, labels, or markers
+//! to a message they received by sending them on a separate output. The
+//! [`annotates`](crate::MetadataParameters::annotates) metadata field of the
+//! annotation message is set to the timestamp of the annotated message, which
+//! uniquely identifies it. Visualization sinks use [`AnnotationMerger`] to join
+//! the annotations with the original stream again, e.g. to draw debugging
+//! overlays.
+//!
+//! Annotations are sent as JSON-encoded byte arrays, so they can be created and
+//! read from all languages.
+
+use crate::{uhlc, Metadata};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// An annotation of a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    /// An axis-aligned rectangle, e.g. a detected object in an image.
+    BoundingBox {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        score: Option<f32>,
+    },
+    /// A text label for the whole message.
+    Label { text: String },
+    /// A single point, e.g. a keypoint in an image.
+    Marker {
+        x: f32,
+        y: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+}
+
+impl Annotation {
+    /// Encodes the given annotations as data of an annotation message.
+    pub fn encode(annotations: &[Annotation]) -> Vec<u8> {
+        serde_json::to_vec(annotations).expect("failed to serialize annotations")
+    }
+
+    /// Decodes the data of an annotation message.
+    pub fn decode(data: &[u8]) -> eyre::Result<Vec<Annotation>> {
+        serde_json::from_slice(data).context("failed to deserialize annotations")
+    }
+}
+
+/// A message together with the annotations that were sent for it.
+#[derive(Debug)]
+pub struct AnnotatedMessage<T> {
+    pub metadata: Metadata,
+    pub data: T,
+    pub annotations: Vec<Annotation>,
+}
+
+/// Joins a stream of messages with the annotations that refer to them.
+///
+/// Messages are held back until annotations for them arrive. To avoid waiting
+/// forever for messages that are never annotated, at most `capacity` messages
+/// are held back; older messages are released without annotations when this
+/// limit is exceeded. Annotations might arrive before the message they refer
+/// to, so up to `capacity` annotation messages are buffered too.
+pub struct AnnotationMerger<T> {
+    capacity: usize,
+    pending: VecDeque<(Metadata, T)>,
+    annotations: HashMap<uhlc::Timestamp, Vec<Annotation>>,
+    annotation_order: VecDeque<uhlc::Timestamp>,
+}
+
+impl<T> AnnotationMerger<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            pending: VecDeque::new(),
+            annotations: HashMap::new(),
+            annotation_order: VecDeque::new(),
+        }
+    }
+
+    /// Adds a message of the annotated stream.
+    ///
+    /// Returns the messages that are ready to be displayed, in their original order.
+    pub fn push_message(&mut self, metadata: Metadata, data: T) -> Vec<AnnotatedMessage<T>> {
+        self.pending.push_back((metadata, data));
+        self.release()
+    }
+
+    /// Adds an annotation message, i.e. a message with the `annotates` metadata field set.
+    ///
+    /// Returns the messages that are ready to be displayed, in their original order.
+    pub fn push_annotations(
+        &mut self,
+        metadata: &Metadata,
+        data: &[u8],
+    ) -> eyre::Result<Vec<AnnotatedMessage<T>>> {
+        let target = metadata
+            .parameters
+            .annotates
+            .ok_or_else(|| eyre::eyre!("message has no `annotates` metadata field"))?;
+        let annotations = Annotation::decode(data)?;
+        if !self.annotations.contains_key(&target) {
+            self.annotation_order.push_back(target);
+        }
+        self.annotations
+            .entry(target)
+            .or_default()
+            .extend(annotations);
+        while self.annotation_order.len() > self.capacity {
+            if let Some(oldest) = self.annotation_order.pop_front() {
+                self.annotations.remove(&oldest);
+            }
+        }
+        Ok(self.release())
+    }
+
+    /// Releases all held back messages, e.g. when the annotated stream was closed.
+    pub fn flush(&mut self) -> Vec<AnnotatedMessage<T>> {
+        let pending = std::mem::take(&mut self.pending);
+        pending
+            .into_iter()
+            .map(|(metadata, data)| self.annotate(metadata, data))
+            .collect()
+    }
+
+    fn release(&mut self) -> Vec<AnnotatedMessage<T>> {
+        // annotations are typically sent in order, so messages before an
+        // annotated message are not expected to be annotated anymore
+        let last_annotated = self
+            .pending
+            .iter()
+            .rposition(|(metadata, _)| self.annotations.contains_key(&metadata.timestamp()))
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let overflow = self.pending.len().saturating_sub(self.capacity);
+        let count = last_annotated.max(overflow);
+
+        let released: Vec<_> = self.pending.drain(..count).collect();
+        released
+            .into_iter()
+            .map(|(metadata, data)| self.annotate(metadata, data))
+            .collect()
+    }
+
+    fn annotate(&mut self, metadata: Metadata, data: T) -> AnnotatedMessage<T> {
+        let annotations = self
+            .annotations
+            .remove(&metadata.timestamp())
+            .unwrap_or_default();
+        AnnotatedMessage {
+            metadata,
+            data,
+            annotations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArrowTypeInfo, MetadataParameters};
+
+    #[test]
+    fn merge_annotations() {
+        let clock = uhlc::HLC::default();
+        let mut merger = AnnotationMerger::new(2);
+
+        let first = Metadata::new(clock.new_timestamp(), ArrowTypeInfo::empty());
+        let second = Metadata::new(clock.new_timestamp(), ArrowTypeInfo::empty());
+        assert!(merger.push_message(first, 1).is_empty());
+        assert!(merger.push_message(second.clone(), 2).is_empty());
+
+        let label = Annotation::Label { text: "cat".into() };
+        let annotation_metadata = Metadata::from_parameters(
+            clock.new_timestamp(),
+            ArrowTypeInfo::empty(),
+            MetadataParameters {
+                annotates: Some(second.timestamp()),
+                ..Default::default()
+            },
+        );
+        let ready = merger
+            .push_annotations(&annotation_metadata, &Annotation::encode(&[label.clone()]))
+            .unwrap();
+
+        // the first message is released without annotations to keep the order
+        let ready: Vec<_> = ready.into_iter().map(|m| (m.data, m.annotations)).collect();
+        assert_eq!(ready, vec![(1, vec![]), (2, vec![label])]);
+    }
+}
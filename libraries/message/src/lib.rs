@@ -5,8 +5,12 @@ use arrow_data::ArrayData;
 use arrow_schema::DataType;
 use eyre::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 pub use uhlc;
 
+pub mod annotation;
+pub mod lineage;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Metadata {
     metadata_version: u16,
@@ -108,6 +112,117 @@ pub struct MetadataParameters {
     pub watermark: u64,
     pub deadline: u64,
     pub open_telemetry_context: String,
+    /// Logical position of the message in a keyed stream.
+    ///
+    /// Used by receivers that enable `order_by_key` to restore the order of
+    /// messages that were produced in parallel.
+    pub sequence: Option<SequenceNumber>,
+    /// Device that the data was located on before it was sent.
+    ///
+    /// Set when a tensor that was not located in host memory was copied to
+    /// the host for sending.
+    pub device: Option<Device>,
+    /// Timestamp of the message that this message annotates.
+    ///
+    /// See the [`annotation`] module for details.
+    pub annotates: Option<uhlc::Timestamp>,
+    /// CRC32C checksum of the message data.
+    ///
+    /// Set by senders that enable the `checksum` feature of the node API and
+    /// verified by receivers with the same feature.
+    pub checksum: Option<u32>,
+    /// Serialization format of the data, if it was converted from Arrow.
+    ///
+    /// Set by senders on the serialized copies of their outputs, which are
+    /// delivered to the inputs that specify a `format` in the dataflow. The data
+    /// is then a `UInt8` array that contains the encoded values.
+    pub format: Option<DataFormat>,
+    /// Logical timestamp of the message in the 64-bit NTP format of the message
+    /// timestamps, e.g. the time at which it was recorded.
+    ///
+    /// Operators of dataflows with `deterministic` scheduling receive their inputs
+    /// in the order of their logical time instead of their arrival order. Their
+    /// outputs inherit the logical time of the input that they were produced for.
+    pub logical_time: Option<u64>,
+    /// The output that sent the message and the upstream messages that it was
+    /// derived from.
+    ///
+    /// See the [`lineage`] module for details.
+    pub lineage: Vec<lineage::LineageEntry>,
+    /// User-defined metadata values, e.g. frame IDs or calibration data.
+    pub values: BTreeMap<String, MetadataValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct SequenceNumber {
+    /// Identifies the logical stream that the message belongs to.
+    pub key: String,
+    /// Position of the message in the stream, starting at `0`.
+    pub number: u64,
+}
+
+/// A typed user-defined metadata value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum MetadataValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<MetadataValue>),
+}
+
+impl PartialEq for MetadataValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            // compare the bit patterns to make this an equivalence relation, so that
+            // metadata can be `Eq`
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MetadataValue {}
+
+/// A device as identified by the DLPack protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Device {
+    /// The `DLDeviceType` code, e.g. `1` for CPU or `2` for CUDA.
+    pub device_type: i32,
+    pub device_id: i32,
+}
+
+/// Wire format of message data for consumers that don't use Arrow.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    /// The Arrow array itself, i.e. no conversion.
+    Arrow,
+    /// A UTF-8 encoded JSON array of the values.
+    Json,
+    /// A CBOR array of the values.
+    Cbor,
+    /// A `google.protobuf.ListValue` message of the values.
+    Protobuf,
+}
+
+impl std::fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataFormat::Arrow => f.write_str("arrow"),
+            DataFormat::Json => f.write_str("json"),
+            DataFormat::Cbor => f.write_str("cbor"),
+            DataFormat::Protobuf => f.write_str("protobuf"),
+        }
+    }
 }
 
 impl MetadataParameters {
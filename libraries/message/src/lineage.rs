@@ -0,0 +1,83 @@
+//! Provenance of messages.
+//!
+//! Messages of dataflows that enable lineage tracking carry a
+//! [`lineage`](crate::MetadataParameters::lineage) chain. The first entry
+//! identifies the output that sent the message, the following entries the
+//! upstream messages that it was derived from, with the most recent first. The
+//! chain of an output is the chain of the input that the node or operator
+//! processed last when it sent the output, so it follows one path through the
+//! dataflow. It is truncated after [`MAX_LINEAGE_DEPTH`] hops.
+//!
+//! The lineage is maintained by the node API and the runtime, nodes don't need
+//! to forward it. It is only tracked if the dataflow sets `lineage: true` or
+//! defines `latency_budgets`, as it adds to the size of every message.
+
+use crate::{uhlc, Metadata};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Maximum number of entries in the lineage of a message.
+pub const MAX_LINEAGE_DEPTH: usize = 16;
+
+/// A message in the lineage of another message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LineageEntry {
+    /// The node that sent the message.
+    pub node: String,
+    /// The output that the message was sent on, `<operator>/<output>` for
+    /// operators.
+    pub output: String,
+    /// The timestamp of the message.
+    pub timestamp: uhlc::Timestamp,
+}
+
+impl fmt::Display for LineageEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} @ {}", self.node, self.output, self.timestamp)
+    }
+}
+
+/// Creates the lineage of a message sent on the given output at `timestamp`,
+/// derived from a message with the given `upstream` lineage.
+pub fn extend_lineage(
+    node: &str,
+    output: &str,
+    timestamp: uhlc::Timestamp,
+    upstream: &[LineageEntry],
+) -> Vec<LineageEntry> {
+    let mut lineage = Vec::with_capacity((upstream.len() + 1).min(MAX_LINEAGE_DEPTH));
+    lineage.push(LineageEntry {
+        node: node.to_owned(),
+        output: output.to_owned(),
+        timestamp,
+    });
+    lineage.extend(upstream.iter().take(MAX_LINEAGE_DEPTH - 1).cloned());
+    lineage
+}
+
+impl Metadata {
+    /// The output that sent this message and the upstream messages that it
+    /// was derived from.
+    pub fn lineage(&self) -> &[LineageEntry] {
+        &self.parameters.lineage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_lineage() {
+        let clock = uhlc::HLC::default();
+        let mut lineage = Vec::new();
+        for i in 0..MAX_LINEAGE_DEPTH + 4 {
+            lineage = extend_lineage(&format!("node-{i}"), "out", clock.new_timestamp(), &lineage);
+        }
+        assert_eq!(lineage.len(), MAX_LINEAGE_DEPTH);
+        assert_eq!(lineage[0].node, format!("node-{}", MAX_LINEAGE_DEPTH + 3));
+        assert!(lineage
+            .windows(2)
+            .all(|pair| pair[0].timestamp > pair[1].timestamp));
+    }
+}